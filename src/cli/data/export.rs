@@ -54,11 +54,13 @@ impl Context for Export {
             query_type: "".to_owned(),
             start_time: c.start_time,
             end_time: c.end_time,
-            sort_by: Some(format!("{} ASC", cfg.common.column_timestamp)),
+            sort_by: Some(format!("{} ASC", cfg.common.column_timestamp).into()),
             track_total_hits: false,
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            wal_only: false,
+            fields: vec![],
         };
 
         let req = search::Request {
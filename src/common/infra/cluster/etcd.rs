@@ -152,6 +152,7 @@ async fn register() -> Result<()> {
         status: NodeStatus::Prepare,
         scheduled: true,
         broadcasted: false,
+        load: Default::default(),
     };
     let val = json::to_string(&node).unwrap();
 
@@ -232,6 +233,7 @@ pub(crate) async fn set_status(status: NodeStatus, new_lease_id: bool) -> Result
             status: status.clone(),
             scheduled: true,
             broadcasted: false,
+            load: Default::default(),
         },
     };
     let val = json::to_string(&node).unwrap();
@@ -0,0 +1,222 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::{
+    get_config,
+    meta::cluster::{Node, NodeEviction, NodeStatus, Role, RoleGroup},
+    utils::{json, time::now_micros},
+    RwAHashMap,
+};
+use infra::{
+    db::{get_coordinator, Event, NEED_WATCH},
+    errors::{Error, Result},
+};
+use once_cell::sync::Lazy;
+
+const EVICTED_NODES_KEY: &str = "/evicted_nodes/";
+
+/// Nodes excluded from scheduling, they finish the in-flight work but get no new requests
+static EVICTED_NODES: Lazy<RwAHashMap<String, NodeEviction>> = Lazy::new(Default::default);
+
+/// Evict a node cluster-wide, the eviction is stored in the coordinator so that all the
+/// nodes stop routing to it
+pub async fn evict_node(uuid: &str) -> Result<NodeEviction> {
+    if get_config().common.local_mode {
+        return Err(Error::Message(
+            "node eviction is not supported in local mode".to_string(),
+        ));
+    }
+    let Some(node) = super::get_node_by_uuid(uuid).await else {
+        return Err(Error::Message(format!("node {uuid} not found")));
+    };
+    let eviction = NodeEviction {
+        uuid: node.uuid,
+        name: node.name,
+        evicted_at: now_micros(),
+    };
+    let client = get_coordinator().await;
+    client
+        .put(
+            &format!("{EVICTED_NODES_KEY}{uuid}"),
+            json::to_vec(&eviction)?.into(),
+            NEED_WATCH,
+            None,
+        )
+        .await?;
+    Ok(eviction)
+}
+
+/// Bring an evicted node back to scheduling
+pub async fn unevict_node(uuid: &str) -> Result<()> {
+    if !EVICTED_NODES.read().await.contains_key(uuid) {
+        return Err(Error::Message(format!("node {uuid} is not evicted")));
+    }
+    let client = get_coordinator().await;
+    client
+        .delete(
+            &format!("{EVICTED_NODES_KEY}{uuid}"),
+            false,
+            NEED_WATCH,
+            None,
+        )
+        .await
+}
+
+pub async fn list_evicted_nodes() -> Vec<NodeEviction> {
+    EVICTED_NODES.read().await.values().cloned().collect()
+}
+
+#[inline]
+pub async fn is_evicted(uuid: &str) -> bool {
+    EVICTED_NODES.read().await.contains_key(uuid)
+}
+
+pub(crate) async fn filter_evicted_nodes(nodes: Option<Vec<Node>>) -> Option<Vec<Node>> {
+    filter_evicted_nodes_by(nodes, |_| true).await
+}
+
+/// Removes the evicted nodes matching the predicate, the others are kept even if evicted
+pub(crate) async fn filter_evicted_nodes_by(
+    nodes: Option<Vec<Node>>,
+    predicate: impl Fn(&Node) -> bool,
+) -> Option<Vec<Node>> {
+    let mut nodes = nodes?;
+    let r = EVICTED_NODES.read().await;
+    if !r.is_empty() {
+        nodes.retain(|n| !(r.contains_key(&n.uuid) && predicate(n)));
+    }
+    Some(nodes)
+}
+
+pub(crate) async fn cache_evicted_nodes() -> Result<()> {
+    let client = get_coordinator().await;
+    let items = client.list_values(EVICTED_NODES_KEY).await?;
+    let mut w = EVICTED_NODES.write().await;
+    for item in items {
+        let eviction: NodeEviction = json::from_slice(&item)?;
+        w.insert(eviction.uuid.clone(), eviction);
+    }
+    Ok(())
+}
+
+pub(crate) async fn watch_evicted_nodes() -> Result<()> {
+    let client = get_coordinator().await;
+    let mut events = client.watch(EVICTED_NODES_KEY).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching evicted_nodes");
+
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_evicted_nodes: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(EVICTED_NODES_KEY).unwrap();
+                let item_value: NodeEviction = json::from_slice(&ev.value.unwrap()).unwrap();
+                log::info!("[CLUSTER] evict node {}", item_value.name);
+                EVICTED_NODES
+                    .write()
+                    .await
+                    .insert(item_key.to_string(), item_value);
+                if let Some(node) = super::get_node_by_uuid(item_key).await {
+                    update_consistent_hash(&node, false).await;
+                }
+            }
+            Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(EVICTED_NODES_KEY).unwrap();
+                log::info!("[CLUSTER] unevict node {}", item_key);
+                EVICTED_NODES.write().await.remove(item_key);
+                if let Some(node) = super::get_node_by_uuid(item_key).await {
+                    if node.status == NodeStatus::Online {
+                        update_consistent_hash(&node, true).await;
+                    }
+                }
+            }
+            Event::Empty => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn update_consistent_hash(node: &Node, add: bool) {
+    let mut roles = Vec::new();
+    if node.is_interactive_querier() {
+        roles.push((Role::Querier, Some(RoleGroup::Interactive)));
+    }
+    if node.is_background_querier() {
+        roles.push((Role::Querier, Some(RoleGroup::Background)));
+    }
+    if node.is_compactor() {
+        roles.push((Role::Compactor, None));
+    }
+    if node.is_flatten_compactor() {
+        roles.push((Role::FlattenCompactor, None));
+    }
+    for (role, group) in roles {
+        if add {
+            super::add_node_to_consistent_hash(node, &role, group).await;
+        } else {
+            super::remove_node_from_consistent_hash(node, &role, group).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_filter_evicted_nodes_by() {
+        let node = |uuid: &str, role: Role| Node {
+            uuid: uuid.to_string(),
+            role: vec![role],
+            ..Node::default()
+        };
+        for uuid in ["evicted_ingester", "evicted_querier"] {
+            EVICTED_NODES.write().await.insert(
+                uuid.to_string(),
+                NodeEviction {
+                    uuid: uuid.to_string(),
+                    name: uuid.to_string(),
+                    evicted_at: 0,
+                },
+            );
+        }
+        let nodes = vec![
+            node("evicted_ingester", Role::Ingester),
+            node("evicted_querier", Role::Querier),
+            node("ingester", Role::Ingester),
+        ];
+
+        // the ingest routing excludes all the evicted nodes
+        let routed = filter_evicted_nodes(Some(nodes.clone())).await.unwrap();
+        assert_eq!(routed.len(), 1);
+        assert_eq!(routed[0].uuid, "ingester");
+
+        // the search fan-out keeps the evicted ingesters
+        let searched = filter_evicted_nodes_by(Some(nodes), |node| !node.is_ingester())
+            .await
+            .unwrap();
+        let searched = searched.iter().map(|n| n.uuid.as_str()).collect::<Vec<_>>();
+        assert_eq!(searched, vec!["evicted_ingester", "ingester"]);
+    }
+}
@@ -19,10 +19,10 @@ use config::{
     cluster::*,
     get_config,
     meta::{
-        cluster::{Node, NodeStatus, Role, RoleGroup},
+        cluster::{Node, NodeLoad, NodeStatus, Role, RoleGroup},
         meta_store::MetaStore,
     },
-    utils::{hash::Sum64, json},
+    utils::{hash::Sum64, json, time::now_micros},
     RwAHashMap, RwBTreeMap,
 };
 use infra::{
@@ -30,13 +30,17 @@ use infra::{
     errors::Result,
 };
 use once_cell::sync::Lazy;
+use prometheus::core::Collector;
 use tokio::time;
 
 use crate::service::db as db_service;
 
 mod etcd;
+mod eviction;
 mod nats;
 
+pub use eviction::{evict_node, is_evicted, list_evicted_nodes, unevict_node};
+
 const HEALTH_CHECK_FAILED_TIMES: usize = 3;
 const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
 const CONSISTENT_HASH_PRIME: u32 = 16777619;
@@ -177,6 +181,10 @@ pub async fn register_and_keepalive() -> Result<()> {
         _ => etcd::register_and_keepalive().await?,
     };
 
+    // watch evicted nodes
+    eviction::cache_evicted_nodes().await?;
+    tokio::task::spawn(async move { eviction::watch_evicted_nodes().await });
+
     // check node heatbeat
     tokio::task::spawn(async move {
         let ttl_keep_alive = min(10, (cfg.limit.node_heartbeat_ttl / 2) as u64);
@@ -189,6 +197,21 @@ pub async fn register_and_keepalive() -> Result<()> {
         }
     });
 
+    // report node load, the router uses it to pick the least loaded querier
+    if LOCAL_NODE.is_querier() {
+        tokio::task::spawn(async move {
+            loop {
+                time::sleep(time::Duration::from_secs(
+                    get_config().route.load_report_interval,
+                ))
+                .await;
+                if let Err(e) = report_local_node_load().await {
+                    log::error!("[CLUSTER] report_local_node_load failed: {}", e);
+                }
+            }
+        });
+    }
+
     Ok(())
 }
 
@@ -287,6 +310,17 @@ async fn watch_node_list() -> Result<()> {
                 if exist {
                     continue;
                 }
+                // only the load changed, update it without rejoining the node
+                if let Some(v) = NODES.write().await.get_mut(item_key) {
+                    if v.status == item_value.status
+                        && v.scheduled == item_value.scheduled
+                        && v.load != item_value.load
+                        && item_value.status == NodeStatus::Online
+                    {
+                        v.load = item_value.load;
+                        continue;
+                    }
+                }
                 if item_value.status == NodeStatus::Offline {
                     log::info!("[CLUSTER] offline {:?}", item_value);
                     if item_value.is_interactive_querier() {
@@ -346,27 +380,31 @@ async fn watch_node_list() -> Result<()> {
                     }
                 }
                 item_value.broadcasted = true;
-                if item_value.is_interactive_querier() {
-                    add_node_to_consistent_hash(
-                        &item_value,
-                        &Role::Querier,
-                        Some(RoleGroup::Interactive),
-                    )
-                    .await;
-                }
-                if item_value.is_background_querier() {
-                    add_node_to_consistent_hash(
-                        &item_value,
-                        &Role::Querier,
-                        Some(RoleGroup::Background),
-                    )
-                    .await;
-                }
-                if item_value.is_compactor() {
-                    add_node_to_consistent_hash(&item_value, &Role::Compactor, None).await;
-                }
-                if item_value.is_flatten_compactor() {
-                    add_node_to_consistent_hash(&item_value, &Role::FlattenCompactor, None).await;
+                // evicted node stays out of the consistent hash until it's unevicted
+                if !is_evicted(item_key).await {
+                    if item_value.is_interactive_querier() {
+                        add_node_to_consistent_hash(
+                            &item_value,
+                            &Role::Querier,
+                            Some(RoleGroup::Interactive),
+                        )
+                        .await;
+                    }
+                    if item_value.is_background_querier() {
+                        add_node_to_consistent_hash(
+                            &item_value,
+                            &Role::Querier,
+                            Some(RoleGroup::Background),
+                        )
+                        .await;
+                    }
+                    if item_value.is_compactor() {
+                        add_node_to_consistent_hash(&item_value, &Role::Compactor, None).await;
+                    }
+                    if item_value.is_flatten_compactor() {
+                        add_node_to_consistent_hash(&item_value, &Role::FlattenCompactor, None)
+                            .await;
+                    }
                 }
                 NODES.write().await.insert(item_key.to_string(), item_value);
             }
@@ -411,6 +449,32 @@ async fn watch_node_list() -> Result<()> {
     Ok(())
 }
 
+async fn report_local_node_load() -> Result<()> {
+    let status = unsafe { LOCAL_NODE_STATUS.clone() };
+    if status != NodeStatus::Online {
+        return Ok(());
+    }
+    let Some(mut node) = get_node_by_uuid(LOCAL_NODE.uuid.as_str()).await else {
+        return Ok(());
+    };
+    node.load = NodeLoad {
+        running_queries: get_local_running_queries(),
+        updated_at: now_micros(),
+    };
+    update_local_node(&node).await
+}
+
+/// Number of queries running on the local node, summed over all organizations
+fn get_local_running_queries() -> u64 {
+    config::metrics::QUERY_RUNNING_NUMS
+        .collect()
+        .iter()
+        .flat_map(|mf| mf.get_metric())
+        .map(|m| m.get_gauge().get_value())
+        .sum::<f64>()
+        .max(0.0) as u64
+}
+
 async fn check_nodes_status(client: &reqwest::Client) -> Result<()> {
     let cfg = get_config();
     let nodes = get_cached_online_nodes().await.unwrap_or_default();
@@ -490,6 +554,7 @@ pub async fn get_cached_online_nodes() -> Option<Vec<Node>> {
     get_cached_nodes(|node| node.status == NodeStatus::Online && node.scheduled).await
 }
 
+/// The online ingesters, including the evicted ones as they still hold data in their WAL
 #[inline]
 pub async fn get_cached_online_ingester_nodes() -> Option<Vec<Node>> {
     get_cached_nodes(|node| {
@@ -498,12 +563,19 @@ pub async fn get_cached_online_ingester_nodes() -> Option<Vec<Node>> {
     .await
 }
 
+/// The online ingesters new ingest requests are routed to, the evicted ones are excluded
+#[inline]
+pub async fn get_cached_schedulable_ingester_nodes() -> Option<Vec<Node>> {
+    eviction::filter_evicted_nodes(get_cached_online_ingester_nodes().await).await
+}
+
 #[inline]
 pub async fn get_cached_online_querier_nodes(group: Option<RoleGroup>) -> Option<Vec<Node>> {
     let nodes = get_cached_nodes(|node| {
         node.status == NodeStatus::Online && node.scheduled && node.is_querier()
     })
     .await;
+    let nodes = eviction::filter_evicted_nodes(nodes).await;
     filter_nodes_with_group(nodes, group)
 }
 
@@ -515,6 +587,8 @@ pub async fn get_cached_online_query_nodes(group: Option<RoleGroup>) -> Option<V
             && (node.is_querier() || node.is_ingester())
     })
     .await;
+    // the evicted ingesters are still searched for the data in their WAL
+    let nodes = eviction::filter_evicted_nodes_by(nodes, |node| !node.is_ingester()).await;
     filter_nodes_with_group(nodes, group)
 }
 
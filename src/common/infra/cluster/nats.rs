@@ -144,6 +144,7 @@ async fn register() -> Result<()> {
         status: NodeStatus::Prepare,
         scheduled: true,
         broadcasted: false,
+        load: Default::default(),
     };
     let val = json::to_vec(&node).unwrap();
 
@@ -211,6 +212,7 @@ pub(crate) async fn set_status(status: NodeStatus) -> Result<()> {
             status: status.clone(),
             scheduled: true,
             broadcasted: false,
+            load: Default::default(),
         },
     };
     let val = json::to_string(&node).unwrap();
@@ -18,7 +18,7 @@ use std::sync::Arc;
 use config::{RwAHashMap, RwHashMap};
 use dashmap::DashMap;
 use hashbrown::HashMap;
-use infra::table::short_urls::ShortUrlRecord;
+use infra::table::{api_keys::ApiKeyRecord, short_urls::ShortUrlRecord};
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use vector_enrichment::TableRegistry;
@@ -86,3 +86,5 @@ pub static GEOIP_ASN_TABLE: Lazy<Arc<RwLock<Option<Geoip>>>> =
 pub static USER_SESSIONS: Lazy<RwHashMap<String, String>> = Lazy::new(Default::default);
 pub static STREAM_PIPELINES: Lazy<RwHashMap<String, PipeLine>> = Lazy::new(DashMap::default);
 pub static SHORT_URLS: Lazy<RwHashMap<String, ShortUrlRecord>> = Lazy::new(DashMap::default);
+/// The API keys by their hash, with the time they were cached
+pub static API_KEYS: Lazy<RwHashMap<String, (ApiKeyRecord, i64)>> = Lazy::new(DashMap::default);
@@ -19,7 +19,7 @@ use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use super::{QueryCondition, TriggerCondition};
+use super::{DeduplicationConfig, QueryCondition, TriggerCondition};
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct Alert {
@@ -31,12 +31,20 @@ pub struct Alert {
     pub stream_type: StreamType,
     #[serde(default)]
     pub stream_name: String,
+    /// The organization the stream is queried from, defaults to `org_id`.
+    /// The alert and its notifications are still owned by `org_id`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_org_id: Option<String>,
     #[serde(default)]
     pub is_real_time: bool,
     #[serde(default)]
     pub query_condition: QueryCondition,
     #[serde(default)]
     pub trigger_condition: TriggerCondition,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deduplication: Option<DeduplicationConfig>,
     pub destinations: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context_attributes: Option<HashMap<String, String>>,
@@ -46,6 +54,10 @@ pub struct Alert {
     pub description: String,
     #[serde(default)]
     pub enabled: bool,
+    /// Seconds a scheduled evaluation can run before it's aborted, capped by
+    /// `ZO_ALERT_EVALUATION_TIMEOUT`. 0 uses `ZO_ALERT_EVALUATION_TIMEOUT`.
+    #[serde(default)]
+    pub evaluation_timeout_secs: i64,
     #[serde(default)]
     /// Timezone offset in minutes.
     /// The negative secs means the Western Hemisphere
@@ -78,14 +90,17 @@ impl Default for Alert {
             org_id: "".to_string(),
             stream_type: StreamType::default(),
             stream_name: "".to_string(),
+            source_org_id: None,
             is_real_time: false,
             query_condition: QueryCondition::default(),
             trigger_condition: TriggerCondition::default(),
+            deduplication: None,
             destinations: vec![],
             context_attributes: None,
             row_template: "".to_string(),
             description: "".to_string(),
             enabled: false,
+            evaluation_timeout_secs: 0,
             tz_offset: 0, // UTC
             last_triggered_at: None,
             owner: None,
@@ -101,3 +116,11 @@ pub struct AlertListFilter {
     pub enabled: Option<bool>,
     pub owner: Option<String>,
 }
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct AlertBackfillResponse {
+    /// Number of evaluations run over the requested time range
+    pub evaluated: usize,
+    /// End time of each evaluation window in which the alert would have fired
+    pub fired_at: Vec<i64>,
+}
@@ -44,6 +44,8 @@ pub struct DerivedStreamMeta {
     /// Timezone offset in minutes.
     /// The negative secs means the Western Hemisphere
     pub tz_offset: i32,
+    #[serde(default)]
+    pub timestamp_alignment: TimestampAlignment,
 }
 
 impl Default for DerivedStreamMeta {
@@ -59,6 +61,27 @@ impl Default for DerivedStreamMeta {
             description: "".to_string(),
             enabled: true,
             tz_offset: 0, // UTC
+            timestamp_alignment: TimestampAlignment::default(),
         }
     }
 }
+
+/// Decides the `_timestamp` of the rows a DerivedStream ingests into its destination.
+///
+/// With `window_start`/`window_end` the derived rows line up with the source data they were
+/// computed from, so a downstream query over `[t1, t2)` on the destination stream returns the
+/// aggregates of the source windows starting (or ending) in that range, regardless of when
+/// the DerivedStream actually ran.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampAlignment {
+    /// Rows get the ingestion time, unless the query already selects a `_timestamp` column.
+    #[default]
+    Ingestion,
+    /// Rows get `zo_sql_min_time` if the query returns it, otherwise the start of the
+    /// evaluated period.
+    WindowStart,
+    /// Rows get `zo_sql_max_time` if the query returns it, otherwise the end of the
+    /// evaluated period.
+    WindowEnd,
+}
@@ -33,6 +33,8 @@ pub struct Destination {
     pub method: HTTPType,
     #[serde(default)]
     pub skip_tls_verify: bool,
+    /// A value can be a reference to a secret backend, `secret://<backend>/<name>[#<key>]`,
+    /// resolved when a notification is sent
     #[serde(skip_serializing_if = "Option::is_none")]
     pub headers: Option<HashMap<String, String>>,
     pub template: String,
@@ -47,8 +49,16 @@ pub struct Destination {
     #[serde(rename = "type")]
     #[serde(default)]
     pub destination_type: DestinationType,
+    /// Only for `Http` destination_type, the requests are signed with the secret in the
+    /// `X-O2-Signature` header. It can be a reference to a secret backend as the headers. It's
+    /// redacted in the responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_secret: Option<String>,
 }
 
+/// The signing secret in the responses, an update with it keeps the saved secret
+pub const REDACTED_SECRET: &str = "[REDACTED]";
+
 #[derive(Serialize, Debug, Default, PartialEq, Eq, Deserialize, Clone, ToSchema)]
 pub enum DestinationType {
     #[default]
@@ -61,6 +71,14 @@ pub enum DestinationType {
 }
 
 impl Destination {
+    /// Hides the signing secret, for the responses
+    pub fn redact(mut self) -> Self {
+        if self.signing_secret.is_some() {
+            self.signing_secret = Some(REDACTED_SECRET.to_string());
+        }
+        self
+    }
+
     pub fn with_template(&self, template: Template) -> DestinationWithTemplate {
         DestinationWithTemplate {
             name: self.name.clone(),
@@ -73,6 +91,7 @@ impl Destination {
             destination_type: self.destination_type.clone(),
             sns_topic_arn: self.sns_topic_arn.clone(),
             aws_region: self.aws_region.clone(),
+            signing_secret: self.signing_secret.clone(),
         }
     }
 }
@@ -93,6 +112,8 @@ pub struct DestinationWithTemplate {
     pub sns_topic_arn: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub aws_region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_secret: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
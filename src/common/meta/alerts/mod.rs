@@ -41,6 +41,29 @@ pub struct TriggerCondition {
     pub timezone: Option<String>,
     #[serde(default)]
     pub tolerance_in_secs: Option<i64>,
+    #[serde(default)]
+    pub tolerance_mode: ToleranceMode,
+}
+
+/// How the tolerance delays the next run of an alert
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum ToleranceMode {
+    /// A random delay within the tolerance for every run
+    #[serde(rename = "random")]
+    #[default]
+    Random,
+    /// A stable delay within the tolerance derived from the alert, the same for every run
+    #[serde(rename = "deterministic")]
+    Deterministic,
+}
+
+/// Suppresses the notifications of the rows already notified within a time window
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DeduplicationConfig {
+    /// The row fields which form the dedup key, e.g. `host` and `service`
+    pub fields: Vec<String>,
+    /// Only the first row of a key within the window fires a notification
+    pub time_window_minutes: i64,
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize, ToSchema, PartialEq)]
@@ -67,6 +90,12 @@ pub struct QueryCondition {
     pub sql: Option<String>,
     pub promql: Option<String>,              // (cpu usage / cpu total)
     pub promql_condition: Option<Condition>, // value >= 80
+    /// The step of the PromQL query, a duration like `30s`, or `auto` to use the scrape interval
+    /// detected from the stored samples of the metric. Defaults to the time range divided by
+    /// 256, at least 10s, a shorter step is raised to it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub promql_step: Option<String>,
     pub aggregation: Option<Aggregation>,
     #[serde(default)]
     pub vrl_function: Option<String>,
@@ -76,6 +105,9 @@ pub struct QueryCondition {
     pub multi_time_range: Option<Vec<CompareHistoricData>>,
 }
 
+/// The `promql_step` which detects the step from the scrape interval of the metric
+pub const PROMQL_STEP_AUTO: &str = "auto";
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema, PartialEq)]
 pub struct Aggregation {
     pub group_by: Option<Vec<String>>,
@@ -95,6 +127,8 @@ pub enum AggFunction {
     Sum,
     #[serde(rename = "count")]
     Count,
+    #[serde(rename = "count_distinct")]
+    CountDistinct,
     #[serde(rename = "median")]
     Median,
     #[serde(rename = "p50")]
@@ -117,6 +151,7 @@ impl std::fmt::Display for AggFunction {
             AggFunction::Max => write!(f, "max"),
             AggFunction::Sum => write!(f, "sum"),
             AggFunction::Count => write!(f, "count"),
+            AggFunction::CountDistinct => write!(f, "count_distinct"),
             AggFunction::Median => write!(f, "median"),
             AggFunction::P50 => write!(f, "p50"),
             AggFunction::P75 => write!(f, "p75"),
@@ -136,6 +171,7 @@ impl TryFrom<&str> for AggFunction {
             "max" => AggFunction::Max,
             "sum" => AggFunction::Sum,
             "count" => AggFunction::Count,
+            "count_distinct" => AggFunction::CountDistinct,
             "median" => AggFunction::Median,
             "p50" => AggFunction::P50,
             "p75" => AggFunction::P75,
@@ -0,0 +1,83 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The operations an API key is allowed to call, `admin` allows every operation the user who
+/// created the key is allowed to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    Search,
+    Ingest,
+    Admin,
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiKeyScope::Search => write!(f, "search"),
+            ApiKeyScope::Ingest => write!(f, "ingest"),
+            ApiKeyScope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "search" => Ok(ApiKeyScope::Search),
+            "ingest" => Ok(ApiKeyScope::Ingest),
+            "admin" => Ok(ApiKeyScope::Admin),
+            _ => Err(format!("Invalid API key scope: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+/// The created key, it is only returned once as only its hash is stored
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    pub name: String,
+    pub key: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_by: String,
+    /// microseconds
+    pub created_at: i64,
+    /// microseconds, none if the key was never used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeys {
+    pub keys: Vec<ApiKey>,
+}
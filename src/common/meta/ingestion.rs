@@ -338,6 +338,8 @@ pub enum IngestionData<'a> {
 pub enum IngestionError {
     IoError(std::io::Error),
     JsonError(json::Error),
+    /// a record which isn't valid JSON, with the record as received
+    InvalidRecord(String, json::Error),
     AWSError(KinesisFHIngestionResponse),
     GCPError(GCPIngestionResponse),
 }
@@ -354,9 +356,10 @@ impl From<std::io::Error> for IngestionError {
     }
 }
 
+/// The records of a request, the JSON and multi iterators keep the last record as received
 pub enum IngestionDataIter<'a> {
-    JSONIter(std::slice::Iter<'a, json::Value>),
-    MultiIter(Lines<BufReader<&'a [u8]>>),
+    JSONIter(std::slice::Iter<'a, json::Value>, Option<&'a json::Value>),
+    MultiIter(Lines<BufReader<&'a [u8]>>, Option<String>),
     GCP(
         std::vec::IntoIter<json::Value>,
         Option<GCPIngestionResponse>,
@@ -41,7 +41,8 @@ pub struct GeoInfoData<'a> {
 
 /// This is a global cache for user agent parser. This is lazily initialized only when
 /// the first request comes in.
-static UA_PARSER: Lazy<Arc<UserAgentParser>> = Lazy::new(|| Arc::new(initialize_ua_parser()));
+pub(crate) static UA_PARSER: Lazy<Arc<UserAgentParser>> =
+    Lazy::new(|| Arc::new(initialize_ua_parser()));
 
 pub fn initialize_ua_parser() -> UserAgentParser {
     UserAgentParser::builder()
@@ -14,6 +14,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod alerts;
+pub mod api_key;
 pub mod authz;
 pub mod dashboards;
 pub mod functions;
@@ -25,6 +26,7 @@ pub mod organization;
 pub mod pipelines;
 pub mod prom;
 pub mod proxy;
+pub mod reingest;
 pub mod saved_view;
 pub mod search;
 pub mod service;
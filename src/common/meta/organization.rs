@@ -119,6 +119,38 @@ pub struct OrganizationSetting {
     pub trace_id_field_name: String,
     #[serde(default = "default_span_id_field_name")]
     pub span_id_field_name: String,
+    /// Overrides the maximum rows returned by a query, `ZO_QUERY_MAX_ROWS` is used if not set
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_max_rows: Option<i64>,
+    /// Overrides the maximum size in MB a query can scan, `ZO_QUERY_MAX_SCAN_SIZE` is used if
+    /// not set
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_max_scan_size: Option<i64>,
+    /// Overrides what to do with a query spanning more than the max query range of a stream,
+    /// `clamp` or `reject`, `ZO_QUERY_RANGE_POLICY` is used if not set
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_range_policy: Option<String>,
+    /// Drops the OTLP/HTTP batches whose `X-Request-Id` was seen within
+    /// `ZO_INGEST_OTLP_DEDUPE_TTL`, eg: sent again by a proxy retrying the request
+    #[serde(default)]
+    pub otlp_dedupe_enabled: bool,
+    /// Writes the ingested records failing the JSON parsing or the timestamp extraction to this
+    /// logs stream instead of dropping them, not set disables it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quarantine_stream: Option<String>,
+    /// Overrides the seconds a cached query result is used for, `ZO_RESULT_CACHE_TTL` is used if
+    /// not set
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_cache_ttl: Option<i64>,
+    /// Overrides the maximum groups of a GROUP BY query, `ZO_QUERY_MAX_GROUPS` is used if not set
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_max_groups: Option<i64>,
 }
 
 impl Default for OrganizationSetting {
@@ -127,6 +159,13 @@ impl Default for OrganizationSetting {
             scrape_interval: default_scrape_interval(),
             trace_id_field_name: default_trace_id_field_name(),
             span_id_field_name: default_span_id_field_name(),
+            query_max_rows: None,
+            query_max_scan_size: None,
+            query_range_policy: None,
+            otlp_dedupe_enabled: false,
+            quarantine_stream: None,
+            result_cache_ttl: None,
+            query_max_groups: None,
         }
     }
 }
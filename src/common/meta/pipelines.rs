@@ -40,6 +40,10 @@ pub struct PipeLine {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub derived_streams: Option<Vec<DerivedStreamMeta>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_ip: Option<Vec<GeoIpEnrichment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<Vec<UserAgentParsing>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<HashMap<String, Value>>,
 }
 
@@ -53,9 +57,35 @@ impl PipeLine {
             routing: self.routing,
             derived_streams: self.derived_streams,
             functions,
+            geo_ip: self.geo_ip,
+            user_agent: self.user_agent,
             meta: self.meta,
         }
     }
+
+    /// Checks the geo-IP enrichments and the user-agent parsings have a source field and output
+    /// fields
+    pub fn validate_enrichments(&self) -> Result<(), String> {
+        let geo_ip = self.geo_ip.iter().flatten();
+        if !geo_ip.all(|v| has_fields(&v.source_field, &v.fields)) {
+            return Err(
+                "Invalid geo-IP enrichment details. Source field and output fields required"
+                    .to_string(),
+            );
+        }
+        let user_agent = self.user_agent.iter().flatten();
+        if !user_agent.all(|v| has_fields(&v.source_field, &v.fields)) {
+            return Err(
+                "Invalid user-agent parsing details. Source field and output fields required"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn has_fields<T>(source_field: &str, fields: &HashMap<String, T>) -> bool {
+    !source_field.is_empty() && !fields.is_empty() && fields.keys().all(|k| !k.is_empty())
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema, PartialEq)]
@@ -74,9 +104,57 @@ pub struct PipeLineResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub derived_streams: Option<Vec<DerivedStreamMeta>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_ip: Option<Vec<GeoIpEnrichment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<Vec<UserAgentParsing>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<HashMap<String, Value>>,
 }
 
+/// Adds the location and the network of the IP address in `source_field` to the records, looked
+/// up in the database of `ZO_GEOIP_ENRICHMENT_DB_PATH`
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct GeoIpEnrichment {
+    pub source_field: String,
+    /// The output field of each looked up attribute
+    pub fields: HashMap<String, GeoIpAttribute>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeoIpAttribute {
+    CountryName,
+    CountryIsoCode,
+    CityName,
+    ContinentCode,
+    Latitude,
+    Longitude,
+    TimeZone,
+    Asn,
+    AsOrg,
+}
+
+/// Parses the user agent in `source_field` of the records with the bundled regexes of
+/// `ua_regex/regexes.yaml`
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct UserAgentParsing {
+    pub source_field: String,
+    /// The output field of each parsed attribute
+    pub fields: HashMap<String, UserAgentAttribute>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserAgentAttribute {
+    BrowserFamily,
+    BrowserVersion,
+    OsFamily,
+    OsVersion,
+    DeviceFamily,
+    DeviceBrand,
+    DeviceModel,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct PipeLineList {
     pub list: Vec<PipeLineResponse>,
@@ -13,6 +13,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeMap;
+
 use config::FxIndexMap;
 use hashbrown::HashMap;
 use proto::prometheus_rpc;
@@ -27,6 +29,15 @@ pub const VALUE_LABEL: &str = "value";
 pub const BUCKET_LABEL: &str = "le";
 pub const QUANTILE_LABEL: &str = "quantile";
 pub const METADATA_LABEL: &str = "prom_metadata"; // for schema metadata key
+// the exemplars of a sample, stored as a JSON array string
+pub const EXEMPLARS_LABEL: &str = "exemplars";
+// aggregated columns of the rollup streams, the last sample is kept in `value` too
+pub const ROLLUP_LABEL_PREFIX: &str = "__rollup_";
+pub const ROLLUP_LAST_LABEL: &str = "__rollup_last__";
+pub const ROLLUP_MIN_LABEL: &str = "__rollup_min__";
+pub const ROLLUP_MAX_LABEL: &str = "__rollup_max__";
+pub const ROLLUP_SUM_LABEL: &str = "__rollup_sum__";
+pub const ROLLUP_COUNT_LABEL: &str = "__rollup_count__";
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Metric<'a> {
@@ -217,6 +228,34 @@ pub struct RequestFormatQuery {
     pub query: String,
 }
 
+/// Request the exemplars of the series selected by a query.
+#[derive(Debug, Deserialize)]
+pub struct RequestQueryExemplars {
+    /// Prometheus expression query string.
+    pub query: Option<String>,
+    /// Start timestamp.
+    pub start: Option<String>,
+    /// End timestamp.
+    pub end: Option<String>,
+}
+
+/// The exemplars of a series.
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExemplarSeries {
+    pub series_labels: BTreeMap<String, String>,
+    pub exemplars: Vec<Exemplar>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Exemplar {
+    /// The labels of the exemplar, like `trace_id` and `span_id`
+    pub labels: BTreeMap<String, String>,
+    pub value: String,
+    /// Seconds
+    pub timestamp: f64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
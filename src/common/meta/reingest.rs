@@ -0,0 +1,62 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Replays the stored data of a logs stream in a time range through a pipeline
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReingestRequest {
+    /// microseconds
+    pub start_time: i64,
+    /// microseconds
+    pub end_time: i64,
+    /// the name of a logs pipeline, its functions are applied to the records
+    pub pipeline: String,
+    /// the stream the results are written to
+    pub target_stream: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReingestStatus {
+    #[default]
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ReingestJob {
+    pub id: String,
+    pub org_id: String,
+    pub stream_name: String,
+    pub pipeline: String,
+    pub target_stream: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub status: ReingestStatus,
+    pub total_files: usize,
+    pub processed_files: usize,
+    /// the files which were already replayed by a previous job
+    pub skipped_files: usize,
+    pub records: u64,
+    pub failed_records: u64,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
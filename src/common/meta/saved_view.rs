@@ -74,3 +74,90 @@ pub struct CreateViewResponse {
     pub view_id: String,
     pub view_name: String,
 }
+
+/// A saved view which runs on a cron and writes its results to the object storage
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScheduledExport {
+    pub name: String,
+    /// The saved view to run
+    pub view_id: String,
+    /// Cron expression with seconds, e.g. `0 0 2 * * *` runs every night at 02:00
+    pub cron: String,
+    /// The timezone of the cron, e.g. `Europe/Berlin`, the runs follow its daylight saving time
+    #[serde(default = "default_export_timezone")]
+    pub timezone: String,
+    /// The time range the query reads before the run, in minutes
+    #[serde(default = "default_export_period")]
+    pub period: i64,
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// The prefix of the files in the bucket, the files are written to
+    /// `{destination}/{org_id}/{name}/{YYYY/MM/DD}/{timestamp}.{format}`
+    pub destination: String,
+    #[serde(default = "default_export_enabled")]
+    pub enabled: bool,
+    /// The user the export runs as, with the permissions of the user. It's set to the user who
+    /// saves the export.
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_triggered_at: Option<i64>,
+}
+
+fn default_export_period() -> i64 {
+    1440
+}
+
+fn default_export_enabled() -> bool {
+    true
+}
+
+fn default_export_timezone() -> String {
+    "UTC".to_string()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Parquet,
+    Json,
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::Parquet => write!(f, "parquet"),
+            ExportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// A run of a scheduled export
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ExportRun {
+    /// Start of the run in microseconds
+    pub timestamp: i64,
+    pub success: bool,
+    /// The path of the file in the bucket, empty when the run failed
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub records: usize,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ScheduledExports {
+    pub exports: Vec<ScheduledExport>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ExportRuns {
+    pub runs: Vec<ExportRun>,
+}
@@ -62,6 +62,9 @@ pub struct MultiCachedQueryResponse {
     pub took: usize,
     pub histogram_interval: i64,
     pub total_cache_duration: usize,
+    /// The response is served from the cache only, but the cached responses don't cover the
+    /// newest (or the oldest for an ascending query) part of the query range
+    pub is_partial_cache: bool,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
@@ -46,6 +46,16 @@ pub struct StreamProperty {
     pub prop_type: String,
 }
 
+/// The newest records of a stream with its schema
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamSample {
+    pub schema: Vec<StreamProperty>,
+    #[schema(value_type = Vec<Object>)]
+    pub hits: Vec<json::Value>,
+    /// milliseconds
+    pub took: usize,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StreamQueryParams {
     #[serde(rename = "type")]
@@ -81,6 +91,87 @@ pub struct StreamDeleteFields {
     pub fields: Vec<String>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamSchemaDiff {
+    pub stream_name: String,
+    pub stream_type: StreamType,
+    pub schema: Vec<SchemaField>,
+    /// The changes before this time are not known
+    pub history_start: Option<i64>,
+    pub changes: Vec<SchemaFieldChange>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SchemaField {
+    pub name: String,
+    pub r#type: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SchemaFieldChange {
+    pub field: String,
+    /// Empty when the field was added
+    pub old_type: Option<String>,
+    /// Empty when the field was removed
+    pub new_type: Option<String>,
+    pub version: u64,
+    pub timestamp: i64,
+}
+
+/// How far behind now the newest data of a stream is, the timestamps are in microseconds
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct IngestionLag {
+    /// The newest timestamp of the files in the file list, 0 if none
+    pub file_list_max_ts: i64,
+    /// The newest timestamp received by the ingesters, 0 if none
+    pub memtable_max_ts: i64,
+    pub max_ts: i64,
+    /// Empty when the stream has no data
+    pub lag_seconds: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct CompactRequest {
+    /// microseconds
+    pub start_time: i64,
+    /// microseconds
+    pub end_time: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactJobStatus {
+    #[default]
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// A compaction of a stream time range requested manually, executed by a compactor
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct CompactJob {
+    pub id: String,
+    pub org_id: String,
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub status: CompactJobStatus,
+    /// The compactor node working on the job
+    #[serde(default)]
+    pub node: String,
+    /// The time buckets, hourly or daily as the partition time level of the stream
+    pub total_buckets: usize,
+    pub processed_buckets: usize,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,6 +86,18 @@ pub struct SpanLink {
     pub dropped_attributes_count: u32,
 }
 
+/// The logs emitted during a span
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SpanLogs {
+    pub span_id: String,
+    pub service_name: String,
+    pub operation_name: String,
+    pub start_time: i64, // nanoseconds
+    pub end_time: i64,   // nanoseconds
+    #[schema(value_type = Vec<Object>)]
+    pub logs: Vec<json::Value>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct ExportTraceServiceResponse {
     // The details of a partially successful export request.
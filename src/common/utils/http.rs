@@ -146,17 +146,8 @@ pub(crate) fn get_or_create_trace_id(headers: &HeaderMap, span: &tracing::Span)
             trace_id
         } else {
             // manually parse trace_id
-            if let Ok(traceparent_str) = traceparent.to_str() {
-                let parts: Vec<&str> = traceparent_str.split('-').collect();
-                if parts.len() >= 3 {
-                    let trace_id = parts[1].to_string();
-                    // If the trace-id value is invalid (for example if it contains non-allowed
-                    // characters or all zeros), vendors MUST ignore the traceparent.
-                    // https://www.w3.org/TR/trace-context/#traceparent-header
-                    if trace_id.len() == 32 && !trace_id.chars().all(|c| c == '0') {
-                        return trace_id;
-                    }
-                }
+            if let Some(trace_id) = traceparent.to_str().ok().and_then(parse_traceparent) {
+                return trace_id;
             }
             // If parsing fails or trace_id is invalid, generate a new one
             log::warn!("Failed to parse valid trace_id from received [Traceparent] header");
@@ -169,6 +160,25 @@ pub(crate) fn get_or_create_trace_id(headers: &HeaderMap, span: &tracing::Span)
     }
 }
 
+/// Returns the trace id of a `traceparent` header value, or None if it is invalid
+pub(crate) fn parse_traceparent(traceparent: &str) -> Option<String> {
+    let parts: Vec<&str> = traceparent.split('-').collect();
+    if parts.len() < 3 {
+        return None;
+    }
+    // If the trace-id value is invalid (for example if it contains non-allowed
+    // characters or all zeros), vendors MUST ignore the traceparent.
+    // https://www.w3.org/TR/trace-context/#traceparent-header
+    is_valid_trace_id(parts[1]).then(|| parts[1].to_string())
+}
+
+/// A trace id is 32 hex characters and not all zeros
+pub(crate) fn is_valid_trace_id(trace_id: &str) -> bool {
+    trace_id.len() == 32
+        && trace_id.chars().all(|c| c.is_ascii_hexdigit())
+        && !trace_id.chars().all(|c| c == '0')
+}
+
 /// This function can handle IPv4 and IPv6 addresses which may have port numbers appended
 pub fn parse_ip_addr(ip_address: &str) -> Result<(IpAddr, Option<u16>), AddrParseError> {
     let mut port: Option<u16> = None;
@@ -275,4 +285,21 @@ mod tests {
                 .fold(true, |acc, x| { acc | x })
         );
     }
+
+    #[test]
+    fn test_parse_traceparent() {
+        assert_eq!(
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string())
+        );
+        assert_eq!(
+            parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01"),
+            None
+        );
+        assert_eq!(
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e47zz-00f067aa0ba902b7-01"),
+            None
+        );
+        assert_eq!(parse_traceparent("4bf92f3577b34da6a3ce929d0e0e4736"), None);
+    }
 }
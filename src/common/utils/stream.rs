@@ -189,6 +189,7 @@ mod tests {
             original_size: 1000,
             compressed_size: 700,
             flattened: false,
+            index_size: 0,
         };
         populate_file_meta(schema, vec![vec![batch]], &mut file_meta, None, None)
             .await
@@ -225,6 +226,7 @@ mod tests {
             original_size: 1000,
             compressed_size: 700,
             flattened: false,
+            index_size: 0,
         };
         populate_file_meta(
             schema,
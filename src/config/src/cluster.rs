@@ -41,6 +41,7 @@ pub fn load_local_node() -> Node {
         status: NodeStatus::Online,
         scheduled: true,
         broadcasted: false,
+        load: Default::default(),
     }
 }
 
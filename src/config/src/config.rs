@@ -33,7 +33,7 @@ use sysinfo::{DiskExt, SystemExt};
 
 use crate::{
     meta::cluster,
-    utils::{cgroup, file::get_file_meta},
+    utils::{cgroup, file::get_file_meta, flatten::flattened_field_name},
 };
 
 pub type FxIndexMap<K, V> = indexmap::IndexMap<K, V, ahash::RandomState>;
@@ -73,6 +73,8 @@ pub const REQUIRED_DB_CONNECTIONS: u32 = 4;
 // Used for storing and querying unflattened original data
 pub const ORIGINAL_DATA_COL_NAME: &str = "_original";
 pub const ID_COL_NAME: &str = "_o2_id";
+// The number of identical records collapsed into a record at ingestion
+pub const COLLAPSED_COUNT_COL_NAME: &str = "_count";
 
 const _DEFAULT_SQL_FULL_TEXT_SEARCH_FIELDS: [&str; 7] =
     ["log", "message", "msg", "content", "data", "body", "json"];
@@ -132,7 +134,8 @@ pub static DISTINCT_FIELDS: Lazy<Vec<String>> = Lazy::new(|| {
                 if s.is_empty() {
                     None
                 } else {
-                    Some(s.to_string())
+                    // a nested field is tracked under its flattened name
+                    Some(flattened_field_name(s))
                 }
             }),
     )
@@ -191,6 +194,31 @@ pub static MEM_TABLE_INDIVIDUAL_STREAMS: Lazy<HashMap<String, usize>> = Lazy::ne
 });
 
 static CONFIG: Lazy<ArcSwap<Config>> = Lazy::new(|| ArcSwap::from(Arc::new(init())));
+// the environment variables the current config was built from, with the reloaded settings. The
+// reloaded settings are only kept here, the process environment isn't changed at runtime.
+static CONFIG_ENVS: Lazy<parking_lot::RwLock<HashMap<String, String>>> =
+    Lazy::new(Default::default);
+
+// settings which are read on use, so they can be changed by `reload_config` without a restart
+const RELOADABLE_ENVS: [&str; 17] = [
+    "ZO_QUERY_TIMEOUT",
+    "ZO_QUERY_DEFAULT_LIMIT",
+    "ZO_INGEST_ALLOWED_UPTO",
+    "ZO_INGEST_IDEMPOTENCY_TTL",
+    "ZO_INGEST_OTLP_DEDUPE_TTL",
+    "ZO_INGEST_QUARANTINE_MAX_RECORDS",
+    "ZO_INGEST_QUARANTINE_MAX_RECORD_SIZE",
+    "ZO_COLS_PER_RECORD_LIMIT",
+    "ZO_MAX_FILE_RETENTION_TIME",
+    "ZO_WIDENING_SCHEMA_EVOLUTION",
+    "ZO_SKIP_SCHEMA_VALIDATION",
+    "ZO_MEMORY_CACHE_SKIP_SIZE",
+    "ZO_MEMORY_CACHE_RELEASE_SIZE",
+    "ZO_MEMORY_CACHE_GC_SIZE",
+    "ZO_DISK_CACHE_SKIP_SIZE",
+    "ZO_DISK_CACHE_RELEASE_SIZE",
+    "ZO_DISK_CACHE_GC_SIZE",
+];
 static INSTANCE_ID: Lazy<RwHashMap<String, String>> = Lazy::new(Default::default);
 
 pub static TELEMETRY_CLIENT: Lazy<segment::HttpClient> = Lazy::new(|| {
@@ -212,6 +240,104 @@ pub fn refresh_config() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Re-reads the `.env` file and swaps in a config with the changed settings. Only the settings
+/// in `RELOADABLE_ENVS` can change, any other change rejects the reload. A setting removed from
+/// the file keeps its value until a restart. Returns the names of the changed settings.
+pub fn reload_config() -> Result<Vec<String>, anyhow::Error> {
+    let envs = match dotenvy::dotenv_iter() {
+        Ok(iter) => iter.collect::<Result<Vec<_>, _>>()?,
+        // no `.env` file, nothing to reload
+        Err(e) if e.not_found() => vec![],
+        Err(e) => return Err(e.into()),
+    };
+    reload_config_from(envs)
+}
+
+fn reload_config_from(
+    envs: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<String>, anyhow::Error> {
+    // make sure the current config and its environment variables are loaded
+    let _ = get_config();
+    let mut new_envs = CONFIG_ENVS.read().clone();
+    let mut changed = vec![];
+    for (k, v) in envs {
+        if new_envs.get(&k) != Some(&v) {
+            changed.push(k.clone());
+            new_envs.insert(k, v);
+        }
+    }
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+    changed.sort();
+    changed.dedup();
+
+    let rejected = changed
+        .iter()
+        .filter(|k| !RELOADABLE_ENVS.contains(&k.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !rejected.is_empty() {
+        return Err(anyhow::anyhow!(
+            "settings [{}] can't be reloaded, a restart is required to change them",
+            rejected.join(", ")
+        ));
+    }
+
+    // the config is built from the process environment, with the reloaded settings on top
+    let overrides = new_envs
+        .iter()
+        .filter(|(k, v)| {
+            RELOADABLE_ENVS.contains(&k.as_str()) && std::env::var(k).ok().as_ref() != Some(*v)
+        })
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>();
+    let cfg = load(&overrides)?;
+    *CONFIG_ENVS.write() = new_envs;
+    CONFIG.store(Arc::new(cfg));
+    log::info!("[CONFIG] reloaded settings: {}", changed.join(", "));
+    Ok(changed)
+}
+
+/// Sets the setting of a reloadable environment variable, before the config checks convert it
+fn apply_reloadable_env(cfg: &mut Config, key: &str, value: &str) -> Result<(), anyhow::Error> {
+    fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, anyhow::Error> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid value [{value}] of setting {key}"))
+    }
+    match key {
+        "ZO_QUERY_TIMEOUT" => cfg.limit.query_timeout = parse(key, value)?,
+        "ZO_QUERY_DEFAULT_LIMIT" => cfg.limit.query_default_limit = parse(key, value)?,
+        "ZO_INGEST_ALLOWED_UPTO" => cfg.limit.ingest_allowed_upto = parse(key, value)?,
+        "ZO_INGEST_IDEMPOTENCY_TTL" => cfg.limit.ingest_idempotency_ttl = parse(key, value)?,
+        "ZO_INGEST_OTLP_DEDUPE_TTL" => cfg.limit.ingest_otlp_dedupe_ttl = parse(key, value)?,
+        "ZO_INGEST_QUARANTINE_MAX_RECORDS" => {
+            cfg.limit.ingest_quarantine_max_records = parse(key, value)?
+        }
+        "ZO_INGEST_QUARANTINE_MAX_RECORD_SIZE" => {
+            cfg.limit.ingest_quarantine_max_record_size = parse(key, value)?
+        }
+        "ZO_COLS_PER_RECORD_LIMIT" => cfg.limit.req_cols_per_record_limit = parse(key, value)?,
+        "ZO_MAX_FILE_RETENTION_TIME" => cfg.limit.max_file_retention_time = parse(key, value)?,
+        "ZO_WIDENING_SCHEMA_EVOLUTION" => {
+            cfg.common.widening_schema_evolution = parse(key, &value.to_lowercase())?
+        }
+        "ZO_SKIP_SCHEMA_VALIDATION" => {
+            cfg.common.skip_schema_validation = parse(key, &value.to_lowercase())?
+        }
+        "ZO_MEMORY_CACHE_SKIP_SIZE" => cfg.memory_cache.skip_size = parse(key, value)?,
+        "ZO_MEMORY_CACHE_RELEASE_SIZE" => cfg.memory_cache.release_size = parse(key, value)?,
+        "ZO_MEMORY_CACHE_GC_SIZE" => cfg.memory_cache.gc_size = parse(key, value)?,
+        "ZO_DISK_CACHE_SKIP_SIZE" => cfg.disk_cache.skip_size = parse(key, value)?,
+        "ZO_DISK_CACHE_RELEASE_SIZE" => cfg.disk_cache.release_size = parse(key, value)?,
+        "ZO_DISK_CACHE_GC_SIZE" => cfg.disk_cache.gc_size = parse(key, value)?,
+        _ => return Err(anyhow::anyhow!("setting {key} can't be reloaded")),
+    }
+    Ok(())
+}
+
 pub fn cache_instance_id(instance_id: &str) {
     INSTANCE_ID.insert("instance_id".to_owned(), instance_id.to_owned());
 }
@@ -318,6 +444,31 @@ pub async fn get_sns_client() -> &'static aws_sdk_sns::Client {
     SNS_CLIENT.get_or_init(init_sns_client).await
 }
 
+static SECRETS_MANAGER_CLIENT: tokio::sync::OnceCell<aws_sdk_secretsmanager::Client> =
+    tokio::sync::OnceCell::const_new();
+
+async fn init_secrets_manager_client() -> aws_sdk_secretsmanager::Client {
+    let cfg = get_config();
+    let shared_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+
+    let mut builder = aws_sdk_secretsmanager::config::Builder::from(&shared_config).timeout_config(
+        aws_config::timeout::TimeoutConfig::builder()
+            .operation_timeout(std::time::Duration::from_secs(cfg.secret.timeout))
+            .build(),
+    );
+    if !cfg.secret.aws_endpoint.is_empty() {
+        builder = builder.endpoint_url(cfg.secret.aws_endpoint.clone());
+    }
+
+    aws_sdk_secretsmanager::Client::from_conf(builder.build())
+}
+
+pub async fn get_secrets_manager_client() -> &'static aws_sdk_secretsmanager::Client {
+    SECRETS_MANAGER_CLIENT
+        .get_or_init(init_secrets_manager_client)
+        .await
+}
+
 pub static BLOCKED_STREAMS: Lazy<Vec<String>> = Lazy::new(|| {
     let blocked_streams = get_config()
         .common
@@ -345,6 +496,7 @@ pub struct Config {
     pub nats: Nats,
     pub s3: S3,
     pub sns: Sns,
+    pub secret: Secret,
     pub tcp: TCP,
     pub prom: Prometheus,
     pub profiling: Pyroscope,
@@ -461,6 +613,18 @@ pub struct Http {
     pub addr: String,
     #[env_config(name = "ZO_HTTP_IPV6_ENABLED", default = false)]
     pub ipv6_enabled: bool,
+    #[env_config(
+        name = "ZO_HTTP_COMPRESSION_PREFERRED",
+        default = "zstd",
+        help = "Encoding of the responses when the client accepts it, zstd or gzip, gzip is the fallback"
+    )]
+    pub compression_preferred: String,
+    #[env_config(
+        name = "ZO_HTTP_COMPRESSION_LEVEL",
+        default = 3,
+        help = "Compression level of the responses, gzip levels are capped to 9"
+    )]
+    pub compression_level: i32,
 }
 
 #[derive(EnvConfig)]
@@ -483,6 +647,36 @@ pub struct Grpc {
     pub max_message_size: usize,
     #[env_config(name = "ZO_GRPC_CONNECT_TIMEOUT", default = 5)] // in seconds
     pub connect_timeout: u64,
+    #[env_config(
+        name = "ZO_GRPC_TLS_ENABLED",
+        default = false,
+        help = "Serve the grpc servers over TLS, the nodes connect to each other over TLS too"
+    )]
+    pub tls_enabled: bool,
+    #[env_config(
+        name = "ZO_GRPC_TLS_CERT_PATH",
+        default = "",
+        help = "PEM file of the grpc server certificate, it's also the client certificate of the node"
+    )]
+    pub tls_cert_path: String,
+    #[env_config(
+        name = "ZO_GRPC_TLS_KEY_PATH",
+        default = "",
+        help = "PEM file of the private key of the grpc server certificate"
+    )]
+    pub tls_key_path: String,
+    #[env_config(
+        name = "ZO_GRPC_TLS_CLIENT_CA_PATH",
+        default = "",
+        help = "PEM file of the CA verifying the client certificates, when it's set the grpc servers reject the connections without a certificate signed by it. The nodes also verify the certificates of each other against it"
+    )]
+    pub tls_client_ca_path: String,
+    #[env_config(
+        name = "ZO_GRPC_TLS_DOMAIN_NAME",
+        default = "",
+        help = "Domain name the nodes verify the certificate of the other nodes against, defaults to the host of the address"
+    )]
+    pub tls_domain_name: String,
 }
 
 #[derive(EnvConfig)]
@@ -504,6 +698,34 @@ pub struct Route {
     // zo1-openobserve-ingester.ziox-dev.svc.cluster.local
     #[env_config(name = "ZO_INGESTER_SERVICE_URL", default = "")]
     pub ingester_srv_url: String,
+    #[env_config(
+        name = "ZO_ROUTE_STRATEGY",
+        default = "random",
+        help = "Strategy for the router to pick a querier: round_robin, least_loaded or random"
+    )]
+    pub strategy: String,
+    #[env_config(name = "ZO_ROUTE_LOAD_REPORT_INTERVAL", default = 10)] // seconds
+    pub load_report_interval: u64,
+    #[env_config(
+        name = "ZO_ROUTE_LOAD_STALE_TIMEOUT",
+        default = 30,
+        help = "Node load older than this is considered stale and the router falls back to round_robin"
+    )] // seconds
+    pub load_stale_timeout: i64,
+    #[env_config(
+        name = "ZO_ROUTE_HEDGE_ENABLED",
+        default = false,
+        help = "Send a duplicate search to a second querier when the first one is slow"
+    )]
+    pub hedge_enabled: bool,
+    #[env_config(
+        name = "ZO_ROUTE_HEDGE_PERCENTILE",
+        default = 95,
+        help = "Hedge a search when it runs longer than this percentile of recent search latencies"
+    )]
+    pub hedge_percentile: usize,
+    #[env_config(name = "ZO_ROUTE_HEDGE_MIN_DELAY", default = 100)] // milliseconds
+    pub hedge_min_delay: u64,
 }
 
 #[derive(EnvConfig)]
@@ -519,6 +741,18 @@ pub struct Common {
     pub cluster_coordinator: String,
     #[env_config(name = "ZO_QUEUE_STORE", default = "")]
     pub queue_store: String,
+    #[env_config(
+        name = "ZO_QUEUE_VISIBILITY_TIMEOUT",
+        default = 30,
+        help = "Seconds before an unacknowledged queue message is delivered again"
+    )]
+    pub queue_visibility_timeout: u64,
+    #[env_config(
+        name = "ZO_QUEUE_MAX_REDELIVERY",
+        default = 5,
+        help = "Times a queue message is redelivered before it's moved to the dead letter topic"
+    )]
+    pub queue_max_redelivery: u32,
     #[env_config(name = "ZO_META_STORE", default = "")]
     pub meta_store: String,
     pub meta_store_external: bool, // external storage no need sync file_list to s3
@@ -566,7 +800,11 @@ pub struct Common {
     pub feature_per_thread_lock: bool,
     #[env_config(name = "ZO_FEATURE_FULLTEXT_EXTRA_FIELDS", default = "")]
     pub feature_fulltext_extra_fields: String,
-    #[env_config(name = "ZO_FEATURE_DISTINCT_EXTRA_FIELDS", default = "")]
+    #[env_config(
+        name = "ZO_FEATURE_DISTINCT_EXTRA_FIELDS",
+        default = "",
+        help = "Extra fields whose distinct values are tracked, a nested field can be given as a path like kubernetes.namespace"
+    )]
     pub feature_distinct_extra_fields: String,
     #[env_config(name = "ZO_FEATURE_QUICK_MODE_FIELDS", default = "")]
     pub feature_quick_mode_fields: String,
@@ -635,6 +873,18 @@ pub struct Common {
     pub telemetry_heartbeat: i64,
     #[env_config(name = "ZO_PROMETHEUS_ENABLED", default = true)]
     pub prometheus_enabled: bool,
+    #[env_config(
+        name = "ZO_PROMETHEUS_SEARCH_LATENCY_BUCKETS",
+        default = "0.01,0.05,0.1,0.25,0.5,1,2.5,5,10,30,60,120",
+        help = "Buckets in seconds of the search latency histograms, use comma to split"
+    )]
+    pub prometheus_search_latency_buckets: String,
+    #[env_config(
+        name = "ZO_PROMETHEUS_INGEST_LATENCY_BUCKETS",
+        default = "0.001,0.005,0.01,0.025,0.05,0.1,0.25,0.5,1,2.5,5,10",
+        help = "Buckets in seconds of the ingestion latency histograms, use comma to split"
+    )]
+    pub prometheus_ingest_latency_buckets: String,
     #[env_config(name = "ZO_PRINT_KEY_CONFIG", default = false)]
     pub print_key_config: bool,
     #[env_config(name = "ZO_PRINT_KEY_EVENT", default = false)]
@@ -667,6 +917,12 @@ pub struct Common {
     )]
     // in seconds
     pub usage_publish_interval: i64,
+    #[env_config(
+        name = "ZO_USAGE_SAMPLING_RATIO",
+        default = 1.0,
+        help = "fraction of per-request usage events recorded, between 0 and 1. The recorded events have size and num_records scaled by 1/ratio, so the usage totals are estimates when it is less than 1. Partial search results are always recorded."
+    )]
+    pub usage_sampling_ratio: f64,
     #[env_config(name = "ZO_MMDB_DATA_DIR")] // ./data/openobserve/mmdb/
     pub mmdb_data_dir: String,
     #[env_config(name = "ZO_MMDB_DISABLE_DOWNLOAD", default = false)]
@@ -693,6 +949,12 @@ pub struct Common {
         default = "https://geoip.zinclabs.dev/GeoLite2-ASN.sha256"
     )]
     pub mmdb_geolite_asndb_sha256_url: String,
+    #[env_config(
+        name = "ZO_GEOIP_ENRICHMENT_DB_PATH",
+        default = "",
+        help = "MaxMind-format database of the geo-IP enrichment of pipelines, the GeoLite2 city database in ZO_MMDB_DATA_DIR if empty. The file is reloaded when it changes."
+    )]
+    pub geoip_enrichment_db_path: String,
     #[env_config(name = "ZO_DEFAULT_SCRAPE_INTERVAL", default = 15)]
     // Default scrape_interval value 15s
     pub default_scrape_interval: u32,
@@ -796,6 +1058,30 @@ pub struct Common {
         help = "traces span metrics channel send buffer"
     )]
     pub traces_span_metrics_channel_buffer: usize,
+    #[env_config(
+        name = "ZO_TRACES_SAMPLING_ENABLED",
+        default = false,
+        help = "Only keep a percentage of the traces, decided by their trace id"
+    )]
+    pub traces_sampling_enabled: bool,
+    #[env_config(
+        name = "ZO_TRACES_SAMPLING_PERCENTAGE",
+        default = 10.0,
+        help = "Percentage of the traces to keep, from 0 to 100"
+    )]
+    pub traces_sampling_percentage: f64,
+    #[env_config(
+        name = "ZO_TRACES_LOGS_TRACE_ID_FIELDS",
+        default = "trace_id,traceId,traceid",
+        help = "Field names carrying the trace id in logs streams, the first one found in the stream schema is used to correlate logs with traces"
+    )]
+    pub traces_logs_trace_id_fields: String,
+    #[env_config(
+        name = "ZO_TRACES_LOGS_SPAN_ID_FIELDS",
+        default = "span_id,spanId,spanid",
+        help = "Field names carrying the span id in logs streams, the first one found in the stream schema is used to correlate logs with spans"
+    )]
+    pub traces_logs_span_id_fields: String,
     #[env_config(
         name = "ZO_RESULT_CACHE_ENABLED",
         default = false,
@@ -820,6 +1106,12 @@ pub struct Common {
         help = "Discard data of last n seconds from cached results"
     )]
     pub result_cache_discard_duration: i64,
+    #[env_config(
+        name = "ZO_RESULT_CACHE_TTL",
+        default = 0,
+        help = "Seconds a cached query result is used for, 0 keeps it until it's evicted"
+    )]
+    pub result_cache_ttl: i64,
     #[env_config(name = "ZO_SWAGGER_ENABLED", default = true)]
     pub swagger_enabled: bool,
 }
@@ -846,6 +1138,18 @@ pub struct Limit {
     pub max_file_size_in_memory: usize,
     #[env_config(name = "ZO_UDSCHEMA_MAX_FIELDS", default = 0)]
     pub udschema_max_fields: usize,
+    #[env_config(
+        name = "ZO_PARTITION_KEYS_MAX",
+        default = 3,
+        help = "Maximum number of enabled partition keys of a stream, each key adds a level to the storage path"
+    )]
+    pub partition_keys_max: usize,
+    #[env_config(
+        name = "ZO_PARTITION_KEY_MAX_CARDINALITY",
+        default = 1000,
+        help = "Maximum distinct values in the last 24 hours of a field to be a value partition key, use a hash partition for a field with more values"
+    )]
+    pub partition_key_max_cardinality: usize,
     // MB, total data size in memory, default is 50% of system memory
     #[env_config(name = "ZO_MEM_TABLE_MAX_SIZE", default = 0)]
     pub mem_table_max_size: usize,
@@ -859,6 +1163,12 @@ pub struct Limit {
     pub mem_persist_interval: u64,
     #[env_config(name = "ZO_WAL_WRITE_BUFFER_SIZE", default = 16384)] // 16 KB
     pub wal_write_buffer_size: usize,
+    #[env_config(
+        name = "ZO_WAL_MAX_OPEN_WRITERS",
+        default = 0,
+        help = "Maximum wal writers an ingester keeps open, the least recently used idle writer is closed over it, 0 means no limit"
+    )]
+    pub wal_max_open_writers: usize,
     #[env_config(name = "ZO_FILE_PUSH_INTERVAL", default = 10)] // seconds
     pub file_push_interval: u64,
     #[env_config(name = "ZO_FILE_PUSH_LIMIT", default = 0)] // files
@@ -872,20 +1182,140 @@ pub struct Limit {
     pub file_merge_thread_num: usize,
     #[env_config(name = "ZO_MEM_DUMP_THREAD_NUM", default = 0)]
     pub mem_dump_thread_num: usize,
+    #[env_config(
+        name = "ZO_WAL_REPLAY_THREAD_NUM",
+        default = 0,
+        help = "number of workers replaying wal files on startup, default equal to CPU cores"
+    )]
+    pub wal_replay_thread_num: usize,
     #[env_config(name = "ZO_QUERY_THREAD_NUM", default = 0)]
     pub query_thread_num: usize,
     #[env_config(name = "ZO_QUERY_TIMEOUT", default = 600)]
     pub query_timeout: u64,
+    #[env_config(
+        name = "ZO_SEARCH_REGION_TIMEOUT",
+        default = 0,
+        help = "Seconds to wait for each region of a super cluster search, a slower region is left out of a partial result, 0 means wait until the query timeout"
+    )]
+    pub search_region_timeout: u64,
+    #[env_config(
+        name = "ZO_SEARCH_WORK_GROUP_SHORT_MAX_CONCURRENCY",
+        default = 0,
+        help = "Maximum number of short queries a node runs at once, the others wait in the queue, default equal to CPU cores * 2"
+    )]
+    pub search_work_group_short_max_concurrency: usize,
+    #[env_config(
+        name = "ZO_SEARCH_WORK_GROUP_LONG_MAX_CONCURRENCY",
+        default = 0,
+        help = "Maximum number of long queries a node runs at once, the others wait in the queue, default equal to CPU cores"
+    )]
+    pub search_work_group_long_max_concurrency: usize,
+    #[env_config(
+        name = "ZO_SEARCH_WORK_GROUP_LONG_QUERY_SIZE",
+        default = 1024,
+        help = "MB of data a query scans from which it runs in the long work group"
+    )]
+    pub search_work_group_long_query_size: usize,
+    #[env_config(
+        name = "ZO_SEARCH_WORK_GROUP_MAX_QUEUE_TIME",
+        default = 60,
+        help = "Seconds a query waits for a slot of its work group before it fails"
+    )]
+    pub search_work_group_max_queue_time: u64,
     #[env_config(name = "ZO_QUERY_DEFAULT_LIMIT", default = 1000)]
     pub query_default_limit: i64,
+    #[env_config(
+        name = "ZO_STREAM_SAMPLE_MAX_SIZE",
+        default = 100,
+        help = "Maximum number of records the sample of a stream returns"
+    )]
+    pub stream_sample_max_size: usize,
+    #[env_config(
+        name = "ZO_QUERY_TIMINGS_BUFFER_SIZE",
+        default = 1000,
+        help = "Number of recent searches whose timings are kept in memory for lookup by trace id, 0 disables it"
+    )]
+    pub query_timings_buffer_size: usize,
+    #[env_config(
+        name = "ZO_QUERY_MAX_ROWS",
+        default = 0,
+        help = "Maximum rows returned by a query, 0 means no limit, can be overridden per org"
+    )]
+    pub query_max_rows: i64,
+    #[env_config(
+        name = "ZO_QUERY_MAX_ROWS_POLICY",
+        default = "clamp",
+        help = "What to do when a query asks for more than the max rows: clamp or reject"
+    )]
+    pub query_max_rows_policy: String,
+    #[env_config(
+        name = "ZO_QUERY_RANGE_POLICY",
+        default = "clamp",
+        help = "What to do when a query spans more than the max query range of a stream: clamp or reject, can be overridden per org and per stream"
+    )]
+    pub query_range_policy: String,
+    #[env_config(
+        name = "ZO_QUERY_MAX_SCAN_SIZE",
+        default = 0,
+        help = "Maximum size in MB a query can scan, estimated from the file list before the query runs, 0 means no limit, can be overridden per org"
+    )]
+    pub query_max_scan_size: i64,
+    #[env_config(
+        name = "ZO_QUERY_MAX_GROUPS",
+        default = 0,
+        help = "Maximum groups of a GROUP BY query, 0 means no limit, can be overridden per org"
+    )]
+    pub query_max_groups: i64,
+    #[env_config(
+        name = "ZO_QUERY_MAX_GROUPS_POLICY",
+        default = "reject",
+        help = "What to do when a GROUP BY query has more than the max groups: reject, or truncate to keep the first groups, a query with an ORDER BY is rejected"
+    )]
+    pub query_max_groups_policy: String,
+    #[env_config(
+        name = "ZO_QUERY_RANGE_READ",
+        default = false,
+        help = "The queries with filters read only the parquet footer and the row groups not pruned by the filters from the object storage, instead of downloading the whole files to the cache. The files already cached are still read from the cache."
+    )]
+    pub query_range_read: bool,
     #[env_config(name = "ZO_QUERY_PARTITION_BY_SECS", default = 1)] // seconds
     pub query_partition_by_secs: usize,
+    #[env_config(
+        name = "ZO_QUERY_PARTITION_MAX_NUM",
+        default = 1000,
+        help = "Maximum number of partitions returned by the _search_partition API"
+    )]
+    pub query_partition_max_num: usize,
     #[env_config(name = "ZO_QUERY_GROUP_BASE_SPEED", default = 768)] // MB/s/core
     pub query_group_base_speed: usize,
     #[env_config(name = "ZO_INGEST_ALLOWED_UPTO", default = 5)] // in hours - in past
     pub ingest_allowed_upto: i64,
     #[env_config(name = "ZO_INGEST_FLATTEN_LEVEL", default = 3)] // default flatten level
     pub ingest_flatten_level: u32,
+    #[env_config(
+        name = "ZO_INGEST_IDEMPOTENCY_TTL",
+        default = 3600,
+        help = "Dedupe window of the X-Idempotency-Key header of the ingestion requests in seconds, 0 disables the dedupe"
+    )]
+    pub ingest_idempotency_ttl: i64,
+    #[env_config(
+        name = "ZO_INGEST_OTLP_DEDUPE_TTL",
+        default = 300,
+        help = "Seconds the X-Request-Id of the OTLP/HTTP ingestion requests is remembered to drop the batches sent again, for the orgs which enable otlp_dedupe_enabled"
+    )]
+    pub ingest_otlp_dedupe_ttl: i64,
+    #[env_config(
+        name = "ZO_INGEST_QUARANTINE_MAX_RECORDS",
+        default = 1000,
+        help = "Maximum records a node writes to the quarantine stream of an org per minute, the failed records over it are dropped"
+    )]
+    pub ingest_quarantine_max_records: usize,
+    #[env_config(
+        name = "ZO_INGEST_QUARANTINE_MAX_RECORD_SIZE",
+        default = 64,
+        help = "Maximum size in KB of a quarantined record, a longer record is truncated"
+    )]
+    pub ingest_quarantine_max_record_size: usize,
     #[env_config(name = "ZO_IGNORE_FILE_RETENTION_BY_STREAM", default = false)]
     pub ignore_file_retention_by_stream: bool,
     #[env_config(name = "ZO_LOGS_FILE_RETENTION", default = "hourly")]
@@ -898,6 +1328,18 @@ pub struct Limit {
     pub metrics_leader_push_interval: u64,
     #[env_config(name = "ZO_METRICS_LEADER_ELECTION_INTERVAL", default = 30)]
     pub metrics_leader_election_interval: i64,
+    #[env_config(
+        name = "ZO_METRICS_LABEL_VALUES_LIMIT",
+        default = 1000,
+        help = "Maximum number of label names or values returned by the prometheus labels APIs"
+    )]
+    pub metrics_label_values_limit: usize,
+    #[env_config(
+        name = "ZO_METRICS_DISTINCT_VALUES_LIMIT",
+        default = 1000,
+        help = "Maximum number of distinct label values a prometheus remote write request records, only the labels of ZO_FEATURE_DISTINCT_EXTRA_FIELDS are recorded"
+    )]
+    pub metrics_distinct_values_limit: usize,
     #[env_config(name = "ZO_COLS_PER_RECORD_LIMIT", default = 1000)]
     pub req_cols_per_record_limit: usize,
     #[env_config(name = "ZO_NODE_HEARTBEAT_TTL", default = 30)] // seconds
@@ -928,16 +1370,58 @@ pub struct Limit {
     pub keep_alive: u64,
     #[env_config(name = "ZO_ACTIX_SHUTDOWN_TIMEOUT", default = 10)] // seconds
     pub http_shutdown_timeout: u64,
+    #[env_config(
+        name = "ZO_HTTP_DRAIN_TIMEOUT",
+        default = 0,
+        help = "Seconds to wait for in-flight HTTP requests after the node goes offline and before the HTTP server stops. Default to 60 for querier and 10 for other roles"
+    )] // seconds
+    pub http_drain_timeout: u64,
     #[env_config(name = "ZO_ALERT_SCHEDULE_INTERVAL", default = 10)] // seconds
     pub alert_schedule_interval: i64,
     #[env_config(name = "ZO_ALERT_SCHEDULE_CONCURRENCY", default = 5)]
     pub alert_schedule_concurrency: i64,
     #[env_config(name = "ZO_ALERT_SCHEDULE_TIMEOUT", default = 90)] // seconds
     pub alert_schedule_timeout: i64,
+    #[env_config(
+        name = "ZO_ALERT_RESULT_CACHE_TTL",
+        default = 60,
+        help = "Seconds the alerts with the same query and time window share the search result, 0 disables it"
+    )] // seconds
+    pub alert_result_cache_ttl: i64,
+    #[env_config(
+        name = "ZO_ALERT_EVALUATION_TIMEOUT",
+        default = 60,
+        help = "Seconds a scheduled alert evaluation can run before it's aborted, it also caps the evaluation timeout of each alert, 0 means no limit"
+    )] // seconds
+    pub alert_evaluation_timeout: i64,
+    #[env_config(
+        name = "ZO_ALERT_REMOTE_EVALUATION_ENABLED",
+        default = false,
+        help = "Sends the scheduled alert evaluations to the background queriers in batches instead of running them on the alert manager"
+    )]
+    pub alert_remote_evaluation_enabled: bool,
+    #[env_config(
+        name = "ZO_ALERT_EVALUATION_BATCH_SIZE",
+        default = 20,
+        help = "Maximum number of alert evaluations sent to a querier in one batch"
+    )]
+    pub alert_evaluation_batch_size: usize,
     #[env_config(name = "ZO_REPORT_SCHEDULE_TIMEOUT", default = 300)] // seconds
     pub report_schedule_timeout: i64,
     #[env_config(name = "ZO_DERIVED_STREAM_SCHEDULE_INTERVAL", default = 300)] // seconds
     pub derived_stream_schedule_interval: i64,
+    #[env_config(
+        name = "ZO_SCHEDULED_EXPORT_MAX_RECORDS",
+        default = 100000,
+        help = "Maximum number of records a scheduled export writes to a file"
+    )]
+    pub scheduled_export_max_records: i64,
+    #[env_config(
+        name = "ZO_SCHEDULED_EXPORT_RUN_HISTORY",
+        default = 100,
+        help = "Number of runs kept in the history of a scheduled export"
+    )]
+    pub scheduled_export_run_history: usize,
     #[env_config(name = "ZO_SCHEDULER_MAX_RETRIES", default = 3)]
     pub scheduler_max_retries: i32,
     #[env_config(name = "ZO_SCHEDULER_PAUSE_ALERT_AFTER_RETRIES", default = false)]
@@ -948,6 +1432,30 @@ pub struct Limit {
         help = "Integer value representing the delay in percentage of the alert frequency that will be included in alert evaluation timerange. Default is 20. This can be changed in runtime."
     )]
     pub alert_considerable_delay: i32,
+    #[env_config(
+        name = "ZO_ALERT_BACKFILL_MAX_STEPS",
+        default = 1000,
+        help = "Maximum number of evaluations a single alert backfill request can run"
+    )]
+    pub alert_backfill_max_steps: i64,
+    #[env_config(
+        name = "ZO_ALERT_FLAPPING_THRESHOLD",
+        default = 0,
+        help = "Number of firing/resolved transitions of a scheduled alert within ZO_ALERT_FLAPPING_WINDOW after which it's flapping and its notifications are suppressed, 0 disables the detection"
+    )]
+    pub alert_flapping_threshold: usize,
+    #[env_config(
+        name = "ZO_ALERT_FLAPPING_WINDOW",
+        default = 60,
+        help = "Minutes the transitions of an alert are counted in for the flapping detection"
+    )] // minutes
+    pub alert_flapping_window: i64,
+    #[env_config(
+        name = "ZO_ALERT_FLAPPING_STABILIZATION_WINDOW",
+        default = 30,
+        help = "Minutes a flapping alert must go without a transition before its notifications are sent again"
+    )] // minutes
+    pub alert_flapping_stabilization_window: i64,
     #[env_config(name = "ZO_SCHEDULER_CLEAN_INTERVAL", default = 30)] // seconds
     pub scheduler_clean_interval: u64,
     #[env_config(name = "ZO_SCHEDULER_WATCH_INTERVAL", default = 30)] // seconds
@@ -1056,8 +1564,18 @@ pub struct Compact {
     pub step_secs: i64,
     #[env_config(name = "ZO_COMPACT_SYNC_TO_DB_INTERVAL", default = 600)] // seconds
     pub sync_to_db_interval: u64,
-    #[env_config(name = "ZO_COMPACT_MAX_FILE_SIZE", default = 512)] // MB
+    #[env_config(
+        name = "ZO_COMPACT_MAX_FILE_SIZE",
+        default = 512,
+        help = "Target size of the merged files, small files are merged until this size"
+    )] // MB
     pub max_file_size: usize,
+    #[env_config(
+        name = "ZO_COMPACT_MIN_FILES_TO_MERGE",
+        default = 2,
+        help = "Minimum number of small files in a group to merge them into a file"
+    )]
+    pub min_files_to_merge: usize,
     #[env_config(name = "ZO_COMPACT_DATA_RETENTION_DAYS", default = 3650)] // days
     pub data_retention_days: i64,
     #[env_config(name = "ZO_COMPACT_DELETE_FILES_DELAY_HOURS", default = 2)] // hours
@@ -1066,6 +1584,12 @@ pub struct Compact {
     pub blocked_orgs: String,
     #[env_config(name = "ZO_COMPACT_DATA_RETENTION_HISTORY", default = false)]
     pub data_retention_history: bool,
+    #[env_config(
+        name = "ZO_COMPACT_RETENTION_INTERVAL",
+        default = 0,
+        help = "Interval of the data retention sweep, default is the compact interval"
+    )] // seconds
+    pub retention_interval: u64,
     #[env_config(
         name = "ZO_COMPACT_BATCH_SIZE",
         default = 500,
@@ -1086,6 +1610,24 @@ pub struct Compact {
     pub job_clean_wait_time: i64,
     #[env_config(name = "ZO_COMPACT_PENDING_JOBS_METRIC_INTERVAL", default = 300)] // seconds
     pub pending_jobs_metric_interval: u64,
+    #[env_config(
+        name = "ZO_COMPACT_METRICS_ROLLUP_STREAMS",
+        default = "",
+        help = "Metrics streams to generate rollup streams for, use comma to split, * for all streams. Empty disables the rollup"
+    )]
+    pub metrics_rollup_streams: String,
+    #[env_config(
+        name = "ZO_COMPACT_METRICS_ROLLUP_RESOLUTIONS",
+        default = "5m,1h",
+        help = "Resolutions of the metrics rollup streams, use comma to split, eg: 5m,1h"
+    )]
+    pub metrics_rollup_resolutions: String,
+    #[env_config(
+        name = "ZO_COMPACT_METRICS_ROLLUP_DELAY",
+        default = 600, // 10 minutes
+        help = "Wait time for late samples before a rollup window is generated, in seconds"
+    )]
+    pub metrics_rollup_delay: i64,
 }
 
 #[derive(EnvConfig)]
@@ -1121,6 +1663,21 @@ pub struct MemoryCache {
     pub datafusion_max_size: usize,
     #[env_config(name = "ZO_MEMORY_CACHE_DATAFUSION_MEMORY_POOL", default = "")]
     pub datafusion_memory_pool: String,
+    #[env_config(
+        name = "ZO_MEMORY_CACHE_DATAFUSION_QUERY_CONCURRENCY",
+        default = 1,
+        help = "The memory limit of a query is the datafusion max size divided by this value"
+    )]
+    pub datafusion_query_concurrency: usize,
+    #[env_config(
+        name = "ZO_MEMORY_CACHE_DATAFUSION_SPILL_ENABLED",
+        default = true,
+        help = "Spill the sorts and aggregations of a query exceeding its memory limit to disk"
+    )]
+    pub datafusion_spill_enabled: bool,
+    // default is {data_dir}spill/, the spill files of a query are removed when it is done
+    #[env_config(name = "ZO_MEMORY_CACHE_DATAFUSION_SPILL_DIR", default = "")]
+    pub datafusion_spill_dir: String,
 }
 
 #[derive(EnvConfig)]
@@ -1282,6 +1839,12 @@ pub struct S3 {
     pub feature_http1_only: bool,
     #[env_config(name = "ZO_S3_FEATURE_HTTP2_ONLY", default = false)]
     pub feature_http2_only: bool,
+    #[env_config(
+        name = "ZO_S3_FEATURE_RANGE_READ",
+        default = true,
+        help = "The object storage supports HTTP range requests, when false the reads of a part of an object fetch the whole object"
+    )]
+    pub feature_range_read: bool,
     #[env_config(name = "ZO_S3_ALLOW_INVALID_CERTIFICATES", default = false)]
     pub allow_invalid_certificates: bool,
     #[env_config(name = "ZO_S3_SYNC_TO_CACHE_INTERVAL", default = 600)] // seconds
@@ -1290,6 +1853,30 @@ pub struct S3 {
     pub max_retries: usize,
     #[env_config(name = "ZO_S3_MAX_IDLE_PER_HOST", default = 0)]
     pub max_idle_per_host: usize,
+    #[env_config(
+        name = "ZO_S3_RETRY_ATTEMPTS",
+        default = 3,
+        help = "Times a get or put of the object storage failed with a transient error (5xx, throttling, network) is retried, 0 disables the retry"
+    )]
+    pub retry_attempts: usize,
+    #[env_config(
+        name = "ZO_S3_RETRY_BASE_DELAY",
+        default = 100,
+        help = "Milliseconds before the first retry of the object storage, doubled for each retry, with a random jitter"
+    )]
+    pub retry_base_delay: u64,
+    #[env_config(
+        name = "ZO_S3_RETRY_MAX_DELAY",
+        default = 5000,
+        help = "Maximum milliseconds between two retries of the object storage"
+    )]
+    pub retry_max_delay: u64,
+    #[env_config(
+        name = "ZO_S3_RETRY_TIMEOUT",
+        default = 180,
+        help = "Maximum seconds a get or put of the object storage takes with its retries, it fails after it"
+    )]
+    pub retry_timeout: u64,
 }
 
 #[derive(Debug, EnvConfig)]
@@ -1302,6 +1889,44 @@ pub struct Sns {
     pub operation_timeout: u64,
 }
 
+#[derive(Debug, EnvConfig)]
+pub struct Secret {
+    #[env_config(
+        name = "ZO_SECRET_CACHE_TTL",
+        default = 60,
+        help = "Seconds a secret resolved from a secret backend is cached"
+    )]
+    pub cache_ttl: u64,
+    #[env_config(name = "ZO_SECRET_TIMEOUT", default = 10)] // seconds
+    pub timeout: u64,
+    #[env_config(
+        name = "ZO_SECRET_ENV_PREFIX",
+        default = "O2_SECRET_",
+        help = "Prefix of the environment variables the env secret backend can read"
+    )]
+    pub env_prefix: String,
+    #[env_config(name = "ZO_SECRET_VAULT_ADDR", default = "")]
+    pub vault_addr: String,
+    #[env_config(name = "ZO_SECRET_VAULT_TOKEN", default = "")]
+    pub vault_token: String,
+    #[env_config(name = "ZO_SECRET_VAULT_NAMESPACE", default = "")]
+    pub vault_namespace: String,
+    #[env_config(
+        name = "ZO_SECRET_VAULT_MOUNT",
+        default = "secret",
+        help = "Mount of the KV v2 engine, the secrets of an org are under <mount>/data/<org_id>/"
+    )]
+    pub vault_mount: String,
+    #[env_config(name = "ZO_SECRET_AWS_ENDPOINT", default = "")]
+    pub aws_endpoint: String,
+    #[env_config(
+        name = "ZO_SECRET_AWS_PREFIX",
+        default = "openobserve/",
+        help = "Prefix of the AWS Secrets Manager secret ids, the secrets of an org are under <prefix><org_id>/"
+    )]
+    pub aws_prefix: String,
+}
+
 #[derive(Debug, EnvConfig)]
 pub struct Prometheus {
     #[env_config(name = "ZO_PROMETHEUS_HA_CLUSTER", default = "cluster")]
@@ -1336,7 +1961,22 @@ pub struct RUM {
 
 pub fn init() -> Config {
     dotenv_override().ok();
-    let mut cfg = Config::init().unwrap();
+    match load(&HashMap::new()) {
+        Ok(cfg) => {
+            *CONFIG_ENVS.write() = std::env::vars().collect();
+            cfg
+        }
+        Err(e) => panic!("{e}"),
+    }
+}
+
+// builds the config from the current environment variables, with the reloadable settings of
+// `overrides` on top
+fn load(overrides: &HashMap<String, String>) -> Result<Config, anyhow::Error> {
+    let mut cfg = Config::init().map_err(|e| anyhow::anyhow!("config error: {e:?}"))?;
+    for (k, v) in overrides.iter() {
+        apply_reloadable_env(&mut cfg, k, v)?;
+    }
 
     // set local mode
     if cfg.common.local_mode {
@@ -1375,6 +2015,15 @@ pub fn init() -> Config {
             cfg.limit.query_thread_num = cpu_num * 4;
         }
     }
+    if cfg.limit.search_work_group_short_max_concurrency == 0 {
+        cfg.limit.search_work_group_short_max_concurrency = cpu_num * 2;
+    }
+    if cfg.limit.search_work_group_long_max_concurrency == 0 {
+        cfg.limit.search_work_group_long_max_concurrency = cpu_num;
+    }
+    if cfg.limit.search_work_group_max_queue_time == 0 {
+        cfg.limit.search_work_group_max_queue_time = 60;
+    }
     // HACK for move_file_thread_num equal to CPU core
     if cfg.limit.file_move_thread_num == 0 {
         if cfg.common.local_mode {
@@ -1395,6 +2044,10 @@ pub fn init() -> Config {
     if cfg.limit.mem_dump_thread_num == 0 {
         cfg.limit.mem_dump_thread_num = cpu_num;
     }
+    // HACK for wal_replay_thread_num equal to CPU core
+    if cfg.limit.wal_replay_thread_num == 0 {
+        cfg.limit.wal_replay_thread_num = cpu_num;
+    }
     if cfg.limit.file_push_interval == 0 {
         cfg.limit.file_push_interval = 10;
     }
@@ -1422,40 +2075,212 @@ pub fn init() -> Config {
 
     // check common config
     if let Err(e) = check_common_config(&mut cfg) {
-        panic!("common config error: {e}");
+        return Err(anyhow::anyhow!("common config error: {e}"));
     }
 
     // check data path config
     if let Err(e) = check_path_config(&mut cfg) {
-        panic!("data path config error: {e}");
+        return Err(anyhow::anyhow!("data path config error: {e}"));
     }
 
     // check memory cache
     if let Err(e) = check_memory_config(&mut cfg) {
-        panic!("memory cache config error: {e}");
+        return Err(anyhow::anyhow!("memory cache config error: {e}"));
     }
 
     // check disk cache
     if let Err(e) = check_disk_cache_config(&mut cfg) {
-        panic!("disk cache config error: {e}");
+        return Err(anyhow::anyhow!("disk cache config error: {e}"));
     }
 
     // check etcd config
     if let Err(e) = check_etcd_config(&mut cfg) {
-        panic!("etcd config error: {e}");
+        return Err(anyhow::anyhow!("etcd config error: {e}"));
     }
 
     // check s3 config
     if let Err(e) = check_s3_config(&mut cfg) {
-        panic!("s3 config error: {e}");
+        return Err(anyhow::anyhow!("s3 config error: {e}"));
     }
 
     // check sns config
     if let Err(e) = check_sns_config(&mut cfg) {
-        panic!("sns config error: {e}");
+        return Err(anyhow::anyhow!("sns config error: {e}"));
+    }
+
+    Ok(cfg)
+}
+
+/// Checks the settings which depend on each other, all the problems are reported at once with
+/// the offending setting names.
+pub fn validate(cfg: &Config) -> Result<(), anyhow::Error> {
+    let mut problems: Vec<(&str, String)> = Vec::new();
+
+    // storage
+    let local_storage = ["disk", "local", "s3"];
+    if cfg.common.local_mode && !local_storage.contains(&cfg.common.local_mode_storage.as_str()) {
+        problems.push((
+            "ZO_LOCAL_MODE_STORAGE",
+            format!(
+                "unknown storage [{}], must be one of {}",
+                cfg.common.local_mode_storage,
+                local_storage.join(", ")
+            ),
+        ));
+    }
+    let remote_storage = !cfg.common.local_mode || cfg.common.local_mode_storage == "s3";
+    if remote_storage && cfg.s3.bucket_name.is_empty() {
+        problems.push((
+            "ZO_S3_BUCKET_NAME",
+            "object storage is used but the bucket name is empty".to_string(),
+        ));
     }
 
-    cfg
+    // cluster
+    if !cfg.common.local_mode {
+        match cfg.common.cluster_coordinator.as_str() {
+            "etcd" if cfg.etcd.addr.is_empty() => problems.push((
+                "ZO_ETCD_ADDR",
+                "the cluster coordinator is etcd but the address is empty".to_string(),
+            )),
+            "nats" if cfg.nats.addr.is_empty() => problems.push((
+                "ZO_NATS_ADDR",
+                "the cluster coordinator is nats but the address is empty".to_string(),
+            )),
+            "etcd" | "nats" => {}
+            v => problems.push((
+                "ZO_CLUSTER_COORDINATOR",
+                format!("unknown cluster coordinator [{v}], must be one of etcd, nats"),
+            )),
+        }
+    }
+    if cfg.common.meta_store == "etcd" && cfg.etcd.addr.is_empty() {
+        problems.push((
+            "ZO_ETCD_ADDR",
+            "the meta store is etcd but the address is empty".to_string(),
+        ));
+    }
+
+    // ports
+    let mut ports = vec![
+        ("ZO_HTTP_PORT", cfg.http.port),
+        ("ZO_GRPC_PORT", cfg.grpc.port),
+    ];
+    if cfg.report_server.enable_report_server {
+        ports.push(("ZO_REPORT_SERVER_HTTP_PORT", cfg.report_server.port));
+    }
+    for (i, (key, port)) in ports.iter().enumerate() {
+        if *port == 0 {
+            problems.push((*key, "the port can't be 0".to_string()));
+        } else if let Some((other, _)) = ports[..i].iter().find(|(_, p)| p == port) {
+            problems.push((*key, format!("the port {port} is already used by {other}")));
+        }
+    }
+
+    // query
+    if !["clamp", "reject"].contains(&cfg.limit.query_max_rows_policy.as_str()) {
+        problems.push((
+            "ZO_QUERY_MAX_ROWS_POLICY",
+            format!(
+                "unknown policy [{}], must be one of clamp, reject",
+                cfg.limit.query_max_rows_policy
+            ),
+        ));
+    }
+    if !["clamp", "reject"].contains(&cfg.limit.query_range_policy.as_str()) {
+        problems.push((
+            "ZO_QUERY_RANGE_POLICY",
+            format!(
+                "unknown policy [{}], must be one of clamp, reject",
+                cfg.limit.query_range_policy
+            ),
+        ));
+    }
+    if !["reject", "truncate"].contains(&cfg.limit.query_max_groups_policy.as_str()) {
+        problems.push((
+            "ZO_QUERY_MAX_GROUPS_POLICY",
+            format!(
+                "unknown policy [{}], must be one of reject, truncate",
+                cfg.limit.query_max_groups_policy
+            ),
+        ));
+    }
+
+    // grpc tls
+    if cfg.grpc.tls_enabled {
+        for (key, path) in [
+            ("ZO_GRPC_TLS_CERT_PATH", &cfg.grpc.tls_cert_path),
+            ("ZO_GRPC_TLS_KEY_PATH", &cfg.grpc.tls_key_path),
+        ] {
+            if path.is_empty() {
+                problems.push((key, "grpc tls is enabled but the path is empty".to_string()));
+            } else if !std::path::Path::new(path).is_file() {
+                problems.push((key, format!("the file [{path}] doesn't exist")));
+            }
+        }
+        let path = &cfg.grpc.tls_client_ca_path;
+        if path.is_empty() {
+            // the nodes verify the certificates of each other against this CA
+            if !cfg.common.local_mode {
+                problems.push((
+                    "ZO_GRPC_TLS_CLIENT_CA_PATH",
+                    "grpc tls is enabled in cluster mode but the CA path is empty".to_string(),
+                ));
+            }
+        } else if !std::path::Path::new(path).is_file() {
+            problems.push((
+                "ZO_GRPC_TLS_CLIENT_CA_PATH",
+                format!("the file [{path}] doesn't exist"),
+            ));
+        }
+    }
+
+    // smtp
+    if cfg.smtp.smtp_enabled && cfg.smtp.smtp_host.is_empty() {
+        problems.push((
+            "ZO_SMTP_HOST",
+            "smtp is enabled but the host is empty".to_string(),
+        ));
+    }
+
+    // workers and intervals
+    for (key, value) in [
+        (
+            "ZO_MEM_PERSIST_INTERVAL",
+            cfg.limit.mem_persist_interval as i64,
+        ),
+        (
+            "ZO_ALERT_SCHEDULE_INTERVAL",
+            cfg.limit.alert_schedule_interval,
+        ),
+        (
+            "ZO_ALERT_SCHEDULE_CONCURRENCY",
+            cfg.limit.alert_schedule_concurrency,
+        ),
+        ("ZO_HTTP_WORKER_NUM", cfg.limit.http_worker_num as i64),
+        (
+            "ZO_JOB_RUNTIME_WORKER_NUM",
+            cfg.limit.job_runtime_worker_num as i64,
+        ),
+        (
+            "ZO_GRPC_RUNTIME_WORKER_NUM",
+            cfg.limit.grpc_runtime_worker_num as i64,
+        ),
+    ] {
+        if value <= 0 {
+            problems.push((key, format!("must be greater than 0, got {value}")));
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let problems = problems
+        .iter()
+        .map(|(key, problem)| format!("  - {key}: {problem}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(anyhow::anyhow!("invalid configuration:\n{problems}"))
 }
 
 fn check_common_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
@@ -1506,6 +2331,17 @@ fn check_common_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
         cfg.common.tracing_enabled = false;
     }
 
+    // HACK for http drain timeout, querier needs more time to finish searches
+    if cfg.limit.http_drain_timeout == 0 {
+        if local_node_role.contains(&cluster::Role::All)
+            || local_node_role.contains(&cluster::Role::Querier)
+        {
+            cfg.limit.http_drain_timeout = 60;
+        } else {
+            cfg.limit.http_drain_timeout = 10;
+        }
+    }
+
     // format local_mode_storage
     cfg.common.local_mode_storage = cfg.common.local_mode_storage.to_lowercase();
 
@@ -1537,9 +2373,15 @@ fn check_common_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
 
     // check compact_max_file_size to MB
     cfg.compact.max_file_size *= 1024 * 1024;
+    if cfg.compact.min_files_to_merge < 2 {
+        cfg.compact.min_files_to_merge = 2;
+    }
     if cfg.compact.interval == 0 {
         cfg.compact.interval = 60;
     }
+    if cfg.compact.retention_interval == 0 {
+        cfg.compact.retention_interval = cfg.compact.interval + 1;
+    }
     if cfg.compact.pending_jobs_metric_interval == 0 {
         cfg.compact.pending_jobs_metric_interval = 300;
     }
@@ -1562,6 +2404,23 @@ fn check_common_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     if cfg.compact.batch_size < 1 {
         cfg.compact.batch_size = 100;
     }
+    for resolution in cfg.compact.metrics_rollup_resolutions.split(',') {
+        let resolution = resolution.trim();
+        if resolution.is_empty() {
+            continue;
+        }
+        match crate::utils::time::parse_milliseconds(resolution) {
+            Ok(v) if v >= 60_000 && v % 1000 == 0 => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid metrics rollup resolution [{resolution}], must be at least 1m."
+                ));
+            }
+        }
+    }
+    if cfg.compact.metrics_rollup_delay < 0 {
+        cfg.compact.metrics_rollup_delay = 600;
+    }
 
     // If the default scrape interval is less than 5s, raise an error
     if cfg.common.default_scrape_interval < 5 {
@@ -1589,6 +2448,44 @@ fn check_common_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
         cfg.common.inverted_index_search_format = cfg.common.inverted_index_store_format.clone();
     }
 
+    // check route strategy
+    cfg.route.strategy = cfg.route.strategy.to_lowercase();
+    if !["round_robin", "least_loaded", "random"].contains(&cfg.route.strategy.as_str()) {
+        return Err(anyhow::anyhow!(
+            "ZO_ROUTE_STRATEGY must be one of round_robin, least_loaded, random."
+        ));
+    }
+    if cfg.route.load_report_interval == 0 {
+        cfg.route.load_report_interval = 10;
+    }
+    if cfg.route.hedge_percentile == 0 || cfg.route.hedge_percentile > 100 {
+        return Err(anyhow::anyhow!(
+            "ZO_ROUTE_HEDGE_PERCENTILE must be between 1 and 100."
+        ));
+    }
+
+    // check usage sampling ratio
+    if cfg.common.usage_sampling_ratio <= 0.0 || cfg.common.usage_sampling_ratio > 1.0 {
+        return Err(anyhow::anyhow!(
+            "ZO_USAGE_SAMPLING_RATIO must be greater than 0 and at most 1."
+        ));
+    }
+
+    // check http compression
+    cfg.http.compression_preferred = cfg.http.compression_preferred.trim().to_lowercase();
+    if !["zstd", "gzip"].contains(&cfg.http.compression_preferred.as_str()) {
+        return Err(anyhow::anyhow!(
+            "ZO_HTTP_COMPRESSION_PREFERRED must be zstd or gzip."
+        ));
+    }
+
+    // check traces sampling
+    if !(0.0..=100.0).contains(&cfg.common.traces_sampling_percentage) {
+        return Err(anyhow::anyhow!(
+            "ZO_TRACES_SAMPLING_PERCENTAGE must be between 0 and 100."
+        ));
+    }
+
     Ok(())
 }
 
@@ -1637,6 +2534,17 @@ fn check_path_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     if !cfg.common.mmdb_data_dir.ends_with('/') {
         cfg.common.mmdb_data_dir = format!("{}/", cfg.common.mmdb_data_dir);
     }
+    if cfg.common.geoip_enrichment_db_path.is_empty() {
+        cfg.common.geoip_enrichment_db_path =
+            format!("{}{MMDB_CITY_FILE_NAME}", cfg.common.mmdb_data_dir);
+    }
+    if cfg.memory_cache.datafusion_spill_dir.is_empty() {
+        cfg.memory_cache.datafusion_spill_dir = format!("{}spill/", cfg.common.data_dir);
+    }
+    if !cfg.memory_cache.datafusion_spill_dir.ends_with('/') {
+        cfg.memory_cache.datafusion_spill_dir =
+            format!("{}/", cfg.memory_cache.datafusion_spill_dir);
+    }
     Ok(())
 }
 
@@ -1713,6 +2621,9 @@ fn check_memory_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     } else {
         cfg.memory_cache.datafusion_max_size *= 1024 * 1024;
     }
+    if cfg.memory_cache.datafusion_query_concurrency == 0 {
+        cfg.memory_cache.datafusion_query_concurrency = 1;
+    }
 
     if cfg.memory_cache.bucket_num == 0 {
         cfg.memory_cache.bucket_num = 1;
@@ -1749,6 +2660,9 @@ fn check_memory_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     if cfg.limit.query_partition_by_secs == 0 {
         cfg.limit.query_partition_by_secs = 30;
     }
+    if cfg.limit.query_partition_max_num == 0 {
+        cfg.limit.query_partition_max_num = 1000;
+    }
     if cfg.limit.query_default_limit == 0 {
         cfg.limit.query_default_limit = 1000;
     }
@@ -2005,4 +2919,133 @@ mod tests {
         assert_eq!(cfg.common.data_dir, "/abc/".to_string());
         assert_eq!(cfg.common.base_uri, "/abc".to_string());
     }
+
+    #[test]
+    fn test_reload_config() {
+        let max_records = get_config().limit.ingest_quarantine_max_records;
+        let old_env = CONFIG_ENVS
+            .read()
+            .get("ZO_INGEST_QUARANTINE_MAX_RECORDS")
+            .cloned()
+            .unwrap_or_else(|| max_records.to_string());
+
+        let envs = |v: String| vec![("ZO_INGEST_QUARANTINE_MAX_RECORDS".to_string(), v)];
+        let changed = reload_config_from(envs((max_records + 1).to_string())).unwrap();
+        assert_eq!(
+            changed,
+            vec!["ZO_INGEST_QUARANTINE_MAX_RECORDS".to_string()]
+        );
+        assert_eq!(
+            get_config().limit.ingest_quarantine_max_records,
+            max_records + 1
+        );
+        // the process environment isn't changed
+        assert_ne!(
+            std::env::var("ZO_INGEST_QUARANTINE_MAX_RECORDS").ok(),
+            Some((max_records + 1).to_string())
+        );
+        assert!(
+            reload_config_from(envs((max_records + 1).to_string()))
+                .unwrap()
+                .is_empty()
+        );
+
+        // structural settings are rejected with the whole reload
+        let http_port = get_config().http.port;
+        let err = reload_config_from(vec![
+            ("ZO_HTTP_PORT".to_string(), (http_port + 1).to_string()),
+            (
+                "ZO_INGEST_QUARANTINE_MAX_RECORDS".to_string(),
+                "1".to_string(),
+            ),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("ZO_HTTP_PORT"));
+        assert_eq!(get_config().http.port, http_port);
+        assert_eq!(
+            get_config().limit.ingest_quarantine_max_records,
+            max_records + 1
+        );
+
+        // invalid values are rejected
+        assert!(reload_config_from(envs("many".to_string())).is_err());
+
+        reload_config_from(envs(old_env)).unwrap();
+        assert_eq!(
+            get_config().limit.ingest_quarantine_max_records,
+            max_records
+        );
+    }
+
+    #[test]
+    fn test_reloadable_envs() {
+        let mut cfg = Config::init().unwrap();
+        for key in RELOADABLE_ENVS {
+            let value = if key.ends_with("SCHEMA_EVOLUTION") || key.ends_with("VALIDATION") {
+                "true"
+            } else {
+                "1"
+            };
+            apply_reloadable_env(&mut cfg, key, value).unwrap();
+        }
+        assert!(apply_reloadable_env(&mut cfg, "ZO_HTTP_PORT", "1").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut cfg = Config::init().unwrap();
+        cfg.common.local_mode = true;
+        cfg.common.local_mode_storage = "disk".to_string();
+        cfg.common.meta_store = "sqlite".to_string();
+        cfg.http.port = 5080;
+        cfg.grpc.port = 5081;
+        cfg.report_server.enable_report_server = false;
+        cfg.smtp.smtp_enabled = false;
+        cfg.limit.mem_persist_interval = 5;
+        cfg.limit.alert_schedule_interval = 10;
+        cfg.limit.alert_schedule_concurrency = 5;
+        cfg.limit.http_worker_num = 2;
+        cfg.limit.job_runtime_worker_num = 2;
+        cfg.limit.grpc_runtime_worker_num = 2;
+        cfg.limit.query_max_rows_policy = "clamp".to_string();
+        cfg.limit.query_range_policy = "reject".to_string();
+        cfg.limit.query_max_groups_policy = "truncate".to_string();
+        cfg.grpc.tls_enabled = false;
+        assert!(validate(&cfg).is_ok());
+
+        // every problem is reported
+        cfg.common.local_mode_storage = "s3".to_string();
+        cfg.s3.bucket_name = "".to_string();
+        cfg.grpc.port = 5080;
+        cfg.smtp.smtp_enabled = true;
+        cfg.smtp.smtp_host = "".to_string();
+        cfg.limit.mem_persist_interval = 0;
+        cfg.limit.query_max_rows_policy = "drop".to_string();
+        cfg.limit.query_range_policy = "drop".to_string();
+        cfg.limit.query_max_groups_policy = "drop".to_string();
+        cfg.grpc.tls_enabled = true;
+        cfg.grpc.tls_cert_path = "".to_string();
+        cfg.grpc.tls_key_path = "/not/exist/server.key".to_string();
+        cfg.grpc.tls_client_ca_path = "".to_string();
+        let err = validate(&cfg).unwrap_err().to_string();
+        assert!(err.contains("ZO_QUERY_MAX_ROWS_POLICY"));
+        assert!(err.contains("ZO_QUERY_RANGE_POLICY"));
+        assert!(err.contains("ZO_QUERY_MAX_GROUPS_POLICY"));
+        assert!(err.contains("ZO_S3_BUCKET_NAME"));
+        assert!(err.contains("ZO_GRPC_PORT: the port 5080 is already used by ZO_HTTP_PORT"));
+        assert!(err.contains("ZO_SMTP_HOST"));
+        assert!(err.contains("ZO_MEM_PERSIST_INTERVAL"));
+        assert!(err.contains("ZO_GRPC_TLS_CERT_PATH: grpc tls is enabled but the path is empty"));
+        assert!(
+            err.contains("ZO_GRPC_TLS_KEY_PATH: the file [/not/exist/server.key] doesn't exist")
+        );
+        assert!(!err.contains("ZO_GRPC_TLS_CLIENT_CA_PATH"));
+        assert!(!err.contains("ZO_HTTP_PORT:"));
+
+        cfg.common.local_mode = false;
+        cfg.common.cluster_coordinator = "zookeeper".to_string();
+        let err = validate(&cfg).unwrap_err().to_string();
+        assert!(err.contains("ZO_CLUSTER_COORDINATOR"));
+        assert!(err.contains("ZO_GRPC_TLS_CLIENT_CA_PATH"));
+    }
 }
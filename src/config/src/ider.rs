@@ -33,10 +33,30 @@ pub fn init() {
 }
 
 /// Generate a distributed unique id with snowflake.
+///
+/// The id is an i64 formatted as a decimal string, the bits from high to low are:
+/// - 42 bits: milliseconds since the UNIX epoch
+/// - 10 bits: machine id
+/// - 12 bits: sequence in the millisecond
+///
+/// The ids are sortable by time, but the time is only refreshed every 4096 ids, so it can be
+/// earlier than the real generation time and is only good for a coarse time filter.
 pub fn generate() -> String {
     IDER.lock().generate().to_string()
 }
 
+/// Decode an id generated by [generate] into the embedded timestamp in microseconds and the
+/// sequence, returns None if it isn't a valid id.
+pub fn decode(id: &str) -> Option<(i64, u64)> {
+    let id = id.parse::<i64>().ok()?;
+    if id < 0 {
+        return None;
+    }
+    let millis = id >> 22;
+    let seq = (id & 0xfff) as u64;
+    Some((millis * 1000, seq))
+}
+
 /// Generate a unique id like uuid.
 pub fn uuid() -> String {
     Ksuid::new(None, None).to_string()
@@ -207,3 +227,29 @@ fn biding_time_conditions(last_time_millis: i64, epoch: SystemTime) -> i64 {
         spin_loop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let start = get_time_millis(UNIX_EPOCH);
+        let mut generator = SnowflakeIdGenerator::new(1);
+        let id = generator.real_time_generate();
+        let (ts, seq) = decode(&id.to_string()).unwrap();
+        assert!(ts >= start * 1000 && ts <= get_time_millis(UNIX_EPOCH) * 1000);
+        assert_eq!(seq, (id & 0xfff) as u64);
+
+        let (ts, _) = decode(&generate()).unwrap();
+        assert!(ts <= get_time_millis(UNIX_EPOCH) * 1000);
+        assert!(ts > 0);
+
+        assert_eq!(
+            decode(&(1000_i64 << 22 | 1 << 12 | 7).to_string()),
+            Some((1_000_000, 7))
+        );
+        assert_eq!(decode("abc"), None);
+        assert_eq!(decode("-1"), None);
+    }
+}
@@ -22,6 +22,11 @@ use crate::{get_config, get_instance_id, meta::search::SearchEventType};
 pub trait NodeInfo: Debug + Send + Sync {
     fn get_grpc_addr(&self) -> String;
     fn get_auth_token(&self) -> String;
+    /// The name reported in the timings of a search, the region or the cluster of a super
+    /// cluster node
+    fn get_name(&self) -> String {
+        self.get_grpc_addr()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -40,6 +45,32 @@ pub struct Node {
     pub scheduled: bool,
     #[serde(default)]
     pub broadcasted: bool,
+    #[serde(default)]
+    pub load: NodeLoad,
+}
+
+/// Load reported by a node through the cluster coordinator
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeLoad {
+    /// number of queries running on the node
+    pub running_queries: u64,
+    /// when the load was reported, in microseconds
+    pub updated_at: i64,
+}
+
+/// A node excluded from scheduling cluster-wide
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeEviction {
+    pub uuid: String,
+    pub name: String,
+    /// when the node was evicted, in microseconds
+    pub evicted_at: i64,
+}
+
+impl NodeLoad {
+    pub fn is_stale(&self, now: i64) -> bool {
+        now - self.updated_at > get_config().route.load_stale_timeout * 1_000_000
+    }
 }
 
 impl Node {
@@ -56,6 +87,7 @@ impl Node {
             status: NodeStatus::Prepare,
             scheduled: false,
             broadcasted: false,
+            load: NodeLoad::default(),
         }
     }
     pub fn is_single_node(&self) -> bool {
@@ -103,6 +135,10 @@ impl NodeInfo for Node {
     fn get_grpc_addr(&self) -> String {
         self.grpc_addr.clone()
     }
+
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
 }
 
 pub trait IntoArcVec {
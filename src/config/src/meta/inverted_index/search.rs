@@ -70,12 +70,13 @@ impl<'b> PrefixSearch<'b> {
 
     fn filter(&mut self) {
         self.terms.retain(|term| {
-            // Check if the term is within the min and max length
-            term.len() <= self.meta.max_len
-                && term.len() >= self.meta.min_len
-                // Check if the term is within the min and max values
-                && self.meta.min_val.as_slice() <= term.as_bytes()
-                && term.as_bytes() <= self.meta.max_val.as_slice()
+            // Check if the term is within the max length, a prefix shorter than the min length
+            // still matches the longer terms
+            let len = term.len();
+            len <= self.meta.max_len
+                // Check if the term is within the prefixes of the min and max values
+                && self.meta.min_val[..len.min(self.meta.min_val.len())] <= *term.as_bytes()
+                && *term.as_bytes() <= self.meta.max_val[..len.min(self.meta.max_val.len())]
         });
     }
 }
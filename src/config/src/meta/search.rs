@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use proto::cluster_rpc;
 use serde::{Deserialize, Deserializer, Serialize};
@@ -21,6 +21,7 @@ use utoipa::ToSchema;
 
 use crate::{
     ider,
+    meta::sql::OrderBy,
     utils::{base64, json},
 };
 
@@ -93,12 +94,14 @@ pub struct Query {
     pub from: i64,
     #[serde(default = "default_size")]
     pub size: i64,
+    /// microseconds, the search also takes a time relative to the request like `now-15m`
     #[serde(default)]
     pub start_time: i64,
+    /// microseconds, the search also takes a time relative to the request like `now`
     #[serde(default)]
     pub end_time: i64,
     #[serde(default)]
-    pub sort_by: Option<String>,
+    pub sort_by: Option<SortBy>,
     #[serde(default)]
     pub quick_mode: bool,
     #[serde(default)]
@@ -111,6 +114,93 @@ pub struct Query {
     pub query_fn: Option<String>,
     #[serde(default)]
     pub skip_wal: bool,
+    /// Only search the data which isn't persisted yet, in the memtables and the WAL of the
+    /// ingesters, so the results are limited to the last `ZO_MAX_FILE_RETENTION_TIME` or so
+    #[serde(default)]
+    pub wal_only: bool,
+    /// Only return these fields and `_timestamp`, a `SELECT *` is rewritten to select them.
+    /// A field which isn't in the streams is an error
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+/// The sort of the results, either a string like `"severity DESC, _timestamp DESC"` or a list
+/// of keys, the results are sorted by the keys in order
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum SortBy {
+    Single(String),
+    Keys(Vec<SortKey>),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SortKey {
+    pub field: String,
+    /// `asc` or `desc`, defaults to `desc`
+    #[serde(default)]
+    #[schema(value_type = String)]
+    pub direction: OrderBy,
+}
+
+impl SortBy {
+    /// Returns the sort keys, the string form is a comma separated list of a field followed by
+    /// an optional direction
+    pub fn keys(&self) -> Result<Vec<SortKey>, String> {
+        let keys = match self {
+            SortBy::Keys(keys) => keys.clone(),
+            SortBy::Single(s) => s
+                .split(',')
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| {
+                    let mut parts = v.split_whitespace();
+                    let field = parts.next().unwrap_or_default().to_string();
+                    let direction = match parts.next().map(|d| d.to_lowercase()).as_deref() {
+                        None => OrderBy::default(),
+                        Some("asc") => OrderBy::Asc,
+                        Some("desc") => OrderBy::Desc,
+                        Some(d) => {
+                            return Err(format!("invalid sort direction [{d}] of [{field}]"));
+                        }
+                    };
+                    if parts.next().is_some() {
+                        return Err(format!("invalid sort key [{}]", v.trim()));
+                    }
+                    Ok(SortKey { field, direction })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        if let Some(key) = keys.iter().find(|k| k.field.is_empty()) {
+            return Err(format!(
+                "invalid sort key with direction {:?}",
+                key.direction
+            ));
+        }
+        Ok(keys)
+    }
+}
+
+impl From<String> for SortBy {
+    fn from(s: String) -> Self {
+        SortBy::Single(s)
+    }
+}
+
+impl std::fmt::Display for SortBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SortBy::Single(s) => write!(f, "{s}"),
+            SortBy::Keys(keys) => {
+                let keys = keys
+                    .iter()
+                    .map(|k| match k.direction {
+                        OrderBy::Asc => format!("{} ASC", k.field),
+                        OrderBy::Desc => format!("{} DESC", k.field),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{}", keys.join(", "))
+            }
+        }
+    }
 }
 
 fn default_size() -> i64 {
@@ -132,6 +222,8 @@ impl Default for Query {
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            wal_only: false,
+            fields: vec![],
         }
     }
 }
@@ -155,6 +247,27 @@ impl Request {
     }
 }
 
+/// Replaces the relative `start_time` and `end_time` of the query, like `now-15m`, with the
+/// microseconds from `now`, the time the request was received, so both are anchored to the same
+/// time. The absolute times are kept.
+pub fn resolve_relative_time_range(query: &mut json::Value, now: i64) -> Result<(), String> {
+    let Some(query) = query.as_object_mut() else {
+        return Ok(());
+    };
+    for key in ["start_time", "end_time"] {
+        let Some(json::Value::String(expr)) = query.get(key) else {
+            continue;
+        };
+        let time = match expr.trim().parse::<i64>() {
+            Ok(v) => v,
+            Err(_) => crate::utils::time::parse_relative_time(expr, now)
+                .map_err(|e| format!("{key}: {e}"))?,
+        };
+        query.insert(key.to_string(), json::Value::from(time));
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
 #[schema(as = SearchResponse)]
 pub struct Response {
@@ -199,6 +312,64 @@ pub struct Response {
     pub result_cache_ratio: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub work_group: Option<String>,
+    /// Why the results are incomplete or degraded
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ResponseWarning>,
+    /// The data the query was estimated to scan before it ran, only when the org has a max scan
+    /// size
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_estimate: Option<CostEstimate>,
+    /// The groups of a GROUP BY query over the max groups were dropped
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups_truncated: Option<bool>,
+}
+
+/// The data a query is estimated to scan, summed from the metadata of the files in the
+/// file_list, the data which isn't persisted yet isn't counted
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CostEstimate {
+    pub file_num: usize,
+    pub records: i64,
+    pub original_size: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ResponseWarning {
+    pub code: ResponseWarningCode,
+    pub message: String,
+}
+
+impl ResponseWarning {
+    pub fn new(code: ResponseWarningCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseWarningCode {
+    /// The time range was clamped by the `max_query_range` of the stream
+    RangeClamped,
+    /// The size was clamped by the max rows per query
+    SizeClamped,
+    /// The data in the WAL of the ingesters was not searched
+    WalSkipped,
+    /// A node failed during the search, its data is missing
+    NodeFailed,
+    /// The response was served from the result cache only, which doesn't cover the whole range
+    CacheOnly,
+    /// The query exceeded its memory limit and spilled to disk, the response is complete
+    Spilled,
+    /// The request uses a deprecated format which will be removed, the response is complete
+    Deprecation,
+    /// The groups of a GROUP BY query over the max groups were dropped
+    GroupsTruncated,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
@@ -211,6 +382,10 @@ pub struct ResponseTook {
     #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub nodes: Vec<ResponseNodeTook>,
+    /// The regions of a super cluster search
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<ResponseRegionTook>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
@@ -220,6 +395,96 @@ pub struct ResponseNodeTook {
     pub took: usize,
 }
 
+/// The contribution of a region to a super cluster search
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, ToSchema)]
+pub struct ResponseRegionTook {
+    pub region: String,
+    /// milliseconds
+    pub took: usize,
+    pub files: i64,
+    pub scan_size: i64,
+    /// why the data of the region is missing or incomplete
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The timing of a search, looked up by the trace id
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchTiming {
+    pub trace_id: String,
+    pub org_id: String,
+    pub stream_type: String,
+    pub sql: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    /// when the search finished, microseconds
+    pub timestamp: i64,
+    /// milliseconds
+    pub took: usize,
+    pub took_detail: Option<ResponseTook>,
+    pub scan_size: usize,
+    pub scan_records: usize,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error: String,
+}
+
+/// The record count of a stream, summed from the metadata of the files
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct CountResponse {
+    pub total: i64,
+    /// number of files the count was summed from
+    pub file_count: usize,
+    /// the data of the ingesters, not persisted yet, is counted
+    pub include_wal: bool,
+    /// milliseconds
+    pub took: usize,
+}
+
+/// Where a value of the field statistics was taken from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldStatsSource {
+    /// the column statistics of the parquet files, no data is read
+    #[default]
+    Metadata,
+    /// a search over the data
+    Scan,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FieldStatsSources {
+    pub min: FieldStatsSource,
+    pub max: FieldStatsSource,
+    pub null_count: FieldStatsSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg: Option<FieldStatsSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cardinality: Option<FieldStatsSource>,
+}
+
+/// The statistics of a numeric field of a stream, the values taken from the metadata cover the
+/// files overlapping the time range whole
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FieldStatsResponse {
+    pub field: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub avg: Option<f64>,
+    pub null_count: i64,
+    /// null_count / records
+    pub null_ratio: f64,
+    /// the approximate number of distinct values
+    pub cardinality: Option<i64>,
+    pub records: i64,
+    /// number of files the metadata was read from
+    pub file_count: usize,
+    pub sources: FieldStatsSources,
+    /// milliseconds
+    pub took: usize,
+}
+
 impl Response {
     pub fn new(from: i64, size: i64) -> Self {
         Response {
@@ -244,6 +509,9 @@ impl Response {
             new_end_time: None,
             result_cache_ratio: 0,
             work_group: None,
+            warnings: Vec::new(),
+            cost_estimate: None,
+            groups_truncated: None,
         }
     }
 
@@ -261,9 +529,16 @@ impl Response {
             cluster_total: val,
             cluster_wait_queue: wait,
             nodes: Vec::new(),
+            regions: Vec::new(),
         });
     }
 
+    pub fn set_regions_took(&mut self, regions: Vec<ResponseRegionTook>) {
+        if let Some(took_detail) = self.took_detail.as_mut() {
+            took_detail.regions = regions;
+        }
+    }
+
     pub fn set_local_took(&mut self, val: usize, wait: usize) {
         if self.took_detail.is_some() {
             self.took_detail.as_mut().unwrap().total = val;
@@ -316,6 +591,13 @@ impl Response {
         }
     }
 
+    /// Adds a warning if it isn't reported yet, the caller decides if it makes the response partial
+    pub fn add_warning(&mut self, warning: ResponseWarning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
     pub fn set_histogram_interval(&mut self, val: Option<i64>) {
         self.histogram_interval = val;
     }
@@ -338,6 +620,10 @@ pub struct SearchPartitionRequest {
     pub clusters: Vec<String>,
     #[serde(default)]
     pub query_fn: Option<String>,
+    /// Split the time range at file boundaries so that each partition scans roughly the
+    /// same amount of data, for clients running the partitions in parallel
+    #[serde(default)]
+    pub partition_by_files: bool,
 }
 
 impl SearchPartitionRequest {
@@ -359,6 +645,46 @@ impl SearchPartitionRequest {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidateSqlRequest {
+    pub sql: String,
+    #[serde(default)]
+    pub encoding: RequestEncoding,
+}
+
+impl ValidateSqlRequest {
+    #[inline]
+    pub fn decode(&mut self) -> Result<(), std::io::Error> {
+        if self.encoding == RequestEncoding::Base64 {
+            self.sql = base64::decode_url(&self.sql)?;
+        }
+        self.encoding = RequestEncoding::Empty;
+        Ok(())
+    }
+}
+
+/// The streams and the fields a sql references, found without executing it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ValidateSqlResponse {
+    pub valid: bool,
+    pub streams: Vec<String>,
+    /// stream name -> the fields of the stream used in the projection, the filter, group by
+    /// and order by
+    pub fields: HashMap<String, Vec<String>>,
+    pub has_match_all: bool,
+    pub errors: Vec<SqlValidationError>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SqlValidationError {
+    pub message: String,
+    /// 1-based position of the error in the sql, if it is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u64>,
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct SearchPartitionResponse {
     pub trace_id: String,
@@ -420,13 +746,15 @@ impl SearchHistoryRequest {
                 size: self.size,
                 start_time: self.start_time,
                 end_time: self.end_time,
-                sort_by: Some(format!("{} DESC", sort_by)),
+                sort_by: Some(format!("{} DESC", sort_by).into()),
                 quick_mode: false,
                 query_type: "".to_string(),
                 track_total_hits: false,
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                wal_only: false,
+                fields: vec![],
             },
             encoding: RequestEncoding::Empty,
             regions: Vec::new(),
@@ -618,11 +946,13 @@ impl From<Request> for cluster_rpc::SearchRequest {
             size: req.query.size as i32,
             start_time: req.query.start_time,
             end_time: req.query.end_time,
-            sort_by: req.query.sort_by.unwrap_or_default(),
+            sort_by: req.query.sort_by.map(|v| v.to_string()).unwrap_or_default(),
             track_total_hits: req.query.track_total_hits,
             uses_zo_fn: req.query.uses_zo_fn,
             query_fn: req.query.query_fn.unwrap_or_default(),
             skip_wal: req.query.skip_wal,
+            wal_only: req.query.wal_only,
+            fields: req.query.fields,
         };
 
         let job = cluster_rpc::Job {
@@ -659,11 +989,13 @@ impl From<Query> for cluster_rpc::SearchQuery {
             size: query.size as i32,
             start_time: query.start_time,
             end_time: query.end_time,
-            sort_by: query.sort_by.unwrap_or_default(),
+            sort_by: query.sort_by.map(|v| v.to_string()).unwrap_or_default(),
             track_total_hits: query.track_total_hits,
             uses_zo_fn: query.uses_zo_fn,
             query_fn: query.query_fn.unwrap_or_default(),
             skip_wal: query.skip_wal,
+            wal_only: query.wal_only,
+            fields: query.fields,
         }
     }
 }
@@ -758,6 +1090,8 @@ pub struct MultiSearchPartitionRequest {
     pub clusters: Vec<String>,
     #[serde(default)]
     pub query_fn: Option<String>,
+    #[serde(default)]
+    pub partition_by_files: bool,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
@@ -795,7 +1129,7 @@ pub struct MultiStreamRequest {
     pub start_time: i64,
     pub end_time: i64,
     #[serde(default)]
-    pub sort_by: Option<String>,
+    pub sort_by: Option<SortBy>,
     #[serde(default)]
     pub quick_mode: bool,
     #[serde(default)]
@@ -809,6 +1143,8 @@ pub struct MultiStreamRequest {
     #[serde(default)]
     pub skip_wal: bool,
     #[serde(default)]
+    pub wal_only: bool,
+    #[serde(default)]
     pub regions: Vec<String>, // default query all regions, local: only query local region clusters
     #[serde(default)]
     pub clusters: Vec<String>, // default query all clusters, local: only query local cluster
@@ -817,8 +1153,19 @@ pub struct MultiStreamRequest {
     pub index_type: String, // parquet(default) or fst
     #[serde(default)]
     pub per_query_response: bool,
+    /// Adds the name of the stream each hit comes from as `_stream`, unless the hit has a
+    /// `_stream` field
+    #[serde(default)]
+    pub with_stream_name: bool,
+    /// Returns the hits grouped per stream, `[{"stream": "...", "hits": [...]}]`, instead of
+    /// a flat array
+    #[serde(default)]
+    pub group_by_stream: bool,
 }
 
+/// The field holding the stream of a hit of a multi stream search with `with_stream_name`
+pub const STREAM_NAME_FIELD: &str = "_stream";
+
 fn deserialize_sql<'de, D>(deserializer: D) -> Result<Vec<SqlQuery>, D::Error>
 where
     D: Deserializer<'de>,
@@ -851,6 +1198,70 @@ where
 }
 
 impl MultiStreamRequest {
+    /// Returns the warning for a request giving some `sql` as plain strings, the format before
+    /// the queries could have their own time range and function
+    pub fn deprecation_warning(&self) -> Option<ResponseWarning> {
+        self.sql.iter().any(|query| query.is_old_format).then(|| {
+            ResponseWarning::new(
+                ResponseWarningCode::Deprecation,
+                "Giving `sql` as a list of strings is deprecated and will be removed, give a list \
+                 of objects like `{\"sql\": \"...\", \"start_time\": 0, \"end_time\": 0, \
+                 \"query_fn\": \"...\"}` instead, the time range and function are optional",
+            )
+        })
+    }
+
+    /// Adds the hits of the query on the stream to the hits of the response, in the shape the
+    /// request asks for. The hits grouped per stream are added to `stream_hits`, they are
+    /// grouped by `group_hits_by_stream` once the hits of all the queries are sorted. A hit with
+    /// a `_stream` field keeps it.
+    pub fn merge_hits(
+        &self,
+        hits: &mut Vec<json::Value>,
+        stream_hits: &mut Vec<(String, Vec<json::Value>)>,
+        stream_name: &str,
+        mut query_hits: Vec<json::Value>,
+    ) {
+        if self.with_stream_name {
+            for hit in query_hits.iter_mut() {
+                if let Some(hit) = hit.as_object_mut() {
+                    if !hit.contains_key(STREAM_NAME_FIELD) {
+                        hit.insert(STREAM_NAME_FIELD.to_string(), stream_name.into());
+                    }
+                }
+            }
+        }
+        if self.per_query_response {
+            hits.push(json::Value::Array(query_hits));
+        } else if self.group_by_stream {
+            stream_hits.push((stream_name.to_string(), query_hits));
+        } else {
+            hits.extend(query_hits);
+        }
+    }
+
+    /// Returns the hits grouped per stream, `[{"stream": "...", "hits": [...]}]`, in the order
+    /// the streams are queried. The hits of a stream are sorted with `compare`.
+    pub fn group_hits_by_stream(
+        stream_hits: Vec<(String, Vec<json::Value>)>,
+        compare: impl Fn(&json::Value, &json::Value) -> std::cmp::Ordering,
+    ) -> Vec<json::Value> {
+        let mut groups: Vec<(String, Vec<json::Value>)> = Vec::new();
+        for (stream_name, hits) in stream_hits {
+            match groups.iter_mut().find(|(name, _)| *name == stream_name) {
+                Some((_, group)) => group.extend(hits),
+                None => groups.push((stream_name, hits)),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(stream_name, mut hits)| {
+                hits.sort_by(&compare);
+                json::json!({"stream": stream_name, "hits": hits})
+            })
+            .collect()
+    }
+
     pub fn to_query_req(&self) -> Vec<Request> {
         let mut res = vec![];
         for query in &self.sql {
@@ -878,6 +1289,8 @@ impl MultiStreamRequest {
                     uses_zo_fn: self.uses_zo_fn,
                     query_fn,
                     skip_wal: self.skip_wal,
+                    wal_only: self.wal_only,
+                    fields: vec![],
                 },
                 regions: self.regions.clone(),
                 clusters: self.clusters.clone(),
@@ -907,6 +1320,123 @@ mod tests {
         assert_eq!(res.total, 11);
     }
 
+    #[test]
+    fn test_multi_stream_request_deprecation_warning() {
+        let old_format = json::json!({
+            "sql": ["select * from a", "select * from b"],
+            "start_time": 0,
+            "end_time": 10,
+        });
+        let req: MultiStreamRequest = json::from_value(old_format).unwrap();
+        let warning = req.deprecation_warning().unwrap();
+        assert_eq!(warning.code, ResponseWarningCode::Deprecation);
+        let mut res = Response::default();
+        res.add_warning(warning);
+        assert_eq!(
+            json::to_value(&res).unwrap()["warnings"][0]["code"],
+            "deprecation"
+        );
+
+        let new_format = json::json!({
+            "sql": [{"sql": "select * from a", "start_time": 5}, {"sql": "select * from b"}],
+            "start_time": 0,
+            "end_time": 10,
+        });
+        let req: MultiStreamRequest = json::from_value(new_format).unwrap();
+        assert!(req.deprecation_warning().is_none());
+        // the behavior doesn't change
+        assert_eq!(req.to_query_req()[0].query.start_time, 5);
+    }
+
+    #[test]
+    fn test_multi_stream_request_merge_hits() {
+        let mut req: MultiStreamRequest = json::from_value(json::json!({
+            "sql": [{"sql": "select * from a"}, {"sql": "select * from b"}],
+            "start_time": 0,
+            "end_time": 10,
+        }))
+        .unwrap();
+        let merge = |req: &MultiStreamRequest| {
+            let mut hits = Vec::new();
+            let mut stream_hits = Vec::new();
+            req.merge_hits(
+                &mut hits,
+                &mut stream_hits,
+                "a",
+                vec![json::json!({"n": 1})],
+            );
+            req.merge_hits(
+                &mut hits,
+                &mut stream_hits,
+                "b",
+                vec![json::json!({"n": 2})],
+            );
+            req.merge_hits(
+                &mut hits,
+                &mut stream_hits,
+                "a",
+                vec![json::json!({"n": 3, "_stream": "real"})],
+            );
+            if req.group_by_stream {
+                assert!(hits.is_empty());
+                // the newest first
+                hits = MultiStreamRequest::group_hits_by_stream(stream_hits, |a, b| {
+                    b["n"].as_i64().cmp(&a["n"].as_i64())
+                });
+            }
+            json::Value::Array(hits)
+        };
+
+        // the hits are unchanged by default
+        assert_eq!(
+            merge(&req),
+            json::json!([{"n": 1}, {"n": 2}, {"n": 3, "_stream": "real"}])
+        );
+
+        // a real `_stream` field isn't overwritten
+        req.with_stream_name = true;
+        assert_eq!(
+            merge(&req),
+            json::json!([
+                {"n": 1, "_stream": "a"},
+                {"n": 2, "_stream": "b"},
+                {"n": 3, "_stream": "real"}
+            ])
+        );
+
+        req.with_stream_name = false;
+        req.group_by_stream = true;
+        assert_eq!(
+            merge(&req),
+            json::json!([
+                {"stream": "a", "hits": [{"n": 3, "_stream": "real"}, {"n": 1}]},
+                {"stream": "b", "hits": [{"n": 2}]}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_response_warnings() {
+        let mut res = Response::default();
+        let warning = ResponseWarning::new(ResponseWarningCode::RangeClamped, "clamped");
+        res.add_warning(warning.clone());
+        res.add_warning(warning);
+        res.add_warning(ResponseWarning::new(
+            ResponseWarningCode::NodeFailed,
+            "node1 failed",
+        ));
+        assert_eq!(res.warnings.len(), 2);
+        let val = json::to_value(&res).unwrap();
+        assert_eq!(val["warnings"][0]["code"], "range_clamped");
+        assert_eq!(val["warnings"][1]["code"], "node_failed");
+        assert!(
+            json::to_value(Response::default())
+                .unwrap()
+                .get("warnings")
+                .is_none()
+        );
+    }
+
     #[test]
     fn test_request_encoding() {
         let req = json::json!(
@@ -963,6 +1493,8 @@ mod tests {
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                wal_only: false,
+                fields: vec![],
             },
             encoding: "base64".into(),
             regions: vec![],
@@ -1155,4 +1687,56 @@ mod search_history_utils {
             assert_eq!(query, expected_query);
         }
     }
+
+    #[test]
+    fn test_resolve_relative_time_range() {
+        let now = 1_700_000_000_000_000;
+        let hour = 3_600_000_000;
+        let resolve = |start_time: json::Value, end_time: json::Value| {
+            let mut query = json::json!({
+                "sql": "select * from t",
+                "start_time": start_time,
+                "end_time": end_time,
+            });
+            resolve_relative_time_range(&mut query, now)?;
+            let query: Query = json::from_value(query).map_err(|e| e.to_string())?;
+            Ok::<_, String>((query.start_time, query.end_time))
+        };
+
+        // both relative times are anchored to the same now
+        assert_eq!(
+            resolve("now-1h".into(), "now".into()).unwrap(),
+            (now - hour, now)
+        );
+        // the absolute times are kept
+        assert_eq!(
+            resolve((now - 2 * hour).into(), now.into()).unwrap(),
+            (now - 2 * hour, now)
+        );
+        assert_eq!(
+            resolve((now - hour).to_string().into(), "now".into()).unwrap(),
+            (now - hour, now)
+        );
+        // a relative start with an absolute end and the other way
+        assert_eq!(
+            resolve("now-15m".into(), now.into()).unwrap(),
+            (now - hour / 4, now)
+        );
+        assert_eq!(
+            resolve((now - hour).into(), "now-1m".into()).unwrap(),
+            (now - hour, now - hour / 60)
+        );
+        // the absolute end on the boundary of the relative start
+        assert_eq!(
+            resolve("now-1d".into(), (now - 24 * hour).into()).unwrap(),
+            (now - 24 * hour, now - 24 * hour)
+        );
+
+        let err = resolve("now-15x".into(), "now".into()).unwrap_err();
+        assert!(
+            err.starts_with("start_time: Invalid relative time [now-15x]"),
+            "{err}"
+        );
+        assert!(resolve(now.into(), "yesterday".into()).is_err());
+    }
 }
@@ -36,7 +36,9 @@ pub const MAX_OFFSET: i64 = 100000;
 #[serde(rename_all = "camelCase")]
 pub enum OrderBy {
     #[default]
+    #[serde(alias = "DESC")]
     Desc,
+    #[serde(alias = "ASC")]
     Asc,
 }
 
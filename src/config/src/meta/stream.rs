@@ -16,7 +16,7 @@
 use std::{cmp::max, fmt::Display};
 
 use byteorder::{ByteOrder, LittleEndian};
-use chrono::Duration;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use hashbrown::HashMap;
 use proto::cluster_rpc;
 use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
@@ -28,6 +28,7 @@ use crate::{
     utils::{
         hash::{gxhash, Sum64},
         json::{self, Map, Value},
+        time,
     },
 };
 
@@ -148,6 +149,14 @@ pub struct FileMeta {
     pub original_size: i64,
     pub compressed_size: i64,
     pub flattened: bool,
+    /// the size of the secondary index file of the parquet file, 0 if it has none and -1 if it's
+    /// unknown, for the files registered before the size was recorded
+    #[serde(default = "default_index_size")]
+    pub index_size: i64,
+}
+
+fn default_index_size() -> i64 {
+    -1
 }
 
 impl FileMeta {
@@ -190,6 +199,7 @@ impl TryFrom<&[u8]> for FileMeta {
             original_size,
             compressed_size,
             flattened: false,
+            index_size: 0,
         })
     }
 }
@@ -378,6 +388,7 @@ impl From<&FileMeta> for cluster_rpc::FileMeta {
             records: req.records,
             original_size: req.original_size,
             compressed_size: req.compressed_size,
+            index_size: req.index_size,
         }
     }
 }
@@ -391,6 +402,7 @@ impl From<&cluster_rpc::FileMeta> for FileMeta {
             original_size: req.original_size,
             compressed_size: req.compressed_size,
             flattened: false,
+            index_size: req.index_size,
         }
     }
 }
@@ -457,6 +469,17 @@ impl std::fmt::Display for PartitionTimeLevel {
     }
 }
 
+/// How the arrays of a record are flattened at ingestion
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FlattenArrayMode {
+    /// the array is stored as a JSON string
+    #[default]
+    Json,
+    /// each element is flattened with its index as key suffix, e.g. `tags_0`
+    IndexSuffix,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, ToSchema)]
 pub struct UpdateStreamPartition {
     pub add: Vec<StreamPartition>,
@@ -483,17 +506,43 @@ pub struct UpdateStreamSettings {
     #[serde(default)]
     pub bloom_filter_fields: UpdateStringSettingsArray,
     #[serde(skip_serializing_if = "Option::None")]
-    #[serde(default)]
+    #[serde(default, alias = "retention_days")]
     pub data_retention: Option<i64>,
     #[serde(skip_serializing_if = "Option::None")]
     #[serde(default)]
     pub flatten_level: Option<i64>,
     #[serde(default)]
+    pub flatten_array: Option<FlattenArrayMode>,
+    #[serde(default)]
     pub defined_schema_fields: UpdateStringSettingsArray,
     #[serde(default)]
     pub max_query_range: Option<i64>,
+    /// an empty policy removes it, the policy of the org is used
+    #[serde(default)]
+    pub query_range_policy: Option<String>,
     #[serde(default)]
     pub store_original_data: Option<bool>,
+    /// an empty field removes the timestamp settings
+    #[serde(default)]
+    pub timestamp_settings: Option<TimestampSettings>,
+    /// replaces the field access rules, an empty list removes them
+    #[serde(default)]
+    pub field_access: Option<Vec<FieldAccess>>,
+    /// replaces the required fields, an empty list removes them
+    #[serde(default)]
+    pub required_fields: Option<Vec<RequiredField>>,
+    /// replaces the field aliases, an empty list removes them
+    #[serde(default)]
+    pub field_aliases: Option<Vec<FieldAlias>>,
+    /// replaces the stream aliases, an empty list removes them
+    #[serde(default)]
+    pub aliases: Option<Vec<String>>,
+    /// empty fields turn the collapsing of duplicates off
+    #[serde(default)]
+    pub collapse_duplicates: Option<CollapseDuplicates>,
+    /// replaces the computed columns, an empty list removes them
+    #[serde(default)]
+    pub computed_columns: Option<Vec<ComputedColumn>>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, ToSchema)]
@@ -516,11 +565,32 @@ pub struct StreamSettings {
     #[serde(skip_serializing_if = "Option::None")]
     pub flatten_level: Option<i64>,
     #[serde(skip_serializing_if = "Option::None")]
+    pub flatten_array: Option<FlattenArrayMode>,
+    #[serde(skip_serializing_if = "Option::None")]
     pub defined_schema_fields: Option<Vec<String>>,
     #[serde(default)]
     pub max_query_range: i64,
+    /// what to do with a query spanning more than `max_query_range`, `clamp` or `reject`,
+    /// overrides the policy of the org
+    #[serde(default)]
+    pub query_range_policy: Option<String>,
     #[serde(default)]
     pub store_original_data: bool,
+    #[serde(skip_serializing_if = "Option::None")]
+    pub timestamp_settings: Option<TimestampSettings>,
+    #[serde(default)]
+    pub field_access: Vec<FieldAccess>,
+    #[serde(default)]
+    pub required_fields: Vec<RequiredField>,
+    #[serde(default)]
+    pub field_aliases: Vec<FieldAlias>,
+    /// other names the stream is queried and ingested under, like its name before a rename
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub collapse_duplicates: Option<CollapseDuplicates>,
+    #[serde(default)]
+    pub computed_columns: Vec<ComputedColumn>,
 }
 
 impl Serialize for StreamSettings {
@@ -543,6 +613,14 @@ impl Serialize for StreamSettings {
         state.serialize_field("bloom_filter_fields", &self.bloom_filter_fields)?;
         state.serialize_field("data_retention", &self.data_retention)?;
         state.serialize_field("max_query_range", &self.max_query_range)?;
+        match self.query_range_policy.as_ref() {
+            Some(query_range_policy) => {
+                state.serialize_field("query_range_policy", query_range_policy)?;
+            }
+            None => {
+                state.skip_field("query_range_policy")?;
+            }
+        }
         state.serialize_field("store_original_data", &self.store_original_data)?;
 
         match self.defined_schema_fields.as_ref() {
@@ -565,6 +643,55 @@ impl Serialize for StreamSettings {
                 state.skip_field("flatten_level")?;
             }
         }
+        match self.flatten_array.as_ref() {
+            Some(flatten_array) => {
+                state.serialize_field("flatten_array", flatten_array)?;
+            }
+            None => {
+                state.skip_field("flatten_array")?;
+            }
+        }
+        match self.timestamp_settings.as_ref() {
+            Some(timestamp_settings) => {
+                state.serialize_field("timestamp_settings", timestamp_settings)?;
+            }
+            None => {
+                state.skip_field("timestamp_settings")?;
+            }
+        }
+        if self.field_access.is_empty() {
+            state.skip_field("field_access")?;
+        } else {
+            state.serialize_field("field_access", &self.field_access)?;
+        }
+        if self.required_fields.is_empty() {
+            state.skip_field("required_fields")?;
+        } else {
+            state.serialize_field("required_fields", &self.required_fields)?;
+        }
+        if self.field_aliases.is_empty() {
+            state.skip_field("field_aliases")?;
+        } else {
+            state.serialize_field("field_aliases", &self.field_aliases)?;
+        }
+        if self.aliases.is_empty() {
+            state.skip_field("aliases")?;
+        } else {
+            state.serialize_field("aliases", &self.aliases)?;
+        }
+        if self.computed_columns.is_empty() {
+            state.skip_field("computed_columns")?;
+        } else {
+            state.serialize_field("computed_columns", &self.computed_columns)?;
+        }
+        match self.collapse_duplicates.as_ref() {
+            Some(collapse_duplicates) => {
+                state.serialize_field("collapse_duplicates", collapse_duplicates)?;
+            }
+            None => {
+                state.skip_field("collapse_duplicates")?;
+            }
+        }
         state.end()
     }
 }
@@ -625,7 +752,10 @@ impl From<&str> for StreamSettings {
         }
 
         let mut data_retention = 0;
-        if let Some(v) = settings.get("data_retention") {
+        if let Some(v) = settings
+            .get("data_retention")
+            .or_else(|| settings.get("retention_days"))
+        {
             data_retention = v.as_i64().unwrap();
         };
 
@@ -634,6 +764,11 @@ impl From<&str> for StreamSettings {
             max_query_range = v.as_i64().unwrap();
         };
 
+        let query_range_policy = settings
+            .get("query_range_policy")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string());
+
         let mut defined_schema_fields: Option<Vec<String>> = None;
         if let Some(value) = settings.get("defined_schema_fields") {
             let fields = value
@@ -648,12 +783,48 @@ impl From<&str> for StreamSettings {
         }
 
         let flatten_level = settings.get("flatten_level").map(|v| v.as_i64().unwrap());
+        let flatten_array = settings
+            .get("flatten_array")
+            .and_then(|v| json::from_value(v.clone()).ok());
 
         let store_original_data = settings
             .get("store_original_data")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
 
+        let timestamp_settings = settings
+            .get("timestamp_settings")
+            .and_then(|v| json::from_value(v.clone()).ok());
+
+        let field_access = settings
+            .get("field_access")
+            .and_then(|v| json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let required_fields = settings
+            .get("required_fields")
+            .and_then(|v| json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let field_aliases = settings
+            .get("field_aliases")
+            .and_then(|v| json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let aliases = settings
+            .get("aliases")
+            .and_then(|v| json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let collapse_duplicates = settings
+            .get("collapse_duplicates")
+            .and_then(|v| json::from_value(v.clone()).ok());
+
+        let computed_columns = settings
+            .get("computed_columns")
+            .and_then(|v| json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
         Self {
             partition_time_level,
             partition_keys,
@@ -662,9 +833,18 @@ impl From<&str> for StreamSettings {
             bloom_filter_fields,
             data_retention,
             max_query_range,
+            query_range_policy,
             flatten_level,
+            flatten_array,
             defined_schema_fields,
             store_original_data,
+            timestamp_settings,
+            field_access,
+            required_fields,
+            field_aliases,
+            aliases,
+            collapse_duplicates,
+            computed_columns,
         }
     }
 }
@@ -744,6 +924,331 @@ impl Display for StreamPartitionType {
     }
 }
 
+/// The fields of the stream a role can read in search results. If the allow list isn't empty
+/// only its fields can be read, and the fields in the deny list can't be read.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldAccess {
+    pub role: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+/// A field the records of the stream must have, a record without it or with a value of another
+/// type is rejected at ingestion instead of evolving the schema
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RequiredField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: RequiredFieldType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RequiredFieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+impl RequiredFieldType {
+    /// Checks the type of the value, an integer is a valid float
+    pub fn matches(&self, value: &json::Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Float => value.is_number(),
+            Self::Boolean => value.is_boolean(),
+        }
+    }
+}
+
+impl Display for RequiredFieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String => write!(f, "string"),
+            Self::Integer => write!(f, "integer"),
+            Self::Float => write!(f, "float"),
+            Self::Boolean => write!(f, "boolean"),
+        }
+    }
+}
+
+/// Checks the record has the required fields with their types, returns the reason of the first
+/// mismatch
+pub fn check_required_fields(
+    fields: &[RequiredField],
+    record: &json::Map<String, json::Value>,
+) -> Result<(), String> {
+    for field in fields.iter() {
+        match record.get(&field.name) {
+            None | Some(json::Value::Null) => {
+                return Err(format!("required field [{}] is missing", field.name));
+            }
+            Some(value) if !field.field_type.matches(value) => {
+                return Err(format!(
+                    "required field [{}] should be of type {}",
+                    field.name, field.field_type
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// A flat name to query a nested field by, like `kubernetes_pod_name` for the path
+/// `kubernetes.pod_name`. It's only resolved at query time, the data is stored as ingested.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldAlias {
+    pub name: String,
+    /// the field holding the nested value, then the keys to the value, separated by dots
+    pub path: String,
+}
+
+impl FieldAlias {
+    /// Returns the field and the path of the value in it
+    pub fn split_path(&self) -> Option<(&str, &str)> {
+        self.path
+            .split_once('.')
+            .filter(|(field, path)| !field.is_empty() && !path.is_empty())
+    }
+}
+
+/// Checks the aliases are valid paths and don't collide with the fields of the stream or with
+/// each other
+pub fn check_field_aliases(
+    aliases: &[FieldAlias],
+    field_exists: impl Fn(&str) -> bool,
+) -> Result<(), String> {
+    let mut names = std::collections::HashSet::with_capacity(aliases.len());
+    for alias in aliases.iter() {
+        if alias.name.trim().is_empty() {
+            return Err("field alias name is required".to_string());
+        }
+        if alias.split_path().is_none() {
+            return Err(format!(
+                "field alias [{}] should have a nested path like field.key, got [{}]",
+                alias.name, alias.path
+            ));
+        }
+        if field_exists(&alias.name) {
+            return Err(format!(
+                "field alias [{}] collides with a field of the stream",
+                alias.name
+            ));
+        }
+        if !names.insert(alias.name.as_str()) {
+            return Err(format!("field alias [{}] is duplicated", alias.name));
+        }
+    }
+    Ok(())
+}
+
+/// A virtual field computed at query time from the fields of the stream, like `latency_ms` for
+/// `duration / 1000` or `status` for `response_code`, so the queries survive a field rename
+/// without re-ingesting the data.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ComputedColumn {
+    pub name: String,
+    /// a SQL expression over the fields of the stream
+    pub expr: String,
+}
+
+impl ComputedColumn {
+    pub fn parse_expr(&self) -> Result<sqlparser::ast::Expr, sqlparser::parser::ParserError> {
+        sqlparser::parser::Parser::new(&sqlparser::dialect::PostgreSqlDialect {})
+            .try_with_sql(&self.expr)?
+            .parse_expr()
+    }
+}
+
+/// Checks the computed columns are valid expressions over the fields of the stream, and don't
+/// collide with the fields or with each other
+pub fn check_computed_columns(
+    columns: &[ComputedColumn],
+    field_exists: impl Fn(&str) -> bool,
+) -> Result<(), String> {
+    let mut names = std::collections::HashSet::with_capacity(columns.len());
+    for column in columns.iter() {
+        if column.name.trim().is_empty() {
+            return Err("computed column name is required".to_string());
+        }
+        if field_exists(&column.name) {
+            return Err(format!(
+                "computed column [{}] collides with a field of the stream",
+                column.name
+            ));
+        }
+        if !names.insert(column.name.as_str()) {
+            return Err(format!("computed column [{}] is duplicated", column.name));
+        }
+        let expr = column.parse_expr().map_err(|e| {
+            format!(
+                "computed column [{}] has an invalid expression [{}]: {e}",
+                column.name, column.expr
+            )
+        })?;
+        // the expression replaces the column in the filters and the groups of the queries
+        if let Some(v) = crate::utils::sql::non_row_expr(&expr) {
+            return Err(format!(
+                "computed column [{}] can only use the fields of a record, not {v}",
+                column.name
+            ));
+        }
+        let mut fields = crate::utils::sql::expr_columns(&expr)
+            .into_iter()
+            .collect::<Vec<_>>();
+        fields.sort();
+        if let Some(field) = fields.iter().find(|field| !field_exists(field)) {
+            return Err(format!(
+                "computed column [{}] uses the field [{field}] which isn't in the stream",
+                column.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the aliases of a stream are free names: not the name of a stream, which also keeps an
+/// alias from pointing to another alias, and not an alias of another stream. `alias_target`
+/// returns the stream an alias belongs to.
+pub fn check_stream_aliases(
+    stream_name: &str,
+    aliases: &[String],
+    stream_exists: impl Fn(&str) -> bool,
+    alias_target: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    if aliases.is_empty() {
+        return Ok(());
+    }
+    if let Some(target) = alias_target(stream_name) {
+        return Err(format!(
+            "stream [{stream_name}] is an alias of stream [{target}], an alias can't have aliases"
+        ));
+    }
+    let mut names = std::collections::HashSet::with_capacity(aliases.len());
+    for alias in aliases.iter() {
+        if alias.trim().is_empty() {
+            return Err("stream alias is required".to_string());
+        }
+        if alias.contains('/') {
+            return Err(format!("stream alias [{alias}] can't contain '/'"));
+        }
+        if alias == stream_name || stream_exists(alias) {
+            return Err(format!("stream alias [{alias}] collides with a stream"));
+        }
+        if let Some(target) = alias_target(alias).filter(|target| target != stream_name) {
+            return Err(format!(
+                "stream alias [{alias}] is already an alias of stream [{target}]"
+            ));
+        }
+        if !names.insert(alias.as_str()) {
+            return Err(format!("stream alias [{alias}] is duplicated"));
+        }
+    }
+    Ok(())
+}
+
+/// Collapses the identical records of an ingestion request into one record, which counts them
+/// in the `_count` field. The records are collapsed before they are written to the WAL, so the
+/// identical records of different requests aren't collapsed together. It's lossy: `count(*)`
+/// counts a collapsed record once, the ingested records are counted by `sum(_count)`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CollapseDuplicates {
+    /// the records with the same values of the fields are identical
+    pub fields: Vec<String>,
+    /// seconds, the identical records of a request with timestamps in the same window are
+    /// collapsed
+    #[serde(default = "default_collapse_window")]
+    pub window_secs: i64,
+}
+
+fn default_collapse_window() -> i64 {
+    10
+}
+
+/// Where the event time of a record is read from, applied at ingestion to populate the
+/// timestamp column
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TimestampSettings {
+    pub field: String,
+    #[serde(default)]
+    pub format: TimestampFormat,
+    #[serde(default)]
+    pub on_error: TimestampErrorPolicy,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    #[default]
+    Auto, // guess the unit of numbers and the layout of strings
+    EpochSeconds,
+    EpochMillis,
+    EpochMicros,
+    Rfc3339,
+    Custom(String), // strftime format, without offset the time is in UTC
+}
+
+impl TimestampFormat {
+    /// Parses the value of the timestamp field to microseconds
+    pub fn parse(&self, v: &Value) -> Result<i64, anyhow::Error> {
+        match self {
+            TimestampFormat::Auto => time::parse_timestamp_micro_from_value(v),
+            TimestampFormat::EpochSeconds => parse_epoch_micros(v, 1_000_000),
+            TimestampFormat::EpochMillis => parse_epoch_micros(v, 1_000),
+            TimestampFormat::EpochMicros => parse_epoch_micros(v, 1),
+            TimestampFormat::Rfc3339 => {
+                let Some(s) = v.as_str() else {
+                    return Err(anyhow::anyhow!("Invalid time format [type]"));
+                };
+                Ok(DateTime::parse_from_rfc3339(s.trim())?.timestamp_micros())
+            }
+            TimestampFormat::Custom(format) => {
+                let Some(s) = v.as_str() else {
+                    return Err(anyhow::anyhow!("Invalid time format [type]"));
+                };
+                let s = s.trim();
+                if let Ok(t) = DateTime::parse_from_str(s, format) {
+                    return Ok(t.timestamp_micros());
+                }
+                if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+                    return Ok(t.and_utc().timestamp_micros());
+                }
+                let t = NaiveDate::parse_from_str(s, format)?;
+                Ok(t.and_time(NaiveTime::MIN).and_utc().timestamp_micros())
+            }
+        }
+    }
+}
+
+fn parse_epoch_micros(v: &Value, unit: i64) -> Result<i64, anyhow::Error> {
+    let n = match v {
+        Value::Number(n) => n.clone(),
+        Value::String(s) => json::from_str::<json::Number>(s.trim())
+            .map_err(|_| anyhow::anyhow!("Invalid time format [epoch]"))?,
+        _ => return Err(anyhow::anyhow!("Invalid time format [type]")),
+    };
+    let micros = match n.as_i64() {
+        Some(i) => i.checked_mul(unit),
+        None => n.as_f64().map(|f| (f * unit as f64) as i64),
+    };
+    micros.ok_or_else(|| anyhow::anyhow!("Invalid time format [epoch]"))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampErrorPolicy {
+    #[default]
+    Reject,
+    UseIngestTime,
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub struct PartitioningDetails {
@@ -924,6 +1429,7 @@ mod tests {
             original_size: 10,
             compressed_size: 1,
             flattened: false,
+            index_size: 0,
         };
 
         let rpc_meta = cluster_rpc::FileMeta::from(&file_meta);
@@ -931,6 +1437,136 @@ mod tests {
         assert_eq!(file_meta, resp);
     }
 
+    #[test]
+    fn test_check_computed_columns() {
+        let column = |name: &str, expr: &str| ComputedColumn {
+            name: name.to_string(),
+            expr: expr.to_string(),
+        };
+        let fields = ["duration", "response_code"];
+        let field_exists = |name: &str| fields.contains(&name);
+        assert!(
+            check_computed_columns(
+                &[
+                    column("latency_ms", "duration / 1000"),
+                    column("status", "response_code"),
+                ],
+                field_exists
+            )
+            .is_ok()
+        );
+        assert!(
+            check_computed_columns(&[column("duration", "duration * 2")], field_exists).is_err()
+        );
+        assert!(
+            check_computed_columns(&[column("latency_ms", "duration /")], field_exists).is_err()
+        );
+        assert_eq!(
+            check_computed_columns(&[column("latency_ms", "latency / 1000")], field_exists)
+                .unwrap_err(),
+            "computed column [latency_ms] uses the field [latency] which isn't in the stream"
+        );
+        assert!(
+            check_computed_columns(
+                &[
+                    column("status", "response_code"),
+                    column("status", "duration")
+                ],
+                field_exists
+            )
+            .is_err()
+        );
+
+        // only the fields of the record can be used
+        for (expr, reason) in [
+            ("(SELECT max(duration) FROM t)", "a subquery"),
+            ("EXISTS (SELECT 1 FROM t)", "a subquery"),
+            ("response_code IN (SELECT code FROM t)", "a subquery"),
+            ("duration - avg(duration)", "the aggregate function [avg]"),
+            ("STDDEV(duration)", "the aggregate function [STDDEV]"),
+            (
+                "approx_distinct(response_code)",
+                "the aggregate function [approx_distinct]",
+            ),
+            (
+                "row_number() OVER (ORDER BY duration)",
+                "the window function [row_number]",
+            ),
+        ] {
+            assert_eq!(
+                check_computed_columns(&[column("c", expr)], field_exists).unwrap_err(),
+                format!("computed column [c] can only use the fields of a record, not {reason}"),
+                "{expr}"
+            );
+        }
+        assert!(check_computed_columns(&[column("c", "abs(duration)")], field_exists).is_ok());
+    }
+
+    #[test]
+    fn test_check_field_aliases() {
+        let alias = |name: &str, path: &str| FieldAlias {
+            name: name.to_string(),
+            path: path.to_string(),
+        };
+        let fields = ["kubernetes", "message"];
+        let field_exists = |name: &str| fields.contains(&name);
+        assert_eq!(
+            alias("a", "kubernetes.labels.app").split_path(),
+            Some(("kubernetes", "labels.app"))
+        );
+        assert!(
+            check_field_aliases(
+                &[
+                    alias("kubernetes_pod_name", "kubernetes.pod_name"),
+                    alias("app", "kubernetes.labels.app"),
+                ],
+                field_exists
+            )
+            .is_ok()
+        );
+        assert!(
+            check_field_aliases(&[alias("message", "kubernetes.message")], field_exists).is_err()
+        );
+        assert!(check_field_aliases(&[alias("pod", "kubernetes")], field_exists).is_err());
+        assert!(check_field_aliases(&[alias("pod", "kubernetes.")], field_exists).is_err());
+        assert!(
+            check_field_aliases(
+                &[
+                    alias("pod", "kubernetes.pod_name"),
+                    alias("pod", "kubernetes.pod")
+                ],
+                field_exists
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_stream_aliases() {
+        let aliases = |names: &[&str]| names.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let streams = ["app", "web"];
+        let stream_exists = |name: &str| streams.contains(&name);
+        // `old_app` belongs to `app`, `old_web` to `web`
+        let alias_target = |name: &str| match name {
+            "old_app" => Some("app".to_string()),
+            "old_web" => Some("web".to_string()),
+            _ => None,
+        };
+        let check = |stream_name: &str, names: &[&str]| {
+            check_stream_aliases(stream_name, &aliases(names), stream_exists, alias_target)
+        };
+        assert!(check("app", &["old_app", "app_v1"]).is_ok());
+        assert!(check("app", &[]).is_ok());
+        assert!(check("app", &["web"]).is_err());
+        assert!(check("app", &["app"]).is_err());
+        assert!(check("app", &["old_web"]).is_err());
+        assert!(check("app", &["a/b"]).is_err());
+        assert!(check("app", &[" "]).is_err());
+        assert!(check("app", &["app_v1", "app_v1"]).is_err());
+        // an alias can't have aliases
+        assert!(check("old_app", &["older_app"]).is_err());
+    }
+
     #[cfg(feature = "gxhash")]
     #[test]
     fn test_hash_partition() {
@@ -996,4 +1632,204 @@ mod tests {
         assert_eq!(params.stream_name, "stream_name");
         assert_eq!(params.stream_type, StreamType::Logs);
     }
+
+    #[test]
+    fn test_timestamp_format_epoch() {
+        let micros = 1_700_000_000_000_000;
+        let format = TimestampFormat::EpochSeconds;
+        assert_eq!(format.parse(&json::json!(1_700_000_000)).unwrap(), micros);
+        assert_eq!(format.parse(&json::json!("1700000000")).unwrap(), micros);
+        assert_eq!(
+            format.parse(&json::json!(1_700_000_000.5)).unwrap(),
+            micros + 500_000
+        );
+        let format = TimestampFormat::EpochMillis;
+        assert_eq!(
+            format.parse(&json::json!(1_700_000_000_000i64)).unwrap(),
+            micros
+        );
+        let format = TimestampFormat::EpochMicros;
+        assert_eq!(format.parse(&json::json!(micros)).unwrap(), micros);
+        assert!(format.parse(&json::json!("yesterday")).is_err());
+        assert!(format.parse(&json::json!(true)).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_format_rfc3339() {
+        let micros = 1_700_000_000_000_000;
+        let format = TimestampFormat::Rfc3339;
+        assert_eq!(
+            format.parse(&json::json!("2023-11-14T22:13:20Z")).unwrap(),
+            micros
+        );
+        assert_eq!(
+            format
+                .parse(&json::json!("2023-11-15T03:43:20.25+05:30"))
+                .unwrap(),
+            micros + 250_000
+        );
+        assert_eq!(
+            format
+                .parse(&json::json!("2023-11-14T17:13:20-05:00"))
+                .unwrap(),
+            micros
+        );
+        assert!(format.parse(&json::json!("2023-11-14 22:13:20")).is_err());
+        assert!(format.parse(&json::json!(1_700_000_000)).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_format_custom() {
+        let micros = 1_700_000_000_000_000;
+        // without offset the time is in UTC
+        let format = TimestampFormat::Custom("%d/%m/%Y %H:%M:%S".to_string());
+        assert_eq!(
+            format.parse(&json::json!("14/11/2023 22:13:20")).unwrap(),
+            micros
+        );
+        let format = TimestampFormat::Custom("%d/%b/%Y:%H:%M:%S %z".to_string());
+        assert_eq!(
+            format
+                .parse(&json::json!("15/Nov/2023:00:13:20 +0200"))
+                .unwrap(),
+            micros
+        );
+        let format = TimestampFormat::Custom("%Y-%m-%d".to_string());
+        assert_eq!(
+            format.parse(&json::json!("2023-11-14")).unwrap(),
+            micros - (22 * 3600 + 13 * 60 + 20) * 1_000_000
+        );
+        assert!(format.parse(&json::json!("14/11/2023")).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_settings() {
+        let settings: TimestampSettings = json::from_str(
+            r#"{"field":"event_time","format":{"custom":"%Y"},"on_error":"use_ingest_time"}"#,
+        )
+        .unwrap();
+        assert_eq!(settings.format, TimestampFormat::Custom("%Y".to_string()));
+        assert_eq!(settings.on_error, TimestampErrorPolicy::UseIngestTime);
+        let settings: TimestampSettings =
+            json::from_str(r#"{"field":"event_time","format":"rfc3339"}"#).unwrap();
+        assert_eq!(settings.format, TimestampFormat::Rfc3339);
+        assert_eq!(settings.on_error, TimestampErrorPolicy::Reject);
+
+        let stream_settings = StreamSettings {
+            timestamp_settings: Some(settings.clone()),
+            ..Default::default()
+        };
+        let data = json::to_string(&stream_settings).unwrap();
+        let stream_settings = StreamSettings::from(data.as_str());
+        assert_eq!(stream_settings.timestamp_settings, Some(settings));
+    }
+
+    #[test]
+    fn test_stream_settings_field_access() {
+        let field_access = vec![FieldAccess {
+            role: "member".to_string(),
+            allow: vec![],
+            deny: vec!["email".to_string()],
+        }];
+        let stream_settings = StreamSettings {
+            field_access: field_access.clone(),
+            ..Default::default()
+        };
+        let data = json::to_string(&stream_settings).unwrap();
+        assert!(data.contains(r#""field_access":[{"role":"member","deny":["email"]}]"#));
+        let stream_settings = StreamSettings::from(data.as_str());
+        assert_eq!(stream_settings.field_access, field_access);
+
+        let data = json::to_string(&StreamSettings::default()).unwrap();
+        assert!(!data.contains("field_access"));
+    }
+
+    fn required_fields() -> Vec<RequiredField> {
+        vec![
+            RequiredField {
+                name: "level".to_string(),
+                field_type: RequiredFieldType::String,
+            },
+            RequiredField {
+                name: "code".to_string(),
+                field_type: RequiredFieldType::Integer,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_check_required_fields_conforming() {
+        let record = json::json!({"level": "error", "code": 500, "extra": true});
+        assert!(check_required_fields(&required_fields(), record.as_object().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_check_required_fields_missing() {
+        let record = json::json!({"level": "error"});
+        assert_eq!(
+            check_required_fields(&required_fields(), record.as_object().unwrap()),
+            Err("required field [code] is missing".to_string())
+        );
+        let record = json::json!({"level": "error", "code": null});
+        assert!(check_required_fields(&required_fields(), record.as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_check_required_fields_wrong_type() {
+        let record = json::json!({"level": "error", "code": "500"});
+        assert_eq!(
+            check_required_fields(&required_fields(), record.as_object().unwrap()),
+            Err("required field [code] should be of type integer".to_string())
+        );
+        let record = json::json!({"level": "error", "code": 1.5});
+        assert!(check_required_fields(&required_fields(), record.as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_stream_settings_required_fields() {
+        let stream_settings = StreamSettings {
+            required_fields: required_fields(),
+            ..Default::default()
+        };
+        let data = json::to_string(&stream_settings).unwrap();
+        assert!(data.contains(r#"{"name":"level","type":"string"}"#));
+        let stream_settings = StreamSettings::from(data.as_str());
+        assert_eq!(stream_settings.required_fields, required_fields());
+    }
+
+    #[test]
+    fn test_stream_settings_flatten_array() {
+        let stream_settings = StreamSettings {
+            flatten_level: Some(3),
+            flatten_array: Some(FlattenArrayMode::IndexSuffix),
+            ..Default::default()
+        };
+        let data = json::to_string(&stream_settings).unwrap();
+        assert!(data.contains(r#""flatten_array":"index_suffix""#));
+        let stream_settings = StreamSettings::from(data.as_str());
+        assert_eq!(stream_settings.flatten_level, Some(3));
+        assert_eq!(
+            stream_settings.flatten_array,
+            Some(FlattenArrayMode::IndexSuffix)
+        );
+    }
+
+    #[test]
+    fn test_stream_settings_query_range_policy() {
+        let data = json::to_string(&StreamSettings::default()).unwrap();
+        assert!(!data.contains("query_range_policy"));
+        assert_eq!(StreamSettings::from(data.as_str()).query_range_policy, None);
+
+        let stream_settings = StreamSettings {
+            max_query_range: 24,
+            query_range_policy: Some("reject".to_string()),
+            ..Default::default()
+        };
+        let data = json::to_string(&stream_settings).unwrap();
+        let stream_settings = StreamSettings::from(data.as_str());
+        assert_eq!(
+            stream_settings.query_range_policy.as_deref(),
+            Some("reject")
+        );
+    }
 }
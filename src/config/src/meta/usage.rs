@@ -47,6 +47,8 @@ pub enum TriggerDataType {
     Alert,
     #[serde(rename = "derived_stream")]
     DerivedStream,
+    #[serde(rename = "scheduled_export")]
+    ScheduledExport,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
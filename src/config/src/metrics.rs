@@ -66,6 +66,42 @@ pub static HTTP_RESPONSE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     .expect("Metric created")
 });
 
+// latency percentiles by endpoint, the stream is not a label to keep the cardinality bounded
+pub static HTTP_SEARCH_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "http_search_latency",
+            "HTTP search latency in seconds. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .buckets(parse_buckets(
+            &crate::config::get_config()
+                .common
+                .prometheus_search_latency_buckets,
+        ))
+        .const_labels(create_const_labels()),
+        &["endpoint", "organization"],
+    )
+    .expect("Metric created")
+});
+pub static HTTP_INGEST_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "http_ingest_latency",
+            "HTTP ingestion latency in seconds. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .buckets(parse_buckets(
+            &crate::config::get_config()
+                .common
+                .prometheus_ingest_latency_buckets,
+        ))
+        .const_labels(create_const_labels()),
+        &["endpoint", "organization"],
+    )
+    .expect("Metric created")
+});
+
 // grpc latency
 pub static GRPC_INCOMING_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
     IntCounterVec::new(
@@ -162,6 +198,20 @@ pub static INGEST_WAL_READ_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static INGEST_WAL_PARQUET_VERIFY_FAILED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_wal_parquet_verify_failed",
+            "Ingestor parquet files failed verification before the WAL file is removed. "
+                .to_owned()
+                + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream_type"],
+    )
+    .expect("Metric created")
+});
 pub static INGEST_MEMTABLE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
         Opts::new(
@@ -225,6 +275,44 @@ pub static INGEST_WAL_LOCK_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     .expect("Metric created")
 });
 
+pub static INGEST_WAL_OPEN_WRITERS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "ingest_wal_open_writers",
+            "Ingestor open wal writers.".to_owned(),
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+
+pub static INGEST_WAL_WRITER_EVICTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_wal_writer_evictions",
+            "Ingestor wal writers closed over the max open writers.".to_owned(),
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_LAG_SECONDS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "ingest_lag_seconds",
+            "Seconds between now and the newest record of the stream. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type"],
+    )
+    .expect("Metric created")
+});
+
 // querier memory cache stats
 pub static QUERY_MEMORY_CACHE_LIMIT_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
@@ -327,6 +415,18 @@ pub static QUERY_DISK_RESULT_CACHE_FILES: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static QUERY_RESULT_CACHE_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_result_cache_requests",
+            "Querier result cache lookups, by result hit or miss. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "result"],
+    )
+    .expect("Metric created")
+});
 
 // compactor stats
 pub static COMPACT_USED_TIME: Lazy<CounterVec> = Lazy::new(|| {
@@ -365,6 +465,33 @@ pub static COMPACT_MERGED_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static COMPACT_RETENTION_RECLAIMED_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "compact_retention_reclaimed_bytes",
+            "Bytes of the files deleted by the data retention. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type"],
+    )
+    .expect("Metric created")
+});
+pub static COMPACT_MERGED_FILE_SIZE: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "compact_merged_file_size",
+            "Compactor merged file size in MB. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .buckets(vec![
+            1.0, 4.0, 16.0, 32.0, 64.0, 128.0, 256.0, 512.0, 1024.0, 2048.0,
+        ])
+        .const_labels(create_const_labels()),
+        &["organization", "stream_type"],
+    )
+    .expect("Metric created")
+});
 pub static COMPACT_DELAY_HOURS: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
         Opts::new(
@@ -485,6 +612,18 @@ pub static STORAGE_READ_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static STORAGE_RETRIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "storage_retries",
+            "Retried operations of the object storage",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["method_type", "result"],
+    )
+    .expect("Metric created")
+});
 pub static STORAGE_TIME: Lazy<CounterVec> = Lazy::new(|| {
     CounterVec::new(
         Opts::new(
@@ -606,6 +745,19 @@ pub static MEMORY_USAGE: Lazy<IntGaugeVec> = Lazy::new(|| {
     .expect("Metric created")
 });
 
+pub static TRACES_SAMPLING_TRACES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "traces_sampling_traces",
+            "Traces kept or dropped by the sampling. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "decision"],
+    )
+    .expect("Metric created")
+});
+
 pub static SPAN_DURATION_MILLISECONDS: Lazy<HistogramVec> = Lazy::new(|| {
     HistogramVec::new(
         HistogramOpts::new("span_duration_milliseconds", "span duration milliseconds")
@@ -643,6 +795,30 @@ pub static QUERY_PENDING_NUMS: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static QUERY_WORK_GROUP_RUNNING_NUMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_work_group_running_nums",
+            "Running query numbers of each work group",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["work_group"],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_WORK_GROUP_QUEUED_NUMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_work_group_queued_nums",
+            "Query numbers waiting for a slot of each work group",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["work_group"],
+    )
+    .expect("Metric created")
+});
 pub static QUERY_TIMEOUT_NUMS: Lazy<IntCounterVec> = Lazy::new(|| {
     IntCounterVec::new(
         Opts::new("query_timeout_nums", "Timeout query numbers")
@@ -661,6 +837,30 @@ pub static QUERY_CANCELED_NUMS: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static QUERY_HEDGED_NUMS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_hedged_nums",
+            "Hedged query numbers of the router, by the request that won",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["winner"],
+    )
+    .expect("Metric created")
+});
+pub static ALERT_RESULT_CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "alert_result_cache_hits",
+            "Alert searches served from the alert result cache. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream_type"],
+    )
+    .expect("Metric created")
+});
 
 // This corresponds to mysql or pgsql queries, not sqlite as that is local and can be ignored
 pub static DB_QUERY_NUMS: Lazy<IntCounterVec> = Lazy::new(|| {
@@ -717,6 +917,12 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(HTTP_RESPONSE_TIME.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(HTTP_SEARCH_LATENCY.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(HTTP_INGEST_LATENCY.clone()))
+        .expect("Metric registered");
 
     // grpc latency
     registry
@@ -742,6 +948,9 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(INGEST_WAL_READ_BYTES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_WAL_PARQUET_VERIFY_FAILED.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(INGEST_MEMTABLE_BYTES.clone()))
         .expect("Metric registered");
@@ -757,6 +966,15 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(INGEST_WAL_LOCK_TIME.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_WAL_OPEN_WRITERS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_WAL_WRITER_EVICTIONS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_LAG_SECONDS.clone()))
+        .expect("Metric registered");
 
     // querier stats
     registry
@@ -785,12 +1003,24 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(QUERY_PENDING_NUMS.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_WORK_GROUP_RUNNING_NUMS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_WORK_GROUP_QUEUED_NUMS.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(QUERY_TIMEOUT_NUMS.clone()))
         .expect("Metric registered");
     registry
         .register(Box::new(QUERY_CANCELED_NUMS.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_HEDGED_NUMS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(ALERT_RESULT_CACHE_HITS.clone()))
+        .expect("Metric registered");
 
     // compactor stats
     registry
@@ -802,6 +1032,12 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(COMPACT_MERGED_BYTES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(COMPACT_RETENTION_RECLAIMED_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(COMPACT_MERGED_FILE_SIZE.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(COMPACT_DELAY_HOURS.clone()))
         .expect("Metric registered");
@@ -831,6 +1067,9 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(STORAGE_TIME.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(STORAGE_RETRIES.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(STORAGE_READ_REQUESTS.clone()))
         .expect("Metric registered");
@@ -871,6 +1110,9 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(MEMORY_USAGE.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(TRACES_SAMPLING_TRACES.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(SPAN_DURATION_MILLISECONDS.clone()))
         .expect("Metric registered");
@@ -880,6 +1122,9 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(QUERY_DISK_RESULT_CACHE_FILES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_RESULT_CACHE_REQUESTS.clone()))
+        .expect("Metric registered");
 
     // db stats
     registry
@@ -898,6 +1143,23 @@ fn register_metrics(registry: &Registry) {
         .expect("Metric registered");
 }
 
+/// Parses the comma separated buckets of a histogram, the invalid values are ignored and the
+/// default buckets are used if there is no valid value
+fn parse_buckets(s: &str) -> Vec<f64> {
+    let mut buckets = s
+        .split(',')
+        .filter_map(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite() && *v > 0.0)
+        .collect::<Vec<_>>();
+    buckets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    buckets.dedup();
+    if buckets.is_empty() {
+        prometheus::DEFAULT_BUCKETS.to_vec()
+    } else {
+        buckets
+    }
+}
+
 fn create_const_labels() -> HashMap<String, String> {
     let cfg = crate::config::get_config();
     let mut labels = HashMap::new();
@@ -918,3 +1180,14 @@ pub fn create_prometheus_handler() -> PrometheusMetrics {
         .build()
         .expect("Prometheus build failed")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_buckets() {
+        assert_eq!(parse_buckets("0.5, 0.1,1,x,0.1,-1"), vec![0.1, 0.5, 1.0]);
+        assert_eq!(parse_buckets(""), prometheus::DEFAULT_BUCKETS.to_vec());
+    }
+}
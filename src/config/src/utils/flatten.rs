@@ -15,6 +15,8 @@
 
 use serde_json::value::{Map, Value};
 
+use crate::meta::stream::FlattenArrayMode;
+
 const KEY_SEPARATOR: &str = "_";
 
 #[inline]
@@ -31,7 +33,19 @@ pub fn flatten(to_flatten: Value) -> Result<Value, anyhow::Error> {
 /// # Errors
 /// Will return `Err` if `to_flatten` it's not an object, or if flattening the
 /// object would result in two or more keys colliding.
+#[inline]
 pub fn flatten_with_level(to_flatten: Value, max_level: u32) -> Result<Value, anyhow::Error> {
+    flatten_with_options(to_flatten, max_level, FlattenArrayMode::Json)
+}
+
+/// Flattens the provided JSON object like [`flatten_with_level`], the objects deeper than
+/// `max_level` are stored as JSON strings, the arrays are stored as JSON strings or flattened
+/// with the index of each element as key suffix, depending on `array_mode`.
+pub fn flatten_with_options(
+    to_flatten: Value,
+    max_level: u32,
+    array_mode: FlattenArrayMode,
+) -> Result<Value, anyhow::Error> {
     // quick check to see if we have an object`
     let to_flatten = match to_flatten {
         Value::Object(v) => {
@@ -54,7 +68,23 @@ pub fn flatten_with_level(to_flatten: Value, max_level: u32) -> Result<Value, an
     };
 
     let mut flat = Map::<String, Value>::new();
-    flatten_value(to_flatten, "".to_owned(), max_level, 0, &mut flat).map(|_x| Value::Object(flat))
+    let opts = FlattenOpts {
+        max_level,
+        array_mode,
+    };
+    flatten_value(to_flatten, "".to_owned(), &opts, 0, &mut flat).map(|_x| Value::Object(flat))
+}
+
+struct FlattenOpts {
+    max_level: u32,
+    array_mode: FlattenArrayMode,
+}
+
+impl FlattenOpts {
+    #[inline]
+    fn exceeds_level(&self, depth: u32) -> bool {
+        self.max_level > 0 && depth >= self.max_level
+    }
 }
 
 /// Flattens the passed JSON value (`current`), whose path is `parent_key` and
@@ -63,16 +93,16 @@ pub fn flatten_with_level(to_flatten: Value, max_level: u32) -> Result<Value, an
 fn flatten_value(
     current: Value,
     parent_key: String,
-    max_level: u32,
+    opts: &FlattenOpts,
     depth: u32,
     flattened: &mut Map<String, Value>,
 ) -> Result<(), anyhow::Error> {
     match current {
         Value::Object(map) => {
-            flatten_object(map, &parent_key, max_level, depth, flattened)?;
+            flatten_object(map, &parent_key, opts, depth, flattened)?;
         }
         Value::Array(arr) => {
-            flatten_array(arr, &parent_key, max_level, depth, flattened)?;
+            flatten_array(arr, &parent_key, opts, depth, flattened)?;
         }
         _ => {
             flattened.insert(parent_key, current);
@@ -87,16 +117,16 @@ fn flatten_value(
 fn flatten_object(
     current: Map<String, Value>,
     parent_key: &str,
-    max_level: u32,
+    opts: &FlattenOpts,
     depth: u32,
     flattened: &mut Map<String, Value>,
 ) -> Result<(), anyhow::Error> {
     if current.is_empty() {
         return Ok(());
     }
-    if max_level > 0 && depth >= max_level {
+    if opts.exceeds_level(depth) {
         let v = Value::String(Value::Object(current).to_string());
-        flatten_value(v, parent_key.to_string(), max_level, depth, flattened)?;
+        flatten_value(v, parent_key.to_string(), opts, depth, flattened)?;
         return Ok(());
     }
     for (mut k, v) in current.into_iter() {
@@ -106,7 +136,7 @@ fn flatten_object(
         } else {
             k
         };
-        flatten_value(v, parent_key, max_level, depth + 1, flattened)?;
+        flatten_value(v, parent_key, opts, depth + 1, flattened)?;
     }
     Ok(())
 }
@@ -117,19 +147,22 @@ fn flatten_object(
 fn flatten_array(
     current: Vec<Value>,
     parent_key: &str,
-    max_level: u32,
+    opts: &FlattenOpts,
     depth: u32,
     flattened: &mut Map<String, Value>,
 ) -> Result<(), anyhow::Error> {
     if current.is_empty() {
         return Ok(());
     }
-    // for (i, obj) in current.iter().enumerate() {
-    //     let parent_key = format!("{}{}{}", parent_key, KEY_SEPARATOR, i);
-    //     flatten_value(obj, parent_key, depth + 1, flattened)?;
-    // }
-    let v = Value::String(Value::Array(current.to_vec()).to_string());
-    flatten_value(v, parent_key.to_string(), max_level, depth, flattened)?;
+    if opts.array_mode == FlattenArrayMode::IndexSuffix && !opts.exceeds_level(depth) {
+        for (i, v) in current.into_iter().enumerate() {
+            let parent_key = format!("{}{}{}", parent_key, KEY_SEPARATOR, i);
+            flatten_value(v, parent_key, opts, depth + 1, flattened)?;
+        }
+        return Ok(());
+    }
+    let v = Value::String(Value::Array(current).to_string());
+    flatten_value(v, parent_key.to_string(), opts, depth, flattened)?;
     Ok(())
 }
 
@@ -172,6 +205,14 @@ pub fn format_key(key: &mut String) {
     }
 }
 
+/// Returns the name of the flattened field of a nested path, e.g. `kubernetes.namespace` is
+/// stored as `kubernetes_namespace`
+pub fn flattened_field_name(path: &str) -> String {
+    let mut key = path.to_string();
+    format_key(&mut key);
+    key
+}
+
 fn check_key(key: &str) -> bool {
     key.chars()
         .all(|c| c.is_lowercase() || c.is_numeric() || c == '_')
@@ -516,6 +557,63 @@ mod tests {
         assert_eq!(output, expected_output_level4);
     }
 
+    #[test]
+    fn test_flatten_with_options_exceeds_level() {
+        let input = json!({
+            "a": {"b": {"c": {"d": "e", "f": [1, 2]}}, "g": 1},
+            "h": "i"
+        });
+        let expected = json!({
+            "a_b": "{\"c\":{\"d\":\"e\",\"f\":[1,2]}}",
+            "a_g": 1,
+            "h": "i"
+        });
+        for array_mode in [FlattenArrayMode::Json, FlattenArrayMode::IndexSuffix] {
+            let output = flatten_with_options(input.clone(), 2, array_mode).unwrap();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_flatten_with_options_array_mode() {
+        let input = json!({
+            "tags": ["a", "b"],
+            "spans": [{"id": 1, "events": [{"name": "x"}]}, {"id": 2}],
+            "empty": []
+        });
+        let output = flatten_with_options(input.clone(), 0, FlattenArrayMode::Json).unwrap();
+        assert_eq!(
+            output,
+            json!({
+                "tags": "[\"a\",\"b\"]",
+                "spans": "[{\"events\":[{\"name\":\"x\"}],\"id\":1},{\"id\":2}]"
+            })
+        );
+        let output = flatten_with_options(input.clone(), 0, FlattenArrayMode::IndexSuffix).unwrap();
+        assert_eq!(
+            output,
+            json!({
+                "tags_0": "a",
+                "tags_1": "b",
+                "spans_0_id": 1,
+                "spans_0_events_0_name": "x",
+                "spans_1_id": 2
+            })
+        );
+        // the elements beyond the level are stored as JSON strings
+        let output = flatten_with_options(input, 3, FlattenArrayMode::IndexSuffix).unwrap();
+        assert_eq!(
+            output,
+            json!({
+                "tags_0": "a",
+                "tags_1": "b",
+                "spans_0_id": 1,
+                "spans_0_events": "[{\"name\":\"x\"}]",
+                "spans_1_id": 2
+            })
+        );
+    }
+
     #[test]
     fn test_form_keys() {
         let test_cases = [
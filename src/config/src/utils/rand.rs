@@ -13,7 +13,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use rand::distributions::{Alphanumeric, DistString};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    Rng,
+};
 
 pub fn get_rand_element<T>(arr: &[T]) -> &T {
     let mut buf = [0u8; 1];
@@ -27,7 +30,5 @@ pub fn generate_random_string(len: usize) -> String {
 
 /// Generate random number within the given range
 pub fn get_rand_num_within(min: u64, max: u64) -> u64 {
-    let mut buf = [0u8; 1];
-    getrandom::getrandom(&mut buf).unwrap();
-    min + buf[0] as u64 % (max - min)
+    rand::thread_rng().gen_range(min..max)
 }
@@ -13,8 +13,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::ops::ControlFlow;
+use std::{collections::HashSet, ops::ControlFlow};
 
+use once_cell::sync::Lazy;
 use sqlparser::{
     ast::{Expr, Function, GroupByExpr, Query, SelectItem, SetExpr, Statement, Visit, Visitor},
     dialect::GenericDialect,
@@ -33,6 +34,16 @@ pub const AGGREGATE_UDF_LIST: [&str; 9] = [
     "percentile_cont",
 ];
 
+/// The names of the aggregate functions of DataFusion, with their aliases
+static AGGREGATE_FUNCTIONS: Lazy<HashSet<String>> = Lazy::new(|| {
+    datafusion::functions_aggregate::all_default_aggregate_functions()
+        .iter()
+        .flat_map(|f| std::iter::once(f.name()).chain(f.aliases().iter().map(|v| v.as_str())))
+        .chain(AGGREGATE_UDF_LIST)
+        .map(|v| v.to_lowercase())
+        .collect()
+});
+
 pub fn is_aggregate_query(query: &str) -> Result<bool, sqlparser::parser::ParserError> {
     let ast = Parser::parse_sql(&GenericDialect {}, query)?;
 
@@ -170,3 +181,78 @@ impl Visitor for SubqueryVisitor {
         ControlFlow::Continue(())
     }
 }
+
+/// Returns the columns referenced by name anywhere in the query, e.g. in the projection,
+/// the filters or the arguments of the aggregate functions, a wildcard isn't a reference
+pub fn referenced_columns(query: &str) -> Result<HashSet<String>, sqlparser::parser::ParserError> {
+    let ast = Parser::parse_sql(&GenericDialect {}, query)?;
+    let mut visitor = ColumnVisitor::default();
+    for statement in ast.iter() {
+        statement.visit(&mut visitor);
+    }
+    Ok(visitor.columns)
+}
+
+/// Returns what keeps the expression from being computed from a single row: a subquery, an
+/// aggregate function or a window function. None if it's computed from a single row.
+pub fn non_row_expr(expr: &Expr) -> Option<String> {
+    let mut visitor = NonRowExprVisitor;
+    match expr.visit(&mut visitor) {
+        ControlFlow::Break(v) => Some(v),
+        ControlFlow::Continue(()) => None,
+    }
+}
+
+struct NonRowExprVisitor;
+
+impl Visitor for NonRowExprVisitor {
+    type Break = String;
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        match expr {
+            Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. } => {
+                ControlFlow::Break("a subquery".to_string())
+            }
+            Expr::Function(func) if func.over.is_some() => {
+                ControlFlow::Break(format!("the window function [{}]", func.name))
+            }
+            Expr::Function(func)
+                if AGGREGATE_FUNCTIONS.contains(&func.name.to_string().to_lowercase()) =>
+            {
+                ControlFlow::Break(format!("the aggregate function [{}]", func.name))
+            }
+            _ => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// Returns the columns referenced by name in the expression
+pub fn expr_columns(expr: &Expr) -> HashSet<String> {
+    let mut visitor = ColumnVisitor::default();
+    expr.visit(&mut visitor);
+    visitor.columns
+}
+
+#[derive(Default)]
+struct ColumnVisitor {
+    columns: HashSet<String>,
+}
+
+impl Visitor for ColumnVisitor {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        match expr {
+            Expr::Identifier(ident) => {
+                self.columns.insert(ident.value.clone());
+            }
+            Expr::CompoundIdentifier(idents) => {
+                if let Some(ident) = idents.last() {
+                    self.columns.insert(ident.value.clone());
+                }
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
@@ -215,6 +215,42 @@ pub fn parse_str_to_timestamp_micros_as_option(v: &str) -> Option<i64> {
     }
 }
 
+/// Parses a time relative to `now`, both in microseconds: `now`, `now-30s`, `now-15m`,
+/// `now-1h`, `now-1d` or `now-2w`
+pub fn parse_relative_time(expr: &str, now: i64) -> Result<i64, anyhow::Error> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid relative time [{expr}], the format is now or now-<number><unit>, the unit is one of s, m, h, d, w"
+        )
+    };
+    let offset = expr.trim().strip_prefix("now").ok_or_else(invalid)?;
+    if offset.is_empty() {
+        return Ok(now);
+    }
+    let offset = offset.strip_prefix('-').ok_or_else(invalid)?;
+    let Some(unit) = offset.chars().last() else {
+        return Err(invalid());
+    };
+    let value = &offset[..offset.len() - unit.len_utf8()];
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let unit_micros = match unit {
+        's' => 1_000_000,
+        'm' => 60 * 1_000_000,
+        'h' => 3600 * 1_000_000,
+        'd' => DAY_MICRO_SECS,
+        'w' => 7 * DAY_MICRO_SECS,
+        _ => return Err(invalid()),
+    };
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(|v| v.checked_mul(unit_micros))
+        .and_then(|v| now.checked_sub(v))
+        .ok_or_else(invalid)
+}
+
 /// Get the end of the day timestamp_micros
 pub fn end_of_the_day(timestamp: i64) -> i64 {
     let t = Utc.timestamp_nanos((timestamp + DAY_MICRO_SECS) * 1000);
@@ -396,4 +432,49 @@ mod tests {
             assert_eq!(end_of_the_day(t[i]), d[i]);
         }
     }
+
+    #[test]
+    fn test_parse_relative_time() {
+        let now = 1_700_000_000_000_000;
+        assert_eq!(parse_relative_time("now", now).unwrap(), now);
+        assert_eq!(
+            parse_relative_time("now-30s", now).unwrap(),
+            now - 30_000_000
+        );
+        assert_eq!(
+            parse_relative_time("now-15m", now).unwrap(),
+            now - 900_000_000
+        );
+        assert_eq!(
+            parse_relative_time("now-1h", now).unwrap(),
+            now - 3_600_000_000
+        );
+        assert_eq!(
+            parse_relative_time("now-1d", now).unwrap(),
+            now - DAY_MICRO_SECS
+        );
+        assert_eq!(
+            parse_relative_time("now-2w", now).unwrap(),
+            now - 14 * DAY_MICRO_SECS
+        );
+        assert_eq!(parse_relative_time(" now-0s ", now).unwrap(), now);
+
+        for expr in [
+            "",
+            "now-",
+            "now-m",
+            "now-15",
+            "now-15y",
+            "now+15m",
+            "now-+15m",
+            "now--15m",
+            "now-1.5h",
+            "now-15mm",
+            "now-15m-1h",
+            "later",
+            "now-99999999999999999d",
+        ] {
+            assert!(parse_relative_time(expr, now).is_err(), "{expr}");
+        }
+    }
 }
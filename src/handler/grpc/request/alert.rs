@@ -0,0 +1,35 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use proto::cluster_rpc::{
+    alert_evaluator_server::AlertEvaluator, EvaluateAlertsRequest, EvaluateAlertsResponse,
+};
+use tonic::{Request, Response, Status};
+
+use crate::service::alerts::evaluator;
+
+#[derive(Default)]
+pub struct AlertEvaluatorServerImpl;
+
+#[tonic::async_trait]
+impl AlertEvaluator for AlertEvaluatorServerImpl {
+    async fn evaluate_alerts(
+        &self,
+        request: Request<EvaluateAlertsRequest>,
+    ) -> Result<Response<EvaluateAlertsResponse>, Status> {
+        let resp = evaluator::evaluate_batch(request.into_inner(), evaluator::evaluate_alert).await;
+        Ok(Response::new(resp))
+    }
+}
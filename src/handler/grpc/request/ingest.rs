@@ -13,10 +13,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::future::Future;
+
 use actix_web::http::StatusCode;
 use config::utils::json;
-use proto::cluster_rpc::{ingest_server::Ingest, IngestionRequest, IngestionResponse, StreamType};
-use tonic::{Request, Response, Status};
+use futures::{Stream, StreamExt};
+use proto::cluster_rpc::{
+    ingest_server::Ingest, IngestionRequest, IngestionResponse, IngestionStreamAck,
+    IngestionStreamRequest, MemtableMaxTimestampRequest, MemtableMaxTimestampResponse, StreamType,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
 
 use crate::service::ingestion::create_log_ingestion_req;
 
@@ -30,86 +38,232 @@ impl Ingest for Ingester {
         request: Request<IngestionRequest>,
     ) -> Result<Response<IngestionResponse>, Status> {
         // let metadata = request.metadata().clone();
+        let reply = match ingest_request(request.into_inner()).await {
+            Ok(_) => IngestionResponse {
+                status_code: 200,
+                message: "OK".to_string(),
+            },
+            Err(err) => IngestionResponse {
+                status_code: 500,
+                message: err.to_string(),
+            },
+        };
+        Ok(Response::new(reply))
+    }
+
+    type IngestStreamStream = ReceiverStream<Result<IngestionStreamAck, Status>>;
+
+    async fn ingest_stream(
+        &self,
+        request: Request<Streaming<IngestionStreamRequest>>,
+    ) -> Result<Response<Self::IngestStreamStream>, Status> {
+        let stream = request.into_inner();
+        let (tx, rx) = mpsc::channel(2);
+        tokio::spawn(handle_stream(stream, tx, ingest_request, || {
+            ingester::check_memtable_size().is_err()
+        }));
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn memtable_max_timestamp(
+        &self,
+        request: Request<MemtableMaxTimestampRequest>,
+    ) -> Result<Response<MemtableMaxTimestampResponse>, Status> {
         let req = request.into_inner();
-        let org_id = req.org_id;
-        let stream_name = req.stream_name;
-        let in_data = req.data.unwrap_or_default();
-
-        let resp = match StreamType::try_from(req.stream_type) {
-            Ok(StreamType::Logs) => {
-                let log_ingestion_type = req.ingestion_type.unwrap_or_default();
-                let data = bytes::Bytes::from(in_data.data);
-                match create_log_ingestion_req(log_ingestion_type, &data) {
-                    Err(e) => Err(e),
-                    Ok(ingestion_req) => crate::service::logs::ingest::ingest(
-                        0,
-                        &org_id,
-                        &stream_name,
-                        ingestion_req,
-                        "",
-                        None,
-                    )
-                    .await
-                    .map_or_else(Err, |_| Ok(())),
-                }
-            }
-            Ok(StreamType::EnrichmentTables) => {
-                let json_records: Vec<json::Map<String, json::Value>> =
-                    json::from_slice(&in_data.data).unwrap_or({
-                        let vec_value: Vec<json::Value> = json::from_slice(&in_data.data).unwrap();
-                        vec_value
-                            .into_iter()
-                            .filter_map(|v| match v {
-                                json::Value::Object(map) => Some(map),
-                                _ => None,
-                            })
-                            .collect()
-                    });
-                match crate::service::enrichment_table::save_enrichment_data(
+        let max_ts = ingester::get_max_timestamp(&req.org_id, &req.stream_type, &req.stream_name);
+        Ok(Response::new(MemtableMaxTimestampResponse { max_ts }))
+    }
+}
+
+async fn ingest_request(req: IngestionRequest) -> Result<(), anyhow::Error> {
+    let org_id = req.org_id;
+    let stream_name = req.stream_name;
+    let in_data = req.data.unwrap_or_default();
+
+    match StreamType::try_from(req.stream_type) {
+        Ok(StreamType::Logs) => {
+            let log_ingestion_type = req.ingestion_type.unwrap_or_default();
+            let data = bytes::Bytes::from(in_data.data);
+            match create_log_ingestion_req(log_ingestion_type, &data) {
+                Err(e) => Err(e),
+                Ok(ingestion_req) => crate::service::logs::ingest::ingest(
+                    0,
                     &org_id,
                     &stream_name,
-                    json_records,
-                    true,
+                    ingestion_req,
+                    "",
+                    None,
                 )
                 .await
-                {
-                    Err(e) => Err(anyhow::anyhow!(
-                        "Internal gPRC ingestion service errors saving enrichment data: {}",
-                        e.to_string()
-                    )),
-                    Ok(res) => {
-                        if res.status() != StatusCode::OK {
-                            let status: StatusCode = res.status();
-                            log::error!(
-                                "Internal gPRC ingestion service errors saving enrichment data: code: {}, body: {:?}",
-                                status,
-                                res.into_body()
-                            );
-                            Err(anyhow::anyhow!(
-                                "Internal gPRC ingestion service errors saving enrichment data: http code {}",
-                                status
-                            ))
-                        } else {
-                            Ok(())
-                        }
+                .map_or_else(Err, |_| Ok(())),
+            }
+        }
+        Ok(StreamType::EnrichmentTables) => {
+            let json_records: Vec<json::Map<String, json::Value>> = json::from_slice(&in_data.data)
+                .unwrap_or({
+                    let vec_value: Vec<json::Value> = json::from_slice(&in_data.data).unwrap();
+                    vec_value
+                        .into_iter()
+                        .filter_map(|v| match v {
+                            json::Value::Object(map) => Some(map),
+                            _ => None,
+                        })
+                        .collect()
+                });
+            match crate::service::enrichment_table::save_enrichment_data(
+                &org_id,
+                &stream_name,
+                json_records,
+                true,
+            )
+            .await
+            {
+                Err(e) => Err(anyhow::anyhow!(
+                    "Internal gPRC ingestion service errors saving enrichment data: {}",
+                    e.to_string()
+                )),
+                Ok(res) => {
+                    if res.status() != StatusCode::OK {
+                        let status: StatusCode = res.status();
+                        log::error!(
+                            "Internal gPRC ingestion service errors saving enrichment data: code: {}, body: {:?}",
+                            status,
+                            res.into_body()
+                        );
+                        Err(anyhow::anyhow!(
+                            "Internal gPRC ingestion service errors saving enrichment data: http code {}",
+                            status
+                        ))
+                    } else {
+                        Ok(())
                     }
                 }
             }
-            _ => Err(anyhow::anyhow!(
-                "Internal gPRC ingestion service currently only supports Logs and EnrichmentTables",
-            )),
+        }
+        Ok(StreamType::Metrics) => {
+            // internal metrics ingestion writes prepared samples, eg: the rollup streams
+            match json::from_slice::<Vec<json::Map<String, json::Value>>>(&in_data.data) {
+                Err(e) => Err(anyhow::anyhow!(
+                    "Internal gPRC ingestion service errors parsing metrics samples: {e}"
+                )),
+                Ok(records) => {
+                    crate::service::metrics::json::ingest_samples(&org_id, &stream_name, records)
+                        .await
+                }
+            }
+        }
+        _ => Err(anyhow::anyhow!(
+            "Internal gPRC ingestion service currently only supports Logs, Metrics and EnrichmentTables",
+        )),
+    }
+}
+
+/// Ingests the batches of a stream one by one in the order they are received, and acks each
+/// batch by its seq once it is processed. A reset stream is dropped, the client reconnects and
+/// resends the batches after the last acked seq, so no processed batch is sent again.
+async fn handle_stream<S, F, Fut>(
+    mut stream: S,
+    tx: mpsc::Sender<Result<IngestionStreamAck, Status>>,
+    ingest: F,
+    overloaded: impl Fn() -> bool,
+) where
+    S: Stream<Item = Result<IngestionStreamRequest, Status>> + Unpin,
+    F: Fn(IngestionRequest) -> Fut,
+    Fut: Future<Output = Result<(), anyhow::Error>>,
+{
+    let mut ack = IngestionStreamAck::default();
+    while let Some(item) = stream.next().await {
+        let req = match item {
+            Ok(req) => req,
+            Err(e) => {
+                log::warn!("[INGEST:STREAM] stream reset at seq {}: {e}", ack.seq);
+                break;
+            }
         };
+        // the batch was already processed in this stream
+        if (ack.accepted > 0 || ack.failed > 0) && req.seq <= ack.seq {
+            continue;
+        }
 
-        let reply = match resp {
-            Ok(_) => IngestionResponse {
-                status_code: 200,
-                message: "OK".to_string(),
-            },
-            Err(err) => IngestionResponse {
-                status_code: 500,
-                message: err.to_string(),
-            },
+        let ret = match req.request {
+            Some(request) => ingest(request).await,
+            None => Err(anyhow::anyhow!("batch {} without request", req.seq)),
         };
-        Ok(Response::new(reply))
+        match ret {
+            Ok(_) => ack.accepted += 1,
+            Err(e) => {
+                ack.failed += 1;
+                ack.message = e.to_string();
+            }
+        }
+        ack.seq = req.seq;
+        ack.backpressure = overloaded();
+        if tx.send(Ok(ack.clone())).await.is_err() {
+            return; // the client is gone
+        }
+        ack.message.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn batch(seq: u64) -> IngestionStreamRequest {
+        IngestionStreamRequest {
+            seq,
+            request: Some(IngestionRequest {
+                stream_name: format!("s{seq}"),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_stream() {
+        let (req_tx, req_rx) = mpsc::channel(10);
+        let (ack_tx, mut ack_rx) = mpsc::channel(10);
+        for seq in 1..=5 {
+            req_tx.send(Ok(batch(seq))).await.unwrap();
+        }
+        // a batch resent in the same stream is skipped
+        req_tx.send(Ok(batch(3))).await.unwrap();
+        // the batches after a reset are not processed
+        req_tx.send(Err(Status::cancelled("reset"))).await.unwrap();
+        req_tx.send(Ok(batch(6))).await.unwrap();
+        drop(req_tx);
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let ingest = |req: IngestionRequest| {
+            let received = received.clone();
+            async move {
+                if req.stream_name == "s4" {
+                    return Err(anyhow::anyhow!("failed"));
+                }
+                received.lock().unwrap().push(req.stream_name);
+                Ok(())
+            }
+        };
+        handle_stream(ReceiverStream::new(req_rx), ack_tx, ingest, || false).await;
+
+        // the batches are ingested in order
+        assert_eq!(*received.lock().unwrap(), vec!["s1", "s2", "s3", "s5"]);
+        let mut acks = Vec::new();
+        while let Some(ack) = ack_rx.recv().await {
+            let ack = ack.unwrap();
+            acks.push((ack.seq, ack.accepted, ack.failed, ack.message));
+        }
+        assert_eq!(
+            acks,
+            vec![
+                (1, 1, 0, "".to_string()),
+                (2, 2, 0, "".to_string()),
+                (3, 3, 0, "".to_string()),
+                (4, 3, 1, "failed".to_string()),
+                (5, 4, 1, "".to_string()),
+            ]
+        );
     }
 }
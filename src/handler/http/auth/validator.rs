@@ -20,7 +20,7 @@ use actix_web::{
     web, Error,
 };
 use actix_web_httpauth::extractors::basic::BasicAuth;
-use config::{get_config, utils::base64};
+use config::{get_config, meta::stream::StreamType, utils::base64};
 #[cfg(feature = "enterprise")]
 use o2_enterprise::enterprise::common::infra::config::get_config as get_o2_config;
 
@@ -38,7 +38,7 @@ use crate::{
             redirect_response::RedirectResponseBuilder,
         },
     },
-    service::{db, users},
+    service::{api_keys, db, users},
 };
 
 pub const PKCE_STATE_ORG: &str = "o2_pkce_state";
@@ -556,6 +556,9 @@ async fn oo_validator_internal(
         validator(req, &username, &password, auth_info, path_prefix).await
     } else if auth_info.auth.starts_with("Bearer") {
         super::token::token_validator(req, auth_info).await
+    } else if let Some(key) = auth_info.auth.strip_prefix("ApiKey ") {
+        let key = key.trim().to_string();
+        validator_api_key(req, &key, auth_info, path_prefix).await
     } else if auth_info.auth.starts_with("{\"auth_ext\":") {
         let auth_tokens: AuthTokensExt =
             config::utils::json::from_str(&auth_info.auth).unwrap_or_default();
@@ -581,6 +584,46 @@ async fn oo_validator_internal(
     }
 }
 
+/// Validates a scoped API key, the request acts as the user who created the key so the key stops
+/// working when the user is removed from the org
+async fn validator_api_key(
+    req: ServiceRequest,
+    key: &str,
+    auth_info: AuthExtractor,
+    path_prefix: &str,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let cfg = get_config();
+    let path = match req
+        .request()
+        .path()
+        .strip_prefix(format!("{}{}", cfg.common.base_uri, path_prefix).as_str())
+    {
+        Some(path) => path,
+        None => req.request().path(),
+    };
+    let record = match api_keys::validate(key, req.method().as_str(), path).await {
+        Ok(record) => record,
+        Err(e) => {
+            log::warn!("[API_KEY] rejected request to {path}: {e}");
+            return Err((ErrorUnauthorized("Unauthorized Access"), req));
+        }
+    };
+    let Some(user) = users::get_user(Some(&record.org), &record.created_by).await else {
+        return Err((ErrorUnauthorized("Unauthorized Access"), req));
+    };
+
+    let mut req = req;
+    req.headers_mut().insert(
+        header::HeaderName::from_static("user_id"),
+        header::HeaderValue::from_str(&user.email).unwrap(),
+    );
+    if auth_info.bypass_check || check_permissions(&user.email, auth_info, Some(user.role)).await {
+        Ok(req)
+    } else {
+        Err((ErrorForbidden("Unauthorized Access"), req))
+    }
+}
+
 #[cfg(feature = "enterprise")]
 pub async fn get_user_email_from_auth_str(auth_str: &str) -> Option<String> {
     if auth_str.starts_with("Basic") {
@@ -750,6 +793,50 @@ pub(crate) async fn check_permissions(
     true
 }
 
+/// Checks the user can read the given stream
+#[cfg(feature = "enterprise")]
+pub(crate) async fn check_stream_permission(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    user_id: &str,
+    role: UserRole,
+) -> bool {
+    use o2_enterprise::enterprise::openfga::meta::mapping::OFGA_MODELS;
+
+    let stream_type_str = stream_type.to_string();
+    check_permissions(
+        user_id,
+        AuthExtractor {
+            auth: "".to_string(),
+            method: "GET".to_string(),
+            o2_type: format!(
+                "{}:{}",
+                OFGA_MODELS
+                    .get(stream_type_str.as_str())
+                    .map_or(stream_type_str.as_str(), |model| model.key),
+                stream_name
+            ),
+            org_id: org_id.to_string(),
+            bypass_check: false,
+            parent_id: "".to_string(),
+        },
+        Some(role),
+    )
+    .await
+}
+
+#[cfg(not(feature = "enterprise"))]
+pub(crate) async fn check_stream_permission(
+    _org_id: &str,
+    _stream_type: StreamType,
+    _stream_name: &str,
+    _user_id: &str,
+    _role: UserRole,
+) -> bool {
+    true
+}
+
 #[cfg(feature = "enterprise")]
 async fn list_objects(
     user_id: &str,
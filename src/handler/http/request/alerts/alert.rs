@@ -19,14 +19,19 @@ use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse};
 
 use crate::{
     common::{
+        infra::config::USERS,
         meta::{
-            alerts::alert::{Alert, AlertListFilter},
+            alerts::alert::{Alert, AlertBackfillResponse, AlertListFilter},
             dashboards::datetime_now,
             http::HttpResponse as MetaHttpResponse,
         },
-        utils::{auth::UserEmail, http::get_stream_type_from_request},
+        utils::{
+            auth::{is_root_user, UserEmail},
+            http::get_stream_type_from_request,
+        },
     },
-    service::alerts::alert,
+    handler::http::auth::validator::check_stream_permission,
+    service::alerts::{alert, backfill},
 };
 
 /// CreateAlert
@@ -59,10 +64,15 @@ pub async fn save_alert(
     let mut alert = alert.into_inner();
     alert.trigger_condition.frequency *= 60;
     alert.owner = Some(user_email.user_id.clone());
-    alert.last_edited_by = Some(user_email.user_id);
+    alert.last_edited_by = Some(user_email.user_id.clone());
     alert.updated_at = Some(datetime_now());
     alert.last_triggered_at = None;
     alert.last_satisfied_at = None;
+    if !can_read_source_org(&org_id, &stream_name, &alert, &user_email.user_id).await {
+        return Ok(MetaHttpResponse::forbidden(
+            "Unauthorized Access to the source organization",
+        ));
+    }
 
     match alert::save(&org_id, &stream_name, "", alert, true).await {
         Ok(_) => Ok(MetaHttpResponse::ok("Alert saved")),
@@ -100,6 +110,11 @@ pub async fn update_alert(
     // Hack for frequency: convert minutes to seconds
     let mut alert = alert.into_inner();
     alert.trigger_condition.frequency *= 60;
+    if !can_read_source_org(&org_id, &stream_name, &alert, &user_email.user_id).await {
+        return Ok(MetaHttpResponse::forbidden(
+            "Unauthorized Access to the source organization",
+        ));
+    }
     alert.last_edited_by = Some(user_email.user_id);
     alert.updated_at = Some(datetime_now());
     match alert::save(&org_id, &stream_name, &name, alert, false).await {
@@ -426,3 +441,93 @@ async fn trigger_alert(
         },
     }
 }
+
+/// BackfillAlert
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Alerts",
+    operation_id = "BackfillAlert",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("alert_name" = String, Path, description = "Alert name"),
+        ("start_time" = i64, Query, description = "Start time of the backfill range, in microseconds"),
+        ("end_time" = i64, Query, description = "End time of the backfill range, in microseconds"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = AlertBackfillResponse),
+        (status = 400, description = "Error",    content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",  content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/{stream_name}/alerts/{alert_name}/backfill")]
+async fn backfill_alert(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(MetaHttpResponse::bad_request(e));
+        }
+    };
+    let start_time = query
+        .get("start_time")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or_default();
+    let end_time = query
+        .get("end_time")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or_default();
+    match backfill::backfill(
+        &org_id,
+        stream_type,
+        &stream_name,
+        &name,
+        start_time,
+        end_time,
+    )
+    .await
+    {
+        Ok(resp) => Ok(MetaHttpResponse::json(resp)),
+        Err(e) => match e {
+            (http::StatusCode::NOT_FOUND, e) => Ok(MetaHttpResponse::not_found(e)),
+            (http::StatusCode::BAD_REQUEST, e) => Ok(MetaHttpResponse::bad_request(e)),
+            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        },
+    }
+}
+
+/// Checks the user can read the stream of an alert querying a different organization
+async fn can_read_source_org(
+    org_id: &str,
+    stream_name: &str,
+    alert: &Alert,
+    user_id: &str,
+) -> bool {
+    let Some(source_org_id) = alert
+        .source_org_id
+        .as_deref()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty() && *v != org_id)
+    else {
+        return true;
+    };
+    if is_root_user(user_id) {
+        return true;
+    }
+    // the user must be a member of the source organization
+    let Some(role) = USERS
+        .get(&format!("{source_org_id}/{user_id}"))
+        .map(|user| user.role.clone())
+    else {
+        return false;
+    };
+    check_stream_permission(source_org_id, alert.stream_type, stream_name, user_id, role).await
+}
@@ -110,7 +110,7 @@ pub async fn update_destination(
 async fn get_destination(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
     let (org_id, name) = path.into_inner();
     match destinations::get(&org_id, &name).await {
-        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Ok(data) => Ok(MetaHttpResponse::json(data.redact())),
         Err(e) => Ok(MetaHttpResponse::not_found(e)),
     }
 }
@@ -164,7 +164,11 @@ async fn list_destinations(
     }
 
     match destinations::list(&org_id, _permitted).await {
-        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Ok(data) => Ok(MetaHttpResponse::json(
+            data.into_iter()
+                .map(Destination::redact)
+                .collect::<Vec<_>>(),
+        )),
         Err(e) => Ok(MetaHttpResponse::bad_request(e)),
     }
 }
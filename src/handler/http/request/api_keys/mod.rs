@@ -0,0 +1,117 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{
+            api_key::{ApiKeys, CreateApiKeyRequest, CreateApiKeyResponse},
+            http::HttpResponse as MetaHttpResponse,
+        },
+        utils::auth::UserEmail,
+    },
+    service::api_keys,
+};
+
+/// CreateApiKey
+#[utoipa::path(
+    context_path = "/api",
+    tag = "API Keys",
+    operation_id = "CreateApiKey",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = CreateApiKeyRequest, description = "API key name and scopes", content_type = "application/json", example = json!({
+        "name": "fluent-bit",
+        "scopes": ["ingest"]
+    })),
+    responses(
+        (status = 200, description = "Success, the key is only returned here", content_type = "application/json", body = CreateApiKeyResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/api_keys")]
+pub async fn create(
+    path: web::Path<String>,
+    req: web::Json<CreateApiKeyRequest>,
+    user_email: UserEmail,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match api_keys::create(&org_id, &user_email.user_id, req.into_inner()).await {
+        Ok(res) => Ok(MetaHttpResponse::json(res)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// ListApiKeys
+#[utoipa::path(
+    context_path = "/api",
+    tag = "API Keys",
+    operation_id = "ListApiKeys",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ApiKeys),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/api_keys")]
+pub async fn list(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match api_keys::list(&org_id).await {
+        Ok(keys) => Ok(MetaHttpResponse::json(ApiKeys { keys })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// RevokeApiKey
+#[utoipa::path(
+    context_path = "/api",
+    tag = "API Keys",
+    operation_id = "RevokeApiKey",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "API key name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/api_keys/{name}")]
+pub async fn delete(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match api_keys::delete(&org_id, &name).await {
+        Ok(true) => Ok(MetaHttpResponse::ok("API key revoked")),
+        Ok(false) => Ok(MetaHttpResponse::not_found(format!(
+            "API key {name} not found"
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
@@ -15,7 +15,7 @@
 
 use std::io::Error;
 
-use actix_web::{http, post, web, HttpRequest, HttpResponse};
+use actix_web::{get, http, post, web, HttpRequest, HttpResponse};
 
 use crate::{
     common::meta::{
@@ -23,9 +23,12 @@ use crate::{
         ingestion::{
             GCPIngestionRequest, IngestionRequest, KinesisFHIngestionResponse, KinesisFHRequest,
         },
+        reingest::{ReingestJob, ReingestRequest},
     },
     handler::http::request::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO},
     service::{
+        db,
+        ingestion::idempotency,
         logs,
         logs::otlp_http::{logs_json_handler, logs_proto_handler},
     },
@@ -57,18 +60,21 @@ pub async fn bulk(
 ) -> Result<HttpResponse, Error> {
     let org_id = org_id.into_inner();
     let user_email = in_req.headers().get("user_id").unwrap().to_str().unwrap();
-    Ok(
-        match logs::bulk::ingest(**thread_id, &org_id, body, user_email).await {
-            Ok(v) => MetaHttpResponse::json(v),
-            Err(e) => {
-                log::error!("Error processing request {org_id}/_bulk: {:?}", e);
-                HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    e.to_string(),
-                ))
-            }
-        },
-    )
+    idempotency::ingest_once(&org_id, &in_req, || async {
+        Ok(
+            match logs::bulk::ingest(**thread_id, &org_id, body, user_email).await {
+                Ok(v) => MetaHttpResponse::json(v),
+                Err(e) => {
+                    log::error!("Error processing request {org_id}/_bulk: {:?}", e);
+                    HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                        http::StatusCode::BAD_REQUEST.into(),
+                        e.to_string(),
+                    ))
+                }
+            },
+        )
+    })
+    .await
 }
 
 /// _multi ingestion API
@@ -98,30 +104,33 @@ pub async fn multi(
 ) -> Result<HttpResponse, Error> {
     let (org_id, stream_name) = path.into_inner();
     let user_email = in_req.headers().get("user_id").unwrap().to_str().unwrap();
-    Ok(
-        match logs::ingest::ingest(
-            **thread_id,
-            &org_id,
-            &stream_name,
-            IngestionRequest::Multi(&body),
-            user_email,
-            None,
-        )
-        .await
-        {
-            Ok(v) => match v.code {
-                503 => HttpResponse::ServiceUnavailable().json(v),
-                _ => MetaHttpResponse::json(v),
+    idempotency::ingest_once(&org_id, &in_req, || async {
+        Ok(
+            match logs::ingest::ingest(
+                **thread_id,
+                &org_id,
+                &stream_name,
+                IngestionRequest::Multi(&body),
+                user_email,
+                None,
+            )
+            .await
+            {
+                Ok(v) => match v.code {
+                    503 => HttpResponse::ServiceUnavailable().json(v),
+                    _ => MetaHttpResponse::json(v),
+                },
+                Err(e) => {
+                    log::error!("Error processing request {org_id}/{stream_name}: {:?}", e);
+                    HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                        http::StatusCode::BAD_REQUEST.into(),
+                        e.to_string(),
+                    ))
+                }
             },
-            Err(e) => {
-                log::error!("Error processing request {org_id}/{stream_name}: {:?}", e);
-                HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    e.to_string(),
-                ))
-            }
-        },
-    )
+        )
+    })
+    .await
 }
 
 /// _json ingestion API
@@ -151,30 +160,33 @@ pub async fn json(
 ) -> Result<HttpResponse, Error> {
     let (org_id, stream_name) = path.into_inner();
     let user_email = in_req.headers().get("user_id").unwrap().to_str().unwrap();
-    Ok(
-        match logs::ingest::ingest(
-            **thread_id,
-            &org_id,
-            &stream_name,
-            IngestionRequest::JSON(&body),
-            user_email,
-            None,
-        )
-        .await
-        {
-            Ok(v) => match v.code {
-                503 => HttpResponse::ServiceUnavailable().json(v),
-                _ => MetaHttpResponse::json(v),
+    idempotency::ingest_once(&org_id, &in_req, || async {
+        Ok(
+            match logs::ingest::ingest(
+                **thread_id,
+                &org_id,
+                &stream_name,
+                IngestionRequest::JSON(&body),
+                user_email,
+                None,
+            )
+            .await
+            {
+                Ok(v) => match v.code {
+                    503 => HttpResponse::ServiceUnavailable().json(v),
+                    _ => MetaHttpResponse::json(v),
+                },
+                Err(e) => {
+                    log::error!("Error processing request {org_id}/{stream_name}: {:?}", e);
+                    HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                        http::StatusCode::BAD_REQUEST.into(),
+                        e.to_string(),
+                    ))
+                }
             },
-            Err(e) => {
-                log::error!("Error processing request {org_id}/{stream_name}: {:?}", e);
-                HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    e.to_string(),
-                ))
-            }
-        },
-    )
+        )
+    })
+    .await
 }
 
 /// _kinesis_firehose ingestion API
@@ -293,42 +305,119 @@ pub async fn otlp_logs_write(
         .headers()
         .get(&config::get_config().grpc.stream_header_key)
         .map(|header| header.to_str().unwrap());
-    if content_type.eq(CONTENT_TYPE_PROTO) {
-        // log::info!("otlp::logs_proto_handler");
-        match logs_proto_handler(**thread_id, &org_id, body, in_stream_name, user_email).await {
-            Ok(v) => Ok(v),
-            Err(e) => {
-                log::error!(
-                    "Error processing otlp pb logs write request {org_id}/{:?}: {:?}",
-                    in_stream_name,
-                    e
-                );
-                Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    e.to_string(),
-                )))
+    idempotency::ingest_otlp_once(&org_id, &req, || async {
+        if content_type.eq(CONTENT_TYPE_PROTO) {
+            // log::info!("otlp::logs_proto_handler");
+            match logs_proto_handler(**thread_id, &org_id, body, in_stream_name, user_email).await {
+                Ok(v) => Ok(v),
+                Err(e) => {
+                    log::error!(
+                        "Error processing otlp pb logs write request {org_id}/{:?}: {:?}",
+                        in_stream_name,
+                        e
+                    );
+                    Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                        http::StatusCode::BAD_REQUEST.into(),
+                        e.to_string(),
+                    )))
+                }
             }
-        }
-    } else if content_type.starts_with(CONTENT_TYPE_JSON) {
-        // log::info!("otlp::logs_json_handler");
-        match logs_json_handler(**thread_id, &org_id, body, in_stream_name, user_email).await {
-            Ok(v) => Ok(v),
-            Err(e) => {
-                log::error!(
-                    "Error processing otlp json logs write request {org_id}/{:?}: {:?}",
-                    in_stream_name,
-                    e
-                );
-                Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    e.to_string(),
-                )))
+        } else if content_type.starts_with(CONTENT_TYPE_JSON) {
+            // log::info!("otlp::logs_json_handler");
+            match logs_json_handler(**thread_id, &org_id, body, in_stream_name, user_email).await {
+                Ok(v) => Ok(v),
+                Err(e) => {
+                    log::error!(
+                        "Error processing otlp json logs write request {org_id}/{:?}: {:?}",
+                        in_stream_name,
+                        e
+                    );
+                    Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                        http::StatusCode::BAD_REQUEST.into(),
+                        e.to_string(),
+                    )))
+                }
             }
+        } else {
+            Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                "Bad Request".to_string(),
+            )))
+        }
+    })
+    .await
+}
+
+/// Replay the stored data of a stream through a pipeline
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsReingest",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Source stream name"),
+    ),
+    request_body(content = ReingestRequest, description = "Reingest request", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ReingestJob),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/{stream_name}/_reingest")]
+pub async fn reingest(
+    thread_id: web::Data<usize>,
+    path: web::Path<(String, String)>,
+    body: web::Json<ReingestRequest>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let user_email = in_req.headers().get("user_id").unwrap().to_str().unwrap();
+    match logs::reingest::start(
+        **thread_id,
+        &org_id,
+        &stream_name,
+        body.into_inner(),
+        user_email,
+    )
+    .await
+    {
+        Ok(job) => Ok(MetaHttpResponse::json(job)),
+        Err(e) => {
+            log::error!("Error processing request {org_id}/{stream_name}/_reingest: {e}");
+            Ok(MetaHttpResponse::bad_request(e))
         }
-    } else {
-        Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
-            http::StatusCode::BAD_REQUEST.into(),
-            "Bad Request".to_string(),
-        )))
+    }
+}
+
+/// Get the progress of a reingest job
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsReingestStatus",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Source stream name"),
+        ("job_id" = String, Path, description = "Reingest job id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ReingestJob),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/_reingest/{job_id}")]
+pub async fn get_reingest(
+    path: web::Path<(String, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, job_id) = path.into_inner();
+    match db::reingest::get_job(&org_id, &job_id).await {
+        Ok(Some(job)) if job.stream_name == stream_name => Ok(MetaHttpResponse::json(job)),
+        Ok(_) => Ok(MetaHttpResponse::not_found("reingest job not found")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
     }
 }
@@ -20,9 +20,12 @@ use actix_web::{http, post, web, HttpRequest, HttpResponse};
 use crate::{
     common::meta::http::HttpResponse as MetaHttpResponse,
     handler::http::request::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO},
-    service::metrics::{
-        otlp_http::{metrics_json_handler, metrics_proto_handler},
-        {self},
+    service::{
+        ingestion::idempotency,
+        metrics::{
+            otlp_http::{metrics_json_handler, metrics_proto_handler},
+            {self},
+        },
     },
 };
 
@@ -44,18 +47,25 @@ use crate::{
     )
 )]
 #[post("/{org_id}/ingest/metrics/_json")]
-pub async fn json(org_id: web::Path<String>, body: web::Bytes) -> Result<HttpResponse, Error> {
+pub async fn json(
+    org_id: web::Path<String>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
     let org_id = org_id.into_inner();
-    Ok(match metrics::json::ingest(&org_id, body).await {
-        Ok(v) => HttpResponse::Ok().json(v),
-        Err(e) => {
-            log::error!("Error processing request {org_id}/metrics: {:?}", e);
-            HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                e.to_string(),
-            ))
-        }
+    idempotency::ingest_once(&org_id, &req, || async {
+        Ok(match metrics::json::ingest(&org_id, body).await {
+            Ok(v) => HttpResponse::Ok().json(v),
+            Err(e) => {
+                log::error!("Error processing request {org_id}/metrics: {:?}", e);
+                HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                ))
+            }
+        })
     })
+    .await
 }
 
 /// MetricsIngest
@@ -77,16 +87,19 @@ pub async fn otlp_metrics_write(
 ) -> Result<HttpResponse, Error> {
     let org_id = org_id.into_inner();
     let content_type = req.headers().get("Content-Type").unwrap().to_str().unwrap();
-    if content_type.eq(CONTENT_TYPE_PROTO) {
-        // log::info!("otlp::metrics_proto_handler");
-        metrics_proto_handler(&org_id, body).await
-    } else if content_type.starts_with(CONTENT_TYPE_JSON) {
-        // log::info!("otlp::metrics_json_handler");
-        metrics_json_handler(&org_id, body).await
-    } else {
-        Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
-            http::StatusCode::BAD_REQUEST.into(),
-            "Bad Request".to_string(),
-        )))
-    }
+    idempotency::ingest_otlp_once(&org_id, &req, || async {
+        if content_type.eq(CONTENT_TYPE_PROTO) {
+            // log::info!("otlp::metrics_proto_handler");
+            metrics_proto_handler(&org_id, body).await
+        } else if content_type.starts_with(CONTENT_TYPE_JSON) {
+            // log::info!("otlp::metrics_json_handler");
+            metrics_json_handler(&org_id, body).await
+        } else {
+            Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                "Bad Request".to_string(),
+            )))
+        }
+    })
+    .await
 }
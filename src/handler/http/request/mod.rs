@@ -14,6 +14,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod alerts;
+pub mod api_keys;
 pub mod authz;
 pub mod clusters;
 pub mod dashboards;
@@ -60,6 +60,35 @@ async fn create(
             "scrape_interval should be a positive value",
         ));
     }
+    if settings.query_max_rows.is_some_and(|v| v < 0) {
+        return Ok(MetaHttpResponse::bad_request(
+            "query_max_rows should not be a negative value",
+        ));
+    }
+    if settings.query_max_scan_size.is_some_and(|v| v < 0) {
+        return Ok(MetaHttpResponse::bad_request(
+            "query_max_scan_size should not be a negative value",
+        ));
+    }
+    if settings.query_max_groups.is_some_and(|v| v < 0) {
+        return Ok(MetaHttpResponse::bad_request(
+            "query_max_groups should not be a negative value",
+        ));
+    }
+    if settings.result_cache_ttl.is_some_and(|v| v < 0) {
+        return Ok(MetaHttpResponse::bad_request(
+            "result_cache_ttl should not be a negative value",
+        ));
+    }
+    if settings
+        .query_range_policy
+        .as_ref()
+        .is_some_and(|v| !["clamp", "reject"].contains(&v.as_str()))
+    {
+        return Ok(MetaHttpResponse::bad_request(
+            "query_range_policy should be one of clamp, reject",
+        ));
+    }
 
     let org_id = path.into_inner();
     match set_org_setting(&org_id, &settings).await {
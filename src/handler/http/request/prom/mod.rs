@@ -27,7 +27,7 @@ use promql_parser::parser;
 
 use crate::{
     common::meta::{self, http::HttpResponse as MetaHttpResponse},
-    service::{metrics, promql, promql::MetricsQueryRequest},
+    service::{ingestion::idempotency, metrics, promql, promql::MetricsQueryRequest},
 };
 
 /// prometheus remote-write endpoint for metrics
@@ -56,13 +56,16 @@ pub async fn remote_write(
     let org_id = org_id.into_inner();
     let content_type = req.headers().get("Content-Type").unwrap().to_str().unwrap();
     if content_type == "application/x-protobuf" {
-        Ok(match metrics::prom::remote_write(&org_id, body).await {
-            Ok(_) => HttpResponse::Ok().into(),
-            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                e.to_string(),
-            )),
+        idempotency::ingest_once(&org_id, &req, || async {
+            Ok(match metrics::prom::remote_write(&org_id, body).await {
+                Ok(_) => HttpResponse::Ok().into(),
+                Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            })
         })
+        .await
     } else {
         Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
             http::StatusCode::BAD_REQUEST.into(),
@@ -834,6 +837,103 @@ fn validate_metadata_params(
     Ok((selector, start, end))
 }
 
+/// prometheus querying exemplars
+// refer: https://prometheus.io/docs/prometheus/latest/querying/api/#querying-exemplars
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "PrometheusQueryExemplars",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("query" = String, Query, description = "Prometheus expression query string"),
+        ("start" = Option<String>, Query, description = "<rfc3339 | unix_timestamp>: Start timestamp"),
+        ("end" = Option<String>, Query, description = "<rfc3339 | unix_timestamp>: End timestamp"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse, example = json!({
+            "status": "success",
+            "data": [
+                {
+                    "seriesLabels": {
+                        "__name__": "http_request_duration_seconds_bucket",
+                        "job": "api",
+                        "le": "0.5"
+                    },
+                    "exemplars": [
+                        {
+                            "labels": {
+                                "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736"
+                            },
+                            "value": "0.25",
+                            "timestamp": 1700000000.123
+                        }
+                    ]
+                }
+            ]
+        })),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/prometheus/api/v1/query_exemplars")]
+pub async fn query_exemplars_get(
+    org_id: web::Path<String>,
+    req: web::Query<meta::prom::RequestQueryExemplars>,
+) -> Result<HttpResponse, Error> {
+    query_exemplars(&org_id, req.into_inner()).await
+}
+
+#[post("/{org_id}/prometheus/api/v1/query_exemplars")]
+pub async fn query_exemplars_post(
+    org_id: web::Path<String>,
+    req: web::Query<meta::prom::RequestQueryExemplars>,
+    web::Form(form): web::Form<meta::prom::RequestQueryExemplars>,
+) -> Result<HttpResponse, Error> {
+    let req = if form.query.is_some() || form.start.is_some() || form.end.is_some() {
+        form
+    } else {
+        req.into_inner()
+    };
+    query_exemplars(&org_id, req).await
+}
+
+async fn query_exemplars(
+    org_id: &str,
+    req: meta::prom::RequestQueryExemplars,
+) -> Result<HttpResponse, Error> {
+    let meta::prom::RequestQueryExemplars { query, start, end } = req;
+    let expr = match parser::parse(query.as_deref().unwrap_or_default()) {
+        Ok(expr) => expr,
+        Err(err) => {
+            return Ok(HttpResponse::BadRequest().json(
+                promql::ApiFuncResponse::<()>::err_bad_data(format!("parse promql error: {err}")),
+            ));
+        }
+    };
+    let (_, start, end) = match validate_metadata_params(None, start, end) {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(promql::ApiFuncResponse::<()>::err_bad_data(e))
+            );
+        }
+    };
+
+    Ok(
+        match metrics::prom::get_exemplars(org_id, &expr, start, end).await {
+            Ok(resp) => HttpResponse::Ok().json(promql::ApiFuncResponse::ok(resp)),
+            Err(err) => {
+                log::error!("get_exemplars failed: {err}");
+                HttpResponse::InternalServerError()
+                    .json(promql::ApiFuncResponse::<()>::err_internal(err.to_string()))
+            }
+        },
+    )
+}
+
 /// prometheus formatting query expressions
 // refer: https://prometheus.io/docs/prometheus/latest/querying/api/#formatting-query-expressions
 #[utoipa::path(
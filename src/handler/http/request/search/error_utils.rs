@@ -0,0 +1,262 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The error responses of the search endpoints. Every error has the same envelope: the `code`
+//! of the other endpoints, the http status or the custom code of the error like `20001`, and a
+//! stable `error_type` the clients can branch on instead of matching the message.
+
+use actix_web::{http::StatusCode, HttpResponse};
+use infra::errors::{Error, ErrorCodes};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The category of a search error
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchErrorType {
+    /// the request is malformed, eg: a missing parameter
+    BadRequest,
+    /// the SQL can't be parsed, or it uses a field with a type it doesn't support
+    InvalidSql,
+    StreamNotFound,
+    FieldNotFound,
+    FunctionNotDefined,
+    /// the user can't access the stream or some of its fields
+    PermissionDenied,
+    Timeout,
+    /// the query was cancelled, or it waited too long for a slot
+    Cancelled,
+    /// only a part of the data could be searched
+    Partial,
+    Internal,
+}
+
+impl SearchErrorType {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            SearchErrorType::BadRequest
+            | SearchErrorType::InvalidSql
+            | SearchErrorType::FieldNotFound
+            | SearchErrorType::FunctionNotDefined => StatusCode::BAD_REQUEST,
+            SearchErrorType::StreamNotFound => StatusCode::NOT_FOUND,
+            SearchErrorType::PermissionDenied => StatusCode::FORBIDDEN,
+            SearchErrorType::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            SearchErrorType::Cancelled => StatusCode::TOO_MANY_REQUESTS,
+            SearchErrorType::Partial | SearchErrorType::Internal => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl From<&ErrorCodes> for SearchErrorType {
+    fn from(code: &ErrorCodes) -> Self {
+        match code {
+            ErrorCodes::SearchSQLNotValid(_)
+            | ErrorCodes::SearchFieldHasNoCompatibleDataType(_) => SearchErrorType::InvalidSql,
+            ErrorCodes::SearchQueryRangeExceeded(_) => SearchErrorType::BadRequest,
+            ErrorCodes::SearchStreamNotFound(_) => SearchErrorType::StreamNotFound,
+            ErrorCodes::FullTextSearchFieldNotFound | ErrorCodes::SearchFieldNotFound(_) => {
+                SearchErrorType::FieldNotFound
+            }
+            ErrorCodes::SearchFunctionNotDefined(_) => SearchErrorType::FunctionNotDefined,
+            ErrorCodes::SearchCancelQuery(_) => SearchErrorType::Cancelled,
+            ErrorCodes::SearchTimeout(_) => SearchErrorType::Timeout,
+            ErrorCodes::SearchFieldAccessDenied(_) => SearchErrorType::PermissionDenied,
+            ErrorCodes::SearchPartialResult(_) => SearchErrorType::Partial,
+            ErrorCodes::ServerInternalError(_)
+            | ErrorCodes::SearchParquetFileNotFound
+            | ErrorCodes::SearchSQLExecuteError(_) => SearchErrorType::Internal,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchErrorResponse {
+    /// the http status, or the custom code of the error like `20001`
+    pub code: u16,
+    pub error_type: SearchErrorType,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+impl SearchErrorResponse {
+    pub fn new(error_type: SearchErrorType, message: impl ToString, trace_id: &str) -> Self {
+        Self {
+            code: error_type.status().as_u16(),
+            error_type,
+            message: message.to_string(),
+            error_detail: None,
+            trace_id: (!trace_id.is_empty()).then(|| trace_id.to_string()),
+        }
+    }
+
+    pub fn from_error(err: &Error, trace_id: &str) -> Self {
+        match err {
+            Error::ErrorCode(code) => {
+                let detail = code.get_error_detail();
+                Self {
+                    code: code.get_code(),
+                    message: code.get_message(),
+                    error_detail: (!detail.is_empty()).then_some(detail),
+                    ..Self::new(code.into(), "", trace_id)
+                }
+            }
+            _ => Self::new(SearchErrorType::Internal, err, trace_id),
+        }
+    }
+
+    pub fn into_response(self) -> HttpResponse {
+        HttpResponse::build(self.error_type.status()).json(self)
+    }
+}
+
+/// Returns the response of the error of a search
+pub fn map_error_to_http_response(err: &Error, trace_id: &str) -> HttpResponse {
+    SearchErrorResponse::from_error(err, trace_id).into_response()
+}
+
+/// Returns the response of a malformed search request
+pub fn bad_request(message: impl ToString, trace_id: &str) -> HttpResponse {
+    SearchErrorResponse::new(SearchErrorType::BadRequest, message, trace_id).into_response()
+}
+
+/// Returns the response of a search of data the user can't access
+pub fn forbidden(message: impl ToString, trace_id: &str) -> HttpResponse {
+    SearchErrorResponse::new(SearchErrorType::PermissionDenied, message, trace_id).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_error_types() {
+        let cases = [
+            (
+                Error::ErrorCode(ErrorCodes::SearchSQLNotValid("selec *".to_string())),
+                SearchErrorType::InvalidSql,
+                20001,
+                400,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchStreamNotFound("k8s".to_string())),
+                SearchErrorType::StreamNotFound,
+                20002,
+                404,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchFieldNotFound("lvl".to_string())),
+                SearchErrorType::FieldNotFound,
+                20004,
+                400,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchFunctionNotDefined("f".to_string())),
+                SearchErrorType::FunctionNotDefined,
+                20005,
+                400,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchCancelQuery("queue".to_string())),
+                SearchErrorType::Cancelled,
+                20009,
+                429,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchTimeout("60s".to_string())),
+                SearchErrorType::Timeout,
+                20010,
+                504,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchFieldAccessDenied("email".to_string())),
+                SearchErrorType::PermissionDenied,
+                20011,
+                403,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchQueryRangeExceeded("48h".to_string())),
+                SearchErrorType::BadRequest,
+                20013,
+                400,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchSQLExecuteError("oom".to_string())),
+                SearchErrorType::Internal,
+                20008,
+                500,
+            ),
+            (
+                Error::ErrorCode(ErrorCodes::SearchPartialResult("node failed".to_string())),
+                SearchErrorType::Partial,
+                20012,
+                500,
+            ),
+            // only the typed error is partial
+            (
+                Error::Message("Partial response: node failed".to_string()),
+                SearchErrorType::Internal,
+                500,
+                500,
+            ),
+            (
+                Error::Message("connection refused".to_string()),
+                SearchErrorType::Internal,
+                500,
+                500,
+            ),
+        ];
+        for (err, error_type, code, status) in cases {
+            let res = SearchErrorResponse::from_error(&err, "trace-1");
+            assert_eq!(res.error_type, error_type, "{err}");
+            assert_eq!(res.code, code, "{err}");
+            assert_eq!(res.trace_id.as_deref(), Some("trace-1"));
+            assert!(!res.message.is_empty());
+            assert_eq!(
+                map_error_to_http_response(&err, "trace-1")
+                    .status()
+                    .as_u16(),
+                status,
+                "{err}"
+            );
+        }
+
+        // the human message and the detail are kept
+        let res = SearchErrorResponse::from_error(
+            &Error::ErrorCode(ErrorCodes::SearchSQLNotValid("selec *".to_string())),
+            "",
+        );
+        assert_eq!(res.message, "Search SQL not valid");
+        assert_eq!(res.error_detail.as_deref(), Some("selec *"));
+        assert!(res.trace_id.is_none());
+
+        assert_eq!(bad_request("size is invalid", "t").status(), 400);
+        assert_eq!(forbidden("Unauthorized Access", "t").status(), 403);
+
+        // the types are serialized as stable snake_case strings
+        let body = config::utils::json::to_value(SearchErrorResponse::new(
+            SearchErrorType::PermissionDenied,
+            "Unauthorized Access",
+            "t",
+        ))
+        .unwrap();
+        assert_eq!(body["error_type"], "permission_denied");
+        assert_eq!(body["code"], 403);
+    }
+}
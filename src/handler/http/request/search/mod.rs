@@ -13,24 +13,33 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{collections::HashMap, io::Error};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Error,
+};
 
-use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use arrow_schema::Schema;
 use chrono::{Duration, Utc};
 use config::{
     get_config,
     meta::{
-        search::{SearchEventType, SearchHistoryHitResponse},
+        search::{
+            resolve_relative_time_range, ResponseWarning, ResponseWarningCode, SearchEventType,
+            SearchHistoryHitResponse,
+        },
         sql::resolve_stream_names,
         stream::StreamType,
         usage::{RequestStats, UsageType, USAGE_STREAM},
     },
     metrics,
-    utils::{base64, json},
+    utils::{base64, json, time::now_micros},
     DISTINCT_FIELDS,
 };
-use infra::{cache::stats, errors};
+use infra::{
+    cache::stats,
+    errors::{Error as InfraError, ErrorCodes},
+};
 use tracing::{Instrument, Span};
 
 use crate::{
@@ -44,15 +53,18 @@ use crate::{
             },
         },
     },
+    handler::http::request::search::error_utils::{SearchErrorResponse, SearchErrorType},
     service::{
         search as SearchService,
         usage::{http_report_metrics, report_request_usage_stats},
     },
 };
 
+pub mod error_utils;
 pub mod job;
 pub mod multi_streams;
 pub mod saved_view;
+pub mod websocket;
 
 /// SearchStreamData
 #[utoipa::path(
@@ -64,6 +76,7 @@ pub mod saved_view;
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("confirm" = Option<bool>, Query, description = "Run the query even if it's estimated to scan more than the max scan size of the org, default is false"),
     ),
     request_body(content = SearchRequest, description = "Search query", content_type = "application/json", example = json!({
         "query": {
@@ -100,8 +113,17 @@ pub mod saved_view;
             "size": 1,
             "scan_size": 28943
         })),
-        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
-        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = SearchErrorResponse, example = json!({
+            "code": 20001,
+            "error_type": "invalid_sql",
+            "message": "Search SQL not valid",
+            "error_detail": "sql parser error: Expected: an expression, found: EOF",
+            "trace_id": "0b5f62dc3e3b4b5f8a5d0e0c6a6b7f1e"
+        })),
+        (status = 403, description = "Failure", content_type = "application/json", body = SearchErrorResponse),
+        (status = 429, description = "Failure", content_type = "application/json", body = SearchErrorResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = SearchErrorResponse),
+        (status = 504, description = "Failure", content_type = "application/json", body = SearchErrorResponse),
     )
 )]
 #[post("/{org_id}/_search")]
@@ -111,6 +133,7 @@ pub async fn search(
     body: web::Bytes,
 ) -> Result<HttpResponse, Error> {
     let start = std::time::Instant::now();
+    let received_at = now_micros();
     let cfg = get_config();
 
     let org_id = org_id.into_inner();
@@ -132,58 +155,81 @@ pub async fn search(
     let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
     let stream_type = match get_stream_type_from_request(&query) {
         Ok(v) => v.unwrap_or(StreamType::Logs),
-        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+        Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
     };
 
     let use_cache = cfg.common.result_cache_enabled && get_use_cache_from_request(&query);
+    // the relative times of the query, like `now-15m`, are anchored to the request receipt
+    let mut body: json::Value = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
+    };
+    if let Some(query) = body.get_mut("query") {
+        if let Err(e) = resolve_relative_time_range(query, received_at) {
+            return Ok(error_utils::bad_request(e, &trace_id));
+        }
+    }
     // handle encoding for query and aggs
-    let mut req: config::meta::search::Request = match json::from_slice(&body) {
+    let mut req: config::meta::search::Request = match json::from_value(body) {
         Ok(v) => v,
-        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+        Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
     };
     if let Err(e) = req.decode() {
-        return Ok(MetaHttpResponse::bad_request(e));
+        return Ok(error_utils::bad_request(e, &trace_id));
     }
 
     // set search event type
     req.search_type = match get_search_type_from_request(&query) {
         Ok(v) => v,
-        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+        Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
     };
 
     // set index_type
     req.index_type = match get_index_type_from_request(&query) {
         Ok(typ) => typ,
-        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+        Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
+    };
+
+    // check the max rows of a query before planning it
+    let size_error = match SearchService::check_query_max_rows(&org_id, &mut req.query.size).await {
+        Ok(v) => v,
+        Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
     };
 
     // get stream name
-    let stream_names = match resolve_stream_names(&req.query.sql) {
+    let mut stream_names = match resolve_stream_names(&req.query.sql) {
         Ok(v) => v.clone(),
         Err(e) => {
             return Ok(
-                HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    e.to_string(),
-                )),
+                SearchErrorResponse::new(SearchErrorType::InvalidSql, e, &trace_id).into_response(),
             );
         }
     };
 
+    // a stream alias is checked as the stream it belongs to
+    for stream_name in stream_names.iter_mut() {
+        *stream_name = infra::schema::resolve_stream_alias(&org_id, stream_name, stream_type).await;
+    }
+
     // get stream settings
-    for stream_name in stream_names {
+    let range_policy = SearchService::get_query_range_policy(&org_id).await;
+    for stream_name in stream_names.iter() {
         if let Some(settings) =
             infra::schema::get_settings(&org_id, &stream_name, stream_type).await
         {
-            let max_query_range = settings.max_query_range;
-            if max_query_range > 0
-                && (req.query.end_time - req.query.start_time) > max_query_range * 3600 * 1_000_000
-            {
-                req.query.start_time = req.query.end_time - max_query_range * 3600 * 1_000_000;
-                range_error = format!(
-                    "Query duration is modified due to query range restriction of {} hours",
-                    max_query_range
-                );
+            match SearchService::apply_max_query_range(
+                stream_name,
+                &mut req.query.start_time,
+                req.query.end_time,
+                settings.max_query_range,
+                settings
+                    .query_range_policy
+                    .as_deref()
+                    .unwrap_or(&range_policy),
+            ) {
+                Ok(Some(e)) => range_error = e,
+                Ok(None) => {}
+                Err(e) => return Ok(error_utils::map_error_to_http_response(&e, &trace_id)),
             }
         }
 
@@ -223,18 +269,105 @@ pub async fn search(
                     )
                     .await
                 {
-                    return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+                    return Ok(error_utils::forbidden("Unauthorized Access", &trace_id));
                 }
                 // Check permissions on stream ends
             }
         }
     }
 
+    // check the query doesn't read the fields the user can't access. The search checks it as
+    // well, but the query of the distinct values stream below no longer names the fields.
+    let denied_fields = match SearchService::field_access::check_request(
+        &org_id,
+        Some(&user_id),
+        stream_type,
+        &req.query.sql,
+        req.query.query_fn.as_deref(),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => return Ok(error_utils::map_error_to_http_response(&e, &trace_id)),
+    };
+
+    // push the sort keys into the ORDER BY of the query
+    if let Some(sort_by) = req.query.sort_by.as_ref() {
+        let keys = match sort_by.keys() {
+            Ok(v) => v,
+            Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
+        };
+        let mut stream_fields = HashSet::new();
+        for stream_name in stream_names.iter() {
+            if let Ok(schema) = infra::schema::get(&org_id, stream_name, stream_type).await {
+                stream_fields.extend(schema.fields().iter().map(|f| f.name().to_string()));
+            }
+        }
+        req.query.sql =
+            match SearchService::sql::apply_sort_by(&req.query.sql, &keys, &stream_fields) {
+                Ok(v) => v,
+                Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
+            };
+    }
+
+    // read the distinct values stream instead of scanning the data, if possible
+    let mut search_stream_type = stream_type;
+    if req.query.query_fn.is_none() {
+        if let Some(sql) = SearchService::sql::rewrite_distinct_sql(&req.query.sql, stream_type) {
+            let distinct_stream = crate::service::metadata::distinct_values::STREAM_NAME;
+            let has_distinct_stream =
+                infra::schema::get(&org_id, distinct_stream, StreamType::Metadata)
+                    .await
+                    .map(|schema| !schema.fields().is_empty())
+                    .unwrap_or_default();
+            if has_distinct_stream {
+                log::info!(
+                    "[trace_id {trace_id}] search: rewrite distinct query [{}] to [{sql}]",
+                    req.query.sql
+                );
+                req.query.sql = sql;
+                search_stream_type = StreamType::Metadata;
+            }
+        }
+    }
+
+    // estimate the data the query scans, a query over the budget of the org is rejected unless
+    // it's confirmed. Without a budget the file list isn't queried twice.
+    let max_scan_size = SearchService::cost::get_max_scan_size(&org_id).await;
+    let cost_estimate = if max_scan_size > 0 {
+        match SearchService::cost::estimate(
+            &org_id,
+            search_stream_type,
+            &req.query.sql,
+            (req.query.start_time, req.query.end_time),
+        )
+        .await
+        {
+            Ok(v) => Some(v),
+            Err(e) => {
+                log::warn!("[trace_id {trace_id}] search: estimate query cost error: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let confirmed = query
+        .get("confirm")
+        .map_or(false, |v| v.parse::<bool>().unwrap_or(false));
+    if let Some(estimate) = cost_estimate.as_ref() {
+        if !confirmed {
+            if let Err(e) = SearchService::cost::check_scan_size(estimate, max_scan_size) {
+                return Ok(error_utils::bad_request(e, &trace_id));
+            }
+        }
+    }
+
     // run search with cache
     let res = SearchService::cache::search(
         &trace_id,
         &org_id,
-        stream_type,
+        search_stream_type,
         Some(user_id),
         &req,
         use_cache,
@@ -243,6 +376,8 @@ pub async fn search(
     .await;
     match res {
         Ok(mut res) => {
+            SearchService::field_access::strip_response(&mut res, &denied_fields);
+            res.cost_estimate = cost_estimate;
             if res.is_partial {
                 let partial_err = "Please be aware that the response is based on partial data";
                 res.function_error = if res.function_error.is_empty() {
@@ -253,6 +388,10 @@ pub async fn search(
             }
             if !range_error.is_empty() {
                 res.is_partial = true;
+                res.add_warning(ResponseWarning::new(
+                    ResponseWarningCode::RangeClamped,
+                    range_error.clone(),
+                ));
                 res.function_error = if res.function_error.is_empty() {
                     range_error
                 } else {
@@ -261,27 +400,32 @@ pub async fn search(
                 res.new_start_time = Some(req.query.start_time);
                 res.new_end_time = Some(req.query.end_time);
             }
+            if let Some(size_error) = size_error {
+                res.is_partial = true;
+                res.add_warning(ResponseWarning::new(
+                    ResponseWarningCode::SizeClamped,
+                    size_error.clone(),
+                ));
+                res.function_error = if res.function_error.is_empty() {
+                    size_error
+                } else {
+                    format!("{} \n {}", size_error, res.function_error)
+                };
+            }
             Ok(HttpResponse::Ok().json(res))
         }
         Err(err) => {
-            http_report_metrics(start, &org_id, stream_type, "", "500", "_search");
+            let resp = error_utils::map_error_to_http_response(&err, &trace_id);
+            http_report_metrics(
+                start,
+                &org_id,
+                stream_type,
+                "",
+                resp.status().as_str(),
+                "_search",
+            );
             log::error!("[trace_id {trace_id}] search error: {}", err);
-            Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            })
+            Ok(resp)
         }
     }
 }
@@ -299,6 +443,7 @@ pub async fn search(
         ("stream_name" = String, Path, description = "stream_name name"),
         ("key" = i64, Query, description = "around key"),
         ("size" = i64, Query, description = "around size"),
+        ("filter" = Option<String>, Query, description = "only return the rows matching the predicate, eg: k8s_pod_name='app-0'"),
         ("regions" = Option<String>, Query, description = "regions, split by comma"),
         ("timeout" = Option<i64>, Query, description = "timeout, seconds"),
     ),
@@ -391,6 +536,19 @@ pub async fn around(
         },
     };
 
+    // only the rows matching the filter are returned around the key
+    let around_sql = match query.get("filter").filter(|v| !v.trim().is_empty()) {
+        Some(filter) => match SearchService::sql::add_filter(&around_sql, filter) {
+            Ok(sql) => sql,
+            Err(e) => {
+                return Ok(MetaHttpResponse::bad_request(format!(
+                    "invalid filter: {e}"
+                )));
+            }
+        },
+        None => around_sql,
+    };
+
     let around_size = query
         .get("size")
         .map_or(10, |v| v.parse::<i64>().unwrap_or(10));
@@ -456,13 +614,15 @@ pub async fn around(
             size: around_size / 2,
             start_time: around_start_time,
             end_time: around_key,
-            sort_by: Some(format!("{} DESC", cfg.common.column_timestamp)),
+            sort_by: Some(format!("{} DESC", cfg.common.column_timestamp).into()),
             quick_mode: false,
             query_type: "".to_string(),
             track_total_hits: false,
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            wal_only: false,
+            fields: vec![],
         },
         encoding: config::meta::search::RequestEncoding::Empty,
         regions: regions.clone(),
@@ -478,24 +638,17 @@ pub async fn around(
     let resp_forward = match search_res {
         Ok(res) => res,
         Err(err) => {
-            http_report_metrics(start, &org_id, stream_type, &stream_name, "500", "_around");
+            let resp = error_utils::map_error_to_http_response(&err, &trace_id);
+            http_report_metrics(
+                start,
+                &org_id,
+                stream_type,
+                &stream_name,
+                resp.status().as_str(),
+                "_around",
+            );
             log::error!("search around error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(resp);
         }
     };
 
@@ -507,13 +660,15 @@ pub async fn around(
             size: around_size / 2,
             start_time: around_key,
             end_time: around_end_time,
-            sort_by: Some(format!("{} ASC", cfg.common.column_timestamp)),
+            sort_by: Some(format!("{} ASC", cfg.common.column_timestamp).into()),
             quick_mode: false,
             query_type: "".to_string(),
             track_total_hits: false,
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            wal_only: false,
+            fields: vec![],
         },
         encoding: config::meta::search::RequestEncoding::Empty,
         regions,
@@ -529,24 +684,17 @@ pub async fn around(
     let resp_backward = match search_res {
         Ok(res) => res,
         Err(err) => {
-            http_report_metrics(start, &org_id, stream_type, &stream_name, "500", "_around");
+            let resp = error_utils::map_error_to_http_response(&err, &trace_id);
+            http_report_metrics(
+                start,
+                &org_id,
+                stream_type,
+                &stream_name,
+                resp.status().as_str(),
+                "_around",
+            );
             log::error!("search around error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(resp);
         }
     };
 
@@ -686,6 +834,23 @@ pub async fn values(
     };
     let trace_id = get_or_create_trace_id(in_req.headers(), &http_span);
 
+    // the distinct values stream is queried by the names of the fields, check them here
+    let mut named_fields = fields.iter().map(|f| f.as_str()).collect::<Vec<_>>();
+    if let Some((column, _)) = query.get("filter").and_then(|v| v.split_once('=')) {
+        named_fields.push(column);
+    }
+    if let Err(e) = SearchService::field_access::check_fields(
+        &org_id,
+        &user_id,
+        &stream_name,
+        stream_type,
+        &named_fields,
+    )
+    .await
+    {
+        return Ok(error_utils::map_error_to_http_response(&e, &trace_id));
+    }
+
     if fields.len() == 1
         && DISTINCT_FIELDS.contains(&fields[0])
         && !query_sql.to_lowercase().contains(" where ")
@@ -950,27 +1115,17 @@ async fn values_v1(
         let resp_search = match search_res {
             Ok(res) => res,
             Err(err) => {
-                http_report_metrics(start, org_id, stream_type, stream_name, "500", "_values/v1");
+                let resp = error_utils::map_error_to_http_response(&err, &trace_id);
+                http_report_metrics(
+                    start,
+                    org_id,
+                    stream_type,
+                    stream_name,
+                    resp.status().as_str(),
+                    "_values/v1",
+                );
                 log::error!("search values error: {:?}", err);
-                return Ok(match err {
-                    errors::Error::ErrorCode(code) => match code {
-                        errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                            .json(meta::http::HttpResponse::error_code_with_trace_id(
-                                code,
-                                Some(trace_id),
-                            )),
-                        _ => HttpResponse::InternalServerError().json(
-                            meta::http::HttpResponse::error_code_with_trace_id(
-                                code,
-                                Some(trace_id),
-                            ),
-                        ),
-                    },
-                    _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                        StatusCode::INTERNAL_SERVER_ERROR.into(),
-                        err.to_string(),
-                    )),
-                });
+                return Ok(resp);
             }
         };
         query_results.push((field.to_string(), resp_search));
@@ -1212,6 +1367,8 @@ async fn values_v2(
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            wal_only: false,
+            fields: vec![],
         },
         encoding: config::meta::search::RequestEncoding::Empty,
         regions,
@@ -1233,24 +1390,17 @@ async fn values_v2(
     let resp_search = match search_res {
         Ok(res) => res,
         Err(err) => {
-            http_report_metrics(start, org_id, stream_type, stream_name, "500", "_values/v2");
+            let resp = error_utils::map_error_to_http_response(&err, &trace_id);
+            http_report_metrics(
+                start,
+                org_id,
+                stream_type,
+                stream_name,
+                resp.status().as_str(),
+                "_values/v2",
+            );
             log::error!("search values error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(resp);
         }
     };
 
@@ -1369,9 +1519,15 @@ pub async fn search_partition(
         return Ok(MetaHttpResponse::bad_request(e));
     }
 
-    let search_res = SearchService::search_partition(&trace_id, &org_id, stream_type, &req)
-        .instrument(http_span)
-        .await;
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let search_res =
+        SearchService::search_partition(&trace_id, &org_id, stream_type, Some(user_id), &req)
+            .instrument(http_span)
+            .await;
 
     // do search
     match search_res {
@@ -1380,17 +1536,17 @@ pub async fn search_partition(
             Ok(HttpResponse::Ok().json(res))
         }
         Err(err) => {
-            http_report_metrics(start, &org_id, stream_type, "", "500", "_search_partition");
+            let resp = error_utils::map_error_to_http_response(&err, &trace_id);
+            http_report_metrics(
+                start,
+                &org_id,
+                stream_type,
+                "",
+                resp.status().as_str(),
+                "_search_partition",
+            );
             log::error!("search error: {:?}", err);
-            Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError().json(
-                    meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                ),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            })
+            Ok(resp)
         }
     }
 }
@@ -1544,24 +1700,17 @@ pub async fn search_history(
     let mut search_res = match search_res {
         Ok(res) => res,
         Err(err) => {
+            let resp = error_utils::map_error_to_http_response(&err, &trace_id);
             http_report_metrics(
                 start,
                 &org_id,
                 stream_type,
                 stream_name,
-                "500",
+                resp.status().as_str(),
                 "_search_history",
             );
             log::error!("[trace_id {}] Search history error : {:?}", trace_id, err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError().json(
-                    meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                ),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(resp);
         }
     };
 
@@ -1632,3 +1781,333 @@ pub async fn search_history(
 
     Ok(HttpResponse::Ok().json(search_res))
 }
+
+/// SearchTimings
+///
+/// Returns the timings of the recent searches of a trace id, the trace id of every response is
+/// in the `X-O2-Trace-Id` header. The timings are kept in memory on the node which handled the
+/// search.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchTimings",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("trace_id" = String, Path, description = "Trace ID of the search"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<SearchTiming>),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_search_timings/{trace_id}")]
+pub async fn search_timings(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, trace_id) = path.into_inner();
+    let timings = SearchService::timings::get(&org_id, &trace_id);
+    if timings.is_empty() {
+        return Ok(MetaHttpResponse::not_found(format!(
+            "no search timings of trace_id [{trace_id}] on this node"
+        )));
+    }
+    Ok(HttpResponse::Ok().json(timings))
+}
+
+/// SearchCount
+///
+/// Returns the record count of a stream in the time range, summed from the metadata of the files
+/// so no data is read. The data which isn't persisted yet is only counted with `wal=true`, which
+/// runs a `count(*)` search over the recent hours the ingesters can still hold data of.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchCount",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type, default is logs"),
+        ("start_time" = i64, Query, description = "start time"),
+        ("end_time" = i64, Query, description = "end time"),
+        ("wal" = Option<bool>, Query, description = "Count the data which isn't persisted yet, default is false"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = CountResponse, example = json!({
+            "total": 1024,
+            "file_count": 3,
+            "include_wal": false,
+            "took": 5
+        })),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/_count")]
+pub async fn count(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let start_time = query
+        .get("start_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    if start_time == 0 {
+        return Ok(MetaHttpResponse::bad_request("start_time is empty"));
+    }
+    let end_time = query
+        .get("end_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    if end_time == 0 {
+        return Ok(MetaHttpResponse::bad_request("end_time is empty"));
+    }
+    if start_time >= end_time {
+        return Ok(MetaHttpResponse::bad_request(
+            "start_time should be before end_time",
+        ));
+    }
+    let include_wal = query
+        .get("wal")
+        .map_or(false, |v| v.parse::<bool>().unwrap_or(false));
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .map(|v| v.to_str().unwrap_or("").to_string());
+    let trace_id = get_or_create_trace_id(in_req.headers(), &Span::none());
+
+    match SearchService::count::count(
+        &trace_id,
+        &org_id,
+        stream_type,
+        &stream_name,
+        user_id,
+        (start_time, end_time),
+        include_wal,
+    )
+    .await
+    {
+        Ok(res) => Ok(HttpResponse::Ok().json(res)),
+        Err(err) => Ok(error_utils::map_error_to_http_response(&err, &trace_id)),
+    }
+}
+
+/// SearchFieldStats
+///
+/// Returns the min, max, average, null count and approximate cardinality of a numeric field in
+/// the time range. The min, max and null count are taken from the column statistics of the
+/// files, a search only runs for the values the files don't have, `sources` tells where each
+/// value was taken from. With `metadata_only=true` the average and the cardinality, which always
+/// need a search, are left out.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchFieldStats",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type, default is logs"),
+        ("field" = String, Query, description = "Numeric field name"),
+        ("start_time" = i64, Query, description = "start time"),
+        ("end_time" = i64, Query, description = "end time"),
+        ("metadata_only" = Option<bool>, Query, description = "Skip the average and the cardinality, default is false"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = FieldStatsResponse, example = json!({
+            "field": "code",
+            "min": 200.0,
+            "max": 504.0,
+            "avg": 231.7,
+            "null_count": 12,
+            "null_ratio": 0.0117,
+            "cardinality": 9,
+            "records": 1024,
+            "file_count": 3,
+            "sources": {
+                "min": "metadata",
+                "max": "metadata",
+                "null_count": "metadata",
+                "avg": "scan",
+                "cardinality": "scan"
+            },
+            "took": 18
+        })),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/_field_stats")]
+pub async fn field_stats(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let field = match query.get("field") {
+        Some(v) if !v.is_empty() => v.to_string(),
+        _ => return Ok(MetaHttpResponse::bad_request("field is empty")),
+    };
+    let start_time = query
+        .get("start_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    if start_time == 0 {
+        return Ok(MetaHttpResponse::bad_request("start_time is empty"));
+    }
+    let end_time = query
+        .get("end_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    if end_time == 0 {
+        return Ok(MetaHttpResponse::bad_request("end_time is empty"));
+    }
+    if start_time >= end_time {
+        return Ok(MetaHttpResponse::bad_request(
+            "start_time should be before end_time",
+        ));
+    }
+    let metadata_only = query
+        .get("metadata_only")
+        .map_or(false, |v| v.parse::<bool>().unwrap_or(false));
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .map(|v| v.to_str().unwrap_or("").to_string());
+    let trace_id = get_or_create_trace_id(in_req.headers(), &Span::none());
+
+    match SearchService::field_stats::field_stats(
+        &trace_id,
+        &org_id,
+        stream_type,
+        &stream_name,
+        user_id,
+        &field,
+        (start_time, end_time),
+        metadata_only,
+    )
+    .await
+    {
+        Ok(res) => Ok(HttpResponse::Ok().json(res)),
+        Err(err) => Ok(error_utils::map_error_to_http_response(&err, &trace_id)),
+    }
+}
+
+/// ValidateSql
+///
+/// Parses the SQL without executing it and returns the streams and the fields it references,
+/// if it uses `match_all`, and the validation errors with their position when it is known.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "ValidateSql",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("type" = Option<String>, Query, description = "Stream type, default is logs"),
+    ),
+    request_body(content = ValidateSqlRequest, description = "SQL to validate", content_type = "application/json", example = json!({
+        "sql": "SELECT level, count(*) AS cnt FROM k8s WHERE match_all('error') GROUP BY level"
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ValidateSqlResponse, example = json!({
+            "valid": false,
+            "streams": ["k8s"],
+            "fields": {"k8s": ["level"]},
+            "has_match_all": true,
+            "errors": [{"message": "Field not found: lvl"}]
+        })),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_validate_sql")]
+pub async fn validate_sql(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let mut req: config::meta::search::ValidateSqlRequest = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if let Err(e) = req.decode() {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    if req.sql.trim().is_empty() {
+        return Ok(MetaHttpResponse::bad_request("sql is empty"));
+    }
+
+    // the errors tell the schema of the streams, check the user can search them first. A SQL
+    // the streams can't be resolved from is left to the validation.
+    let user_id = in_req.headers().get("user_id").unwrap().to_str().unwrap();
+    #[cfg(feature = "enterprise")]
+    if let Ok(stream_names) = resolve_stream_names(&req.sql) {
+        use crate::{
+            common::{infra::config::USERS, utils::auth::is_root_user},
+            handler::http::auth::validator::check_stream_permission,
+        };
+
+        if !is_root_user(user_id) {
+            let Some(user) = USERS.get(&format!("{org_id}/{user_id}")).map(|v| v.clone()) else {
+                return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+            };
+            if user.is_external {
+                for stream_name in stream_names {
+                    // a stream alias is checked as the stream it belongs to
+                    let stream_name =
+                        infra::schema::resolve_stream_alias(&org_id, &stream_name, stream_type)
+                            .await;
+                    if !check_stream_permission(
+                        &org_id,
+                        stream_type,
+                        &stream_name,
+                        user_id,
+                        user.role.clone(),
+                    )
+                    .await
+                    {
+                        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+                    }
+                }
+            }
+        }
+    }
+    match SearchService::field_access::check_request(
+        &org_id,
+        Some(user_id),
+        stream_type,
+        &req.sql,
+        None,
+    )
+    .await
+    {
+        Ok(_) | Err(InfraError::ErrorCode(ErrorCodes::SearchSQLNotValid(_))) => {}
+        Err(InfraError::ErrorCode(ErrorCodes::SearchFieldAccessDenied(e))) => {
+            return Ok(MetaHttpResponse::forbidden(e));
+        }
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    }
+
+    let res = SearchService::sql::validate(&org_id, stream_type, &req.sql).await;
+    Ok(HttpResponse::Ok().json(res))
+}
@@ -13,14 +13,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{collections::HashMap, io::Error};
+use std::{
+    collections::HashMap,
+    io::Error,
+    sync::atomic::{AtomicI64, Ordering},
+};
 
-use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use chrono::{Duration, Utc};
 use config::{
     get_config,
     meta::{
-        search,
+        search::{self, ResponseWarning, ResponseWarningCode},
         sql::resolve_stream_names,
         stream::StreamType,
         usage::{RequestStats, UsageType},
@@ -42,9 +46,10 @@ use crate::{
             },
         },
     },
+    handler::http::request::search::error_utils,
     service::{
         search::{self as SearchService, RESULT_ARRAY},
-        usage::report_request_usage_stats,
+        usage::{report_latency, report_request_usage_stats, LatencyKind},
     },
 };
 
@@ -136,14 +141,14 @@ pub async fn search_multi(
     let stream_type = match get_stream_type_from_request(&query) {
         Ok(v) => v.unwrap_or(StreamType::Logs),
         Err(e) => {
-            return Ok(MetaHttpResponse::bad_request(e));
+            return Ok(error_utils::bad_request(e, &trace_id));
         }
     };
 
     let search_type = match get_search_type_from_request(&query) {
         Ok(v) => v,
         Err(e) => {
-            return Ok(MetaHttpResponse::bad_request(e));
+            return Ok(error_utils::bad_request(e, &trace_id));
         }
     };
 
@@ -151,7 +156,7 @@ pub async fn search_multi(
     let multi_req: search::MultiStreamRequest = match json::from_slice(&body) {
         Ok(v) => v,
         Err(e) => {
-            return Ok(MetaHttpResponse::bad_request(e));
+            return Ok(error_utils::bad_request(e, &trace_id));
         }
     };
 
@@ -170,19 +175,37 @@ pub async fn search_multi(
 
     let user_id = in_req.headers().get("user_id").unwrap().to_str().unwrap();
     let mut queries = multi_req.to_query_req();
+
+    // check the max rows of a query before planning it
+    let mut size_error = None;
+    for req in queries.iter_mut() {
+        match SearchService::check_query_max_rows(&org_id, &mut req.query.size).await {
+            Ok(Some(e)) => size_error = Some(e),
+            Ok(None) => {}
+            Err(e) => return Ok(error_utils::bad_request(e, &trace_id)),
+        }
+    }
     let mut multi_res = search::Response::new(multi_req.from, multi_req.size);
+    let mut stream_hits = Vec::new();
 
     let per_query_resp = multi_req.per_query_response;
+    if per_query_resp && multi_req.group_by_stream {
+        return Ok(error_utils::bad_request(
+            "group_by_stream can't be used with per_query_response",
+            &trace_id,
+        ));
+    }
 
     // Before making any rpc requests, first check the sql expressions can be decoded correctly
     for req in queries.iter_mut() {
         if let Err(e) = req.decode() {
-            return Ok(MetaHttpResponse::bad_request(e));
+            return Ok(error_utils::bad_request(e, &trace_id));
         }
     }
     let queries_len = queries.len();
     let mut vrl_stream_name = "".to_string();
     let mut sqls = vec![];
+    let range_policy = SearchService::get_query_range_policy(&org_id).await;
 
     for mut req in queries {
         sqls.push(req.query.sql.clone());
@@ -192,12 +215,12 @@ pub async fn search_multi(
         let stream_name = match resolve_stream_names(&req.query.sql) {
             Ok(v) => v[0].clone(),
             Err(e) => {
-                return Ok(HttpResponse::InternalServerError().json(
-                    meta::http::HttpResponse::error(
-                        StatusCode::INTERNAL_SERVER_ERROR.into(),
-                        e.to_string(),
-                    ),
-                ));
+                return Ok(error_utils::SearchErrorResponse::new(
+                    error_utils::SearchErrorType::InvalidSql,
+                    e,
+                    &trace_id,
+                )
+                .into_response());
             }
         };
         vrl_stream_name = stream_name.clone();
@@ -206,20 +229,25 @@ pub async fn search_multi(
         if let Some(settings) =
             infra::schema::get_settings(&org_id, &stream_name, stream_type).await
         {
-            let max_query_range = settings.max_query_range;
-            if max_query_range > 0
-                && (req.query.end_time - req.query.start_time) > max_query_range * 3600 * 1_000_000
-            {
-                req.query.start_time = req.query.end_time - max_query_range * 3600 * 1_000_000;
-                range_error = format!(
-                    "{} Query duration for stream {} is modified due to query range restriction of {} hours",
-                    range_error, &stream_name, max_query_range
-                );
-
-                if multi_res.new_start_time.is_none() {
-                    multi_res.new_start_time = Some(req.query.start_time);
-                    multi_res.new_end_time = Some(req.query.end_time);
+            match SearchService::apply_max_query_range(
+                &stream_name,
+                &mut req.query.start_time,
+                req.query.end_time,
+                settings.max_query_range,
+                settings
+                    .query_range_policy
+                    .as_deref()
+                    .unwrap_or(&range_policy),
+            ) {
+                Ok(Some(e)) => {
+                    range_error = format!("{range_error} {e}");
+                    if multi_res.new_start_time.is_none() {
+                        multi_res.new_start_time = Some(req.query.start_time);
+                        multi_res.new_end_time = Some(req.query.end_time);
+                    }
                 }
+                Ok(None) => {}
+                Err(e) => return Ok(error_utils::map_error_to_http_response(&e, &trace_id)),
             }
         }
 
@@ -259,7 +287,7 @@ pub async fn search_multi(
                     )
                     .await
                 {
-                    return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+                    return Ok(error_utils::forbidden("Unauthorized Access", &trace_id));
                 }
             }
             // Check permissions on stream ends
@@ -320,6 +348,7 @@ pub async fn search_multi(
                         stream_type.to_string().as_str(),
                     ])
                     .observe(time);
+                report_latency(LatencyKind::Search, "/api/org/_search_multi", &org_id, time);
                 metrics::HTTP_INCOMING_REQUESTS
                     .with_label_values(&[
                         "/api/org/_search_multi",
@@ -381,12 +410,16 @@ pub async fn search_multi(
                 multi_res.trace_id = res.trace_id;
                 multi_res.cached_ratio = res.cached_ratio;
 
-                if per_query_resp {
-                    multi_res.hits.push(serde_json::Value::Array(res.hits));
-                } else {
-                    multi_res.hits.extend(res.hits);
-                }
+                multi_req.merge_hits(
+                    &mut multi_res.hits,
+                    &mut stream_hits,
+                    &stream_name,
+                    res.hits,
+                );
 
+                for warning in res.warnings {
+                    multi_res.add_warning(warning);
+                }
                 if res.is_partial {
                     multi_res.is_partial = true;
                     let partial_err = "Please be aware that the response is based on partial data";
@@ -401,20 +434,22 @@ pub async fn search_multi(
                 }
             }
             Err(err) => {
+                let resp = error_utils::map_error_to_http_response(&err, &trace_id);
                 let time = start.elapsed().as_secs_f64();
                 metrics::HTTP_RESPONSE_TIME
                     .with_label_values(&[
                         "/api/org/_search_multi",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         "",
                         stream_type.to_string().as_str(),
                     ])
                     .observe(time);
+                report_latency(LatencyKind::Search, "/api/org/_search_multi", &org_id, time);
                 metrics::HTTP_INCOMING_REQUESTS
                     .with_label_values(&[
                         "/api/org/_search_multi",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         "",
                         stream_type.to_string().as_str(),
@@ -423,15 +458,8 @@ pub async fn search_multi(
 
                 log::error!("search error: {:?}", err);
                 multi_res.function_error = format!("{};{:?}", multi_res.function_error, err);
-                if let errors::Error::ErrorCode(code) = err {
-                    if let errors::ErrorCodes::SearchCancelQuery(_) = code {
-                        return Ok(HttpResponse::TooManyRequests().json(
-                            meta::http::HttpResponse::error_code_with_trace_id(
-                                code,
-                                Some(trace_id),
-                            ),
-                        ));
-                    }
+                if let errors::Error::ErrorCode(errors::ErrorCodes::SearchCancelQuery(_)) = err {
+                    return Ok(resp);
                 }
             }
         }
@@ -526,23 +554,50 @@ pub async fn search_multi(
 
     if !range_error.is_empty() {
         multi_res.is_partial = true;
+        multi_res.add_warning(ResponseWarning::new(
+            ResponseWarningCode::RangeClamped,
+            range_error.clone(),
+        ));
         multi_res.function_error = if multi_res.function_error.is_empty() {
             range_error
         } else {
             format!("{} \n {}", range_error, multi_res.function_error)
         };
     }
+    if let Some(size_error) = size_error {
+        multi_res.is_partial = true;
+        multi_res.add_warning(ResponseWarning::new(
+            ResponseWarningCode::SizeClamped,
+            size_error.clone(),
+        ));
+        multi_res.function_error = if multi_res.function_error.is_empty() {
+            size_error
+        } else {
+            format!("{} \n {}", size_error, multi_res.function_error)
+        };
+    }
+
+    if let Some(warning) = multi_req.deprecation_warning() {
+        log_deprecated_format(&org_id, user_id);
+        multi_res.add_warning(warning);
+    }
 
     let column_timestamp = get_config().common.column_timestamp.to_string();
     multi_res.cached_ratio /= queries_len;
-    multi_res.hits.sort_by(|a, b| {
+    let newest_first = |a: &json::Value, b: &json::Value| {
         if a.get(&column_timestamp).is_none() || b.get(&column_timestamp).is_none() {
             return std::cmp::Ordering::Equal;
         }
         let a_ts = a.get(&column_timestamp).unwrap().as_i64().unwrap();
         let b_ts = b.get(&column_timestamp).unwrap().as_i64().unwrap();
         b_ts.cmp(&a_ts)
-    });
+    };
+    multi_res.hits.sort_by(newest_first);
+    // the hits are grouped once they are sorted, the groups are the shape of the response
+    if multi_req.group_by_stream {
+        multi_res.hits =
+            search::MultiStreamRequest::group_hits_by_stream(stream_hits, newest_first);
+    }
 
     let time = start.elapsed().as_secs_f64();
     if report_function_usage {
@@ -575,6 +630,23 @@ pub async fn search_multi(
     Ok(HttpResponse::Ok().json(multi_res))
 }
 
+/// Logs the requests in the deprecated format at most once a minute, the response of each
+/// request carries a warning
+fn log_deprecated_format(org_id: &str, user_id: &str) {
+    static LAST_LOGGED: AtomicI64 = AtomicI64::new(0);
+    let now = Utc::now().timestamp();
+    let last = LAST_LOGGED.load(Ordering::Relaxed);
+    if now - last >= 60
+        && LAST_LOGGED
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        log::warn!(
+            "[search_multi] org {org_id} user {user_id} gives sql as a list of strings, the format is deprecated"
+        );
+    }
+}
+
 /// SearchMultiStreamPartition
 #[utoipa::path(
     context_path = "/api",
@@ -657,7 +729,13 @@ pub async fn _search_partition_multi(
         }
     };
 
-    let search_fut = SearchService::search_partition_multi(&trace_id, &org_id, stream_type, &req);
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let search_fut =
+        SearchService::search_partition_multi(&trace_id, &org_id, stream_type, Some(user_id), &req);
     let search_res = if !cfg.common.tracing_enabled && cfg.common.tracing_search_enabled {
         search_fut.instrument(http_span).await
     } else {
@@ -676,6 +754,12 @@ pub async fn _search_partition_multi(
                     stream_type.to_string().as_str(),
                 ])
                 .observe(time);
+            report_latency(
+                LatencyKind::Search,
+                "/api/org/_search_partition_multi",
+                &org_id,
+                time,
+            );
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_search_partition_multi",
@@ -688,35 +772,34 @@ pub async fn _search_partition_multi(
             Ok(HttpResponse::Ok().json(res))
         }
         Err(err) => {
+            let resp = error_utils::map_error_to_http_response(&err, &trace_id);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_search_partition_multi",
-                    "500",
+                    resp.status().as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
                 ])
                 .observe(time);
+            report_latency(
+                LatencyKind::Search,
+                "/api/org/_search_partition_multi",
+                &org_id,
+                time,
+            );
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_search_partition_multi",
-                    "500",
+                    resp.status().as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search error: {:?}", err);
-            Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError().json(
-                    meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                ),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            })
+            Ok(resp)
         }
     }
 }
@@ -734,6 +817,7 @@ pub async fn _search_partition_multi(
         ("stream_names" = String, Path, description = "base64 encoded comma separated stream names"),
         ("key" = i64, Query, description = "around key"),
         ("size" = i64, Query, description = "around size"),
+        ("filter" = Option<String>, Query, description = "only return the rows matching the predicate, eg: k8s_pod_name='app-0'"),
         ("timeout" = Option<i64>, Query, description = "timeout, seconds"),
     ),
     responses(
@@ -832,6 +916,20 @@ pub async fn around_multi(
         }
     }
 
+    // only the rows matching the filter are returned around the key
+    if let Some(filter) = query.get("filter").filter(|v| !v.trim().is_empty()) {
+        for sql in around_sqls.iter_mut() {
+            *sql = match SearchService::sql::add_filter(sql, filter) {
+                Ok(sql) => sql,
+                Err(e) => {
+                    return Ok(MetaHttpResponse::bad_request(format!(
+                        "invalid filter: {e}"
+                    )));
+                }
+            };
+        }
+    }
+
     let around_size = query
         .get("size")
         .map_or(10, |v| v.parse::<i64>().unwrap_or(10));
@@ -910,13 +1008,15 @@ pub async fn around_multi(
                 size: around_size / 2,
                 start_time: around_start_time,
                 end_time: around_key,
-                sort_by: Some(format!("{} DESC", cfg.common.column_timestamp)),
+                sort_by: Some(format!("{} DESC", cfg.common.column_timestamp).into()),
                 quick_mode: false,
                 query_type: "".to_string(),
                 track_total_hits: false,
                 uses_zo_fn: uses_fn,
                 query_fn: query_fn.clone(),
                 skip_wal: false,
+                wal_only: false,
+                fields: vec![],
             },
             encoding: config::meta::search::RequestEncoding::Empty,
             regions: regions.clone(),
@@ -933,45 +1033,29 @@ pub async fn around_multi(
         let resp_forward = match search_res {
             Ok(res) => res,
             Err(err) => {
+                let resp = error_utils::map_error_to_http_response(&err, &trace_id);
                 let time = start.elapsed().as_secs_f64();
                 metrics::HTTP_RESPONSE_TIME
                     .with_label_values(&[
                         "/api/org/_around_multi",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         &stream_names,
                         stream_type.to_string().as_str(),
                     ])
                     .observe(time);
+                report_latency(LatencyKind::Search, "/api/org/_around_multi", &org_id, time);
                 metrics::HTTP_INCOMING_REQUESTS
                     .with_label_values(&[
                         "/api/org/_around_multi",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         &stream_names,
                         stream_type.to_string().as_str(),
                     ])
                     .inc();
                 log::error!("multi search around error: {:?}", err);
-                return Ok(match err {
-                    errors::Error::ErrorCode(code) => match code {
-                        errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                            .json(meta::http::HttpResponse::error_code_with_trace_id(
-                                code,
-                                Some(trace_id),
-                            )),
-                        _ => HttpResponse::InternalServerError().json(
-                            meta::http::HttpResponse::error_code_with_trace_id(
-                                code,
-                                Some(trace_id),
-                            ),
-                        ),
-                    },
-                    _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                        StatusCode::INTERNAL_SERVER_ERROR.into(),
-                        err.to_string(),
-                    )),
-                });
+                return Ok(resp);
             }
         };
 
@@ -983,13 +1067,15 @@ pub async fn around_multi(
                 size: around_size / 2,
                 start_time: around_key,
                 end_time: around_end_time,
-                sort_by: Some(format!("{} ASC", cfg.common.column_timestamp)),
+                sort_by: Some(format!("{} ASC", cfg.common.column_timestamp).into()),
                 quick_mode: false,
                 query_type: "".to_string(),
                 track_total_hits: false,
                 uses_zo_fn: uses_fn,
                 query_fn: query_fn.clone(),
                 skip_wal: false,
+                wal_only: false,
+                fields: vec![],
             },
             encoding: config::meta::search::RequestEncoding::Empty,
             regions: regions.clone(),
@@ -1006,45 +1092,29 @@ pub async fn around_multi(
         let resp_backward = match search_res {
             Ok(res) => res,
             Err(err) => {
+                let resp = error_utils::map_error_to_http_response(&err, &trace_id);
                 let time = start.elapsed().as_secs_f64();
                 metrics::HTTP_RESPONSE_TIME
                     .with_label_values(&[
                         "/api/org/_around_multi",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         &stream_names,
                         stream_type.to_string().as_str(),
                     ])
                     .observe(time);
+                report_latency(LatencyKind::Search, "/api/org/_around_multi", &org_id, time);
                 metrics::HTTP_INCOMING_REQUESTS
                     .with_label_values(&[
                         "/api/org/_around_multi",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         &stream_names,
                         stream_type.to_string().as_str(),
                     ])
                     .inc();
                 log::error!("multi search around error: {:?}", err);
-                return Ok(match err {
-                    errors::Error::ErrorCode(code) => match code {
-                        errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                            .json(meta::http::HttpResponse::error_code_with_trace_id(
-                                code,
-                                Some(trace_id),
-                            )),
-                        _ => HttpResponse::InternalServerError().json(
-                            meta::http::HttpResponse::error_code_with_trace_id(
-                                code,
-                                Some(trace_id),
-                            ),
-                        ),
-                    },
-                    _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                        StatusCode::INTERNAL_SERVER_ERROR.into(),
-                        err.to_string(),
-                    )),
-                });
+                return Ok(resp);
             }
         };
 
@@ -1075,6 +1145,7 @@ pub async fn around_multi(
                 stream_type.to_string().as_str(),
             ])
             .observe(time);
+        report_latency(LatencyKind::Search, "/api/org/_around_multi", &org_id, time);
         metrics::HTTP_INCOMING_REQUESTS
             .with_label_values(&[
                 "/api/org/_around_multi",
@@ -15,7 +15,7 @@
 
 use std::io::Error;
 
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 
 use crate::{
     common::{
@@ -23,12 +23,13 @@ use crate::{
             authz::Authz,
             http::HttpResponse as MetaHttpResponse,
             saved_view::{
-                CreateViewRequest, CreateViewResponse, DeleteViewResponse, UpdateViewRequest, View,
+                CreateViewRequest, CreateViewResponse, DeleteViewResponse, ExportRuns,
+                ScheduledExport, ScheduledExports, UpdateViewRequest, View,
             },
         },
         utils::auth::{remove_ownership, set_ownership},
     },
-    service::db::saved_view,
+    service::{db::saved_view, scheduled_export},
 };
 
 // GetSavedView
@@ -227,6 +228,171 @@ pub async fn update_view(
     }
 }
 
+// SaveScheduledExport
+//
+// Create or update a scheduled export, which runs a saved view on a cron and writes the results
+// to the object storage.
+//
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Saved Views",
+    operation_id = "SaveScheduledExport",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = ScheduledExport, description = "Scheduled export data", content_type = "application/json", example = json!({
+        "name": "nightly-errors",
+        "view_id": "view-id",
+        "cron": "0 0 2 * * *",
+        "timezone": "Europe/Berlin",
+        "period": 1440,
+        "format": "parquet",
+        "destination": "exports"
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/scheduled_exports")]
+pub async fn save_export(
+    path: web::Path<String>,
+    export: web::Json<ScheduledExport>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let user_id = req.headers().get("user_id").unwrap().to_str().unwrap();
+    match scheduled_export::save(&org_id, user_id, export.into_inner()).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Scheduled export saved")),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+// ListScheduledExports
+//
+// Retrieve the list of scheduled exports.
+//
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Saved Views",
+    operation_id = "ListScheduledExports",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ScheduledExports),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/scheduled_exports")]
+pub async fn list_exports(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match saved_view::list_exports(&org_id).await {
+        Ok(exports) => Ok(MetaHttpResponse::json(ScheduledExports { exports })),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+// GetScheduledExport
+//
+// Retrieve a scheduled export.
+//
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Saved Views",
+    operation_id = "GetScheduledExport",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Scheduled export name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ScheduledExport),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/scheduled_exports/{name}")]
+pub async fn get_export(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match saved_view::get_export(&org_id, &name).await {
+        Ok(export) => Ok(MetaHttpResponse::json(export)),
+        Err(e) => Ok(MetaHttpResponse::not_found(e)),
+    }
+}
+
+// DeleteScheduledExport
+//
+// Delete a scheduled export with its run history.
+//
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Saved Views",
+    operation_id = "DeleteScheduledExport",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Scheduled export name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/scheduled_exports/{name}")]
+pub async fn delete_export(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match saved_view::delete_export(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Scheduled export deleted")),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+// ListScheduledExportRuns
+//
+// Retrieve the run history of a scheduled export, the latest run first.
+//
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Saved Views",
+    operation_id = "ListScheduledExportRuns",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Scheduled export name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ExportRuns, example = json!({
+            "runs": [{
+                "timestamp": 1722470400000000i64,
+                "success": true,
+                "path": "exports/default/nightly-errors/2024/08/01/1722470400000000.parquet",
+                "records": 1024
+            }]
+        })),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/scheduled_exports/{name}/runs")]
+pub async fn list_export_runs(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match saved_view::get_export_runs(&org_id, &name).await {
+        Ok(runs) => Ok(MetaHttpResponse::json(ExportRuns { runs })),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use actix_web::{test, App};
@@ -0,0 +1,351 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Progressive search over a WebSocket.
+//!
+//! The client sends a search request as the first text message, the query is
+//! split into partitions at file boundaries and the result of every partition
+//! is sent as soon as it is done, with the percent of the files scanned. The
+//! client stops the query by sending `cancel` or closing the socket.
+
+use std::{cmp::min, collections::HashMap, io::Error};
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_ws::{Message, MessageStream, Session};
+use config::{
+    meta::{
+        search::{self, ResponseWarning, ResponseWarningCode},
+        sql::resolve_stream_names,
+        stream::StreamType,
+    },
+    utils::json,
+};
+use futures::StreamExt;
+use serde::Serialize;
+use tracing::Span;
+
+use crate::{
+    common::{
+        meta::http::HttpResponse as MetaHttpResponse,
+        utils::http::{get_or_create_trace_id, get_stream_type_from_request},
+    },
+    service::search as SearchService,
+};
+
+/// The message the client sends to stop the query
+const CANCEL_MESSAGE: &str = "cancel";
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SearchEvent {
+    /// The result of a partition, the client merges the results of all the partitions
+    Progress {
+        trace_id: String,
+        percent: usize,
+        partition: usize,
+        partitions: usize,
+        results: Box<search::Response>,
+    },
+    End {
+        trace_id: String,
+        percent: usize,
+    },
+    Cancelled {
+        trace_id: String,
+    },
+    Error {
+        trace_id: String,
+        message: String,
+    },
+}
+
+/// SearchWebSocket
+///
+/// Upgrades to a WebSocket running the search request of the first message by partitions
+#[get("/{org_id}/_search_ws")]
+pub async fn search_ws(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let trace_id = get_or_create_trace_id(in_req.headers(), &Span::none());
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+
+    let (res, session, msg_stream) = match actix_ws::handle(&in_req, body) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    actix_web::rt::spawn(handle_search(
+        session,
+        msg_stream,
+        trace_id,
+        org_id,
+        stream_type,
+        user_id,
+    ));
+    Ok(res)
+}
+
+async fn handle_search(
+    mut session: Session,
+    mut msg_stream: MessageStream,
+    trace_id: String,
+    org_id: String,
+    stream_type: StreamType,
+    user_id: String,
+) {
+    // the first text message is the search request
+    let body = loop {
+        match msg_stream.next().await {
+            Some(Ok(Message::Text(text))) => break text,
+            Some(Ok(Message::Ping(bytes))) => {
+                if session.pong(&bytes).await.is_err() {
+                    return;
+                }
+            }
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+            _ => {}
+        }
+    };
+
+    let event = match run_search(
+        &mut session,
+        &mut msg_stream,
+        &trace_id,
+        &org_id,
+        stream_type,
+        &user_id,
+        &body,
+    )
+    .await
+    {
+        Ok(event) => event,
+        Err(e) => {
+            log::error!("[trace_id {trace_id}] websocket search error: {e}");
+            let message = match e {
+                infra::errors::Error::ErrorCode(code) => code.get_message(),
+                e => e.to_string(),
+            };
+            SearchEvent::Error { trace_id, message }
+        }
+    };
+    // the client may have gone already
+    let _ = send_event(&mut session, &event).await;
+    let _ = session.close(None).await;
+}
+
+/// Runs the partitions one by one and returns the last event to send
+async fn run_search(
+    session: &mut Session,
+    msg_stream: &mut MessageStream,
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: &str,
+    body: &str,
+) -> Result<SearchEvent, infra::errors::Error> {
+    let mut req: search::Request = json::from_str(body)?;
+    req.decode()?;
+    let size_error = SearchService::check_query_max_rows(org_id, &mut req.query.size).await?;
+    check_permissions(org_id, stream_type, user_id, &req.query.sql).await?;
+
+    let partition_req = search::SearchPartitionRequest {
+        sql: req.query.sql.clone(),
+        start_time: req.query.start_time,
+        end_time: req.query.end_time,
+        encoding: search::RequestEncoding::Empty,
+        regions: req.regions.clone(),
+        clusters: req.clusters.clone(),
+        query_fn: req.query.query_fn.clone(),
+        partition_by_files: true,
+    };
+    let partition_resp = SearchService::search_partition(
+        trace_id,
+        org_id,
+        stream_type,
+        Some(user_id),
+        &partition_req,
+    )
+    .await?;
+    let partitions = partition_resp.partitions.len();
+
+    let mut scanned_files = 0;
+    for (i, [start_time, end_time]) in partition_resp.partitions.into_iter().enumerate() {
+        let mut req = req.clone();
+        req.query.start_time = start_time;
+        req.query.end_time = end_time;
+        let search = SearchService::search(
+            trace_id,
+            org_id,
+            stream_type,
+            Some(user_id.to_string()),
+            &req,
+        );
+        tokio::pin!(search);
+        let res = loop {
+            tokio::select! {
+                res = &mut search => break Some(res),
+                msg = msg_stream.next() => match msg {
+                    Some(Ok(Message::Text(text))) if text.trim() == CANCEL_MESSAGE => break None,
+                    Some(Ok(Message::Ping(bytes))) => {
+                        let _ = session.pong(&bytes).await;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break None,
+                    _ => {}
+                },
+            }
+        };
+        let Some(res) = res else {
+            // dropping the search stops it on this node, the other nodes are told to stop
+            #[cfg(feature = "enterprise")]
+            {
+                if let Err(e) = SearchService::cancel_query(org_id, trace_id).await {
+                    log::warn!("[trace_id {trace_id}] websocket search cancel error: {e}");
+                }
+            }
+            return Ok(SearchEvent::Cancelled {
+                trace_id: trace_id.to_string(),
+            });
+        };
+        let mut res = res?;
+        if let Some(size_error) = size_error.as_ref() {
+            res.add_warning(ResponseWarning::new(
+                ResponseWarningCode::SizeClamped,
+                size_error,
+            ));
+        }
+        scanned_files += res.file_count;
+        let event = SearchEvent::Progress {
+            trace_id: trace_id.to_string(),
+            percent: progress_percent(scanned_files, partition_resp.file_num, i + 1, partitions),
+            partition: i,
+            partitions,
+            results: Box::new(res),
+        };
+        if send_event(session, &event).await.is_err() {
+            // the client is gone
+            return Ok(SearchEvent::Cancelled {
+                trace_id: trace_id.to_string(),
+            });
+        }
+    }
+
+    Ok(SearchEvent::End {
+        trace_id: trace_id.to_string(),
+        percent: 100,
+    })
+}
+
+async fn send_event(session: &mut Session, event: &SearchEvent) -> Result<(), actix_ws::Closed> {
+    session.text(json::to_string(event).unwrap()).await
+}
+
+/// Returns the percent of the files scanned, or of the partitions done if there is no
+/// file list. It is 100 only when the search ends.
+fn progress_percent(
+    scanned_files: usize,
+    total_files: usize,
+    done_partitions: usize,
+    total_partitions: usize,
+) -> usize {
+    let percent = if total_files > 0 {
+        scanned_files * 100 / total_files
+    } else {
+        done_partitions * 100 / total_partitions.max(1)
+    };
+    min(percent, 99)
+}
+
+#[cfg(feature = "enterprise")]
+async fn check_permissions(
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: &str,
+    sql: &str,
+) -> Result<(), infra::errors::Error> {
+    use crate::{
+        common::{infra::config::USERS, utils::auth::is_root_user},
+        handler::http::auth::validator::check_stream_permission,
+    };
+
+    if is_root_user(user_id) {
+        return Ok(());
+    }
+    let Some(user) = USERS.get(&format!("{org_id}/{user_id}")).map(|v| v.clone()) else {
+        return Err(infra::errors::Error::Message(
+            "Unauthorized Access".to_string(),
+        ));
+    };
+    if !user.is_external {
+        return Ok(());
+    }
+    let stream_names =
+        resolve_stream_names(sql).map_err(|e| infra::errors::Error::Message(e.to_string()))?;
+    for stream_name in stream_names {
+        if !check_stream_permission(
+            org_id,
+            stream_type,
+            &stream_name,
+            user_id,
+            user.role.clone(),
+        )
+        .await
+        {
+            return Err(infra::errors::Error::Message(
+                "Unauthorized Access".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "enterprise"))]
+async fn check_permissions(
+    _org_id: &str,
+    _stream_type: StreamType,
+    _user_id: &str,
+    sql: &str,
+) -> Result<(), infra::errors::Error> {
+    // only check the query refers to the streams
+    resolve_stream_names(sql).map_err(|e| infra::errors::Error::Message(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_percent() {
+        assert_eq!(progress_percent(0, 10, 1, 4), 0);
+        assert_eq!(progress_percent(5, 10, 2, 4), 50);
+        // it reaches 100 only at the end
+        assert_eq!(progress_percent(10, 10, 4, 4), 99);
+        // no file list, by partitions
+        assert_eq!(progress_percent(0, 0, 1, 4), 25);
+        assert_eq!(progress_percent(0, 0, 1, 0), 99);
+    }
+}
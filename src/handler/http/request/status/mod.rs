@@ -18,7 +18,7 @@ use std::{io::Error, sync::Arc};
 use actix_web::{
     cookie,
     cookie::{Cookie, SameSite},
-    get, head,
+    delete, get, head,
     http::header,
     put, web, HttpRequest, HttpResponse,
 };
@@ -383,6 +383,46 @@ pub async fn config_reload() -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Ok().json(serde_json::json!({"status": status})))
 }
 
+/// Reloads the runtime settings which can be changed without a restart
+#[get("/settings")]
+pub async fn config_reload_settings() -> Result<HttpResponse, Error> {
+    let (resp, response_code, body) = match config::reload_config() {
+        Ok(changed) => {
+            let body = changed.join(",");
+            (
+                HttpResponse::Ok().json(serde_json::json!({
+                    "status": "successfully reloaded settings",
+                    "changed": changed,
+                })),
+                200,
+                body,
+            )
+        }
+        Err(e) => (
+            HttpResponse::BadRequest().json(serde_json::json!({"status": e.to_string()})),
+            400,
+            e.to_string(),
+        ),
+    };
+    // Audit this event
+    #[cfg(feature = "enterprise")]
+    audit(AuditMessage {
+        // Since this is not a protected route, there is no way to get the user email
+        user_email: "".to_string(),
+        org_id: "".to_string(),
+        method: "GET".to_string(),
+        _timestamp: chrono::Utc::now().timestamp_micros(),
+        path: "/config/reload/settings".to_string(),
+        query_params: "".to_string(),
+        body,
+        response_code,
+    })
+    .await;
+    #[cfg(not(feature = "enterprise"))]
+    log::info!("[CONFIG] reload settings response {response_code}: {body}");
+    Ok(resp)
+}
+
 async fn get_stream_schema_status() -> (usize, usize, usize) {
     let mut stream_num = 0;
     let mut stream_schema_num = 0;
@@ -736,3 +776,26 @@ async fn flush_node() -> Result<HttpResponse, Error> {
         Err(e) => Ok(MetaHttpResponse::internal_error(e)),
     }
 }
+
+#[put("/evict/{uuid}")]
+async fn evict_node(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let uuid = path.into_inner();
+    match cluster::evict_node(&uuid).await {
+        Ok(eviction) => Ok(MetaHttpResponse::json(eviction)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+#[delete("/evict/{uuid}")]
+async fn unevict_node(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let uuid = path.into_inner();
+    match cluster::unevict_node(&uuid).await {
+        Ok(_) => Ok(MetaHttpResponse::json(true)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+#[get("/evictions")]
+async fn list_evicted_nodes() -> Result<HttpResponse, Error> {
+    Ok(MetaHttpResponse::json(cluster::list_evicted_nodes().await))
+}
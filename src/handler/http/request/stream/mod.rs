@@ -20,6 +20,7 @@ use std::{
 
 use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse, Responder};
 use config::{
+    get_config,
     meta::stream::{StreamSettings, StreamType, UpdateStreamSettings},
     utils::schema::format_stream_name,
 };
@@ -29,13 +30,16 @@ use crate::{
         meta::{
             self,
             http::HttpResponse as MetaHttpResponse,
-            stream::{ListStream, StreamDeleteFields},
+            stream::{CompactJob, CompactRequest, ListStream, StreamDeleteFields, StreamSample},
         },
-        utils::http::get_stream_type_from_request,
+        utils::{auth::is_root_user, http::get_stream_type_from_request},
     },
-    service::stream,
+    service::{compact, search as SearchService, stream},
 };
 
+/// The number of records of a stream sample when the size isn't given
+const DEFAULT_SAMPLE_SIZE: usize = 10;
+
 /// GetSchema
 #[utoipa::path(
     context_path = "/api",
@@ -75,6 +79,99 @@ async fn schema(
     stream::get_stream(&org_id, &stream_name, stream_type).await
 }
 
+/// GetSchemaDiff
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSchemaDiff",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type"),
+        ("since" = Option<i64>, Query, description = "Return the field changes since this time, in microseconds"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamSchemaDiff),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/_schema")]
+async fn schema_diff(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    let since = match query.get("since").map(|v| v.parse::<i64>()) {
+        None => 0,
+        Some(Ok(v)) => v,
+        Some(Err(_)) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    "since must be a timestamp in microseconds".to_string(),
+                )),
+            );
+        }
+    };
+    stream::get_stream_schema_diff(&org_id, &stream_name, stream_type, since).await
+}
+
+/// GetStreamIngestionLag
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamIngestionLag",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = IngestionLag),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/ingestion_lag")]
+async fn ingestion_lag(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    stream::get_stream_ingestion_lag(&org_id, &stream_name, stream_type).await
+}
+
 /// CreateStreamSettings
 #[utoipa::path(
     context_path = "/api",
@@ -131,7 +228,14 @@ async fn settings(
     };
 
     let stream_type = stream_type.unwrap_or(StreamType::Logs);
-    stream::save_stream_settings(&org_id, &stream_name, stream_type, settings.into_inner()).await
+    let settings = settings.into_inner();
+    if let Err(e) =
+        stream::check_partition_keys(&org_id, &stream_name, stream_type, &settings.partition_keys)
+            .await
+    {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    stream::save_stream_settings(&org_id, &stream_name, stream_type, settings).await
 }
 
 /// UpdateStreamSettings
@@ -465,3 +569,184 @@ async fn delete_stream_cache(
         ))),
     }
 }
+
+/// CompactStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamCompact",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = String, Query, description = "Stream type"),
+    ),
+    request_body(content = CompactRequest, description = "Time range to compact", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = CompactJob),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/compact")]
+async fn compact(
+    path: web::Path<(String, String)>,
+    body: web::Json<CompactRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let user_id = req.headers().get("user_id").unwrap().to_str().unwrap();
+    if !is_root_user(user_id) {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match compact::manual::add_job(
+        &org_id,
+        stream_type,
+        &stream_name,
+        body.start_time,
+        body.end_time,
+    )
+    .await
+    {
+        Ok(job) => Ok(MetaHttpResponse::json(job)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// GetCompactJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamCompactJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("job_id" = String, Path, description = "Compaction job id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = CompactJob),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/compact/{job_id}")]
+async fn get_compact_job(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, job_id) = path.into_inner();
+    let user_id = req.headers().get("user_id").unwrap().to_str().unwrap();
+    if !is_root_user(user_id) {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+    match compact::manual::get_job(&org_id, &job_id).await {
+        Ok(Some(job)) if job.stream_name == stream_name => Ok(MetaHttpResponse::json(job)),
+        Ok(_) => Ok(MetaHttpResponse::not_found("compaction job not found")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// StreamSample
+///
+/// Returns a few of the newest records of the stream with its schema, read from the newest files
+/// and the data not persisted yet instead of running a search, so it's fast on a stream of any
+/// size. An empty stream returns the schema without records.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSample",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type, default is logs"),
+        ("size" = Option<usize>, Query, description = "Number of records, default is 10, capped by ZO_STREAM_SAMPLE_MAX_SIZE"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamSample),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/_sample")]
+async fn sample(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let max_size = get_config().limit.stream_sample_max_size;
+    let size = match query.get("size").map(|v| v.parse::<usize>()) {
+        None => DEFAULT_SAMPLE_SIZE.min(max_size),
+        Some(Ok(v)) => v.min(max_size),
+        Some(Err(_)) => {
+            return Ok(MetaHttpResponse::bad_request(
+                "size must be a positive integer",
+            ));
+        }
+    };
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    match SearchService::sample::sample(&org_id, stream_type, &stream_name, user_id, size).await {
+        Ok(res) => Ok(MetaHttpResponse::json(res)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// CancelCompactJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamCompactJobCancel",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("job_id" = String, Path, description = "Compaction job id"),
+    ),
+    responses(
+        (status = 200, description = "Success, a running job stops before its next partition", content_type = "application/json", body = CompactJob),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/streams/{stream_name}/compact/{job_id}")]
+async fn cancel_compact_job(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, job_id) = path.into_inner();
+    let user_id = req.headers().get("user_id").unwrap().to_str().unwrap();
+    if !is_root_user(user_id) {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+    match compact::manual::get_job(&org_id, &job_id).await {
+        Ok(Some(job)) if job.stream_name == stream_name => {}
+        Ok(_) => return Ok(MetaHttpResponse::not_found("compaction job not found")),
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    }
+    match compact::manual::cancel_job(&org_id, &job_id).await {
+        Ok(Some(job)) => Ok(MetaHttpResponse::json(job)),
+        Ok(None) => Ok(MetaHttpResponse::not_found("compaction job not found")),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
@@ -23,11 +23,15 @@ use tracing::{Instrument, Span};
 
 use crate::{
     common::{
-        meta::{self, http::HttpResponse as MetaHttpResponse},
+        meta::{self, http::HttpResponse as MetaHttpResponse, traces::SpanLogs},
         utils::http::get_or_create_trace_id,
     },
     handler::http::request::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO},
-    service::{search as SearchService, traces},
+    service::{
+        ingestion::idempotency,
+        search as SearchService, traces,
+        usage::{http_report_metrics, report_latency, LatencyKind},
+    },
 };
 
 /// TracesIngest
@@ -73,18 +77,21 @@ async fn handle_req(
         .headers()
         .get(&get_config().grpc.stream_header_key)
         .map(|header| header.to_str().unwrap());
-    if content_type.eq(CONTENT_TYPE_PROTO) {
-        traces::traces_proto(&org_id, body, in_stream_name).await
-    } else if content_type.starts_with(CONTENT_TYPE_JSON) {
-        traces::traces_json(&org_id, body, in_stream_name).await
-    } else {
-        Ok(
-            HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                "Bad Request".to_string(),
-            )),
-        )
-    }
+    idempotency::ingest_otlp_once(&org_id, &req, || async {
+        if content_type.eq(CONTENT_TYPE_PROTO) {
+            traces::traces_proto(&org_id, body, in_stream_name).await
+        } else if content_type.starts_with(CONTENT_TYPE_JSON) {
+            traces::traces_json(&org_id, body, in_stream_name).await
+        } else {
+            Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    "Bad Request".to_string(),
+                )),
+            )
+        }
+    })
+    .await
 }
 
 /// GetLatestTraces
@@ -266,6 +273,8 @@ pub async fn get_latest_traces(
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            wal_only: false,
+            fields: vec![],
         },
         encoding: config::meta::search::RequestEncoding::Empty,
         regions: vec![],
@@ -290,38 +299,43 @@ pub async fn get_latest_traces(
     let resp_search = match search_res {
         Ok(res) => res,
         Err(err) => {
+            log::error!("get traces latest data error: {:?}", err);
+            let resp = match err {
+                errors::Error::ErrorCode(code) => match code {
+                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
+                        .json(meta::http::HttpResponse::error_code(code)),
+                    errors::ErrorCodes::SearchFieldAccessDenied(_) => {
+                        HttpResponse::Forbidden().json(meta::http::HttpResponse::error_code(code))
+                    }
+                    _ => HttpResponse::InternalServerError()
+                        .json(meta::http::HttpResponse::error_code(code)),
+                },
+                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
+                    http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                    err.to_string(),
+                )),
+            };
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/traces/latest",
-                    "500",
+                    resp.status().as_str(),
                     &org_id,
                     "default",
                     stream_type.to_string().as_str(),
                 ])
                 .observe(time);
+            report_latency(LatencyKind::Search, "/api/org/traces/latest", &org_id, time);
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/traces/latest",
-                    "500",
+                    resp.status().as_str(),
                     &org_id,
                     "default",
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
-            log::error!("get traces latest data error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code(code)),
-                    _ => HttpResponse::InternalServerError()
-                        .json(meta::http::HttpResponse::error_code(code)),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(resp);
         }
     };
     if resp_search.hits.is_empty() {
@@ -381,38 +395,42 @@ pub async fn get_latest_traces(
         let resp_search = match search_res {
             Ok(res) => res,
             Err(err) => {
+                log::error!("get traces latest data error: {:?}", err);
+                let resp = match err {
+                    errors::Error::ErrorCode(code) => match code {
+                        errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
+                            .json(meta::http::HttpResponse::error_code(code)),
+                        errors::ErrorCodes::SearchFieldAccessDenied(_) => HttpResponse::Forbidden()
+                            .json(meta::http::HttpResponse::error_code(code)),
+                        _ => HttpResponse::InternalServerError()
+                            .json(meta::http::HttpResponse::error_code(code)),
+                    },
+                    _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
+                        http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                        err.to_string(),
+                    )),
+                };
                 let time = start.elapsed().as_secs_f64();
                 metrics::HTTP_RESPONSE_TIME
                     .with_label_values(&[
                         "/api/org/traces/latest",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         &stream_name,
                         stream_type.to_string().as_str(),
                     ])
                     .observe(time);
+                report_latency(LatencyKind::Search, "/api/org/traces/latest", &org_id, time);
                 metrics::HTTP_INCOMING_REQUESTS
                     .with_label_values(&[
                         "/api/org/traces/latest",
-                        "500",
+                        resp.status().as_str(),
                         &org_id,
                         &stream_name,
                         stream_type.to_string().as_str(),
                     ])
                     .inc();
-                log::error!("get traces latest data error: {:?}", err);
-                return Ok(match err {
-                    errors::Error::ErrorCode(code) => match code {
-                        errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                            .json(meta::http::HttpResponse::error_code(code)),
-                        _ => HttpResponse::InternalServerError()
-                            .json(meta::http::HttpResponse::error_code(code)),
-                    },
-                    _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                        http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                        err.to_string(),
-                    )),
-                });
+                return Ok(resp);
             }
         };
 
@@ -484,6 +502,7 @@ pub async fn get_latest_traces(
             stream_type.to_string().as_str(),
         ])
         .observe(time);
+    report_latency(LatencyKind::Search, "/api/org/traces/latest", &org_id, time);
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             "/api/org/traces/latest",
@@ -504,6 +523,127 @@ pub async fn get_latest_traces(
     Ok(HttpResponse::Ok().json(resp))
 }
 
+/// GetSpanLogs
+///
+/// Returns the logs emitted during the spans of a trace, grouped by span
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Traces",
+    operation_id = "GetSpanLogs",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Traces stream name"),
+        ("trace_id" = String, Path, description = "Trace id"),
+        ("span_id" = Option<String>, Query, description = "Only the logs of this span"),
+        ("logs_streams" = Option<String>, Query, description = "Comma separated logs streams to search, default all the logs streams"),
+        ("start_time" = i64, Query, description = "start time"),
+        ("end_time" = i64, Query, description = "end time"),
+        ("size" = Option<i64>, Query, description = "max logs returned per logs stream"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<SpanLogs>),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/traces/{trace_id}/logs")]
+pub async fn get_span_logs(
+    path: web::Path<(String, String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let start = std::time::Instant::now();
+    let cfg = get_config();
+
+    let (org_id, stream_name, span_trace_id) = path.into_inner();
+    let http_span = if cfg.common.tracing_search_enabled {
+        tracing::info_span!(
+            "/api/{org_id}/{stream_name}/traces/{trace_id}/logs",
+            org_id = org_id.clone(),
+            stream_name = stream_name.clone()
+        )
+    } else {
+        Span::none()
+    };
+    let trace_id = get_or_create_trace_id(in_req.headers(), &http_span);
+
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let start_time = query
+        .get("start_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    if start_time == 0 {
+        return Ok(MetaHttpResponse::bad_request("start_time is empty"));
+    }
+    let end_time = query
+        .get("end_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    if end_time == 0 {
+        return Ok(MetaHttpResponse::bad_request("end_time is empty"));
+    }
+    let size = query
+        .get("size")
+        .map_or(cfg.limit.query_default_limit, |v| {
+            v.parse::<i64>().unwrap_or(cfg.limit.query_default_limit)
+        });
+    let span_id = query.get("span_id").filter(|v| !v.is_empty());
+    let logs_streams = query
+        .get("logs_streams")
+        .map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+
+    let res = traces::logs::get_span_logs(
+        &trace_id,
+        &org_id,
+        &stream_name,
+        user_id,
+        &span_trace_id,
+        span_id.map(|v| v.as_str()),
+        (start_time, end_time),
+        logs_streams,
+        size,
+    )
+    .instrument(http_span)
+    .await;
+    match res {
+        Ok(spans) => {
+            http_report_metrics(
+                start,
+                &org_id,
+                StreamType::Traces,
+                &stream_name,
+                "200",
+                "traces/logs",
+            );
+            Ok(MetaHttpResponse::json(spans))
+        }
+        Err(errors::Error::Message(e)) => Ok(MetaHttpResponse::bad_request(e)),
+        Err(e) => {
+            http_report_metrics(
+                start,
+                &org_id,
+                StreamType::Traces,
+                &stream_name,
+                "500",
+                "traces/logs",
+            );
+            log::error!("get span logs error: {:?}", e);
+            Ok(MetaHttpResponse::internal_error(e))
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct TraceResponseItem {
     trace_id: String,
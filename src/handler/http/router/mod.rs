@@ -13,18 +13,27 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{rc::Rc, str::FromStr};
+use std::{
+    io::Write,
+    rc::Rc,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use actix_cors::Cors;
 use actix_web::{
-    body::MessageBody,
+    body::{BodySize, BoxBody, MessageBody},
     dev::{Service, ServiceRequest, ServiceResponse},
-    http::{header, ConnectionType, StatusCode},
+    http::{
+        header::{self, ContentEncoding},
+        ConnectionType, StatusCode,
+    },
     middleware, web, HttpRequest, HttpResponse,
 };
 use actix_web_httpauth::middleware::HttpAuthentication;
 use actix_web_lab::middleware::{from_fn, Next};
 use config::get_config;
+use flate2::{write::GzEncoder, Compression};
 use futures::FutureExt;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
@@ -44,7 +53,10 @@ use super::{
     auth::validator::{validator_aws, validator_gcp, validator_proxy_url, validator_rum},
     request::*,
 };
-use crate::common::meta::{middleware_data::RumExtraData, proxy::PathParamProxyURL};
+use crate::common::{
+    meta::{middleware_data::RumExtraData, proxy::PathParamProxyURL},
+    utils::http::{is_valid_trace_id, parse_traceparent},
+};
 
 pub mod openapi;
 pub mod ui;
@@ -57,7 +69,9 @@ fn get_cors() -> Rc<Cors> {
             header::ACCEPT,
             header::CONTENT_TYPE,
             header::HeaderName::from_lowercase(b"traceparent").unwrap(),
+            header::HeaderName::from_static(TRACE_ID_HEADER),
         ])
+        .expose_headers(vec![header::HeaderName::from_static(TRACE_ID_HEADER)])
         .allow_any_origin()
         .supports_credentials()
         .max_age(3600);
@@ -155,6 +169,162 @@ async fn check_keepalive(
     Ok(resp)
 }
 
+/// The response header with the trace id of the request
+const TRACE_ID_HEADER: &str = "x-o2-trace-id";
+
+/// Echoes the trace id of the request in the response, the trace id is taken from the
+/// `traceparent` header or the `X-O2-Trace-Id` header of the request, otherwise a new one is
+/// generated and set in the `traceparent` header so the handlers use the same trace id.
+async fn trace_id_middleware(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let traceparent = header::HeaderName::from_static("traceparent");
+    let trace_id = match req
+        .headers()
+        .get(&traceparent)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_traceparent)
+    {
+        Some(trace_id) => trace_id,
+        None => {
+            let trace_id = req
+                .headers()
+                .get(TRACE_ID_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| is_valid_trace_id(v))
+                .map(|v| v.to_lowercase())
+                .unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+            let span_id = hex::encode(rand::random::<[u8; 8]>());
+            if let Ok(v) = header::HeaderValue::from_str(&format!("00-{trace_id}-{span_id}-01")) {
+                req.headers_mut().insert(traceparent, v);
+            }
+            trace_id
+        }
+    };
+    let mut resp = next.call(req).await?;
+    if let Ok(v) = header::HeaderValue::from_str(&trace_id) {
+        resp.headers_mut()
+            .insert(header::HeaderName::from_static(TRACE_ID_HEADER), v);
+    }
+    Ok(resp)
+}
+
+/// Number of HTTP requests being handled, used to drain the node before shutdown.
+pub static HTTP_INFLIGHT_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+struct InflightGuard;
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        HTTP_INFLIGHT_REQUESTS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub async fn count_inflight_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    HTTP_INFLIGHT_REQUESTS.fetch_add(1, Ordering::Relaxed);
+    let _guard = InflightGuard;
+    next.call(req).await
+}
+
+/// Compresses the responses with the preferred encoding when the client accepts it, else with
+/// gzip. The streamed responses are left to the `Compress` middleware, which skips the
+/// responses already encoded.
+pub async fn compress_response(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let cfg = get_config();
+    let preferred =
+        ContentEncoding::from_str(&cfg.http.compression_preferred).unwrap_or(ContentEncoding::Zstd);
+    let encoding = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| negotiate_encoding(v, preferred));
+    let res = next.call(req).await?;
+
+    let Some(encoding) = encoding else {
+        return Ok(res.map_into_boxed_body());
+    };
+    if res.headers().contains_key(header::CONTENT_ENCODING)
+        || !matches!(res.response().body().size(), BodySize::Sized(n) if n > 0)
+    {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let body = actix_web::body::to_bytes(body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        actix_web::error::ErrorInternalServerError(e.to_string())
+    })?;
+    let body = compress_body(&body, encoding, cfg.http.compression_level)?;
+    res.headers_mut().insert(
+        header::CONTENT_ENCODING,
+        header::HeaderValue::from_static(encoding.as_str()),
+    );
+    res.headers_mut().append(
+        header::VARY,
+        header::HeaderValue::from_static("accept-encoding"),
+    );
+    res.headers_mut().remove(header::CONTENT_LENGTH);
+    Ok(ServiceResponse::new(
+        req,
+        res.set_body(body).map_into_boxed_body(),
+    ))
+}
+
+/// Picks the encoding of the response from the `Accept-Encoding` header of the request, the
+/// preferred encoding if the client accepts it, else gzip
+fn negotiate_encoding(
+    accept_encoding: &str,
+    preferred: ContentEncoding,
+) -> Option<ContentEncoding> {
+    let accepted = accept_encoding
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let name = parts.next()?.trim().to_lowercase();
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(1.0, |q| q.trim().parse::<f32>().unwrap_or(0.0));
+            (!name.is_empty()).then_some((name, quality))
+        })
+        .collect::<Vec<_>>();
+    // an explicit quality of 0 refuses the encoding even if `*` is accepted
+    let accepts = |encoding: ContentEncoding| {
+        accepted
+            .iter()
+            .find(|(name, _)| name == encoding.as_str())
+            .or_else(|| accepted.iter().find(|(name, _)| name == "*"))
+            .is_some_and(|(_, quality)| *quality > 0.0)
+    };
+    [preferred, ContentEncoding::Gzip]
+        .into_iter()
+        .find(|encoding| accepts(*encoding))
+}
+
+fn compress_body(
+    body: &[u8],
+    encoding: ContentEncoding,
+    level: i32,
+) -> Result<Vec<u8>, actix_web::Error> {
+    let ret = match encoding {
+        ContentEncoding::Zstd => zstd::encode_all(body, level),
+        ContentEncoding::Gzip => {
+            let mut encoder =
+                GzEncoder::new(Vec::new(), Compression::new(level.clamp(1, 9) as u32));
+            encoder.write_all(body).and_then(|_| encoder.finish())
+        }
+        _ => Ok(body.to_vec()),
+    };
+    ret.map_err(actix_web::error::ErrorInternalServerError)
+}
+
 /// This is a very trivial proxy to overcome the cors errors while
 /// session-replay in rrweb.
 pub fn get_proxy_routes(cfg: &mut web::ServiceConfig) {
@@ -227,7 +397,10 @@ pub fn get_basic_routes(cfg: &mut web::ServiceConfig) {
             .wrap(cors.clone())
             .service(status::cache_status)
             .service(status::enable_node)
-            .service(status::flush_node),
+            .service(status::flush_node)
+            .service(status::evict_node)
+            .service(status::unevict_node)
+            .service(status::list_evicted_nodes),
     );
 
     if get_config().common.swagger_enabled {
@@ -292,7 +465,11 @@ pub fn get_config_routes(cfg: &mut web::ServiceConfig) {
             .wrap(cors.clone())
             .service(status::zo_config)
             .service(status::logout)
-            .service(web::scope("/reload").service(status::config_reload)),
+            .service(
+                web::scope("/reload")
+                    .service(status::config_reload_settings)
+                    .service(status::config_reload),
+            ),
     );
 }
 
@@ -307,7 +484,11 @@ pub fn get_config_routes(cfg: &mut web::ServiceConfig) {
             .service(status::dex_login)
             .service(status::refresh_token_with_dex)
             .service(status::logout)
-            .service(web::scope("/reload").service(status::config_reload)),
+            .service(
+                web::scope("/reload")
+                    .service(status::config_reload_settings)
+                    .service(status::config_reload),
+            ),
     );
 }
 
@@ -331,12 +512,16 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             ))
             .wrap(cors.clone())
             .wrap(from_fn(check_keepalive))
+            .wrap(from_fn(trace_id_middleware))
             .wrap(middleware::DefaultHeaders::new().add(("X-Api-Node", server)))
             .service(users::list)
             .service(users::save)
             .service(users::delete)
             .service(users::update)
             .service(users::add_user_to_org)
+            .service(api_keys::create)
+            .service(api_keys::list)
+            .service(api_keys::delete)
             .service(organization::org::organizations)
             .service(organization::settings::get)
             .service(organization::settings::create)
@@ -358,11 +543,17 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(organization::es::org_data_stream)
             .service(organization::es::org_data_stream_create)
             .service(stream::schema)
+            .service(stream::schema_diff)
+            .service(stream::ingestion_lag)
             .service(stream::settings)
             .service(stream::update_settings)
             .service(stream::delete_fields)
             .service(stream::delete)
             .service(stream::list)
+            .service(stream::compact)
+            .service(stream::get_compact_job)
+            .service(stream::cancel_compact_job)
+            .service(stream::sample)
             .service(logs::ingest::bulk)
             .service(logs::ingest::multi)
             .service(logs::ingest::json)
@@ -370,6 +561,7 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(traces::traces_write)
             .service(traces::otlp_traces_write)
             .service(traces::get_latest_traces)
+            .service(traces::get_span_logs)
             .service(metrics::ingest::json)
             .service(metrics::ingest::otlp_metrics_write)
             .service(prom::remote_write)
@@ -383,6 +575,8 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(prom::labels_get)
             .service(prom::labels_post)
             .service(prom::label_values)
+            .service(prom::query_exemplars_get)
+            .service(prom::query_exemplars_post)
             .service(prom::format_query_get)
             .service(prom::format_query_post)
             .service(enrichment_table::save_enrichment_table)
@@ -391,14 +585,24 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(search::job::cancel_query)
             .service(search::job::query_status)
             .service(search::search_partition)
+            .service(search::websocket::search_ws)
             .service(search::around)
             .service(search::values)
             .service(search::search_history)
+            .service(search::search_timings)
+            .service(search::count)
+            .service(search::field_stats)
+            .service(search::validate_sql)
             .service(search::saved_view::create_view)
             .service(search::saved_view::update_view)
             .service(search::saved_view::get_view)
             .service(search::saved_view::get_views)
             .service(search::saved_view::delete_view)
+            .service(search::saved_view::save_export)
+            .service(search::saved_view::list_exports)
+            .service(search::saved_view::get_export)
+            .service(search::saved_view::delete_export)
+            .service(search::saved_view::list_export_runs)
             .service(functions::save_function)
             .service(functions::list_functions)
             .service(functions::delete_function)
@@ -432,6 +636,7 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(alerts::alert::delete_alert)
             .service(alerts::alert::enable_alert)
             .service(alerts::alert::trigger_alert)
+            .service(alerts::alert::backfill_alert)
             .service(alerts::templates::save_template)
             .service(alerts::templates::update_template)
             .service(alerts::templates::get_template)
@@ -462,10 +667,13 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(dashboards::folders::delete_folder)
             .service(dashboards::move_dashboard)
             .service(traces::get_latest_traces)
+            .service(traces::get_span_logs)
             .service(logs::ingest::multi)
             .service(logs::ingest::json)
             .service(logs::ingest::handle_kinesis_request)
             .service(logs::ingest::handle_gcp_request)
+            .service(logs::ingest::reingest)
+            .service(logs::ingest::get_reingest)
             .service(organization::org::create_org)
             .service(authz::fga::create_role)
             .service(authz::fga::get_roles)
@@ -537,6 +745,76 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_negotiate_encoding() {
+        let zstd = ContentEncoding::Zstd;
+        assert_eq!(
+            negotiate_encoding("gzip, deflate, br, zstd", zstd),
+            Some(ContentEncoding::Zstd)
+        );
+        assert_eq!(
+            negotiate_encoding("gzip, deflate", zstd),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(
+            negotiate_encoding("zstd;q=0, gzip;q=0.5", zstd),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(negotiate_encoding("*", zstd), Some(ContentEncoding::Zstd));
+        assert_eq!(negotiate_encoding("br, identity", zstd), None);
+        assert_eq!(
+            negotiate_encoding("gzip, zstd", ContentEncoding::Gzip),
+            Some(ContentEncoding::Gzip)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compress_response() {
+        let body = "a".repeat(1024);
+        let app = init_service(
+            App::new()
+                .wrap(middleware::Compress::default())
+                .wrap(from_fn(compress_response))
+                .route(
+                    "/_search",
+                    web::get().to(move || {
+                        let body = body.clone();
+                        async move { HttpResponse::Ok().body(body) }
+                    }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/_search")
+            .insert_header((header::ACCEPT_ENCODING, "gzip, zstd"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "zstd"
+        );
+        let body = actix_web::test::read_body(resp).await;
+        assert_eq!(
+            zstd::decode_all(body.as_ref()).unwrap(),
+            "a".repeat(1024).as_bytes()
+        );
+
+        let req = TestRequest::get()
+            .uri("/_search")
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+
+        let req = TestRequest::get().uri("/_search").to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
     #[tokio::test]
     async fn test_get_proxy_routes() {
         let mut app =
@@ -38,15 +38,24 @@ use crate::{common::meta, handler::http::request};
         request::organization::settings::create,
         request::stream::list,
         request::stream::schema,
+        request::stream::schema_diff,
+        request::stream::ingestion_lag,
         request::stream::settings,
         request::stream::update_settings,
         request::stream::delete_fields,
         request::stream::delete,
+        request::stream::compact,
+        request::stream::get_compact_job,
+        request::stream::cancel_compact_job,
+        request::stream::sample,
         request::logs::ingest::bulk,
         request::logs::ingest::multi,
         request::logs::ingest::json,
+        request::logs::ingest::reingest,
+        request::logs::ingest::get_reingest,
         request::traces::traces_write,
         request::traces::get_latest_traces,
+        request::traces::get_span_logs,
         request::metrics::ingest::json,
         request::prom::remote_write,
         request::prom::query_get,
@@ -55,6 +64,7 @@ use crate::{common::meta, handler::http::request};
         request::prom::series_get,
         request::prom::labels_get,
         request::prom::label_values,
+        request::prom::query_exemplars_get,
         request::prom::format_query_get,
         request::enrichment_table::save_enrichment_table,
         request::rum::ingest::log,
@@ -65,11 +75,20 @@ use crate::{common::meta, handler::http::request};
         request::search::around,
         request::search::values,
         request::search::search_history,
+        request::search::search_timings,
+        request::search::count,
+        request::search::field_stats,
+        request::search::validate_sql,
         request::search::saved_view::create_view,
         request::search::saved_view::delete_view,
         request::search::saved_view::get_view,
         request::search::saved_view::get_views,
         request::search::saved_view::update_view,
+        request::search::saved_view::save_export,
+        request::search::saved_view::list_exports,
+        request::search::saved_view::get_export,
+        request::search::saved_view::delete_export,
+        request::search::saved_view::list_export_runs,
         request::functions::list_functions,
         request::functions::update_function,
         request::functions::save_function,
@@ -96,6 +115,7 @@ use crate::{common::meta, handler::http::request};
         request::alerts::alert::delete_alert,
         request::alerts::alert::enable_alert,
         request::alerts::alert::trigger_alert,
+        request::alerts::alert::backfill_alert,
         request::alerts::templates::list_templates,
         request::alerts::templates::get_template,
         request::alerts::templates::save_template,
@@ -117,13 +137,26 @@ use crate::{common::meta, handler::http::request};
         request::clusters::list_clusters,
         request::short_url::shorten,
         request::short_url::retrieve,
+        request::api_keys::create,
+        request::api_keys::list,
+        request::api_keys::delete,
     ),
     components(
         schemas(
             meta::http::HttpResponse,
+            request::search::error_utils::SearchErrorResponse,
+            request::search::error_utils::SearchErrorType,
             StreamType,
             meta::stream::Stream,
             meta::stream::StreamProperty,
+            meta::stream::StreamSample,
+            meta::stream::StreamSchemaDiff,
+            meta::stream::IngestionLag,
+            meta::stream::SchemaField,
+            meta::stream::SchemaFieldChange,
+            meta::stream::CompactRequest,
+            meta::stream::CompactJobStatus,
+            meta::stream::CompactJob,
             meta::stream::StreamDeleteFields,
             meta::stream::ListStream,
             config::meta::stream::StreamSettings,
@@ -131,9 +164,22 @@ use crate::{common::meta, handler::http::request};
             config::meta::stream::StreamPartitionType,
             config::meta::stream::StreamStats,
             config::meta::stream::PartitionTimeLevel,
+            config::meta::stream::TimestampSettings,
+            config::meta::stream::FieldAccess,
+            config::meta::stream::RequiredField,
+            config::meta::stream::RequiredFieldType,
+            config::meta::stream::FieldAlias,
+            config::meta::stream::CollapseDuplicates,
+            config::meta::stream::ComputedColumn,
+            config::meta::stream::TimestampFormat,
+            config::meta::stream::TimestampErrorPolicy,
+            config::meta::stream::FlattenArrayMode,
             meta::ingestion::RecordStatus,
             meta::ingestion::StreamStatus,
             meta::ingestion::IngestionResponse,
+            meta::reingest::ReingestRequest,
+            meta::reingest::ReingestStatus,
+            meta::reingest::ReingestJob,
             meta::dashboards::Dashboard,
             meta::dashboards::Dashboards,
             meta::dashboards::v1::AxisItem,
@@ -152,11 +198,25 @@ use crate::{common::meta, handler::http::request};
             meta::dashboards::MoveDashboard,
             meta::dashboards::FolderList,
             config::meta::search::Query,
+            config::meta::search::SortBy,
+            config::meta::search::SortKey,
             config::meta::search::Request,
             config::meta::search::RequestEncoding,
             config::meta::search::Response,
             config::meta::search::ResponseTook,
+            config::meta::search::ResponseWarning,
+            config::meta::search::ResponseWarningCode,
             config::meta::search::ResponseNodeTook,
+            config::meta::search::ResponseRegionTook,
+            config::meta::search::SearchTiming,
+            config::meta::search::CountResponse,
+            config::meta::search::FieldStatsResponse,
+            config::meta::search::FieldStatsSources,
+            config::meta::search::FieldStatsSource,
+            config::meta::search::CostEstimate,
+            config::meta::search::ValidateSqlRequest,
+            config::meta::search::ValidateSqlResponse,
+            config::meta::search::SqlValidationError,
             config::meta::search::SearchEventType,
             config::meta::search::SearchPartitionRequest,
             config::meta::search::SearchPartitionResponse,
@@ -165,6 +225,7 @@ use crate::{common::meta, handler::http::request};
             config::meta::search::QueryStatus,
             config::meta::search::QueryInfo,
             config::meta::search::ScanStats,
+            meta::traces::SpanLogs,
             meta::saved_view::View,
             meta::saved_view::ViewWithoutData,
             meta::saved_view::ViewsWithoutData,
@@ -172,7 +233,13 @@ use crate::{common::meta, handler::http::request};
             meta::saved_view::DeleteViewResponse,
             meta::saved_view::CreateViewResponse,
             meta::saved_view::UpdateViewRequest,
+            meta::saved_view::ScheduledExport,
+            meta::saved_view::ScheduledExports,
+            meta::saved_view::ExportFormat,
+            meta::saved_view::ExportRun,
+            meta::saved_view::ExportRuns,
             meta::alerts::alert::Alert,
+            meta::alerts::alert::AlertBackfillResponse,
             meta::alerts::Condition,
             meta::alerts::Operator,
             meta::alerts::Aggregation,
@@ -180,6 +247,8 @@ use crate::{common::meta, handler::http::request};
             meta::alerts::QueryType,
             meta::alerts::TriggerCondition,
             meta::alerts::FrequencyType,
+            meta::alerts::ToleranceMode,
+            meta::alerts::DeduplicationConfig,
             meta::alerts::QueryCondition,
             meta::alerts::destinations::Destination,
             meta::alerts::destinations::DestinationWithTemplate,
@@ -194,6 +263,11 @@ use crate::{common::meta, handler::http::request};
             meta::user::UpdateUser,
             meta::user::UserRequest,
             meta::user::UserRole,
+            meta::api_key::ApiKeyScope,
+            meta::api_key::CreateApiKeyRequest,
+            meta::api_key::CreateApiKeyResponse,
+            meta::api_key::ApiKey,
+            meta::api_key::ApiKeys,
             meta::user::UserOrgRole,
             meta::user::UserList,
             meta::user::UserResponse,
@@ -239,6 +313,7 @@ use crate::{common::meta, handler::http::request};
         (name = "Syslog Routes", description = "Syslog Routes retrieval & management operations"),
         (name = "Clusters", description = "Super cluster operations"),
         (name = "Short Url", description = "Short Url Service"),
+        (name = "API Keys", description = "Scoped API keys retrieval & management operations"),
     ),
     info(
         description = "OpenObserve API documents [https://openobserve.ai/docs/](https://openobserve.ai/docs/)",
@@ -513,6 +513,13 @@ async fn load(root_dir: &PathBuf, scan_dir: &PathBuf) -> Result<(), anyhow::Erro
                                 "{}_{}_{}_{}",
                                 columns[1], columns[2], columns[3], columns[4]
                             );
+                            // the file was written when the result was cached
+                            let created_at = meta
+                                .modified()
+                                .ok()
+                                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                                .map(|d| d.as_micros() as i64)
+                                .unwrap_or_default();
                             let meta = columns[5].split('_').collect::<Vec<&str>>();
                             let is_aggregate = meta[2] == "1";
                             let is_descending = meta[3] == "1";
@@ -522,6 +529,7 @@ async fn load(root_dir: &PathBuf, scan_dir: &PathBuf) -> Result<(), anyhow::Erro
                                     end_time: meta[1].parse().unwrap(),
                                     is_aggregate,
                                     is_descending,
+                                    created_at,
                                 },
                             );
                         };
@@ -21,4 +21,7 @@ pub struct ResultCacheMeta {
     pub end_time: i64,
     pub is_aggregate: bool,
     pub is_descending: bool,
+    /// when the result was cached, in microseconds
+    #[serde(default)]
+    pub created_at: i64,
 }
@@ -25,7 +25,8 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use config::{cluster, get_config};
 use etcd_client::{
-    Certificate, DeleteOptions, EventType, GetOptions, Identity, SortOrder, SortTarget, TlsOptions,
+    Certificate, Compare, CompareOp, DeleteOptions, EventType, GetOptions, Identity, SortOrder,
+    SortTarget, TlsOptions, Txn, TxnOp,
 };
 use hashbrown::HashMap;
 use tokio::{
@@ -685,6 +686,33 @@ impl Locker {
     }
 }
 
+/// Get the record of a lease lock and its revision
+pub(crate) async fn lease_get(key: &str) -> Result<Option<(Bytes, u64)>> {
+    let key = format!("{}lease_locker{}", get_config().etcd.prefix, key);
+    let mut client = get_etcd_client().await.clone();
+    let resp = client.get(key, None).await?;
+    Ok(resp
+        .kvs()
+        .first()
+        .map(|kv| (Bytes::from(kv.value().to_vec()), kv.mod_revision() as u64)))
+}
+
+/// Put the record of a lease lock only if its revision is still `revision`, 0 means the record
+/// doesn't exist. Returns false if the record was changed by others.
+pub(crate) async fn lease_compare_and_put(key: &str, revision: u64, value: Bytes) -> Result<bool> {
+    let key = format!("{}lease_locker{}", get_config().etcd.prefix, key);
+    let mut client = get_etcd_client().await.clone();
+    let txn = Txn::new()
+        .when([Compare::mod_revision(
+            key.as_str(),
+            CompareOp::Equal,
+            revision as i64,
+        )])
+        .and_then([TxnOp::put(key.as_str(), value.to_vec(), None)]);
+    let resp = client.txn(txn).await?;
+    Ok(resp.succeeded())
+}
+
 #[cfg(test)]
 mod tests {
     use super::{super::Db, *};
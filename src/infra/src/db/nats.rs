@@ -824,6 +824,44 @@ impl Locker {
     }
 }
 
+/// Get the record of a lease lock and its revision
+pub(crate) async fn lease_get(key: &str) -> Result<Option<(Bytes, u64)>> {
+    let cfg = get_config();
+    let key = format!("/lease_locker{}", key);
+    let (bucket, new_key) = get_bucket_by_key(&cfg.nats.prefix, &key).await?;
+    let key = key_encode(new_key);
+    let entry = bucket
+        .entry(&key)
+        .await
+        .map_err(|e| Error::Message(format!("nats get lease for key: {}, error: {}", key, e)))?;
+    Ok(entry
+        .filter(|e| e.operation == jetstream::kv::Operation::Put)
+        .map(|e| (e.value, e.revision)))
+}
+
+/// Put the record of a lease lock only if its revision is still `revision`, 0 means the record
+/// doesn't exist. Returns false if the record was changed by others.
+pub(crate) async fn lease_compare_and_put(key: &str, revision: u64, value: Bytes) -> Result<bool> {
+    let cfg = get_config();
+    let key = format!("/lease_locker{}", key);
+    let (bucket, new_key) = get_bucket_by_key(&cfg.nats.prefix, &key).await?;
+    let key = key_encode(new_key);
+    let ret = if revision == 0 {
+        bucket.create(&key, value).await.map_err(|e| e.to_string())
+    } else {
+        bucket
+            .update(&key, value, revision)
+            .await
+            .map_err(|e| e.to_string())
+    };
+    if let Err(e) = ret {
+        // the revision is changed by others, or the key is created by others
+        log::debug!("nats put lease for key: {}, error: {}", key, e);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
 #[inline]
 fn key_encode(key: &str) -> String {
     base64::encode(key).replace('+', "-").replace('/', "_")
@@ -13,13 +13,35 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use hashbrown::HashSet;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use config::{
+    ider,
+    utils::{json, time::now_micros},
+};
+use hashbrown::{HashMap, HashSet};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
 
 use crate::{
     db::{etcd, nats},
-    errors::Result,
+    errors::{Error, Result},
 };
 
+const LEASE_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// lease records for local mode
+static LOCAL_LEASES: Lazy<Mutex<HashMap<String, (Bytes, u64)>>> = Lazy::new(Default::default);
+
 pub struct Locker(LockerStore);
 
 enum LockerStore {
@@ -106,3 +128,292 @@ pub async fn unlock(locker: &Option<Locker>) -> Result<()> {
         Ok(())
     }
 }
+
+/// A lock held by a lease. The lease is renewed in background while the lock is held, if the
+/// holder crashes others can reclaim the lock once the lease expires.
+///
+/// Every acquisition gets a fencing token which is greater than the tokens of all the previous
+/// holders of the same key. A paused holder may still believe it owns the lock after its lease
+/// expired, so storage writes guarded by the lock should carry the token: store the token along
+/// with the data and reject, in the same transaction, a write whose token is less than the stored
+/// one. `check` verifies the lease is still held right before committing, which narrows the window
+/// but can't close it on its own.
+pub struct LeaseLock {
+    key: String,
+    holder: String,
+    token: u64,
+    store: LeaseStore,
+    lost: Arc<AtomicBool>,
+    renewer: Option<JoinHandle<()>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Lease {
+    holder: String,
+    token: u64,
+    expires_at: i64,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum LeaseStore {
+    Local,
+    Etcd,
+    Nats,
+}
+
+impl LeaseStore {
+    fn new() -> Self {
+        let cfg = config::get_config();
+        if cfg.common.local_mode {
+            return Self::Local;
+        }
+        match cfg.common.cluster_coordinator.as_str() {
+            "nats" => Self::Nats,
+            _ => Self::Etcd,
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<(Lease, u64)>> {
+        let ret = match self {
+            Self::Local => LOCAL_LEASES.lock().get(key).cloned(),
+            Self::Etcd => etcd::lease_get(key).await?,
+            Self::Nats => nats::lease_get(key).await?,
+        };
+        match ret {
+            Some((value, revision)) => Ok(Some((json::from_slice(&value)?, revision))),
+            None => Ok(None),
+        }
+    }
+
+    async fn compare_and_put(&self, key: &str, revision: u64, lease: &Lease) -> Result<bool> {
+        let value = Bytes::from(json::to_vec(lease)?);
+        match self {
+            Self::Local => {
+                let mut leases = LOCAL_LEASES.lock();
+                let current = leases.get(key).map(|(_, r)| *r).unwrap_or_default();
+                if current != revision {
+                    return Ok(false);
+                }
+                leases.insert(key.to_string(), (value, revision + 1));
+                Ok(true)
+            }
+            Self::Etcd => etcd::lease_compare_and_put(key, revision, value).await,
+            Self::Nats => nats::lease_compare_and_put(key, revision, value).await,
+        }
+    }
+}
+
+/// Acquire a lease lock, `ttl` is the lease time in seconds, `wait_ttl` is how long to wait for
+/// the lock in seconds, 0 means don't wait
+pub async fn lease_lock(key: &str, ttl: u64, wait_ttl: u64) -> Result<LeaseLock> {
+    LeaseLock::acquire(LeaseStore::new(), key, ttl, wait_ttl, true).await
+}
+
+impl LeaseLock {
+    async fn acquire(
+        store: LeaseStore,
+        key: &str,
+        ttl: u64,
+        wait_ttl: u64,
+        auto_renew: bool,
+    ) -> Result<Self> {
+        let ttl = ttl.max(1);
+        let holder = ider::uuid();
+        let deadline = now_micros() + Duration::from_secs(wait_ttl).as_micros() as i64;
+        loop {
+            let now = now_micros();
+            let (prev, revision) = match store.get(key).await? {
+                Some((lease, revision)) => (Some(lease), revision),
+                None => (None, 0),
+            };
+            if prev.as_ref().map_or(true, |lease| lease.expires_at <= now) {
+                let lease = Lease {
+                    holder: holder.clone(),
+                    token: prev.map_or(1, |lease| lease.token + 1),
+                    expires_at: now + Duration::from_secs(ttl).as_micros() as i64,
+                };
+                if store.compare_and_put(key, revision, &lease).await? {
+                    let mut locker = Self {
+                        key: key.to_string(),
+                        holder,
+                        token: lease.token,
+                        store,
+                        lost: Arc::new(AtomicBool::new(false)),
+                        renewer: None,
+                    };
+                    if auto_renew {
+                        locker.start_renew(ttl);
+                    }
+                    return Ok(locker);
+                }
+            }
+            if now >= deadline {
+                return Err(Error::Message(format!(
+                    "lease lock for key: {key}, acquire timeout in {wait_ttl}s"
+                )));
+            }
+            tokio::time::sleep(LEASE_RETRY_INTERVAL).await;
+        }
+    }
+
+    /// renew the lease every third of the ttl until the lock is released or lost
+    fn start_renew(&mut self, ttl: u64) {
+        let store = self.store;
+        let key = self.key.clone();
+        let holder = self.holder.clone();
+        let token = self.token;
+        let lost = self.lost.clone();
+        self.renewer = Some(tokio::task::spawn(async move {
+            let interval = Duration::from_millis(ttl * 1000 / 3);
+            loop {
+                tokio::time::sleep(interval).await;
+                let now = now_micros();
+                let (lease, revision) = match store.get(&key).await {
+                    Ok(Some(v)) => v,
+                    Ok(None) => {
+                        lost.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                    Err(e) => {
+                        log::error!("lease lock for key: {key}, renew error: {e}");
+                        continue;
+                    }
+                };
+                if lease.holder != holder || lease.token != token || lease.expires_at <= now {
+                    log::warn!("lease lock for key: {key}, lease lost with token: {token}");
+                    lost.store(true, Ordering::SeqCst);
+                    break;
+                }
+                let lease = Lease {
+                    expires_at: now + Duration::from_secs(ttl).as_micros() as i64,
+                    ..lease
+                };
+                match store.compare_and_put(&key, revision, &lease).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        log::warn!("lease lock for key: {key}, lease lost with token: {token}");
+                        lost.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                    Err(e) => log::error!("lease lock for key: {key}, renew error: {e}"),
+                }
+            }
+        }));
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The fencing token of this acquisition
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Check the lock is still held, call it right before committing the guarded operation
+    pub async fn check(&self) -> Result<bool> {
+        if self.lost.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
+        Ok(self.store.get(&self.key).await?.is_some_and(|(lease, _)| {
+            lease.holder == self.holder
+                && lease.token == self.token
+                && lease.expires_at > now_micros()
+        }))
+    }
+
+    /// Release the lock, the token is kept in the record so that the next holder gets a greater one
+    pub async fn release(mut self) -> Result<()> {
+        if let Some(renewer) = self.renewer.take() {
+            renewer.abort();
+        }
+        let Some((lease, revision)) = self.store.get(&self.key).await? else {
+            return Ok(());
+        };
+        if lease.holder != self.holder || lease.token != self.token {
+            return Ok(());
+        }
+        let lease = Lease {
+            expires_at: 0,
+            ..lease
+        };
+        self.store
+            .compare_and_put(&self.key, revision, &lease)
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for LeaseLock {
+    fn drop(&mut self) {
+        // the lease expires by itself if the lock is not released
+        if let Some(renewer) = self.renewer.take() {
+            renewer.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lease_lock_token() {
+        let key = "/test/lease/token";
+        let locker = LeaseLock::acquire(LeaseStore::Local, key, 10, 0, true)
+            .await
+            .unwrap();
+        assert!(locker.check().await.unwrap());
+        assert!(
+            LeaseLock::acquire(LeaseStore::Local, key, 10, 0, true)
+                .await
+                .is_err()
+        );
+        let token = locker.token();
+        locker.release().await.unwrap();
+
+        let locker = LeaseLock::acquire(LeaseStore::Local, key, 10, 0, true)
+            .await
+            .unwrap();
+        assert_eq!(locker.token(), token + 1);
+    }
+
+    #[tokio::test]
+    async fn test_lease_lock_renew() {
+        let key = "/test/lease/renew";
+        let locker = LeaseLock::acquire(LeaseStore::Local, key, 1, 0, true)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(locker.check().await.unwrap());
+        assert!(
+            LeaseLock::acquire(LeaseStore::Local, key, 1, 0, true)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lease_lock_expiry_reacquire() {
+        let key = "/test/lease/expiry";
+        // a holder which stopped renewing, e.g. a crashed or paused node
+        let zombie = LeaseLock::acquire(LeaseStore::Local, key, 1, 0, false)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        // racing acquisitions after expiry, only one of them wins
+        let (a, b) = tokio::join!(
+            LeaseLock::acquire(LeaseStore::Local, key, 10, 0, true),
+            LeaseLock::acquire(LeaseStore::Local, key, 10, 0, true),
+        );
+        assert!(a.is_ok() ^ b.is_ok());
+        let locker = a.or(b).unwrap();
+        assert_eq!(locker.token(), zombie.token() + 1);
+
+        // the zombie can't act or release the new holder's lock
+        assert!(!zombie.check().await.unwrap());
+        zombie.release().await.unwrap();
+        assert!(locker.check().await.unwrap());
+    }
+}
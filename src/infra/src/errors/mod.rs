@@ -103,6 +103,9 @@ pub enum ErrorCodes {
     SearchSQLExecuteError(String),
     SearchCancelQuery(String),
     SearchTimeout(String),
+    SearchFieldAccessDenied(String),
+    SearchPartialResult(String),
+    SearchQueryRangeExceeded(String),
 }
 
 impl std::fmt::Display for ErrorCodes {
@@ -130,6 +133,9 @@ impl ErrorCodes {
             ErrorCodes::SearchSQLExecuteError(_) => 20008,
             ErrorCodes::SearchCancelQuery(_) => 20009,
             ErrorCodes::SearchTimeout(_) => 20010,
+            ErrorCodes::SearchFieldAccessDenied(_) => 20011,
+            ErrorCodes::SearchPartialResult(_) => 20012,
+            ErrorCodes::SearchQueryRangeExceeded(_) => 20013,
         }
     }
 
@@ -154,6 +160,11 @@ impl ErrorCodes {
             ErrorCodes::SearchSQLExecuteError(_) => "Search SQL execute error".to_string(),
             ErrorCodes::SearchCancelQuery(_) => "Search query was cancelled".to_string(),
             ErrorCodes::SearchTimeout(_) => "Search query timed out".to_string(),
+            ErrorCodes::SearchFieldAccessDenied(_) => "Search field access denied".to_string(),
+            ErrorCodes::SearchPartialResult(_) => "Search only got a part of the data".to_string(),
+            ErrorCodes::SearchQueryRangeExceeded(_) => {
+                "Search query range exceeds the max query range".to_string()
+            }
         }
     }
 
@@ -170,6 +181,9 @@ impl ErrorCodes {
             ErrorCodes::SearchSQLExecuteError(msg) => msg.to_owned(),
             ErrorCodes::SearchCancelQuery(msg) => msg.to_owned(),
             ErrorCodes::SearchTimeout(msg) => msg.to_owned(),
+            ErrorCodes::SearchFieldAccessDenied(msg) => msg.to_owned(),
+            ErrorCodes::SearchPartialResult(msg) => msg.to_owned(),
+            ErrorCodes::SearchQueryRangeExceeded(msg) => msg.to_owned(),
         }
     }
 
@@ -186,6 +200,9 @@ impl ErrorCodes {
             ErrorCodes::SearchSQLExecuteError(msg) => msg.to_owned(),
             ErrorCodes::SearchCancelQuery(msg) => msg.to_string(),
             ErrorCodes::SearchTimeout(msg) => msg.to_owned(),
+            ErrorCodes::SearchFieldAccessDenied(msg) => msg.to_owned(),
+            ErrorCodes::SearchPartialResult(msg) => msg.to_owned(),
+            ErrorCodes::SearchQueryRangeExceeded(msg) => msg.to_owned(),
         }
     }
 
@@ -235,6 +252,9 @@ impl ErrorCodes {
             20008 => Ok(ErrorCodes::SearchSQLExecuteError(message)),
             20009 => Ok(ErrorCodes::SearchCancelQuery(message)),
             20010 => Ok(ErrorCodes::SearchTimeout(message)),
+            20011 => Ok(ErrorCodes::SearchFieldAccessDenied(message)),
+            20012 => Ok(ErrorCodes::SearchPartialResult(message)),
+            20013 => Ok(ErrorCodes::SearchQueryRangeExceeded(message)),
             _ => Ok(ErrorCodes::ServerInternalError(json.to_string())),
         }
     }
@@ -457,6 +457,8 @@ pub struct FileRecord {
     pub compressed_size: i64,
     #[sqlx(default)]
     pub flattened: bool,
+    #[sqlx(default)]
+    pub index_size: i64,
 }
 
 impl From<&FileRecord> for FileMeta {
@@ -468,6 +470,7 @@ impl From<&FileRecord> for FileMeta {
             original_size: record.original_size,
             compressed_size: record.compressed_size,
             flattened: record.flattened,
+            index_size: record.index_size,
         }
     }
 }
@@ -533,4 +536,6 @@ pub struct FileId {
     pub id: i64,
     pub records: i64,
     pub original_size: i64,
+    pub min_ts: i64,
+    pub max_ts: i64,
 }
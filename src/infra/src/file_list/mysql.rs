@@ -263,7 +263,7 @@ impl super::FileList for MysqlFileList {
         let start = std::time::Instant::now();
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list WHERE stream = ? AND date = ? AND file = ?;
             "#,
         )
@@ -352,7 +352,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let ret = if flattened.is_some() {
             sqlx::query_as::<_, super::FileRecord>(
                 r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list
     WHERE stream = ? AND flattened = ? LIMIT 1000;
                 "#,
@@ -369,7 +369,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                     time_end + cfg.limit.upper_bound_for_max_ts * 60 * 1_000_000;
                 sqlx::query_as::<_, super::FileRecord>(
                 r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list
     WHERE stream = ? AND max_ts >= ? AND max_ts <= ? AND min_ts <= ?;
                 "#,
@@ -383,7 +383,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
             } else {
                 sqlx::query_as::<_, super::FileRecord>(
                 r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list
     WHERE stream = ? AND max_ts >= ? AND min_ts <= ?;
                 "#,
@@ -427,7 +427,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                 .collect::<Vec<String>>()
                 .join(",");
             let query_str = format!(
-                "SELECT id, stream, date, file, min_ts, max_ts, records, original_size, compressed_size FROM file_list WHERE id IN ({ids})"
+                "SELECT id, stream, date, file, min_ts, max_ts, records, original_size, compressed_size, index_size FROM file_list WHERE id IN ({ids})"
             );
             DB_QUERY_NUMS
                 .with_label_values(&["select", "file_list"])
@@ -501,7 +501,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                 .inc();
                  if cfg.limit.use_upper_bound_for_max_ts {
                     let max_ts_upper_bound = time_end + cfg.limit.upper_bound_for_max_ts * 60 * 1_000_000;
-                    let query = "SELECT id, records, original_size FROM file_list WHERE stream = ? AND max_ts >= ? AND max_ts <= ? AND min_ts <= ?;";
+                    let query = "SELECT id, records, original_size, min_ts, max_ts FROM file_list WHERE stream = ? AND max_ts >= ? AND max_ts <= ? AND min_ts <= ?;";
                     sqlx::query_as::<_, super::FileId>(query)
                     .bind(stream_key)
                     .bind(time_start)
@@ -510,7 +510,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                     .fetch_all(&pool)
                     .await
                 } else {
-                    let query = "SELECT id, records, original_size FROM file_list WHERE stream = ? AND max_ts >= ? AND min_ts <= ?;"; 
+                    let query = "SELECT id, records, original_size, min_ts, max_ts FROM file_list WHERE stream = ? AND max_ts >= ? AND min_ts <= ?;"; 
                     sqlx::query_as::<_, super::FileId>(query)
                     .bind(stream_key)
                     .bind(time_start)
@@ -1195,8 +1195,8 @@ impl MysqlFileList {
         DB_QUERY_NUMS.with_label_values(&["insert", table]).inc();
         match  sqlx::query(
             format!(r#"
-INSERT IGNORE INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened)
-    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
+INSERT IGNORE INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
             "#).as_str(),
         )
         .bind(org_id)
@@ -1210,6 +1210,7 @@ INSERT IGNORE INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, re
         .bind(meta.original_size)
         .bind(meta.compressed_size)
         .bind(meta.flattened)
+        .bind(meta.index_size)
         .execute(&pool)
         .await {
             Err(sqlx::Error::Database(e)) => if e.is_unique_violation() {
@@ -1231,7 +1232,7 @@ INSERT IGNORE INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, re
         for files in chunks {
             let mut tx = pool.begin().await?;
             let mut query_builder: QueryBuilder<MySql> = QueryBuilder::new(
-                format!("INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened)").as_str(),
+                format!("INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size)").as_str(),
             );
             query_builder.push_values(files, |mut b, item| {
                 let (stream_key, date_key, file_name) =
@@ -1247,7 +1248,8 @@ INSERT IGNORE INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, re
                     .push_bind(item.meta.records)
                     .push_bind(item.meta.original_size)
                     .push_bind(item.meta.compressed_size)
-                    .push_bind(item.meta.flattened);
+                    .push_bind(item.meta.flattened)
+                    .push_bind(item.meta.index_size);
             });
             DB_QUERY_NUMS.with_label_values(&["insert", table]).inc();
             let need_single_insert = match query_builder.build().execute(&mut *tx).await {
@@ -1309,7 +1311,8 @@ CREATE TABLE IF NOT EXISTS file_list
     max_ts    BIGINT not null,
     records   BIGINT not null,
     original_size   BIGINT not null,
-    compressed_size BIGINT not null
+    compressed_size BIGINT not null,
+    index_size      BIGINT default 0 not null
 );
         "#,
     )
@@ -1334,7 +1337,8 @@ CREATE TABLE IF NOT EXISTS file_list_history
     max_ts    BIGINT not null,
     records   BIGINT not null,
     original_size   BIGINT not null,
-    compressed_size BIGINT not null
+    compressed_size BIGINT not null,
+    index_size      BIGINT default 0 not null
 );
         "#,
     )
@@ -1416,6 +1420,13 @@ CREATE TABLE IF NOT EXISTS stream_stats
     let data_type = "BIGINT default 0 not null";
     add_column("file_list_jobs", column, data_type).await?;
 
+    // create column index_size for old version <= 0.12.0, the files before it have an unknown
+    // index size
+    let column = "index_size";
+    let data_type = "BIGINT default -1 not null";
+    add_column("file_list", column, data_type).await?;
+    add_column("file_list_history", column, data_type).await?;
+
     Ok(())
 }
 
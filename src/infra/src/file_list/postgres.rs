@@ -277,7 +277,7 @@ impl super::FileList for PostgresFileList {
         let start = std::time::Instant::now();
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list WHERE stream = $1 AND date = $2 AND file = $3;
             "#
             )
@@ -366,7 +366,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let ret = if flattened.is_some() {
             sqlx::query_as::<_, super::FileRecord>(
                 r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list 
     WHERE stream = $1 AND flattened = $2 LIMIT 1000;
                 "#
@@ -381,7 +381,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                 let max_ts_upper_bound =
                     time_end + cfg.limit.upper_bound_for_max_ts * 60 * 1_000_000;
                 let sql = r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list 
     WHERE stream = $1 AND max_ts >= $2 AND max_ts <= $3 AND min_ts <= $4;
                 "#;
@@ -395,7 +395,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                     .await
             } else {
                 let sql = r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size
     FROM file_list 
     WHERE stream = $1 AND max_ts >= $2 AND min_ts <= $3;
                 "#;
@@ -440,7 +440,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                 .collect::<Vec<String>>()
                 .join(",");
             let query_str = format!(
-                "SELECT id, stream, date, file, min_ts, max_ts, records, original_size, compressed_size FROM file_list WHERE id IN ({ids})"
+                "SELECT id, stream, date, file, min_ts, max_ts, records, original_size, compressed_size, index_size FROM file_list WHERE id IN ({ids})"
             );
             DB_QUERY_NUMS
                 .with_label_values(&["select", "file_list"])
@@ -514,7 +514,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                 .inc();
                 if cfg.limit.use_upper_bound_for_max_ts {
                     let max_ts_upper_bound = time_end + cfg.limit.upper_bound_for_max_ts * 60 * 1_000_000;
-                    let query = "SELECT id, records, original_size FROM file_list WHERE stream = $1 AND max_ts >= $2 AND max_ts <= $3 AND min_ts <= $4;";
+                    let query = "SELECT id, records, original_size, min_ts, max_ts FROM file_list WHERE stream = $1 AND max_ts >= $2 AND max_ts <= $3 AND min_ts <= $4;";
                     sqlx::query_as::<_, super::FileId>(query)
                     .bind(stream_key)
                     .bind(time_start)
@@ -523,7 +523,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
                     .fetch_all(&pool)
                     .await
                 } else {
-                    let query = "SELECT id, records, original_size FROM file_list WHERE stream = $1 AND max_ts >= $2 AND min_ts <= $3;";
+                    let query = "SELECT id, records, original_size, min_ts, max_ts FROM file_list WHERE stream = $1 AND max_ts >= $2 AND min_ts <= $3;";
                     sqlx::query_as::<_, super::FileId>(query)
                     .bind(stream_key)
                     .bind(time_start)
@@ -1156,8 +1156,8 @@ impl PostgresFileList {
                 ::query(
                     format!(
                         r#"
-INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened)
-    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
     ON CONFLICT DO NOTHING;
             "#
                     ).as_str()
@@ -1173,6 +1173,7 @@ INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records,
                 .bind(meta.original_size)
                 .bind(meta.compressed_size)
                 .bind(meta.flattened)
+                .bind(meta.index_size)
                 .execute(&pool).await
         {
             Err(sqlx::Error::Database(e)) => if e.is_unique_violation() {
@@ -1195,7 +1196,7 @@ INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records,
             let mut tx = pool.begin().await?;
             let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
                 format!(
-                    "INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened)"
+                    "INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, flattened, index_size)"
                 ).as_str()
             );
             query_builder.push_values(files, |mut b, item| {
@@ -1212,7 +1213,8 @@ INSERT INTO {table} (org, stream, date, file, deleted, min_ts, max_ts, records,
                     .push_bind(item.meta.records)
                     .push_bind(item.meta.original_size)
                     .push_bind(item.meta.compressed_size)
-                    .push_bind(item.meta.flattened);
+                    .push_bind(item.meta.flattened)
+                    .push_bind(item.meta.index_size);
             });
             DB_QUERY_NUMS.with_label_values(&["insert", table]).inc();
             let need_single_insert = match query_builder.build().execute(&mut *tx).await {
@@ -1274,7 +1276,8 @@ CREATE TABLE IF NOT EXISTS file_list
     max_ts    BIGINT not null,
     records   BIGINT not null,
     original_size   BIGINT not null,
-    compressed_size BIGINT not null
+    compressed_size BIGINT not null,
+    index_size      BIGINT default 0 not null
 );
         "#,
     )
@@ -1299,7 +1302,8 @@ CREATE TABLE IF NOT EXISTS file_list_history
     max_ts    BIGINT not null,
     records   BIGINT not null,
     original_size   BIGINT not null,
-    compressed_size BIGINT not null
+    compressed_size BIGINT not null,
+    index_size      BIGINT default 0 not null
 );
         "#,
     )
@@ -1381,6 +1385,13 @@ CREATE TABLE IF NOT EXISTS stream_stats
     let data_type = "BIGINT default 0 not null";
     add_column("file_list_jobs", column, data_type).await?;
 
+    // create column index_size for old version <= 0.12.0, the files before it have an unknown
+    // index size
+    let column = "index_size";
+    let data_type = "BIGINT default -1 not null";
+    add_column("file_list", column, data_type).await?;
+    add_column("file_list_history", column, data_type).await?;
+
     Ok(())
 }
 
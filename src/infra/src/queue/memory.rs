@@ -0,0 +1,295 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    cmp::max,
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use tokio::sync::{mpsc, Notify};
+
+use crate::errors::*;
+
+const REDELIVERY_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// In-process queue with the same at-least-once delivery as the other queues
+pub struct MemoryQueue {
+    topics: Mutex<HashMap<String, Arc<Topic>>>,
+    visibility_timeout: Duration,
+    max_redelivery: u32,
+}
+
+#[derive(Default)]
+struct Topic {
+    state: Mutex<TopicState>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct TopicState {
+    next_id: u64,
+    pending: VecDeque<Entry>,
+    in_flight: HashMap<u64, (Entry, Instant)>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    id: u64,
+    payload: Bytes,
+    delivered: u32,
+}
+
+pub struct MemoryMessage {
+    id: u64,
+    payload: Bytes,
+    topic: Arc<Topic>,
+}
+
+impl MemoryQueue {
+    pub fn new(visibility_timeout: Duration, max_redelivery: u32) -> Self {
+        Self {
+            topics: Mutex::new(HashMap::new()),
+            visibility_timeout,
+            max_redelivery,
+        }
+    }
+
+    fn topic(&self, topic: &str) -> Arc<Topic> {
+        self.topics
+            .lock()
+            .entry(topic.to_string())
+            .or_default()
+            .clone()
+    }
+}
+
+impl Default for MemoryQueue {
+    fn default() -> Self {
+        let cfg = config::get_config();
+        Self::new(
+            Duration::from_secs(max(1, cfg.common.queue_visibility_timeout)),
+            cfg.common.queue_max_redelivery,
+        )
+    }
+}
+
+impl Topic {
+    fn push(&self, payload: Bytes) {
+        let mut state = self.state.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pending.push_back(Entry {
+            id,
+            payload,
+            delivered: 0,
+        });
+        drop(state);
+        self.notify.notify_one();
+    }
+
+    /// Returns the next message to deliver and the messages exceeded the max redelivery
+    fn next(
+        &self,
+        visibility_timeout: Duration,
+        max_redelivery: u32,
+    ) -> (Option<Entry>, Vec<Entry>) {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        // messages not acknowledged in time are delivered again
+        let expired = state
+            .in_flight
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in expired {
+            if let Some((entry, _)) = state.in_flight.remove(&id) {
+                state.pending.push_back(entry);
+            }
+        }
+
+        let mut dead = Vec::new();
+        while let Some(mut entry) = state.pending.pop_front() {
+            if entry.delivered > max_redelivery {
+                dead.push(entry);
+                continue;
+            }
+            entry.delivered += 1;
+            state
+                .in_flight
+                .insert(entry.id, (entry.clone(), now + visibility_timeout));
+            return (Some(entry), dead);
+        }
+        (None, dead)
+    }
+}
+
+impl MemoryMessage {
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    pub fn ack(&self) {
+        self.topic.state.lock().in_flight.remove(&self.id);
+    }
+
+    pub fn nack(&self) {
+        let mut state = self.topic.state.lock();
+        if let Some((entry, _)) = state.in_flight.remove(&self.id) {
+            state.pending.push_front(entry);
+        }
+        drop(state);
+        self.topic.notify.notify_one();
+    }
+}
+
+#[async_trait]
+impl super::Queue for MemoryQueue {
+    async fn create(&self, topic: &str) -> Result<()> {
+        self.topic(topic);
+        self.topic(&super::dead_letter_topic(topic));
+        Ok(())
+    }
+
+    async fn publish(&self, topic: &str, value: Bytes) -> Result<()> {
+        self.topic(topic).push(value);
+        Ok(())
+    }
+
+    async fn consume(&self, topic: &str) -> Result<Arc<mpsc::Receiver<super::Message>>> {
+        let (tx, rx) = mpsc::channel(1024);
+        let topic_name = topic.to_string();
+        let topic = self.topic(topic);
+        let dead_letter = self.topic(&super::dead_letter_topic(&topic_name));
+        let visibility_timeout = self.visibility_timeout;
+        let max_redelivery = self.max_redelivery;
+        tokio::task::spawn(async move {
+            loop {
+                let (entry, dead) = topic.next(visibility_timeout, max_redelivery);
+                for entry in dead {
+                    log::warn!("memory queue message of {topic_name} exceeded max redelivery");
+                    dead_letter.push(entry.payload);
+                }
+                match entry {
+                    Some(entry) => {
+                        let message = super::Message::Memory(MemoryMessage {
+                            id: entry.id,
+                            payload: entry.payload,
+                            topic: topic.clone(),
+                        });
+                        // the consumer is gone, the message stays in flight and is delivered
+                        // again to the next consumer after the visibility timeout
+                        if tx.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                    None => {
+                        if tx.is_closed() {
+                            break;
+                        }
+                        tokio::select! {
+                            _ = topic.notify.notified() => {}
+                            _ = tokio::time::sleep(REDELIVERY_CHECK_INTERVAL) => {}
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Arc::new(rx))
+    }
+
+    async fn purge(&self, topic: &str, _sequence: usize) -> Result<()> {
+        let topic = self.topic(topic);
+        let mut state = topic.state.lock();
+        state.pending.clear();
+        state.in_flight.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::Queue, *};
+
+    async fn recv(rx: &mut Arc<mpsc::Receiver<super::super::Message>>) -> super::super::Message {
+        let rx = Arc::get_mut(rx).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_memory_queue_ack() {
+        let queue = MemoryQueue::new(Duration::from_millis(200), 3);
+        queue.create("test").await.unwrap();
+        queue.publish("test", Bytes::from("hello")).await.unwrap();
+        let mut rx = queue.consume("test").await.unwrap();
+        let msg = recv(&mut rx).await;
+        assert_eq!(msg.message(), &Bytes::from("hello"));
+        msg.ack().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(Arc::get_mut(&mut rx).unwrap().try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_queue_redelivery() {
+        let queue = MemoryQueue::new(Duration::from_millis(200), 3);
+        queue.create("test").await.unwrap();
+        queue.publish("test", Bytes::from("hello")).await.unwrap();
+
+        // the consumer crashes while processing the message
+        let mut rx = queue.consume("test").await.unwrap();
+        let msg = recv(&mut rx).await;
+        drop(msg);
+        drop(rx);
+
+        // a new consumer gets the message after the visibility timeout
+        let mut rx = queue.consume("test").await.unwrap();
+        let msg = recv(&mut rx).await;
+        assert_eq!(msg.message(), &Bytes::from("hello"));
+        msg.nack().await.unwrap();
+        let msg = recv(&mut rx).await;
+        assert_eq!(msg.message(), &Bytes::from("hello"));
+        msg.ack().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_queue_dead_letter() {
+        let queue = MemoryQueue::new(Duration::from_millis(100), 1);
+        queue.create("test").await.unwrap();
+        queue.publish("test", Bytes::from("hello")).await.unwrap();
+        let mut rx = queue.consume("test").await.unwrap();
+        // first delivery and one redelivery
+        for _ in 0..2 {
+            let msg = recv(&mut rx).await;
+            msg.nack().await.unwrap();
+        }
+
+        let mut rx = queue
+            .consume(&super::super::dead_letter_topic("test"))
+            .await
+            .unwrap();
+        let msg = recv(&mut rx).await;
+        assert_eq!(msg.message(), &Bytes::from("hello"));
+    }
+}
@@ -22,6 +22,7 @@ use tokio::sync::{mpsc, OnceCell};
 
 use crate::errors::{Error, Result};
 
+pub mod memory;
 pub mod nats;
 pub mod nop;
 
@@ -54,6 +55,13 @@ async fn init_super_cluster() -> Box<dyn Queue> {
     Box::new(nats::NatsQueue::super_cluster())
 }
 
+/// Messages which exceed `ZO_QUEUE_MAX_REDELIVERY` are moved to this topic
+pub fn dead_letter_topic(topic: &str) -> String {
+    format!("{topic}_dead_letter")
+}
+
+/// Messages are delivered at least once, a consumer must `ack` a message after processing it,
+/// otherwise it's delivered again after `ZO_QUEUE_VISIBILITY_TIMEOUT`.
 #[async_trait]
 pub trait Queue: Sync + Send + 'static {
     async fn create(&self, topic: &str) -> Result<()>;
@@ -64,21 +72,37 @@ pub trait Queue: Sync + Send + 'static {
 
 pub enum Message {
     Nats(async_nats::jetstream::Message),
+    Memory(memory::MemoryMessage),
 }
 
 impl Message {
     pub fn message(&self) -> &Bytes {
         match self {
             Message::Nats(msg) => &msg.payload,
+            Message::Memory(msg) => msg.payload(),
         }
     }
 
+    /// Acknowledge the message is processed, it won't be delivered again
     pub async fn ack(&self) -> Result<()> {
         match self {
             Message::Nats(msg) => msg
                 .ack()
                 .await
                 .map_err(|e| Error::Message(format!("ack error:{}", e)))?,
+            Message::Memory(msg) => msg.ack(),
+        }
+        Ok(())
+    }
+
+    /// Reject the message, it will be delivered again without waiting for the visibility timeout
+    pub async fn nack(&self) -> Result<()> {
+        match self {
+            Message::Nats(msg) => msg
+                .ack_with(async_nats::jetstream::AckKind::Nak(None))
+                .await
+                .map_err(|e| Error::Message(format!("nack error:{}", e)))?,
+            Message::Memory(msg) => msg.nack(),
         }
         Ok(())
     }
@@ -57,16 +57,18 @@ impl super::Queue for NatsQueue {
         let cfg = config::get_config();
         let client = get_nats_client().await.clone();
         let jetstream = jetstream::new(client);
-        let topic_name = format!("{}{}", self.prefix, topic);
-        let config = jetstream::stream::Config {
-            name: topic_name.to_string(),
-            subjects: vec![topic_name.to_string(), format!("{}.*", topic_name)],
-            retention: jetstream::stream::RetentionPolicy::Limits,
-            max_age: Duration::from_secs(60 * 60 * 24 * max(1, cfg.nats.queue_max_age)),
-            num_replicas: cfg.nats.replicas,
-            ..Default::default()
-        };
-        _ = jetstream.get_or_create_stream(config).await?;
+        for topic in [topic.to_string(), super::dead_letter_topic(topic)] {
+            let topic_name = format!("{}{}", self.prefix, topic);
+            let config = jetstream::stream::Config {
+                name: topic_name.to_string(),
+                subjects: vec![topic_name.to_string(), format!("{}.*", topic_name)],
+                retention: jetstream::stream::RetentionPolicy::Limits,
+                max_age: Duration::from_secs(60 * 60 * 24 * max(1, cfg.nats.queue_max_age)),
+                num_replicas: cfg.nats.replicas,
+                ..Default::default()
+            };
+            _ = jetstream.get_or_create_stream(config).await?;
+        }
         Ok(())
     }
 
@@ -84,14 +86,21 @@ impl super::Queue for NatsQueue {
     async fn consume(&self, topic: &str) -> Result<Arc<mpsc::Receiver<super::Message>>> {
         let (tx, rx) = mpsc::channel(1024);
         let stream_name = format!("{}{}", self.prefix, topic);
+        let dead_letter_name = format!("{}{}", self.prefix, super::dead_letter_topic(topic));
         let _task: JoinHandle<Result<()>> = tokio::task::spawn(async move {
+            let cfg = config::get_config();
             let client = get_nats_client().await.clone();
             let jetstream = jetstream::new(client);
             let stream = jetstream.get_stream(&stream_name).await?;
             let consumer_name = get_cluster_name();
+            let max_redelivery = cfg.common.queue_max_redelivery as i64;
             let config = jetstream::consumer::pull::Config {
                 name: Some(consumer_name.to_string()),
                 durable_name: Some(consumer_name.to_string()),
+                ack_policy: jetstream::consumer::AckPolicy::Explicit,
+                ack_wait: Duration::from_secs(max(1, cfg.common.queue_visibility_timeout)),
+                // one more delivery to move the message to the dead letter topic
+                max_deliver: max_redelivery + 2,
                 ..Default::default()
             };
             let consumer = stream
@@ -100,6 +109,23 @@ impl super::Queue for NatsQueue {
             // Consume messages from the consumer
             let mut messages = consumer.messages().await.expect("consumer messages error");
             while let Ok(Some(message)) = messages.try_next().await {
+                let delivered = message.info().map(|info| info.delivered).unwrap_or(1);
+                if delivered > max_redelivery + 1 {
+                    log::warn!(
+                        "nats message of {} exceeded max redelivery, move to {}",
+                        stream_name,
+                        dead_letter_name
+                    );
+                    jetstream
+                        .publish(dead_letter_name.clone(), message.payload.clone())
+                        .await?
+                        .await?;
+                    message
+                        .ack()
+                        .await
+                        .map_err(|e| Error::Message(format!("ack error:{}", e)))?;
+                    continue;
+                }
                 let message = super::Message::Nats(message);
                 tx.send(message)
                     .await
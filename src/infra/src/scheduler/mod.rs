@@ -82,6 +82,7 @@ pub enum TriggerModule {
     #[default]
     Alert,
     DerivedStream,
+    ScheduledExport,
 }
 
 impl std::fmt::Display for TriggerModule {
@@ -90,6 +91,7 @@ impl std::fmt::Display for TriggerModule {
             TriggerModule::Alert => write!(f, "alert"),
             TriggerModule::Report => write!(f, "report"),
             TriggerModule::DerivedStream => write!(f, "derived_stream"),
+            TriggerModule::ScheduledExport => write!(f, "scheduled_export"),
         }
     }
 }
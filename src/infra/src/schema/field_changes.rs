@@ -0,0 +1,229 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compact history of the fields of a stream schema: the latest field list and the fields which
+//! were added, removed or changed type by each schema version.
+
+use chrono::Utc;
+use config::{meta::stream::StreamType, utils::json};
+use datafusion::arrow::datatypes::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db as infra_db,
+    errors::{DbError, Error, Result},
+};
+
+/// Only the latest changes are retained
+const MAX_CHANGES: usize = 1000;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldHistory {
+    /// When the history started, the changes before it are unknown
+    pub start_ts: i64,
+    pub version: u64,
+    /// The field names and data types of the latest version
+    pub fields: Vec<(String, String)>,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    /// None when the field was added
+    pub old_type: Option<String>,
+    /// None when the field was removed
+    pub new_type: Option<String>,
+    pub version: u64,
+    pub timestamp: i64,
+}
+
+pub fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("/schema_field_history/{org_id}/{stream_type}/{stream_name}")
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> Result<Option<FieldHistory>> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    let db = infra_db::get_db().await;
+    match db.get(&key).await {
+        Ok(v) => Ok(Some(json::from_slice(&v)?)),
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Records the fields of the latest schema as a new version if any field changed
+pub async fn record(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    schema: &Schema,
+) -> Result<()> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    let fields = schema_fields(schema);
+    let db = infra_db::get_db().await;
+    db.get_for_update(
+        &key,
+        infra_db::NO_NEED_WATCH,
+        None,
+        Box::new(move |value| {
+            let history = match value {
+                Some(value) => {
+                    let history: FieldHistory = json::from_slice(&value)?;
+                    match next_version(history, fields, Utc::now().timestamp_micros()) {
+                        Some(history) => history,
+                        None => return Ok(None),
+                    }
+                }
+                // there is no history, it starts from the current fields
+                None => FieldHistory {
+                    start_ts: Utc::now().timestamp_micros(),
+                    version: 0,
+                    fields,
+                    changes: vec![],
+                },
+            };
+            Ok(Some((Some(json::to_vec(&history).unwrap().into()), None)))
+        }),
+    )
+    .await
+}
+
+pub async fn delete(org_id: &str, stream_name: &str, stream_type: StreamType) -> Result<()> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    let db = infra_db::get_db().await;
+    db.delete_if_exists(&key, false, infra_db::NO_NEED_WATCH)
+        .await
+}
+
+pub fn schema_fields(schema: &Schema) -> Vec<(String, String)> {
+    let mut fields = schema
+        .fields()
+        .iter()
+        .map(|f| (f.name().to_string(), f.data_type().to_string()))
+        .collect::<Vec<_>>();
+    fields.sort();
+    fields
+}
+
+/// Returns the history with a new version for the given fields, or None if no field changed
+fn next_version(
+    mut history: FieldHistory,
+    fields: Vec<(String, String)>,
+    timestamp: i64,
+) -> Option<FieldHistory> {
+    let version = history.version + 1;
+    let old_fields = history
+        .fields
+        .iter()
+        .cloned()
+        .collect::<std::collections::HashMap<_, _>>();
+    let new_fields = fields
+        .iter()
+        .cloned()
+        .collect::<std::collections::HashMap<_, _>>();
+    let mut changes = Vec::new();
+    for (name, new_type) in fields.iter() {
+        match old_fields.get(name) {
+            Some(old_type) if old_type == new_type => {}
+            old_type => changes.push(FieldChange {
+                field: name.to_string(),
+                old_type: old_type.cloned(),
+                new_type: Some(new_type.to_string()),
+                version,
+                timestamp,
+            }),
+        }
+    }
+    for (name, old_type) in history.fields.iter() {
+        if !new_fields.contains_key(name) {
+            changes.push(FieldChange {
+                field: name.to_string(),
+                old_type: Some(old_type.to_string()),
+                new_type: None,
+                version,
+                timestamp,
+            });
+        }
+    }
+    if changes.is_empty() {
+        return None;
+    }
+
+    history.version = version;
+    history.fields = fields;
+    history.changes.extend(changes);
+    if history.changes.len() > MAX_CHANGES {
+        let n = history.changes.len() - MAX_CHANGES;
+        history.changes.drain(..n);
+    }
+    Some(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(v: &[(&str, &str)]) -> Vec<(String, String)> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_next_version() {
+        let history = FieldHistory {
+            start_ts: 1,
+            version: 0,
+            fields: fields(&[("a", "Utf8"), ("b", "Int64"), ("c", "Utf8")]),
+            changes: vec![],
+        };
+        assert!(next_version(history.clone(), history.fields.clone(), 2).is_none());
+
+        let history = next_version(
+            history,
+            fields(&[("a", "Utf8"), ("b", "Float64"), ("d", "Boolean")]),
+            2,
+        )
+        .unwrap();
+        assert_eq!(history.version, 1);
+        assert_eq!(history.changes.len(), 3);
+        assert!(history.changes.contains(&FieldChange {
+            field: "b".to_string(),
+            old_type: Some("Int64".to_string()),
+            new_type: Some("Float64".to_string()),
+            version: 1,
+            timestamp: 2,
+        }));
+        assert!(history.changes.contains(&FieldChange {
+            field: "d".to_string(),
+            old_type: None,
+            new_type: Some("Boolean".to_string()),
+            version: 1,
+            timestamp: 2,
+        }));
+        assert!(history.changes.contains(&FieldChange {
+            field: "c".to_string(),
+            old_type: Some("Utf8".to_string()),
+            new_type: None,
+            version: 1,
+            timestamp: 2,
+        }));
+    }
+}
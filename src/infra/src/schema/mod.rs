@@ -33,6 +33,7 @@ use crate::{
     errors::{DbError, Error, Result},
 };
 
+pub mod field_changes;
 pub mod history;
 
 pub static STREAM_SCHEMAS: Lazy<RwAHashMap<String, Vec<(i64, Schema)>>> =
@@ -240,6 +241,38 @@ pub async fn get_settings(
         .and_then(|schema| unwrap_stream_settings(&schema))
 }
 
+/// Returns the stream an alias belongs to, `None` when the name isn't an alias. The name of a
+/// stream is never an alias, so the aliases are only searched for a name without a stream.
+pub async fn get_stream_alias_target(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> Option<String> {
+    let prefix = format!("{}/{}/", org_id, stream_type);
+    let r = STREAM_SETTINGS.read().await;
+    if r.contains_key(&format!("{prefix}{stream_name}")) {
+        return None;
+    }
+    r.iter().find_map(|(key, settings)| {
+        if settings.aliases.iter().any(|alias| alias == stream_name) {
+            key.strip_prefix(&prefix).map(|v| v.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the stream a name refers to, the stream of an alias or else the name itself
+pub async fn resolve_stream_alias(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> String {
+    get_stream_alias_target(org_id, stream_name, stream_type)
+        .await
+        .unwrap_or_else(|| stream_name.to_string())
+}
+
 pub fn unwrap_stream_settings(schema: &Schema) -> Option<StreamSettings> {
     if schema.metadata().is_empty() {
         return None;
@@ -739,6 +772,32 @@ pub fn is_widening_conversion(from: &DataType, to: &DataType) -> bool {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_resolve_stream_alias() {
+        let settings = StreamSettings {
+            aliases: vec!["old_app".to_string()],
+            ..Default::default()
+        };
+        let mut w = STREAM_SETTINGS.write().await;
+        w.insert("alias_org/logs/app".to_string(), settings);
+        w.insert("alias_org/logs/web".to_string(), StreamSettings::default());
+        drop(w);
+
+        let resolve = |name| resolve_stream_alias("alias_org", name, StreamType::Logs);
+        assert_eq!(resolve("old_app").await, "app");
+        assert_eq!(resolve("app").await, "app");
+        assert_eq!(resolve("web").await, "web");
+        assert_eq!(resolve("unknown").await, "unknown");
+        assert_eq!(
+            get_stream_alias_target("alias_org", "old_app", StreamType::Traces).await,
+            None
+        );
+        assert_eq!(
+            get_stream_alias_target("other_org", "old_app", StreamType::Logs).await,
+            None
+        );
+    }
+
     #[test]
     fn test_is_widening_conversion() {
         assert!(is_widening_conversion(&DataType::Int8, &DataType::Int32));
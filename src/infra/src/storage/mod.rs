@@ -13,14 +13,27 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::{ops::Range, sync::Arc};
+
+use chrono::Utc;
 use config::{get_config, is_local_disk_storage, metrics};
-use datafusion::parquet::data_type::AsBytes;
-use futures::{StreamExt, TryStreamExt};
-use object_store::{path::Path, ObjectStore, WriteMultipart};
+use datafusion::{
+    arrow::record_batch::RecordBatch,
+    datasource::file_format::parquet::fetch_parquet_metadata,
+    parquet::{
+        arrow::{async_reader::AsyncFileReader, ParquetRecordBatchStreamBuilder},
+        data_type::AsBytes,
+        errors::ParquetError,
+        file::metadata::ParquetMetaData,
+    },
+};
+use futures::{future::BoxFuture, FutureExt, StreamExt, TryStreamExt};
+use object_store::{path::Path, ObjectMeta, ObjectStore, WriteMultipart};
 use once_cell::sync::Lazy;
 
 pub mod local;
 pub mod remote;
+pub mod retry;
 
 pub const CONCURRENT_REQUESTS: usize = 1000;
 pub const MULTI_PART_UPLOAD_DATA_SIZE: f64 = 100.0;
@@ -78,6 +91,71 @@ pub async fn get(file: &str) -> Result<bytes::Bytes, anyhow::Error> {
     Ok(data)
 }
 
+/// Reads the footer of the parquet file, with the statistics of its row groups, the data isn't
+/// read. `size` is the size of the file, as recorded in the file_list.
+pub async fn get_parquet_metadata(
+    file: &str,
+    size: usize,
+) -> Result<ParquetMetaData, anyhow::Error> {
+    let meta = ObjectMeta {
+        location: file.into(),
+        last_modified: Utc::now(),
+        size,
+        e_tag: None,
+        version: None,
+    };
+    let metadata = fetch_parquet_metadata(&**DEFAULT, &meta, None).await?;
+    Ok(metadata)
+}
+
+/// Reads a row group of the parquet file, the other row groups aren't downloaded. `metadata` is
+/// the footer of the file, read by `get_parquet_metadata`.
+pub async fn get_parquet_row_group(
+    file: &str,
+    metadata: Arc<ParquetMetaData>,
+    row_group: usize,
+) -> Result<Vec<RecordBatch>, anyhow::Error> {
+    let reader = ParquetRangeReader {
+        location: file.into(),
+        metadata,
+    };
+    let batches = ParquetRecordBatchStreamBuilder::new(reader)
+        .await?
+        .with_row_groups(vec![row_group])
+        .build()?
+        .try_collect()
+        .await?;
+    Ok(batches)
+}
+
+/// Reads the byte ranges of a parquet file whose footer was read before
+struct ParquetRangeReader {
+    location: Path,
+    metadata: Arc<ParquetMetaData>,
+}
+
+impl AsyncFileReader for ParquetRangeReader {
+    fn get_bytes(
+        &mut self,
+        range: Range<usize>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<bytes::Bytes>> {
+        async move {
+            DEFAULT
+                .get_range(&self.location, range)
+                .await
+                .map_err(|e| ParquetError::External(Box::new(e)))
+        }
+        .boxed()
+    }
+
+    fn get_metadata(
+        &mut self,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Arc<ParquetMetaData>>> {
+        let metadata = self.metadata.clone();
+        async move { Ok(metadata) }.boxed()
+    }
+}
+
 pub async fn put(file: &str, data: bytes::Bytes) -> Result<(), anyhow::Error> {
     if bytes_size_in_mb(&data) >= MULTI_PART_UPLOAD_DATA_SIZE {
         put_multipart(file, data).await?;
@@ -18,24 +18,65 @@ use std::{ops::Range, time::Duration};
 use async_trait::async_trait;
 use bytes::Bytes;
 use config::{get_config, metrics};
-use futures::stream::BoxStream;
+use futures::{stream::BoxStream, StreamExt};
 use object_store::{
-    limit::LimitStore, path::Path, Error, GetOptions, GetResult, ListResult, MultipartUpload,
-    ObjectMeta, ObjectStore, PutMultipartOpts, PutOptions, PutPayload, PutResult, Result,
+    coalesce_ranges, limit::LimitStore, path::Path, Error, GetOptions, GetResult, GetResultPayload,
+    ListResult, MultipartUpload, ObjectMeta, ObjectStore, PutMultipartOpts, PutOptions, PutPayload,
+    PutResult, Result, OBJECT_STORE_COALESCE_DEFAULT,
 };
 
-use crate::storage::{format_key, CONCURRENT_REQUESTS};
+use crate::storage::{format_key, retry::RetryPolicy, CONCURRENT_REQUESTS};
 
 pub struct Remote {
     client: LimitStore<Box<dyn object_store::ObjectStore>>,
+    retry: RetryPolicy,
+    /// The storage supports range requests, otherwise the whole object is read
+    range_read: bool,
 }
 
 impl Default for Remote {
     fn default() -> Self {
+        Self::new(init_client(), RetryPolicy::from_config())
+            .with_range_read(get_config().s3.feature_range_read)
+    }
+}
+
+impl Remote {
+    pub fn new(client: Box<dyn object_store::ObjectStore>, retry: RetryPolicy) -> Self {
         Self {
-            client: LimitStore::new(init_client(), CONCURRENT_REQUESTS),
+            client: LimitStore::new(client, CONCURRENT_REQUESTS),
+            retry,
+            range_read: true,
         }
     }
+
+    pub fn with_range_read(mut self, range_read: bool) -> Self {
+        self.range_read = range_read;
+        self
+    }
+
+    /// Reads the object with its body, so a failure reading the body is retried as well
+    async fn get_buffered(&self, key: &Path, options: GetOptions) -> Result<GetResult> {
+        self.retry
+            .run("get", || async {
+                let result = self.client.get_opts(key, options.clone()).await?;
+                let (meta, range, attributes) = (
+                    result.meta.clone(),
+                    result.range.clone(),
+                    result.attributes.clone(),
+                );
+                let data = result.bytes().await?;
+                Ok(GetResult {
+                    payload: GetResultPayload::Stream(
+                        futures::stream::once(async move { Ok(data) }).boxed(),
+                    ),
+                    meta,
+                    range,
+                    attributes,
+                })
+            })
+            .await
+    }
 }
 
 impl std::fmt::Debug for Remote {
@@ -61,9 +102,12 @@ impl ObjectStore for Remote {
         let start = std::time::Instant::now();
         let file = location.to_string();
         let data_size = payload.content_length();
+        let key: Path = format_key(&file, true).into();
         match self
-            .client
-            .put_opts(&(format_key(&file, true).into()), payload, opts)
+            .retry
+            .run("put", || {
+                self.client.put_opts(&key, payload.clone(), opts.clone())
+            })
             .await
         {
             Ok(_) => {
@@ -115,7 +159,8 @@ impl ObjectStore for Remote {
     async fn get(&self, location: &Path) -> Result<GetResult> {
         let start = std::time::Instant::now();
         let file = location.to_string();
-        let result = self.client.get(&(format_key(&file, true).into())).await?;
+        let key: Path = format_key(&file, true).into();
+        let result = self.get_buffered(&key, GetOptions::default()).await?;
 
         // metrics
         let data_len = result.meta.size;
@@ -140,10 +185,8 @@ impl ObjectStore for Remote {
     async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
         let start = std::time::Instant::now();
         let file = location.to_string();
-        let result = self
-            .client
-            .get_opts(&(format_key(&file, true).into()), options)
-            .await?;
+        let key: Path = format_key(&file, true).into();
+        let result = self.get_buffered(&key, options).await?;
 
         // metrics
         let data_len = result.meta.size;
@@ -165,11 +208,15 @@ impl ObjectStore for Remote {
     }
 
     async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        if !self.range_read {
+            return Ok(self.get_ranges(location, &[range]).await?.remove(0));
+        }
         let start = std::time::Instant::now();
         let file = location.to_string();
+        let key: Path = format_key(&file, true).into();
         let data = self
-            .client
-            .get_range(&(format_key(&file, true).into()), range)
+            .retry
+            .run("get", || self.client.get_range(&key, range.clone()))
             .await?;
 
         // metrics
@@ -191,6 +238,19 @@ impl ObjectStore for Remote {
         Ok(data)
     }
 
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+        if self.range_read {
+            return coalesce_ranges(
+                ranges,
+                |range| self.get_range(location, range),
+                OBJECT_STORE_COALESCE_DEFAULT,
+            )
+            .await;
+        }
+        let data = self.get(location).await?.bytes().await?;
+        slice_ranges(location, &data, ranges)
+    }
+
     async fn head(&self, _location: &Path) -> Result<ObjectMeta> {
         Err(Error::NotImplemented)
     }
@@ -234,6 +294,26 @@ impl ObjectStore for Remote {
     }
 }
 
+/// Slices the ranges out of the whole object
+fn slice_ranges(location: &Path, data: &Bytes, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+    ranges
+        .iter()
+        .map(|range| {
+            if range.start > range.end || range.end > data.len() {
+                return Err(Error::Generic {
+                    store: "remote",
+                    source: format!(
+                        "range {range:?} is out of the object {location} of {} bytes",
+                        data.len()
+                    )
+                    .into(),
+                });
+            }
+            Ok(data.slice(range.clone()))
+        })
+        .collect()
+}
+
 fn init_aws_config() -> object_store::Result<object_store::aws::AmazonS3> {
     let cfg = get_config();
     let mut opts = object_store::ClientOptions::default()
@@ -346,3 +426,162 @@ fn init_client() -> Box<dyn object_store::ObjectStore> {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    /// A store whose first reads fail with a server error
+    #[derive(Debug)]
+    struct FlakyStore {
+        inner: InMemory,
+        failures: Arc<AtomicUsize>,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl std::fmt::Display for FlakyStore {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("flaky storage")
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for FlakyStore {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> Result<PutResult> {
+            self.inner.put_opts(location, payload, opts).await
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            location: &Path,
+            opts: PutMultipartOpts,
+        ) -> Result<Box<dyn MultipartUpload>> {
+            self.inner.put_multipart_opts(location, opts).await
+        }
+
+        async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            if self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| v.checked_sub(1))
+                .is_ok()
+            {
+                return Err(Error::Generic {
+                    store: "flaky",
+                    source: "Server error with status 503 Service Unavailable: SlowDown".into(),
+                });
+            }
+            self.inner.get_opts(location, options).await
+        }
+
+        async fn delete(&self, location: &Path) -> Result<()> {
+            self.inner.delete(location).await
+        }
+
+        fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+            self.inner.copy(from, to).await
+        }
+
+        async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+            self.inner.copy_if_not_exists(from, to).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_get_retry() {
+        let failures = Arc::new(AtomicUsize::new(2));
+        let reads = Arc::new(AtomicUsize::new(0));
+        let store = FlakyStore {
+            inner: InMemory::new(),
+            failures: failures.clone(),
+            reads: reads.clone(),
+        };
+        let remote = Remote::new(
+            Box::new(store),
+            RetryPolicy {
+                attempts: 3,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(10),
+                timeout: Duration::from_secs(10),
+            },
+        );
+        let file: Path = "test/retry.txt".into();
+        remote
+            .put(&file, PutPayload::from_static(b"hello"))
+            .await
+            .unwrap();
+
+        // the read fails twice then succeeds
+        let data = remote.get(&file).await.unwrap().bytes().await.unwrap();
+        assert_eq!(data.as_ref(), b"hello");
+        assert_eq!(failures.load(Ordering::SeqCst), 0);
+        assert_eq!(reads.load(Ordering::SeqCst), 3);
+
+        // a missing object isn't retried
+        let err = remote.get(&"test/missing.txt".into()).await.unwrap_err();
+        assert!(matches!(err, Error::NotFound { .. }));
+        assert_eq!(reads.load(Ordering::SeqCst), 4);
+
+        // the read fails when the errors outlast the retries
+        failures.store(4, Ordering::SeqCst);
+        assert!(remote.get(&file).await.is_err());
+        assert_eq!(reads.load(Ordering::SeqCst), 8);
+    }
+
+    #[tokio::test]
+    async fn test_remote_range_read_fallback() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let store = FlakyStore {
+            inner: InMemory::new(),
+            failures: Arc::new(AtomicUsize::new(0)),
+            reads: reads.clone(),
+        };
+        let file: Path = "test/range.bin".into();
+        let data = (0..2 * 1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        store
+            .put(&file, PutPayload::from(data.clone()))
+            .await
+            .unwrap();
+        let ranges = [0..2, 1_500_000..1_500_003];
+        let expected = ranges
+            .iter()
+            .map(|r| Bytes::copy_from_slice(&data[r.clone()]))
+            .collect::<Vec<_>>();
+
+        // the ranges too far apart to be coalesced are two requests
+        let remote = Remote::new(Box::new(store), RetryPolicy::from_config());
+        assert_eq!(remote.get_ranges(&file, &ranges).await.unwrap(), expected);
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+        // the whole object is read once without range requests
+        let remote = remote.with_range_read(false);
+        assert_eq!(remote.get_ranges(&file, &ranges).await.unwrap(), expected);
+        assert_eq!(reads.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            remote.get_range(&file, 10..20).await.unwrap(),
+            Bytes::copy_from_slice(&data[10..20])
+        );
+        assert_eq!(reads.load(Ordering::SeqCst), 4);
+        assert!(remote.get_range(&file, 0..data.len() + 1).await.is_err());
+    }
+}
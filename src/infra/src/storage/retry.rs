@@ -0,0 +1,249 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Retry of the object storage operations failed with a transient error, with an exponential
+//! backoff and a random jitter so the nodes throttled together don't retry together.
+
+use std::{future::Future, time::Duration};
+
+use config::{get_config, metrics, utils::rand::get_rand_num_within};
+use object_store::{Error, Result};
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// retries after the first attempt, 0 disables the retry
+    pub attempts: usize,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// the time an operation takes with its retries, it fails after it
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config() -> Self {
+        let cfg = get_config();
+        Self {
+            attempts: cfg.s3.retry_attempts,
+            base_delay: Duration::from_millis(cfg.s3.retry_base_delay),
+            max_delay: Duration::from_millis(cfg.s3.retry_max_delay),
+            timeout: Duration::from_secs(cfg.s3.retry_timeout),
+        }
+    }
+
+    /// The delay before the retry, a random duration between half and all of the exponential
+    /// backoff
+    fn delay(&self, retry: usize) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(1 << retry.min(16))
+            .min(self.max_delay)
+            .as_millis() as u64;
+        if backoff == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(get_rand_num_within(backoff / 2, backoff + 1))
+    }
+
+    /// Runs the operation, retrying it while it fails with a transient error. The client of the
+    /// object storage retries a request as well, the timeout caps the time of the operation
+    /// with all the retries.
+    pub async fn run<T, F, Fut>(&self, method_type: &str, operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if self.attempts == 0 {
+            let mut operation = operation;
+            return operation().await;
+        }
+        match tokio::time::timeout(self.timeout, self.run_with_retries(method_type, operation))
+            .await
+        {
+            Ok(ret) => ret,
+            Err(_) => {
+                metrics::STORAGE_RETRIES
+                    .with_label_values(&[method_type, "timeout"])
+                    .inc();
+                Err(Error::Generic {
+                    store: "retry",
+                    source: format!(
+                        "{method_type} timed out after {} seconds",
+                        self.timeout.as_secs()
+                    )
+                    .into(),
+                })
+            }
+        }
+    }
+
+    async fn run_with_retries<T, F, Fut>(&self, method_type: &str, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = std::time::Instant::now();
+        let mut retry = 0;
+        loop {
+            let err = match operation().await {
+                Ok(v) => {
+                    if retry > 0 {
+                        metrics::STORAGE_RETRIES
+                            .with_label_values(&[method_type, "ok"])
+                            .inc();
+                    }
+                    return Ok(v);
+                }
+                Err(e) => e,
+            };
+            let delay = self.delay(retry);
+            if retry >= self.attempts
+                || !is_retryable(&err)
+                || start.elapsed() + delay > self.timeout
+            {
+                if retry > 0 {
+                    metrics::STORAGE_RETRIES
+                        .with_label_values(&[method_type, "error"])
+                        .inc();
+                }
+                return Err(err);
+            }
+            retry += 1;
+            log::warn!(
+                "[STORAGE] {method_type} failed, retry {retry}/{} in {} ms: {err}",
+                self.attempts,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// The messages of the transient errors of the client: the server errors, and the network
+/// errors, sending the request or reading the body of the response
+const TRANSIENT_ERRORS: [&str; 8] = [
+    "server error with status",
+    "error sending request",
+    "error decoding response body",
+    "request or response body error",
+    "connection",
+    "broken pipe",
+    "timed out",
+    "timeout",
+];
+
+/// Returns if the error is transient: a server error, a throttling or a network error. A
+/// missing object, a denied access, a bad request or an unknown error fails the same when
+/// retried.
+pub fn is_retryable(err: &Error) -> bool {
+    match err {
+        Error::Generic { source, .. } => {
+            // the cause of the error is in its sources, like the io error of a reqwest error
+            let mut message = source.to_string();
+            let mut cause = source.source();
+            while let Some(e) = cause {
+                message.push_str(": ");
+                message.push_str(&e.to_string());
+                cause = e.source();
+            }
+            match client_error_status(&message) {
+                Some(status) => status == 408 || status == 429,
+                None => {
+                    let message = message.to_lowercase();
+                    TRANSIENT_ERRORS.iter().any(|v| message.contains(v))
+                }
+            }
+        }
+        Error::JoinError { .. } => true,
+        _ => false,
+    }
+}
+
+/// The status of a 4xx response, the client of the object_store formats it as
+/// `Client error with status 403 Forbidden: ...`
+fn client_error_status(message: &str) -> Option<u16> {
+    let (_, status) = message.split_once("Client error with status ")?;
+    status.get(..3)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generic_error(message: &str) -> Error {
+        Error::Generic {
+            store: "S3",
+            source: message.to_string().into(),
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&generic_error(
+            "Error after 10 retries, source: Server error with status 503 Service Unavailable"
+        )));
+        assert!(is_retryable(&generic_error("connection reset by peer")));
+        assert!(is_retryable(&generic_error(
+            "error decoding response body: request or response body error"
+        )));
+        assert!(!is_retryable(&generic_error(
+            "Unable to access credentials from environment"
+        )));
+        assert!(is_retryable(&generic_error(
+            "Client error with status 429 Too Many Requests: SlowDown"
+        )));
+        assert!(!is_retryable(&generic_error(
+            "Client error with status 403 Forbidden: AccessDenied"
+        )));
+        assert!(!is_retryable(&Error::NotFound {
+            path: "files/a.parquet".to_string(),
+            source: "not found".into(),
+        }));
+        assert!(!is_retryable(&Error::NotImplemented));
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout() {
+        let policy = RetryPolicy {
+            attempts: 100,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+            timeout: Duration::from_millis(100),
+        };
+        let start = std::time::Instant::now();
+        let ret: Result<()> = policy
+            .run("get", || async {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                Err(generic_error("connection reset by peer"))
+            })
+            .await;
+        assert!(ret.unwrap_err().to_string().contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            timeout: Duration::from_secs(30),
+        };
+        for retry in 0..10 {
+            let backoff = (100 << retry).min(1000);
+            let delay = policy.delay(retry).as_millis() as u64;
+            assert!(delay >= backoff / 2 && delay <= backoff, "{retry}: {delay}");
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use sea_orm::{
+    entity::prelude::*, ColumnTrait, ConnectionTrait, DatabaseBackend, EntityTrait,
+    FromQueryResult, QueryFilter, QueryOrder, QuerySelect, Schema, Set, SqlErr,
+};
+
+use super::get_lock;
+use crate::{
+    db::{connect_to_orm, mysql, postgres, sqlite, IndexStatement, ORM_CLIENT},
+    errors::{self, DbError, Error},
+};
+
+// define the api_keys table
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "api_keys")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    #[sea_orm(column_type = "String(StringLen::N(100))")]
+    pub org: String,
+    #[sea_orm(column_type = "String(StringLen::N(100))")]
+    pub name: String,
+    /// sha256 of the key, the key itself isn't stored
+    #[sea_orm(column_type = "String(StringLen::N(64))")]
+    pub key_hash: String,
+    /// comma separated scopes
+    #[sea_orm(column_type = "String(StringLen::N(100))")]
+    pub scopes: String,
+    #[sea_orm(column_type = "String(StringLen::N(256))")]
+    pub created_by: String,
+    pub created_ts: i64,
+    /// 0 if the key was never used
+    pub last_used_ts: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No relations defined")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(FromQueryResult, Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyRecord {
+    pub org: String,
+    pub name: String,
+    pub scopes: String,
+    pub created_by: String,
+    pub created_ts: i64,
+    pub last_used_ts: i64,
+}
+
+pub async fn init() -> Result<(), errors::Error> {
+    create_table().await?;
+    create_table_index().await?;
+    Ok(())
+}
+
+pub async fn create_table() -> Result<(), errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let builder = client.get_database_backend();
+
+    let schema = Schema::new(builder);
+    let create_table_stmt = schema
+        .create_table_from_entity(Entity)
+        .if_not_exists()
+        .take();
+
+    client
+        .execute(builder.build(&create_table_stmt))
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))?;
+
+    Ok(())
+}
+
+pub async fn create_table_index() -> Result<(), errors::Error> {
+    let index1 = IndexStatement::new("api_keys_org_name_idx", "api_keys", true, &["org", "name"]);
+    let index2 = IndexStatement::new("api_keys_key_hash_idx", "api_keys", true, &["key_hash"]);
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    match client.get_database_backend() {
+        DatabaseBackend::MySql => {
+            mysql::create_index(index1).await?;
+            mysql::create_index(index2).await?;
+        }
+        DatabaseBackend::Postgres => {
+            postgres::create_index(index1).await?;
+            postgres::create_index(index2).await?;
+        }
+        _ => {
+            sqlite::create_index(index1).await?;
+            sqlite::create_index(index2).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds the key, returns false if the org already has a key with the name
+pub async fn add(
+    org: &str,
+    name: &str,
+    key_hash: &str,
+    scopes: &str,
+    created_by: &str,
+) -> Result<bool, errors::Error> {
+    let record = ActiveModel {
+        org: Set(org.to_string()),
+        name: Set(name.to_string()),
+        key_hash: Set(key_hash.to_string()),
+        scopes: Set(scopes.to_string()),
+        created_by: Set(created_by.to_string()),
+        created_ts: Set(chrono::Utc::now().timestamp_micros()),
+        last_used_ts: Set(0),
+        ..Default::default()
+    };
+
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    match Entity::insert(record).exec(client).await {
+        Ok(_) => Ok(true),
+        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => Ok(false),
+        Err(e) => Err(Error::DbError(DbError::SeaORMError(e.to_string()))),
+    }
+}
+
+/// Looks up a key by its hash
+pub async fn get_by_hash(key_hash: &str) -> Result<Option<ApiKeyRecord>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    Entity::find()
+        .select_only()
+        .column(Column::Org)
+        .column(Column::Name)
+        .column(Column::Scopes)
+        .column(Column::CreatedBy)
+        .column(Column::CreatedTs)
+        .column(Column::LastUsedTs)
+        .filter(Column::KeyHash.eq(key_hash))
+        .into_model::<ApiKeyRecord>()
+        .one(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))
+}
+
+pub async fn list(org: &str) -> Result<Vec<ApiKeyRecord>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    Entity::find()
+        .select_only()
+        .column(Column::Org)
+        .column(Column::Name)
+        .column(Column::Scopes)
+        .column(Column::CreatedBy)
+        .column(Column::CreatedTs)
+        .column(Column::LastUsedTs)
+        .filter(Column::Org.eq(org))
+        .order_by_asc(Column::Name)
+        .into_model::<ApiKeyRecord>()
+        .all(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))
+}
+
+/// Records the time the key was last used
+pub async fn set_last_used(key_hash: &str, last_used_ts: i64) -> Result<(), errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    Entity::update_many()
+        .col_expr(Column::LastUsedTs, Expr::value(last_used_ts))
+        .filter(Column::KeyHash.eq(key_hash))
+        .exec(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))?;
+
+    Ok(())
+}
+
+/// Removes the key, returns false if the org has no key with the name
+pub async fn remove(org: &str, name: &str) -> Result<bool, errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let res = Entity::delete_many()
+        .filter(Column::Org.eq(org))
+        .filter(Column::Name.eq(name))
+        .exec(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))?;
+
+    Ok(res.rows_affected > 0)
+}
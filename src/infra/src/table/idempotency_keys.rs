@@ -0,0 +1,209 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use sea_orm::{
+    entity::prelude::*, ColumnTrait, ConnectionTrait, DatabaseBackend, EntityTrait,
+    FromQueryResult, QueryFilter, QuerySelect, Schema, Set, SqlErr,
+};
+
+use super::get_lock;
+use crate::{
+    db::{connect_to_orm, mysql, postgres, sqlite, IndexStatement, ORM_CLIENT},
+    errors::{self, DbError, Error},
+};
+
+// define the idempotency_keys table
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "idempotency_keys")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    #[sea_orm(column_type = "String(StringLen::N(100))")]
+    pub org: String,
+    #[sea_orm(column_type = "String(StringLen::N(256))")]
+    pub idempotency_key: String,
+    /// http status of the processed request, 0 while the request is in progress
+    pub status: i32,
+    #[sea_orm(column_type = "Text")]
+    pub response: String,
+    pub created_ts: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No relations defined")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(FromQueryResult, Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyRecord {
+    pub status: i32,
+    pub response: String,
+    pub created_ts: i64,
+}
+
+pub async fn init() -> Result<(), errors::Error> {
+    create_table().await?;
+    create_table_index().await?;
+    Ok(())
+}
+
+pub async fn create_table() -> Result<(), errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let builder = client.get_database_backend();
+
+    let schema = Schema::new(builder);
+    let create_table_stmt = schema
+        .create_table_from_entity(Entity)
+        .if_not_exists()
+        .take();
+
+    client
+        .execute(builder.build(&create_table_stmt))
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))?;
+
+    Ok(())
+}
+
+pub async fn create_table_index() -> Result<(), errors::Error> {
+    let index1 = IndexStatement::new(
+        "idempotency_keys_org_key_idx",
+        "idempotency_keys",
+        true,
+        &["org", "idempotency_key"],
+    );
+    let index2 = IndexStatement::new(
+        "idempotency_keys_created_ts_idx",
+        "idempotency_keys",
+        false,
+        &["created_ts"],
+    );
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    match client.get_database_backend() {
+        DatabaseBackend::MySql => {
+            mysql::create_index(index1).await?;
+            mysql::create_index(index2).await?;
+        }
+        DatabaseBackend::Postgres => {
+            postgres::create_index(index1).await?;
+            postgres::create_index(index2).await?;
+        }
+        _ => {
+            sqlite::create_index(index1).await?;
+            sqlite::create_index(index2).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Records the key as in progress, returns false if the key is already recorded
+pub async fn add(org: &str, idempotency_key: &str) -> Result<bool, errors::Error> {
+    let record = ActiveModel {
+        org: Set(org.to_string()),
+        idempotency_key: Set(idempotency_key.to_string()),
+        status: Set(0),
+        response: Set("".to_string()),
+        created_ts: Set(chrono::Utc::now().timestamp_micros()),
+        ..Default::default()
+    };
+
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    match Entity::insert(record).exec(client).await {
+        Ok(_) => Ok(true),
+        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => Ok(false),
+        Err(e) => Err(Error::DbError(DbError::SeaORMError(e.to_string()))),
+    }
+}
+
+/// Saves the response of the processed request of the key
+pub async fn finish(
+    org: &str,
+    idempotency_key: &str,
+    status: i32,
+    response: &str,
+) -> Result<(), errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    Entity::update_many()
+        .col_expr(Column::Status, Expr::value(status))
+        .col_expr(Column::Response, Expr::value(response))
+        .filter(Column::Org.eq(org))
+        .filter(Column::IdempotencyKey.eq(idempotency_key))
+        .exec(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))?;
+
+    Ok(())
+}
+
+pub async fn get(
+    org: &str,
+    idempotency_key: &str,
+) -> Result<Option<IdempotencyRecord>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    Entity::find()
+        .select_only()
+        .column(Column::Status)
+        .column(Column::Response)
+        .column(Column::CreatedTs)
+        .filter(Column::Org.eq(org))
+        .filter(Column::IdempotencyKey.eq(idempotency_key))
+        .into_model::<IdempotencyRecord>()
+        .one(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))
+}
+
+pub async fn remove(org: &str, idempotency_key: &str) -> Result<(), errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    Entity::delete_many()
+        .filter(Column::Org.eq(org))
+        .filter(Column::IdempotencyKey.eq(idempotency_key))
+        .exec(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))?;
+
+    Ok(())
+}
+
+/// Removes the keys recorded before the time, returns the number of removed keys
+pub async fn remove_expired(expired_before: i64) -> Result<u64, errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let res = Entity::delete_many()
+        .filter(Column::CreatedTs.lt(expired_before))
+        .exec(client)
+        .await
+        .map_err(|e| Error::DbError(DbError::SeaORMError(e.to_string())))?;
+
+    Ok(res.rows_affected)
+}
@@ -17,10 +17,14 @@ use config::get_config;
 
 use crate::db::{sqlite::CLIENT_RW, SQLITE_STORE};
 
+pub mod api_keys;
+pub mod idempotency_keys;
 pub mod short_urls;
 
 pub async fn init() -> Result<(), anyhow::Error> {
     short_urls::init().await?;
+    api_keys::init().await?;
+    idempotency_keys::init().await?;
     Ok(())
 }
 
@@ -107,4 +107,9 @@ pub enum Error {
         source: tokio::sync::mpsc::error::SendError<PathBuf>,
     },
     MemoryTableOverflowError {},
+    #[snafu(display("Parquet file {} failed verification: {}", path.display(), message))]
+    ParquetVerifyError {
+        path: PathBuf,
+        message: String,
+    },
 }
@@ -13,11 +13,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use config::metrics;
 use hashbrown::HashSet;
 use once_cell::sync::Lazy;
+use parquet::arrow::arrow_reader::ArrowReaderMetadata;
 use snafu::ResultExt;
 use tokio::{
     fs,
@@ -26,11 +30,12 @@ use tokio::{
 
 use crate::{
     entry::PersistStat,
-    errors::{DeleteFileSnafu, RenameFileSnafu, Result, TokioMpscSendSnafu, WriteDataSnafu},
+    errors::{DeleteFileSnafu, Error, RenameFileSnafu, Result, TokioMpscSendSnafu, WriteDataSnafu},
     memtable::MemTable,
+    partition::wal_parquet_file_key,
     rwmap::RwIndexMap,
     writer::WriterKey,
-    ReadRecordBatchEntry,
+    ReadRecordBatchEntry, WAL_PARQUET_METADATA,
 };
 
 pub(crate) static IMMUTABLES: Lazy<RwIndexMap<PathBuf, Arc<Immutable>>> =
@@ -72,41 +77,119 @@ impl Immutable {
     }
 
     pub(crate) async fn persist(&self, wal_path: &PathBuf) -> Result<PersistStat> {
-        let mut persist_stat = PersistStat::default();
         // 1. dump memtable to disk
         let (schema_size, paths) = self
             .memtable
             .persist(self.idx, &self.key.org_id, &self.key.stream_type)
             .await?;
+        let mut persist_stat = commit_persisted_files(&self.key, wal_path, paths).await?;
         persist_stat.arrow_size += schema_size;
-        // 2. create a lock file
-        let done_path = wal_path.with_extension("lock");
-        let lock_data = paths
-            .iter()
-            .map(|(p, ..)| p.to_string_lossy())
-            .collect::<Vec<_>>()
-            .join("\n");
-        fs::write(&done_path, lock_data.as_bytes())
-            .await
-            .context(WriteDataSnafu)?;
-        // 3. delete wal file
-        fs::remove_file(wal_path)
-            .await
-            .context(DeleteFileSnafu { path: wal_path })?;
-        // 4. rename the tmp files to parquet files
-        for (path, stat) in paths {
-            persist_stat += stat;
-            let parquet_path = path.with_extension("parquet");
-            fs::rename(&path, &parquet_path)
+        Ok(persist_stat)
+    }
+}
+
+// replace the wal file with the dumped files, the wal file is kept to retry when any of the files
+// is broken
+async fn commit_persisted_files(
+    key: &WriterKey,
+    wal_path: &PathBuf,
+    paths: Vec<(PathBuf, PersistStat)>,
+) -> Result<PersistStat> {
+    let mut persist_stat = PersistStat::default();
+    // 2. verify the dumped files
+    for (path, _) in paths.iter() {
+        let Err(e) = verify_parquet_file(path).await else {
+            continue;
+        };
+        log::error!(
+            "[INGESTER:MEM] {e}, keep wal file {} to retry",
+            wal_path.to_string_lossy()
+        );
+        metrics::INGEST_WAL_PARQUET_VERIFY_FAILED
+            .with_label_values(&[key.org_id.as_ref(), key.stream_type.as_ref()])
+            .inc();
+        for (path, _) in paths.iter() {
+            if let Err(e) = fs::remove_file(path).await {
+                log::error!(
+                    "[INGESTER:MEM] delete file {} error: {e}",
+                    path.to_string_lossy()
+                );
+            }
+            WAL_PARQUET_METADATA
+                .write()
                 .await
-                .context(RenameFileSnafu { path: &path })?;
+                .remove(&wal_parquet_file_key(path));
         }
-        // 5. delete the lock file
-        fs::remove_file(&done_path)
+        return Err(e);
+    }
+    // 3. create a lock file
+    let done_path = wal_path.with_extension("lock");
+    let lock_data = paths
+        .iter()
+        .map(|(p, ..)| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n");
+    fs::write(&done_path, lock_data.as_bytes())
+        .await
+        .context(WriteDataSnafu)?;
+    // 4. delete wal file
+    fs::remove_file(wal_path)
+        .await
+        .context(DeleteFileSnafu { path: wal_path })?;
+    // 5. rename the tmp files to parquet files
+    for (path, stat) in paths {
+        persist_stat += stat;
+        let parquet_path = path.with_extension("parquet");
+        fs::rename(&path, &parquet_path)
             .await
-            .context(DeleteFileSnafu { path: &done_path })?;
-        Ok(persist_stat)
+            .context(RenameFileSnafu { path: &path })?;
+    }
+    // 6. delete the lock file
+    fs::remove_file(&done_path)
+        .await
+        .context(DeleteFileSnafu { path: &done_path })?;
+    Ok(persist_stat)
+}
+
+// check the file is complete and has the records of its metadata
+async fn verify_parquet_file(path: &Path) -> Result<()> {
+    let verify_error = |message: String| Error::ParquetVerifyError {
+        path: path.to_path_buf(),
+        message,
+    };
+    let Some(expected) = WAL_PARQUET_METADATA
+        .read()
+        .await
+        .get(&wal_parquet_file_key(path))
+        .cloned()
+    else {
+        return Err(verify_error("file metadata not found".to_string()));
+    };
+    let mut file = fs::File::open(path)
+        .await
+        .map_err(|e| verify_error(e.to_string()))?;
+    let file_size = file
+        .metadata()
+        .await
+        .map_err(|e| verify_error(e.to_string()))?
+        .len() as i64;
+    if file_size != expected.compressed_size {
+        return Err(verify_error(format!(
+            "file size {file_size} doesn't match expected {}",
+            expected.compressed_size
+        )));
     }
+    let metadata = ArrowReaderMetadata::load_async(&mut file, Default::default())
+        .await
+        .map_err(|e| verify_error(format!("read footer error: {e}")))?;
+    let records = metadata.metadata().file_metadata().num_rows();
+    if records != expected.records {
+        return Err(verify_error(format!(
+            "records {records} doesn't match expected {}",
+            expected.records
+        )));
+    }
+    Ok(())
 }
 
 pub(crate) async fn persist(tx: mpsc::Sender<PathBuf>) -> Result<()> {
@@ -184,3 +267,97 @@ pub(crate) async fn persist_table(idx: usize, path: PathBuf) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow::{array::Int64Array, record_batch::RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use config::{meta::stream::FileMeta, utils::parquet::new_parquet_writer};
+
+    use super::*;
+
+    async fn write_parquet(path: &Path, truncate: bool) -> FileMeta {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "_timestamp",
+            DataType::Int64,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        let mut file_meta = FileMeta {
+            min_ts: 1,
+            max_ts: 3,
+            records: 3,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        let mut writer = new_parquet_writer(&mut buf, &schema, &[], &file_meta);
+        writer.write(&batch).await.unwrap();
+        writer.close().await.unwrap();
+        file_meta.compressed_size = buf.len() as i64;
+        if truncate {
+            buf.truncate(buf.len() / 2);
+        }
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(path, &buf).await.unwrap();
+        WAL_PARQUET_METADATA
+            .write()
+            .await
+            .insert(wal_parquet_file_key(path), file_meta.clone());
+        file_meta
+    }
+
+    #[tokio::test]
+    async fn test_commit_persisted_files_keeps_wal_on_broken_parquet() {
+        let base_path = PathBuf::from(&config::get_config().common.data_wal_dir);
+        let key = WriterKey::new("test_verify", "logs");
+        let wal_path = base_path.join("logs/0/test_verify/logs/1.wal");
+        let par_path = base_path.join("files/test_verify/logs/default/0/2024/01/01/00/1.par");
+        fs::create_dir_all(wal_path.parent().unwrap())
+            .await
+            .unwrap();
+        fs::write(&wal_path, b"wal").await.unwrap();
+
+        // a truncated file keeps the wal file
+        write_parquet(&par_path, true).await;
+        let ret = commit_persisted_files(
+            &key,
+            &wal_path,
+            vec![(par_path.clone(), PersistStat::default())],
+        )
+        .await;
+        assert!(matches!(ret, Err(Error::ParquetVerifyError { .. })));
+        assert!(wal_path.exists());
+        assert!(!par_path.exists());
+        assert!(!wal_path.with_extension("lock").exists());
+        assert!(
+            !WAL_PARQUET_METADATA
+                .read()
+                .await
+                .contains_key(&wal_parquet_file_key(&par_path))
+        );
+
+        // a complete file replaces the wal file
+        write_parquet(&par_path, false).await;
+        let ret = commit_persisted_files(
+            &key,
+            &wal_path,
+            vec![(par_path.clone(), PersistStat::default())],
+        )
+        .await;
+        assert!(ret.is_ok());
+        assert!(!wal_path.exists());
+        assert!(par_path.with_extension("parquet").exists());
+
+        fs::remove_file(par_path.with_extension("parquet"))
+            .await
+            .unwrap();
+        WAL_PARQUET_METADATA
+            .write()
+            .await
+            .remove(&wal_parquet_file_key(&par_path));
+    }
+}
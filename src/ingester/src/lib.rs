@@ -34,7 +34,10 @@ use tokio::{
     sync::{mpsc, Mutex},
     time,
 };
-pub use writer::{check_memtable_size, flush_all, get_writer, read_from_memtable, Writer};
+pub use writer::{
+    check_memtable_size, flush_all, get_max_timestamp, get_max_timestamps, get_writer,
+    read_from_memtable, remove_max_timestamp, Writer,
+};
 
 pub(crate) type ReadRecordBatchEntry = (Arc<Schema>, Vec<Arc<entry::RecordBatchEntry>>);
 
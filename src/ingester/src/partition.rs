@@ -13,7 +13,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{collections::BTreeMap, fs::create_dir_all, path::PathBuf, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use arrow_schema::Schema;
 use config::{
@@ -130,8 +135,7 @@ impl Partition {
         stream_name: &str,
     ) -> Result<(usize, Vec<(PathBuf, PersistStat)>)> {
         let cfg = config::get_config();
-        let base_path = PathBuf::from(&cfg.common.data_wal_dir);
-        let mut path = base_path.clone();
+        let mut path = PathBuf::from(&cfg.common.data_wal_dir);
         path.push("files");
         path.push(org_id);
         path.push(stream_type);
@@ -212,19 +216,10 @@ impl Partition {
             drop(f);
 
             // set parquet metadata cache
-            let mut file_key = path.clone();
-            file_key.set_extension("parquet");
-            let file_key = file_key
-                .strip_prefix(base_path.clone())
-                .unwrap()
-                .to_string_lossy()
-                .replace('\\', "/")
-                .trim_start_matches('/')
-                .to_string();
             super::WAL_PARQUET_METADATA
                 .write()
                 .await
-                .insert(file_key, file_meta);
+                .insert(wal_parquet_file_key(&path), file_meta);
 
             // update metrics
             metrics::INGEST_WAL_USED_BYTES
@@ -240,6 +235,20 @@ impl Partition {
     }
 }
 
+/// The key of a persisted file in `WAL_PARQUET_METADATA`, the path of its parquet file relative
+/// to the wal dir
+pub(crate) fn wal_parquet_file_key(path: &Path) -> String {
+    let base_path = PathBuf::from(&config::get_config().common.data_wal_dir);
+    let file_key = path.with_extension("parquet");
+    file_key
+        .strip_prefix(&base_path)
+        .unwrap_or(&file_key)
+        .to_string_lossy()
+        .replace('\\', "/")
+        .trim_start_matches('/')
+        .to_string()
+}
+
 struct PartitionFile {
     data: Vec<Arc<RecordBatchEntry>>,
 }
@@ -16,12 +16,15 @@
 use std::{
     fs::{create_dir_all, File},
     io::{BufRead, BufReader},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
 use async_walkdir::WalkDir;
-use config::utils::schema::infer_json_schema_from_values;
+use config::utils::{
+    hash::{gxhash, Sum64},
+    schema::infer_json_schema_from_values,
+};
 use futures::StreamExt;
 use snafu::ResultExt;
 
@@ -102,112 +105,161 @@ pub(crate) async fn check_uncompleted_parquet_files() -> Result<()> {
 }
 
 // replay wal files to create immutable
+//
+// the files are replayed by a pool of workers, the files of a writer always go to the same worker
+// so they are replayed in order
 pub(crate) async fn replay_wal_files() -> Result<()> {
     let wal_dir = PathBuf::from(&config::get_config().common.data_wal_dir).join("logs");
     create_dir_all(&wal_dir).context(OpenDirSnafu {
         path: wal_dir.clone(),
     })?;
-    let wal_files = wal_scan_files(&wal_dir, "wal").await.unwrap_or_default();
+    let mut wal_files = wal_scan_files(&wal_dir, "wal").await.unwrap_or_default();
     if wal_files.is_empty() {
         return Ok(());
     }
-    for wal_file in wal_files.iter() {
-        log::warn!("starting replay wal file: {:?}", wal_file);
-        let file_str = wal_file
-            .strip_prefix(&wal_dir)
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .replace('\\', "/")
-            .to_string();
-        let file_columns = file_str.split('/').collect::<Vec<_>>();
-        let stream_type = file_columns[file_columns.len() - 2];
-        let org_id = file_columns[file_columns.len() - 3];
-        let idx: usize = file_columns[file_columns.len() - 4]
-            .parse()
-            .unwrap_or_default();
-        let key = WriterKey::new(org_id, stream_type);
-        let mut memtable = memtable::MemTable::new();
-        let mut reader = match wal::Reader::from_path(wal_file) {
-            Ok(v) => v,
-            Err(e) => {
-                log::error!("Unable to open the wal file err: {}, skip", e);
+    wal_files.sort();
+
+    let start = std::time::Instant::now();
+    let files_num = wal_files.len();
+    let thread_num = std::cmp::max(1, config::get_config().limit.wal_replay_thread_num);
+    let mut worker_files = vec![Vec::new(); std::cmp::min(thread_num, files_num)];
+    for wal_file in wal_files {
+        let writer_dir = wal_file.parent().unwrap_or(&wal_dir).to_string_lossy();
+        let worker_id = gxhash::new().sum64(&writer_dir) as usize % worker_files.len();
+        worker_files[worker_id].push(wal_file);
+    }
+
+    let mut tasks = Vec::with_capacity(worker_files.len());
+    for files in worker_files.into_iter().filter(|files| !files.is_empty()) {
+        let wal_dir = wal_dir.clone();
+        tasks.push(tokio::task::spawn(async move {
+            let (mut entries, mut records) = (0, 0);
+            for wal_file in files.iter() {
+                if let Some((file_entries, file_records)) =
+                    replay_wal_file(&wal_dir, wal_file).await?
+                {
+                    entries += file_entries;
+                    records += file_records;
+                }
+            }
+            Ok::<_, Error>((entries, records))
+        }));
+    }
+    let (mut entries, mut records) = (0, 0);
+    for task in tasks {
+        let (task_entries, task_records) = task.await.context(TokioJoinSnafu)??;
+        entries += task_entries;
+        records += task_records;
+    }
+
+    let took = start.elapsed().as_secs_f64();
+    log::warn!(
+        "replay wal files done, files: {}, entries: {}, records: {}, took: {:.3} s, throughput: {:.0} records/s",
+        files_num,
+        entries,
+        records,
+        took,
+        records as f64 / took.max(0.001)
+    );
+    Ok(())
+}
+
+// replay one wal file into an immutable, returns the replayed entries and records, or None if
+// the file can't be opened
+async fn replay_wal_file(wal_dir: &Path, wal_file: &Path) -> Result<Option<(usize, usize)>> {
+    log::warn!("starting replay wal file: {:?}", wal_file);
+    let file_str = wal_file
+        .strip_prefix(wal_dir)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .replace('\\', "/")
+        .to_string();
+    let file_columns = file_str.split('/').collect::<Vec<_>>();
+    let stream_type = file_columns[file_columns.len() - 2];
+    let org_id = file_columns[file_columns.len() - 3];
+    let idx: usize = file_columns[file_columns.len() - 4]
+        .parse()
+        .unwrap_or_default();
+    let key = WriterKey::new(org_id, stream_type);
+    let mut memtable = memtable::MemTable::new();
+    let mut reader = match wal::Reader::from_path(wal_file) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Unable to open the wal file err: {}, skip", e);
+            return Ok(None);
+        }
+    };
+    let mut total = 0;
+    let mut i = 0;
+    loop {
+        if i > 0 && i % 1000 == 0 {
+            log::warn!(
+                "replay wal file: {:?}, entries: {}, records: {}",
+                wal_file,
+                i,
+                total
+            );
+        }
+        let entry = match reader.read_entry() {
+            Ok(entry) => entry,
+            Err(wal::Error::UnableToReadData { source }) => {
+                log::error!("Unable to read entry from: {}, skip the entry", source);
                 continue;
             }
-        };
-        let mut total = 0;
-        let mut i = 0;
-        loop {
-            if i > 0 && i % 1000 == 0 {
-                log::warn!(
-                    "replay wal file: {:?}, entries: {}, records: {}",
-                    wal_file,
-                    i,
-                    total
+            Err(wal::Error::LengthMismatch { expected, actual }) => {
+                log::error!(
+                    "Unable to read entry: Length mismatch: expected {}, actual {}, skip the entry",
+                    expected,
+                    actual
                 );
+                continue;
             }
-            let entry = match reader.read_entry() {
-                Ok(entry) => entry,
-                Err(wal::Error::UnableToReadData { source }) => {
-                    log::error!("Unable to read entry from: {}, skip the entry", source);
-                    continue;
-                }
-                Err(wal::Error::LengthMismatch { expected, actual }) => {
-                    log::error!(
-                        "Unable to read entry: Length mismatch: expected {}, actual {}, skip the entry",
-                        expected,
-                        actual
-                    );
-                    continue;
-                }
-                Err(wal::Error::ChecksumMismatch { expected, actual }) => {
-                    log::error!(
-                        "Unable to read entry: Checksum mismatch: expected {}, actual {}, skip the entry",
-                        expected,
-                        actual
-                    );
-                    continue;
-                }
-                Err(e) => {
-                    return Err(Error::WalError { source: e });
-                }
-            };
-            let Some(entry_bytes) = entry else {
-                break;
-            };
-            let entry = match super::Entry::from_bytes(&entry_bytes) {
-                Ok(v) => v,
-                Err(Error::ReadDataError { source }) => {
-                    log::error!("Unable to read entry from: {}, skip the entry", source);
-                    continue;
-                }
-                Err(e) => {
-                    return Err(e);
-                }
-            };
-            i += 1;
-            total += entry.data.len();
-            let infer_schema =
-                infer_json_schema_from_values(entry.data.iter().cloned(), stream_type)
-                    .context(InferJsonSchemaSnafu)?;
-            let infer_schema = Arc::new(infer_schema);
-            let batch = entry.into_batch(key.stream_type.clone(), infer_schema.clone())?;
-            memtable.write(infer_schema, entry, batch)?;
-        }
-        log::warn!(
-            "replay wal file: {:?}, entries: {}, records: {}",
-            wal_file,
-            i,
-            total
-        );
-
-        immutable::IMMUTABLES.write().await.insert(
-            wal_file.to_owned(),
-            Arc::new(immutable::Immutable::new(idx, key, memtable)),
-        );
+            Err(wal::Error::ChecksumMismatch { expected, actual }) => {
+                log::error!(
+                    "Unable to read entry: Checksum mismatch: expected {}, actual {}, skip the entry",
+                    expected,
+                    actual
+                );
+                continue;
+            }
+            Err(e) => {
+                return Err(Error::WalError { source: e });
+            }
+        };
+        let Some(entry_bytes) = entry else {
+            break;
+        };
+        let entry = match super::Entry::from_bytes(&entry_bytes) {
+            Ok(v) => v,
+            Err(Error::ReadDataError { source }) => {
+                log::error!("Unable to read entry from: {}, skip the entry", source);
+                continue;
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        i += 1;
+        total += entry.data.len();
+        let infer_schema = infer_json_schema_from_values(entry.data.iter().cloned(), stream_type)
+            .context(InferJsonSchemaSnafu)?;
+        let infer_schema = Arc::new(infer_schema);
+        let batch = entry.into_batch(key.stream_type.clone(), infer_schema.clone())?;
+        memtable.write(infer_schema, entry, batch)?;
     }
+    log::warn!(
+        "replay wal file: {:?}, entries: {}, records: {}",
+        wal_file,
+        i,
+        total
+    );
 
-    Ok(())
+    immutable::IMMUTABLES.write().await.insert(
+        wal_file.to_owned(),
+        Arc::new(immutable::Immutable::new(idx, key, memtable)),
+    );
+    Ok(Some((i, total)))
 }
 
 async fn wal_scan_files(root_dir: impl Into<PathBuf>, ext: &str) -> Result<Vec<PathBuf>> {
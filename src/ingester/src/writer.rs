@@ -16,7 +16,7 @@
 use std::{
     path::PathBuf,
     sync::{
-        atomic::{AtomicI64, AtomicU64, Ordering},
+        atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
 };
@@ -26,7 +26,7 @@ use chrono::{Duration, Utc};
 use config::{
     get_config, metrics,
     utils::hash::{gxhash, Sum64},
-    MEM_TABLE_INDIVIDUAL_STREAMS,
+    RwHashMap, MEM_TABLE_INDIVIDUAL_STREAMS,
 };
 use hashbrown::HashSet;
 use once_cell::sync::Lazy;
@@ -53,6 +53,13 @@ static WRITERS: Lazy<Vec<RwMap<WriterKey, Arc<Writer>>>> = Lazy::new(|| {
     writers
 });
 
+/// The number of writers in `WRITERS`, each one keeps a wal file open
+static OPEN_WRITERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The newest timestamp written for each stream since the ingester started, key:
+/// org_id/stream_type/stream_name
+static MAX_TIMESTAMPS: Lazy<RwHashMap<String, i64>> = Lazy::new(Default::default);
+
 pub struct Writer {
     idx: usize,
     key: WriterKey,
@@ -60,6 +67,35 @@ pub struct Writer {
     memtable: Arc<RwLock<MemTable>>,
     next_seq: AtomicU64,
     created_at: AtomicI64,
+    last_used: AtomicI64,
+}
+
+/// Returns the newest timestamp written for the stream since the ingester started, 0 if none
+pub fn get_max_timestamp(org_id: &str, stream_type: &str, stream_name: &str) -> i64 {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    MAX_TIMESTAMPS.get(&key).map(|v| *v).unwrap_or_default()
+}
+
+/// Returns the newest timestamp written for each stream, key: org_id/stream_type/stream_name
+pub fn get_max_timestamps() -> Vec<(String, i64)> {
+    MAX_TIMESTAMPS
+        .iter()
+        .map(|v| (v.key().clone(), *v.value()))
+        .collect()
+}
+
+/// Forgets the newest timestamp written for the stream, when it's deleted
+pub fn remove_max_timestamp(org_id: &str, stream_type: &str, stream_name: &str) {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    MAX_TIMESTAMPS.remove(&key);
+}
+
+fn update_max_timestamp(org_id: &str, stream_type: &str, stream_name: &str, max_ts: i64) {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    let mut v = MAX_TIMESTAMPS.entry(key).or_default();
+    if *v < max_ts {
+        *v = max_ts;
+    }
 }
 
 // check total memory size
@@ -94,10 +130,79 @@ pub async fn get_writer(
     let key = WriterKey::new(org_id, stream_type);
     let idx = get_table_idx(thread_id, stream_name);
     let mut rw = WRITERS[idx].write().await;
-    let w = rw
-        .entry(key.clone())
-        .or_insert_with(|| Arc::new(Writer::new(idx, key)));
-    w.clone()
+    if let Some(w) = rw.get(&key) {
+        w.last_used
+            .store(Utc::now().timestamp_micros(), Ordering::Relaxed);
+        return w.clone();
+    }
+    let w = Arc::new(Writer::new(idx, key.clone()));
+    rw.insert(key, w.clone());
+    drop(rw);
+    OPEN_WRITERS.fetch_add(1, Ordering::Relaxed);
+    metrics::INGEST_WAL_OPEN_WRITERS
+        .with_label_values(&[])
+        .inc();
+
+    if let Err(e) = evict_writers().await {
+        log::error!(
+            "[INGESTER:MEM] close least recently used writer error: {}",
+            e
+        );
+    }
+    w
+}
+
+/// Closes the least recently used writers while more writers than `ZO_WAL_MAX_OPEN_WRITERS`
+/// are open, the data of a closed writer is moved to the immutables like on a rotation and the
+/// writer is created again on the next write. A writer in use by a write is never closed.
+async fn evict_writers() -> Result<()> {
+    let max_open_writers = get_config().limit.wal_max_open_writers;
+    if max_open_writers == 0 {
+        return Ok(());
+    }
+    while OPEN_WRITERS.load(Ordering::Relaxed) > max_open_writers {
+        // only the map holds an idle writer
+        let mut lru: Option<(usize, WriterKey, i64)> = None;
+        for (idx, w) in WRITERS.iter().enumerate() {
+            let w = w.read().await;
+            for (key, r) in w.iter() {
+                let last_used = r.last_used.load(Ordering::Relaxed);
+                if Arc::strong_count(r) == 1
+                    && lru.as_ref().map_or(true, |(_, _, v)| last_used < *v)
+                {
+                    lru = Some((idx, key.clone(), last_used));
+                }
+            }
+        }
+        let Some((idx, key, _)) = lru else {
+            // all the writers are in use
+            return Ok(());
+        };
+
+        let mut w = WRITERS[idx].write().await;
+        // the writer can be taken for a write since it was picked
+        let Some(r) = w.get(&key).filter(|r| Arc::strong_count(r) == 1) else {
+            continue;
+        };
+        r.close().await?;
+        w.remove(&key);
+        drop(w);
+        OPEN_WRITERS.fetch_sub(1, Ordering::Relaxed);
+        metrics::INGEST_WAL_OPEN_WRITERS
+            .with_label_values(&[])
+            .dec();
+        metrics::INGEST_WAL_WRITER_EVICTIONS
+            .with_label_values(&[])
+            .inc();
+        metrics::INGEST_MEMTABLE_FILES.with_label_values(&[]).dec();
+        log::info!(
+            "[INGESTER:MEM] closed least recently used writer {}/{}/{}",
+            idx,
+            key.org_id,
+            key.stream_type
+        );
+    }
+    Ok(())
 }
 
 pub async fn read_from_memtable(
@@ -156,6 +261,10 @@ pub async fn flush_all() -> Result<()> {
         for r in w.values() {
             r.close().await?; // close writer
             metrics::INGEST_MEMTABLE_FILES.with_label_values(&[]).dec();
+            OPEN_WRITERS.fetch_sub(1, Ordering::Relaxed);
+            metrics::INGEST_WAL_OPEN_WRITERS
+                .with_label_values(&[])
+                .dec();
         }
         for key in keys {
             w.remove(&key);
@@ -197,6 +306,7 @@ impl Writer {
             memtable: Arc::new(RwLock::new(MemTable::new())),
             next_seq,
             created_at: AtomicI64::new(now),
+            last_used: AtomicI64::new(now),
         }
     }
 
@@ -279,6 +389,12 @@ impl Writer {
             let Some(entry_batch) = entry_batch else {
                 return Ok(());
             };
+            update_max_timestamp(
+                &self.key.org_id,
+                &self.key.stream_type,
+                &entry.stream,
+                entry_batch.max_ts,
+            );
             mem.write(schema, entry, entry_batch)?;
         }
 
@@ -97,6 +97,7 @@ pub async fn run() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { run_merge(tx).await });
     tokio::task::spawn(async move { run_retention().await });
     tokio::task::spawn(async move { run_delay_deletion().await });
+    tokio::task::spawn(async move { run_rollup().await });
     tokio::task::spawn(async move { run_sync_to_db().await });
     tokio::task::spawn(async move { run_check_running_jobs().await });
     tokio::task::spawn(async move { run_clean_done_jobs().await });
@@ -152,13 +153,20 @@ async fn run_merge(tx: mpsc::Sender<(MergeSender, MergeBatch)>) -> Result<(), an
         if let Err(e) = compact::run_merge(tx.clone()).await {
             log::error!("[COMPACTOR] run data merge error: {e}");
         }
+        // the manual jobs run after the scheduled jobs, so they don't work on the same files
+        if let Err(e) = compact::manual::run(tx.clone()).await {
+            log::error!("[COMPACTOR] run manual merge error: {e}");
+        }
     }
 }
 
 /// Deletion for data retention
 async fn run_retention() -> Result<(), anyhow::Error> {
     loop {
-        time::sleep(time::Duration::from_secs(get_config().compact.interval + 1)).await;
+        time::sleep(time::Duration::from_secs(
+            get_config().compact.retention_interval,
+        ))
+        .await;
         log::debug!("[COMPACTOR] Running data retention");
         if let Err(e) = compact::run_retention().await {
             log::error!("[COMPACTOR] run data retention error: {e}");
@@ -177,6 +185,17 @@ async fn run_delay_deletion() -> Result<(), anyhow::Error> {
     }
 }
 
+/// Generate the rollup streams of metrics
+async fn run_rollup() -> Result<(), anyhow::Error> {
+    loop {
+        time::sleep(time::Duration::from_secs(get_config().compact.interval + 3)).await;
+        log::debug!("[COMPACTOR] Running metrics rollup");
+        if let Err(e) = compact::rollup::run_rollup().await {
+            log::error!("[COMPACTOR] run metrics rollup error: {e}");
+        }
+    }
+}
+
 async fn run_sync_to_db() -> Result<(), anyhow::Error> {
     loop {
         time::sleep(time::Duration::from_secs(
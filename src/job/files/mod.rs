@@ -33,6 +33,7 @@ pub async fn run() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { parquet::run().await });
     tokio::task::spawn(async move { broadcast::run().await });
     tokio::task::spawn(async move { clean_empty_dirs().await });
+    tokio::task::spawn(async move { clean_idempotency_keys().await });
 
     Ok(())
 }
@@ -55,6 +56,20 @@ async fn clean_empty_dirs() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+async fn clean_idempotency_keys() -> Result<(), anyhow::Error> {
+    loop {
+        if is_offline() {
+            break;
+        }
+        time::sleep(time::Duration::from_secs(600)).await;
+        if let Err(e) = crate::service::ingestion::idempotency::run_gc().await {
+            log::error!("clean_idempotency_keys, err: {}", e);
+        }
+    }
+    log::info!("job::files::clean_idempotency_keys is stopped");
+    Ok(())
+}
+
 pub fn generate_storage_file_name(
     org_id: &str,
     stream_type: StreamType,
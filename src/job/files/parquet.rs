@@ -647,6 +647,7 @@ async fn merge_files(
         original_size: new_file_size,
         compressed_size: 0,
         flattened: false,
+        index_size: 0,
     };
     if new_file_meta.records == 0 {
         return Err(anyhow::anyhow!(
@@ -757,14 +758,14 @@ async fn merge_files(
                         InvertedIndexFormat::FST | InvertedIndexFormat::Both
                     ) {
                         // generate fst inverted index and write to storage
-                        generate_fst_inverted_index(
+                        new_file_meta.index_size = generate_fst_inverted_index(
                             inverted_idx_batch,
                             &new_file_key,
                             &full_text_search_fields,
                             &index_fields,
                             None,
                         )
-                        .await?;
+                        .await? as i64;
                     }
                 }
             }
@@ -1287,7 +1288,8 @@ fn prepare_index_record_batches(
     Ok(indexed_record_batches_to_merge)
 }
 
-/// Creates fst inverted index bytes and writes to storage.
+/// Creates fst inverted index bytes and writes to storage, returns the size of the index file, 0
+/// when no index is created.
 /// Called by both ingester and compactor. Compactor needs to provide `file_list_to_invalidate`
 /// to delete previously created small index files
 pub(crate) async fn generate_fst_inverted_index(
@@ -1297,12 +1299,12 @@ pub(crate) async fn generate_fst_inverted_index(
     index_fields: &[String],
     file_list_to_invalidate: Option<&[FileKey]>, /* for compactor to delete corresponding small
                                                   * .idx files */
-) -> Result<(), anyhow::Error> {
+) -> Result<usize, anyhow::Error> {
     let Some((compressed_bytes, file_meta)) =
         prepare_fst_index_bytes(inverted_idx_batch, full_text_search_fields, index_fields)?
     else {
         log::info!("generate_fst_index_on_compactor creates empty index. skip");
-        return Ok(());
+        return Ok(0);
     };
 
     // delete corresponding small .puffin files
@@ -1323,7 +1325,7 @@ pub(crate) async fn generate_fst_inverted_index(
 
     // write fst bytes into disk
     let Some(idx_file_name) = convert_parquet_idx_file_name(parquet_file_name) else {
-        return Ok(());
+        return Ok(0);
     };
     let caller = if file_list_to_invalidate.is_some() {
         "[COMPACTOR:JOB]"
@@ -1338,7 +1340,7 @@ pub(crate) async fn generate_fst_inverted_index(
                 file_meta.compressed_size,
                 file_meta.original_size,
             );
-            Ok(())
+            Ok(file_meta.compressed_size as usize)
         }
         Err(e) => {
             log::error!("{} Written fst index file error: {}", caller, e.to_string());
@@ -1460,6 +1462,7 @@ pub(crate) fn prepare_fst_index_bytes(
         original_size: 0,
         compressed_size: 0,
         flattened: false,
+        index_size: 0,
     };
 
     let _ = index_file_metas.finish(&mut writer)?;
@@ -39,7 +39,7 @@ use tokio::{sync::RwLock, time};
 use crate::{
     common::infra::{cluster::get_cached_online_nodes, config::USERS},
     service::{
-        db,
+        self, db,
         exporter::otlp_o2_metrics_exporter::{O2MetricsClient, O2MetricsExporter},
     },
 };
@@ -101,6 +101,9 @@ pub async fn run() -> Result<(), anyhow::Error> {
         if let Err(e) = update_memory_usage().await {
             log::error!("Error update memory_usage metrics: {}", e);
         }
+        if config::cluster::LOCAL_NODE.is_ingester() {
+            service::ingestion::lag::update_metrics();
+        }
         interval.tick().await;
     }
 }
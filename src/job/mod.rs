@@ -46,6 +46,11 @@ pub async fn init() -> Result<(), anyhow::Error> {
     .expect("Email regex is valid");
 
     let cfg = config::get_config();
+    // remove the spill files left by the queries of the last run
+    if let Err(e) = crate::service::search::datafusion::exec::clean_spill_dir() {
+        log::warn!("Failed to clean the datafusion spill dir: {e}");
+    }
+
     // init root user
     if !db::user::root_user_exists().await {
         if cfg.auth.root_user_email.is_empty()
@@ -111,6 +116,7 @@ pub async fn init() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { db::schema::watch().await });
     tokio::task::spawn(async move { db::functions::watch().await });
     tokio::task::spawn(async move { db::compact::retention::watch().await });
+    tokio::task::spawn(async move { db::compact::manual::watch().await });
     tokio::task::spawn(async move { db::metrics::watch_prom_cluster_leader().await });
     tokio::task::spawn(async move { db::alerts::templates::watch().await });
     tokio::task::spawn(async move { db::alerts::destinations::watch().await });
@@ -142,6 +148,9 @@ pub async fn init() -> Result<(), anyhow::Error> {
     db::compact::retention::cache()
         .await
         .expect("compact delete cache failed");
+    db::compact::manual::cache()
+        .await
+        .expect("compact manual cancel cache failed");
     db::metrics::cache_prom_cluster_leader()
         .await
         .expect("prom cluster leader cache failed");
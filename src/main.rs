@@ -22,10 +22,11 @@ use std::{
         atomic::{AtomicU16, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use actix_web::{dev::ServerHandle, http::KeepAlive, middleware, web, App, HttpServer};
+use actix_web_lab::middleware::from_fn;
 use actix_web_opentelemetry::RequestTracing;
 use arrow_flight::flight_service_server::FlightServiceServer;
 use config::get_config;
@@ -42,6 +43,7 @@ use openobserve::{
             auth::check_auth,
             flight::FlightServiceImpl,
             request::{
+                alert::AlertEvaluatorServerImpl,
                 event::Eventer,
                 ingest::Ingester,
                 logs::LogsServer,
@@ -54,7 +56,7 @@ use openobserve::{
         http::router::*,
     },
     job, router,
-    service::{db, metadata, search::SEARCH_SERVER, usage},
+    service::{self, db, metadata, search::SEARCH_SERVER, usage},
 };
 use opentelemetry::{global, trace::TracerProvider, KeyValue};
 use opentelemetry_otlp::WithExportConfig;
@@ -65,7 +67,8 @@ use opentelemetry_proto::tonic::collector::{
 };
 use opentelemetry_sdk::{propagation::TraceContextPropagator, Resource};
 use proto::cluster_rpc::{
-    event_server::EventServer, ingest_server::IngestServer, metrics_server::MetricsServer,
+    alert_evaluator_server::AlertEvaluatorServer, event_server::EventServer,
+    ingest_server::IngestServer, metrics_server::MetricsServer,
     query_cache_server::QueryCacheServer, search_server::SearchServer, usage_server::UsageServer,
 };
 #[cfg(feature = "profiling")]
@@ -76,6 +79,12 @@ use tokio::sync::oneshot;
 use tonic::{
     codec::CompressionEncoding,
     metadata::{MetadataKey, MetadataMap, MetadataValue},
+    service::interceptor::InterceptedService,
+};
+use tonic_health::{
+    pb::health_server::{Health, HealthServer},
+    server::HealthReporter,
+    ServingStatus,
 };
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_opentelemetry::OpenTelemetryLayer;
@@ -170,6 +179,12 @@ async fn main() -> Result<(), anyhow::Error> {
         cfg.limit.disk_free / 1024 / 1024 / 1024,
     );
 
+    // check the settings before any component is initialized
+    if let Err(e) = config::validate(&cfg) {
+        log::error!("{e}");
+        return Err(e);
+    }
+
     // init backend jobs
     let (job_init_tx, job_init_rx) = oneshot::channel();
     let (job_shutudown_tx, job_shutdown_rx) = oneshot::channel();
@@ -279,10 +294,17 @@ async fn main() -> Result<(), anyhow::Error> {
         }
     }
 
+    // init gRPC health service, reports NOT_SERVING until the node is ready
+    let (mut health_reporter, health_svc) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_service_status("", ServingStatus::NotServing)
+        .await;
+
     // init gRPC server
     let (grpc_init_tx, grpc_init_rx) = oneshot::channel();
     let (grpc_shutudown_tx, grpc_shutdown_rx) = oneshot::channel();
     let (grpc_stopped_tx, grpc_stopped_rx) = oneshot::channel();
+    let grpc_health_reporter = health_reporter.clone();
     let grpc_rt_handle = std::thread::spawn(move || {
         let cfg = get_config();
         let rt = tokio::runtime::Builder::new_multi_thread()
@@ -295,13 +317,24 @@ async fn main() -> Result<(), anyhow::Error> {
         let _guard = rt.enter();
         rt.block_on(async move {
             if config::cluster::LOCAL_NODE.is_router() {
-                init_router_grpc_server(grpc_init_tx, grpc_shutdown_rx, grpc_stopped_tx)
-                    .await
-                    .expect("router gRPC server init failed");
+                init_router_grpc_server(
+                    grpc_init_tx,
+                    grpc_shutdown_rx,
+                    grpc_stopped_tx,
+                    grpc_health_reporter,
+                    health_svc,
+                )
+                .await
+                .expect("router gRPC server init failed");
             } else {
-                init_common_grpc_server(grpc_init_tx, grpc_shutdown_rx, grpc_stopped_tx)
-                    .await
-                    .expect("router gRPC server init failed");
+                init_common_grpc_server(
+                    grpc_init_tx,
+                    grpc_shutdown_rx,
+                    grpc_stopped_tx,
+                    health_svc,
+                )
+                .await
+                .expect("router gRPC server init failed");
             }
         });
     });
@@ -312,6 +345,14 @@ async fn main() -> Result<(), anyhow::Error> {
     // let node online
     let _ = cluster::set_online(false).await;
 
+    // the node is ready after infra init and WAL replay, router is marked as ready when its gRPC
+    // server starts as it doesn't depend on the ingester state
+    if !config::cluster::LOCAL_NODE.is_router() {
+        health_reporter
+            .set_service_status("", ServingStatus::Serving)
+            .await;
+    }
+
     // This is specifically for enrichment tables, as caching is happening using
     // search service
     db::schema::cache_enrichment_tables()
@@ -331,10 +372,10 @@ async fn main() -> Result<(), anyhow::Error> {
 
     // init http server
     if !cfg.common.tracing_enabled && cfg.common.tracing_search_enabled {
-        if let Err(e) = init_http_server_without_tracing().await {
+        if let Err(e) = init_http_server_without_tracing(health_reporter).await {
             log::error!("HTTP server runs failed: {}", e);
         }
-    } else if let Err(e) = init_http_server().await {
+    } else if let Err(e) = init_http_server(health_reporter).await {
         log::error!("HTTP server runs failed: {}", e);
     }
     log::info!("HTTP server stopped");
@@ -380,6 +421,7 @@ async fn init_common_grpc_server(
     init_tx: oneshot::Sender<()>,
     shutdown_rx: oneshot::Receiver<()>,
     stopped_tx: oneshot::Sender<()>,
+    health_svc: HealthServer<impl Health>,
 ) -> Result<(), anyhow::Error> {
     let cfg = get_config();
     let ip = if !cfg.grpc.addr.is_empty() {
@@ -424,21 +466,32 @@ async fn init_common_grpc_server(
     let flight_svc = FlightServiceServer::new(FlightServiceImpl)
         .send_compressed(CompressionEncoding::Gzip)
         .accept_compressed(CompressionEncoding::Gzip);
+    let alert_evaluator_svc = AlertEvaluatorServer::new(AlertEvaluatorServerImpl)
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
+        .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
 
+    let mut builder = tonic::transport::Server::builder();
+    if let Some(tls) = service::grpc::server_tls_config()? {
+        builder = builder.tls_config(tls)?;
+    }
     log::info!("starting gRPC server at {}", gaddr);
     init_tx.send(()).ok();
-    tonic::transport::Server::builder()
-        .layer(tonic::service::interceptor(check_auth))
-        .add_service(event_svc)
-        .add_service(search_svc)
-        .add_service(metrics_svc)
-        .add_service(metrics_ingest_svc)
-        .add_service(trace_svc)
-        .add_service(usage_svc)
-        .add_service(logs_svc)
-        .add_service(query_cache_svc)
-        .add_service(ingest_svc)
-        .add_service(flight_svc)
+    // health service is not behind auth so that probes can reach it
+    builder
+        .add_service(health_svc)
+        .add_service(InterceptedService::new(event_svc, check_auth))
+        .add_service(InterceptedService::new(search_svc, check_auth))
+        .add_service(InterceptedService::new(metrics_svc, check_auth))
+        .add_service(InterceptedService::new(metrics_ingest_svc, check_auth))
+        .add_service(InterceptedService::new(trace_svc, check_auth))
+        .add_service(InterceptedService::new(usage_svc, check_auth))
+        .add_service(InterceptedService::new(logs_svc, check_auth))
+        .add_service(InterceptedService::new(query_cache_svc, check_auth))
+        .add_service(InterceptedService::new(ingest_svc, check_auth))
+        .add_service(InterceptedService::new(flight_svc, check_auth))
+        .add_service(InterceptedService::new(alert_evaluator_svc, check_auth))
         .serve_with_shutdown(gaddr, async {
             shutdown_rx.await.ok();
             log::info!("gRPC server starts shutting down");
@@ -453,6 +506,8 @@ async fn init_router_grpc_server(
     init_tx: oneshot::Sender<()>,
     shutdown_rx: oneshot::Receiver<()>,
     stopped_tx: oneshot::Sender<()>,
+    mut health_reporter: HealthReporter,
+    health_svc: HealthServer<impl Health>,
 ) -> Result<(), anyhow::Error> {
     let cfg = get_config();
     let gaddr: SocketAddr = format!("0.0.0.0:{}", cfg.grpc.port).parse()?;
@@ -472,13 +527,21 @@ async fn init_router_grpc_server(
         .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
         .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
 
+    let mut builder = tonic::transport::Server::builder();
+    if let Some(tls) = service::grpc::server_tls_config()? {
+        builder = builder.tls_config(tls)?;
+    }
     log::info!("starting gRPC server at {}", gaddr);
     init_tx.send(()).ok();
-    tonic::transport::Server::builder()
-        .layer(tonic::service::interceptor(check_auth))
-        .add_service(logs_svc)
-        .add_service(metrics_svc)
-        .add_service(traces_svc)
+    health_reporter
+        .set_service_status("", ServingStatus::Serving)
+        .await;
+    // health service is not behind auth so that probes can reach it
+    builder
+        .add_service(health_svc)
+        .add_service(InterceptedService::new(logs_svc, check_auth))
+        .add_service(InterceptedService::new(metrics_svc, check_auth))
+        .add_service(InterceptedService::new(traces_svc, check_auth))
         .serve_with_shutdown(gaddr, async {
             shutdown_rx.await.ok();
             log::info!("gRPC server starts shutting down");
@@ -489,7 +552,7 @@ async fn init_router_grpc_server(
     Ok(())
 }
 
-async fn init_http_server() -> Result<(), anyhow::Error> {
+async fn init_http_server(health_reporter: HealthReporter) -> Result<(), anyhow::Error> {
     let cfg = get_config();
     // metrics
     let prometheus = config::metrics::create_prometheus_handler();
@@ -551,11 +614,13 @@ async fn init_http_server() -> Result<(), anyhow::Error> {
         app.app_data(web::JsonConfig::default().limit(cfg.limit.req_json_limit))
             .app_data(web::PayloadConfig::new(cfg.limit.req_payload_limit)) // size is in bytes
             .app_data(web::Data::new(local_id))
+            .wrap(from_fn(compress_response))
             .wrap(middleware::Compress::default())
             .wrap(middleware::Logger::new(
                 r#"%a "%r" %s %b "%{Content-Length}i" "%{Referer}i" "%{User-Agent}i" %T"#,
             ))
             .wrap(RequestTracing::new())
+            .wrap(from_fn(count_inflight_requests))
     })
     .keep_alive(KeepAlive::Timeout(Duration::from_secs(max(
         15,
@@ -572,13 +637,15 @@ async fn init_http_server() -> Result<(), anyhow::Error> {
         .run();
     let handle = server.handle();
     tokio::task::spawn(async move {
-        graceful_shutdown(handle).await;
+        graceful_shutdown(handle, health_reporter).await;
     });
     server.await?;
     Ok(())
 }
 
-async fn init_http_server_without_tracing() -> Result<(), anyhow::Error> {
+async fn init_http_server_without_tracing(
+    health_reporter: HealthReporter,
+) -> Result<(), anyhow::Error> {
     let cfg = get_config();
     // metrics
     let prometheus = config::metrics::create_prometheus_handler();
@@ -640,10 +707,12 @@ async fn init_http_server_without_tracing() -> Result<(), anyhow::Error> {
         app.app_data(web::JsonConfig::default().limit(cfg.limit.req_json_limit))
             .app_data(web::PayloadConfig::new(cfg.limit.req_payload_limit)) // size is in bytes
             .app_data(web::Data::new(local_id))
+            .wrap(from_fn(compress_response))
             .wrap(middleware::Compress::default())
             .wrap(middleware::Logger::new(
                 r#"%a "%r" %s %b "%{Content-Length}i" "%{Referer}i" "%{User-Agent}i" %T"#,
             ))
+            .wrap(from_fn(count_inflight_requests))
     })
     .keep_alive(KeepAlive::Timeout(Duration::from_secs(max(
         15,
@@ -660,13 +729,13 @@ async fn init_http_server_without_tracing() -> Result<(), anyhow::Error> {
         .run();
     let handle = server.handle();
     tokio::task::spawn(async move {
-        graceful_shutdown(handle).await;
+        graceful_shutdown(handle, health_reporter).await;
     });
     server.await?;
     Ok(())
 }
 
-async fn graceful_shutdown(handle: ServerHandle) {
+async fn graceful_shutdown(handle: ServerHandle, mut health_reporter: HealthReporter) {
     #[cfg(unix)]
     {
         use tokio::signal::unix::{signal, SignalKind};
@@ -701,12 +770,37 @@ async fn graceful_shutdown(handle: ServerHandle) {
     // tokio::signal::ctrl_c().await.unwrap();
     // println!("ctrl-c received!");
 
+    // stop receiving new traffic
+    health_reporter
+        .set_service_status("", ServingStatus::NotServing)
+        .await;
+
     // offline the node
     if let Err(e) = cluster::set_offline(true).await {
         log::error!("set offline failed: {}", e);
     }
     log::info!("Node is offline");
 
+    // wait for in-flight requests to finish before stopping the server
+    let drain_timeout = Duration::from_secs(get_config().limit.http_drain_timeout);
+    let start = Instant::now();
+    loop {
+        let inflight = HTTP_INFLIGHT_REQUESTS.load(Ordering::Relaxed);
+        if inflight == 0 {
+            log::info!("HTTP server drained");
+            break;
+        }
+        if start.elapsed() >= drain_timeout {
+            log::warn!(
+                "HTTP server drain timeout, {} requests still in flight",
+                inflight
+            );
+            break;
+        }
+        log::info!("HTTP server draining, {} requests in flight", inflight);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
     handle.stop(true).await;
 }
 
@@ -62,6 +62,7 @@ fn main() -> Result<()> {
                 "proto/cluster/usage.proto",
                 "proto/cluster/querycache.proto",
                 "proto/cluster/plan.proto",
+                "proto/cluster/alert.proto",
             ],
             &["proto"],
         )
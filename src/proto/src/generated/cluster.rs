@@ -5,8 +5,7 @@ pub struct EmptyRequest {}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EmptyResponse {}
-#[derive(Eq)]
-#[derive(serde::Serialize)]
+#[derive(Eq, serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FileDescriptor {
@@ -15,8 +14,7 @@ pub struct FileDescriptor {
     #[prost(enumeration = "StreamType", tag = "2")]
     pub file_type: i32,
 }
-#[derive(Eq)]
-#[derive(serde::Serialize)]
+#[derive(Eq, serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FileMeta {
@@ -32,10 +30,11 @@ pub struct FileMeta {
     pub original_size: i64,
     #[prost(int64, tag = "5")]
     pub compressed_size: i64,
+    #[prost(int64, tag = "6")]
+    pub index_size: i64,
 }
 /// Job information for a request
-#[derive(Eq)]
-#[derive(serde::Serialize)]
+#[derive(Eq, serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Job {
@@ -48,8 +47,7 @@ pub struct Job {
     #[prost(int32, tag = "4")]
     pub partition: i32,
 }
-#[derive(Eq)]
-#[derive(serde::Serialize)]
+#[derive(Eq, serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ScanStats {
@@ -76,16 +74,14 @@ pub struct ScanStats {
     #[prost(int64, tag = "9")]
     pub idx_took: i64,
 }
-#[derive(Eq)]
-#[derive(serde::Serialize)]
+#[derive(Eq, serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FileList {
     #[prost(message, repeated, tag = "1")]
     pub items: ::prost::alloc::vec::Vec<FileKey>,
 }
-#[derive(Eq)]
-#[derive(serde::Serialize)]
+#[derive(Eq, serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FileKey {
@@ -151,8 +147,7 @@ impl StreamType {
 /// Generated client implementations.
 pub mod event_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
+    use tonic::codegen::{http::Uri, *};
     #[derive(Debug, Clone)]
     pub struct EventClient<T> {
         inner: tonic::client::Grpc<T>,
@@ -191,14 +186,13 @@ pub mod event_client {
             F: tonic::service::Interceptor,
             T::ResponseBody: Default,
             T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    http::Request<tonic::body::BoxBody>,
+                    Response = http::Response<
+                        <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    >,
                 >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
         {
             EventClient::new(InterceptedService::new(inner, interceptor))
         }
@@ -237,19 +231,14 @@ pub mod event_client {
             &mut self,
             request: impl tonic::IntoRequest<super::FileList>,
         ) -> std::result::Result<tonic::Response<super::EmptyResponse>, tonic::Status> {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.Event/SendFileList",
-            );
+            let path = http::uri::PathAndQuery::from_static("/cluster.Event/SendFileList");
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(GrpcMethod::new("cluster.Event", "SendFileList"));
@@ -292,10 +281,7 @@ pub mod event_server {
                 max_encoding_message_size: None,
             }
         }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> InterceptedService<Self, F>
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
         where
             F: tonic::service::Interceptor,
         {
@@ -351,21 +337,16 @@ pub mod event_server {
                 "/cluster.Event/SendFileList" => {
                     #[allow(non_camel_case_types)]
                     struct SendFileListSvc<T: Event>(pub Arc<T>);
-                    impl<T: Event> tonic::server::UnaryService<super::FileList>
-                    for SendFileListSvc<T> {
+                    impl<T: Event> tonic::server::UnaryService<super::FileList> for SendFileListSvc<T> {
                         type Response = super::EmptyResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::FileList>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                <T as Event>::send_file_list(&inner, request).await
-                            };
+                            let fut =
+                                async move { <T as Event>::send_file_list(&inner, request).await };
                             Box::pin(fut)
                         }
                     }
@@ -392,18 +373,14 @@ pub mod event_server {
                     };
                     Box::pin(fut)
                 }
-                _ => {
-                    Box::pin(async move {
-                        Ok(
-                            http::Response::builder()
-                                .status(200)
-                                .header("grpc-status", "12")
-                                .header("content-type", "application/grpc")
-                                .body(empty_body())
-                                .unwrap(),
-                        )
-                    })
-                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
             }
         }
     }
@@ -553,8 +530,7 @@ pub struct MetricsWalFile {
 /// Generated client implementations.
 pub mod metrics_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
+    use tonic::codegen::{http::Uri, *};
     #[derive(Debug, Clone)]
     pub struct MetricsClient<T> {
         inner: tonic::client::Grpc<T>,
@@ -593,14 +569,13 @@ pub mod metrics_client {
             F: tonic::service::Interceptor,
             T::ResponseBody: Default,
             T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    http::Request<tonic::body::BoxBody>,
+                    Response = http::Response<
+                        <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    >,
                 >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
         {
             MetricsClient::new(InterceptedService::new(inner, interceptor))
         }
@@ -638,45 +613,37 @@ pub mod metrics_client {
         pub async fn query(
             &mut self,
             request: impl tonic::IntoRequest<super::MetricsQueryRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::MetricsQueryResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::MetricsQueryResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static("/cluster.Metrics/Query");
             let mut req = request.into_request();
-            req.extensions_mut().insert(GrpcMethod::new("cluster.Metrics", "Query"));
+            req.extensions_mut()
+                .insert(GrpcMethod::new("cluster.Metrics", "Query"));
             self.inner.unary(req, path, codec).await
         }
         pub async fn wal_file(
             &mut self,
             request: impl tonic::IntoRequest<super::MetricsWalFileRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::MetricsWalFileResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::MetricsWalFileResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static("/cluster.Metrics/WalFile");
             let mut req = request.into_request();
-            req.extensions_mut().insert(GrpcMethod::new("cluster.Metrics", "WalFile"));
+            req.extensions_mut()
+                .insert(GrpcMethod::new("cluster.Metrics", "WalFile"));
             self.inner.unary(req, path, codec).await
         }
     }
@@ -685,23 +652,18 @@ pub mod metrics_client {
 pub mod metrics_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with MetricsServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with
+    /// MetricsServer.
     #[async_trait]
     pub trait Metrics: Send + Sync + 'static {
         async fn query(
             &self,
             request: tonic::Request<super::MetricsQueryRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::MetricsQueryResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::MetricsQueryResponse>, tonic::Status>;
         async fn wal_file(
             &self,
             request: tonic::Request<super::MetricsWalFileRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::MetricsWalFileResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::MetricsWalFileResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct MetricsServer<T: Metrics> {
@@ -726,10 +688,7 @@ pub mod metrics_server {
                 max_encoding_message_size: None,
             }
         }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> InterceptedService<Self, F>
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
         where
             F: tonic::service::Interceptor,
         {
@@ -785,23 +744,15 @@ pub mod metrics_server {
                 "/cluster.Metrics/Query" => {
                     #[allow(non_camel_case_types)]
                     struct QuerySvc<T: Metrics>(pub Arc<T>);
-                    impl<
-                        T: Metrics,
-                    > tonic::server::UnaryService<super::MetricsQueryRequest>
-                    for QuerySvc<T> {
+                    impl<T: Metrics> tonic::server::UnaryService<super::MetricsQueryRequest> for QuerySvc<T> {
                         type Response = super::MetricsQueryResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::MetricsQueryRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                <T as Metrics>::query(&inner, request).await
-                            };
+                            let fut = async move { <T as Metrics>::query(&inner, request).await };
                             Box::pin(fut)
                         }
                     }
@@ -831,23 +782,16 @@ pub mod metrics_server {
                 "/cluster.Metrics/WalFile" => {
                     #[allow(non_camel_case_types)]
                     struct WalFileSvc<T: Metrics>(pub Arc<T>);
-                    impl<
-                        T: Metrics,
-                    > tonic::server::UnaryService<super::MetricsWalFileRequest>
-                    for WalFileSvc<T> {
+                    impl<T: Metrics> tonic::server::UnaryService<super::MetricsWalFileRequest> for WalFileSvc<T> {
                         type Response = super::MetricsWalFileResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::MetricsWalFileRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                <T as Metrics>::wal_file(&inner, request).await
-                            };
+                            let fut =
+                                async move { <T as Metrics>::wal_file(&inner, request).await };
                             Box::pin(fut)
                         }
                     }
@@ -874,18 +818,14 @@ pub mod metrics_server {
                     };
                     Box::pin(fut)
                 }
-                _ => {
-                    Box::pin(async move {
-                        Ok(
-                            http::Response::builder()
-                                .status(200)
-                                .header("grpc-status", "12")
-                                .header("content-type", "application/grpc")
-                                .body(empty_body())
-                                .unwrap(),
-                        )
-                    })
-                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
             }
         }
     }
@@ -944,6 +884,10 @@ pub struct SearchQuery {
     pub query_fn: ::prost::alloc::string::String,
     #[prost(bool, tag = "14")]
     pub skip_wal: bool,
+    #[prost(bool, tag = "15")]
+    pub wal_only: bool,
+    #[prost(string, repeated, tag = "16")]
+    pub fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// Search request
 #[derive(Eq)]
@@ -975,8 +919,7 @@ pub struct SearchRequest {
     #[prost(string, optional, tag = "13")]
     pub search_event_type: ::core::option::Option<::prost::alloc::string::String>,
 }
-#[derive(Eq)]
-#[derive(serde::Serialize)]
+#[derive(Eq, serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SearchResponse {
@@ -1109,8 +1052,7 @@ impl WorkGroup {
 /// Generated client implementations.
 pub mod search_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
+    use tonic::codegen::{http::Uri, *};
     #[derive(Debug, Clone)]
     pub struct SearchClient<T> {
         inner: tonic::client::Grpc<T>,
@@ -1149,14 +1091,13 @@ pub mod search_client {
             F: tonic::service::Interceptor,
             T::ResponseBody: Default,
             T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    http::Request<tonic::body::BoxBody>,
+                    Response = http::Response<
+                        <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    >,
                 >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
         {
             SearchClient::new(InterceptedService::new(inner, interceptor))
         }
@@ -1194,23 +1135,16 @@ pub mod search_client {
         pub async fn query_status(
             &mut self,
             request: impl tonic::IntoRequest<super::QueryStatusRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::QueryStatusResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::QueryStatusResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.Search/QueryStatus",
-            );
+            let path = http::uri::PathAndQuery::from_static("/cluster.Search/QueryStatus");
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(GrpcMethod::new("cluster.Search", "QueryStatus"));
@@ -1219,23 +1153,16 @@ pub mod search_client {
         pub async fn cancel_query(
             &mut self,
             request: impl tonic::IntoRequest<super::CancelQueryRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::CancelQueryResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::CancelQueryResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.Search/CancelQuery",
-            );
+            let path = http::uri::PathAndQuery::from_static("/cluster.Search/CancelQuery");
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(GrpcMethod::new("cluster.Search", "CancelQuery"));
@@ -1244,23 +1171,16 @@ pub mod search_client {
         pub async fn cluster_cancel_query(
             &mut self,
             request: impl tonic::IntoRequest<super::CancelQueryRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::CancelQueryResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::CancelQueryResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.Search/ClusterCancelQuery",
-            );
+            let path = http::uri::PathAndQuery::from_static("/cluster.Search/ClusterCancelQuery");
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(GrpcMethod::new("cluster.Search", "ClusterCancelQuery"));
@@ -1272,30 +1192,22 @@ pub mod search_client {
 pub mod search_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with SearchServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with
+    /// SearchServer.
     #[async_trait]
     pub trait Search: Send + Sync + 'static {
         async fn query_status(
             &self,
             request: tonic::Request<super::QueryStatusRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::QueryStatusResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::QueryStatusResponse>, tonic::Status>;
         async fn cancel_query(
             &self,
             request: tonic::Request<super::CancelQueryRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::CancelQueryResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::CancelQueryResponse>, tonic::Status>;
         async fn cluster_cancel_query(
             &self,
             request: tonic::Request<super::CancelQueryRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::CancelQueryResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::CancelQueryResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct SearchServer<T: Search> {
@@ -1320,10 +1232,7 @@ pub mod search_server {
                 max_encoding_message_size: None,
             }
         }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> InterceptedService<Self, F>
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
         where
             F: tonic::service::Interceptor,
         {
@@ -1379,23 +1288,16 @@ pub mod search_server {
                 "/cluster.Search/QueryStatus" => {
                     #[allow(non_camel_case_types)]
                     struct QueryStatusSvc<T: Search>(pub Arc<T>);
-                    impl<
-                        T: Search,
-                    > tonic::server::UnaryService<super::QueryStatusRequest>
-                    for QueryStatusSvc<T> {
+                    impl<T: Search> tonic::server::UnaryService<super::QueryStatusRequest> for QueryStatusSvc<T> {
                         type Response = super::QueryStatusResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::QueryStatusRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                <T as Search>::query_status(&inner, request).await
-                            };
+                            let fut =
+                                async move { <T as Search>::query_status(&inner, request).await };
                             Box::pin(fut)
                         }
                     }
@@ -1425,23 +1327,16 @@ pub mod search_server {
                 "/cluster.Search/CancelQuery" => {
                     #[allow(non_camel_case_types)]
                     struct CancelQuerySvc<T: Search>(pub Arc<T>);
-                    impl<
-                        T: Search,
-                    > tonic::server::UnaryService<super::CancelQueryRequest>
-                    for CancelQuerySvc<T> {
+                    impl<T: Search> tonic::server::UnaryService<super::CancelQueryRequest> for CancelQuerySvc<T> {
                         type Response = super::CancelQueryResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::CancelQueryRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                <T as Search>::cancel_query(&inner, request).await
-                            };
+                            let fut =
+                                async move { <T as Search>::cancel_query(&inner, request).await };
                             Box::pin(fut)
                         }
                     }
@@ -1471,15 +1366,11 @@ pub mod search_server {
                 "/cluster.Search/ClusterCancelQuery" => {
                     #[allow(non_camel_case_types)]
                     struct ClusterCancelQuerySvc<T: Search>(pub Arc<T>);
-                    impl<
-                        T: Search,
-                    > tonic::server::UnaryService<super::CancelQueryRequest>
-                    for ClusterCancelQuerySvc<T> {
+                    impl<T: Search> tonic::server::UnaryService<super::CancelQueryRequest>
+                        for ClusterCancelQuerySvc<T>
+                    {
                         type Response = super::CancelQueryResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::CancelQueryRequest>,
@@ -1514,18 +1405,14 @@ pub mod search_server {
                     };
                     Box::pin(fut)
                 }
-                _ => {
-                    Box::pin(async move {
-                        Ok(
-                            http::Response::builder()
-                                .status(200)
-                                .header("grpc-status", "12")
-                                .header("content-type", "application/grpc")
-                                .body(empty_body())
-                                .unwrap(),
-                        )
-                    })
-                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
             }
         }
     }
@@ -1583,6 +1470,50 @@ pub struct IngestionResponse {
     #[prost(string, tag = "2")]
     pub message: ::prost::alloc::string::String,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IngestionStreamRequest {
+    /// set by the client, increases within a stream
+    #[prost(uint64, tag = "1")]
+    pub seq: u64,
+    #[prost(message, optional, tag = "2")]
+    pub request: ::core::option::Option<IngestionRequest>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IngestionStreamAck {
+    /// the batch processed, all the batches before it are processed too
+    #[prost(uint64, tag = "1")]
+    pub seq: u64,
+    /// the number of batches accepted and failed since the stream was opened
+    #[prost(uint64, tag = "2")]
+    pub accepted: u64,
+    #[prost(uint64, tag = "3")]
+    pub failed: u64,
+    /// the ingester is overloaded, the client should slow down
+    #[prost(bool, tag = "4")]
+    pub backpressure: bool,
+    /// the error of the last failed batch
+    #[prost(string, tag = "5")]
+    pub message: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MemtableMaxTimestampRequest {
+    #[prost(string, tag = "1")]
+    pub org_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub stream_type: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub stream_name: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MemtableMaxTimestampResponse {
+    /// 0 if the ingester didn't receive data for the stream
+    #[prost(int64, tag = "1")]
+    pub max_ts: i64,
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum IngestionType {
@@ -1624,8 +1555,7 @@ impl IngestionType {
 /// Generated client implementations.
 pub mod ingest_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
+    use tonic::codegen::{http::Uri, *};
     #[derive(Debug, Clone)]
     pub struct IngestClient<T> {
         inner: tonic::client::Grpc<T>,
@@ -1664,14 +1594,13 @@ pub mod ingest_client {
             F: tonic::service::Interceptor,
             T::ResponseBody: Default,
             T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    http::Request<tonic::body::BoxBody>,
+                    Response = http::Response<
+                        <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    >,
                 >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
         {
             IngestClient::new(InterceptedService::new(inner, interceptor))
         }
@@ -1709,23 +1638,64 @@ pub mod ingest_client {
         pub async fn ingest(
             &mut self,
             request: impl tonic::IntoRequest<super::IngestionRequest>,
+        ) -> std::result::Result<tonic::Response<super::IngestionResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/cluster.Ingest/Ingest");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("cluster.Ingest", "Ingest"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// The client pushes the batches continuously, the server ingests them in order and acks
+        /// each one by its seq. After a reconnect the client resends the batches after the last
+        /// acked seq.
+        pub async fn ingest_stream(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::IngestionStreamRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::IngestionResponse>,
+            tonic::Response<tonic::codec::Streaming<super::IngestionStreamAck>>,
             tonic::Status,
         > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static("/cluster.Ingest/Ingest");
+            let path = http::uri::PathAndQuery::from_static("/cluster.Ingest/IngestStream");
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("cluster.Ingest", "IngestStream"));
+            self.inner.streaming(req, path, codec).await
+        }
+        /// The newest timestamp the ingester received for the stream, it's in the memtable or not
+        /// yet in the file list
+        pub async fn memtable_max_timestamp(
+            &mut self,
+            request: impl tonic::IntoRequest<super::MemtableMaxTimestampRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::MemtableMaxTimestampResponse>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/cluster.Ingest/MemtableMaxTimestamp");
             let mut req = request.into_request();
-            req.extensions_mut().insert(GrpcMethod::new("cluster.Ingest", "Ingest"));
+            req.extensions_mut()
+                .insert(GrpcMethod::new("cluster.Ingest", "MemtableMaxTimestamp"));
             self.inner.unary(req, path, codec).await
         }
     }
@@ -1734,14 +1704,33 @@ pub mod ingest_client {
 pub mod ingest_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with IngestServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with
+    /// IngestServer.
     #[async_trait]
     pub trait Ingest: Send + Sync + 'static {
         async fn ingest(
             &self,
             request: tonic::Request<super::IngestionRequest>,
+        ) -> std::result::Result<tonic::Response<super::IngestionResponse>, tonic::Status>;
+        /// Server streaming response type for the IngestStream method.
+        type IngestStreamStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::IngestionStreamAck, tonic::Status>,
+            > + Send
+            + 'static;
+        /// The client pushes the batches continuously, the server ingests them in order and acks
+        /// each one by its seq. After a reconnect the client resends the batches after the last
+        /// acked seq.
+        async fn ingest_stream(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::IngestionStreamRequest>>,
+        ) -> std::result::Result<tonic::Response<Self::IngestStreamStream>, tonic::Status>;
+        /// The newest timestamp the ingester received for the stream, it's in the memtable or not
+        /// yet in the file list
+        async fn memtable_max_timestamp(
+            &self,
+            request: tonic::Request<super::MemtableMaxTimestampRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::IngestionResponse>,
+            tonic::Response<super::MemtableMaxTimestampResponse>,
             tonic::Status,
         >;
     }
@@ -1768,10 +1757,7 @@ pub mod ingest_server {
                 max_encoding_message_size: None,
             }
         }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> InterceptedService<Self, F>
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
         where
             F: tonic::service::Interceptor,
         {
@@ -1827,21 +1813,15 @@ pub mod ingest_server {
                 "/cluster.Ingest/Ingest" => {
                     #[allow(non_camel_case_types)]
                     struct IngestSvc<T: Ingest>(pub Arc<T>);
-                    impl<T: Ingest> tonic::server::UnaryService<super::IngestionRequest>
-                    for IngestSvc<T> {
+                    impl<T: Ingest> tonic::server::UnaryService<super::IngestionRequest> for IngestSvc<T> {
                         type Response = super::IngestionResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::IngestionRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                <T as Ingest>::ingest(&inner, request).await
-                            };
+                            let fut = async move { <T as Ingest>::ingest(&inner, request).await };
                             Box::pin(fut)
                         }
                     }
@@ -1868,18 +1848,101 @@ pub mod ingest_server {
                     };
                     Box::pin(fut)
                 }
-                _ => {
-                    Box::pin(async move {
-                        Ok(
-                            http::Response::builder()
-                                .status(200)
-                                .header("grpc-status", "12")
-                                .header("content-type", "application/grpc")
-                                .body(empty_body())
-                                .unwrap(),
-                        )
-                    })
+                "/cluster.Ingest/IngestStream" => {
+                    #[allow(non_camel_case_types)]
+                    struct IngestStreamSvc<T: Ingest>(pub Arc<T>);
+                    impl<T: Ingest> tonic::server::StreamingService<super::IngestionStreamRequest>
+                        for IngestStreamSvc<T>
+                    {
+                        type Response = super::IngestionStreamAck;
+                        type ResponseStream = T::IngestStreamStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                tonic::Streaming<super::IngestionStreamRequest>,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Ingest>::ingest_stream(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = IngestStreamSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/cluster.Ingest/MemtableMaxTimestamp" => {
+                    #[allow(non_camel_case_types)]
+                    struct MemtableMaxTimestampSvc<T: Ingest>(pub Arc<T>);
+                    impl<T: Ingest> tonic::server::UnaryService<super::MemtableMaxTimestampRequest>
+                        for MemtableMaxTimestampSvc<T>
+                    {
+                        type Response = super::MemtableMaxTimestampResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::MemtableMaxTimestampRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Ingest>::memtable_max_timestamp(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = MemtableMaxTimestampSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
                 }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
             }
         }
     }
@@ -1934,8 +1997,7 @@ pub struct UsageResponse {
 /// Generated client implementations.
 pub mod usage_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
+    use tonic::codegen::{http::Uri, *};
     #[derive(Debug, Clone)]
     pub struct UsageClient<T> {
         inner: tonic::client::Grpc<T>,
@@ -1974,14 +2036,13 @@ pub mod usage_client {
             F: tonic::service::Interceptor,
             T::ResponseBody: Default,
             T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    http::Request<tonic::body::BoxBody>,
+                    Response = http::Response<
+                        <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    >,
                 >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
         {
             UsageClient::new(InterceptedService::new(inner, interceptor))
         }
@@ -2020,21 +2081,17 @@ pub mod usage_client {
             &mut self,
             request: impl tonic::IntoRequest<super::UsageRequest>,
         ) -> std::result::Result<tonic::Response<super::UsageResponse>, tonic::Status> {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.Usage/ReportUsage",
-            );
+            let path = http::uri::PathAndQuery::from_static("/cluster.Usage/ReportUsage");
             let mut req = request.into_request();
-            req.extensions_mut().insert(GrpcMethod::new("cluster.Usage", "ReportUsage"));
+            req.extensions_mut()
+                .insert(GrpcMethod::new("cluster.Usage", "ReportUsage"));
             self.inner.unary(req, path, codec).await
         }
     }
@@ -2074,10 +2131,7 @@ pub mod usage_server {
                 max_encoding_message_size: None,
             }
         }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> InterceptedService<Self, F>
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
         where
             F: tonic::service::Interceptor,
         {
@@ -2133,21 +2187,16 @@ pub mod usage_server {
                 "/cluster.Usage/ReportUsage" => {
                     #[allow(non_camel_case_types)]
                     struct ReportUsageSvc<T: Usage>(pub Arc<T>);
-                    impl<T: Usage> tonic::server::UnaryService<super::UsageRequest>
-                    for ReportUsageSvc<T> {
+                    impl<T: Usage> tonic::server::UnaryService<super::UsageRequest> for ReportUsageSvc<T> {
                         type Response = super::UsageResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::UsageRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                <T as Usage>::report_usage(&inner, request).await
-                            };
+                            let fut =
+                                async move { <T as Usage>::report_usage(&inner, request).await };
                             Box::pin(fut)
                         }
                     }
@@ -2174,18 +2223,14 @@ pub mod usage_server {
                     };
                     Box::pin(fut)
                 }
-                _ => {
-                    Box::pin(async move {
-                        Ok(
-                            http::Response::builder()
-                                .status(200)
-                                .header("grpc-status", "12")
-                                .header("content-type", "application/grpc")
-                                .body(empty_body())
-                                .unwrap(),
-                        )
-                    })
-                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
             }
         }
     }
@@ -2296,8 +2341,7 @@ pub struct DeleteResultCacheResponse {
 /// Generated client implementations.
 pub mod query_cache_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
+    use tonic::codegen::{http::Uri, *};
     #[derive(Debug, Clone)]
     pub struct QueryCacheClient<T> {
         inner: tonic::client::Grpc<T>,
@@ -2336,14 +2380,13 @@ pub mod query_cache_client {
             F: tonic::service::Interceptor,
             T::ResponseBody: Default,
             T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    http::Request<tonic::body::BoxBody>,
+                    Response = http::Response<
+                        <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    >,
                 >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
         {
             QueryCacheClient::new(InterceptedService::new(inner, interceptor))
         }
@@ -2381,23 +2424,16 @@ pub mod query_cache_client {
         pub async fn get_cached_result(
             &mut self,
             request: impl tonic::IntoRequest<super::QueryCacheRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::QueryCacheResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::QueryCacheResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.QueryCache/GetCachedResult",
-            );
+            let path = http::uri::PathAndQuery::from_static("/cluster.QueryCache/GetCachedResult");
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(GrpcMethod::new("cluster.QueryCache", "GetCachedResult"));
@@ -2406,50 +2442,38 @@ pub mod query_cache_client {
         pub async fn get_multiple_cached_result(
             &mut self,
             request: impl tonic::IntoRequest<super::QueryCacheRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::MultiQueryCacheResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::MultiQueryCacheResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.QueryCache/GetMultipleCachedResult",
-            );
+            let path =
+                http::uri::PathAndQuery::from_static("/cluster.QueryCache/GetMultipleCachedResult");
             let mut req = request.into_request();
-            req.extensions_mut()
-                .insert(
-                    GrpcMethod::new("cluster.QueryCache", "GetMultipleCachedResult"),
-                );
+            req.extensions_mut().insert(GrpcMethod::new(
+                "cluster.QueryCache",
+                "GetMultipleCachedResult",
+            ));
             self.inner.unary(req, path, codec).await
         }
         pub async fn delete_result_cache(
             &mut self,
             request: impl tonic::IntoRequest<super::DeleteResultCacheRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::DeleteResultCacheResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
+        ) -> std::result::Result<tonic::Response<super::DeleteResultCacheResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
             let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/cluster.QueryCache/DeleteResultCache",
-            );
+            let path =
+                http::uri::PathAndQuery::from_static("/cluster.QueryCache/DeleteResultCache");
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(GrpcMethod::new("cluster.QueryCache", "DeleteResultCache"));
@@ -2461,30 +2485,22 @@ pub mod query_cache_client {
 pub mod query_cache_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with QueryCacheServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with
+    /// QueryCacheServer.
     #[async_trait]
     pub trait QueryCache: Send + Sync + 'static {
         async fn get_cached_result(
             &self,
             request: tonic::Request<super::QueryCacheRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::QueryCacheResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::QueryCacheResponse>, tonic::Status>;
         async fn get_multiple_cached_result(
             &self,
             request: tonic::Request<super::QueryCacheRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::MultiQueryCacheResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::MultiQueryCacheResponse>, tonic::Status>;
         async fn delete_result_cache(
             &self,
             request: tonic::Request<super::DeleteResultCacheRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::DeleteResultCacheResponse>,
-            tonic::Status,
-        >;
+        ) -> std::result::Result<tonic::Response<super::DeleteResultCacheResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct QueryCacheServer<T: QueryCache> {
@@ -2509,10 +2525,7 @@ pub mod query_cache_server {
                 max_encoding_message_size: None,
             }
         }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> InterceptedService<Self, F>
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
         where
             F: tonic::service::Interceptor,
         {
@@ -2568,15 +2581,11 @@ pub mod query_cache_server {
                 "/cluster.QueryCache/GetCachedResult" => {
                     #[allow(non_camel_case_types)]
                     struct GetCachedResultSvc<T: QueryCache>(pub Arc<T>);
-                    impl<
-                        T: QueryCache,
-                    > tonic::server::UnaryService<super::QueryCacheRequest>
-                    for GetCachedResultSvc<T> {
+                    impl<T: QueryCache> tonic::server::UnaryService<super::QueryCacheRequest>
+                        for GetCachedResultSvc<T>
+                    {
                         type Response = super::QueryCacheResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::QueryCacheRequest>,
@@ -2614,26 +2623,18 @@ pub mod query_cache_server {
                 "/cluster.QueryCache/GetMultipleCachedResult" => {
                     #[allow(non_camel_case_types)]
                     struct GetMultipleCachedResultSvc<T: QueryCache>(pub Arc<T>);
-                    impl<
-                        T: QueryCache,
-                    > tonic::server::UnaryService<super::QueryCacheRequest>
-                    for GetMultipleCachedResultSvc<T> {
+                    impl<T: QueryCache> tonic::server::UnaryService<super::QueryCacheRequest>
+                        for GetMultipleCachedResultSvc<T>
+                    {
                         type Response = super::MultiQueryCacheResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::QueryCacheRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as QueryCache>::get_multiple_cached_result(
-                                        &inner,
-                                        request,
-                                    )
-                                    .await
+                                <T as QueryCache>::get_multiple_cached_result(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -2664,23 +2665,18 @@ pub mod query_cache_server {
                 "/cluster.QueryCache/DeleteResultCache" => {
                     #[allow(non_camel_case_types)]
                     struct DeleteResultCacheSvc<T: QueryCache>(pub Arc<T>);
-                    impl<
-                        T: QueryCache,
-                    > tonic::server::UnaryService<super::DeleteResultCacheRequest>
-                    for DeleteResultCacheSvc<T> {
+                    impl<T: QueryCache> tonic::server::UnaryService<super::DeleteResultCacheRequest>
+                        for DeleteResultCacheSvc<T>
+                    {
                         type Response = super::DeleteResultCacheResponse;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
                             request: tonic::Request<super::DeleteResultCacheRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as QueryCache>::delete_result_cache(&inner, request)
-                                    .await
+                                <T as QueryCache>::delete_result_cache(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -2708,18 +2704,14 @@ pub mod query_cache_server {
                     };
                     Box::pin(fut)
                 }
-                _ => {
-                    Box::pin(async move {
-                        Ok(
-                            http::Response::builder()
-                                .status(200)
-                                .header("grpc-status", "12")
-                                .header("content-type", "application/grpc")
-                                .body(empty_body())
-                                .unwrap(),
-                        )
-                    })
-                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
             }
         }
     }
@@ -2808,6 +2800,8 @@ pub struct FlightSearchRequest {
     pub user_id: ::core::option::Option<::prost::alloc::string::String>,
     #[prost(string, optional, tag = "19")]
     pub search_event_type: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "20")]
+    pub prefix_keys: ::prost::alloc::vec::Vec<KvItem>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -2817,3 +2811,313 @@ pub struct KvItem {
     #[prost(string, tag = "2")]
     pub value: ::prost::alloc::string::String,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AlertEvaluation {
+    /// identifies the run of the trigger, a redelivered evaluation has the same id
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub org_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub stream_type: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub stream_name: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub alert_name: ::prost::alloc::string::String,
+    #[prost(int64, optional, tag = "6")]
+    pub start_time: ::core::option::Option<i64>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EvaluateAlertsRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub evaluations: ::prost::alloc::vec::Vec<AlertEvaluation>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AlertEvaluationResult {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    /// the evaluated rows as a json array, `null` when the condition didn't match
+    #[prost(bytes = "vec", tag = "2")]
+    pub rows: ::prost::alloc::vec::Vec<u8>,
+    #[prost(int64, tag = "3")]
+    pub end_time: i64,
+    #[prost(string, optional, tag = "4")]
+    pub error: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bool, tag = "5")]
+    pub timed_out: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EvaluateAlertsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub results: ::prost::alloc::vec::Vec<AlertEvaluationResult>,
+}
+/// Generated client implementations.
+pub mod alert_evaluator_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::{http::Uri, *};
+    #[derive(Debug, Clone)]
+    pub struct AlertEvaluatorClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl AlertEvaluatorClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> AlertEvaluatorClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> AlertEvaluatorClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                    http::Request<tonic::body::BoxBody>,
+                    Response = http::Response<
+                        <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                    >,
+                >,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
+        {
+            AlertEvaluatorClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        pub async fn evaluate_alerts(
+            &mut self,
+            request: impl tonic::IntoRequest<super::EvaluateAlertsRequest>,
+        ) -> std::result::Result<tonic::Response<super::EvaluateAlertsResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/cluster.AlertEvaluator/EvaluateAlerts");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("cluster.AlertEvaluator", "EvaluateAlerts"));
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod alert_evaluator_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with AlertEvaluatorServer.
+    #[async_trait]
+    pub trait AlertEvaluator: Send + Sync + 'static {
+        async fn evaluate_alerts(
+            &self,
+            request: tonic::Request<super::EvaluateAlertsRequest>,
+        ) -> std::result::Result<tonic::Response<super::EvaluateAlertsResponse>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct AlertEvaluatorServer<T: AlertEvaluator> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: AlertEvaluator> AlertEvaluatorServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for AlertEvaluatorServer<T>
+    where
+        T: AlertEvaluator,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/cluster.AlertEvaluator/EvaluateAlerts" => {
+                    #[allow(non_camel_case_types)]
+                    struct EvaluateAlertsSvc<T: AlertEvaluator>(pub Arc<T>);
+                    impl<T: AlertEvaluator> tonic::server::UnaryService<super::EvaluateAlertsRequest> for EvaluateAlertsSvc<T> {
+                        type Response = super::EvaluateAlertsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EvaluateAlertsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as AlertEvaluator>::evaluate_alerts(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = EvaluateAlertsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: AlertEvaluator> Clone for AlertEvaluatorServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
+        }
+    }
+    impl<T: AlertEvaluator> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(Arc::clone(&self.0))
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: AlertEvaluator> tonic::server::NamedService for AlertEvaluatorServer<T> {
+        const NAME: &'static str = "cluster.AlertEvaluator";
+    }
+}
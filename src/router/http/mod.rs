@@ -13,12 +13,27 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
 use ::config::{
     get_config,
-    meta::cluster::{Role, RoleGroup},
-    utils::rand::get_rand_element,
+    meta::cluster::{Node, Role, RoleGroup},
+    metrics,
+    utils::{rand::get_rand_element, time::now_micros},
+};
+use actix_web::{
+    http::{header::HeaderMap, Error, Method, StatusCode},
+    route,
+    web::{self, Bytes, BytesMut},
+    HttpRequest, HttpResponse,
 };
-use actix_web::{http::Error, route, web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 
 use crate::common::infra::cluster;
 
@@ -45,6 +60,23 @@ const QUERIER_ROUTES: [&str; 18] = [
 
 const FIXED_QUERIER_ROUTES: [&str; 3] = ["/summary", "/schema", "/streams"];
 
+// read-only search routes which are safe to send twice
+const HEDGE_ROUTES: [&str; 5] = [
+    "/_search",
+    "/_around",
+    "/_values",
+    "/prometheus/api/v1/query_range",
+    "/prometheus/api/v1/query",
+];
+
+// number of recent search latencies used to compute the hedge delay
+const HEDGE_LATENCY_WINDOW: usize = 1000;
+const HEDGE_LATENCY_MIN_SAMPLES: usize = 100;
+
+static ROUND_ROBIN_INDEX: AtomicUsize = AtomicUsize::new(0);
+static SEARCH_LATENCIES: Lazy<Mutex<VecDeque<u64>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(HEDGE_LATENCY_WINDOW)));
+
 #[inline]
 fn check_querier_route(path: &str) -> bool {
     QUERIER_ROUTES.iter().any(|x| path.contains(x))
@@ -55,6 +87,12 @@ fn is_fixed_querier_route(path: &str) -> bool {
     FIXED_QUERIER_ROUTES.iter().any(|x| path.contains(x))
 }
 
+#[inline]
+fn is_hedge_route(method: &Method, path: &str) -> bool {
+    (method == Method::GET || method == Method::POST)
+        && HEDGE_ROUTES.iter().any(|x| path.contains(x))
+}
+
 #[route(
     "/config",
     method = "GET",
@@ -159,6 +197,9 @@ async fn dispatch(
 
     // send query
     let cfg = get_config();
+    if cfg.route.hedge_enabled && is_hedge_route(req.method(), path) {
+        return dispatch_with_hedge(req, payload, client, path, new_url.value).await;
+    }
     let resp = if cfg.route.connection_pool_disabled {
         let client = awc::Client::builder()
             .timeout(std::time::Duration::from_secs(cfg.route.timeout))
@@ -211,6 +252,146 @@ async fn dispatch(
     Ok(new_resp.body(body))
 }
 
+/// Sends the search to one querier, and if it doesn't respond within the hedge
+/// delay sends the same request to a second querier, the slower one is cancelled.
+async fn dispatch_with_hedge(
+    req: HttpRequest,
+    mut payload: web::Payload,
+    client: web::Data<awc::Client>,
+    path: &str,
+    url: String,
+) -> actix_web::Result<HttpResponse, Error> {
+    let start = std::time::Instant::now();
+
+    // buffer the body so that it can be sent twice
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        match chunk {
+            Ok(chunk) => body.extend_from_slice(&chunk),
+            Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string())),
+        }
+    }
+    let body = body.freeze();
+
+    let primary = forward(&client, &req, &url, body.clone());
+    tokio::pin!(primary);
+    let resp = tokio::select! {
+        resp = &mut primary => resp,
+        _ = tokio::time::sleep(get_hedge_delay()) => match get_hedge_url(path, &url).await {
+            None => primary.await,
+            Some(hedge_url) => {
+                let hedge = forward(&client, &req, &hedge_url, body);
+                tokio::pin!(hedge);
+                tokio::select! {
+                    resp = &mut primary => {
+                        metrics::QUERY_HEDGED_NUMS
+                            .with_label_values(&["primary"])
+                            .inc();
+                        resp
+                    }
+                    resp = &mut hedge => {
+                        metrics::QUERY_HEDGED_NUMS
+                            .with_label_values(&["hedge"])
+                            .inc();
+                        resp
+                    }
+                }
+            }
+        },
+    };
+
+    match resp {
+        Ok((status, headers, body)) => {
+            record_search_latency(start.elapsed());
+            let mut new_resp = HttpResponse::build(status);
+            for (key, value) in headers.iter() {
+                if !key.eq("content-encoding") {
+                    new_resp.insert_header((key.clone(), value.clone()));
+                }
+            }
+            Ok(new_resp.body(body))
+        }
+        Err(e) => {
+            log::error!(
+                "dispatch: {}, error: {}, took: {} ms",
+                url,
+                e,
+                start.elapsed().as_millis()
+            );
+            Ok(HttpResponse::ServiceUnavailable().body(e))
+        }
+    }
+}
+
+async fn forward(
+    client: &awc::Client,
+    req: &HttpRequest,
+    url: &str,
+    body: Bytes,
+) -> Result<(StatusCode, HeaderMap, Bytes), String> {
+    let cfg = get_config();
+    let resp = if cfg.route.connection_pool_disabled {
+        let client = awc::Client::builder()
+            .timeout(Duration::from_secs(cfg.route.timeout))
+            .disable_redirects()
+            .finish();
+        client
+            .request_from(url, req.head())
+            .insert_header((awc::http::header::CONNECTION, "close"))
+            .send_body(body)
+            .await
+    } else {
+        client.request_from(url, req.head()).send_body(body).await
+    };
+    let mut resp = resp.map_err(|e| e.to_string())?;
+    let body = resp
+        .body()
+        .limit(cfg.limit.req_payload_limit)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((resp.status(), resp.headers().clone(), body))
+}
+
+/// Returns a querier other than the one serving `url` to send the hedged request to
+async fn get_hedge_url(path: &str, url: &str) -> Option<String> {
+    let nodes = cluster::get_cached_online_querier_nodes(Some(RoleGroup::Interactive))
+        .await?
+        .into_iter()
+        .filter(|node| format!("{}{}", node.http_addr, path) != url)
+        .collect::<Vec<_>>();
+    if nodes.is_empty() {
+        return None;
+    }
+    let node = select_querier(&nodes, &get_config().route.strategy, now_micros());
+    Some(format!("{}{}", node.http_addr, path))
+}
+
+/// The hedge delay is the configured percentile of recent search latencies, and
+/// at least `hedge_min_delay`
+fn get_hedge_delay() -> Duration {
+    let cfg = get_config();
+    let latencies = SEARCH_LATENCIES.lock().iter().copied().collect::<Vec<_>>();
+    let delay = hedge_delay_from_latencies(latencies, cfg.route.hedge_percentile);
+    Duration::from_millis(delay.max(cfg.route.hedge_min_delay))
+}
+
+fn hedge_delay_from_latencies(mut latencies: Vec<u64>, percentile: usize) -> u64 {
+    if latencies.len() < HEDGE_LATENCY_MIN_SAMPLES {
+        return 0;
+    }
+    latencies.sort_unstable();
+    let idx = (latencies.len() * percentile / 100).min(latencies.len() - 1);
+    latencies[idx]
+}
+
+fn record_search_latency(took: Duration) {
+    let mut latencies = SEARCH_LATENCIES.lock();
+    if latencies.len() >= HEDGE_LATENCY_WINDOW {
+        latencies.pop_front();
+    }
+    latencies.push_back(took.as_millis() as u64);
+}
+
 async fn get_url(path: &str) -> URLDetails {
     let node_type;
     let is_querier_path = check_querier_route(path);
@@ -225,7 +406,7 @@ async fn get_url(path: &str) -> URLDetails {
         }
     } else {
         node_type = Role::Ingester;
-        cluster::get_cached_online_ingester_nodes().await
+        cluster::get_cached_schedulable_ingester_nodes().await
     };
     if nodes.is_none() || nodes.as_ref().unwrap().is_empty() {
         let cfg = get_config();
@@ -245,13 +426,32 @@ async fn get_url(path: &str) -> URLDetails {
     }
 
     let nodes = nodes.unwrap();
-    let node = get_rand_element(&nodes);
+    let node = if node_type == Role::Querier {
+        select_querier(&nodes, &get_config().route.strategy, now_micros())
+    } else {
+        get_rand_element(&nodes)
+    };
     URLDetails {
         is_error: false,
         value: format!("{}{}", node.http_addr, path),
     }
 }
 
+/// Picks a querier with the given strategy, `least_loaded` falls back to
+/// `round_robin` when the load of any node is stale.
+fn select_querier<'a>(nodes: &'a [Node], strategy: &str, now: i64) -> &'a Node {
+    match strategy {
+        "least_loaded" if !nodes.iter().any(|n| n.load.is_stale(now)) => nodes
+            .iter()
+            .min_by_key(|n| n.load.running_queries * 1000 / n.cpu_num.max(1))
+            .unwrap(),
+        "least_loaded" | "round_robin" => {
+            &nodes[ROUND_ROBIN_INDEX.fetch_add(1, Ordering::Relaxed) % nodes.len()]
+        }
+        _ => get_rand_element(nodes),
+    }
+}
+
 struct URLDetails {
     is_error: bool,
     value: String,
@@ -267,4 +467,42 @@ mod tests {
         assert!(check_querier_route("/api/_around"));
         assert!(!check_querier_route("/api/_bulk"));
     }
+
+    #[test]
+    fn test_is_hedge_route() {
+        assert!(is_hedge_route(&Method::POST, "/api/default/_search"));
+        assert!(is_hedge_route(&Method::GET, "/api/default/_values"));
+        assert!(!is_hedge_route(&Method::DELETE, "/api/default/_search"));
+        assert!(!is_hedge_route(&Method::POST, "/api/default/default/_json"));
+    }
+
+    #[test]
+    fn test_hedge_delay_from_latencies() {
+        assert_eq!(hedge_delay_from_latencies(vec![10; 10], 95), 0);
+        let latencies = (1..=HEDGE_LATENCY_MIN_SAMPLES as u64).rev().collect();
+        assert_eq!(hedge_delay_from_latencies(latencies, 95), 96);
+    }
+
+    #[test]
+    fn test_select_querier() {
+        let now = now_micros();
+        let nodes = (0..3)
+            .map(|i| Node {
+                name: format!("node-{i}"),
+                cpu_num: 4,
+                load: ::config::meta::cluster::NodeLoad {
+                    running_queries: [8, 2, 4][i],
+                    updated_at: now,
+                },
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(select_querier(&nodes, "least_loaded", now).name, "node-1");
+
+        // stale load falls back to round robin
+        let now = now + 3600 * 1_000_000;
+        let first = select_querier(&nodes, "least_loaded", now).name.clone();
+        let second = select_querier(&nodes, "round_robin", now).name.clone();
+        assert_ne!(first, second);
+    }
 }
@@ -26,6 +26,7 @@ use config::{
     utils::{
         base64,
         json::{Map, Value},
+        time::parse_milliseconds,
     },
     SMTP_CLIENT,
 };
@@ -38,7 +39,7 @@ use crate::{
             alerts::{
                 alert::{Alert, AlertListFilter},
                 destinations::{DestinationType, DestinationWithTemplate, HTTPType},
-                FrequencyType, Operator, QueryType,
+                AggFunction, FrequencyType, Operator, QueryType, PROMQL_STEP_AUTO,
             },
             authz::Authz,
         },
@@ -48,7 +49,7 @@ use crate::{
         alerts::{build_sql, destinations},
         db,
         search::sql::RE_ONLY_SELECT,
-        short_url,
+        secrets, short_url,
     },
 };
 
@@ -71,6 +72,21 @@ pub async fn save(
         ));
     }
     alert.org_id = org_id.to_string();
+    if alert
+        .source_org_id
+        .as_ref()
+        .is_some_and(|v| v.trim().is_empty() || v.trim() == org_id)
+    {
+        alert.source_org_id = None;
+    }
+    if let Some(source_org_id) = alert.source_org_id.as_mut() {
+        *source_org_id = source_org_id.trim().to_string();
+        if alert.is_real_time {
+            return Err(anyhow::anyhow!(
+                "Realtime alert can not query a different organization"
+            ));
+        }
+    }
     let stream_type = alert.stream_type;
     alert.stream_name = stream_name.to_string();
     alert.row_template = alert.row_template.trim().to_string();
@@ -120,6 +136,11 @@ pub async fn save(
     if alert.name.contains('/') {
         return Err(anyhow::anyhow!("Alert name cannot contain '/'"));
     }
+    if alert.evaluation_timeout_secs < 0 {
+        return Err(anyhow::anyhow!(
+            "Alert evaluation timeout should not be negative"
+        ));
+    }
 
     if let Some(vrl) = alert.query_condition.vrl_function.as_ref() {
         match base64::decode_url(vrl) {
@@ -165,16 +186,52 @@ pub async fn save(
     }
 
     // before saving alert check column type to decide numeric condition
-    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    let source_org_id = alert.source_org_id.as_deref().unwrap_or(org_id);
+    let schema = infra::schema::get(source_org_id, stream_name, stream_type).await?;
     if stream_name.is_empty() || schema.fields().is_empty() {
         return Err(anyhow::anyhow!("Stream {stream_name} not found"));
     }
 
+    if let Some(dedup) = alert.deduplication.as_mut() {
+        if alert.is_real_time {
+            return Err(anyhow::anyhow!(
+                "Realtime alert does not support deduplication"
+            ));
+        }
+        dedup.fields = dedup
+            .fields
+            .iter()
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect();
+        if dedup.fields.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Alert deduplication should have at least one field"
+            ));
+        }
+        if dedup.time_window_minutes <= 0 {
+            return Err(anyhow::anyhow!(
+                "Alert deduplication time window should be greater than 0"
+            ));
+        }
+    }
+
     if alert.is_real_time && alert.query_condition.query_type != QueryType::Custom {
         return Err(anyhow::anyhow!(
             "Realtime alert should use Custom query type"
         ));
     }
+    if alert.is_real_time
+        && alert
+            .query_condition
+            .aggregation
+            .as_ref()
+            .is_some_and(|agg| agg.function == AggFunction::CountDistinct)
+    {
+        return Err(anyhow::anyhow!(
+            "Realtime alert does not support the count_distinct aggregation"
+        ));
+    }
 
     match alert.query_condition.query_type {
         QueryType::Custom => {
@@ -207,6 +264,13 @@ pub async fn save(
                     "Alert with PromQL mode should have a query"
                 ));
             }
+            if let Some(step) = alert.query_condition.promql_step.as_deref() {
+                if step != PROMQL_STEP_AUTO && !parse_milliseconds(step).is_ok_and(|v| v > 0) {
+                    return Err(anyhow::anyhow!(
+                        "Alert PromQL step should be a duration like 30s or {PROMQL_STEP_AUTO}"
+                    ));
+                }
+            }
         }
     }
 
@@ -354,16 +418,31 @@ impl Alert {
         if self.is_real_time {
             self.query_condition.evaluate_realtime(row).await
         } else {
-            self.query_condition
-                .evaluate_scheduled(
-                    &self.get_stream_params(),
-                    &self.trigger_condition,
-                    start_time,
-                )
-                .await
+            let timeout = self.get_evaluation_timeout();
+            let evaluation = self.query_condition.evaluate_scheduled(
+                &self.get_stream_params(),
+                &self.trigger_condition,
+                start_time,
+                None,
+                timeout,
+            );
+            if timeout > 0 {
+                evaluate_with_timeout(evaluation, std::time::Duration::from_secs(timeout as u64))
+                    .await
+            } else {
+                evaluation.await
+            }
         }
     }
 
+    /// Returns the seconds a scheduled evaluation can run, 0 means no limit
+    pub fn get_evaluation_timeout(&self) -> i64 {
+        evaluation_timeout(
+            self.evaluation_timeout_secs,
+            get_config().limit.alert_evaluation_timeout,
+        )
+    }
+
     /// Returns a tuple containing a boolean - if all the send notification jobs succeeded
     /// and the error message if any
     pub async fn send_notification(
@@ -407,9 +486,14 @@ impl Alert {
         }
     }
 
+    /// Returns the params of the queried stream, which belongs to `source_org_id` if set
     pub fn get_stream_params(&self) -> StreamParams {
         StreamParams {
-            org_id: self.org_id.clone().into(),
+            org_id: self
+                .source_org_id
+                .clone()
+                .unwrap_or_else(|| self.org_id.clone())
+                .into(),
             stream_name: self.stream_name.clone().into(),
             stream_type: self.stream_type,
         }
@@ -453,13 +537,14 @@ pub async fn send_notification(
     };
 
     match dest.destination_type {
-        DestinationType::Http => send_http_notification(dest, msg.clone()).await,
+        DestinationType::Http => send_http_notification(&alert.org_id, dest, msg.clone()).await,
         DestinationType::Email => send_email_notification(&email_subject, dest, msg).await,
         DestinationType::Sns => send_sns_notification(&alert.name, dest, msg).await,
     }
 }
 
 pub async fn send_http_notification(
+    org_id: &str,
     dest: &DestinationWithTemplate,
     msg: String,
 ) -> Result<String, anyhow::Error> {
@@ -485,6 +570,9 @@ pub async fn send_http_notification(
                 if key.to_lowercase().trim() == "content-type" {
                     has_context_type = true;
                 }
+                let value = secrets::resolve(org_id, value)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("header {key}: {e}"))?;
                 req = req.header(key, value);
             }
         }
@@ -493,6 +581,15 @@ pub async fn send_http_notification(
     if !has_context_type {
         req = req.header("Content-type", "application/json");
     }
+    if let Some(secret) = dest.signing_secret.as_ref() {
+        let secret = secrets::resolve(org_id, secret)
+            .await
+            .map_err(|e| anyhow::anyhow!("signing secret: {e}"))?;
+        req = req.header(
+            destinations::SIGNATURE_HEADER,
+            destinations::sign_webhook(&secret, Utc::now().timestamp(), &msg),
+        );
+    }
 
     let resp = req.body(msg.clone()).send().await?;
     let resp_status = resp.status();
@@ -774,6 +871,8 @@ async fn process_dest_template(
                 .replace('+', "%2B")
         )
     };
+    // the queried stream is only visible in the source organization
+    let source_org_id = alert.source_org_id.as_deref().unwrap_or(&alert.org_id);
     let alert_url = if alert.query_condition.query_type == QueryType::PromQL {
         if let Some(promql) = &alert.query_condition.promql {
             let condition = alert.query_condition.promql_condition.as_ref().unwrap();
@@ -798,7 +897,7 @@ async fn process_dest_template(
             alert_start_time,
             alert_end_time,
             base64::encode_url(&alert_query).replace('+', "%2B"),
-            alert.org_id,
+            source_org_id,
             function_content,
         )
     } else {
@@ -834,7 +933,7 @@ async fn process_dest_template(
             alert_start_time,
             alert_end_time,
             base64::encode_url(&alert_query),
-            alert.org_id,
+            source_org_id,
             function_content,
         )
     };
@@ -1070,6 +1169,38 @@ impl<'a> VarValue<'a> {
     }
 }
 
+/// The error of a scheduled evaluation which ran over its timeout
+#[derive(Debug, thiserror::Error)]
+#[error("Alert evaluation timed out after {0} seconds")]
+pub struct EvaluationTimeout(pub u64);
+
+/// The alert's own timeout capped by the global one, a timeout of 0 means no limit
+fn evaluation_timeout(alert_timeout: i64, max_timeout: i64) -> i64 {
+    if alert_timeout > 0 && max_timeout > 0 {
+        alert_timeout.min(max_timeout)
+    } else if alert_timeout > 0 {
+        alert_timeout
+    } else {
+        max_timeout.max(0)
+    }
+}
+
+/// Aborts the evaluation after the timeout. The search of the evaluation has the same timeout
+/// and can fail on its own first, its error is reported as a timeout too.
+pub(crate) async fn evaluate_with_timeout<T>(
+    evaluation: impl std::future::Future<Output = Result<T, anyhow::Error>>,
+    timeout: std::time::Duration,
+) -> Result<T, anyhow::Error> {
+    let start = std::time::Instant::now();
+    match tokio::time::timeout(timeout, evaluation).await {
+        Ok(Err(_)) if start.elapsed() >= timeout => {
+            Err(EvaluationTimeout(timeout.as_secs()).into())
+        }
+        Ok(ret) => ret,
+        Err(_) => Err(EvaluationTimeout(timeout.as_secs()).into()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1087,4 +1218,52 @@ mod tests {
         // alert name should not contain /
         assert!(ret.is_err());
     }
+    #[test]
+    fn test_alert_stream_params() {
+        let mut alert = Alert {
+            org_id: "central".to_string(),
+            stream_name: "default".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(alert.get_stream_params().org_id, "central");
+        alert.source_org_id = Some("tenant".to_string());
+        assert_eq!(alert.get_stream_params().org_id, "tenant");
+    }
+
+    #[test]
+    fn test_evaluation_timeout() {
+        assert_eq!(evaluation_timeout(0, 60), 60);
+        assert_eq!(evaluation_timeout(30, 60), 30);
+        assert_eq!(evaluation_timeout(120, 60), 60);
+        assert_eq!(evaluation_timeout(120, 0), 120);
+        assert_eq!(evaluation_timeout(0, 0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_with_timeout() {
+        let timeout = std::time::Duration::from_millis(50);
+        let ret = evaluate_with_timeout(async { Ok(1) }, timeout).await;
+        assert_eq!(ret.unwrap(), 1);
+
+        let ret: Result<i32, _> = evaluate_with_timeout(
+            async {
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                Ok(1)
+            },
+            timeout,
+        )
+        .await;
+        assert!(ret.unwrap_err().is::<EvaluationTimeout>());
+
+        // the search failing at the timeout is a timeout too
+        let ret: Result<i32, _> = evaluate_with_timeout(
+            async {
+                tokio::time::sleep(timeout).await;
+                Err(anyhow::anyhow!("search timeout"))
+            },
+            timeout,
+        )
+        .await;
+        assert!(ret.unwrap_err().is::<EvaluationTimeout>());
+    }
 }
@@ -0,0 +1,111 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::Duration;
+use config::utils::json::{Map, Value};
+use hashbrown::HashMap;
+
+use crate::common::meta::alerts::DeduplicationConfig;
+
+/// Returns the dedup key of a row, the missing fields are treated as null
+pub fn dedup_key(fields: &[String], row: &Map<String, Value>) -> String {
+    let values = fields
+        .iter()
+        .map(|field| row.get(field).cloned().unwrap_or(Value::Null))
+        .collect::<Vec<_>>();
+    Value::Array(values).to_string()
+}
+
+/// Keeps the first row of every key not yet seen within the dedup window.
+/// `seen` holds the keys and the time they were first notified, it is updated
+/// with the keys of the kept rows and the expired keys are removed.
+pub fn dedup_rows(
+    config: &DeduplicationConfig,
+    rows: Vec<Map<String, Value>>,
+    seen: &mut HashMap<String, i64>,
+    now: i64,
+) -> Vec<Map<String, Value>> {
+    if config.fields.is_empty() {
+        return rows;
+    }
+    let window = Duration::try_minutes(config.time_window_minutes)
+        .unwrap_or_default()
+        .num_microseconds()
+        .unwrap_or_default();
+    seen.retain(|_, first_seen| now - *first_seen < window);
+    rows.into_iter()
+        .filter(|row| {
+            let key = dedup_key(&config.fields, row);
+            if seen.contains_key(&key) {
+                return false;
+            }
+            seen.insert(key, now);
+            true
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    fn row(host: &str, service: &str, msg: &str) -> Map<String, Value> {
+        json::json!({"host": host, "service": service, "message": msg})
+            .as_object()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_dedup_rows() {
+        let config = DeduplicationConfig {
+            fields: vec!["host".to_string(), "service".to_string()],
+            time_window_minutes: 10,
+        };
+        let minute = 60_000_000;
+        let rows = vec![
+            row("a", "api", "1"),
+            row("a", "api", "2"),
+            row("a", "web", "3"),
+            row("b", "api", "4"),
+            row("b", "api", "5"),
+        ];
+        let mut seen = HashMap::new();
+
+        // exactly one row per key
+        let ret = dedup_rows(&config, rows.clone(), &mut seen, 0);
+        let messages = ret
+            .iter()
+            .map(|r| r["message"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["1", "3", "4"]);
+        assert_eq!(seen.len(), 3);
+
+        // the keys are suppressed within the window
+        let ret = dedup_rows(&config, rows.clone(), &mut seen, 5 * minute);
+        assert!(ret.is_empty());
+
+        // a new key is still notified within the window
+        let ret = dedup_rows(&config, vec![row("c", "api", "6")], &mut seen, 5 * minute);
+        assert_eq!(ret.len(), 1);
+
+        // the keys are notified again after the window
+        let ret = dedup_rows(&config, rows, &mut seen, 10 * minute);
+        assert_eq!(ret.len(), 3);
+        assert_eq!(seen.len(), 4);
+    }
+}
@@ -27,7 +27,10 @@ use crate::{
     common::{
         meta::{
             // authz::Authz,
-            alerts::{derived_streams::DerivedStreamMeta, FrequencyType, QueryType},
+            alerts::{
+                derived_streams::{DerivedStreamMeta, TimestampAlignment},
+                FrequencyType, QueryType,
+            },
         },
         // utils::auth::{remove_ownership, set_ownership},
     },
@@ -187,8 +190,65 @@ impl DerivedStreamMeta {
             self.query_condition.evaluate_realtime(row).await
         } else {
             self.query_condition
-                .evaluate_scheduled(&self.source, &self.trigger_condition, start_time)
+                .evaluate_scheduled(&self.source, &self.trigger_condition, start_time, None, 0)
                 .await
         }
     }
+
+    /// Sets the `_timestamp` of a derived row according to `timestamp_alignment`, given the
+    /// evaluated period `[start_time, end_time]` of the source stream.
+    pub fn align_timestamp(&self, row: &mut Map<String, Value>, start_time: i64, end_time: i64) {
+        let timestamp = match self.timestamp_alignment {
+            TimestampAlignment::Ingestion => return,
+            TimestampAlignment::WindowStart => row
+                .get("zo_sql_min_time")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(start_time),
+            TimestampAlignment::WindowEnd => row
+                .get("zo_sql_max_time")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(end_time),
+        };
+        row.insert(
+            get_config().common.column_timestamp.clone(),
+            Value::from(timestamp),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    #[test]
+    fn test_align_timestamp() {
+        let mut derived_stream = DerivedStreamMeta::default();
+        let row =
+            json::json!({"alert_agg_value": 10, "zo_sql_min_time": 150, "zo_sql_max_time": 180});
+        let row = row.as_object().unwrap();
+
+        let mut aligned = row.clone();
+        derived_stream.align_timestamp(&mut aligned, 100, 200);
+        assert!(!aligned.contains_key("_timestamp"));
+
+        derived_stream.timestamp_alignment = TimestampAlignment::WindowStart;
+        let mut aligned = row.clone();
+        derived_stream.align_timestamp(&mut aligned, 100, 200);
+        assert_eq!(aligned["_timestamp"], json::json!(150));
+
+        derived_stream.timestamp_alignment = TimestampAlignment::WindowEnd;
+        let mut aligned = row.clone();
+        derived_stream.align_timestamp(&mut aligned, 100, 200);
+        assert_eq!(aligned["_timestamp"], json::json!(180));
+
+        // falls back to the evaluated period when the query has no min/max time
+        let mut aligned = json::json!({"alert_agg_value": 10})
+            .as_object()
+            .unwrap()
+            .clone();
+        derived_stream.align_timestamp(&mut aligned, 100, 200);
+        assert_eq!(aligned["_timestamp"], json::json!(200));
+    }
 }
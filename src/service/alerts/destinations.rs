@@ -14,17 +14,24 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use actix_web::http;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 
 use crate::{
     common::{
         infra::config::STREAM_ALERTS,
         meta::{
-            alerts::destinations::{Destination, DestinationType, DestinationWithTemplate},
+            alerts::destinations::{
+                Destination, DestinationType, DestinationWithTemplate, REDACTED_SECRET,
+            },
             authz::Authz,
         },
         utils::auth::{is_ofga_unsupported, remove_ownership, set_ownership},
     },
-    service::db::{self, user},
+    service::{
+        db::{self, user},
+        secrets,
+    },
 };
 
 pub async fn save(
@@ -85,6 +92,41 @@ pub async fn save(
             }
         }
     }
+    // the redacted secret of a response sent back keeps the saved secret
+    if destination.signing_secret.as_deref() == Some(REDACTED_SECRET) {
+        if create {
+            return Err((
+                http::StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Signing secret can't be {REDACTED_SECRET}"),
+            ));
+        }
+        destination.signing_secret = db::alerts::destinations::get(org_id, name)
+            .await
+            .ok()
+            .and_then(|v| v.signing_secret);
+    }
+    if let Some(secret) = destination.signing_secret.as_ref() {
+        if destination.destination_type != DestinationType::Http {
+            return Err((
+                http::StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Signing secret is only supported for http destinations"),
+            ));
+        }
+        if secret.trim().is_empty() {
+            destination.signing_secret = None;
+        }
+    }
+    // the credentials can be references to a secret backend, resolved when a notification is sent
+    let credentials = destination
+        .headers
+        .iter()
+        .flat_map(|headers| headers.values())
+        .chain(destination.signing_secret.iter());
+    for value in credentials {
+        if let Err(e) = secrets::validate(value) {
+            return Err((http::StatusCode::BAD_REQUEST, e));
+        }
+    }
 
     if !name.is_empty() {
         destination.name = name.to_string();
@@ -152,6 +194,25 @@ pub async fn save(
     }
 }
 
+/// The header of the signature of the webhook requests
+pub const SIGNATURE_HEADER: &str = "X-O2-Signature";
+
+/// Signs the body of a webhook request with the secret of the destination. The value of the
+/// `X-O2-Signature` header is `t=<timestamp>,v1=<signature>`, where the timestamp is the unix
+/// time in seconds of the request and the signature is the hex encoded HMAC-SHA256, keyed by the
+/// secret, of the canonical signing string `<timestamp>.<body>`. A receiver verifies the
+/// signature by computing it again from the raw body, and rejects an old timestamp to prevent
+/// replays.
+pub fn sign_webhook(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!(
+        "t={timestamp},v1={}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
 pub async fn get(org_id: &str, name: &str) -> Result<Destination, anyhow::Error> {
     db::alerts::destinations::get(org_id, name)
         .await
@@ -223,3 +284,33 @@ pub async fn delete(org_id: &str, name: &str) -> Result<(), (http::StatusCode, a
         Err(e) => Err((http::StatusCode::INTERNAL_SERVER_ERROR, e)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_webhook() {
+        let body = "{\"alert\":\"high latency\"}";
+        let signature = sign_webhook("secret", 1700000000, body);
+        assert_eq!(
+            signature,
+            "t=1700000000,v1=f353f9b2be857dc3d6d200ed7ddf3bdd62e7155b48f22aa25b17d6fe81dce543"
+        );
+        // the signature is stable for the same body, secret and timestamp
+        assert_eq!(signature, sign_webhook("secret", 1700000000, body));
+        // and changes when any of them changes
+        assert_ne!(signature, sign_webhook("secret2", 1700000000, body));
+        assert_ne!(
+            signature,
+            sign_webhook("secret", 1700000000, "{\"alert\":\"low latency\"}")
+        );
+        assert_ne!(
+            signature.split_once(",v1=").unwrap().1,
+            sign_webhook("secret", 1700000001, body)
+                .split_once(",v1=")
+                .unwrap()
+                .1
+        );
+    }
+}
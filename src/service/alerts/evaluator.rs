@@ -0,0 +1,412 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Evaluation of the scheduled alerts on the background queriers. The scheduler sends the due
+//! evaluations to a querier in batches and handles the results itself, so it stays the source of
+//! truth of the triggers. An evaluation is sent to the querier picked by the consistent hash of
+//! its id, which keeps its result for `ZO_ALERT_SCHEDULE_TIMEOUT`, so an evaluation delivered
+//! again in that time gets the same result without running again.
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use config::{
+    get_config,
+    meta::{
+        cluster::{get_internal_grpc_token, Role, RoleGroup},
+        stream::StreamType,
+    },
+    utils::{
+        hash::{gxhash, Sum64},
+        json::{self, Map, Value},
+        time::now_micros,
+    },
+};
+use futures::future::join_all;
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use proto::cluster_rpc::{
+    self, AlertEvaluation, AlertEvaluationResult, EvaluateAlertsRequest, EvaluateAlertsResponse,
+};
+use tokio::sync::{mpsc, oneshot, OnceCell};
+use tonic::{codec::CompressionEncoding, metadata::MetadataValue, Request, Status};
+
+use crate::{
+    common::{infra::cluster as infra_cluster, meta::alerts::alert::Alert},
+    service::{alerts::alert::EvaluationTimeout, grpc::get_cached_channel},
+};
+
+/// How long the dispatcher waits for more evaluations before sending a batch
+const BATCH_WAIT: Duration = Duration::from_millis(100);
+
+type Evaluated = (Option<Vec<Map<String, Value>>>, i64);
+
+type PendingEvaluation = (
+    AlertEvaluation,
+    oneshot::Sender<Option<AlertEvaluationResult>>,
+);
+
+/// The evaluations run on this node and when they expire, microseconds
+static EVALUATIONS: Lazy<Mutex<HashMap<String, (i64, Arc<OnceCell<AlertEvaluationResult>>)>>> =
+    Lazy::new(Default::default);
+
+static DISPATCHER: Lazy<mpsc::Sender<PendingEvaluation>> = Lazy::new(|| {
+    let (tx, rx) = mpsc::channel(1024);
+    tokio::task::spawn(dispatch(rx));
+    tx
+});
+
+/// Evaluates the alert, on a background querier when the remote evaluation is enabled. `id`
+/// identifies the run of the trigger, so the querier evaluates it once.
+pub async fn evaluate(
+    id: String,
+    alert: &Alert,
+    start_time: Option<i64>,
+) -> Result<Evaluated, anyhow::Error> {
+    if !get_config().limit.alert_remote_evaluation_enabled || alert.is_real_time {
+        return alert.evaluate(None, start_time).await;
+    }
+    let evaluation = AlertEvaluation {
+        id,
+        org_id: alert.org_id.clone(),
+        stream_type: alert.stream_type.to_string(),
+        stream_name: alert.stream_name.clone(),
+        alert_name: alert.name.clone(),
+        start_time,
+    };
+    let (tx, rx) = oneshot::channel();
+    if DISPATCHER.send((evaluation, tx)).await.is_ok() {
+        if let Ok(Some(result)) = rx.await {
+            return from_result(result, alert.get_evaluation_timeout());
+        }
+    }
+    log::warn!(
+        "[ALERT EVALUATOR] no querier evaluated {}/{}/{}/{}, evaluating it locally",
+        alert.org_id,
+        alert.stream_type,
+        alert.stream_name,
+        alert.name
+    );
+    alert.evaluate(None, start_time).await
+}
+
+/// Collects the evaluations submitted together and sends them to the queriers in batches
+async fn dispatch(mut rx: mpsc::Receiver<PendingEvaluation>) {
+    while let Some(first) = rx.recv().await {
+        let batch_size = get_config().limit.alert_evaluation_batch_size.max(1);
+        let mut batch = vec![first];
+        let deadline = tokio::time::sleep(BATCH_WAIT);
+        tokio::pin!(deadline);
+        while batch.len() < batch_size {
+            tokio::select! {
+                Some(pending) = rx.recv() => batch.push(pending),
+                _ = &mut deadline => break,
+            }
+        }
+        tokio::task::spawn(send_batch(batch));
+    }
+}
+
+async fn send_batch(batch: Vec<PendingEvaluation>) {
+    let nodes = infra_cluster::get_cached_online_querier_nodes(Some(RoleGroup::Background))
+        .await
+        .unwrap_or_default();
+    let mut senders = HashMap::with_capacity(batch.len());
+    let mut node_evaluations: HashMap<String, Vec<AlertEvaluation>> = HashMap::new();
+    if nodes.is_empty() {
+        log::warn!("[ALERT EVALUATOR] no background querier is online");
+    }
+    for (evaluation, tx) in batch {
+        if !nodes.is_empty() {
+            // the same evaluation always goes to the same querier, which runs it once
+            let grpc_addr = match infra_cluster::get_node_from_consistent_hash(
+                &evaluation.id,
+                &Role::Querier,
+                Some(RoleGroup::Background),
+            )
+            .await
+            .and_then(|name| nodes.iter().find(|n| n.name == name))
+            {
+                Some(node) => node.grpc_addr.clone(),
+                None => {
+                    let hash = gxhash::new().sum64(&evaluation.id);
+                    nodes[(hash % nodes.len() as u64) as usize]
+                        .grpc_addr
+                        .clone()
+                }
+            };
+            node_evaluations
+                .entry(grpc_addr)
+                .or_default()
+                .push(evaluation.clone());
+        }
+        senders.insert(evaluation.id, tx);
+    }
+    let responses = join_all(node_evaluations.into_iter().map(
+        |(grpc_addr, evaluations)| async move {
+            let num = evaluations.len();
+            match send(&grpc_addr, EvaluateAlertsRequest { evaluations }).await {
+                Ok(resp) => resp.results,
+                Err(e) => {
+                    log::error!(
+                        "[ALERT EVALUATOR] evaluating {num} alerts on node {grpc_addr} failed: {e}"
+                    );
+                    vec![]
+                }
+            }
+        },
+    ))
+    .await;
+    for result in responses.into_iter().flatten() {
+        if let Some(tx) = senders.remove(&result.id) {
+            tx.send(Some(result)).ok();
+        }
+    }
+    for (_, tx) in senders {
+        tx.send(None).ok();
+    }
+}
+
+/// Sends the batch of evaluations to the querier and waits for the results
+pub async fn send(
+    grpc_addr: &str,
+    req: EvaluateAlertsRequest,
+) -> Result<EvaluateAlertsResponse, Status> {
+    let cfg = get_config();
+    let token: MetadataValue<_> = get_internal_grpc_token()
+        .parse()
+        .map_err(|_| Status::internal("invalid token"))?;
+    let channel = get_cached_channel(grpc_addr).await?;
+    let mut client = cluster_rpc::alert_evaluator_client::AlertEvaluatorClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            Ok(req)
+        },
+    );
+    client = client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
+        .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
+    let mut request = Request::new(req);
+    request.set_timeout(Duration::from_secs(
+        cfg.limit.alert_schedule_timeout.max(1) as u64
+    ));
+    Ok(client.evaluate_alerts(request).await?.into_inner())
+}
+
+/// Runs the evaluations of the batch concurrently, an evaluation already run or running on this
+/// node returns its result instead of running again
+pub async fn evaluate_batch<F, Fut>(
+    req: EvaluateAlertsRequest,
+    evaluate: F,
+) -> EvaluateAlertsResponse
+where
+    F: Fn(AlertEvaluation) -> Fut,
+    Fut: Future<Output = AlertEvaluationResult>,
+{
+    let now = now_micros();
+    let expires_at = now + get_config().limit.alert_schedule_timeout * 1_000_000;
+    let cells = {
+        let mut evaluations = EVALUATIONS.lock();
+        evaluations.retain(|_, (expires_at, _)| *expires_at > now);
+        req.evaluations
+            .into_iter()
+            .map(|evaluation| {
+                let (_, cell) = evaluations
+                    .entry(evaluation.id.clone())
+                    .or_insert_with(|| (expires_at, Arc::new(OnceCell::new())));
+                (evaluation, cell.clone())
+            })
+            .collect::<Vec<_>>()
+    };
+    let evaluate = &evaluate;
+    let results = join_all(cells.into_iter().map(|(evaluation, cell)| async move {
+        let id = evaluation.id.clone();
+        let mut result = cell.get_or_init(|| evaluate(evaluation)).await.clone();
+        result.id = id;
+        result
+    }))
+    .await;
+    EvaluateAlertsResponse { results }
+}
+
+/// Evaluates the alert on this node
+pub async fn evaluate_alert(evaluation: AlertEvaluation) -> AlertEvaluationResult {
+    let stream_type = StreamType::from(evaluation.stream_type.as_str());
+    let ret = match super::alert::get(
+        &evaluation.org_id,
+        stream_type,
+        &evaluation.stream_name,
+        &evaluation.alert_name,
+    )
+    .await
+    {
+        Ok(Some(alert)) => alert.evaluate(None, evaluation.start_time).await,
+        Ok(None) => Err(anyhow::anyhow!(
+            "alert not found: {}/{}/{}/{}",
+            evaluation.org_id,
+            evaluation.stream_name,
+            evaluation.stream_type,
+            evaluation.alert_name
+        )),
+        Err(e) => Err(e),
+    };
+    into_result(evaluation.id, ret)
+}
+
+fn into_result(id: String, ret: Result<Evaluated, anyhow::Error>) -> AlertEvaluationResult {
+    match ret {
+        Ok((rows, end_time)) => AlertEvaluationResult {
+            id,
+            rows: json::to_vec(&rows).unwrap_or_default(),
+            end_time,
+            error: None,
+            timed_out: false,
+        },
+        Err(e) => AlertEvaluationResult {
+            id,
+            rows: vec![],
+            end_time: 0,
+            timed_out: e.is::<EvaluationTimeout>(),
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Turns the result back to the return of [`Alert::evaluate`], `timeout` is the evaluation
+/// timeout of the alert in seconds
+fn from_result(result: AlertEvaluationResult, timeout: i64) -> Result<Evaluated, anyhow::Error> {
+    if result.timed_out {
+        return Err(EvaluationTimeout(timeout.max(0) as u64).into());
+    }
+    if let Some(error) = result.error {
+        return Err(anyhow::anyhow!(error));
+    }
+    let rows = json::from_slice(&result.rows)?;
+    Ok((rows, result.end_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use proto::cluster_rpc::alert_evaluator_server::{
+        AlertEvaluator as AlertEvaluatorService, AlertEvaluatorServer,
+    };
+    use tonic::Response;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TestEvaluator {
+        runs: Arc<AtomicUsize>,
+    }
+
+    #[tonic::async_trait]
+    impl AlertEvaluatorService for TestEvaluator {
+        async fn evaluate_alerts(
+            &self,
+            request: Request<EvaluateAlertsRequest>,
+        ) -> Result<Response<EvaluateAlertsResponse>, Status> {
+            let runs = self.runs.clone();
+            let resp = evaluate_batch(request.into_inner(), |evaluation| {
+                let runs = runs.clone();
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    let ret = match evaluation.alert_name.as_str() {
+                        "slow" => Err(EvaluationTimeout(30).into()),
+                        "broken" => Err(anyhow::anyhow!("Partial response")),
+                        name => {
+                            let mut row = Map::new();
+                            row.insert("alert".to_string(), Value::from(name));
+                            Ok((Some(vec![row]), evaluation.start_time.unwrap_or_default()))
+                        }
+                    };
+                    into_result(evaluation.id, ret)
+                }
+            })
+            .await;
+            Ok(Response::new(resp))
+        }
+    }
+
+    fn evaluation(id: &str, alert_name: &str) -> AlertEvaluation {
+        AlertEvaluation {
+            id: id.to_string(),
+            org_id: "default".to_string(),
+            stream_type: "logs".to_string(),
+            stream_name: "app".to_string(),
+            alert_name: alert_name.to_string(),
+            start_time: Some(1_000),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_batch_round_trip() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let incoming = futures::stream::unfold(listener, |listener| async move {
+            Some((listener.accept().await.map(|(s, _)| s), listener))
+        });
+        let evaluator = TestEvaluator::default();
+        let runs = evaluator.runs.clone();
+        tokio::task::spawn(
+            tonic::transport::Server::builder()
+                .add_service(AlertEvaluatorServer::new(evaluator))
+                .serve_with_incoming(Box::pin(incoming)),
+        );
+
+        let req = EvaluateAlertsRequest {
+            evaluations: vec![
+                evaluation("round_trip/ok/1", "ok"),
+                evaluation("round_trip/slow/1", "slow"),
+                evaluation("round_trip/broken/1", "broken"),
+            ],
+        };
+        let resp = send(&addr, req.clone()).await.unwrap();
+        assert_eq!(resp.results.len(), 3);
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        let mut results = resp.results.clone().into_iter();
+        let ok = results.next().unwrap();
+        assert_eq!(ok.id, "round_trip/ok/1");
+        let (rows, end_time) = from_result(ok, 30).unwrap();
+        assert_eq!(end_time, 1_000);
+        assert_eq!(rows.unwrap()[0]["alert"], "ok");
+        let err = from_result(results.next().unwrap(), 30).unwrap_err();
+        assert!(err.is::<EvaluationTimeout>());
+        let err = from_result(results.next().unwrap(), 30).unwrap_err();
+        assert!(err.to_string().starts_with("Partial"));
+
+        // the redelivered batch gets the same results without evaluating again
+        let again = send(&addr, req).await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(again.results, resp.results);
+
+        // the next run of the trigger is evaluated
+        let next = send(
+            &addr,
+            EvaluateAlertsRequest {
+                evaluations: vec![evaluation("round_trip/ok/2", "ok")],
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(next.results.len(), 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The flapping detection of the scheduled alerts. An alert which turns from firing to
+//! resolved and back `ZO_ALERT_FLAPPING_THRESHOLD` times within `ZO_ALERT_FLAPPING_WINDOW` is
+//! flapping: a single flapping notice is sent and the next notifications are suppressed until it
+//! goes `ZO_ALERT_FLAPPING_STABILIZATION_WINDOW` without a transition. The state is kept in the
+//! data of the trigger, so it survives the restarts.
+
+use chrono::Duration;
+use config::{
+    get_config,
+    utils::json::{Map, Value},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlappingState {
+    /// the condition was satisfied on the last evaluation
+    #[serde(default)]
+    pub firing: bool,
+    /// the times of the recent transitions, microseconds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transitions: Vec<i64>,
+    /// when the alert started flapping, microseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flapping_since: Option<i64>,
+}
+
+impl FlappingState {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlappingAction {
+    /// the notification is sent as usual
+    Notify,
+    /// the alert started flapping, the flapping notice is sent instead of the notification
+    NotifyFlapping,
+    /// the alert is flapping, the notification is suppressed
+    Suppress,
+}
+
+#[derive(Clone, Debug)]
+pub struct FlappingConfig {
+    /// number of transitions within the window to be flapping, 0 disables the detection
+    pub threshold: usize,
+    /// microseconds
+    pub window: i64,
+    /// microseconds
+    pub stabilization_window: i64,
+}
+
+impl FlappingConfig {
+    pub fn from_config() -> Self {
+        let cfg = get_config();
+        let minutes = |v: i64| {
+            Duration::try_minutes(v)
+                .and_then(|d| d.num_microseconds())
+                .unwrap_or_default()
+        };
+        Self {
+            threshold: cfg.limit.alert_flapping_threshold,
+            window: minutes(cfg.limit.alert_flapping_window),
+            stabilization_window: minutes(cfg.limit.alert_flapping_stabilization_window),
+        }
+    }
+}
+
+/// Records the result of an evaluation of the alert, returns what to do with its notification
+pub fn record(
+    config: &FlappingConfig,
+    state: &mut FlappingState,
+    firing: bool,
+    now: i64,
+) -> FlappingAction {
+    if config.threshold == 0 {
+        *state = FlappingState::default();
+        return FlappingAction::Notify;
+    }
+    if state.firing != firing {
+        state.transitions.push(now);
+    }
+    state.firing = firing;
+    // a flapping alert needs the last transition until it's stable
+    let keep = config.window.max(config.stabilization_window);
+    state.transitions.retain(|t| now - *t < keep);
+
+    match state.flapping_since {
+        Some(_) => {
+            let stable = state
+                .transitions
+                .last()
+                .map_or(true, |t| now - *t >= config.stabilization_window);
+            if stable {
+                state.flapping_since = None;
+                state.transitions.clear();
+                FlappingAction::Notify
+            } else {
+                FlappingAction::Suppress
+            }
+        }
+        None => {
+            let recent = state
+                .transitions
+                .iter()
+                .filter(|t| now - **t < config.window)
+                .count();
+            if recent >= config.threshold {
+                state.flapping_since = Some(now);
+                FlappingAction::NotifyFlapping
+            } else {
+                FlappingAction::Notify
+            }
+        }
+    }
+}
+
+/// The rows of the flapping notice, the rows of the evaluation marked with `alert_flapping` and
+/// `alert_flapping_transitions` for the templates, a single row when the alert isn't firing
+pub fn flapping_notice(
+    rows: Option<Vec<Map<String, Value>>>,
+    state: &FlappingState,
+) -> Vec<Map<String, Value>> {
+    let mut rows = rows
+        .filter(|rows| !rows.is_empty())
+        .unwrap_or_else(|| vec![Map::new()]);
+    for row in rows.iter_mut() {
+        row.insert("alert_flapping".to_string(), Value::Bool(true));
+        row.insert(
+            "alert_flapping_transitions".to_string(),
+            Value::from(state.transitions.len()),
+        );
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flapping_dampening() {
+        use FlappingAction::*;
+
+        let minute = 60_000_000;
+        let config = FlappingConfig {
+            threshold: 4,
+            window: 60 * minute,
+            stabilization_window: 30 * minute,
+        };
+        let mut state = FlappingState::default();
+
+        // an alert firing every other evaluation, every 5 minutes
+        let actions = (0..10)
+            .map(|i| record(&config, &mut state, i % 2 == 0, i * 5 * minute))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                Notify,
+                Notify,
+                Notify,
+                NotifyFlapping,
+                Suppress,
+                Suppress,
+                Suppress,
+                Suppress,
+                Suppress,
+                Suppress
+            ]
+        );
+        assert_eq!(state.flapping_since, Some(15 * minute));
+        assert_eq!(flapping_notice(None, &state)[0]["alert_flapping"], true);
+
+        // the state survives the restarts in the trigger data
+        let mut state: FlappingState =
+            config::utils::json::from_str(&config::utils::json::to_string(&state).unwrap())
+                .unwrap();
+
+        // still suppressed until no transition for the stabilization window
+        let last = 45 * minute;
+        assert_eq!(
+            record(&config, &mut state, false, last + 10 * minute),
+            Suppress
+        );
+        assert_eq!(
+            record(&config, &mut state, false, last + 20 * minute),
+            Suppress
+        );
+        assert_eq!(
+            record(&config, &mut state, false, last + 30 * minute),
+            Notify
+        );
+        assert!(state.flapping_since.is_none());
+        assert!(state.transitions.is_empty());
+
+        // a few transitions below the threshold are notified
+        assert_eq!(
+            record(&config, &mut state, true, last + 35 * minute),
+            Notify
+        );
+        assert_eq!(
+            record(&config, &mut state, false, last + 40 * minute),
+            Notify
+        );
+
+        // disabled
+        let disabled = FlappingConfig {
+            threshold: 0,
+            ..config
+        };
+        for i in 0..10 {
+            assert_eq!(
+                record(&disabled, &mut state, i % 2 == 0, i * minute),
+                Notify
+            );
+        }
+    }
+}
@@ -25,20 +25,30 @@ use config::{
     utils::{
         base64,
         json::{Map, Value},
+        time::parse_milliseconds,
     },
 };
 
-use super::promql;
+use super::{metrics::prom::detect_scrape_interval, promql};
 use crate::{
     common::meta::alerts::{
-        AggFunction, Condition, Operator, QueryCondition, QueryType, TriggerCondition,
+        AggFunction, Aggregation, Condition, Operator, QueryCondition, QueryType, TriggerCondition,
+        PROMQL_STEP_AUTO,
+    },
+    service::search::{
+        self as SearchService,
+        datafusion::udf::{MATCH_UDF_IGNORE_CASE_NAME, MATCH_UDF_NAME},
     },
-    service::search as SearchService,
 };
 
 pub mod alert;
+pub mod backfill;
+pub mod deduplication;
 pub mod derived_streams;
 pub mod destinations;
+pub mod evaluator;
+pub mod flapping;
+pub mod result_cache;
 pub mod scheduler;
 pub mod templates;
 
@@ -74,8 +84,11 @@ impl QueryCondition {
         stream_param: &StreamParams,
         trigger_condition: &TriggerCondition,
         start_time: Option<i64>,
+        end_time: Option<i64>,
+        timeout: i64,
     ) -> Result<(Option<Vec<Map<String, Value>>>, i64), anyhow::Error> {
-        let now = Utc::now().timestamp_micros();
+        // `end_time` allows evaluating the condition at a point in the past, e.g. for backfill
+        let now = end_time.unwrap_or_else(|| Utc::now().timestamp_micros());
         let sql = match self.query_type {
             QueryType::Custom => {
                 let Some(v) = self.conditions.as_ref() else {
@@ -109,24 +122,9 @@ impl QueryCondition {
                         .unwrap()
                 };
                 let end = now;
-                let condition = self.promql_condition.as_ref().unwrap();
-                let req = promql::MetricsQueryRequest {
-                    query: format!(
-                        "({}) {} {}",
-                        v,
-                        match &condition.operator {
-                            &Operator::EqualTo => "==".to_string(),
-                            _ => condition.operator.to_string(),
-                        },
-                        to_float(&condition.value)
-                    ),
-                    start,
-                    end,
-                    step: std::cmp::max(
-                        promql::micros(promql::MINIMAL_INTERVAL),
-                        (end - start) / promql::MAX_DATA_POINTS,
-                    ),
-                };
+                let req = self
+                    .promql_request(&stream_param.org_id, v, start, end)
+                    .await;
                 let resp = match promql::search::search(&stream_param.org_id, &req, 0, "").await {
                     Ok(v) => v,
                     Err(_) => {
@@ -258,7 +256,7 @@ impl QueryCondition {
                 encoding: config::meta::search::RequestEncoding::Empty,
                 regions: vec![],
                 clusters: vec![],
-                timeout: 0,
+                timeout,
                 search_type: Some(SearchEventType::Alerts),
                 from: 0,
                 size,
@@ -271,8 +269,11 @@ impl QueryCondition {
                 uses_zo_fn: false,
                 query_fn: self.vrl_function.clone(),
                 skip_wal: false,
+                wal_only: false,
                 index_type: "".to_string(),
                 per_query_response: false, // Will return results in single array
+                with_stream_name: false,
+                group_by_stream: false,
             };
 
             SearchService::search_multi(
@@ -311,20 +312,21 @@ impl QueryCondition {
                         None
                     },
                     skip_wal: false,
+                    wal_only: false,
+                    fields: vec![],
                 },
                 encoding: config::meta::search::RequestEncoding::Empty,
                 regions: vec![],
                 clusters: vec![],
-                timeout: 0,
+                timeout,
                 search_type: Some(SearchEventType::Alerts), /* TODO(taiming): change the name to
                                                              * scheduled & inform FE */
                 index_type: "".to_string(),
             };
-            SearchService::search(
+            result_cache::search(
                 &trace_id,
                 &stream_param.org_id,
                 stream_param.stream_type,
-                None,
                 &req,
             )
             .await
@@ -404,6 +406,64 @@ impl QueryCondition {
             Ok((records, now))
         }
     }
+
+    /// The PromQL query of the alert, comparing the query with the condition
+    async fn promql_request(
+        &self,
+        org_id: &str,
+        promql: &str,
+        start: i64,
+        end: i64,
+    ) -> promql::MetricsQueryRequest {
+        let condition = self.promql_condition.as_ref().unwrap();
+        promql::MetricsQueryRequest {
+            query: format!(
+                "({}) {} {}",
+                promql,
+                match &condition.operator {
+                    &Operator::EqualTo => "==".to_string(),
+                    _ => condition.operator.to_string(),
+                },
+                to_float(&condition.value)
+            ),
+            start,
+            end,
+            step: self.promql_step(org_id, promql, start, end).await,
+        }
+    }
+
+    /// The step of the PromQL query in microseconds, the configured one, the scrape interval of
+    /// the metric with `auto`, else the time range divided by the max data points. A step is at
+    /// least the minimal interval and the time range divided by the max data points.
+    async fn promql_step(&self, org_id: &str, promql: &str, start: i64, end: i64) -> i64 {
+        let default_step = std::cmp::max(
+            promql::micros(promql::MINIMAL_INTERVAL),
+            (end - start) / promql::MAX_DATA_POINTS,
+        );
+        match self.promql_step.as_deref() {
+            None | Some("") => default_step,
+            Some(PROMQL_STEP_AUTO) => {
+                let detected = match promql_parser::parser::parse(promql) {
+                    Ok(expr) => detect_scrape_interval(org_id, &expr, start, end).await,
+                    Err(e) => Err(infra::errors::Error::Message(e)),
+                };
+                match detected {
+                    Ok(Some(step)) => std::cmp::max(step, default_step),
+                    Ok(None) => default_step,
+                    Err(e) => {
+                        log::warn!(
+                            "Alert evaluate: detect the scrape interval of {promql} error: {e}"
+                        );
+                        default_step
+                    }
+                }
+            }
+            Some(step) => match parse_milliseconds(step) {
+                Ok(step) if step > 0 => std::cmp::max(step as i64 * 1000, default_step),
+                _ => default_step,
+            },
+        }
+    }
 }
 
 impl Condition {
@@ -416,8 +476,13 @@ impl Condition {
         };
         match val {
             Value::String(v) => {
-                let val = v.as_str();
                 let con_val = self.value.as_str().unwrap_or_default();
+                let (val, con_val) = if self.ignore_case {
+                    (v.to_lowercase(), con_val.to_lowercase())
+                } else {
+                    (v.to_string(), con_val.to_string())
+                };
+                let (val, con_val) = (val.as_str(), con_val.as_str());
                 match self.operator {
                     Operator::EqualTo => val == con_val,
                     Operator::NotEqualTo => val != con_val,
@@ -511,10 +576,11 @@ async fn build_sql(
     }
 
     // handle aggregation
-    let mut sql = String::new();
     let agg = query_condition.aggregation.as_ref().unwrap();
     let having_expr = {
         let data_type = match schema.field_with_name(&agg.having.column) {
+            // the distinct count of a column is a number whatever the type of the column
+            Ok(_) if agg.function == AggFunction::CountDistinct => &DataType::Int64,
             Ok(field) => field.data_type(),
             Err(_) => {
                 return Err(anyhow::anyhow!(
@@ -526,13 +592,30 @@ async fn build_sql(
         };
         build_expr(&agg.having, "alert_agg_value", data_type)?
     };
+    Ok(build_agg_sql(
+        &stream_params.stream_name,
+        agg,
+        &where_sql,
+        &having_expr,
+    ))
+}
 
+/// Builds the SQL of an aggregation alert. With a group_by the threshold is a `HAVING` on every
+/// group, without it the aggregate is over the whole window and the threshold is applied to the
+/// single aggregated row, by a subquery as `HAVING` without `GROUP BY` isn't accepted everywhere.
+fn build_agg_sql(
+    stream_name: &str,
+    agg: &Aggregation,
+    where_sql: &str,
+    having_expr: &str,
+) -> String {
     let func_expr = match agg.function {
         AggFunction::Avg => format!("AVG(\"{}\")", agg.having.column),
         AggFunction::Max => format!("MAX(\"{}\")", agg.having.column),
         AggFunction::Min => format!("MIN(\"{}\")", agg.having.column),
         AggFunction::Sum => format!("SUM(\"{}\")", agg.having.column),
         AggFunction::Count => format!("COUNT(\"{}\")", agg.having.column),
+        AggFunction::CountDistinct => format!("COUNT(DISTINCT \"{}\")", agg.having.column),
         AggFunction::Median => format!("MEDIAN(\"{}\")", agg.having.column),
         AggFunction::P50 => format!("approx_percentile_cont(\"{}\", 0.5)", agg.having.column),
         AggFunction::P75 => format!("approx_percentile_cont(\"{}\", 0.75)", agg.having.column),
@@ -542,33 +625,28 @@ async fn build_sql(
     };
 
     let cfg = get_config();
-    if let Some(group) = agg.group_by.as_ref() {
-        if !group.is_empty() {
-            sql = format!(
-                "SELECT {}, {} AS alert_agg_value, MIN({}) as zo_sql_min_time, MAX({}) AS zo_sql_max_time FROM \"{}\" {} GROUP BY {} HAVING {}",
-                group.join(", "),
-                func_expr,
-                cfg.common.column_timestamp,
-                cfg.common.column_timestamp,
-                stream_params.stream_name,
-                where_sql,
-                group.join(", "),
-                having_expr
-            );
-        }
-    }
-    if sql.is_empty() {
-        sql = format!(
-            "SELECT {} AS alert_agg_value, MIN({}) as zo_sql_min_time, MAX({}) AS zo_sql_max_time FROM \"{}\" {} HAVING {}",
+    match agg.group_by.as_ref() {
+        Some(group) if !group.is_empty() => format!(
+            "SELECT {}, {} AS alert_agg_value, MIN({}) as zo_sql_min_time, MAX({}) AS zo_sql_max_time FROM \"{}\" {} GROUP BY {} HAVING {}",
+            group.join(", "),
             func_expr,
             cfg.common.column_timestamp,
             cfg.common.column_timestamp,
-            stream_params.stream_name,
+            stream_name,
             where_sql,
+            group.join(", "),
             having_expr
-        );
+        ),
+        _ => format!(
+            "SELECT * FROM (SELECT {} AS alert_agg_value, MIN({}) as zo_sql_min_time, MAX({}) AS zo_sql_max_time FROM \"{}\" {}) WHERE {}",
+            func_expr,
+            cfg.common.column_timestamp,
+            cfg.common.column_timestamp,
+            stream_name,
+            where_sql,
+            having_expr
+        ),
     }
-    Ok(sql)
 }
 
 fn build_expr(
@@ -588,19 +666,30 @@ fn build_expr(
             } else {
                 cond.value.to_string()
             };
+            // escape the single quotes to keep the value a string literal
+            let val = val.replace('\'', "''");
+            let match_udf = if cond.ignore_case {
+                MATCH_UDF_IGNORE_CASE_NAME
+            } else {
+                MATCH_UDF_NAME
+            };
+            let field = format!("\"{field_alias}\"");
+            let (field_alias, val) = if cond.ignore_case {
+                (format!("lower({field})"), val.to_lowercase())
+            } else {
+                (field.clone(), val)
+            };
             match cond.operator {
-                Operator::EqualTo => format!("\"{}\" {} '{}'", field_alias, "=", val),
-                Operator::NotEqualTo => format!("\"{}\" {} '{}'", field_alias, "!=", val),
-                Operator::GreaterThan => format!("\"{}\" {} '{}'", field_alias, ">", val),
+                Operator::EqualTo => format!("{} {} '{}'", field_alias, "=", val),
+                Operator::NotEqualTo => format!("{} {} '{}'", field_alias, "!=", val),
+                Operator::GreaterThan => format!("{} {} '{}'", field_alias, ">", val),
                 Operator::GreaterThanEquals => {
-                    format!("\"{}\" {} '{}'", field_alias, ">=", val)
-                }
-                Operator::LessThan => format!("\"{}\" {} '{}'", field_alias, "<", val),
-                Operator::LessThanEquals => format!("\"{}\" {} '{}'", field_alias, "<=", val),
-                Operator::Contains => format!("\"{}\" {} '%{}%'", field_alias, "LIKE", val),
-                Operator::NotContains => {
-                    format!("\"{}\" {} '%{}%'", field_alias, "NOT LIKE", val)
+                    format!("{} {} '{}'", field_alias, ">=", val)
                 }
+                Operator::LessThan => format!("{} {} '{}'", field_alias, "<", val),
+                Operator::LessThanEquals => format!("{} {} '{}'", field_alias, "<=", val),
+                Operator::Contains => format!("{match_udf}({field}, '{val}')"),
+                Operator::NotContains => format!("NOT {match_udf}({field}, '{val}')"),
             }
         }
         DataType::Int16 | DataType::Int32 | DataType::Int64 => {
@@ -722,3 +811,195 @@ fn build_expr(
     };
     Ok(expr)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(operator: Operator, value: &str, ignore_case: bool) -> Condition {
+        Condition {
+            column: "level".to_string(),
+            operator,
+            value: Value::String(value.to_string()),
+            ignore_case,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_promql_request_step() {
+        let mut query_condition = QueryCondition {
+            query_type: QueryType::PromQL,
+            promql: Some("rate(http_requests_total[5m])".to_string()),
+            promql_condition: Some(Condition {
+                column: "value".to_string(),
+                operator: Operator::GreaterThan,
+                value: Value::from(10),
+                ignore_case: false,
+            }),
+            promql_step: Some("30s".to_string()),
+            ..Default::default()
+        };
+        let (start, end) = (0, 3_600_000_000);
+        let req = query_condition
+            .promql_request("default", "rate(http_requests_total[5m])", start, end)
+            .await;
+        assert_eq!(req.query, "(rate(http_requests_total[5m])) > 10");
+        assert_eq!(req.step, 30_000_000);
+        // a short step over a long range is clamped to the max data points
+        query_condition.promql_step = Some("1s".to_string());
+        let day = 86_400_000_000;
+        let req = query_condition
+            .promql_request("default", "rate(http_requests_total[5m])", 0, day)
+            .await;
+        assert_eq!(req.step, day / promql::MAX_DATA_POINTS);
+        let req = query_condition
+            .promql_request("default", "rate(http_requests_total[5m])", 0, 60_000_000)
+            .await;
+        assert_eq!(req.step, promql::micros(promql::MINIMAL_INTERVAL));
+
+        // without a step it's the time range divided by the max data points
+        query_condition.promql_step = None;
+        let req = query_condition
+            .promql_request("default", "rate(http_requests_total[5m])", start, end)
+            .await;
+        assert_eq!(req.step, (end - start) / promql::MAX_DATA_POINTS);
+        let req = query_condition
+            .promql_request(
+                "default",
+                "rate(http_requests_total[5m])",
+                start,
+                60_000_000,
+            )
+            .await;
+        assert_eq!(req.step, promql::micros(promql::MINIMAL_INTERVAL));
+    }
+
+    #[tokio::test]
+    async fn test_condition_evaluate_ignore_case() {
+        let mut row = Map::new();
+        row.insert("level".to_string(), Value::String("ERROR".to_string()));
+
+        for (operator, value, expected) in [
+            (Operator::EqualTo, "error", true),
+            (Operator::NotEqualTo, "error", false),
+            (Operator::Contains, "rro", true),
+            (Operator::NotContains, "rro", false),
+        ] {
+            let cond = condition(operator.clone(), value, true);
+            assert_eq!(cond.evaluate(&row).await, expected, "{:?}", operator);
+            let cond = condition(operator.clone(), value, false);
+            assert_eq!(cond.evaluate(&row).await, !expected, "{:?}", operator);
+        }
+    }
+
+    #[test]
+    fn test_build_expr_ignore_case() {
+        let cond = condition(Operator::EqualTo, "Error", true);
+        assert_eq!(
+            build_expr(&cond, "", &DataType::Utf8).unwrap(),
+            "lower(\"level\") = 'error'"
+        );
+        let cond = condition(Operator::Contains, "Error", true);
+        assert_eq!(
+            build_expr(&cond, "", &DataType::Utf8).unwrap(),
+            "str_match_ignore_case(\"level\", 'error')"
+        );
+        let cond = condition(Operator::Contains, "Error", false);
+        assert_eq!(
+            build_expr(&cond, "", &DataType::Utf8).unwrap(),
+            "str_match(\"level\", 'Error')"
+        );
+    }
+
+    #[test]
+    fn test_build_expr_escape() {
+        let cond = condition(Operator::Contains, "100%_done", false);
+        assert_eq!(
+            build_expr(&cond, "", &DataType::Utf8).unwrap(),
+            "str_match(\"level\", '100%_done')"
+        );
+        let cond = condition(Operator::NotContains, "100%_done", false);
+        assert_eq!(
+            build_expr(&cond, "", &DataType::Utf8).unwrap(),
+            "NOT str_match(\"level\", '100%_done')"
+        );
+        let cond = condition(Operator::EqualTo, "it's' OR '1'='1", false);
+        assert_eq!(
+            build_expr(&cond, "", &DataType::Utf8).unwrap(),
+            "\"level\" = 'it''s'' OR ''1''=''1'"
+        );
+        let cond = condition(Operator::NotContains, "O'Reilly", true);
+        assert_eq!(
+            build_expr(&cond, "", &DataType::Utf8).unwrap(),
+            "NOT str_match_ignore_case(\"level\", 'o''reilly')"
+        );
+    }
+
+    fn aggregation(function: AggFunction, group_by: Option<Vec<String>>) -> Aggregation {
+        Aggregation {
+            group_by,
+            function,
+            having: Condition {
+                column: "took".to_string(),
+                operator: Operator::GreaterThan,
+                value: Value::from(100),
+                ignore_case: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_build_agg_sql_without_group_by() {
+        let where_sql = "WHERE \"level\" = 'error'";
+        let having = "\"alert_agg_value\" > 100";
+        for (function, func_expr) in [
+            (AggFunction::Count, "COUNT(\"took\")"),
+            (AggFunction::Avg, "AVG(\"took\")"),
+            (AggFunction::P95, "approx_percentile_cont(\"took\", 0.95)"),
+        ] {
+            let expected = format!(
+                "SELECT * FROM (SELECT {func_expr} AS alert_agg_value, MIN(_timestamp) as zo_sql_min_time, MAX(_timestamp) AS zo_sql_max_time FROM \"app\" {where_sql}) WHERE {having}"
+            );
+            // an empty group_by is the same as no group_by
+            for group_by in [None, Some(vec![])] {
+                let agg = aggregation(function.clone(), group_by);
+                let sql = build_agg_sql("app", &agg, where_sql, having);
+                assert_eq!(sql, expected);
+                assert!(!sql.contains("HAVING"));
+                assert!(config::meta::sql::resolve_stream_names(&sql).is_ok());
+            }
+        }
+        let agg = aggregation(AggFunction::Count, None);
+        assert_eq!(
+            build_agg_sql("app", &agg, "", having),
+            "SELECT * FROM (SELECT COUNT(\"took\") AS alert_agg_value, MIN(_timestamp) as zo_sql_min_time, MAX(_timestamp) AS zo_sql_max_time FROM \"app\" ) WHERE \"alert_agg_value\" > 100"
+        );
+    }
+
+    #[test]
+    fn test_build_agg_sql_with_group_by() {
+        let agg = aggregation(AggFunction::Avg, Some(vec!["host".to_string()]));
+        assert_eq!(
+            build_agg_sql("app", &agg, "", "\"alert_agg_value\" > 100"),
+            "SELECT host, AVG(\"took\") AS alert_agg_value, MIN(_timestamp) as zo_sql_min_time, MAX(_timestamp) AS zo_sql_max_time FROM \"app\"  GROUP BY host HAVING \"alert_agg_value\" > 100"
+        );
+    }
+
+    #[test]
+    fn test_build_agg_sql_count_distinct() {
+        let mut agg = aggregation(AggFunction::CountDistinct, None);
+        agg.having.column = "host".to_string();
+        let having = build_expr(&agg.having, "alert_agg_value", &DataType::Int64).unwrap();
+        assert_eq!(having, "\"alert_agg_value\" > 100");
+        assert_eq!(
+            build_agg_sql("app", &agg, "WHERE \"level\" = 'error'", &having),
+            "SELECT * FROM (SELECT COUNT(DISTINCT \"host\") AS alert_agg_value, MIN(_timestamp) as zo_sql_min_time, MAX(_timestamp) AS zo_sql_max_time FROM \"app\" WHERE \"level\" = 'error') WHERE \"alert_agg_value\" > 100"
+        );
+        agg.group_by = Some(vec!["service".to_string()]);
+        assert_eq!(
+            build_agg_sql("app", &agg, "", &having),
+            "SELECT service, COUNT(DISTINCT \"host\") AS alert_agg_value, MIN(_timestamp) as zo_sql_min_time, MAX(_timestamp) AS zo_sql_max_time FROM \"app\"  GROUP BY service HAVING \"alert_agg_value\" > 100"
+        );
+        assert_eq!(AggFunction::CountDistinct.to_string(), "count_distinct");
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Short-lived cache of the alert search results, the alerts evaluating the same query in the
+//! same time window share one search.
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use config::{
+    get_config,
+    meta::{search, stream::StreamType},
+    metrics,
+};
+use hashbrown::HashMap;
+use infra::errors::Error;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+use tokio::sync::OnceCell;
+
+use crate::service::search as SearchService;
+
+type CachedResult = (i64, Arc<OnceCell<search::Response>>);
+
+static RESULTS: Lazy<Mutex<HashMap<String, CachedResult>>> = Lazy::new(Default::default);
+
+/// Runs the search of an alert, reusing the result of the same query over the exact same time
+/// range searched in the last `ZO_ALERT_RESULT_CACHE_TTL` seconds.
+///
+/// The entries expire after the ttl, so a late sample of the time range is missed for at most
+/// ttl seconds. Nothing is cached if the ttl is 0 or the result cache is disabled.
+pub async fn search(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    req: &search::Request,
+) -> Result<search::Response, Error> {
+    let cfg = get_config();
+    let ttl = cfg.limit.alert_result_cache_ttl * 1_000_000;
+    if ttl <= 0 || !cfg.common.result_cache_enabled {
+        return SearchService::search(trace_id, org_id, stream_type, None, req).await;
+    }
+
+    let key = cache_key(org_id, stream_type, req);
+    let now = Utc::now().timestamp_micros();
+    let cell = {
+        let mut results = RESULTS.lock();
+        results.retain(|_, (created_at, _)| now - *created_at < ttl);
+        results
+            .entry(key.clone())
+            .or_insert_with(|| (now, Arc::new(OnceCell::new())))
+            .1
+            .clone()
+    };
+
+    let mut hit = true;
+    let res = cell
+        .get_or_try_init(|| {
+            hit = false;
+            SearchService::search(trace_id, org_id, stream_type, None, req)
+        })
+        .await?
+        .clone();
+    if hit {
+        log::debug!("[trace_id {trace_id}] alert search result cache hit");
+        metrics::ALERT_RESULT_CACHE_HITS
+            .with_label_values(&[org_id, stream_type.to_string().as_str()])
+            .inc();
+    } else if res.is_partial {
+        // don't share a partial result, the next evaluation searches again
+        RESULTS.lock().remove(&key);
+    }
+    Ok(res)
+}
+
+fn cache_key(org_id: &str, stream_type: StreamType, req: &search::Request) -> String {
+    let query = &req.query;
+    format!(
+        "{org_id}/{stream_type}/{}/{}/{}/{}/{}/{}",
+        query.start_time,
+        query.end_time,
+        query.from,
+        query.size,
+        query.query_fn.as_deref().unwrap_or_default(),
+        normalize_sql(&query.sql),
+    )
+}
+
+/// Formats the sql in the same way, so the queries which only differ in whitespace or keyword
+/// case share the result.
+fn normalize_sql(sql: &str) -> String {
+    match Parser::parse_sql(&PostgreSqlDialect {}, sql) {
+        Ok(statements) => statements
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(";"),
+        Err(_) => sql.split_whitespace().collect::<Vec<_>>().join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_sql() {
+        assert_eq!(
+            normalize_sql("select count(*) as cnt\n  from \"default\"   where code = 500"),
+            normalize_sql("SELECT count(*) AS cnt FROM \"default\" WHERE code = 500"),
+        );
+        assert_ne!(
+            normalize_sql("SELECT * FROM t WHERE msg = 'Error'"),
+            normalize_sql("SELECT * FROM t WHERE msg = 'error'"),
+        );
+        assert_eq!(normalize_sql("not a  sql"), "not a sql");
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let req = |start_time: i64, end_time: i64| search::Request {
+            query: search::Query {
+                sql: "SELECT count(*) FROM t".to_string(),
+                start_time,
+                end_time,
+                ..Default::default()
+            },
+            encoding: search::RequestEncoding::Empty,
+            regions: vec![],
+            clusters: vec![],
+            timeout: 0,
+            search_type: None,
+            index_type: "".to_string(),
+        };
+        let key = cache_key("default", StreamType::Logs, &req(1_000, 61_000_000));
+        assert_eq!(
+            key,
+            cache_key("default", StreamType::Logs, &req(1_000, 61_000_000))
+        );
+        // a newer window in the same ttl doesn't reuse the result
+        assert_ne!(
+            key,
+            cache_key("default", StreamType::Logs, &req(2_000, 61_001_000))
+        );
+    }
+}
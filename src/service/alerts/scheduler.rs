@@ -15,25 +15,38 @@
 
 use std::{str::FromStr, time::Instant};
 
-use chrono::{Duration, FixedOffset, Utc};
+use chrono::{Duration, FixedOffset, TimeZone, Utc};
 use config::{
     get_config,
     meta::{
         stream::StreamType,
         usage::{TriggerData, TriggerDataStatus, TriggerDataType},
     },
-    utils::{json, rand::get_rand_num_within},
+    utils::{
+        hash::{fnv, Sum64},
+        json,
+        rand::get_rand_num_within,
+    },
 };
 use cron::Schedule;
 use futures::future::try_join_all;
+use hashbrown::HashMap;
 use proto::cluster_rpc;
 
 use crate::{
-    common::meta::{alerts::FrequencyType, dashboards::reports::ReportFrequencyType},
+    common::meta::{
+        alerts::{FrequencyType, ToleranceMode, TriggerCondition},
+        dashboards::reports::ReportFrequencyType,
+    },
     service::{
-        alerts::alert::{get_alert_start_end_time, get_row_column_map},
+        alerts::{
+            alert::{get_alert_start_end_time, get_row_column_map, EvaluationTimeout},
+            deduplication, evaluator,
+            flapping::{self, FlappingAction, FlappingConfig},
+        },
         db::{self, scheduler::ScheduledTriggerData},
         ingestion::ingestion_service,
+        scheduled_export,
         usage::publish_triggers_usage,
     },
 };
@@ -73,12 +86,25 @@ pub async fn handle_triggers(trigger: db::scheduler::Trigger) -> Result<(), anyh
     match trigger.module {
         db::scheduler::TriggerModule::Report => handle_report_triggers(trigger).await,
         db::scheduler::TriggerModule::Alert => handle_alert_triggers(trigger).await,
+        db::scheduler::TriggerModule::ScheduledExport => {
+            handle_scheduled_export_triggers(trigger).await
+        }
         db::scheduler::TriggerModule::DerivedStream => {
             handle_derived_stream_triggers(trigger).await
         }
     }
 }
 
+/// Returns the next time of the cron in microseconds, the cron is evaluated in the timezone
+pub(crate) fn get_next_trigger_time<Z: TimeZone>(cron: &str, tz: Z) -> Result<i64, anyhow::Error> {
+    let schedule = Schedule::from_str(cron)?;
+    schedule
+        .upcoming(tz)
+        .next()
+        .map(|t| t.timestamp_micros())
+        .ok_or_else(|| anyhow::anyhow!("cron has no upcoming time: {cron}"))
+}
+
 /// Returns maximum considerable delay in microseconds - minimum of 1 hour or 20% of the frequency.
 fn get_max_considerable_delay(frequency: i64) -> i64 {
     // Calculate the maximum delay that can be considered for the alert evaluation.
@@ -96,6 +122,29 @@ fn get_max_considerable_delay(frequency: i64) -> i64 {
     std::cmp::min(max_delay, max_considerable_delay)
 }
 
+/// Returns the tolerance in microseconds to delay the next run of the alert identified by the
+/// key
+fn get_tolerance(trigger_condition: &TriggerCondition, alert_key: &str) -> i64 {
+    let tolerance = match trigger_condition.tolerance_in_secs {
+        Some(tolerance) if tolerance > 0 => tolerance as u64,
+        _ => return 0,
+    };
+    let tolerance = match trigger_condition.tolerance_mode {
+        ToleranceMode::Random => get_rand_num_within(0, tolerance),
+        ToleranceMode::Deterministic => fnv::new().sum64(alert_key) % tolerance,
+    };
+    Duration::seconds(tolerance as i64)
+        .num_microseconds()
+        .unwrap()
+}
+
+/// Returns if a failed alert evaluation out of retries moves to its next run, else the alert is
+/// paused. A timed out evaluation always moves to its next run instead of being retried right
+/// away, so a slow query doesn't hold the scheduler and the alert recovers when it gets faster.
+fn skip_to_next_run(timed_out: bool) -> bool {
+    timed_out || !get_config().limit.pause_alerts_on_retries
+}
+
 async fn handle_alert_triggers(trigger: db::scheduler::Trigger) -> Result<(), anyhow::Error> {
     log::debug!(
         "Inside handle_alert_triggers: processing trigger: {}",
@@ -209,6 +258,8 @@ async fn handle_alert_triggers(trigger: db::scheduler::Trigger) -> Result<(), an
         ScheduledTriggerData {
             period_end_time: None,
             tolerance: 0,
+            dedup_seen: HashMap::new(),
+            flapping: Default::default(),
         }
     };
 
@@ -255,8 +306,12 @@ async fn handle_alert_triggers(trigger: db::scheduler::Trigger) -> Result<(), an
     };
 
     let evalutaion_took = Instant::now();
-    // evaluate alert
-    let result = alert.evaluate(None, start_time).await;
+    // evaluate alert, the id stays the same when the evaluation is delivered again
+    let evaluation_id = format!(
+        "{org_id}/{}/{}/{}",
+        trigger.module_key, trigger.next_run_at, trigger.retries
+    );
+    let result = evaluator::evaluate(evaluation_id, &alert, start_time).await;
     let evaluation_took = evalutaion_took.elapsed().as_secs_f64();
     trigger_data_stream.evaluation_took_in_secs = Some(evaluation_took);
     if result.is_err() {
@@ -267,9 +322,10 @@ async fn handle_alert_triggers(trigger: db::scheduler::Trigger) -> Result<(), an
             trigger_data_stream.is_partial = Some(true);
         }
         trigger_data_stream.error = Some(err_string);
+        let timed_out = err.is::<EvaluationTimeout>();
         // update its status and retries
-        if trigger.retries + 1 >= get_config().limit.scheduler_max_retries {
-            if !get_config().limit.pause_alerts_on_retries {
+        if timed_out || trigger.retries + 1 >= get_config().limit.scheduler_max_retries {
+            if skip_to_next_run(timed_out) {
                 new_trigger.next_run_at += Duration::try_seconds(alert.trigger_condition.frequency)
                     .unwrap()
                     .num_microseconds()
@@ -326,18 +382,56 @@ async fn handle_alert_triggers(trigger: db::scheduler::Trigger) -> Result<(), an
             &new_trigger.module_key
         );
     }
-    let tolerance = match alert.trigger_condition.tolerance_in_secs {
-        Some(tolerance) if tolerance > 0 => {
-            let tolerance = Duration::seconds(get_rand_num_within(0, tolerance as u64) as i64)
-                .num_microseconds()
-                .unwrap();
-            if tolerance > 0 {
-                trigger_data.tolerance = tolerance;
+    // the transitions are of the condition, the deduplication doesn't resolve the alert
+    let firing = ret.is_some();
+    // the rows already counted toward the threshold, only the first row of a key within the
+    // dedup window is notified
+    let ret = match (ret, alert.deduplication.as_ref()) {
+        (Some(rows), Some(config)) => {
+            let rows = deduplication::dedup_rows(config, rows, &mut trigger_data.dedup_seen, now);
+            if rows.is_empty() {
+                log::info!(
+                    "Alert notifications deduplicated, org: {}, module_key: {}",
+                    &new_trigger.org,
+                    &new_trigger.module_key
+                );
+                None
+            } else {
+                Some(rows)
             }
-            tolerance
         }
-        _ => 0,
+        (ret, _) => ret,
     };
+    let ret = match flapping::record(
+        &FlappingConfig::from_config(),
+        &mut trigger_data.flapping,
+        firing,
+        now,
+    ) {
+        FlappingAction::Notify => ret,
+        FlappingAction::NotifyFlapping => {
+            log::warn!(
+                "Alert is flapping, notifications suppressed until it stabilizes, org: {}, module_key: {}",
+                &new_trigger.org,
+                &new_trigger.module_key
+            );
+            Some(flapping::flapping_notice(ret, &trigger_data.flapping))
+        }
+        FlappingAction::Suppress => {
+            if ret.is_some() {
+                log::info!(
+                    "Alert notifications suppressed while flapping, org: {}, module_key: {}",
+                    &new_trigger.org,
+                    &new_trigger.module_key
+                );
+            }
+            None
+        }
+    };
+    let tolerance = get_tolerance(&alert.trigger_condition, &new_trigger.module_key);
+    if tolerance > 0 {
+        trigger_data.tolerance = tolerance;
+    }
     if ret.is_some() && alert.trigger_condition.silence > 0 {
         if alert.trigger_condition.frequency_type == FrequencyType::Cron {
             let schedule = Schedule::from_str(&alert.trigger_condition.cron)?;
@@ -621,14 +715,10 @@ async fn handle_report_triggers(trigger: db::scheduler::Trigger) -> Result<(), a
             run_once = true;
         }
         ReportFrequencyType::Cron => {
-            let schedule = Schedule::from_str(&report.frequency.cron)?;
             // tz_offset is in minutes
-            let tz_offset = FixedOffset::east_opt(report.tz_offset * 60).unwrap();
-            new_trigger.next_run_at = schedule
-                .upcoming(tz_offset)
-                .next()
-                .unwrap()
-                .timestamp_micros();
+            let tz_offset = FixedOffset::east_opt(report.tz_offset * 60)
+                .ok_or_else(|| anyhow::anyhow!("invalid timezone offset: {}", report.tz_offset))?;
+            new_trigger.next_run_at = get_next_trigger_time(&report.frequency.cron, tz_offset)?;
         }
     }
 
@@ -716,6 +806,106 @@ async fn handle_report_triggers(trigger: db::scheduler::Trigger) -> Result<(), a
     Ok(())
 }
 
+async fn handle_scheduled_export_triggers(
+    trigger: db::scheduler::Trigger,
+) -> Result<(), anyhow::Error> {
+    log::debug!(
+        "Inside handle_scheduled_export_triggers, org: {}, module_key: {}",
+        &trigger.org,
+        &trigger.module_key
+    );
+    let org_id = &trigger.org;
+    // For scheduled export, trigger.module_key is the export name
+    let name = &trigger.module_key;
+
+    let export = db::saved_view::get_export(org_id, name).await?;
+    let mut new_trigger = db::scheduler::Trigger {
+        next_run_at: Utc::now().timestamp_micros(),
+        is_realtime: false,
+        is_silenced: false,
+        status: db::scheduler::TriggerStatus::Waiting,
+        retries: 0,
+        ..trigger.clone()
+    };
+
+    if !export.enabled {
+        log::debug!("Scheduled export not enabled: org: {org_id}, export: {name}");
+        // update trigger, check on next week
+        new_trigger.next_run_at += Duration::try_days(7).unwrap().num_microseconds().unwrap();
+        db::scheduler::update_trigger(new_trigger).await?;
+        return Ok(());
+    }
+    new_trigger.next_run_at = scheduled_export::next_run_at(&export)?;
+
+    let triggered_at = trigger.start_time.unwrap_or_default();
+    let processing_delay = triggered_at - trigger.next_run_at;
+    let mut trigger_data_stream = TriggerData {
+        _timestamp: triggered_at,
+        org: trigger.org.clone(),
+        module: TriggerDataType::ScheduledExport,
+        key: trigger.module_key.clone(),
+        next_run_at: new_trigger.next_run_at,
+        is_realtime: trigger.is_realtime,
+        is_silenced: trigger.is_silenced,
+        status: TriggerDataStatus::Completed,
+        start_time: trigger.start_time.unwrap_or_default(),
+        end_time: trigger.end_time.unwrap_or_default(),
+        retries: trigger.retries,
+        error: None,
+        success_response: None,
+        is_partial: None,
+        delay_in_secs: Some(Duration::microseconds(processing_delay).num_seconds()),
+        evaluation_took_in_secs: None,
+    };
+
+    // the query reads the period before the scheduled time of the run
+    let run = scheduled_export::run(org_id, &export, trigger.next_run_at).await;
+    if run.success {
+        log::info!(
+            "Scheduled export {org_id}/{name} wrote {} records to {}",
+            run.records,
+            run.path
+        );
+        db::scheduler::update_trigger(new_trigger).await?;
+    } else {
+        log::error!(
+            "Scheduled export {org_id}/{name} failed: {}",
+            run.error.as_deref().unwrap_or_default()
+        );
+        if trigger.retries + 1 >= get_config().limit.scheduler_max_retries {
+            // It has been tried the maximum time, just update the
+            // next_run_at to the next expected trigger time
+            db::scheduler::update_trigger(new_trigger).await?;
+        } else {
+            // Otherwise update its status only
+            db::scheduler::update_status(
+                &new_trigger.org,
+                new_trigger.module,
+                &new_trigger.module_key,
+                db::scheduler::TriggerStatus::Waiting,
+                trigger.retries + 1,
+            )
+            .await?;
+        }
+        trigger_data_stream.status = TriggerDataStatus::Failed;
+        trigger_data_stream.error = run.error.clone();
+    }
+    trigger_data_stream.end_time = Utc::now().timestamp_micros();
+
+    if let Err(e) = db::saved_view::add_export_run(org_id, name, run).await {
+        log::error!("Failed to save the run of scheduled export {org_id}/{name}: {e}");
+    }
+    // Check if the export has been changed in the mean time
+    let mut export = db::saved_view::get_export(org_id, name).await?;
+    export.last_triggered_at = Some(triggered_at);
+    if let Err(e) = db::saved_view::set_export_without_updating_trigger(org_id, &export).await {
+        log::error!("Failed to update scheduled export: {org_id}/{name} after trigger: {e}");
+    }
+    publish_triggers_usage(trigger_data_stream).await;
+
+    Ok(())
+}
+
 async fn handle_derived_stream_triggers(
     trigger: db::scheduler::Trigger,
 ) -> Result<(), anyhow::Error> {
@@ -803,6 +993,8 @@ async fn handle_derived_stream_triggers(
     new_trigger.data = json::to_string(&ScheduledTriggerData {
         period_end_time: Some(end_time),
         tolerance: 0,
+        dedup_seen: HashMap::new(),
+        flapping: Default::default(),
     })
     .unwrap();
     if ret.is_some() && derived_stream.trigger_condition.silence > 0 {
@@ -842,6 +1034,16 @@ async fn handle_derived_stream_triggers(
                 .unwrap();
     }
 
+    // start time of the evaluated period of the source stream
+    let period_start_time = if let Some(start_time) = start_time {
+        start_time
+    } else {
+        end_time
+            - Duration::try_minutes(derived_stream.trigger_condition.period)
+                .unwrap()
+                .num_microseconds()
+                .unwrap()
+    };
     let mut trigger_data_stream = TriggerData {
         _timestamp: trigger.start_time.unwrap_or_default(),
         org: trigger.org,
@@ -851,15 +1053,7 @@ async fn handle_derived_stream_triggers(
         is_realtime: trigger.is_realtime,
         is_silenced: trigger.is_silenced,
         status: TriggerDataStatus::Completed,
-        start_time: if let Some(start_time) = start_time {
-            start_time
-        } else {
-            end_time
-                - Duration::try_minutes(derived_stream.trigger_condition.period)
-                    .unwrap()
-                    .num_microseconds()
-                    .unwrap()
-        },
+        start_time: period_start_time,
         end_time: trigger.end_time.unwrap_or_default(),
         retries: trigger.retries,
         error: None,
@@ -873,7 +1067,10 @@ async fn handle_derived_stream_triggers(
     if let Some(data) = ret {
         let local_val = data
             .into_iter()
-            .map(json::Value::Object)
+            .map(|mut row| {
+                derived_stream.align_timestamp(&mut row, period_start_time, end_time);
+                json::Value::Object(row)
+            })
             .collect::<Vec<_>>();
         if local_val.is_empty() {
             log::info!(
@@ -957,3 +1154,69 @@ async fn handle_derived_stream_triggers(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::meta::alerts::alert::Alert, service::alerts::alert::evaluate_with_timeout,
+    };
+
+    #[tokio::test]
+    async fn test_timed_out_evaluation_is_rescheduled() {
+        let alert = Alert {
+            evaluation_timeout_secs: 1,
+            ..Default::default()
+        };
+        assert_eq!(alert.get_evaluation_timeout(), 1);
+        let ret: Result<(), _> = evaluate_with_timeout(
+            async {
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                Ok(())
+            },
+            std::time::Duration::from_millis(10),
+        )
+        .await;
+        let err = ret.unwrap_err();
+        assert!(err.is::<EvaluationTimeout>());
+        assert!(skip_to_next_run(err.is::<EvaluationTimeout>()));
+    }
+
+    #[test]
+    fn test_get_tolerance() {
+        let second = 1_000_000;
+        let mut trigger_condition = TriggerCondition {
+            period: 10,
+            frequency: 60,
+            ..Default::default()
+        };
+        assert_eq!(get_tolerance(&trigger_condition, "logs/default/alert1"), 0);
+
+        // random mode stays within the tolerance
+        trigger_condition.tolerance_in_secs = Some(30);
+        for _ in 0..100 {
+            let tolerance = get_tolerance(&trigger_condition, "logs/default/alert1");
+            assert!((0..30 * second).contains(&tolerance));
+        }
+
+        // deterministic mode is stable for an alert and within the tolerance
+        trigger_condition.tolerance_mode = ToleranceMode::Deterministic;
+        let tolerance = get_tolerance(&trigger_condition, "logs/default/alert1");
+        assert!((0..30 * second).contains(&tolerance));
+        assert_eq!(tolerance % second, 0);
+        for _ in 0..10 {
+            assert_eq!(
+                get_tolerance(&trigger_condition, "logs/default/alert1"),
+                tolerance
+            );
+        }
+        let tolerance = fnv::new().sum64("logs/default/alert2") % 30;
+        assert_eq!(
+            get_tolerance(&trigger_condition, "logs/default/alert2"),
+            tolerance as i64 * second
+        );
+
+        trigger_condition.tolerance_in_secs = Some(0);
+        assert_eq!(get_tolerance(&trigger_condition, "logs/default/alert1"), 0);
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Scoped API keys for machine clients. A key is sent as `Authorization: ApiKey <key>`, it acts
+//! as the user who created it but only for the operations of its scopes.
+
+use chrono::Utc;
+use config::utils::rand::generate_random_string;
+use infra::table::api_keys::{self, ApiKeyRecord};
+
+use crate::common::{
+    infra::config::API_KEYS,
+    meta::{
+        api_key::{ApiKey, ApiKeyScope, CreateApiKeyRequest, CreateApiKeyResponse},
+        ingestion::INGESTION_EP,
+    },
+};
+
+pub const API_KEY_PREFIX: &str = "o2k_";
+/// A cached key is looked up again after the interval, so a key revoked on another node stops
+/// working within it. The last used time is recorded on the lookup.
+const API_KEY_CACHE_TTL: i64 = 60_000_000; // microseconds
+
+/// Creates a key with the scopes, the key is only returned here
+pub async fn create(
+    org_id: &str,
+    user_id: &str,
+    req: CreateApiKeyRequest,
+) -> Result<CreateApiKeyResponse, anyhow::Error> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("API key name is required"));
+    }
+    if name.contains('/') {
+        return Err(anyhow::anyhow!("API key name cannot contain '/'"));
+    }
+    if req.scopes.is_empty() {
+        return Err(anyhow::anyhow!("API key should have at least one scope"));
+    }
+    let mut scopes = Vec::with_capacity(req.scopes.len());
+    for scope in req.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let key = format!("{API_KEY_PREFIX}{}", generate_random_string(40));
+    if !api_keys::add(
+        org_id,
+        name,
+        &sha256::digest(key.as_str()),
+        &format_scopes(&scopes),
+        user_id,
+    )
+    .await?
+    {
+        return Err(anyhow::anyhow!("API key {name} already exists"));
+    }
+    Ok(CreateApiKeyResponse {
+        name: name.to_string(),
+        key,
+        scopes,
+    })
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<ApiKey>, anyhow::Error> {
+    Ok(api_keys::list(org_id)
+        .await?
+        .into_iter()
+        .map(|record| ApiKey {
+            scopes: parse_scopes(&record.scopes),
+            name: record.name,
+            created_by: record.created_by,
+            created_at: record.created_ts,
+            last_used_at: (record.last_used_ts > 0).then_some(record.last_used_ts),
+        })
+        .collect())
+}
+
+/// Revokes the key, returns false if the org has no key with the name
+pub async fn delete(org_id: &str, name: &str) -> Result<bool, anyhow::Error> {
+    if !api_keys::remove(org_id, name).await? {
+        return Ok(false);
+    }
+    API_KEYS.retain(|_, (record, _)| record.org != org_id || record.name != name);
+    Ok(true)
+}
+
+/// Returns the key if it belongs to the org and its scopes allow the request, the `path` is
+/// relative to `/api/` and starts with the org
+pub async fn validate(key: &str, method: &str, path: &str) -> Result<ApiKeyRecord, anyhow::Error> {
+    let Some(record) = get(key).await? else {
+        return Err(anyhow::anyhow!("Invalid API key"));
+    };
+    let org_id = path.split('/').next().unwrap_or_default();
+    if record.org != org_id {
+        return Err(anyhow::anyhow!("API key doesn't belong to org {org_id}"));
+    }
+    let required = required_scope(method, path);
+    if !is_allowed(&parse_scopes(&record.scopes), required) {
+        return Err(anyhow::anyhow!("API key has no {required} scope"));
+    }
+    Ok(record)
+}
+
+/// Looks up the key, from the cache while it is fresh, else from the db recording the time
+async fn get(key: &str) -> Result<Option<ApiKeyRecord>, anyhow::Error> {
+    if !key.starts_with(API_KEY_PREFIX) {
+        return Ok(None);
+    }
+    let now = Utc::now().timestamp_micros();
+    let key_hash = sha256::digest(key);
+    if let Some(entry) = API_KEYS.get(&key_hash) {
+        let (record, cached_at) = entry.value();
+        if now - cached_at < API_KEY_CACHE_TTL {
+            return Ok(Some(record.clone()));
+        }
+    }
+    let Some(mut record) = api_keys::get_by_hash(&key_hash).await? else {
+        API_KEYS.remove(&key_hash);
+        return Ok(None);
+    };
+    if let Err(e) = api_keys::set_last_used(&key_hash, now).await {
+        log::error!(
+            "[API_KEY] set last used of {}/{} error: {e}",
+            record.org,
+            record.name
+        );
+    }
+    record.last_used_ts = now;
+    API_KEYS.insert(key_hash, (record.clone(), now));
+    Ok(Some(record))
+}
+
+/// The scope an operation needs, the ingestion and the search/query endpoints need their own
+/// scope, every other endpoint needs the admin scope
+pub fn required_scope(method: &str, path: &str) -> ApiKeyScope {
+    let path_columns = path.trim_end_matches('/').split('/').collect::<Vec<_>>();
+    let last = path_columns.last().copied().unwrap_or_default();
+    if method == "POST" && INGESTION_EP.contains(&last) {
+        return ApiKeyScope::Ingest;
+    }
+    let second = path_columns.get(1).copied().unwrap_or_default();
+    if second.starts_with("_search")
+        || (second == "prometheus" && last != "write")
+        || ["_around", "_values", "_count"].contains(&last)
+        || path.ends_with("/traces/latest")
+        || (method == "GET" && second == "streams")
+    {
+        return ApiKeyScope::Search;
+    }
+    ApiKeyScope::Admin
+}
+
+/// Returns if the scopes allow an operation needing the scope
+pub fn is_allowed(scopes: &[ApiKeyScope], required: ApiKeyScope) -> bool {
+    scopes
+        .iter()
+        .any(|scope| *scope == ApiKeyScope::Admin || *scope == required)
+}
+
+fn format_scopes(scopes: &[ApiKeyScope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_scopes(scopes: &str) -> Vec<ApiKeyScope> {
+    scopes.split(',').filter_map(|s| s.parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(
+            required_scope("POST", "default/app/_json"),
+            ApiKeyScope::Ingest
+        );
+        assert_eq!(required_scope("POST", "default/_bulk"), ApiKeyScope::Ingest);
+        assert_eq!(
+            required_scope("POST", "default/v1/logs"),
+            ApiKeyScope::Ingest
+        );
+        assert_eq!(
+            required_scope("POST", "default/prometheus/api/v1/write"),
+            ApiKeyScope::Ingest
+        );
+        assert_eq!(
+            required_scope("POST", "default/_search"),
+            ApiKeyScope::Search
+        );
+        assert_eq!(
+            required_scope("GET", "default/prometheus/api/v1/query"),
+            ApiKeyScope::Search
+        );
+        assert_eq!(
+            required_scope("GET", "default/app/_values"),
+            ApiKeyScope::Search
+        );
+        assert_eq!(
+            required_scope("GET", "default/streams"),
+            ApiKeyScope::Search
+        );
+        assert_eq!(
+            required_scope("DELETE", "default/streams/app"),
+            ApiKeyScope::Admin
+        );
+        assert_eq!(required_scope("POST", "default/users"), ApiKeyScope::Admin);
+        assert_eq!(
+            required_scope("GET", "default/api_keys"),
+            ApiKeyScope::Admin
+        );
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let ingest = [ApiKeyScope::Ingest];
+        let search = [ApiKeyScope::Search];
+        // an ingest only key can't search and a search only key can't ingest
+        assert!(is_allowed(
+            &ingest,
+            required_scope("POST", "default/app/_json")
+        ));
+        assert!(!is_allowed(
+            &ingest,
+            required_scope("POST", "default/_search")
+        ));
+        assert!(is_allowed(
+            &search,
+            required_scope("POST", "default/_search")
+        ));
+        assert!(!is_allowed(
+            &search,
+            required_scope("POST", "default/app/_json")
+        ));
+        assert!(!is_allowed(
+            &search,
+            required_scope("DELETE", "default/streams/app")
+        ));
+
+        let admin = [ApiKeyScope::Admin];
+        assert!(is_allowed(&admin, ApiKeyScope::Search));
+        assert!(is_allowed(&admin, ApiKeyScope::Ingest));
+        assert!(is_allowed(&admin, ApiKeyScope::Admin));
+    }
+
+    #[test]
+    fn test_scopes() {
+        let scopes = vec![ApiKeyScope::Search, ApiKeyScope::Ingest];
+        assert_eq!(format_scopes(&scopes), "search,ingest");
+        assert_eq!(parse_scopes("search,ingest"), scopes);
+        assert_eq!(parse_scopes("search,unknown"), vec![ApiKeyScope::Search]);
+    }
+}
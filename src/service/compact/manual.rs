@@ -0,0 +1,374 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compaction of a stream time range requested manually, e.g. after a backfill. The jobs are
+//! stored in the db and executed by the compactors after the scheduled merge jobs, the
+//! partitions are locked by [`merge::merge_by_stream`] so the same files are not merged twice.
+//! A running job is cancelled between two partitions, so a partition is either merged or left
+//! untouched.
+
+use std::future::Future;
+
+use chrono::Duration;
+use config::{
+    cluster::LOCAL_NODE,
+    get_config, ider,
+    meta::stream::{PartitionTimeLevel, StreamType},
+    utils::time::now_micros,
+};
+use infra::{dist_lock, schema::unwrap_partition_time_level};
+use tokio::sync::mpsc;
+
+use super::merge::{self, MergeBatch, MergeSender};
+use crate::{
+    common::{
+        infra::cluster::get_node_by_uuid,
+        meta::stream::{CompactJob, CompactJobStatus},
+    },
+    service::db,
+};
+
+/// Adds a job to compact the time range of the stream, returns the job to poll
+pub async fn add_job(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<CompactJob, anyhow::Error> {
+    if start_time <= 0 || end_time <= start_time {
+        return Err(anyhow::anyhow!("end_time must be greater than start_time"));
+    }
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(anyhow::anyhow!("stream [{stream_name}] not found"));
+    }
+    let settings = infra::schema::get_settings(org_id, stream_name, stream_type)
+        .await
+        .unwrap_or_default();
+    let partition_time_level =
+        unwrap_partition_time_level(settings.partition_time_level, stream_type);
+
+    let now = now_micros();
+    let job = CompactJob {
+        id: ider::uuid(),
+        org_id: org_id.to_string(),
+        stream_type,
+        stream_name: stream_name.to_string(),
+        start_time,
+        end_time,
+        status: CompactJobStatus::Pending,
+        total_buckets: buckets(start_time, end_time, partition_time_level).len(),
+        created_at: now,
+        updated_at: now,
+        ..Default::default()
+    };
+    db::compact::manual::set(&job).await?;
+    Ok(job)
+}
+
+pub async fn get_job(org_id: &str, job_id: &str) -> Result<Option<CompactJob>, anyhow::Error> {
+    db::compact::manual::get(org_id, job_id).await
+}
+
+/// Cancels the job, a pending job is cancelled at once and a running job stops before its next
+/// partition, on whichever node it runs. Returns None if the job doesn't exist.
+pub async fn cancel_job(org_id: &str, job_id: &str) -> Result<Option<CompactJob>, anyhow::Error> {
+    let Some(mut job) = db::compact::manual::get(org_id, job_id).await? else {
+        return Ok(None);
+    };
+    match job.status {
+        CompactJobStatus::Pending | CompactJobStatus::Running => {}
+        _ => return Err(anyhow::anyhow!("the job is already finished")),
+    }
+    // a compactor may claim the pending job meanwhile, it stops as soon as it starts
+    db::compact::manual::cancel(org_id, job_id).await?;
+    if job.status == CompactJobStatus::Pending {
+        job.status = CompactJobStatus::Cancelled;
+        job.updated_at = now_micros();
+        db::compact::manual::set(&job).await?;
+    }
+    Ok(Some(job))
+}
+
+/// Runs the pending jobs, and the running jobs of the compactors which are gone, one by one
+pub async fn run(worker_tx: mpsc::Sender<(MergeSender, MergeBatch)>) -> Result<(), anyhow::Error> {
+    let clean_before = now_micros() - get_config().compact.job_clean_wait_time * 1_000_000;
+    for job in db::compact::manual::list().await? {
+        match job.status {
+            CompactJobStatus::Pending => {}
+            CompactJobStatus::Running
+                if job.node != LOCAL_NODE.uuid && get_node_by_uuid(&job.node).await.is_none() => {}
+            CompactJobStatus::Completed
+            | CompactJobStatus::Failed
+            | CompactJobStatus::Cancelled
+                if job.updated_at < clean_before =>
+            {
+                db::compact::manual::delete(&job.org_id, &job.id).await?;
+                db::compact::manual::cancel_done(&job.org_id, &job.id).await?;
+                continue;
+            }
+            _ => continue,
+        }
+        if let Some(job) = claim(&job).await? {
+            run_job(worker_tx.clone(), job).await;
+        }
+    }
+    Ok(())
+}
+
+/// Marks the job running on this node, returns None if another node claimed it first
+async fn claim(job: &CompactJob) -> Result<Option<CompactJob>, anyhow::Error> {
+    let lock_key = format!("/compact/manual/{}/{}", job.org_id, job.id);
+    let locker = dist_lock::lock(&lock_key, 0, None).await?;
+    // check the job again, maybe other node claimed it first
+    let ret = match db::compact::manual::get(&job.org_id, &job.id).await {
+        Ok(Some(mut job))
+            if job.status == CompactJobStatus::Pending
+                || (job.status == CompactJobStatus::Running
+                    && get_node_by_uuid(&job.node).await.is_none()) =>
+        {
+            job.status = CompactJobStatus::Running;
+            job.node = LOCAL_NODE.uuid.clone();
+            job.updated_at = now_micros();
+            db::compact::manual::set(&job).await.map(|_| Some(job))
+        }
+        Ok(_) => Ok(None),
+        Err(e) => Err(e),
+    };
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+async fn run_job(worker_tx: mpsc::Sender<(MergeSender, MergeBatch)>, mut job: CompactJob) {
+    let settings = infra::schema::get_settings(&job.org_id, &job.stream_name, job.stream_type)
+        .await
+        .unwrap_or_default();
+    let partition_time_level =
+        unwrap_partition_time_level(settings.partition_time_level, job.stream_type);
+    let buckets = buckets(job.start_time, job.end_time, partition_time_level);
+    job.total_buckets = buckets.len();
+
+    let (org_id, stream_type, stream_name) =
+        (job.org_id.clone(), job.stream_type, job.stream_name.clone());
+    merge_buckets(&mut job, &buckets, |offset| {
+        let worker_tx = worker_tx.clone();
+        let org_id = org_id.clone();
+        let stream_name = stream_name.clone();
+        async move {
+            merge::merge_by_stream(worker_tx, &org_id, stream_type, &stream_name, None, offset)
+                .await
+        }
+    })
+    .await;
+    if let Err(e) = db::compact::manual::set(&job).await {
+        log::error!("[COMPACTOR] save manual job {} error: {e}", job.id);
+    }
+    if job.status == CompactJobStatus::Cancelled {
+        if let Err(e) = db::compact::manual::cancel_done(&job.org_id, &job.id).await {
+            log::error!("[COMPACTOR] clear manual job {} cancel error: {e}", job.id);
+        }
+    }
+
+    // after compact, compact file list from storage
+    if !get_config().common.meta_store_external {
+        if let Err(e) = super::file_list::run(job.start_time).await {
+            log::error!("[COMPACTOR] merge file list error: {}", e);
+        }
+    }
+}
+
+/// Merges the buckets left one by one. The cancellation is checked before each bucket, the
+/// merge of a bucket is never interrupted so its files are either merged or untouched.
+async fn merge_buckets<F, Fut>(job: &mut CompactJob, buckets: &[i64], mut merge_bucket: F)
+where
+    F: FnMut(i64) -> Fut,
+    Fut: Future<Output = Result<(), anyhow::Error>>,
+{
+    // continue from the last processed bucket if the job was taken over from another node
+    for offset in buckets.iter().skip(job.processed_buckets) {
+        if db::compact::manual::is_cancelled(&job.org_id, &job.id) {
+            log::info!(
+                "[COMPACTOR] manual job {} cancelled after {}/{} buckets",
+                job.id,
+                job.processed_buckets,
+                job.total_buckets
+            );
+            job.status = CompactJobStatus::Cancelled;
+            break;
+        }
+        if db::compact::retention::is_deleting_stream(
+            &job.org_id,
+            job.stream_type,
+            &job.stream_name,
+            None,
+        ) {
+            job.status = CompactJobStatus::Failed;
+            job.error = "the stream is being deleted".to_string();
+            break;
+        }
+        if let Err(e) = merge_bucket(*offset).await {
+            log::error!(
+                "[COMPACTOR] manual job {} merge [{}/{}/{}] offset {offset} error: {e}",
+                job.id,
+                job.org_id,
+                job.stream_type,
+                job.stream_name
+            );
+            job.status = CompactJobStatus::Failed;
+            job.error = e.to_string();
+            break;
+        }
+        job.processed_buckets += 1;
+        job.updated_at = now_micros();
+        if let Err(e) = db::compact::manual::set(job).await {
+            log::error!("[COMPACTOR] save manual job {} error: {e}", job.id);
+        }
+    }
+    if job.status == CompactJobStatus::Running {
+        job.status = CompactJobStatus::Completed;
+    }
+    job.updated_at = now_micros();
+}
+
+/// Returns the start of each partition (hour or day) overlapping the time range
+fn buckets(start_time: i64, end_time: i64, partition_time_level: PartitionTimeLevel) -> Vec<i64> {
+    let step = if partition_time_level == PartitionTimeLevel::Daily {
+        Duration::try_hours(24).unwrap()
+    } else {
+        Duration::try_hours(1).unwrap()
+    }
+    .num_microseconds()
+    .unwrap();
+    let mut offset = merge::partition_start(start_time, partition_time_level);
+    let mut buckets = Vec::new();
+    while offset < end_time {
+        buckets.push(offset);
+        offset += step;
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        let hour = 3600 * 1_000_000;
+        // 2024-07-01T00:00:00Z
+        let day = 1719792000000000;
+        assert_eq!(
+            buckets(
+                day + hour / 2,
+                day + 2 * hour + hour / 2,
+                PartitionTimeLevel::Hourly
+            ),
+            vec![day, day + hour, day + 2 * hour]
+        );
+        assert_eq!(
+            buckets(day, day + 2 * hour, PartitionTimeLevel::Hourly),
+            vec![day, day + hour]
+        );
+        assert_eq!(
+            buckets(day + hour, day + 25 * hour, PartitionTimeLevel::Daily),
+            vec![day, day + 24 * hour]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_job() {
+        let mut job = CompactJob {
+            id: "test_cancel_running_job".to_string(),
+            org_id: "default".to_string(),
+            stream_type: StreamType::Logs,
+            stream_name: "compact_cancel".to_string(),
+            status: CompactJobStatus::Running,
+            total_buckets: 4,
+            ..Default::default()
+        };
+        // the file list of each partition, the files with their records
+        let file_list = Arc::new(Mutex::new(
+            (1..=4)
+                .map(|offset| {
+                    let files = (0..3)
+                        .map(|i| (format!("{offset}/{i}.parquet"), 10 * offset + i))
+                        .collect::<Vec<_>>();
+                    (offset, files)
+                })
+                .collect::<std::collections::HashMap<_, _>>(),
+        ));
+        let original = file_list.lock().clone();
+        let merged = Arc::new(Mutex::new(Vec::new()));
+        merge_buckets(&mut job, &[1, 2, 3, 4], |offset| {
+            let file_list = file_list.clone();
+            let merged = merged.clone();
+            async move {
+                merged.lock().push(offset);
+                // the merged file is added before the merged files are removed
+                let records = file_list.lock()[&offset].iter().map(|f| f.1).sum();
+                file_list
+                    .lock()
+                    .get_mut(&offset)
+                    .unwrap()
+                    .push((format!("{offset}/merged.parquet"), records));
+                // cancelled while merging the second bucket
+                if offset == 2 {
+                    db::compact::manual::cancel("default", "test_cancel_running_job")
+                        .await
+                        .unwrap();
+                }
+                file_list
+                    .lock()
+                    .get_mut(&offset)
+                    .unwrap()
+                    .retain(|f| f.0.ends_with("merged.parquet"));
+                Ok(())
+            }
+        })
+        .await;
+
+        // the merge of the second bucket is finished, the others are not started
+        assert_eq!(job.status, CompactJobStatus::Cancelled);
+        assert_eq!(job.processed_buckets, 2);
+        assert_eq!(*merged.lock(), vec![1, 2]);
+
+        // the merged partitions have only the merged file, with all the records, and the other
+        // partitions are untouched
+        let file_list = file_list.lock();
+        for offset in [1, 2] {
+            let records: i64 = original[&offset].iter().map(|f| f.1).sum();
+            assert_eq!(
+                file_list[&offset],
+                vec![(format!("{offset}/merged.parquet"), records)]
+            );
+        }
+        for offset in [3, 4] {
+            assert_eq!(file_list[&offset], original[&offset]);
+        }
+
+        db::compact::manual::cancel_done("default", "test_cancel_running_job")
+            .await
+            .unwrap();
+        assert!(!db::compact::manual::is_cancelled(
+            "default",
+            "test_cancel_running_job"
+        ));
+    }
+}
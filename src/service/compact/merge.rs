@@ -201,6 +201,52 @@ pub async fn generate_job_by_stream(
     Ok(())
 }
 
+/// Merges the files of the partition (hour or day) of the offset. The partition is locked as the
+/// scheduled and the manual compaction can work on it at the same time, `job_id` is the merge
+/// job of the scheduled compaction.
+pub async fn merge_by_stream(
+    worker_tx: mpsc::Sender<(MergeSender, MergeBatch)>,
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    job_id: Option<i64>,
+    offset: i64,
+) -> Result<(), anyhow::Error> {
+    let stream_settings = infra::schema::get_settings(org_id, stream_name, stream_type)
+        .await
+        .unwrap_or_default();
+    let partition_time_level =
+        unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
+    let lock_key = format!(
+        "/compact/merge_partition/{org_id}/{stream_type}/{stream_name}/{}",
+        partition_start(offset, partition_time_level)
+    );
+    let locker = dist_lock::lock(&lock_key, 0, None).await?;
+    let ret = merge_partition(worker_tx, org_id, stream_type, stream_name, job_id, offset).await;
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+/// Returns the start of the hour, or the day for daily partitions, of the offset
+pub fn partition_start(offset: i64, partition_time_level: PartitionTimeLevel) -> i64 {
+    let offset_time: DateTime<Utc> = Utc.timestamp_nanos(offset * 1000);
+    let hour = if partition_time_level == PartitionTimeLevel::Daily {
+        0
+    } else {
+        offset_time.hour()
+    };
+    Utc.with_ymd_and_hms(
+        offset_time.year(),
+        offset_time.month(),
+        offset_time.day(),
+        hour,
+        0,
+        0,
+    )
+    .unwrap()
+    .timestamp_micros()
+}
+
 /// compactor run steps on a stream:
 /// 3. get a cluster lock for compactor stream
 /// 4. read last compacted offset: year/month/day/hour
@@ -211,12 +257,12 @@ pub async fn generate_job_by_stream(
 /// 9. delete small files from storage
 /// 10. update last compacted offset
 /// 11. release cluster lock
-pub async fn merge_by_stream(
+async fn merge_partition(
     worker_tx: mpsc::Sender<(MergeSender, MergeBatch)>,
     org_id: &str,
     stream_type: StreamType,
     stream_name: &str,
-    job_id: i64,
+    job_id: Option<i64>,
     offset: i64,
 ) -> Result<(), anyhow::Error> {
     let start = std::time::Instant::now();
@@ -335,8 +381,10 @@ pub async fn merge_by_stream(
 
     if files.is_empty() {
         // update job status
-        if let Err(e) = infra_file_list::set_job_done(job_id).await {
-            log::error!("[COMPACT] set_job_done failed: {e}");
+        if let Some(job_id) = job_id {
+            if let Err(e) = infra_file_list::set_job_done(job_id).await {
+                log::error!("[COMPACT] set_job_done failed: {e}");
+            }
         }
         return Ok(());
     }
@@ -381,45 +429,22 @@ pub async fn merge_by_stream(
             }
 
             // group files need to merge
-            let mut batch_groups = Vec::new();
-            let mut new_file_list = Vec::new();
-            let mut new_file_size = 0;
-            for file in files_with_size.iter() {
-                if new_file_size + file.meta.original_size > cfg.compact.max_file_size as i64 {
-                    if new_file_list.len() <= 1 {
-                        break; // no files need to merge
-                    }
-                    batch_groups.push(MergeBatch {
-                        batch_id: batch_groups.len(),
-                        org_id: org_id.clone(),
-                        stream_type,
-                        stream_name: stream_name.clone(),
-                        prefix: prefix.clone(),
-                        files: new_file_list.clone(),
-                    });
-                    new_file_size = 0;
-                    new_file_list.clear();
-                }
-                new_file_size += file.meta.original_size;
-                new_file_list.push(file.clone());
-                // metrics
-                metrics::COMPACT_MERGED_FILES
-                    .with_label_values(&[&org_id, stream_type.to_string().as_str()])
-                    .inc();
-                metrics::COMPACT_MERGED_BYTES
-                    .with_label_values(&[&org_id, stream_type.to_string().as_str()])
-                    .inc_by(file.meta.original_size as u64);
-            }
-            if new_file_list.len() > 1 {
-                batch_groups.push(MergeBatch {
-                    batch_id: batch_groups.len(),
-                    org_id: org_id.clone(),
-                    stream_type,
-                    stream_name: stream_name.clone(),
-                    prefix: prefix.clone(),
-                    files: new_file_list.clone(),
-                });
-            }
+            let batch_groups = group_files(
+                &files_with_size,
+                cfg.compact.max_file_size as i64,
+                cfg.compact.min_files_to_merge,
+            )
+            .into_iter()
+            .enumerate()
+            .map(|(batch_id, files)| MergeBatch {
+                batch_id,
+                org_id: org_id.clone(),
+                stream_type,
+                stream_name: stream_name.clone(),
+                prefix: prefix.clone(),
+                files,
+            })
+            .collect::<Vec<_>>();
 
             if batch_groups.is_empty() {
                 return Ok(()); // no files need to merge
@@ -508,8 +533,10 @@ pub async fn merge_by_stream(
     }
 
     // update job status
-    if let Err(e) = infra_file_list::set_job_done(job_id).await {
-        log::error!("[COMPACT] set_job_done failed: {e}");
+    if let Some(job_id) = job_id {
+        if let Err(e) = infra_file_list::set_job_done(job_id).await {
+            log::error!("[COMPACT] set_job_done failed: {e}");
+        }
     }
 
     // update stream stats
@@ -539,6 +566,31 @@ pub async fn merge_by_stream(
     Ok(())
 }
 
+/// Groups the sorted files of a partition up to the target size, a group with fewer than
+/// `min_files` files is not worth merging and its files are left as they are
+fn group_files(files: &[FileKey], target_size: i64, min_files: usize) -> Vec<Vec<FileKey>> {
+    let min_files = std::cmp::max(2, min_files);
+    let mut groups = Vec::new();
+    let mut group = Vec::new();
+    let mut group_size = 0;
+    for file in files.iter() {
+        if group_size + file.meta.original_size > target_size {
+            if group.len() >= min_files {
+                groups.push(std::mem::take(&mut group));
+            } else {
+                group.clear();
+            }
+            group_size = 0;
+        }
+        group_size += file.meta.original_size;
+        group.push(file.clone());
+    }
+    if group.len() >= min_files {
+        groups.push(group);
+    }
+    groups
+}
+
 /// merge some small files into one big file, upload to storage, returns the big
 /// file key and merged files
 pub async fn merge_files(
@@ -637,6 +689,7 @@ pub async fn merge_files(
         original_size: new_file_size,
         compressed_size: 0,
         flattened: false,
+        index_size: 0,
     };
     if new_file_meta.records == 0 {
         return Err(anyhow::anyhow!("merge_parquet_files error: records is 0"));
@@ -784,6 +837,9 @@ pub async fn merge_files(
             "merge_parquet_files error: compressed_size is 0"
         ));
     }
+    metrics::COMPACT_MERGED_FILE_SIZE
+        .with_label_values(&[org_id, stream_type.to_string().as_str()])
+        .observe(new_file_meta.compressed_size as f64 / 1024.0 / 1024.0);
 
     let id = ider::generate();
     let new_file_key = format!("{prefix}/{id}{}", FILE_EXT_PARQUET);
@@ -864,14 +920,14 @@ pub async fn merge_files(
                         InvertedIndexFormat::FST | InvertedIndexFormat::Both
                     ) {
                         // generate fst inverted index and write to storage
-                        generate_fst_inverted_index(
+                        new_file_meta.index_size = generate_fst_inverted_index(
                             inverted_idx_batch,
                             &new_file_key,
                             &full_text_search_fields,
                             &index_fields,
                             Some(&retain_file_list),
                         )
-                        .await?;
+                        .await? as i64;
                     }
                 }
             }
@@ -1202,3 +1258,53 @@ pub async fn merge_parquet_files(
 
     Ok((schema, vec![new_record_batches]))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(key: &str, size: i64) -> FileKey {
+        let meta = FileMeta {
+            original_size: size,
+            ..Default::default()
+        };
+        FileKey::new(key, meta, false)
+    }
+
+    fn keys(groups: &[Vec<FileKey>]) -> Vec<Vec<&str>> {
+        groups
+            .iter()
+            .map(|g| g.iter().map(|f| f.key.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_group_files() {
+        let files = vec![
+            file("a", 10),
+            file("b", 20),
+            file("c", 30),
+            file("d", 40),
+            file("e", 50),
+            file("f", 60),
+        ];
+        // every group is up to the target size
+        assert_eq!(keys(&group_files(&files, 60, 2)), vec![vec!["a", "b", "c"]]);
+        assert_eq!(
+            keys(&group_files(&files, 110, 2)),
+            vec![vec!["a", "b", "c", "d"], vec!["e", "f"]]
+        );
+        // the groups with fewer files than the threshold are not merged
+        assert_eq!(
+            keys(&group_files(&files, 110, 3)),
+            vec![vec!["a", "b", "c", "d"]]
+        );
+        assert!(group_files(&files, 110, 5).is_empty());
+        assert_eq!(
+            keys(&group_files(&files, 1000, 5)),
+            vec![vec!["a", "b", "c", "d", "e", "f"]]
+        );
+        // a file over the target size is never merged
+        assert!(group_files(&[file("a", 200), file("b", 10)], 100, 2).is_empty());
+    }
+}
@@ -33,8 +33,10 @@ use crate::{common::infra::cluster::get_node_from_consistent_hash, service::db};
 mod file_list;
 pub mod file_list_deleted;
 pub mod flatten;
+pub mod manual;
 pub mod merge;
 pub mod retention;
+pub mod rollup;
 pub mod stats;
 
 /// compactor retention run steps:
@@ -309,6 +311,22 @@ pub async fn run_merge(
             );
             continue;
         }
+        // the files of the job are deleting by the data retention, merging them would write the
+        // expired data back
+        let job_date = Utc
+            .timestamp_nanos(job.offsets * 1000)
+            .format("%Y-%m-%d")
+            .to_string();
+        if db::compact::retention::is_deleting_date(&org_id, stream_type, &stream_name, &job_date) {
+            log::warn!(
+                "[COMPACTOR] the stream [{}/{}/{}] date {} is deleting, just skip",
+                &org_id,
+                stream_type,
+                &stream_name,
+                job_date,
+            );
+            continue;
+        }
 
         let org_id = org_id.clone();
         let permit = semaphore.clone().acquire_owned().await.unwrap();
@@ -319,7 +337,7 @@ pub async fn run_merge(
                 &org_id,
                 stream_type,
                 &stream_name,
-                job.id,
+                Some(job.id),
                 job.offsets,
             )
             .await
@@ -23,6 +23,7 @@ use config::{
     cluster::LOCAL_NODE,
     get_config, ider, is_local_disk_storage,
     meta::stream::{FileKey, FileMeta, PartitionTimeLevel, StreamStats, StreamType},
+    metrics,
     utils::{json, time::BASE_TIME},
 };
 use infra::{cache, dist_lock, file_list as infra_file_list, storage};
@@ -138,7 +139,14 @@ pub async fn delete_all(
     }
 
     // delete from file list
-    delete_from_file_list(org_id, stream_type, stream_name, (start_time, end_time)).await?;
+    delete_from_file_list(
+        org_id,
+        stream_type,
+        stream_name,
+        (start_time, end_time),
+        None,
+    )
+    .await?;
     log::info!(
         "deleted file list for: {}/{}/{}/all",
         org_id,
@@ -258,8 +266,15 @@ pub async fn delete_by_date(
         }
     }
 
-    // delete from file list
-    delete_from_file_list(org_id, stream_type, stream_name, time_range).await?;
+    // delete from file list, only the files which are entirely before the end date
+    delete_from_file_list(
+        org_id,
+        stream_type,
+        stream_name,
+        time_range,
+        Some(time_range.1),
+    )
+    .await?;
 
     // archive old schema versions
     let mut schema_versions =
@@ -312,8 +327,9 @@ async fn delete_from_file_list(
     stream_type: StreamType,
     stream_name: &str,
     time_range: (i64, i64),
+    expired_before: Option<i64>,
 ) -> Result<(), anyhow::Error> {
-    let files = file_list::query(
+    let mut files = file_list::query(
         org_id,
         stream_name,
         stream_type,
@@ -322,12 +338,16 @@ async fn delete_from_file_list(
         time_range.1,
     )
     .await?;
+    if let Some(end_time) = expired_before {
+        files = select_expired_files(files, end_time);
+    }
     if files.is_empty() {
         return Ok(());
     }
 
     // collect stream stats
     let mut stream_stats = StreamStats::default();
+    let reclaimed_bytes = files.iter().map(|f| f.meta.compressed_size).sum::<i64>();
 
     let mut file_list_days: HashSet<String> = HashSet::new();
     let mut hours_files: HashMap<String, Vec<FileKey>> = HashMap::with_capacity(24);
@@ -353,6 +373,10 @@ async fn delete_from_file_list(
     // write file list to storage
     write_file_list(org_id, file_list_days, hours_files).await?;
 
+    metrics::COMPACT_RETENTION_RECLAIMED_BYTES
+        .with_label_values(&[org_id, stream_name, stream_type.to_string().as_str()])
+        .inc_by(reclaimed_bytes.max(0) as u64);
+
     // update stream stats
     if stream_stats.doc_num != 0 {
         infra_file_list::set_stream_stats(
@@ -368,6 +392,15 @@ async fn delete_from_file_list(
     Ok(())
 }
 
+/// Selects the files which are entirely before the end time, a file overlapping the end time
+/// still holds data in the retention period
+fn select_expired_files(files: Vec<FileKey>, end_time: i64) -> Vec<FileKey> {
+    files
+        .into_iter()
+        .filter(|f| f.meta.max_ts < end_time)
+        .collect()
+}
+
 async fn write_file_list(
     org_id: &str,
     file_list_days: HashSet<String>,
@@ -529,6 +562,36 @@ mod tests {
             .unwrap();
     }
 
+    fn file(key: &str, min_ts: i64, max_ts: i64) -> FileKey {
+        FileKey {
+            key: key.to_string(),
+            meta: FileMeta {
+                min_ts,
+                max_ts,
+                compressed_size: 10,
+                ..Default::default()
+            },
+            deleted: false,
+            segment_ids: None,
+        }
+    }
+
+    #[test]
+    fn test_select_expired_files() {
+        let end_time = 1_700_000_000_000_000;
+        let files = vec![
+            file("expired", end_time - 3_600_000_000, end_time - 1),
+            file("overlapping", end_time - 3_600_000_000, end_time + 1),
+            file("boundary", end_time, end_time + 3_600_000_000),
+            file("retained", end_time + 1, end_time + 3_600_000_000),
+        ];
+        let keys = select_expired_files(files, end_time)
+            .into_iter()
+            .map(|f| f.key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["expired".to_string()]);
+    }
+
     #[tokio::test]
     async fn test_delete_all() {
         infra_file_list::create_table().await.unwrap();
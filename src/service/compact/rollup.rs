@@ -0,0 +1,468 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+
+use arrow_schema::DataType;
+use config::{
+    cluster::LOCAL_NODE,
+    get_config, ider,
+    meta::{cluster::Role, search, stream::StreamType},
+    utils::{json, time::now_micros},
+};
+use infra::errors::{Error, ErrorCodes};
+use proto::cluster_rpc;
+
+use crate::{
+    common::{
+        infra::cluster::get_node_from_consistent_hash,
+        meta::prom::{
+            HASH_LABEL, ROLLUP_COUNT_LABEL, ROLLUP_LAST_LABEL, ROLLUP_MAX_LABEL, ROLLUP_MIN_LABEL,
+            ROLLUP_SUM_LABEL, VALUE_LABEL,
+        },
+    },
+    service::{db, ingestion::ingestion_service, search as SearchService},
+};
+
+const HOUR_MICROS: i64 = 3_600_000_000;
+/// Max number of windows rolled up per stream and resolution in one run
+const MAX_WINDOWS_PER_RUN: i64 = 24;
+const PAGE_SIZE: i64 = 10_000;
+
+/// A configured rollup resolution, the name is the canonical one used in the stream name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub name: String,
+    pub micros: i64,
+}
+
+/// Returns the configured rollup resolutions, from the finest to the coarsest
+pub fn get_resolutions() -> Vec<Resolution> {
+    parse_resolutions(&get_config().compact.metrics_rollup_resolutions)
+}
+
+fn parse_resolutions(resolutions: &str) -> Vec<Resolution> {
+    let mut resolutions = resolutions
+        .split(',')
+        .filter_map(|v| config::utils::time::parse_milliseconds(v.trim()).ok())
+        .filter(|v| *v >= 60_000)
+        .map(|v| {
+            let secs = (v / 1000) as i64;
+            Resolution {
+                name: format_resolution(secs),
+                micros: secs * 1_000_000,
+            }
+        })
+        .collect::<Vec<_>>();
+    resolutions.sort_by_key(|r| r.micros);
+    resolutions.dedup();
+    resolutions
+}
+
+fn format_resolution(secs: i64) -> String {
+    if secs % 86400 == 0 {
+        format!("{}d", secs / 86400)
+    } else if secs % 3600 == 0 {
+        format!("{}h", secs / 3600)
+    } else if secs % 60 == 0 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+pub fn rollup_stream_name(stream_name: &str, resolution: &Resolution) -> String {
+    format!("{stream_name}_rollup_{}", resolution.name)
+}
+
+/// Checks if the rollup streams are generated for the metrics stream, the rollup streams
+/// themselves are never rolled up again
+pub fn is_rollup_enabled(stream_name: &str, resolutions: &[Resolution]) -> bool {
+    let cfg = get_config();
+    let streams = cfg.compact.metrics_rollup_streams.trim();
+    if streams.is_empty() {
+        return false;
+    }
+    if resolutions
+        .iter()
+        .any(|r| stream_name.ends_with(&format!("_rollup_{}", r.name)))
+    {
+        return false;
+    }
+    streams == "*" || streams.split(',').any(|s| s.trim() == stream_name)
+}
+
+/// Returns the coarsest resolution not coarser than `max_resolution`
+pub fn pick_resolution(resolutions: &[Resolution], max_resolution: i64) -> Option<&Resolution> {
+    resolutions
+        .iter()
+        .filter(|r| r.micros <= max_resolution)
+        .max_by_key(|r| r.micros)
+}
+
+/// Returns the rollup stream to read the samples of the metrics stream from, at most at
+/// `max_resolution`, and the time until which the rollup stream is generated. The coarsest
+/// rollup stream already generated is picked.
+pub async fn get_rollup_stream(
+    org_id: &str,
+    stream_name: &str,
+    max_resolution: i64,
+) -> Option<(String, i64)> {
+    let mut resolutions = get_resolutions();
+    if !is_rollup_enabled(stream_name, &resolutions) {
+        return None;
+    }
+    while let Some(resolution) = pick_resolution(&resolutions, max_resolution).cloned() {
+        let offset = db::compact::rollup::get_offset(org_id, stream_name, &resolution.name).await;
+        if offset > 0 {
+            return Some((rollup_stream_name(stream_name, &resolution), offset));
+        }
+        resolutions.retain(|r| r != &resolution);
+    }
+    None
+}
+
+/// Generates the rollup streams of the configured metrics streams owned by this node
+pub async fn run_rollup() -> Result<(), anyhow::Error> {
+    let resolutions = get_resolutions();
+    if get_config().compact.metrics_rollup_streams.is_empty() || resolutions.is_empty() {
+        return Ok(());
+    }
+
+    let orgs = db::schema::list_organizations_from_cache().await;
+    for org_id in orgs {
+        let streams = db::schema::list_streams_from_cache(&org_id, StreamType::Metrics).await;
+        for stream_name in streams {
+            if !is_rollup_enabled(&stream_name, &resolutions) {
+                continue;
+            }
+            let Some(node_name) =
+                get_node_from_consistent_hash(&stream_name, &Role::Compactor, None).await
+            else {
+                continue; // no compactor node
+            };
+            if LOCAL_NODE.name.ne(&node_name) {
+                continue; // not this node
+            }
+            for resolution in resolutions.iter() {
+                if let Err(e) = rollup_stream(&org_id, &stream_name, resolution).await {
+                    log::error!(
+                        "[COMPACTOR] rollup [{}/{}/{}] resolution {} error: {}",
+                        org_id,
+                        StreamType::Metrics,
+                        stream_name,
+                        resolution.name,
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Rolls up the completed windows of the stream after the offset. A window is only written
+/// once, the samples already in the rollup stream are skipped when the job reruns over the
+/// same window, eg: after a crash before the offset is saved.
+async fn rollup_stream(
+    org_id: &str,
+    stream_name: &str,
+    resolution: &Resolution,
+) -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    let res = resolution.micros;
+    // the windows are rolled up after the late samples arrived
+    let end = align_time(
+        now_micros() - cfg.compact.metrics_rollup_delay * 1_000_000,
+        res,
+    );
+    let mut offset = db::compact::rollup::get_offset(org_id, stream_name, &resolution.name).await;
+    if offset == 0 {
+        let stats = infra::cache::stats::get_stream_stats(org_id, stream_name, StreamType::Metrics);
+        if stats.doc_time_min == 0 {
+            return Ok(()); // no data yet
+        }
+        offset = align_time(stats.doc_time_min, res);
+    }
+
+    let schema = infra::schema::get(org_id, stream_name, StreamType::Metrics).await?;
+    let labels = schema
+        .fields()
+        .iter()
+        .filter(|f| f.data_type() == &DataType::Utf8 && f.name() != HASH_LABEL)
+        .map(|f| f.name().to_string())
+        .collect::<Vec<_>>();
+    let rollup_stream = rollup_stream_name(stream_name, resolution);
+    let window = std::cmp::max(res, HOUR_MICROS);
+    let mut windows = 0;
+    while offset < end && windows < MAX_WINDOWS_PER_RUN {
+        let window_end = std::cmp::min(offset + window, end);
+        rollup_window(
+            org_id,
+            stream_name,
+            &rollup_stream,
+            &labels,
+            res,
+            (offset, window_end),
+        )
+        .await?;
+        offset = window_end;
+        windows += 1;
+        db::compact::rollup::set_offset(org_id, stream_name, &resolution.name, offset).await?;
+    }
+    Ok(())
+}
+
+async fn rollup_window(
+    org_id: &str,
+    stream_name: &str,
+    rollup_stream: &str,
+    labels: &[String],
+    res: i64,
+    (start, end): (i64, i64),
+) -> Result<(), anyhow::Error> {
+    let ts_column = &get_config().common.column_timestamp;
+    let existing = get_existing_samples(org_id, rollup_stream, res, (start, end)).await?;
+    let label_cols = labels
+        .iter()
+        .map(|l| format!("max(\"{l}\") AS \"{l}\", "))
+        .collect::<String>();
+    let sql = format!(
+        "SELECT \"{HASH_LABEL}\", {label_cols}{ts_column} - {ts_column} % {res} AS zo_rollup_bucket, max({ts_column}) AS zo_rollup_ts, min({VALUE_LABEL}) AS zo_rollup_min, max({VALUE_LABEL}) AS zo_rollup_max, sum({VALUE_LABEL}) AS zo_rollup_sum, count({VALUE_LABEL}) AS zo_rollup_count, last_value({VALUE_LABEL} ORDER BY {ts_column}) AS zo_rollup_last FROM \"{stream_name}\" GROUP BY \"{HASH_LABEL}\", zo_rollup_bucket ORDER BY \"{HASH_LABEL}\", zo_rollup_bucket"
+    );
+
+    let mut from = 0;
+    loop {
+        let hits = search(org_id, StreamType::Metrics, sql.clone(), from, (start, end)).await?;
+        let num = hits.len() as i64;
+        let records = hits
+            .iter()
+            .filter_map(|hit| rollup_record(hit, labels, ts_column, res))
+            .filter(|(key, _)| !existing.contains(key))
+            .map(|(_, record)| json::Value::Object(record))
+            .collect::<Vec<_>>();
+        if !records.is_empty() {
+            let req = cluster_rpc::IngestionRequest {
+                org_id: org_id.to_string(),
+                stream_name: rollup_stream.to_string(),
+                stream_type: cluster_rpc::StreamType::Metrics.into(),
+                data: Some(cluster_rpc::IngestionData::from(records)),
+                ingestion_type: Some(cluster_rpc::IngestionType::Json.into()),
+            };
+            let resp = ingestion_service::ingest(org_id, req).await?;
+            if resp.status_code != 200 {
+                return Err(anyhow::anyhow!(
+                    "ingest rollup samples error: {}",
+                    resp.message
+                ));
+            }
+        }
+        if num < PAGE_SIZE {
+            break;
+        }
+        from += PAGE_SIZE;
+    }
+    Ok(())
+}
+
+/// Returns the series and bucket of the samples already in the rollup stream for the window
+async fn get_existing_samples(
+    org_id: &str,
+    rollup_stream: &str,
+    res: i64,
+    time_range: (i64, i64),
+) -> Result<HashSet<(String, i64)>, anyhow::Error> {
+    let ts_column = &get_config().common.column_timestamp;
+    let sql = format!("SELECT COUNT(*) AS num FROM \"{rollup_stream}\"");
+    let hits = match search(org_id, StreamType::Metrics, sql, 0, time_range).await {
+        Ok(hits) => hits,
+        // the rollup stream doesn't exist before the first window is written
+        Err(Error::ErrorCode(ErrorCodes::SearchStreamNotFound(_))) => return Ok(HashSet::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let num = hits
+        .first()
+        .and_then(|v| v.get("num"))
+        .and_then(|v| v.as_i64())
+        .unwrap_or_default();
+    let mut existing = HashSet::with_capacity(num as usize);
+    if num == 0 {
+        return Ok(existing);
+    }
+
+    let sql = format!(
+        "SELECT \"{HASH_LABEL}\", {ts_column} FROM \"{rollup_stream}\" ORDER BY \"{HASH_LABEL}\", {ts_column}"
+    );
+    let mut from = 0;
+    while from < num {
+        let hits = search(org_id, StreamType::Metrics, sql.clone(), from, time_range).await?;
+        if hits.is_empty() {
+            break;
+        }
+        for hit in hits {
+            let hash = hit.get(HASH_LABEL).and_then(|v| v.as_str());
+            let ts = hit.get(ts_column).and_then(|v| v.as_i64());
+            if let (Some(hash), Some(ts)) = (hash, ts) {
+                existing.insert((hash.to_string(), align_time(ts, res)));
+            }
+        }
+        from += PAGE_SIZE;
+    }
+    Ok(existing)
+}
+
+async fn search(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: String,
+    from: i64,
+    (start_time, end_time): (i64, i64),
+) -> Result<Vec<json::Value>, Error> {
+    let req = search::Request {
+        query: search::Query {
+            sql,
+            from,
+            size: PAGE_SIZE,
+            start_time,
+            end_time,
+            ..Default::default()
+        },
+        encoding: search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+        index_type: "".to_string(),
+    };
+    let trace_id = ider::uuid();
+    let resp = SearchService::search(&trace_id, org_id, stream_type, None, &req).await?;
+    Ok(resp.hits)
+}
+
+/// Builds the rollup sample of a series and bucket, the sample keeps the labels and the hash
+/// of the raw series, its time is the one of the last raw sample in the bucket. The sum, count,
+/// min, max and last value of the bucket are stored in the rollup columns, the value is the
+/// last one. Returns the series and bucket of the sample too.
+fn rollup_record(
+    hit: &json::Value,
+    labels: &[String],
+    ts_column: &str,
+    res: i64,
+) -> Option<((String, i64), json::Map<String, json::Value>)> {
+    let hash = hit.get(HASH_LABEL)?.as_str()?;
+    let ts = hit.get("zo_rollup_ts")?.as_i64()?;
+    let mut record = json::Map::with_capacity(labels.len() + 8);
+    for label in labels {
+        if let Some(value) = hit.get(label).filter(|v| !v.is_null()) {
+            record.insert(label.to_string(), value.clone());
+        }
+    }
+    record.insert(HASH_LABEL.to_string(), hash.into());
+    record.insert(ts_column.to_string(), ts.into());
+    let last = hit.get("zo_rollup_last")?;
+    record.insert(VALUE_LABEL.to_string(), last.clone());
+    record.insert(ROLLUP_LAST_LABEL.to_string(), last.clone());
+    record.insert(
+        ROLLUP_MIN_LABEL.to_string(),
+        hit.get("zo_rollup_min")?.clone(),
+    );
+    record.insert(
+        ROLLUP_MAX_LABEL.to_string(),
+        hit.get("zo_rollup_max")?.clone(),
+    );
+    record.insert(
+        ROLLUP_SUM_LABEL.to_string(),
+        hit.get("zo_rollup_sum")?.clone(),
+    );
+    record.insert(
+        ROLLUP_COUNT_LABEL.to_string(),
+        hit.get("zo_rollup_count")?.clone(),
+    );
+    Some(((hash.to_string(), align_time(ts, res)), record))
+}
+
+#[inline]
+fn align_time(ts: i64, res: i64) -> i64 {
+    ts - ts % res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolutions() {
+        let resolutions = parse_resolutions("1h, 5m,60m,30s,bad");
+        assert_eq!(
+            resolutions,
+            vec![
+                Resolution {
+                    name: "5m".to_string(),
+                    micros: 300_000_000
+                },
+                Resolution {
+                    name: "1h".to_string(),
+                    micros: HOUR_MICROS
+                },
+            ]
+        );
+        assert_eq!(
+            rollup_stream_name("up", &resolutions[1]),
+            "up_rollup_1h".to_string()
+        );
+    }
+
+    #[test]
+    fn test_pick_resolution() {
+        let resolutions = parse_resolutions("5m,1h");
+        assert_eq!(pick_resolution(&resolutions, 60_000_000), None);
+        assert_eq!(
+            pick_resolution(&resolutions, 900_000_000).unwrap().name,
+            "5m"
+        );
+        assert_eq!(
+            pick_resolution(&resolutions, 2 * HOUR_MICROS).unwrap().name,
+            "1h"
+        );
+    }
+
+    #[test]
+    fn test_rollup_record() {
+        let hit = json::json!({
+            "__hash__": "abc",
+            "__name__": "up",
+            "job": "api",
+            "zo_rollup_bucket": 3_600_000_000i64,
+            "zo_rollup_ts": 3_650_000_000i64,
+            "zo_rollup_min": 1.0,
+            "zo_rollup_max": 3.0,
+            "zo_rollup_sum": 6.0,
+            "zo_rollup_count": 3,
+            "zo_rollup_last": 2.0,
+        });
+        let labels = vec!["__name__".to_string(), "job".to_string(), "env".to_string()];
+        let (key, record) = rollup_record(&hit, &labels, "_timestamp", HOUR_MICROS).unwrap();
+        assert_eq!(key, ("abc".to_string(), HOUR_MICROS));
+        assert_eq!(record["_timestamp"], json::json!(3_650_000_000i64));
+        assert_eq!(record["value"], json::json!(2.0));
+        assert_eq!(record["__rollup_count__"], json::json!(3));
+        assert_eq!(record["__rollup_sum__"], json::json!(6.0));
+        assert_eq!(record["__rollup_last__"], json::json!(2.0));
+        assert_eq!(record["job"], json::json!("api"));
+        assert!(!record.contains_key("env"));
+        assert!(!record.contains_key("zo_rollup_bucket"));
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::{utils::json, RwHashSet};
+use infra::errors::{DbError, Error};
+use once_cell::sync::Lazy;
+
+use crate::{common::meta::stream::CompactJob, service::db};
+
+const MANUAL_JOB_KEY: &str = "/compact/manual/";
+const CANCEL_KEY: &str = "/compact/manual_cancel/";
+
+/// The jobs requested to cancel, `org_id/job_id`
+static CANCELLED: Lazy<RwHashSet<String>> = Lazy::new(Default::default);
+
+pub async fn get(org_id: &str, job_id: &str) -> Result<Option<CompactJob>, anyhow::Error> {
+    match db::get(&format!("{MANUAL_JOB_KEY}{org_id}/{job_id}")).await {
+        Ok(v) => Ok(Some(json::from_slice(&v)?)),
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set(job: &CompactJob) -> Result<(), anyhow::Error> {
+    let key = format!("{MANUAL_JOB_KEY}{}/{}", job.org_id, job.id);
+    db::put(&key, json::to_vec(job)?.into(), db::NO_NEED_WATCH, None).await?;
+    Ok(())
+}
+
+pub async fn delete(org_id: &str, job_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{MANUAL_JOB_KEY}{org_id}/{job_id}");
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH).await?;
+    Ok(())
+}
+
+pub async fn list() -> Result<Vec<CompactJob>, anyhow::Error> {
+    let mut jobs = Vec::new();
+    for (_, v) in db::list(MANUAL_JOB_KEY).await? {
+        jobs.push(json::from_slice(&v)?);
+    }
+    Ok(jobs)
+}
+
+/// Requests to cancel the job, the node running it is notified by the watch
+pub async fn cancel(org_id: &str, job_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{org_id}/{job_id}");
+    db::put(
+        &format!("{CANCEL_KEY}{key}"),
+        "OK".into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await?;
+    CANCELLED.insert(key);
+    Ok(())
+}
+
+pub fn is_cancelled(org_id: &str, job_id: &str) -> bool {
+    CANCELLED.contains(&format!("{org_id}/{job_id}"))
+}
+
+pub async fn cancel_done(org_id: &str, job_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{org_id}/{job_id}");
+    db::delete_if_exists(&format!("{CANCEL_KEY}{key}"), false, db::NEED_WATCH).await?;
+    CANCELLED.remove(&key);
+    Ok(())
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(CANCEL_KEY).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching manual compaction job cancelling");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_manual_compact_cancel: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(CANCEL_KEY).unwrap();
+                CANCELLED.insert(item_key.to_string());
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(CANCEL_KEY).unwrap();
+                CANCELLED.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    for (item_key, _) in db::list(CANCEL_KEY).await? {
+        let item_key = item_key.strip_prefix(CANCEL_KEY).unwrap();
+        CANCELLED.insert(item_key.to_string());
+    }
+    Ok(())
+}
@@ -95,6 +95,25 @@ pub fn is_deleting_stream(
     CACHE.contains(&mk_key(org_id, stream_type, stream_name, date_range))
 }
 
+/// Checks if the data of the date (eg: 2023-01-02) is deleting by a retention job of the stream
+pub fn is_deleting_date(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    date: &str,
+) -> bool {
+    let prefix = format!("{org_id}/{stream_type}/{stream_name}/");
+    CACHE.iter().any(|key| {
+        let Some(range) = key.strip_prefix(&prefix) else {
+            return false;
+        };
+        match range.split_once(',') {
+            Some((start, end)) => start <= date && date < end,
+            None => range == "all",
+        }
+    })
+}
+
 pub async fn delete_stream_done(
     org_id: &str,
     stream_type: StreamType,
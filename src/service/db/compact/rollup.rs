@@ -0,0 +1,40 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::service::db;
+
+fn mk_key(org_id: &str, stream_name: &str, resolution: &str) -> String {
+    format!("/compact/rollup/{org_id}/{stream_name}/{resolution}")
+}
+
+/// Returns the time until which the rollup stream of the resolution is generated, 0 if the
+/// rollup didn't run yet
+pub async fn get_offset(org_id: &str, stream_name: &str, resolution: &str) -> i64 {
+    let key = mk_key(org_id, stream_name, resolution);
+    match db::get(&key).await {
+        Ok(ret) => String::from_utf8_lossy(&ret).parse().unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+pub async fn set_offset(
+    org_id: &str,
+    stream_name: &str,
+    resolution: &str,
+    offset: i64,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_name, resolution);
+    Ok(db::put(&key, offset.to_string().into(), db::NO_NEED_WATCH, None).await?)
+}
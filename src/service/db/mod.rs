@@ -35,6 +35,7 @@ pub mod metrics;
 pub mod ofga;
 pub mod organization;
 pub mod pipelines;
+pub mod reingest;
 pub mod saved_view;
 pub mod scheduler;
 pub mod schema;
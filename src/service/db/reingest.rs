@@ -0,0 +1,85 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use bytes::Bytes;
+use config::utils::json;
+use infra::errors::{DbError, Error};
+
+use crate::{common::meta::reingest::ReingestJob, service::db};
+
+const REINGEST_JOB_KEY: &str = "/reingest/job/";
+const REINGEST_FILE_KEY: &str = "/reingest/file/";
+
+pub async fn get_job(org_id: &str, job_id: &str) -> Result<Option<ReingestJob>, anyhow::Error> {
+    match db::get(&format!("{REINGEST_JOB_KEY}{org_id}/{job_id}")).await {
+        Ok(v) => Ok(Some(json::from_slice(&v)?)),
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_job(job: &ReingestJob) -> Result<(), anyhow::Error> {
+    let key = format!("{REINGEST_JOB_KEY}{}/{}", job.org_id, job.id);
+    db::put(&key, json::to_vec(job)?.into(), db::NO_NEED_WATCH, None).await?;
+    Ok(())
+}
+
+pub async fn list_jobs(org_id: &str) -> Result<Vec<ReingestJob>, anyhow::Error> {
+    db::list_values(&format!("{REINGEST_JOB_KEY}{org_id}/"))
+        .await?
+        .iter()
+        .map(|v| json::from_slice(v).map_err(|e| e.into()))
+        .collect()
+}
+
+fn mk_file_key(org_id: &str, target_stream: &str, pipeline: &str, file: &str) -> String {
+    format!("{REINGEST_FILE_KEY}{org_id}/{target_stream}/{pipeline}/{file}")
+}
+
+/// Returns true if the file was replayed through the pipeline to the target stream
+pub async fn is_file_done(
+    org_id: &str,
+    target_stream: &str,
+    pipeline: &str,
+    file: &str,
+) -> Result<bool, anyhow::Error> {
+    match db::get(&mk_file_key(org_id, target_stream, pipeline, file)).await {
+        Ok(_) => Ok(true),
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_file_done(
+    org_id: &str,
+    target_stream: &str,
+    pipeline: &str,
+    file: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_file_key(org_id, target_stream, pipeline, file);
+    db::put(&key, Bytes::new(), db::NO_NEED_WATCH, None).await?;
+    Ok(())
+}
+
+/// Removes the records of the files replayed through the pipeline to the target stream
+pub async fn delete_files(
+    org_id: &str,
+    target_stream: &str,
+    pipeline: &str,
+) -> Result<(), anyhow::Error> {
+    let key = format!("{REINGEST_FILE_KEY}{org_id}/{target_stream}/{pipeline}/");
+    db::delete_if_exists(&key, true, db::NO_NEED_WATCH).await?;
+    Ok(())
+}
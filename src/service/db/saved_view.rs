@@ -18,12 +18,15 @@ use infra::errors::Error;
 
 use crate::{
     common::meta::saved_view::{
-        CreateViewRequest, UpdateViewRequest, View, ViewWithoutData, ViewsWithoutData,
+        CreateViewRequest, ExportRun, ScheduledExport, UpdateViewRequest, View, ViewWithoutData,
+        ViewsWithoutData,
     },
     service::db,
 };
 
 pub const SAVED_VIEWS_KEY_PREFIX: &str = "/organization/savedviews";
+pub const SCHEDULED_EXPORTS_KEY_PREFIX: &str = "/organization/scheduled_exports";
+pub const EXPORT_RUNS_KEY_PREFIX: &str = "/organization/scheduled_export_runs";
 
 pub async fn set_view(org_id: &str, view: &CreateViewRequest) -> Result<View, Error> {
     let view_id = config::ider::uuid();
@@ -99,3 +102,113 @@ pub async fn delete_view(org_id: &str, view_id: &str) -> Result<(), Error> {
     db::delete(&key, false, db::NO_NEED_WATCH, None).await?;
     Ok(())
 }
+
+/// Saves the scheduled export and schedules its next run
+pub async fn set_export(
+    org_id: &str,
+    export: &ScheduledExport,
+    next_run_at: i64,
+) -> Result<(), anyhow::Error> {
+    set_export_without_updating_trigger(org_id, export).await?;
+    let trigger = db::scheduler::Trigger {
+        org: org_id.to_string(),
+        module: db::scheduler::TriggerModule::ScheduledExport,
+        module_key: export.name.clone(),
+        next_run_at,
+        ..Default::default()
+    };
+    let res = if db::scheduler::exists(
+        org_id,
+        db::scheduler::TriggerModule::ScheduledExport,
+        &export.name,
+    )
+    .await
+    {
+        db::scheduler::update_trigger(trigger).await
+    } else {
+        db::scheduler::push(trigger).await
+    };
+    if let Err(e) = res {
+        log::error!("Failed to save trigger of scheduled export: {}", e);
+    }
+    Ok(())
+}
+
+pub async fn set_export_without_updating_trigger(
+    org_id: &str,
+    export: &ScheduledExport,
+) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "{}/{}/{}",
+        SCHEDULED_EXPORTS_KEY_PREFIX, org_id, export.name
+    );
+    db::put(
+        &key,
+        json::to_vec(export).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn get_export(org_id: &str, name: &str) -> Result<ScheduledExport, anyhow::Error> {
+    let key = format!("{}/{}/{}", SCHEDULED_EXPORTS_KEY_PREFIX, org_id, name);
+    match db::get(&key).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Err(anyhow::anyhow!("Scheduled export not found")),
+    }
+}
+
+pub async fn list_exports(org_id: &str) -> Result<Vec<ScheduledExport>, anyhow::Error> {
+    let key = format!("{}/{}", SCHEDULED_EXPORTS_KEY_PREFIX, org_id);
+    let ret = db::list_values(&key).await?;
+    let mut exports: Vec<ScheduledExport> = Vec::with_capacity(ret.len());
+    for item in ret {
+        exports.push(json::from_slice(&item)?);
+    }
+    exports.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(exports)
+}
+
+/// Deletes the scheduled export with its trigger and run history
+pub async fn delete_export(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{}/{}/{}", SCHEDULED_EXPORTS_KEY_PREFIX, org_id, name);
+    db::delete(&key, false, db::NO_NEED_WATCH, None).await?;
+    let key = format!("{}/{}/{}", EXPORT_RUNS_KEY_PREFIX, org_id, name);
+    if let Err(e) = db::delete(&key, false, db::NO_NEED_WATCH, None).await {
+        log::error!("Failed to delete runs of scheduled export: {}", e);
+    }
+    if let Err(e) =
+        db::scheduler::delete(org_id, db::scheduler::TriggerModule::ScheduledExport, name).await
+    {
+        log::error!("Failed to delete trigger of scheduled export: {}", e);
+    }
+    Ok(())
+}
+
+/// Returns the runs of the scheduled export, the latest first
+pub async fn get_export_runs(org_id: &str, name: &str) -> Result<Vec<ExportRun>, anyhow::Error> {
+    let key = format!("{}/{}/{}", EXPORT_RUNS_KEY_PREFIX, org_id, name);
+    match db::get(&key).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Adds a run to the history of the scheduled export, the oldest runs are dropped when the
+/// history is full
+pub async fn add_export_run(org_id: &str, name: &str, run: ExportRun) -> Result<(), anyhow::Error> {
+    let mut runs = get_export_runs(org_id, name).await?;
+    runs.insert(0, run);
+    runs.truncate(config::get_config().limit.scheduled_export_run_history);
+    let key = format!("{}/{}/{}", EXPORT_RUNS_KEY_PREFIX, org_id, name);
+    db::put(
+        &key,
+        json::to_vec(&runs).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
@@ -13,6 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use hashbrown::HashMap;
 pub use infra::scheduler::{Trigger, TriggerModule, TriggerStatus, TRIGGERS_KEY};
 use infra::{
     errors::Result,
@@ -26,12 +27,22 @@ use {
     o2_enterprise::enterprise::super_cluster,
 };
 
+use crate::service::alerts::flapping::FlappingState;
+
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct ScheduledTriggerData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub period_end_time: Option<i64>,
     #[serde(default)]
     pub tolerance: i64,
+    /// The dedup keys of the notified alert rows and when they were first notified
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub dedup_seen: HashMap<String, i64>,
+    /// The recent transitions of the alert and if it's flapping
+    #[serde(default)]
+    #[serde(skip_serializing_if = "FlappingState::is_empty")]
+    pub flapping: FlappingState,
 }
 
 #[inline]
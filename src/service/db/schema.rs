@@ -55,6 +55,9 @@ pub async fn merge(
     min_ts: Option<i64>,
 ) -> Result<Option<(Schema, Vec<Field>)>, anyhow::Error> {
     let ret = infra::schema::merge(org_id, stream_name, stream_type, schema, min_ts).await?;
+    if let Some((latest_schema, _)) = ret.as_ref() {
+        record_field_changes(org_id, stream_name, stream_type, latest_schema).await;
+    }
 
     // super cluster
     #[cfg(feature = "enterprise")]
@@ -73,6 +76,20 @@ pub async fn merge(
     Ok(ret)
 }
 
+// keeps the history of the schema fields, a failure doesn't fail the schema change
+async fn record_field_changes(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    schema: &Schema,
+) {
+    if let Err(e) =
+        infra::schema::field_changes::record(org_id, stream_name, stream_type, schema).await
+    {
+        log::error!("Error recording field history of [{org_id}/{stream_type}/{stream_name}]: {e}");
+    }
+}
+
 pub async fn update_setting(
     org_id: &str,
     stream_name: &str,
@@ -105,6 +122,9 @@ pub async fn delete_fields(
     deleted_fields: Vec<String>,
 ) -> Result<(), anyhow::Error> {
     infra::schema::delete_fields(org_id, stream_name, stream_type, deleted_fields.clone()).await?;
+    if let Ok(latest_schema) = infra::schema::get_from_db(org_id, stream_name, stream_type).await {
+        record_field_changes(org_id, stream_name, stream_type, &latest_schema).await;
+    }
 
     // super cluster
     #[cfg(feature = "enterprise")]
@@ -130,6 +150,9 @@ pub async fn delete(
 ) -> Result<(), anyhow::Error> {
     let stream_type = stream_type.unwrap_or(StreamType::Logs);
     infra::schema::delete(org_id, stream_type, stream_name, None).await?;
+    if let Err(e) = infra::schema::field_changes::delete(org_id, stream_name, stream_type).await {
+        log::error!("Error deleting field history of [{org_id}/{stream_type}/{stream_name}]: {e}");
+    }
 
     // super cluster
     #[cfg(feature = "enterprise")]
@@ -447,6 +470,7 @@ pub async fn watch() -> Result<(), anyhow::Error> {
                 w.shrink_to_fit();
                 drop(w);
                 cache::stats::remove_stream_stats(org_id, stream_name, stream_type);
+                crate::service::ingestion::lag::remove(org_id, stream_name, stream_type);
                 if let Err(e) =
                     super::compact::files::del_offset(org_id, stream_type, stream_name).await
                 {
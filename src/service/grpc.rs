@@ -13,9 +13,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::{utils::rand::get_rand_element, RwAHashMap};
+use anyhow::Context;
+use config::{get_config, utils::rand::get_rand_element, RwAHashMap};
 use once_cell::sync::Lazy;
-use tonic::{transport::Channel, Status};
+use tonic::{
+    transport::{Certificate, Channel, ClientTlsConfig, Identity, ServerTlsConfig},
+    Status,
+};
 
 use crate::common::infra::cluster;
 
@@ -30,7 +34,7 @@ pub(crate) async fn get_ingester_channel() -> Result<(String, Channel), tonic::S
 
 async fn get_rand_ingester_addr() -> Result<String, tonic::Status> {
     let cfg = config::get_config();
-    let nodes = cluster::get_cached_online_ingester_nodes().await;
+    let nodes = cluster::get_cached_schedulable_ingester_nodes().await;
     if nodes.is_none() || nodes.as_ref().unwrap().is_empty() {
         if !cfg.route.ingester_srv_url.is_empty() {
             Ok(format!(
@@ -67,11 +71,24 @@ pub(crate) async fn get_cached_channel(grpc_addr: &str) -> Result<Channel, tonic
 }
 
 async fn create_channel(grpc_addr: &str) -> Result<Channel, tonic::Status> {
-    let channel = Channel::from_shared(grpc_addr.to_string())
-        .unwrap()
-        .connect_timeout(std::time::Duration::from_secs(
-            config::get_config().grpc.connect_timeout,
-        ))
+    let cfg = get_config();
+    let endpoint = if cfg.grpc.tls_enabled {
+        let tls = client_tls_config().map_err(|err| {
+            log::error!("gRPC node: {}, tls config err: {:?}", &grpc_addr, err);
+            Status::internal("load gRPC tls config error".to_string())
+        })?;
+        Channel::from_shared(grpc_addr.replacen("http://", "https://", 1))
+            .unwrap()
+            .tls_config(tls)
+            .map_err(|err| {
+                log::error!("gRPC node: {}, tls config err: {:?}", &grpc_addr, err);
+                Status::internal("load gRPC tls config error".to_string())
+            })?
+    } else {
+        Channel::from_shared(grpc_addr.to_string()).unwrap()
+    };
+    let channel = endpoint
+        .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
         .connect()
         .await
         .map_err(|err| {
@@ -80,3 +97,136 @@ async fn create_channel(grpc_addr: &str) -> Result<Channel, tonic::Status> {
         })?;
     Ok(channel)
 }
+
+/// The TLS config of the grpc servers, None if `ZO_GRPC_TLS_ENABLED` is off
+pub fn server_tls_config() -> Result<Option<ServerTlsConfig>, anyhow::Error> {
+    let cfg = get_config();
+    if !cfg.grpc.tls_enabled {
+        return Ok(None);
+    }
+    load_server_tls_config(
+        &cfg.grpc.tls_cert_path,
+        &cfg.grpc.tls_key_path,
+        &cfg.grpc.tls_client_ca_path,
+    )
+    .map(Some)
+}
+
+/// The TLS config a node connects to the other nodes with. It trusts the CA of the client
+/// certificates and presents its server certificate as the client certificate.
+fn client_tls_config() -> Result<ClientTlsConfig, anyhow::Error> {
+    let cfg = get_config();
+    load_client_tls_config(
+        &cfg.grpc.tls_client_ca_path,
+        Some((&cfg.grpc.tls_cert_path, &cfg.grpc.tls_key_path)),
+        &cfg.grpc.tls_domain_name,
+    )
+}
+
+/// Loads the server certificate and key. With a client CA, the connections without a client
+/// certificate signed by it are refused during the handshake.
+fn load_server_tls_config(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: &str,
+) -> Result<ServerTlsConfig, anyhow::Error> {
+    let mut tls = ServerTlsConfig::new().identity(load_identity(cert_path, key_path)?);
+    if !client_ca_path.is_empty() {
+        tls = tls.client_ca_root(load_certificate(client_ca_path)?);
+    }
+    Ok(tls)
+}
+
+fn load_client_tls_config(
+    ca_path: &str,
+    identity: Option<(&str, &str)>,
+    domain_name: &str,
+) -> Result<ClientTlsConfig, anyhow::Error> {
+    let mut tls = ClientTlsConfig::new();
+    if !ca_path.is_empty() {
+        tls = tls.ca_certificate(load_certificate(ca_path)?);
+    }
+    if let Some((cert_path, key_path)) = identity {
+        tls = tls.identity(load_identity(cert_path, key_path)?);
+    }
+    if !domain_name.is_empty() {
+        tls = tls.domain_name(domain_name);
+    }
+    Ok(tls)
+}
+
+fn load_identity(cert_path: &str, key_path: &str) -> Result<Identity, anyhow::Error> {
+    let cert = std::fs::read(cert_path).with_context(|| format!("read {cert_path}"))?;
+    let key = std::fs::read(key_path).with_context(|| format!("read {key_path}"))?;
+    Ok(Identity::from_pem(cert, key))
+}
+
+fn load_certificate(path: &str) -> Result<Certificate, anyhow::Error> {
+    let cert = std::fs::read(path).with_context(|| format!("read {path}"))?;
+    Ok(Certificate::from_pem(cert))
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::transport::{Endpoint, Server};
+    use tonic_health::pb::{health_client::HealthClient, HealthCheckRequest};
+
+    use super::*;
+
+    fn test_data(name: &str) -> String {
+        format!(
+            "{}/tests/test-data/grpc_tls/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        )
+    }
+
+    async fn health_check(addr: &str, tls: ClientTlsConfig) -> Result<(), anyhow::Error> {
+        let channel = Endpoint::from_shared(addr.to_string())?
+            .tls_config(tls)?
+            .connect()
+            .await?;
+        HealthClient::new(channel)
+            .check(HealthCheckRequest::default())
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_is_required() {
+        let server_tls = load_server_tls_config(
+            &test_data("server.pem"),
+            &test_data("server.key"),
+            &test_data("ca.pem"),
+        )
+        .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!(
+            "https://localhost:{}",
+            listener.local_addr().unwrap().port()
+        );
+        let incoming = futures::stream::unfold(listener, |listener| async move {
+            Some((listener.accept().await.map(|(s, _)| s), listener))
+        });
+        let (_, health_svc) = tonic_health::server::health_reporter();
+        tokio::spawn(
+            Server::builder()
+                .tls_config(server_tls)
+                .unwrap()
+                .add_service(health_svc)
+                .serve_with_incoming(Box::pin(incoming)),
+        );
+
+        // without a client certificate the connection is refused
+        let tls = load_client_tls_config(&test_data("ca.pem"), None, "localhost").unwrap();
+        assert!(health_check(&addr, tls).await.is_err());
+
+        // a client certificate signed by the CA is accepted
+        let tls = load_client_tls_config(
+            &test_data("ca.pem"),
+            Some((&test_data("client.pem"), &test_data("client.key"))),
+            "localhost",
+        )
+        .unwrap();
+        health_check(&addr, tls).await.unwrap();
+    }
+}
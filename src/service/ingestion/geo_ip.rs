@@ -0,0 +1,281 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The geo-IP enrichment of pipelines. The database of `ZO_GEOIP_ENRICHMENT_DB_PATH` is loaded
+//! once and shared by all the ingestion requests, it's loaded again when the modified time of the
+//! file changes.
+
+use std::{collections::HashMap, net::IpAddr, sync::Arc, time::SystemTime};
+
+use anyhow::Result;
+use config::{
+    get_config,
+    meta::stream::StreamParams,
+    utils::json::{Map, Value},
+    RwHashMap,
+};
+use maxminddb::{geoip2, Reader};
+use once_cell::sync::Lazy;
+
+use crate::common::{
+    infra::config::STREAM_PIPELINES,
+    meta::pipelines::{GeoIpAttribute, GeoIpEnrichment},
+};
+
+/// The loaded databases, with the modified time of their file
+static DATABASES: Lazy<RwHashMap<String, (SystemTime, Arc<Reader<Vec<u8>>>)>> =
+    Lazy::new(Default::default);
+
+/// Returns the database of the path, loading it again if the file changed since it was loaded
+pub fn get_database(path: &str) -> Result<Arc<Reader<Vec<u8>>>> {
+    let modified = match std::fs::metadata(path).and_then(|v| v.modified()) {
+        Ok(v) => v,
+        // keep using the loaded database while the file is being replaced
+        Err(e) => match DATABASES.get(path) {
+            Some(db) => return Ok(db.1.clone()),
+            None => return Err(e.into()),
+        },
+    };
+    if let Some(db) = DATABASES.get(path) {
+        if db.0 == modified {
+            return Ok(db.1.clone());
+        }
+    }
+    let reader = Arc::new(Reader::open_readfile(path)?);
+    if DATABASES.contains_key(path) {
+        log::info!("[GEOIP] database {path} changed, reloaded");
+    }
+    DATABASES.insert(path.to_string(), (modified, reader.clone()));
+    Ok(reader)
+}
+
+pub struct GeoIpEnricher {
+    enrichments: Vec<GeoIpEnrichment>,
+    reader: Arc<Reader<Vec<u8>>>,
+}
+
+impl GeoIpEnricher {
+    pub fn new(enrichments: Vec<GeoIpEnrichment>, reader: Arc<Reader<Vec<u8>>>) -> Self {
+        Self {
+            enrichments,
+            reader,
+        }
+    }
+
+    /// Adds the output fields of the looked up attributes to the flattened record. The fields
+    /// are left unset when the source field is missing or isn't an IP address, or the database
+    /// doesn't have the attribute of the address.
+    pub fn enrich(&self, record: &mut Map<String, Value>) {
+        for enrichment in self.enrichments.iter() {
+            let Some(ip) = record
+                .get(&enrichment.source_field)
+                .and_then(|v| v.as_str())
+                .and_then(|v| v.trim().parse::<IpAddr>().ok())
+            else {
+                continue;
+            };
+            let city = if enrichment
+                .fields
+                .values()
+                .any(|v| !matches!(v, GeoIpAttribute::Asn | GeoIpAttribute::AsOrg))
+            {
+                self.reader.lookup::<geoip2::City>(ip).ok()
+            } else {
+                None
+            };
+            let asn = if enrichment
+                .fields
+                .values()
+                .any(|v| matches!(v, GeoIpAttribute::Asn | GeoIpAttribute::AsOrg))
+            {
+                self.reader.lookup::<geoip2::Asn>(ip).ok()
+            } else {
+                None
+            };
+            for (field, attribute) in enrichment.fields.iter() {
+                let value = match attribute {
+                    GeoIpAttribute::CountryName => city
+                        .as_ref()
+                        .and_then(|v| v.country.as_ref())
+                        .and_then(|v| v.names.as_ref())
+                        .and_then(|v| v.get("en"))
+                        .map(|v| Value::from(*v)),
+                    GeoIpAttribute::CountryIsoCode => city
+                        .as_ref()
+                        .and_then(|v| v.country.as_ref())
+                        .and_then(|v| v.iso_code)
+                        .map(Value::from),
+                    GeoIpAttribute::CityName => city
+                        .as_ref()
+                        .and_then(|v| v.city.as_ref())
+                        .and_then(|v| v.names.as_ref())
+                        .and_then(|v| v.get("en"))
+                        .map(|v| Value::from(*v)),
+                    GeoIpAttribute::ContinentCode => city
+                        .as_ref()
+                        .and_then(|v| v.continent.as_ref())
+                        .and_then(|v| v.code)
+                        .map(Value::from),
+                    GeoIpAttribute::Latitude => city
+                        .as_ref()
+                        .and_then(|v| v.location.as_ref())
+                        .and_then(|v| v.latitude)
+                        .map(Value::from),
+                    GeoIpAttribute::Longitude => city
+                        .as_ref()
+                        .and_then(|v| v.location.as_ref())
+                        .and_then(|v| v.longitude)
+                        .map(Value::from),
+                    GeoIpAttribute::TimeZone => city
+                        .as_ref()
+                        .and_then(|v| v.location.as_ref())
+                        .and_then(|v| v.time_zone)
+                        .map(Value::from),
+                    GeoIpAttribute::Asn => asn
+                        .as_ref()
+                        .and_then(|v| v.autonomous_system_number)
+                        .map(Value::from),
+                    GeoIpAttribute::AsOrg => asn
+                        .as_ref()
+                        .and_then(|v| v.autonomous_system_organization)
+                        .map(Value::from),
+                };
+                if let Some(value) = value {
+                    record.insert(field.to_string(), value);
+                }
+            }
+        }
+    }
+}
+
+/// Adds the geo-IP enricher of the stream to the map, if its pipeline has a geo-IP enrichment
+pub fn get_stream_geo_ip(
+    stream_params: StreamParams,
+    stream_geo_ip_map: &mut HashMap<String, GeoIpEnricher>,
+) {
+    let Some(enrichments) = STREAM_PIPELINES
+        .get(&format!(
+            "{}/{}/{}",
+            &stream_params.org_id, stream_params.stream_type, &stream_params.stream_name,
+        ))
+        .and_then(|pipeline| pipeline.geo_ip.clone())
+        .filter(|v| !v.is_empty())
+    else {
+        return;
+    };
+    let path = &get_config().common.geoip_enrichment_db_path;
+    match get_database(path) {
+        Ok(reader) => {
+            stream_geo_ip_map.insert(
+                stream_params.stream_name.to_string(),
+                GeoIpEnricher::new(enrichments, reader),
+            );
+        }
+        Err(e) => {
+            log::error!(
+                "[GEOIP] load database {path} for stream {} error: {e}",
+                stream_params.stream_name
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    fn test_database_path() -> String {
+        format!(
+            "{}/tests/test-data/geoip/test-city-asn.mmdb",
+            env!("CARGO_MANIFEST_DIR")
+        )
+    }
+
+    fn test_enricher() -> GeoIpEnricher {
+        let enrichment = GeoIpEnrichment {
+            source_field: "client_ip".to_string(),
+            fields: HashMap::from([
+                ("geo_country".to_string(), GeoIpAttribute::CountryName),
+                (
+                    "geo_country_code".to_string(),
+                    GeoIpAttribute::CountryIsoCode,
+                ),
+                ("geo_city".to_string(), GeoIpAttribute::CityName),
+                ("geo_lat".to_string(), GeoIpAttribute::Latitude),
+                ("geo_asn".to_string(), GeoIpAttribute::Asn),
+                ("geo_as_org".to_string(), GeoIpAttribute::AsOrg),
+            ]),
+        };
+        let reader = get_database(&test_database_path()).unwrap();
+        GeoIpEnricher::new(vec![enrichment], reader)
+    }
+
+    #[test]
+    fn test_geo_ip_enrich() {
+        let enricher = test_enricher();
+        let mut record = json::json!({"client_ip": "81.2.69.142", "message": "GET /"})
+            .as_object()
+            .unwrap()
+            .clone();
+        enricher.enrich(&mut record);
+        assert_eq!(record["geo_country"], "United Kingdom");
+        assert_eq!(record["geo_country_code"], "GB");
+        assert_eq!(record["geo_city"], "London");
+        assert_eq!(record["geo_lat"], 51.5142);
+        assert_eq!(record["geo_asn"], 20712);
+        assert_eq!(record["geo_as_org"], "Andrews & Arnold Ltd");
+        assert_eq!(record["message"], "GET /");
+    }
+
+    #[test]
+    fn test_geo_ip_enrich_unset() {
+        let enricher = test_enricher();
+        for record in [
+            json::json!({"message": "no ip"}),
+            json::json!({"client_ip": "not an ip"}),
+            json::json!({"client_ip": 42}),
+            // not in the database
+            json::json!({"client_ip": "10.0.0.1"}),
+        ] {
+            let mut record = record.as_object().unwrap().clone();
+            let fields = record.len();
+            enricher.enrich(&mut record);
+            assert_eq!(record.len(), fields, "{record:?}");
+        }
+    }
+
+    #[test]
+    fn test_geo_ip_database_reload() {
+        let path = std::env::temp_dir().join(format!("geoip-reload-{}.mmdb", std::process::id()));
+        std::fs::copy(test_database_path(), &path).unwrap();
+        let path = path.to_str().unwrap();
+
+        let reader = get_database(path).unwrap();
+        // loaded once
+        assert!(Arc::ptr_eq(&reader, &get_database(path).unwrap()));
+
+        let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified + std::time::Duration::from_secs(60))
+            .unwrap();
+        assert!(!Arc::ptr_eq(&reader, &get_database(path).unwrap()));
+        std::fs::remove_file(path).unwrap();
+    }
+}
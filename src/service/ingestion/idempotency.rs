@@ -0,0 +1,310 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{future::Future, io::Error};
+
+use actix_web::{
+    body::to_bytes,
+    http::{header, StatusCode},
+    HttpRequest, HttpResponse,
+};
+use config::{
+    get_config,
+    utils::{json, time::now_micros},
+};
+use infra::{errors, table::idempotency_keys};
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, organization::OrganizationSetting},
+    service::db::organization::get_org_setting,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "X-Idempotency-Key";
+/// OTLP has no header identifying a request, the one the proxies set and forward is used
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const MAX_KEY_LEN: usize = 256;
+/// The prefix of the OTLP request ids in the idempotency keys
+const OTLP_KEY_PREFIX: &str = "otlp:";
+/// A key in progress for longer than this is considered abandoned, eg: the ingester crashed
+const IN_PROGRESS_TIMEOUT_SECS: i64 = 600;
+
+/// Runs the ingestion of the request once per `X-Idempotency-Key`.
+///
+/// The key is recorded in the meta store shared by the ingesters before anything is written to
+/// the WAL, so a retried batch, sent to this or any other ingester, gets the response of the
+/// first request instead of being ingested again. The keys are kept for
+/// `ZO_INGEST_IDEMPOTENCY_TTL` seconds, failed requests release the key so they can be retried.
+pub async fn ingest_once<F, Fut>(
+    org_id: &str,
+    req: &HttpRequest,
+    ingest: F,
+) -> Result<HttpResponse, Error>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<HttpResponse, Error>>,
+{
+    let ttl = get_config().limit.ingest_idempotency_ttl;
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(v) if ttl > 0 => match v.to_str() {
+            Ok(v) if !v.is_empty() && v.len() <= MAX_KEY_LEN => v.to_string(),
+            _ => {
+                return Ok(MetaHttpResponse::bad_request(format!(
+                    "{IDEMPOTENCY_KEY_HEADER} must be a string of at most {MAX_KEY_LEN} characters"
+                )));
+            }
+        },
+        _ => return ingest().await,
+    };
+
+    match reserve(org_id, &key, ttl).await {
+        Ok(None) => {}
+        Ok(Some(record)) => return Ok(replay(record)),
+        Err(e) => {
+            log::error!("[INGEST] record idempotency key {org_id}/{key} error: {e}");
+            return Ok(MetaHttpResponse::internal_error(e));
+        }
+    }
+
+    let resp = match ingest().await {
+        Ok(resp) if resp.status().is_success() => resp,
+        ret => {
+            release(org_id, &key).await;
+            return ret;
+        }
+    };
+    let status = resp.status();
+    let content_type = resp.headers().get(header::CONTENT_TYPE).cloned();
+    let body = match to_bytes(resp.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            release(org_id, &key).await;
+            return Err(Error::other(e.to_string()));
+        }
+    };
+    if let Err(e) = idempotency_keys::finish(
+        org_id,
+        &key,
+        status.as_u16() as i32,
+        &String::from_utf8_lossy(&body),
+    )
+    .await
+    {
+        log::error!("[INGEST] save idempotency key {org_id}/{key} error: {e}");
+    }
+
+    let mut builder = HttpResponse::build(status);
+    if let Some(content_type) = content_type {
+        builder.insert_header((header::CONTENT_TYPE, content_type));
+    }
+    Ok(builder.body(body))
+}
+
+/// Runs the ingestion of an OTLP/HTTP request once per `X-Request-Id`, for the orgs which enable
+/// `otlp_dedupe_enabled`.
+///
+/// The id is recorded with the idempotency keys, shared by the ingesters, for
+/// `ZO_INGEST_OTLP_DEDUPE_TTL` seconds. A batch sent again with the same id, eg: by a CDN retrying
+/// the POST to any ingester, is acknowledged with an empty export response without being
+/// ingested, or gets a conflict while the first request is in progress. A failed request releases
+/// its id so it can be retried.
+pub async fn ingest_otlp_once<F, Fut>(
+    org_id: &str,
+    req: &HttpRequest,
+    ingest: F,
+) -> Result<HttpResponse, Error>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<HttpResponse, Error>>,
+{
+    let ttl = get_config().limit.ingest_otlp_dedupe_ttl;
+    let request_id = match req.headers().get(REQUEST_ID_HEADER) {
+        Some(v) if ttl > 0 => match v.to_str() {
+            Ok(v) if !v.is_empty() && v.len() <= MAX_KEY_LEN - OTLP_KEY_PREFIX.len() => v,
+            _ => return ingest().await,
+        },
+        _ => return ingest().await,
+    };
+    if !is_otlp_dedupe_enabled(org_id).await {
+        return ingest().await;
+    }
+
+    let key = format!("{OTLP_KEY_PREFIX}{request_id}");
+    match reserve(org_id, &key, ttl).await {
+        Ok(None) => {}
+        Ok(Some(record)) if record.status == 0 => {
+            return Ok(MetaHttpResponse::conflict(format!(
+                "a request with the same {REQUEST_ID_HEADER} is in progress"
+            )));
+        }
+        Ok(Some(_)) => {
+            log::info!("[INGEST] drop the OTLP batch {org_id}/{request_id} already received");
+            return Ok(otlp_ack(req));
+        }
+        Err(e) => {
+            log::error!("[INGEST] record OTLP request id {org_id}/{request_id} error: {e}");
+            return Ok(MetaHttpResponse::internal_error(e));
+        }
+    }
+    let ret = ingest().await;
+    match ret.as_ref() {
+        Ok(resp) if resp.status().is_success() => {
+            if let Err(e) =
+                idempotency_keys::finish(org_id, &key, resp.status().as_u16() as i32, "").await
+            {
+                log::error!("[INGEST] save OTLP request id {org_id}/{request_id} error: {e}");
+            }
+        }
+        _ => release(org_id, &key).await,
+    }
+    ret
+}
+
+async fn is_otlp_dedupe_enabled(org_id: &str) -> bool {
+    get_org_setting(org_id)
+        .await
+        .ok()
+        .and_then(|v| json::from_slice::<OrganizationSetting>(&v).ok())
+        .is_some_and(|v| v.otlp_dedupe_enabled)
+}
+
+/// An empty export response, in the encoding of the request. The empty protobuf message has no
+/// bytes.
+fn otlp_ack(req: &HttpRequest) -> HttpResponse {
+    match req.headers().get(header::CONTENT_TYPE) {
+        Some(content_type) if !content_type.as_bytes().starts_with(b"application/json") => {
+            HttpResponse::Ok()
+                .insert_header((header::CONTENT_TYPE, content_type.clone()))
+                .finish()
+        }
+        _ => HttpResponse::Ok().json(json::json!({})),
+    }
+}
+
+/// Records the key as in progress, returns the record of the key if it's already processed or
+/// in progress within the dedupe window
+async fn reserve(
+    org_id: &str,
+    key: &str,
+    ttl: i64,
+) -> Result<Option<idempotency_keys::IdempotencyRecord>, errors::Error> {
+    // retry once, the existing record can be expired or released in between
+    for _ in 0..2 {
+        if idempotency_keys::add(org_id, key).await? {
+            return Ok(None);
+        }
+        match idempotency_keys::get(org_id, key).await? {
+            Some(record) if !is_expired(&record, ttl, now_micros()) => return Ok(Some(record)),
+            Some(_) => idempotency_keys::remove(org_id, key).await?,
+            None => {}
+        }
+    }
+    Err(errors::Error::Message(format!(
+        "idempotency key {key} is being recorded concurrently"
+    )))
+}
+
+async fn release(org_id: &str, key: &str) {
+    if let Err(e) = idempotency_keys::remove(org_id, key).await {
+        log::error!("[INGEST] release idempotency key {org_id}/{key} error: {e}");
+    }
+}
+
+fn is_expired(record: &idempotency_keys::IdempotencyRecord, ttl: i64, now: i64) -> bool {
+    let ttl = if record.status == 0 {
+        std::cmp::min(ttl, IN_PROGRESS_TIMEOUT_SECS)
+    } else {
+        ttl
+    };
+    record.created_ts < now - ttl * 1_000_000
+}
+
+/// Returns the response of the first request of the key
+fn replay(record: idempotency_keys::IdempotencyRecord) -> HttpResponse {
+    if record.status == 0 {
+        return MetaHttpResponse::conflict(format!(
+            "a request with the same {IDEMPOTENCY_KEY_HEADER} is in progress"
+        ));
+    }
+    let status = StatusCode::from_u16(record.status as u16).unwrap_or(StatusCode::OK);
+    HttpResponse::build(status)
+        .content_type(header::ContentType::json())
+        .body(record.response)
+}
+
+/// Removes the keys out of the dedupe window, the window of the OTLP request ids can be longer
+pub async fn run_gc() -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    let ttl = std::cmp::max(
+        cfg.limit.ingest_idempotency_ttl,
+        cfg.limit.ingest_otlp_dedupe_ttl,
+    );
+    if ttl <= 0 {
+        return Ok(());
+    }
+    let expired_before = now_micros() - ttl * 1_000_000;
+    let num = idempotency_keys::remove_expired(expired_before).await?;
+    if num > 0 {
+        log::info!("[INGEST] removed {num} expired idempotency keys");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn test_otlp_ack() {
+        let req = TestRequest::post()
+            .insert_header((header::CONTENT_TYPE, "application/x-protobuf"))
+            .to_http_request();
+        let resp = otlp_ack(&req);
+        assert!(resp.status().is_success());
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/x-protobuf"
+        );
+
+        for req in [
+            TestRequest::post()
+                .insert_header((header::CONTENT_TYPE, "application/json; charset=utf-8"))
+                .to_http_request(),
+            TestRequest::post().to_http_request(),
+        ] {
+            let resp = otlp_ack(&req);
+            assert_eq!(
+                resp.headers().get(header::CONTENT_TYPE).unwrap(),
+                "application/json"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let now = 10_000 * 1_000_000;
+        let record = |status, age_secs: i64| idempotency_keys::IdempotencyRecord {
+            status,
+            response: "".to_string(),
+            created_ts: now - age_secs * 1_000_000,
+        };
+        assert!(!is_expired(&record(200, 3000), 3600, now));
+        assert!(is_expired(&record(200, 4000), 3600, now));
+        // abandoned requests in progress are released earlier
+        assert!(!is_expired(&record(0, 300), 3600, now));
+        assert!(is_expired(&record(0, 900), 3600, now));
+    }
+}
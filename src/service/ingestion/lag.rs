@@ -0,0 +1,173 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The ingestion lag of a stream is the time between now and its newest record, either already
+//! in the file list or still in the memtable of an ingester.
+
+use config::{
+    cluster::LOCAL_NODE,
+    get_config,
+    meta::{
+        cluster::get_internal_grpc_token,
+        stream::{StreamStats, StreamType},
+    },
+    metrics,
+    utils::time::now_micros,
+};
+use futures::future::join_all;
+use infra::cache::stats;
+use proto::cluster_rpc;
+use tonic::{codec::CompressionEncoding, metadata::MetadataValue, Request};
+
+use crate::{
+    common::{infra::cluster::get_cached_online_ingester_nodes, meta::stream::IngestionLag},
+    service::grpc::get_cached_channel,
+};
+
+/// Computes the lag from the file list stats and the newest memtable timestamp of the stream
+pub fn compute(now: i64, stats: &StreamStats, memtable_max_ts: i64) -> IngestionLag {
+    let file_list_max_ts = stats.doc_time_max;
+    let max_ts = file_list_max_ts.max(memtable_max_ts);
+    IngestionLag {
+        file_list_max_ts,
+        memtable_max_ts,
+        max_ts,
+        lag_seconds: (max_ts > 0).then(|| (now - max_ts).max(0) / 1_000_000),
+    }
+}
+
+/// Returns the ingestion lag of the stream, asking the ingesters for their newest timestamp
+pub async fn get(org_id: &str, stream_name: &str, stream_type: StreamType) -> IngestionLag {
+    let stats = stats::get_stream_stats(org_id, stream_name, stream_type);
+    let memtable_max_ts = get_memtable_max_ts(org_id, stream_name, stream_type).await;
+    compute(now_micros(), &stats, memtable_max_ts)
+}
+
+async fn get_memtable_max_ts(org_id: &str, stream_name: &str, stream_type: StreamType) -> i64 {
+    if LOCAL_NODE.is_ingester() && get_config().common.local_mode {
+        return ingester::get_max_timestamp(org_id, &stream_type.to_string(), stream_name);
+    }
+    let Some(nodes) = get_cached_online_ingester_nodes().await else {
+        return 0;
+    };
+    let tasks = nodes.into_iter().map(|node| {
+        let req = cluster_rpc::MemtableMaxTimestampRequest {
+            org_id: org_id.to_string(),
+            stream_type: stream_type.to_string(),
+            stream_name: stream_name.to_string(),
+        };
+        async move {
+            match get_node_memtable_max_ts(&node.grpc_addr, req).await {
+                Ok(max_ts) => max_ts,
+                Err(e) => {
+                    log::error!(
+                        "[INGEST_LAG] get memtable max timestamp from node {} error: {e}",
+                        node.grpc_addr
+                    );
+                    0
+                }
+            }
+        }
+    });
+    join_all(tasks).await.into_iter().max().unwrap_or_default()
+}
+
+async fn get_node_memtable_max_ts(
+    addr: &str,
+    req: cluster_rpc::MemtableMaxTimestampRequest,
+) -> anyhow::Result<i64> {
+    let cfg = get_config();
+    let token: MetadataValue<_> = get_internal_grpc_token().parse()?;
+    let channel = get_cached_channel(addr).await?;
+    let mut client = cluster_rpc::ingest_client::IngestClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            Ok(req)
+        },
+    )
+    .send_compressed(CompressionEncoding::Gzip)
+    .accept_compressed(CompressionEncoding::Gzip);
+    let mut request = Request::new(req);
+    request.set_timeout(std::time::Duration::from_secs(cfg.limit.query_timeout));
+    Ok(client
+        .memtable_max_timestamp(request)
+        .await?
+        .into_inner()
+        .max_ts)
+}
+
+/// Sets the lag metric of the streams ingested by this node
+pub fn update_metrics() {
+    let now = now_micros();
+    for (key, memtable_max_ts) in ingester::get_max_timestamps() {
+        let columns = key.splitn(3, '/').collect::<Vec<_>>();
+        let [org_id, stream_type, stream_name] = columns[..] else {
+            continue;
+        };
+        let stream_type = StreamType::from(stream_type);
+        let stats = stats::get_stream_stats(org_id, stream_name, stream_type);
+        if let Some(lag) = compute(now, &stats, memtable_max_ts).lag_seconds {
+            metrics::INGEST_LAG_SECONDS
+                .with_label_values(&[org_id, stream_name, &stream_type.to_string()])
+                .set(lag);
+        }
+    }
+}
+
+/// Removes the newest timestamp and the lag metric of a deleted stream
+pub fn remove(org_id: &str, stream_name: &str, stream_type: StreamType) {
+    let stream_type = stream_type.to_string();
+    ingester::remove_max_timestamp(org_id, &stream_type, stream_name);
+    // the metric is only set once the stream has a newest timestamp
+    let _ = metrics::INGEST_LAG_SECONDS.remove_label_values(&[org_id, stream_name, &stream_type]);
+}
+
+#[cfg(test)]
+mod tests {
+    use config::meta::stream::FileMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_compute_lag() {
+        let now = 1_700_000_000_000_000;
+        let mut stats = StreamStats::default();
+        for max_ts in [now - 300_000_000, now - 120_000_000] {
+            stats.add_file_meta(&FileMeta {
+                min_ts: max_ts - 60_000_000,
+                max_ts,
+                records: 10,
+                ..Default::default()
+            });
+        }
+
+        // the memtable is behind the file list
+        let lag = compute(now, &stats, 0);
+        assert_eq!(lag.file_list_max_ts, now - 120_000_000);
+        assert_eq!(lag.max_ts, now - 120_000_000);
+        assert_eq!(lag.lag_seconds, Some(120));
+
+        // the memtable has newer records than the file list
+        let lag = compute(now, &stats, now - 5_000_000);
+        assert_eq!(lag.memtable_max_ts, now - 5_000_000);
+        assert_eq!(lag.max_ts, now - 5_000_000);
+        assert_eq!(lag.lag_seconds, Some(5));
+
+        // a stream without data has no lag
+        let lag = compute(now, &StreamStats::default(), 0);
+        assert_eq!(lag.lag_seconds, None);
+    }
+}
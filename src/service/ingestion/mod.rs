@@ -26,8 +26,8 @@ use config::{
     ider::SnowflakeIdGenerator,
     meta::{
         stream::{
-            PartitionTimeLevel, PartitioningDetails, Routing, StreamParams, StreamPartition,
-            StreamType,
+            FlattenArrayMode, PartitionTimeLevel, PartitioningDetails, Routing, StreamParams,
+            StreamPartition, StreamType, TimestampSettings,
         },
         usage::{RequestStats, TriggerData, TriggerDataStatus, TriggerDataType},
     },
@@ -61,8 +61,12 @@ use crate::{
     service::db,
 };
 
+pub mod geo_ip;
 pub mod grpc;
+pub mod idempotency;
 pub mod ingestion_service;
+pub mod lag;
+pub mod user_agent;
 
 pub type TriggerAlertData = Vec<(Alert, Vec<Map<String, Value>>)>;
 
@@ -316,6 +320,12 @@ pub async fn evaluate_trigger(triggers: TriggerAlertData) {
     }
 }
 
+/// Returns the partition of a record in the WAL: `{YYYY}/{MM}/{DD}/{HH}/{suffix}/{field=value}/..`
+/// with one `field=value` level for each enabled partition key in the order of the stream
+/// settings. The hour is `00` for the daily partitions and the suffix is `default` unless set.
+/// The parquet files keep the levels except the suffix, e.g.
+/// `files/{org}/{stream_type}/{stream}/2024/07/01/08/tenant_id=acme/{id}.parquet`, and the search
+/// prunes the files by the partition keys in the path.
 pub fn get_write_partition_key(
     timestamp: i64,
     partition_keys: &Vec<StreamPartition>,
@@ -642,6 +652,67 @@ pub async fn get_uds_and_original_data_streams(
     }
 }
 
+pub async fn get_stream_timestamp_settings(
+    streams: &[StreamParams],
+    timestamp_settings_map: &mut HashMap<String, TimestampSettings>,
+) {
+    for stream in streams {
+        if let Some(timestamp_settings) =
+            infra::schema::get_settings(&stream.org_id, &stream.stream_name, stream.stream_type)
+                .await
+                .and_then(|s| s.timestamp_settings)
+        {
+            timestamp_settings_map.insert(stream.stream_name.to_string(), timestamp_settings);
+        }
+    }
+}
+
+/// The flattening options of a stream which overrides the global flatten level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlattenSettings {
+    pub max_level: u32,
+    pub array_mode: FlattenArrayMode,
+}
+
+pub async fn get_stream_flatten_settings(
+    streams: &[StreamParams],
+    flatten_settings_map: &mut HashMap<String, FlattenSettings>,
+) {
+    let cfg = get_config();
+    for stream in streams {
+        let Some(settings) =
+            infra::schema::get_settings(&stream.org_id, &stream.stream_name, stream.stream_type)
+                .await
+        else {
+            continue;
+        };
+        if settings.flatten_level.is_none() && settings.flatten_array.is_none() {
+            continue;
+        }
+        flatten_settings_map.insert(
+            stream.stream_name.to_string(),
+            FlattenSettings {
+                max_level: settings
+                    .flatten_level
+                    .map(|v| v.max(0) as u32)
+                    .unwrap_or(cfg.limit.ingest_flatten_level),
+                array_mode: settings.flatten_array.unwrap_or_default(),
+            },
+        );
+    }
+}
+
+/// Flattens the record with the settings of the stream, or the global flatten level if the
+/// stream has none
+pub fn flatten_with_settings(value: Value, settings: Option<&FlattenSettings>) -> Result<Value> {
+    match settings {
+        Some(settings) => {
+            flatten::flatten_with_options(value, settings.max_level, settings.array_mode)
+        }
+        None => flatten::flatten_with_level(value, get_config().limit.ingest_flatten_level),
+    }
+}
+
 /// Calls the SnowflakeIdGenerator instance associated with this stream to generate a new i64 ID.
 pub fn generate_record_id(org_id: &str, stream_name: &str, stream_type: &StreamType) -> i64 {
     let key = format!("{}/{}/{}", org_id, stream_type, stream_name);
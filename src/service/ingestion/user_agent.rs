@@ -0,0 +1,172 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The user-agent parsing of pipelines, with the parser of the RUM ingestion.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use config::{
+    meta::stream::StreamParams,
+    utils::json::{Map, Value},
+};
+use uaparser::{Client, Parser};
+
+use crate::common::{
+    infra::config::STREAM_PIPELINES,
+    meta::{
+        middleware_data::UA_PARSER,
+        pipelines::{UserAgentAttribute, UserAgentParsing},
+    },
+};
+
+/// The family of the parts of a user agent the regexes don't match
+const UNKNOWN_FAMILY: &str = "Other";
+
+/// Adds the output fields of the parsed attributes to the flattened record. The fields are null
+/// when the source field is missing or the user agent can't be parsed.
+pub fn parse(record: &mut Map<String, Value>, parsings: &[UserAgentParsing]) {
+    for parsing in parsings.iter() {
+        let client = record
+            .get(&parsing.source_field)
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(|v| UA_PARSER.parse(v));
+        for (field, attribute) in parsing.fields.iter() {
+            let value = client
+                .as_ref()
+                .and_then(|v| get_attribute(v, *attribute))
+                .map(Value::from)
+                .unwrap_or(Value::Null);
+            record.insert(field.to_string(), value);
+        }
+    }
+}
+
+fn get_attribute(client: &Client, attribute: UserAgentAttribute) -> Option<String> {
+    let ua = &client.user_agent;
+    let os = &client.os;
+    let device = &client.device;
+    match attribute {
+        UserAgentAttribute::BrowserFamily => known_family(&ua.family),
+        UserAgentAttribute::BrowserVersion => {
+            known_family(&ua.family).and_then(|_| join_version(&[&ua.major, &ua.minor, &ua.patch]))
+        }
+        UserAgentAttribute::OsFamily => known_family(&os.family),
+        UserAgentAttribute::OsVersion => known_family(&os.family)
+            .and_then(|_| join_version(&[&os.major, &os.minor, &os.patch, &os.patch_minor])),
+        UserAgentAttribute::DeviceFamily => known_family(&device.family),
+        UserAgentAttribute::DeviceBrand => device.brand.as_ref().map(|v| v.to_string()),
+        UserAgentAttribute::DeviceModel => device.model.as_ref().map(|v| v.to_string()),
+    }
+}
+
+fn known_family(family: &str) -> Option<String> {
+    (family != UNKNOWN_FAMILY).then(|| family.to_string())
+}
+
+/// Joins the version parts up to the first missing one
+fn join_version(parts: &[&Option<Cow<str>>]) -> Option<String> {
+    let parts = parts.iter().map_while(|v| v.as_deref()).collect::<Vec<_>>();
+    (!parts.is_empty()).then(|| parts.join("."))
+}
+
+/// Adds the user-agent parsings of the stream to the map, if its pipeline has any
+pub fn get_stream_user_agent(
+    stream_params: StreamParams,
+    stream_user_agent_map: &mut HashMap<String, Vec<UserAgentParsing>>,
+) {
+    let Some(parsings) = STREAM_PIPELINES
+        .get(&format!(
+            "{}/{}/{}",
+            &stream_params.org_id, stream_params.stream_type, &stream_params.stream_name,
+        ))
+        .and_then(|pipeline| pipeline.user_agent.clone())
+        .filter(|v| !v.is_empty())
+    else {
+        return;
+    };
+    stream_user_agent_map.insert(stream_params.stream_name.to_string(), parsings);
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    fn parsings() -> Vec<UserAgentParsing> {
+        vec![UserAgentParsing {
+            source_field: "http_user_agent".to_string(),
+            fields: HashMap::from([
+                ("browser".to_string(), UserAgentAttribute::BrowserFamily),
+                (
+                    "browser_version".to_string(),
+                    UserAgentAttribute::BrowserVersion,
+                ),
+                ("os".to_string(), UserAgentAttribute::OsFamily),
+                ("os_version".to_string(), UserAgentAttribute::OsVersion),
+                ("device".to_string(), UserAgentAttribute::DeviceFamily),
+                ("device_brand".to_string(), UserAgentAttribute::DeviceBrand),
+            ]),
+        }]
+    }
+
+    fn parse_user_agent(user_agent: Value) -> Map<String, Value> {
+        let mut record = json::json!({ "http_user_agent": user_agent })
+            .as_object()
+            .unwrap()
+            .clone();
+        parse(&mut record, &parsings());
+        record
+    }
+
+    #[test]
+    fn test_parse_user_agent() {
+        let record = parse_user_agent(Value::from(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.6099.109 Safari/537.36",
+        ));
+        assert_eq!(record["browser"], "Chrome");
+        assert_eq!(record["browser_version"], "120.0.6099");
+        assert_eq!(record["os"], "Windows");
+        assert_eq!(record["os_version"], "10");
+        assert_eq!(record["device"], Value::Null);
+
+        let record = parse_user_agent(Value::from(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.1 Mobile/15E148 Safari/604.1",
+        ));
+        assert_eq!(record["browser"], "Mobile Safari");
+        assert_eq!(record["browser_version"], "17.1");
+        assert_eq!(record["os"], "iOS");
+        assert_eq!(record["os_version"], "17.1");
+        assert_eq!(record["device"], "iPhone");
+        assert_eq!(record["device_brand"], "Apple");
+    }
+
+    #[test]
+    fn test_parse_user_agent_unparseable() {
+        for user_agent in [Value::from("definitely not a user agent"), Value::from(42)] {
+            let record = parse_user_agent(user_agent);
+            for field in ["browser", "browser_version", "os", "os_version", "device"] {
+                assert_eq!(record[field], Value::Null, "{field}");
+            }
+        }
+
+        // the source field is missing
+        let mut record = Map::new();
+        parse(&mut record, &parsings());
+        assert_eq!(record.len(), 6);
+        assert!(record.values().all(|v| v.is_null()));
+    }
+}
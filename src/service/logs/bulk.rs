@@ -24,11 +24,11 @@ use chrono::{Duration, Utc};
 use config::{
     get_config,
     meta::{
-        stream::{Routing, StreamParams, StreamType},
+        stream::{Routing, StreamParams, StreamType, TimestampSettings},
         usage::UsageType,
     },
     metrics,
-    utils::{flatten, json, time::parse_timestamp_micro_from_value},
+    utils::json,
     BLOCKED_STREAMS, ID_COL_NAME, ORIGINAL_DATA_COL_NAME,
 };
 
@@ -38,13 +38,19 @@ use crate::{
         ingestion::{BulkResponse, BulkResponseError, BulkResponseItem, IngestionStatus},
     },
     service::{
-        format_stream_name, ingestion::check_ingestion_allowed, schema::get_upto_discard_error,
+        format_stream_name,
+        ingestion::{check_ingestion_allowed, FlattenSettings},
+        logs::ingest::handle_timestamp,
+        usage::{report_latency, LatencyKind},
     },
 };
 
 pub const TRANSFORM_FAILED: &str = "document_failed_transform";
 pub const TS_PARSE_FAILED: &str = "timestamp_parsing_failed";
 pub const SCHEMA_CONFORMANCE_FAILED: &str = "schema_conformance_failed";
+pub const DOCUMENT_PARSE_FAILED: &str = "document_parsing_failed";
+pub const STREAM_BLOCKED: &str = "stream_blocked";
+pub const STREAM_WRITE_FAILED: &str = "stream_write_failed";
 
 pub async fn ingest(
     thread_id: usize,
@@ -78,11 +84,17 @@ pub async fn ingest(
     let mut action = String::from("");
     let mut stream_name = String::from("");
     let mut doc_id = None;
+    // the data line of a rejected action line is skipped, the failure is already reported
+    let mut skip_data = false;
 
     let mut blocked_stream_warnings: HashMap<String, bool> = HashMap::new();
     let mut stream_routing_map: HashMap<String, Vec<Routing>> = HashMap::new();
+    let mut stream_geo_ip_map = HashMap::new();
+    let mut stream_user_agent_map = HashMap::new();
     let mut user_defined_schema_map: HashMap<String, HashSet<String>> = HashMap::new();
     let mut streams_need_original_set: HashSet<String> = HashSet::new();
+    let mut timestamp_settings_map: HashMap<String, TimestampSettings> = HashMap::new();
+    let mut flatten_settings_map: HashMap<String, FlattenSettings> = HashMap::new();
 
     let mut json_data_by_stream = HashMap::new();
     let mut next_line_is_data = false;
@@ -93,7 +105,30 @@ pub async fn ingest(
             continue;
         }
 
-        let mut value: json::Value = json::from_slice(line.as_bytes())?;
+        let mut value: json::Value = match json::from_slice(line.as_bytes()) {
+            Ok(v) => v,
+            Err(e) => {
+                bulk_res.errors = true;
+                if next_line_is_data {
+                    next_line_is_data = false;
+                } else {
+                    // the action is unknown, the next line is taken as its data line
+                    (action, stream_name, doc_id) = ("".to_string(), "".to_string(), None);
+                    next_line_is_data = true;
+                    skip_data = true;
+                }
+                add_record_status(
+                    stream_name.clone(),
+                    &doc_id,
+                    action.clone(),
+                    None,
+                    &mut bulk_res,
+                    Some(DOCUMENT_PARSE_FAILED.to_string()),
+                    Some(e.to_string()),
+                );
+                continue;
+            }
+        };
 
         if !next_line_is_data {
             // check bulk operate
@@ -106,6 +141,9 @@ pub async fn ingest(
             if !cfg.common.skip_formatting_stream_name {
                 stream_name = format_stream_name(&stream_name);
             }
+            // ingesting to a stream alias writes to the stream it belongs to
+            stream_name =
+                infra::schema::resolve_stream_alias(org_id, &stream_name, StreamType::Logs).await;
 
             // skip blocked streams
             let key = format!("{org_id}/{}/{stream_name}", StreamType::Logs);
@@ -115,6 +153,18 @@ pub async fn ingest(
                     log::warn!("stream [{stream_name}] is blocked from ingestion");
                     true
                 });
+                bulk_res.errors = true;
+                add_record_status(
+                    stream_name.clone(),
+                    &doc_id,
+                    action.clone(),
+                    None,
+                    &mut bulk_res,
+                    Some(STREAM_BLOCKED.to_string()),
+                    Some(format!("stream [{stream_name}] is blocked from ingestion")),
+                );
+                next_line_is_data = true;
+                skip_data = true;
                 continue; // skip
             }
 
@@ -146,12 +196,43 @@ pub async fn ingest(
             }
             // End get stream keys
 
+            if !stream_geo_ip_map.contains_key(&stream_name) {
+                crate::service::ingestion::geo_ip::get_stream_geo_ip(
+                    StreamParams {
+                        org_id: org_id.to_owned().into(),
+                        stream_type: StreamType::Logs,
+                        stream_name: stream_name.to_owned().into(),
+                    },
+                    &mut stream_geo_ip_map,
+                );
+            }
+            if !stream_user_agent_map.contains_key(&stream_name) {
+                crate::service::ingestion::user_agent::get_stream_user_agent(
+                    StreamParams {
+                        org_id: org_id.to_owned().into(),
+                        stream_type: StreamType::Logs,
+                        stream_name: stream_name.to_owned().into(),
+                    },
+                    &mut stream_user_agent_map,
+                );
+            }
+
             crate::service::ingestion::get_uds_and_original_data_streams(
                 &streams,
                 &mut user_defined_schema_map,
                 &mut streams_need_original_set,
             )
             .await;
+            crate::service::ingestion::get_stream_timestamp_settings(
+                &streams,
+                &mut timestamp_settings_map,
+            )
+            .await;
+            crate::service::ingestion::get_stream_flatten_settings(
+                &streams,
+                &mut flatten_settings_map,
+            )
+            .await;
 
             next_line_is_data = true;
 
@@ -166,6 +247,10 @@ pub async fn ingest(
             // End Register functions for index
         } else {
             next_line_is_data = false;
+            if skip_data {
+                skip_data = false;
+                continue;
+            }
 
             // store a copy of original data before it's being transformed and/or flattened, unless
             // 1. original data is not an object -> won't be flattened.
@@ -187,37 +272,69 @@ pub async fn ingest(
             // Start row based transform before flattening the value
             if let Some(transforms) = stream_before_functions_map.get(&main_stream_key) {
                 if !transforms.is_empty() {
-                    let mut ret_value = value.clone();
-                    ret_value = crate::service::ingestion::apply_stream_functions(
+                    let ret_value = crate::service::ingestion::apply_stream_functions(
                         transforms,
-                        ret_value,
+                        value.clone(),
                         &stream_vrl_map,
                         org_id,
                         &stream_name,
                         &mut runtime,
-                    )?;
-
-                    if ret_value.is_null() || !ret_value.is_object() {
-                        bulk_res.errors = true;
-                        add_record_status(
-                            stream_name.clone(),
-                            &doc_id,
-                            action.clone(),
-                            Some(value),
-                            &mut bulk_res,
-                            Some(TRANSFORM_FAILED.to_owned()),
-                            Some(TRANSFORM_FAILED.to_owned()),
-                        );
-                        continue;
-                    } else {
-                        value = ret_value;
+                    );
+                    match ret_value {
+                        Ok(ret_value) if ret_value.is_object() => {
+                            value = ret_value;
+                        }
+                        ret_value => {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                stream_name.clone(),
+                                &doc_id,
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TRANSFORM_FAILED.to_owned()),
+                                Some(transform_failure_reason(ret_value)),
+                            );
+                            continue;
+                        }
                     }
                 }
             }
             // end row based transformation
 
             // JSON Flattening
-            let mut value = flatten::flatten_with_level(value, cfg.limit.ingest_flatten_level)?;
+            let mut value = match crate::service::ingestion::flatten_with_settings(
+                value.clone(),
+                flatten_settings_map.get(&stream_name),
+            ) {
+                Ok(v) => v,
+                Err(e) => {
+                    bulk_res.errors = true;
+                    add_record_status(
+                        stream_name.clone(),
+                        &doc_id,
+                        action.clone(),
+                        Some(value),
+                        &mut bulk_res,
+                        Some(DOCUMENT_PARSE_FAILED.to_string()),
+                        Some(e.to_string()),
+                    );
+                    continue;
+                }
+            };
+
+            // geo-IP enrichment
+            if let Some(geo_ip) = stream_geo_ip_map.get(&stream_name) {
+                if let Some(record) = value.as_object_mut() {
+                    geo_ip.enrich(record);
+                }
+            }
+            // user-agent parsing
+            if let Some(parsings) = stream_user_agent_map.get(&stream_name) {
+                if let Some(record) = value.as_object_mut() {
+                    crate::service::ingestion::user_agent::parse(record, parsings);
+                }
+            }
 
             let mut routed_stream_name = stream_name.clone();
             // Start re-routing if exists
@@ -246,30 +363,31 @@ pub async fn ingest(
             // Start row based transform
             if let Some(transforms) = stream_after_functions_map.get(&key) {
                 if !transforms.is_empty() {
-                    let mut ret_value = value.clone();
-                    ret_value = crate::service::ingestion::apply_stream_functions(
+                    let ret_value = crate::service::ingestion::apply_stream_functions(
                         transforms,
-                        ret_value,
+                        value.clone(),
                         &stream_vrl_map,
                         org_id,
                         &routed_stream_name,
                         &mut runtime,
-                    )?;
-
-                    if ret_value.is_null() || !ret_value.is_object() {
-                        bulk_res.errors = true;
-                        add_record_status(
-                            routed_stream_name.clone(),
-                            &doc_id,
-                            action.clone(),
-                            Some(value),
-                            &mut bulk_res,
-                            Some(TRANSFORM_FAILED.to_owned()),
-                            Some(TRANSFORM_FAILED.to_owned()),
-                        );
-                        continue;
-                    } else {
-                        value = ret_value;
+                    );
+                    match ret_value {
+                        Ok(ret_value) if ret_value.is_object() => {
+                            value = ret_value;
+                        }
+                        ret_value => {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                routed_stream_name.clone(),
+                                &doc_id,
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TRANSFORM_FAILED.to_owned()),
+                                Some(transform_failure_reason(ret_value)),
+                            );
+                            continue;
+                        }
                     }
                 }
             }
@@ -308,46 +426,27 @@ pub async fn ingest(
             }
 
             // handle timestamp
-            let timestamp = match local_val.get(&cfg.common.column_timestamp) {
-                Some(v) => match parse_timestamp_micro_from_value(v) {
-                    Ok(t) => t,
-                    Err(_e) => {
-                        bulk_res.errors = true;
-                        add_record_status(
-                            routed_stream_name.clone(),
-                            &doc_id,
-                            action.clone(),
-                            Some(value),
-                            &mut bulk_res,
-                            Some(TS_PARSE_FAILED.to_string()),
-                            Some(TS_PARSE_FAILED.to_string()),
-                        );
-                        continue;
-                    }
-                },
-                None => Utc::now().timestamp_micros(),
+            let timestamp = match handle_timestamp(
+                &mut local_val,
+                min_ts,
+                timestamp_settings_map.get(&routed_stream_name),
+            ) {
+                Ok(ts) => ts,
+                Err(e) => {
+                    bulk_res.errors = true;
+                    add_record_status(
+                        routed_stream_name.clone(),
+                        &doc_id,
+                        action.clone(),
+                        Some(value),
+                        &mut bulk_res,
+                        Some(TS_PARSE_FAILED.to_string()),
+                        Some(e.to_string()),
+                    );
+                    continue;
+                }
             };
 
-            // check ingestion time
-            if timestamp < min_ts {
-                bulk_res.errors = true;
-                let failure_reason = Some(get_upto_discard_error().to_string());
-                add_record_status(
-                    routed_stream_name.clone(),
-                    &doc_id,
-                    action.clone(),
-                    Some(value),
-                    &mut bulk_res,
-                    Some(TS_PARSE_FAILED.to_string()),
-                    failure_reason,
-                );
-                continue;
-            }
-            local_val.insert(
-                cfg.common.column_timestamp.clone(),
-                json::Value::Number(timestamp.into()),
-            );
-
             let fns_length = stream_before_functions_map
                 .get(&main_stream_key)
                 .map(|v| v.len())
@@ -402,6 +501,12 @@ pub async fn ingest(
             StreamType::Logs.to_string().as_str(),
         ])
         .observe(took_time);
+    report_latency(
+        LatencyKind::Ingest,
+        "/api/org/ingest/logs/_bulk",
+        org_id,
+        took_time,
+    );
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             "/api/org/ingest/logs/_bulk",
@@ -415,6 +520,13 @@ pub async fn ingest(
     Ok(response_body)
 }
 
+fn transform_failure_reason<E: std::fmt::Display>(ret: Result<json::Value, E>) -> String {
+    match ret {
+        Err(e) => format!("{TRANSFORM_FAILED}: {e}"),
+        Ok(_) => TRANSFORM_FAILED.to_string(),
+    }
+}
+
 pub fn add_record_status(
     stream_name: String,
     doc_id: &Option<String>,
@@ -492,4 +604,15 @@ mod tests {
         );
         assert!(bulk_res.items.len() == 1);
     }
+
+    #[test]
+    fn test_transform_failure_reason() {
+        let ret: Result<json::Value, String> = Err("invalid vrl".to_string());
+        assert_eq!(
+            transform_failure_reason(ret),
+            format!("{TRANSFORM_FAILED}: invalid vrl")
+        );
+        let ret: Result<json::Value, String> = Ok(json::Value::Null);
+        assert_eq!(transform_failure_reason(ret), TRANSFORM_FAILED);
+    }
 }
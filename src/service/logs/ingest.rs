@@ -24,11 +24,11 @@ use chrono::{Duration, Utc};
 use config::{
     get_config,
     meta::{
-        stream::{Routing, StreamParams, StreamType},
+        stream::{Routing, StreamParams, StreamType, TimestampErrorPolicy, TimestampSettings},
         usage::UsageType,
     },
     metrics,
-    utils::{flatten, json, time::parse_timestamp_micro_from_value},
+    utils::{json, time::parse_timestamp_micro_from_value},
     ID_COL_NAME, ORIGINAL_DATA_COL_NAME,
 };
 use flate2::read::GzDecoder;
@@ -51,8 +51,10 @@ use crate::{
         },
     },
     service::{
-        format_stream_name, get_formatted_stream_name, ingestion::check_ingestion_allowed,
+        format_stream_name, get_formatted_stream_name,
+        ingestion::{check_ingestion_allowed, FlattenSettings},
         schema::get_upto_discard_error,
+        usage::{report_latency, LatencyKind},
     },
 };
 
@@ -76,6 +78,9 @@ pub async fn ingest(
     } else {
         format_stream_name(in_stream_name)
     };
+    // ingesting to a stream alias writes to the stream it belongs to
+    let stream_name =
+        infra::schema::resolve_stream_alias(org_id, &stream_name, StreamType::Logs).await;
     check_ingestion_allowed(org_id, Some(&stream_name))?;
 
     let min_ts = (Utc::now() - Duration::try_hours(cfg.limit.ingest_allowed_upto).unwrap())
@@ -107,6 +112,17 @@ pub async fn ingest(
     }
     // End get routing keys
 
+    let mut stream_geo_ip_map = HashMap::new();
+    crate::service::ingestion::geo_ip::get_stream_geo_ip(
+        StreamParams::new(org_id, &stream_name, StreamType::Logs),
+        &mut stream_geo_ip_map,
+    );
+    let mut stream_user_agent_map = HashMap::new();
+    crate::service::ingestion::user_agent::get_stream_user_agent(
+        StreamParams::new(org_id, &stream_name, StreamType::Logs),
+        &mut stream_user_agent_map,
+    );
+
     // Start get user defined schema
     let mut user_defined_schema_map: HashMap<String, HashSet<String>> = HashMap::new();
     let mut streams_need_original_set: HashSet<String> = HashSet::new();
@@ -118,6 +134,20 @@ pub async fn ingest(
     .await;
     // End get user defined schema
 
+    let mut timestamp_settings_map: HashMap<String, TimestampSettings> = HashMap::new();
+    crate::service::ingestion::get_stream_timestamp_settings(
+        &stream_params,
+        &mut timestamp_settings_map,
+    )
+    .await;
+
+    let mut flatten_settings_map: HashMap<String, FlattenSettings> = HashMap::new();
+    crate::service::ingestion::get_stream_flatten_settings(
+        &stream_params,
+        &mut flatten_settings_map,
+    )
+    .await;
+
     // Start Register functions for stream
     crate::service::ingestion::get_stream_functions(
         &stream_params,
@@ -176,11 +206,21 @@ pub async fn ingest(
         }
     };
 
+    let mut quarantine = super::quarantine::Quarantine::get(org_id).await;
     let mut stream_status = StreamStatus::new(&stream_name);
     let mut json_data_by_stream = HashMap::new();
-    for ret in data.iter() {
+    let mut records = data.iter();
+    while let Some(ret) = records.next() {
         let mut item = match ret {
             Ok(item) => item,
+            Err(IngestionError::InvalidRecord(record, e)) if quarantine.is_some() => {
+                if let Some(quarantine) = quarantine.as_mut() {
+                    quarantine.add(&stream_name, &record, &e.to_string());
+                }
+                stream_status.status.failed += 1;
+                stream_status.status.error = e.to_string();
+                continue;
+            }
             Err(e) => {
                 log::error!("IngestionError: {:?}", e);
                 return Err(anyhow::anyhow!("Failed processing: {:?}", e));
@@ -234,7 +274,23 @@ pub async fn ingest(
         // end row based transformation
 
         // JSON Flattening
-        let item = flatten::flatten_with_level(item, cfg.limit.ingest_flatten_level)?;
+        let mut item = crate::service::ingestion::flatten_with_settings(
+            item,
+            flatten_settings_map.get(&stream_name),
+        )?;
+
+        // geo-IP enrichment
+        if let Some(geo_ip) = stream_geo_ip_map.get(&stream_name) {
+            if let Some(record) = item.as_object_mut() {
+                geo_ip.enrich(record);
+            }
+        }
+        // user-agent parsing
+        if let Some(parsings) = stream_user_agent_map.get(&stream_name) {
+            if let Some(record) = item.as_object_mut() {
+                crate::service::ingestion::user_agent::parse(record, parsings);
+            }
+        }
 
         // Start re-routing if exists
         if let Some(routings) = stream_routing_map.get(&routed_stream_name) {
@@ -308,9 +364,20 @@ pub async fn ingest(
         }
 
         // handle timestamp
-        let timestamp = match handle_timestamp(&mut local_val, min_ts) {
+        let timestamp = match handle_timestamp(
+            &mut local_val,
+            min_ts,
+            timestamp_settings_map.get(&routed_stream_name),
+        ) {
             Ok(ts) => ts,
             Err(e) => {
+                if let Some(quarantine) = quarantine.as_mut() {
+                    // the record as received, the processed one if the request doesn't keep it
+                    let record = records
+                        .raw_record()
+                        .unwrap_or_else(|| json::Value::Object(local_val).to_string());
+                    quarantine.add(&routed_stream_name, &record, &e.to_string());
+                }
                 stream_status.status.failed += 1;
                 stream_status.status.error = e.to_string();
                 continue;
@@ -332,6 +399,12 @@ pub async fn ingest(
         *fn_num = need_usage_report.then_some(function_no);
     }
 
+    if let Some(quarantine) = quarantine {
+        if let Err(e) = quarantine.write(thread_id).await {
+            log::error!("[INGEST] writing the quarantined records of org {org_id} failed: {e}");
+        }
+    }
+
     // if no data, fast return
     if json_data_by_stream.is_empty() {
         return Ok(IngestionResponse::new(
@@ -376,6 +449,7 @@ pub async fn ingest(
             StreamType::Logs.to_string().as_str(),
         ])
         .observe(took_time);
+    report_latency(LatencyKind::Ingest, endpoint, org_id, took_time);
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             endpoint,
@@ -428,15 +502,36 @@ pub fn apply_functions<'a>(
 pub fn handle_timestamp(
     local_val: &mut json::Map<String, json::Value>,
     min_ts: i64,
+    timestamp_settings: Option<&TimestampSettings>,
 ) -> Result<i64, anyhow::Error> {
     let cfg = get_config();
     // handle timestamp
-    let timestamp = match local_val.get(&cfg.common.column_timestamp) {
-        Some(v) => match parse_timestamp_micro_from_value(v) {
-            Ok(t) => t,
-            Err(_) => return Err(anyhow::Error::msg("Can't parse timestamp")),
+    let timestamp = match timestamp_settings {
+        Some(settings) => {
+            let ret = match local_val.get(&settings.field) {
+                Some(v) => settings.format.parse(v),
+                None => Err(anyhow::anyhow!("field not found")),
+            };
+            match ret {
+                Ok(t) => t,
+                Err(_) if settings.on_error == TimestampErrorPolicy::UseIngestTime => {
+                    Utc::now().timestamp_micros()
+                }
+                Err(e) => {
+                    return Err(anyhow::anyhow!(
+                        "Can't parse timestamp from field [{}]: {e}",
+                        settings.field
+                    ));
+                }
+            }
+        }
+        None => match local_val.get(&cfg.common.column_timestamp) {
+            Some(v) => match parse_timestamp_micro_from_value(v) {
+                Ok(t) => t,
+                Err(_) => return Err(anyhow::Error::msg("Can't parse timestamp")),
+            },
+            None => Utc::now().timestamp_micros(),
         },
-        None => Utc::now().timestamp_micros(),
     };
     // check ingestion time
     if timestamp < min_ts {
@@ -454,8 +549,11 @@ impl<'a> Iterator for IngestionDataIter<'a> {
 
     fn next(&mut self) -> Option<Result<json::Value, IngestionError>> {
         match self {
-            IngestionDataIter::JSONIter(iter) => iter.next().cloned().map(Ok),
-            IngestionDataIter::MultiIter(iter) => loop {
+            IngestionDataIter::JSONIter(iter, last) => {
+                *last = iter.next();
+                last.cloned().map(Ok)
+            }
+            IngestionDataIter::MultiIter(iter, last) => loop {
                 match iter.next() {
                     Some(Ok(line)) if line.trim().is_empty() => {
                         // If the line is empty, just continue to the next iteration.
@@ -463,7 +561,16 @@ impl<'a> Iterator for IngestionDataIter<'a> {
                     }
                     Some(Ok(line)) => {
                         // If the line is not empty, attempt to parse it as JSON.
-                        return Some(json::from_str(&line).map_err(IngestionError::from));
+                        return Some(match json::from_str(&line) {
+                            Ok(value) => {
+                                *last = Some(line);
+                                Ok(value)
+                            }
+                            Err(e) => {
+                                *last = None;
+                                Err(IngestionError::InvalidRecord(line, e))
+                            }
+                        });
                     }
                     Some(Err(e)) => {
                         // If there's an error reading the line, return it.
@@ -487,12 +594,23 @@ impl<'a> Iterator for IngestionDataIter<'a> {
     }
 }
 
+impl<'a> IngestionDataIter<'a> {
+    /// Returns the last record as received, only the JSON and multi requests keep it
+    pub fn raw_record(&self) -> Option<String> {
+        match self {
+            IngestionDataIter::JSONIter(_, last) => last.map(|v| v.to_string()),
+            IngestionDataIter::MultiIter(_, last) => last.clone(),
+            _ => None,
+        }
+    }
+}
+
 impl<'a> IngestionData<'a> {
     pub fn iter(&'a self) -> IngestionDataIter<'a> {
         match self {
-            IngestionData::JSON(vec) => IngestionDataIter::JSONIter(vec.iter()),
+            IngestionData::JSON(vec) => IngestionDataIter::JSONIter(vec.iter(), None),
             IngestionData::Multi(data) => {
-                IngestionDataIter::MultiIter(std::io::BufReader::new(*data).lines())
+                IngestionDataIter::MultiIter(std::io::BufReader::new(*data).lines(), None)
             }
             IngestionData::GCP(request) => {
                 let data = &request.message.data;
@@ -843,7 +961,8 @@ mod tests {
     use super::{
         decode_and_decompress_to_string, decode_and_decompress_to_vec,
         deserialize_aws_record_from_vec, extract_resource_id_from_amazon_resource_number,
-        get_size_of_var_int_header,
+        get_size_of_var_int_header, handle_timestamp, json, IngestionData, TimestampErrorPolicy,
+        TimestampSettings,
     };
 
     #[test]
@@ -996,4 +1115,58 @@ mod tests {
             "resource-id"
         );
     }
+
+    #[test]
+    fn test_handle_timestamp_with_settings() {
+        let column_timestamp = config::get_config().common.column_timestamp.clone();
+        let mut settings = TimestampSettings {
+            field: "event_time".to_string(),
+            format: config::meta::stream::TimestampFormat::Rfc3339,
+            on_error: TimestampErrorPolicy::Reject,
+        };
+
+        let mut local_val = json::Map::new();
+        local_val.insert("event_time".to_string(), "2023-11-15T03:43:20+05:30".into());
+        let ts = handle_timestamp(&mut local_val, 0, Some(&settings)).unwrap();
+        assert_eq!(ts, 1_700_000_000_000_000);
+        assert_eq!(local_val.get(&column_timestamp), Some(&ts.into()));
+        assert!(local_val.contains_key("event_time"));
+
+        // records before the allowed ingestion time are still discarded
+        assert!(handle_timestamp(&mut local_val, ts + 1, Some(&settings)).is_err());
+
+        let mut local_val = json::Map::new();
+        local_val.insert("event_time".to_string(), "yesterday".into());
+        assert!(handle_timestamp(&mut local_val, 0, Some(&settings)).is_err());
+        assert!(!local_val.contains_key(&column_timestamp));
+
+        settings.on_error = TimestampErrorPolicy::UseIngestTime;
+        let now = chrono::Utc::now().timestamp_micros();
+        let ts = handle_timestamp(&mut local_val, 0, Some(&settings)).unwrap();
+        assert!(ts >= now);
+        let ts = handle_timestamp(&mut json::Map::new(), 0, Some(&settings)).unwrap();
+        assert!(ts >= now);
+    }
+
+    #[test]
+    fn test_raw_record() {
+        // the line is kept verbatim
+        let data = b"{\"message\":  \"a\"}\n\n{\"message\": broken\n";
+        let data = IngestionData::Multi(&data[..]);
+        let mut records = data.iter();
+        assert!(records.raw_record().is_none());
+        assert!(records.next().unwrap().is_ok());
+        assert_eq!(
+            records.raw_record().as_deref(),
+            Some("{\"message\":  \"a\"}")
+        );
+        assert!(records.next().unwrap().is_err());
+        assert!(records.raw_record().is_none());
+
+        let values = vec![json::json!({"message": "b"})];
+        let data = IngestionData::JSON(&values);
+        let mut records = data.iter();
+        assert!(records.next().unwrap().is_ok());
+        assert_eq!(records.raw_record().as_deref(), Some("{\"message\":\"b\"}"));
+    }
 }
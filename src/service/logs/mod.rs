@@ -25,14 +25,17 @@ use arrow_schema::{DataType, Field};
 use config::{
     get_config,
     meta::{
-        stream::{PartitionTimeLevel, StreamParams, StreamPartition, StreamType},
+        stream::{
+            check_required_fields, CollapseDuplicates, PartitionTimeLevel, StreamParams,
+            StreamPartition, StreamType,
+        },
         usage::{RequestStats, UsageType},
     },
     utils::{
         json::{estimate_json_bytes, get_string_value, pickup_string_value, Map, Value},
         schema_ext::SchemaExt,
     },
-    DISTINCT_FIELDS,
+    COLLAPSED_COUNT_COL_NAME, DISTINCT_FIELDS,
 };
 use infra::schema::{unwrap_partition_time_level, SchemaCache};
 
@@ -53,6 +56,8 @@ pub mod bulk;
 pub mod ingest;
 pub mod otlp_grpc;
 pub mod otlp_http;
+pub mod quarantine;
+pub mod reingest;
 pub mod syslog;
 
 static BULK_OPERATORS: [&str; 3] = ["create", "index", "update"];
@@ -193,15 +198,40 @@ async fn write_logs_by_stream(
     json_data_by_stream: HashMap<String, LogJsonData>,
 ) -> Result<()> {
     for (stream_name, (json_data, fn_num)) in json_data_by_stream {
+        // the records of a bulk request are reported as failed when their stream fails, the
+        // other streams are still written
+        let doc_ids = match status {
+            IngestionStatus::Bulk(_) => json_data
+                .iter()
+                .map(|(_, v)| v.get("_id").and_then(|v| v.as_str()).map(String::from))
+                .collect::<Vec<_>>(),
+            IngestionStatus::Record(_) => vec![],
+        };
+
         // check if we are allowed to ingest
         if db::compact::retention::is_deleting_stream(org_id, StreamType::Logs, &stream_name, None)
         {
             log::warn!("stream [{stream_name}] is being deleted");
+            add_stream_failure(
+                status,
+                &stream_name,
+                doc_ids,
+                &format!("stream [{stream_name}] is being deleted"),
+            );
             continue; // skip
         }
 
         // write json data by stream
-        let mut req_stats = write_logs(thread_id, org_id, &stream_name, status, json_data).await?;
+        let mut req_stats =
+            match write_logs(thread_id, org_id, &stream_name, status, json_data).await {
+                Ok(req_stats) => req_stats,
+                Err(e) if matches!(status, IngestionStatus::Bulk(_)) => {
+                    log::error!("Error while writing logs of stream {stream_name}: {e}");
+                    add_stream_failure(status, &stream_name, doc_ids, &e.to_string());
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
 
         let time_took = time_stats.1.elapsed().as_secs_f64();
         req_stats.response_time = time_took;
@@ -227,6 +257,133 @@ async fn write_logs_by_stream(
     Ok(())
 }
 
+/// Reports all the records of a stream as failed in a bulk response
+fn add_stream_failure(
+    status: &mut IngestionStatus,
+    stream_name: &str,
+    doc_ids: Vec<Option<String>>,
+    failure_reason: &str,
+) {
+    let IngestionStatus::Bulk(bulk_res) = status else {
+        return;
+    };
+    bulk_res.errors = true;
+    for doc_id in doc_ids {
+        bulk::add_record_status(
+            stream_name.to_string(),
+            &doc_id,
+            "".to_string(),
+            None,
+            bulk_res,
+            Some(bulk::STREAM_WRITE_FAILED.to_string()),
+            Some(failure_reason.to_string()),
+        );
+    }
+}
+
+/// Reports the records collapsed into other records as ingested
+fn add_collapsed_records(
+    status: &mut IngestionStatus,
+    stream_name: &str,
+    doc_ids: Vec<Option<String>>,
+) {
+    match status {
+        IngestionStatus::Record(status) => {
+            status.successful += doc_ids.len() as u32;
+        }
+        IngestionStatus::Bulk(bulk_res) => {
+            for doc_id in doc_ids {
+                bulk::add_record_status(
+                    stream_name.to_string(),
+                    &doc_id,
+                    "".to_string(),
+                    None,
+                    bulk_res,
+                    None,
+                    None,
+                );
+            }
+        }
+    }
+}
+
+/// Collapses the identical records of the request, by the values of the configured fields, with
+/// timestamps in the same window into the first of them, which counts them in `_count`. Returns
+/// the remaining records and the `_id` of the collapsed ones.
+fn collapse_duplicates(
+    config: &CollapseDuplicates,
+    json_data: Vec<(i64, Map<String, Value>)>,
+) -> (Vec<(i64, Map<String, Value>)>, Vec<Option<String>>) {
+    let window = config.window_secs.max(1) * 1_000_000;
+    let mut records: Vec<(i64, Map<String, Value>)> = Vec::with_capacity(json_data.len());
+    let mut counts: Vec<i64> = Vec::with_capacity(json_data.len());
+    let mut collapsed_ids = Vec::new();
+    let mut seen: HashMap<(i64, String), usize> = HashMap::new();
+    for (timestamp, record_val) in json_data {
+        // a record collapsed before, e.g. reingested, keeps its count
+        let count = record_val
+            .get(COLLAPSED_COUNT_COL_NAME)
+            .and_then(|v| v.as_i64())
+            .unwrap_or(1);
+        let key = config
+            .fields
+            .iter()
+            .map(|field| record_val.get(field).cloned().unwrap_or(Value::Null))
+            .collect::<Vec<_>>();
+        let key = (timestamp / window, Value::Array(key).to_string());
+        match seen.get(&key) {
+            Some(idx) => {
+                counts[*idx] += count;
+                collapsed_ids.push(
+                    record_val
+                        .get("_id")
+                        .and_then(|v| v.as_str())
+                        .map(String::from),
+                );
+            }
+            None => {
+                seen.insert(key, records.len());
+                records.push((timestamp, record_val));
+                counts.push(count);
+            }
+        }
+    }
+    for ((_, record_val), count) in records.iter_mut().zip(counts) {
+        record_val.insert(COLLAPSED_COUNT_COL_NAME.to_string(), count.into());
+    }
+    (records, collapsed_ids)
+}
+
+/// Reports a record as failed, it doesn't conform to the schema of the stream
+fn add_record_failure(
+    status: &mut IngestionStatus,
+    stream_name: &str,
+    record_val: &Map<String, Value>,
+    failure_reason: &str,
+) {
+    match status {
+        IngestionStatus::Record(status) => {
+            status.failed += 1;
+            status.error = failure_reason.to_string();
+        }
+        IngestionStatus::Bulk(bulk_res) => {
+            let doc_id = record_val
+                .get("_id")
+                .map(|v| v.as_str().unwrap().to_string());
+            bulk_res.errors = true;
+            bulk::add_record_status(
+                stream_name.to_string(),
+                &doc_id,
+                "".to_string(),
+                Some(Value::Object(record_val.clone())),
+                bulk_res,
+                Some(bulk::SCHEMA_CONFORMANCE_FAILED.to_string()),
+                Some(failure_reason.to_string()),
+            );
+        }
+    }
+}
+
 async fn write_logs(
     thread_id: usize,
     org_id: &str,
@@ -277,6 +434,39 @@ async fn write_logs(
     let mut evaluated_alerts = HashSet::new();
     // End get stream alert
 
+    // reject the records without the required fields before they evolve the schema
+    let stream_settings = infra::schema::get_settings(org_id, stream_name, StreamType::Logs)
+        .await
+        .unwrap_or_default();
+    let required_fields = stream_settings.required_fields;
+    let json_data = if required_fields.is_empty() {
+        json_data
+    } else {
+        json_data
+            .into_iter()
+            .filter(
+                |(_, record_val)| match check_required_fields(&required_fields, record_val) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        add_record_failure(status, stream_name, record_val, &e);
+                        false
+                    }
+                },
+            )
+            .collect::<Vec<_>>()
+    };
+    let json_data = match stream_settings.collapse_duplicates.as_ref() {
+        Some(config) => {
+            let (json_data, collapsed_ids) = collapse_duplicates(config, json_data);
+            add_collapsed_records(status, stream_name, collapsed_ids);
+            json_data
+        }
+        None => json_data,
+    };
+    if json_data.is_empty() {
+        return Ok(RequestStats::default());
+    }
+
     // start check for schema
     let min_timestamp = json_data.iter().map(|(ts, _)| ts).min().unwrap();
     let (schema_evolution, infer_schema) = check_for_schema(
@@ -338,25 +528,7 @@ async fn write_logs(
                     }
                 };
             if let Err(e) = ret_val {
-                // update status(fail)
-                match status {
-                    IngestionStatus::Record(status) => {
-                        status.failed += 1;
-                        status.error = e.to_string();
-                    }
-                    IngestionStatus::Bulk(bulk_res) => {
-                        bulk_res.errors = true;
-                        bulk::add_record_status(
-                            stream_name.to_string(),
-                            &doc_id,
-                            "".to_string(),
-                            Some(Value::Object(record_val.clone())),
-                            bulk_res,
-                            Some(bulk::SCHEMA_CONFORMANCE_FAILED.to_string()),
-                            Some(e.to_string()),
-                        );
-                    }
-                }
+                add_record_failure(status, stream_name, &record_val, &e.to_string());
                 continue;
             }
         }
@@ -510,8 +682,49 @@ pub fn refactor_map(
 
 #[cfg(test)]
 mod tests {
+    use config::utils::json;
+
     use super::*;
 
+    #[test]
+    fn test_collapse_duplicates() {
+        let config = CollapseDuplicates {
+            fields: vec!["level".to_string(), "message".to_string()],
+            window_secs: 10,
+        };
+        let second = 1_000_000;
+        let record = |id: usize, message: &str| {
+            let record = json::json!({"_id": id.to_string(), "level": "info", "message": message, "pod": id});
+            record.as_object().unwrap().clone()
+        };
+        // a burst of identical lines, another line and the same line in the next window
+        let mut json_data = (0..1000)
+            .map(|i| (second + i as i64, record(i, "connected")))
+            .collect::<Vec<_>>();
+        json_data.push((second + 1, record(1000, "disconnected")));
+        json_data.push((11 * second, record(1001, "connected")));
+
+        let (records, collapsed_ids) = collapse_duplicates(&config, json_data);
+        assert_eq!(records.len(), 3);
+        assert_eq!(collapsed_ids.len(), 999);
+        assert_eq!(collapsed_ids[0].as_deref(), Some("1"));
+        let counts = records
+            .iter()
+            .map(|(_, r)| r.get(COLLAPSED_COUNT_COL_NAME).unwrap().as_i64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![1000, 1, 1]);
+        // the first record of the burst is kept as it is
+        assert_eq!(records[0].0, second);
+        assert_eq!(records[0].1.get("pod").unwrap(), 0);
+
+        // the count of a record collapsed before is added up
+        let mut collapsed = record(0, "connected");
+        collapsed.insert(COLLAPSED_COUNT_COL_NAME.to_string(), 5.into());
+        let (records, _) =
+            collapse_duplicates(&config, vec![(0, collapsed), (1, record(1, "connected"))]);
+        assert_eq!(records[0].1.get(COLLAPSED_COUNT_COL_NAME).unwrap(), 6);
+    }
+
     #[test]
     fn test_set_parsing_error() {
         let mut parse_error = String::new();
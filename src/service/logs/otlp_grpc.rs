@@ -48,6 +48,7 @@ use crate::{
             grpc::{get_val, get_val_with_type_retained},
         },
         schema::get_upto_discard_error,
+        usage::{report_latency, LatencyKind},
     },
 };
 
@@ -68,6 +69,9 @@ pub async fn handle_grpc_request(
         Some(name) => format_stream_name(name),
         None => "default".to_owned(),
     };
+    // ingesting to a stream alias writes to the stream it belongs to
+    let stream_name =
+        infra::schema::resolve_stream_alias(org_id, &stream_name, StreamType::Logs).await;
     check_ingestion_allowed(org_id, Some(&stream_name))?;
 
     let min_ts = (Utc::now() - Duration::try_hours(cfg.limit.ingest_allowed_upto).unwrap())
@@ -390,6 +394,7 @@ pub async fn handle_grpc_request(
             StreamType::Logs.to_string().as_str(),
         ])
         .observe(took_time);
+    report_latency(LatencyKind::Ingest, ep, org_id, took_time);
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             ep,
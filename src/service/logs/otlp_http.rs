@@ -46,6 +46,7 @@ use crate::{
         format_stream_name,
         ingestion::{check_ingestion_allowed, get_val_for_attr},
         schema::get_upto_discard_error,
+        usage::{report_latency, LatencyKind},
     },
 };
 
@@ -101,6 +102,9 @@ pub async fn logs_json_handler(
         Some(name) => format_stream_name(name),
         None => "default".to_owned(),
     };
+    // ingesting to a stream alias writes to the stream it belongs to
+    let stream_name =
+        infra::schema::resolve_stream_alias(org_id, &stream_name, StreamType::Logs).await;
     check_ingestion_allowed(org_id, Some(&stream_name))?;
 
     let min_ts = (Utc::now() - Duration::try_hours(cfg.limit.ingest_allowed_upto).unwrap())
@@ -510,6 +514,7 @@ pub async fn logs_json_handler(
             StreamType::Logs.to_string().as_str(),
         ])
         .observe(took_time);
+    report_latency(LatencyKind::Ingest, "/api/otlp/v1/logs", org_id, took_time);
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             "/api/otlp/v1/logs",
@@ -0,0 +1,181 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The quarantine stream of an org keeps the ingested records failing the JSON parsing or the
+//! timestamp extraction instead of dropping them, so they can be inspected and ingested again.
+//! It's enabled by the `quarantine_stream` org setting. A node writes at most
+//! `ZO_INGEST_QUARANTINE_MAX_RECORDS` records per minute to the quarantine of an org, the failed
+//! records over it are dropped.
+
+use std::{collections::HashMap, time::Instant};
+
+use anyhow::Result;
+use config::{
+    get_config,
+    meta::usage::UsageType,
+    utils::{
+        json::{self, Map, Value},
+        time::now_micros,
+    },
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::{
+    common::meta::{
+        ingestion::{IngestionStatus, RecordStatus},
+        organization::OrganizationSetting,
+    },
+    service::{db::organization::get_org_setting, format_stream_name},
+};
+
+/// The records quarantined by each org in the current minute
+static QUOTAS: Lazy<Mutex<HashMap<String, (i64, usize)>>> = Lazy::new(Default::default);
+
+pub struct Quarantine {
+    org_id: String,
+    stream_name: String,
+    records: Vec<(i64, Map<String, Value>)>,
+}
+
+impl Quarantine {
+    pub fn new(org_id: &str, stream_name: &str) -> Self {
+        Self {
+            org_id: org_id.to_string(),
+            stream_name: stream_name.to_string(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Returns the quarantine of the org, None if the org doesn't enable it
+    pub async fn get(org_id: &str) -> Option<Self> {
+        let stream_name = get_org_setting(org_id)
+            .await
+            .ok()
+            .and_then(|v| json::from_slice::<OrganizationSetting>(&v).ok())
+            .and_then(|v| v.quarantine_stream)
+            .filter(|v| !v.is_empty())?;
+        Some(Self::new(org_id, &format_stream_name(&stream_name)))
+    }
+
+    /// Keeps the failed record of the stream as received, with the reason it failed. Returns
+    /// false if the org has used up its quota and the record is dropped.
+    pub fn add(&mut self, stream_name: &str, record: &str, error: &str) -> bool {
+        let cfg = get_config();
+        let received_at = now_micros();
+        if !acquire(
+            &self.org_id,
+            received_at / 60_000_000,
+            cfg.limit.ingest_quarantine_max_records,
+        ) {
+            return false;
+        }
+        let mut end = record
+            .len()
+            .min(cfg.limit.ingest_quarantine_max_record_size * 1024);
+        while !record.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut value = Map::new();
+        value.insert(
+            cfg.common.column_timestamp.clone(),
+            Value::Number(received_at.into()),
+        );
+        value.insert("stream".to_string(), Value::String(stream_name.to_string()));
+        value.insert("raw".to_string(), Value::String(record[..end].to_string()));
+        value.insert("error".to_string(), Value::String(error.to_string()));
+        self.records.push((received_at, value));
+        true
+    }
+
+    /// Writes the kept records to the quarantine stream
+    pub async fn write(self, thread_id: usize) -> Result<()> {
+        if self.records.is_empty() {
+            return Ok(());
+        }
+        log::warn!(
+            "[INGEST] {} records of org {} are quarantined to stream {}",
+            self.records.len(),
+            self.org_id,
+            self.stream_name
+        );
+        let mut status = IngestionStatus::Record(RecordStatus::default());
+        super::write_logs_by_stream(
+            thread_id,
+            &self.org_id,
+            "",
+            (now_micros(), &Instant::now()),
+            UsageType::Json,
+            &mut status,
+            HashMap::from([(self.stream_name, (self.records, None))]),
+        )
+        .await
+    }
+}
+
+/// Counts a record in the quota of the org for the minute
+fn acquire(org_id: &str, minute: i64, max_records: usize) -> bool {
+    let mut quotas = QUOTAS.lock();
+    let (quota_minute, records) = quotas.entry(org_id.to_string()).or_insert((minute, 0));
+    if *quota_minute != minute {
+        *quota_minute = minute;
+        *records = 0;
+    }
+    if *records >= max_records {
+        return false;
+    }
+    *records += 1;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::ingestion::{IngestionData, IngestionError};
+
+    #[test]
+    fn test_malformed_record_is_quarantined() {
+        let data = b"{\"message\":\"ok\"}\n{\"message\": broken\n";
+        let mut quarantine = Quarantine::new("quarantine_org", "quarantine");
+        let mut valid = 0;
+        for ret in IngestionData::Multi(&data[..]).iter() {
+            match ret {
+                Ok(_) => valid += 1,
+                Err(IngestionError::InvalidRecord(record, e)) => {
+                    assert!(quarantine.add("app", &record, &e.to_string()));
+                }
+                Err(e) => panic!("unexpected error: {e:?}"),
+            }
+        }
+        assert_eq!(valid, 1);
+        assert_eq!(quarantine.records.len(), 1);
+        let (_, record) = &quarantine.records[0];
+        assert_eq!(record["raw"], "{\"message\": broken");
+        assert_eq!(record["stream"], "app");
+        assert!(!record["error"].as_str().unwrap().is_empty());
+        assert!(record.contains_key(&get_config().common.column_timestamp));
+    }
+
+    #[test]
+    fn test_quarantine_quota() {
+        for _ in 0..3 {
+            assert!(acquire("quarantine_quota_org", 100, 3));
+        }
+        assert!(!acquire("quarantine_quota_org", 100, 3));
+        // the quota is per org and per minute
+        assert!(acquire("quarantine_quota_other_org", 100, 3));
+        assert!(acquire("quarantine_quota_org", 101, 3));
+    }
+}
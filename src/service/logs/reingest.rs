@@ -0,0 +1,405 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replays the parquet files of a logs stream through the functions of a pipeline and writes
+//! the results to a target stream. The files are replayed as a whole, the time range selects the
+//! files which overlap it, and a replayed file is recorded so a rerun of a failed job skips it.
+//! The records are removed when a job completes, and only one job runs for a pipeline and a
+//! target stream at a time.
+
+use std::{collections::HashMap, time::Instant};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use config::{
+    get_config, ider,
+    meta::{
+        stream::{FileKey, StreamParams, StreamType},
+        usage::UsageType,
+    },
+    utils::{
+        arrow::record_batches_to_json_rows,
+        json::{Map, Value},
+        parquet::read_recordbatch_from_bytes,
+    },
+};
+use infra::{dist_lock, schema::unwrap_partition_time_level};
+use vrl::compiler::runtime::Runtime;
+
+use super::ingest::apply_functions;
+use crate::{
+    common::meta::{
+        functions::{StreamTransform, VRLResultResolver},
+        ingestion::{IngestionStatus, RecordStatus},
+        pipelines::PipeLine,
+        reingest::{ReingestJob, ReingestRequest, ReingestStatus},
+    },
+    service::{
+        db, file_list, format_stream_name,
+        ingestion::{check_ingestion_allowed, FlattenSettings},
+    },
+};
+
+/// A running job which wasn't updated for this long, in microseconds, was stopped with its node
+const RUNNING_JOB_TIMEOUT: i64 = 3600 * 1_000_000;
+
+/// The functions of the pipeline stream applied to every record
+struct Transforms {
+    stream_name: String,
+    before: Vec<StreamTransform>,
+    after: Vec<StreamTransform>,
+    vrl_map: HashMap<String, VRLResultResolver>,
+    flatten: Option<FlattenSettings>,
+}
+
+/// Validates the request and starts the job in the background, returns the job to poll
+pub async fn start(
+    thread_id: usize,
+    org_id: &str,
+    stream_name: &str,
+    req: ReingestRequest,
+    user_email: &str,
+) -> Result<ReingestJob> {
+    let target_stream = format_stream_name(&req.target_stream);
+    if target_stream.is_empty() {
+        return Err(anyhow!("target_stream is required"));
+    }
+    check_ingestion_allowed(org_id, Some(&target_stream))?;
+
+    let schema = infra::schema::get(org_id, stream_name, StreamType::Logs).await?;
+    if schema.fields().is_empty() {
+        return Err(anyhow!("stream [{stream_name}] not found"));
+    }
+    let settings = infra::schema::get_settings(org_id, stream_name, StreamType::Logs)
+        .await
+        .unwrap_or_default();
+    check_time_range(req.start_time, req.end_time, settings.max_query_range)?;
+
+    let pipeline = db::pipelines::list(org_id)
+        .await?
+        .into_iter()
+        .find(|p| p.name == req.pipeline && p.stream_type == StreamType::Logs)
+        .ok_or_else(|| anyhow!("pipeline [{}] not found", req.pipeline))?;
+
+    let files = file_list::query(
+        org_id,
+        stream_name,
+        StreamType::Logs,
+        unwrap_partition_time_level(settings.partition_time_level, StreamType::Logs),
+        req.start_time,
+        req.end_time,
+    )
+    .await?;
+
+    let locker = dist_lock::lock(
+        &format!("/reingest/lock/{org_id}/{target_stream}/{}", pipeline.name),
+        0,
+        None,
+    )
+    .await?;
+    let ret = check_no_running_job(org_id, &target_stream, &pipeline.name).await;
+    if ret.is_err() {
+        dist_lock::unlock(&locker).await?;
+        ret?;
+    }
+
+    let now = Utc::now().timestamp_micros();
+    let job = ReingestJob {
+        id: ider::uuid(),
+        org_id: org_id.to_string(),
+        stream_name: stream_name.to_string(),
+        pipeline: pipeline.name.clone(),
+        target_stream,
+        start_time: req.start_time,
+        end_time: req.end_time,
+        status: ReingestStatus::Running,
+        total_files: files.len(),
+        created_at: now,
+        updated_at: now,
+        ..Default::default()
+    };
+    let ret = db::reingest::set_job(&job).await;
+    dist_lock::unlock(&locker).await?;
+    ret?;
+
+    // the replay is CPU heavy, keep it off the HTTP workers. The vrl runtime is not Send, so the
+    // job is driven on its own blocking thread.
+    let handle = tokio::runtime::Handle::current();
+    let user_email = user_email.to_string();
+    let run_job = job.clone();
+    tokio::task::spawn_blocking(move || {
+        handle.block_on(run(thread_id, user_email, run_job, pipeline, files))
+    });
+    Ok(job)
+}
+
+/// Returns an error if a job of the pipeline to the target stream is still running
+async fn check_no_running_job(org_id: &str, target_stream: &str, pipeline: &str) -> Result<()> {
+    let now = Utc::now().timestamp_micros();
+    if let Some(job) = db::reingest::list_jobs(org_id)
+        .await?
+        .into_iter()
+        .find(|job| {
+            job.target_stream == target_stream
+                && job.pipeline == pipeline
+                && job.status == ReingestStatus::Running
+                && now - job.updated_at < RUNNING_JOB_TIMEOUT
+        })
+    {
+        return Err(anyhow!(
+            "reingest job {} of pipeline [{pipeline}] to stream [{target_stream}] is running",
+            job.id
+        ));
+    }
+    Ok(())
+}
+
+/// The time range must be in the max query range of the stream, in hours
+fn check_time_range(start_time: i64, end_time: i64, max_query_range: i64) -> Result<()> {
+    if start_time <= 0 || end_time <= start_time {
+        return Err(anyhow!("end_time must be greater than start_time"));
+    }
+    if max_query_range > 0 && end_time - start_time > max_query_range * 3600 * 1_000_000 {
+        return Err(anyhow!(
+            "time range exceeds the max query range of {max_query_range} hours"
+        ));
+    }
+    Ok(())
+}
+
+async fn run(
+    thread_id: usize,
+    user_email: String,
+    mut job: ReingestJob,
+    pipeline: PipeLine,
+    files: Vec<FileKey>,
+) {
+    let streams = [StreamParams::new(
+        &job.org_id,
+        &pipeline.stream_name,
+        StreamType::Logs,
+    )];
+    let mut before_map = HashMap::new();
+    let mut after_map = HashMap::new();
+    let mut vrl_map = HashMap::new();
+    crate::service::ingestion::get_stream_functions(
+        &streams,
+        &mut before_map,
+        &mut after_map,
+        &mut vrl_map,
+    )
+    .await;
+    let mut flatten_map = HashMap::new();
+    crate::service::ingestion::get_stream_flatten_settings(&streams, &mut flatten_map).await;
+
+    let fn_key = format!(
+        "{}/{}/{}",
+        job.org_id,
+        StreamType::Logs,
+        pipeline.stream_name
+    );
+    let transforms = Transforms {
+        before: before_map.remove(&fn_key).unwrap_or_default(),
+        after: after_map.remove(&fn_key).unwrap_or_default(),
+        vrl_map,
+        flatten: flatten_map.remove(&pipeline.stream_name),
+        stream_name: pipeline.stream_name,
+    };
+    let mut runtime = crate::service::ingestion::init_functions_runtime();
+
+    for file in files.iter() {
+        match replay_file(
+            thread_id,
+            &user_email,
+            &job,
+            &file.key,
+            &transforms,
+            &mut runtime,
+        )
+        .await
+        {
+            Ok(Some((records, failed))) => {
+                job.records += records;
+                job.failed_records += failed;
+            }
+            Ok(None) => job.skipped_files += 1,
+            Err(e) => {
+                log::error!(
+                    "[REINGEST] job {} replay file {} error: {e}",
+                    job.id,
+                    file.key
+                );
+                job.status = ReingestStatus::Failed;
+                job.error = format!("replay file {} error: {e}", file.key);
+                job.updated_at = Utc::now().timestamp_micros();
+                if let Err(e) = db::reingest::set_job(&job).await {
+                    log::error!("[REINGEST] save job {} error: {e}", job.id);
+                }
+                return;
+            }
+        }
+        job.processed_files += 1;
+        job.updated_at = Utc::now().timestamp_micros();
+        if let Err(e) = db::reingest::set_job(&job).await {
+            log::error!("[REINGEST] save job {} error: {e}", job.id);
+        }
+    }
+
+    // a rerun after a completed job replays all the files again
+    if let Err(e) = db::reingest::delete_files(&job.org_id, &job.target_stream, &job.pipeline).await
+    {
+        log::error!(
+            "[REINGEST] delete file records of job {} error: {e}",
+            job.id
+        );
+    }
+
+    job.status = ReingestStatus::Completed;
+    job.updated_at = Utc::now().timestamp_micros();
+    if let Err(e) = db::reingest::set_job(&job).await {
+        log::error!("[REINGEST] save job {} error: {e}", job.id);
+    }
+    log::info!(
+        "[REINGEST] job {} completed, files: {}, skipped: {}, records: {}, failed: {}",
+        job.id,
+        job.processed_files,
+        job.skipped_files,
+        job.records,
+        job.failed_records
+    );
+}
+
+/// Replays a file and returns the number of written and failed records, or None if the file
+/// was already replayed
+async fn replay_file(
+    thread_id: usize,
+    user_email: &str,
+    job: &ReingestJob,
+    file: &str,
+    transforms: &Transforms,
+    runtime: &mut Runtime,
+) -> Result<Option<(u64, u64)>> {
+    if db::reingest::is_file_done(&job.org_id, &job.target_stream, &job.pipeline, file).await? {
+        return Ok(None);
+    }
+
+    let start = Instant::now();
+    let started_at = Utc::now().timestamp_micros();
+    let data = infra::storage::get(file).await?;
+    let (_, batches) = read_recordbatch_from_bytes(&data).await?;
+    let rows = record_batches_to_json_rows(&batches.iter().collect::<Vec<_>>())?;
+    drop(batches);
+
+    let mut failed = 0;
+    let mut write_failed = 0;
+    let mut records = Vec::with_capacity(rows.len());
+    for row in rows {
+        match transform(&job.org_id, row, transforms, runtime) {
+            Ok(record) => records.push(record),
+            Err(_) => failed += 1,
+        }
+    }
+    let written = records.len() as u64;
+
+    if !records.is_empty() {
+        let fn_num = transforms.before.len() + transforms.after.len();
+        let json_data_by_stream =
+            HashMap::from([(job.target_stream.clone(), (records, Some(fn_num)))]);
+        let mut status = IngestionStatus::Record(RecordStatus::default());
+        super::write_logs_by_stream(
+            thread_id,
+            &job.org_id,
+            user_email,
+            (started_at, &start),
+            UsageType::Json,
+            &mut status,
+            json_data_by_stream,
+        )
+        .await?;
+        if let IngestionStatus::Record(status) = status {
+            write_failed = status.failed as u64;
+        }
+    }
+
+    db::reingest::set_file_done(&job.org_id, &job.target_stream, &job.pipeline, file).await?;
+    // the records failed by the functions were never written
+    Ok(Some((
+        written.saturating_sub(write_failed),
+        failed + write_failed,
+    )))
+}
+
+/// Applies the functions of the pipeline to a stored record, the stored timestamp is kept
+/// unless a function sets a new one
+fn transform(
+    org_id: &str,
+    row: Map<String, Value>,
+    transforms: &Transforms,
+    runtime: &mut Runtime,
+) -> Result<(i64, Map<String, Value>)> {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let timestamp = row
+        .get(column_timestamp)
+        .and_then(|v| v.as_i64())
+        .ok_or_else(|| anyhow!("record without {column_timestamp}"))?;
+
+    let mut value = Value::Object(row);
+    if !transforms.before.is_empty() {
+        value = apply_functions(
+            value,
+            &transforms.before,
+            &transforms.vrl_map,
+            org_id,
+            &transforms.stream_name,
+            runtime,
+        )?;
+    }
+    value = crate::service::ingestion::flatten_with_settings(value, transforms.flatten.as_ref())?;
+    if !transforms.after.is_empty() {
+        value = apply_functions(
+            value,
+            &transforms.after,
+            &transforms.vrl_map,
+            org_id,
+            &transforms.stream_name,
+            runtime,
+        )?;
+    }
+
+    let Value::Object(mut record) = value else {
+        return Err(anyhow!("apply functions failure"));
+    };
+    let timestamp = record
+        .get(column_timestamp)
+        .and_then(|v| v.as_i64())
+        .unwrap_or(timestamp);
+    record.insert(column_timestamp.to_string(), timestamp.into());
+    Ok((timestamp, record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_time_range() {
+        let hour = 3600 * 1_000_000;
+        assert!(check_time_range(hour, 2 * hour, 0).is_ok());
+        assert!(check_time_range(hour, 30 * hour, 24).is_err());
+        assert!(check_time_range(hour, 25 * hour, 24).is_ok());
+        assert!(check_time_range(2 * hour, hour, 0).is_err());
+        assert!(check_time_range(0, hour, 0).is_err());
+    }
+}
@@ -23,9 +23,9 @@ use anyhow::Result;
 use chrono::{Duration, Utc};
 use config::{
     get_config,
-    meta::stream::{Routing, StreamParams, StreamType},
+    meta::stream::{Routing, StreamParams, StreamType, TimestampSettings},
     metrics,
-    utils::{flatten, json},
+    utils::json,
     ID_COL_NAME, ORIGINAL_DATA_COL_NAME,
 };
 use syslog_loose::{Message, ProcId, Protocol};
@@ -41,7 +41,11 @@ use crate::{
             syslog::SyslogRoute,
         },
     },
-    service::{format_stream_name, ingestion::check_ingestion_allowed},
+    service::{
+        format_stream_name,
+        ingestion::check_ingestion_allowed,
+        usage::{report_latency, LatencyKind},
+    },
 };
 
 pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
@@ -67,6 +71,9 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
 
     // check stream
     let stream_name = format_stream_name(in_stream_name);
+    // ingesting to a stream alias writes to the stream it belongs to
+    let stream_name =
+        infra::schema::resolve_stream_alias(org_id, &stream_name, StreamType::Logs).await;
     if let Err(e) = check_ingestion_allowed(org_id, Some(&stream_name)) {
         return Ok(
             HttpResponse::InternalServerError().json(MetaHttpResponse::error(
@@ -117,6 +124,20 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
     .await;
     // End get user defined schema
 
+    let mut timestamp_settings_map: HashMap<String, TimestampSettings> = HashMap::new();
+    crate::service::ingestion::get_stream_timestamp_settings(
+        &stream_params,
+        &mut timestamp_settings_map,
+    )
+    .await;
+
+    let mut flatten_settings_map = HashMap::new();
+    crate::service::ingestion::get_stream_flatten_settings(
+        &stream_params,
+        &mut flatten_settings_map,
+    )
+    .await;
+
     // Start Register functions for stream
     crate::service::ingestion::get_stream_functions(
         &stream_params,
@@ -165,7 +186,11 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
     // end row based transformation
 
     // JSON Flattening
-    value = flatten::flatten_with_level(value, cfg.limit.ingest_flatten_level).unwrap();
+    value = crate::service::ingestion::flatten_with_settings(
+        value,
+        flatten_settings_map.get(&stream_name),
+    )
+    .unwrap();
 
     let mut routed_stream_name = stream_name.clone();
     // Start re-rerouting if exists
@@ -244,7 +269,11 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
     }
 
     // handle timestamp
-    let timestamp = match handle_timestamp(&mut local_val, min_ts) {
+    let timestamp = match handle_timestamp(
+        &mut local_val,
+        min_ts,
+        timestamp_settings_map.get(&routed_stream_name),
+    ) {
         Ok(ts) => ts,
         Err(e) => {
             stream_status.status.failed += 1;
@@ -289,6 +318,12 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
             StreamType::Logs.to_string().as_str(),
         ])
         .observe(time);
+    report_latency(
+        LatencyKind::Ingest,
+        "/api/org/ingest/logs/_syslog",
+        org_id,
+        time,
+    );
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             "/api/org/ingest/logs/_syslog",
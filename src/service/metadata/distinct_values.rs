@@ -49,7 +49,7 @@ use crate::{
 };
 
 const CHANNEL_SIZE: usize = 10240;
-const STREAM_NAME: &str = "distinct_values";
+pub(crate) const STREAM_NAME: &str = "distinct_values";
 
 pub(crate) static INSTANCE: Lazy<DistinctValues> = Lazy::new(DistinctValues::new);
 
@@ -203,9 +203,18 @@ impl TraceListIndex {
                 bloom_filter_fields: vec!["trace_id".to_string()],
                 data_retention: 0,
                 flatten_level: None,
+                flatten_array: None,
                 max_query_range: 0,
+                query_range_policy: None,
                 defined_schema_fields: None,
                 store_original_data: false,
+                timestamp_settings: None,
+                field_access: vec![],
+                required_fields: vec![],
+                field_aliases: vec![],
+                aliases: vec![],
+                collapse_duplicates: None,
+                computed_columns: vec![],
             };
 
             stream::save_stream_settings(org_id, STREAM_NAME, StreamType::Metadata, settings)
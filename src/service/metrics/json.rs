@@ -42,7 +42,7 @@ use crate::{
         db, format_stream_name,
         ingestion::{get_write_partition_key, write_file},
         schema::check_for_schema,
-        usage::report_request_usage_stats,
+        usage::{report_latency, report_request_usage_stats, LatencyKind},
     },
 };
 
@@ -327,6 +327,12 @@ pub async fn ingest(org_id: &str, body: web::Bytes) -> Result<IngestionResponse>
             &StreamType::Metrics.to_string(),
         ])
         .observe(time);
+    report_latency(
+        LatencyKind::Ingest,
+        "/api/org/ingest/metrics/_json",
+        org_id,
+        time,
+    );
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             "/api/org/ingest/metrics/_json",
@@ -343,6 +349,93 @@ pub async fn ingest(org_id: &str, body: web::Bytes) -> Result<IngestionResponse>
     ))
 }
 
+/// Ingests prepared samples into a metrics stream as they are, the functions are not applied
+/// and the series hash is not computed. It is used by the rollup streams, their samples keep
+/// the labels and the hash of the raw series.
+pub async fn ingest_samples(
+    org_id: &str,
+    stream_name: &str,
+    records: Vec<json::Map<String, json::Value>>,
+) -> Result<()> {
+    if !LOCAL_NODE.is_ingester() {
+        return Err(anyhow::anyhow!("not an ingester"));
+    }
+    if records.is_empty() {
+        return Ok(());
+    }
+    if db::compact::retention::is_deleting_stream(org_id, StreamType::Metrics, stream_name, None) {
+        return Err(anyhow::anyhow!("stream [{stream_name}] is being deleted"));
+    }
+
+    let cfg = config::get_config();
+    let mut timestamps = Vec::with_capacity(records.len());
+    for record in records.iter() {
+        match record
+            .get(&cfg.common.column_timestamp)
+            .and_then(|v| v.as_i64())
+        {
+            Some(ts) => timestamps.push(ts),
+            None => return Err(anyhow::anyhow!("invalid _timestamp, need to be number")),
+        }
+    }
+
+    // check for schema evolution
+    let mut stream_schema_map: HashMap<String, SchemaCache> = HashMap::new();
+    check_for_schema(
+        org_id,
+        stream_name,
+        StreamType::Metrics,
+        &mut stream_schema_map,
+        records.iter().collect(),
+        timestamps.iter().min().copied().unwrap_or_default(),
+    )
+    .await?;
+
+    let partition_det = crate::service::ingestion::get_stream_partition_keys(
+        org_id,
+        &StreamType::Metrics,
+        stream_name,
+    )
+    .await;
+    let partition_time_level =
+        unwrap_partition_time_level(partition_det.partition_time_level, StreamType::Metrics);
+    let schema = stream_schema_map
+        .get(stream_name)
+        .unwrap()
+        .schema()
+        .as_ref()
+        .clone()
+        .with_metadata(HashMap::new());
+    let schema_key = schema.hash_key();
+    let schema = Arc::new(schema);
+
+    let mut stream_data: HashMap<String, SchemaRecords> = HashMap::new();
+    for (record, timestamp) in records.into_iter().zip(timestamps) {
+        let hour_key = get_write_partition_key(
+            timestamp,
+            &partition_det.partition_keys,
+            partition_time_level,
+            &record,
+            Some(&schema_key),
+        );
+        let hour_buf = stream_data
+            .entry(hour_key)
+            .or_insert_with(|| SchemaRecords {
+                schema_key: schema_key.clone(),
+                schema: schema.clone(),
+                records: vec![],
+                records_size: 0,
+            });
+        hour_buf.records_size += json::to_string(&record).unwrap().len();
+        hour_buf.records.push(Arc::new(json::Value::Object(record)));
+    }
+
+    let writer =
+        ingester::get_writer(0, org_id, &StreamType::Metrics.to_string(), stream_name).await;
+    write_file(&writer, stream_name, stream_data).await;
+    Ok(())
+}
+
 fn apply_func(
     runtime: &mut Runtime,
     org_id: &str,
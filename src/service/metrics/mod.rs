@@ -17,7 +17,9 @@ use datafusion::arrow::datatypes::Schema;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
-use crate::common::meta::prom::{Metadata, HASH_LABEL, METADATA_LABEL, VALUE_LABEL};
+use crate::common::meta::prom::{
+    Metadata, EXEMPLARS_LABEL, HASH_LABEL, METADATA_LABEL, VALUE_LABEL,
+};
 
 pub mod json;
 pub mod otlp_grpc;
@@ -28,11 +30,12 @@ const EXCLUDE_LABELS: [&str; 5] = [
     VALUE_LABEL,
     HASH_LABEL,
     "is_monotonic",
-    "exemplars",
+    EXEMPLARS_LABEL,
     "_timestamp",
 ];
 
 static RE_CORRECT_LABEL_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"[^a-zA-Z0-9_]+").unwrap());
+static RE_LABEL_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap());
 
 pub fn get_prom_metadata_from_schema(schema: &Schema) -> Option<Metadata> {
     let metadata = schema.metadata.get(METADATA_LABEL)?;
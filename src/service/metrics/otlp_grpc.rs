@@ -50,7 +50,7 @@ use crate::{
         },
         metrics::{format_label_name, get_exclude_labels},
         schema::{check_for_schema, stream_schema_exists},
-        usage::report_request_usage_stats,
+        usage::{report_latency, report_request_usage_stats, LatencyKind},
     },
 };
 
@@ -441,6 +441,7 @@ pub async fn handle_grpc_request(
                 StreamType::Metrics.to_string().as_str(),
             ])
             .observe(time);
+        report_latency(LatencyKind::Ingest, ep, org_id, time);
         metrics::HTTP_INCOMING_REQUESTS
             .with_label_values(&[
                 ep,
@@ -51,7 +51,7 @@ use crate::{
         ingestion::{evaluate_trigger, get_val_for_attr, write_file, TriggerAlertData},
         metrics::{format_label_name, get_exclude_labels, otlp_grpc::handle_grpc_request},
         schema::{check_for_schema, stream_schema_exists},
-        usage::report_request_usage_stats,
+        usage::{report_latency, report_request_usage_stats, LatencyKind},
     },
 };
 
@@ -523,6 +523,7 @@ pub async fn metrics_json_handler(
                 StreamType::Metrics.to_string().as_str(),
             ])
             .observe(time);
+        report_latency(LatencyKind::Ingest, "/api/otlp/v1/metrics", org_id, time);
         metrics::HTTP_INCOMING_REQUESTS
             .with_label_values(&[
                 "/api/otlp/v1/metrics",
@@ -952,7 +953,7 @@ fn process_summary_data_point(
         let attr = attr.as_object().unwrap();
         if let Some(v) = attr.get("value") {
             rec[format_label_name(attr.get("key").unwrap().as_str().unwrap())] =
-                get_attribute_value(v).into();
+                get_val_for_attr(v);
         }
     }
     let ts = json::get_int_value(data_point.get("timeUnixNano").unwrap());
@@ -1078,26 +1079,6 @@ fn set_data_point_value(rec: &mut json::Value, data_point: &json::Map<String, js
     }
 }
 
-fn get_attribute_value(val: &json::Value) -> String {
-    if val.get("stringValue").is_some() {
-        val.get("stringValue").unwrap().to_string()
-    } else if val.get("boolValue").is_some() {
-        val.get("boolValue").unwrap().to_string()
-    } else if val.get("intValue").is_some() {
-        val.get("intValue").unwrap().to_string()
-    } else if val.get("doubleValue").is_some() {
-        val.get("doubleValue").unwrap().to_string()
-    } else if val.get("arrayValue").is_some() {
-        val.get("arrayValue").unwrap().to_string()
-    } else if val.get("kvlistValue").is_some() {
-        val.get("kvlistValue").unwrap().to_string()
-    } else if val.get("bytesValue").is_some() {
-        val.get("bytesValue").unwrap().to_string()
-    } else {
-        "".to_string()
-    }
-}
-
 fn get_metric_value(val: &json::Value) -> f64 {
     if val.get("stringValue").is_some() {
         val.get("stringValue")
@@ -1177,4 +1158,27 @@ mod tests {
             expected_lens
         );
     }
+
+    #[test]
+    fn test_process_summary_data_point_labels() {
+        let data_point = json!({
+            "attributes": [
+                {"key": "service.name", "value": {"stringValue": "api"}},
+                {"key": "retries", "value": {"intValue": "3"}}
+            ],
+            "timeUnixNano": 1620000000000i64,
+            "count": 2,
+            "sum": 3,
+            "quantileValues": [{"quantile": 0.5, "value": 1}]
+        });
+        let result = process_summary_data_point(
+            &mut json!({"__name__": "test"}),
+            data_point.as_object().unwrap(),
+        );
+        assert_eq!(result.len(), 3);
+        for rec in result {
+            assert_eq!(rec["service_name"], json!("api"));
+            assert_eq!(rec["retries"], json!("3"));
+        }
+    }
 }
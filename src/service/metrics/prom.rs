@@ -26,13 +26,13 @@ use config::{
     },
     metrics,
     utils::{json, schema_ext::SchemaExt, time::parse_i64_to_timestamp_micros},
-    FxIndexMap,
+    FxIndexMap, DISTINCT_FIELDS,
 };
 use datafusion::arrow::datatypes::Schema;
 use hashbrown::HashSet;
 use infra::{
     cache::stats,
-    errors::{Error, Result},
+    errors::{Error, ErrorCodes, Result},
     schema::{unwrap_partition_time_level, update_setting, SchemaCache},
 };
 use promql_parser::{label::MatchOp, parser};
@@ -47,10 +47,11 @@ use crate::{
     service::{
         db, format_stream_name,
         ingestion::{evaluate_trigger, write_file, TriggerAlertData},
-        metrics::format_label_name,
+        metadata::{distinct_values::DvItem, write, MetadataItem, MetadataType},
+        metrics::{format_label_name, RE_LABEL_NAME},
         schema::{check_for_schema, stream_schema_exists},
         search as search_service,
-        usage::report_request_usage_stats,
+        usage::{report_latency, report_request_usage_stats, LatencyKind},
     },
 };
 
@@ -94,6 +95,7 @@ pub async fn remote_write(
     let mut stream_trigger_map: HashMap<String, Option<TriggerAlertData>> = HashMap::new();
     let mut stream_transform_map: HashMap<String, Vec<StreamTransform>> = HashMap::new();
     let mut stream_partitioning_map: HashMap<String, PartitioningDetails> = HashMap::new();
+    let mut distinct_values: HashSet<DvItem> = HashSet::new();
 
     let decoded = snap::raw::Decoder::new()
         .decompress_vec(&body)
@@ -153,6 +155,12 @@ pub async fn remote_write(
                 &StreamType::Metrics.to_string(),
             ])
             .observe(time);
+        report_latency(
+            LatencyKind::Ingest,
+            "/prometheus/api/v1/write",
+            org_id,
+            time,
+        );
         metrics::HTTP_INCOMING_REQUESTS
             .with_label_values(&[
                 "/prometheus/api/v1/write",
@@ -197,10 +205,32 @@ pub async fn remote_write(
             None => continue,
         };
 
+        // the label values API reads the distinct values of the configured distinct fields
+        // instead of scanning the samples
+        for (name, value) in labels.iter() {
+            if distinct_values.len() >= cfg.limit.metrics_distinct_values_limit {
+                break;
+            }
+            if name != NAME_LABEL && DISTINCT_FIELDS.contains(name) {
+                distinct_values.insert(DvItem {
+                    stream_type: StreamType::Metrics,
+                    stream_name: metric_name.to_owned(),
+                    field_name: name.to_owned(),
+                    field_value: value.to_owned(),
+                    filter_name: "".to_string(),
+                    filter_value: "".to_string(),
+                });
+            }
+        }
+
         let buf = metric_data_map.entry(metric_name.to_owned()).or_default();
 
+        // the exemplars of the series are stored with its last sample
+        let mut exemplars = format_exemplars(&event.exemplars);
+        let last_sample = event.samples.len().saturating_sub(1);
+
         // parse samples
-        for sample in event.samples {
+        for (i, sample) in event.samples.into_iter().enumerate() {
             let mut sample_val = sample.value;
             // revisit in future
             if sample_val.is_infinite() {
@@ -261,6 +291,12 @@ pub async fn remote_write(
                         &StreamType::Metrics.to_string(),
                     ])
                     .observe(time);
+                report_latency(
+                    LatencyKind::Ingest,
+                    "/prometheus/api/v1/write",
+                    org_id,
+                    time,
+                );
                 metrics::HTTP_INCOMING_REQUESTS
                     .with_label_values(&[
                         "/prometheus/api/v1/write",
@@ -346,6 +382,11 @@ pub async fn remote_write(
                 cfg.common.column_timestamp.clone(),
                 json::Value::Number(timestamp.into()),
             );
+            if i == last_sample {
+                if let Some(exemplars) = exemplars.take() {
+                    val_map.insert(EXEMPLARS_LABEL.to_string(), json::Value::String(exemplars));
+                }
+            }
             let value_str = config::utils::json::to_string(&val_map).unwrap();
 
             // check for schema evolution
@@ -474,6 +515,17 @@ pub async fn remote_write(
         .await;
     }
 
+    // send distinct_values
+    if !distinct_values.is_empty() {
+        let distinct_values = distinct_values
+            .into_iter()
+            .map(MetadataItem::DistinctValues)
+            .collect();
+        if let Err(e) = write(org_id, MetadataType::DistinctValues, distinct_values).await {
+            log::error!("Error while writing distinct values: {}", e);
+        }
+    }
+
     // only one trigger per request, as it updates etcd
     for (_, entry) in stream_trigger_map {
         if let Some(entry) = entry {
@@ -490,6 +542,12 @@ pub async fn remote_write(
             &StreamType::Metrics.to_string(),
         ])
         .observe(time);
+    report_latency(
+        LatencyKind::Ingest,
+        "/prometheus/api/v1/write",
+        org_id,
+        time,
+    );
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             "/prometheus/api/v1/write",
@@ -611,7 +669,12 @@ pub(crate) async fn get_series(
         .fields()
         .iter()
         .map(|f| f.name().as_str())
-        .filter(|&s| s != cfg.common.column_timestamp && s != VALUE_LABEL && s != HASH_LABEL)
+        .filter(|&s| {
+            s != cfg.common.column_timestamp
+                && s != VALUE_LABEL
+                && s != HASH_LABEL
+                && s != EXEMPLARS_LABEL
+        })
         .collect::<Vec<_>>()
         .join("\", \"");
     if label_names.is_empty() {
@@ -619,30 +682,8 @@ pub(crate) async fn get_series(
     }
 
     let mut sql = format!("SELECT DISTINCT({HASH_LABEL}), \"{label_names}\" FROM {metric_name}");
-    let mut sql_where = Vec::new();
     if let Some(selector) = selector {
-        for mat in selector.matchers.matchers.iter() {
-            if mat.name == cfg.common.column_timestamp
-                || mat.name == VALUE_LABEL
-                || schema.field_with_name(&mat.name).is_err()
-            {
-                continue;
-            }
-            match &mat.op {
-                MatchOp::Equal => {
-                    sql_where.push(format!("{} = '{}'", mat.name, mat.value));
-                }
-                MatchOp::NotEqual => {
-                    sql_where.push(format!("{} != '{}'", mat.name, mat.value));
-                }
-                MatchOp::Re(_re) => {
-                    sql_where.push(format!("re_match({}, '{}')", mat.name, mat.value));
-                }
-                MatchOp::NotRe(_re) => {
-                    sql_where.push(format!("re_not_match({}, '{}')", mat.name, mat.value));
-                }
-            }
-        }
+        let sql_where = selector_to_sql_where(&selector, &schema);
         if !sql_where.is_empty() {
             sql.push_str(" WHERE ");
             sql.push_str(&sql_where.join(" AND "));
@@ -684,6 +725,295 @@ pub(crate) async fn get_series(
     Ok(series)
 }
 
+/// Returns the exemplars of the series selected by the query, the series without exemplars in
+/// the time range are left out.
+pub(crate) async fn get_exemplars(
+    org_id: &str,
+    expr: &parser::Expr,
+    start: i64,
+    end: i64,
+) -> Result<Vec<ExemplarSeries>> {
+    let mut selectors = Vec::new();
+    collect_selectors(expr, &mut selectors);
+
+    let cfg = get_config();
+    let mut series = Vec::new();
+    for selector in selectors {
+        let Some(metric_name) = try_into_metric_name(&selector) else {
+            continue;
+        };
+        let schema = infra::schema::get(org_id, &metric_name, StreamType::Metrics)
+            .await
+            // `db::schema::get` never fails, so it's safe to unwrap
+            .unwrap();
+        if schema.field_with_name(EXEMPLARS_LABEL).is_err() {
+            continue;
+        }
+        let label_names = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .filter(|&s| {
+                s != cfg.common.column_timestamp
+                    && s != VALUE_LABEL
+                    && s != HASH_LABEL
+                    && s != EXEMPLARS_LABEL
+            })
+            .collect::<Vec<_>>()
+            .join("\", \"");
+        let mut sql_where = vec![format!(
+            "{EXEMPLARS_LABEL} IS NOT NULL AND {EXEMPLARS_LABEL} != '[]'"
+        )];
+        sql_where.extend(selector_to_sql_where(&selector, &schema));
+        let sql = format!(
+            "SELECT {HASH_LABEL}, \"{label_names}\", {}, {EXEMPLARS_LABEL} FROM \"{metric_name}\" WHERE {}",
+            cfg.common.column_timestamp,
+            sql_where.join(" AND ")
+        );
+        let req = config::meta::search::Request {
+            query: config::meta::search::Query {
+                sql,
+                from: 0,
+                size: 1000,
+                start_time: start,
+                end_time: end,
+                ..Default::default()
+            },
+            encoding: config::meta::search::RequestEncoding::Empty,
+            regions: vec![],
+            clusters: vec![],
+            timeout: 0,
+            search_type: None,
+            index_type: "".to_string(),
+        };
+        match search_service::search("", org_id, StreamType::Metrics, None, &req).await {
+            Ok(resp) => series.extend(exemplars_from_hits(resp.hits, start, end)),
+            Err(err) => {
+                log::error!("search exemplars error: {err}");
+                return Err(err);
+            }
+        }
+    }
+    Ok(series)
+}
+
+/// Returns the scrape interval of the first metric of the expression in microseconds, detected
+/// from the time between the latest samples of its series in the time range
+pub(crate) async fn detect_scrape_interval(
+    org_id: &str,
+    expr: &parser::Expr,
+    start: i64,
+    end: i64,
+) -> Result<Option<i64>> {
+    let mut selectors = Vec::new();
+    collect_selectors(expr, &mut selectors);
+    let Some((selector, metric_name)) = selectors
+        .into_iter()
+        .find_map(|selector| try_into_metric_name(&selector).map(|name| (selector, name)))
+    else {
+        return Ok(None);
+    };
+    let schema = infra::schema::get(org_id, &metric_name, StreamType::Metrics)
+        .await
+        // `db::schema::get` never fails, so it's safe to unwrap
+        .unwrap();
+    if schema.fields().is_empty() {
+        return Ok(None);
+    }
+
+    let column_timestamp = &get_config().common.column_timestamp;
+    let sql_where = selector_to_sql_where(&selector, &schema);
+    let sql = format!(
+        "SELECT {HASH_LABEL}, {column_timestamp} FROM \"{metric_name}\"{} ORDER BY {column_timestamp} DESC",
+        if sql_where.is_empty() {
+            "".to_string()
+        } else {
+            format!(" WHERE {}", sql_where.join(" AND "))
+        }
+    );
+    let req = config::meta::search::Request {
+        query: config::meta::search::Query {
+            sql,
+            from: 0,
+            size: 1000,
+            start_time: start,
+            end_time: end,
+            ..Default::default()
+        },
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+        index_type: "".to_string(),
+    };
+    let resp = search_service::search("", org_id, StreamType::Metrics, None, &req).await?;
+    Ok(scrape_interval_from_hits(&resp.hits))
+}
+
+/// The median of the time between the consecutive samples of each series
+fn scrape_interval_from_hits(hits: &[json::Value]) -> Option<i64> {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let mut series: HashMap<&str, Vec<i64>> = HashMap::new();
+    for hit in hits {
+        let (Some(hash), Some(ts)) = (
+            hit.get(HASH_LABEL).and_then(|v| v.as_str()),
+            hit.get(column_timestamp).and_then(|v| v.as_i64()),
+        ) else {
+            continue;
+        };
+        series.entry(hash).or_default().push(ts);
+    }
+    let mut intervals = Vec::new();
+    for timestamps in series.values_mut() {
+        timestamps.sort_unstable();
+        intervals.extend(
+            timestamps
+                .windows(2)
+                .map(|w| w[1] - w[0])
+                .filter(|v| *v > 0),
+        );
+    }
+    if intervals.is_empty() {
+        return None;
+    }
+    intervals.sort_unstable();
+    Some(intervals[intervals.len() / 2])
+}
+
+fn collect_selectors(expr: &parser::Expr, selectors: &mut Vec<parser::VectorSelector>) {
+    match expr {
+        parser::Expr::VectorSelector(selector) => selectors.push(selector.clone()),
+        parser::Expr::MatrixSelector(selector) => selectors.push(selector.vs.clone()),
+        parser::Expr::Unary(unary) => collect_selectors(&unary.expr, selectors),
+        parser::Expr::Binary(binary) => {
+            collect_selectors(&binary.lhs, selectors);
+            collect_selectors(&binary.rhs, selectors);
+        }
+        parser::Expr::Paren(paren) => collect_selectors(&paren.expr, selectors),
+        parser::Expr::Subquery(subquery) => collect_selectors(&subquery.expr, selectors),
+        parser::Expr::Call(call) => {
+            for arg in call.args.args.iter() {
+                collect_selectors(arg, selectors);
+            }
+        }
+        parser::Expr::Aggregate(aggregate) => collect_selectors(&aggregate.expr, selectors),
+        parser::Expr::NumberLiteral(_)
+        | parser::Expr::StringLiteral(_)
+        | parser::Expr::Extension(_) => {}
+    }
+}
+
+/// Formats the exemplars of a remote write series like the ones of OTLP, a JSON array of objects
+/// holding the labels, the value and the timestamp
+fn format_exemplars(exemplars: &[prometheus_rpc::Exemplar]) -> Option<String> {
+    if exemplars.is_empty() {
+        return None;
+    }
+    let column_timestamp = &get_config().common.column_timestamp;
+    let exemplars = exemplars
+        .iter()
+        .map(|exemplar| {
+            let mut rec = json::Map::with_capacity(exemplar.labels.len() + 2);
+            for label in exemplar.labels.iter() {
+                rec.insert(label.name.clone(), label.value.clone().into());
+            }
+            rec.insert(VALUE_LABEL.to_string(), exemplar.value.into());
+            rec.insert(
+                column_timestamp.clone(),
+                parse_i64_to_timestamp_micros(exemplar.timestamp).into(),
+            );
+            json::Value::Object(rec)
+        })
+        .collect::<Vec<_>>();
+    json::to_string(&exemplars).ok()
+}
+
+/// Parses the stored exemplars of a sample, keeping the ones in the time range
+fn parse_exemplars(exemplars: &str, start: i64, end: i64) -> Vec<Exemplar> {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let Ok(json::Value::Array(exemplars)) = json::from_str::<json::Value>(exemplars) else {
+        return vec![];
+    };
+    exemplars
+        .into_iter()
+        .filter_map(|exemplar| {
+            let json::Value::Object(exemplar) = exemplar else {
+                return None;
+            };
+            let timestamp = exemplar.get(column_timestamp)?.as_i64()?;
+            if timestamp < start || timestamp > end {
+                return None;
+            }
+            let value = exemplar.get(VALUE_LABEL)?.as_f64()?;
+            let labels = exemplar
+                .into_iter()
+                .filter(|(k, v)| k != column_timestamp && k != VALUE_LABEL && !v.is_null())
+                .map(|(k, v)| match v {
+                    json::Value::String(v) => (k, v),
+                    v => (k, v.to_string()),
+                })
+                .collect();
+            Some(Exemplar {
+                labels,
+                value: value.to_string(),
+                timestamp: timestamp as f64 / 1_000_000.0,
+            })
+        })
+        .collect()
+}
+
+/// Groups the exemplars of the samples by series, the samples without exemplars are skipped
+fn exemplars_from_hits(hits: Vec<json::Value>, start: i64, end: i64) -> Vec<ExemplarSeries> {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let mut series: Vec<ExemplarSeries> = Vec::new();
+    let mut series_idx = HashMap::new();
+    for hit in hits {
+        let json::Value::Object(hit) = hit else {
+            continue;
+        };
+        let exemplars = match hit.get(EXEMPLARS_LABEL).and_then(|v| v.as_str()) {
+            Some(v) => parse_exemplars(v, start, end),
+            None => continue,
+        };
+        if exemplars.is_empty() {
+            continue;
+        }
+        let hash = hit
+            .get(HASH_LABEL)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let idx = *series_idx.entry(hash).or_insert_with(|| {
+            let series_labels = hit
+                .iter()
+                .filter(|(k, v)| {
+                    k.as_str() != HASH_LABEL
+                        && k.as_str() != VALUE_LABEL
+                        && k.as_str() != EXEMPLARS_LABEL
+                        && *k != column_timestamp
+                        && !v.is_null()
+                })
+                .map(|(k, v)| match v {
+                    json::Value::String(v) => (k.clone(), v.clone()),
+                    v => (k.clone(), v.to_string()),
+                })
+                .collect();
+            series.push(ExemplarSeries {
+                series_labels,
+                exemplars: vec![],
+            });
+            series.len() - 1
+        });
+        series[idx].exemplars.extend(exemplars);
+    }
+    for s in series.iter_mut() {
+        s.exemplars
+            .sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    }
+    series
+}
+
 pub(crate) async fn get_labels(
     org_id: &str,
     selector: Option<parser::VectorSelector>,
@@ -713,7 +1043,10 @@ pub(crate) async fn get_labels(
                 .iter()
                 .map(|f| f.name())
                 .filter(|&s| {
-                    s != &cfg.common.column_timestamp && s != VALUE_LABEL && s != HASH_LABEL
+                    s != &cfg.common.column_timestamp
+                        && s != VALUE_LABEL
+                        && s != HASH_LABEL
+                        && s != EXEMPLARS_LABEL
                 })
                 .cloned();
             label_names.extend(field_names);
@@ -721,10 +1054,10 @@ pub(crate) async fn get_labels(
     }
     let mut label_names = label_names.into_iter().collect::<Vec<_>>();
     label_names.sort();
+    label_names.truncate(cfg.limit.metrics_label_values_limit);
     Ok(label_names)
 }
 
-// XXX-TODO: filter the results in accordance with `selector.matchers`
 pub(crate) async fn get_label_values(
     org_id: &str,
     label_name: String,
@@ -772,9 +1105,40 @@ pub(crate) async fn get_label_values(
             }
         }
         label_values.sort();
+        label_values.truncate(get_config().limit.metrics_label_values_limit);
         return Ok(label_values);
     }
 
+    if !RE_LABEL_NAME.is_match(&label_name) {
+        return Ok(vec![]);
+    }
+    let limit = get_config().limit.metrics_label_values_limit;
+
+    // the distinct values only know the metric of a label value, the other matchers need
+    // to scan the series, and only the distinct fields are recorded
+    let has_label_matchers = selector.as_ref().map_or(false, |selector| {
+        selector
+            .matchers
+            .matchers
+            .iter()
+            .any(|mat| mat.name != NAME_LABEL)
+    });
+    if !has_label_matchers && DISTINCT_FIELDS.contains(&label_name) {
+        let label_values = get_label_values_from_distinct_values(
+            org_id,
+            &label_name,
+            opt_metric_name.as_deref(),
+            start,
+            end,
+            limit,
+        )
+        .await?;
+        // the series ingested before the label values were recorded need the scan
+        if !label_values.is_empty() || opt_metric_name.is_none() {
+            return Ok(label_values);
+        }
+    }
+
     let metric_name = match opt_metric_name {
         Some(name) => name,
         None => {
@@ -794,11 +1158,19 @@ pub(crate) async fn get_label_values(
     if schema.field_with_name(&label_name).is_err() {
         return Ok(vec![]);
     }
+    let mut sql = format!("SELECT DISTINCT({label_name}) FROM {metric_name}");
+    if let Some(selector) = selector {
+        let sql_where = selector_to_sql_where(&selector, &schema);
+        if !sql_where.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&sql_where.join(" AND "));
+        }
+    }
     let req = config::meta::search::Request {
         query: config::meta::search::Query {
-            sql: format!("SELECT DISTINCT({label_name}) FROM {metric_name}"),
+            sql,
             from: 0,
-            size: 1000,
+            size: limit as i64,
             start_time: start,
             end_time: end,
             ..Default::default()
@@ -814,8 +1186,7 @@ pub(crate) async fn get_label_values(
         Ok(resp) => resp
             .hits
             .iter()
-            .filter_map(|v| v.as_object().unwrap().get(&label_name))
-            .map(|v| v.as_str().unwrap().to_string())
+            .filter_map(|v| v.get(&label_name).and_then(label_value_to_string))
             .collect::<Vec<_>>(),
         Err(err) => {
             log::error!("search values error: {:?}", err);
@@ -827,6 +1198,96 @@ pub(crate) async fn get_label_values(
     Ok(label_values)
 }
 
+/// Reads the values of a label from the distinct values recorded at ingestion, of all the
+/// metrics when `metric_name` is `None`
+async fn get_label_values_from_distinct_values(
+    org_id: &str,
+    label_name: &str,
+    metric_name: Option<&str>,
+    start: i64,
+    end: i64,
+    limit: usize,
+) -> Result<Vec<String>> {
+    let mut sql = format!(
+        "SELECT DISTINCT(field_value) FROM distinct_values WHERE stream_type='{}' AND field_name='{label_name}'",
+        StreamType::Metrics
+    );
+    if let Some(metric_name) = metric_name {
+        sql.push_str(&format!(" AND stream_name='{metric_name}'"));
+    }
+    let req = config::meta::search::Request {
+        query: config::meta::search::Query {
+            sql,
+            from: 0,
+            size: limit as i64,
+            start_time: start,
+            end_time: end,
+            ..Default::default()
+        },
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+        index_type: "".to_string(),
+    };
+    let mut label_values =
+        match search_service::search("", org_id, StreamType::Metadata, None, &req).await {
+            Ok(resp) => resp
+                .hits
+                .iter()
+                .filter_map(|v| v.get("field_value").and_then(label_value_to_string))
+                .collect::<Vec<_>>(),
+            // the distinct values stream doesn't exist before the first flush
+            Err(Error::ErrorCode(ErrorCodes::SearchStreamNotFound(_))) => vec![],
+            Err(err) => {
+                log::error!("search distinct values error: {:?}", err);
+                return Err(err);
+            }
+        };
+    label_values.sort();
+    label_values.dedup();
+    Ok(label_values)
+}
+
+/// Returns the label value of a hit. The labels of OTLP metrics can be ingested as numbers or
+/// booleans, they are returned as their text like the string labels.
+fn label_value_to_string(value: &json::Value) -> Option<String> {
+    match value {
+        json::Value::Null => None,
+        json::Value::String(v) => Some(v.to_string()),
+        v => Some(v.to_string()),
+    }
+}
+
+fn selector_to_sql_where(selector: &parser::VectorSelector, schema: &Schema) -> Vec<String> {
+    let cfg = get_config();
+    let mut sql_where = Vec::new();
+    for mat in selector.matchers.matchers.iter() {
+        if mat.name == cfg.common.column_timestamp
+            || mat.name == VALUE_LABEL
+            || schema.field_with_name(&mat.name).is_err()
+        {
+            continue;
+        }
+        match &mat.op {
+            MatchOp::Equal => {
+                sql_where.push(format!("{} = '{}'", mat.name, mat.value));
+            }
+            MatchOp::NotEqual => {
+                sql_where.push(format!("{} != '{}'", mat.name, mat.value));
+            }
+            MatchOp::Re(_re) => {
+                sql_where.push(format!("re_match({}, '{}')", mat.name, mat.value));
+            }
+            MatchOp::NotRe(_re) => {
+                sql_where.push(format!("re_not_match({}, '{}')", mat.name, mat.value));
+            }
+        }
+    }
+    sql_where
+}
+
 pub(crate) fn try_into_metric_name(selector: &parser::VectorSelector) -> Option<String> {
     match &selector.name {
         Some(name) => {
@@ -917,3 +1378,97 @@ async fn prom_ha_handler(
 
     _accept_record
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_value_to_string() {
+        assert_eq!(
+            label_value_to_string(&json::json!("api")),
+            Some("api".to_string())
+        );
+        assert_eq!(
+            label_value_to_string(&json::json!(3)),
+            Some("3".to_string())
+        );
+        assert_eq!(
+            label_value_to_string(&json::json!(true)),
+            Some("true".to_string())
+        );
+        assert_eq!(label_value_to_string(&json::Value::Null), None);
+    }
+
+    #[test]
+    fn test_exemplars_round_trip() {
+        let exemplar = prometheus_rpc::Exemplar {
+            labels: vec![prometheus_rpc::Label {
+                name: "trace_id".to_string(),
+                value: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            }],
+            value: 0.25,
+            timestamp: 1_700_000_000_123,
+        };
+        let stored = format_exemplars(&[exemplar]).unwrap();
+        let hit = json::json!({
+            HASH_LABEL: "h1",
+            NAME_LABEL: "http_request_duration_seconds",
+            "job": "api",
+            VALUE_LABEL: 0.3,
+            "_timestamp": 1_700_000_000_500_000_i64,
+            EXEMPLARS_LABEL: stored,
+        });
+        let series = exemplars_from_hits(vec![hit], 0, i64::MAX);
+        assert_eq!(
+            series,
+            vec![ExemplarSeries {
+                series_labels: [
+                    (NAME_LABEL, "http_request_duration_seconds"),
+                    ("job", "api")
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+                exemplars: vec![Exemplar {
+                    labels: [(
+                        "trace_id".to_string(),
+                        "4bf92f3577b34da6a3ce929d0e0e4736".to_string()
+                    )]
+                    .into(),
+                    value: "0.25".to_string(),
+                    timestamp: 1_700_000_000.123,
+                }],
+            }]
+        );
+        // the exemplar is out of the time range
+        assert!(parse_exemplars(&stored, 1_700_000_001_000_000, i64::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_exemplars_missing() {
+        assert_eq!(format_exemplars(&[]), None);
+        let hits = vec![
+            json::json!({HASH_LABEL: "h1", "job": "api", VALUE_LABEL: 1.0}),
+            json::json!({HASH_LABEL: "h2", "job": "api", EXEMPLARS_LABEL: "[]"}),
+            json::json!({HASH_LABEL: "h3", "job": "api", EXEMPLARS_LABEL: "not json"}),
+        ];
+        assert!(exemplars_from_hits(hits, 0, i64::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_scrape_interval_from_hits() {
+        let ts = &get_config().common.column_timestamp;
+        let mut hits = Vec::new();
+        for i in 0..5 {
+            hits.push(json::json!({HASH_LABEL: "h1", ts.as_str(): i * 15_000_000}));
+            hits.push(json::json!({HASH_LABEL: "h2", ts.as_str(): 7_000_000 + i * 15_000_000}));
+        }
+        // a missed scrape doesn't change the interval
+        hits.push(json::json!({HASH_LABEL: "h1", ts.as_str(): 90_000_000}));
+        assert_eq!(scrape_interval_from_hits(&hits), Some(15_000_000));
+
+        let hits = vec![json::json!({HASH_LABEL: "h1", ts.as_str(): 0})];
+        assert_eq!(scrape_interval_from_hits(&hits), None);
+    }
+}
@@ -17,6 +17,7 @@ use config::{meta::stream::StreamParams, utils::schema::format_stream_name};
 use infra::errors::Result;
 
 pub mod alerts;
+pub mod api_keys;
 pub mod compact;
 pub mod dashboards;
 pub mod db;
@@ -34,8 +35,10 @@ pub mod metrics;
 pub mod organization;
 pub mod pipelines;
 pub mod promql;
+pub mod scheduled_export;
 pub mod schema;
 pub mod search;
+pub mod secrets;
 pub mod session;
 pub mod short_url;
 pub mod stream;
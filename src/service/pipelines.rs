@@ -50,6 +50,13 @@ pub async fn save_pipeline(org_id: String, mut pipeline: PipeLine) -> Result<Htt
         )));
     }
 
+    if let Err(e) = pipeline.validate_enrichments() {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            e,
+        )));
+    }
+
     // Save DerivedStream details if there's any
     if let Some(ref mut derived_streams) = &mut pipeline.derived_streams {
         for derived_stream in derived_streams {
@@ -111,6 +118,13 @@ pub async fn update_pipeline(org_id: &str, mut pipeline: PipeLine) -> Result<Htt
         return Ok(HttpResponse::Ok().json(pipeline));
     }
 
+    if let Err(e) = pipeline.validate_enrichments() {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            e,
+        )));
+    }
+
     // Update DerivedStream details if there's any
     if let Some(ref mut derived_streams) = &mut pipeline.derived_streams {
         for derived_stream in derived_streams {
@@ -19,26 +19,37 @@ use async_recursion::async_recursion;
 use datafusion::{
     arrow::{
         array::{Float64Array, Int64Array, StringArray},
-        datatypes::Schema,
+        datatypes::{DataType, Schema},
     },
     error::{DataFusionError, Result},
     functions_aggregate::min_max::max,
-    prelude::{col, lit, SessionContext},
+    prelude::{cast, col, lit, SessionContext},
 };
 use futures::future::try_join_all;
 use hashbrown::HashMap;
 use promql_parser::{
     label::MatchOp,
     parser::{
-        token, AggregateExpr, BinModifier, BinaryExpr, Call, Expr as PromExpr, Function,
-        FunctionArgs, LabelModifier, MatrixSelector, NumberLiteral, Offset, ParenExpr,
+        token, AggregateExpr, AtModifier, BinModifier, BinaryExpr, Call, Expr as PromExpr,
+        Function, FunctionArgs, LabelModifier, MatrixSelector, NumberLiteral, Offset, ParenExpr,
         StringLiteral, UnaryExpr, VectorMatchCardinality, VectorSelector,
     },
 };
 
 use crate::{
-    common::meta::prom::{BUCKET_LABEL, HASH_LABEL, NAME_LABEL, VALUE_LABEL},
-    service::promql::{aggregations, binaries, functions, micros, value::*},
+    common::meta::prom::{
+        BUCKET_LABEL, EXEMPLARS_LABEL, HASH_LABEL, NAME_LABEL, ROLLUP_LABEL_PREFIX,
+        ROLLUP_LAST_LABEL, ROLLUP_MAX_LABEL, ROLLUP_MIN_LABEL, ROLLUP_SUM_LABEL, VALUE_LABEL,
+    },
+    service::{
+        compact::rollup,
+        promql::{
+            aggregations, binaries,
+            functions::{self, Func},
+            micros, micros_since_epoch,
+            value::*,
+        },
+    },
 };
 
 pub struct Engine {
@@ -48,6 +59,9 @@ pub struct Engine {
     /// Filters to include certain columns
     col_filters: Option<HashSet<String>>,
     result_type: Option<String>,
+    /// The rollup column the matrix selectors of the evaluated function read, `None` when the
+    /// function needs the raw samples
+    rollup_column: Option<&'static str>,
 }
 
 impl Engine {
@@ -57,6 +71,7 @@ impl Engine {
             time,
             col_filters: Some(HashSet::new()),
             result_type: None,
+            rollup_column: None,
         }
     }
 
@@ -333,14 +348,13 @@ impl Engine {
             selector.name = Some(name);
         }
 
-        let metrics_name = selector.name.as_ref().expect("Missing selector name");
-
-        let cache_exists = { self.ctx.data_cache.read().await.contains_key(metrics_name) };
+        let cache_key = selector_cache_key(&selector, self.selector_rollup(None));
+        let cache_exists = { self.ctx.data_cache.read().await.contains_key(&cache_key) };
         if !cache_exists {
             self.selector_load_data(&selector, None).await?;
         }
         let metrics_cache = self.ctx.data_cache.read().await;
-        let metrics_cache = match metrics_cache.get(metrics_name) {
+        let metrics_cache = match metrics_cache.get(&cache_key) {
             Some(v) => match v.get_ref_matrix_values() {
                 Some(v) => v,
                 None => return Ok(vec![]),
@@ -351,18 +365,8 @@ impl Engine {
         // Evaluation timestamp.
         let eval_ts = self.time;
         let start = eval_ts - self.ctx.lookback_delta;
-
-        let mut offset_modifier: i64 = 0;
-        if let Some(offset) = selector.offset {
-            match offset {
-                Offset::Pos(off) => {
-                    offset_modifier = micros(off);
-                }
-                Offset::Neg(off) => {
-                    offset_modifier = -micros(off);
-                }
-            }
-        }
+        // shift the samples looked up by the `@` and `offset` modifiers to the evaluation time
+        let offset_modifier = eval_ts - self.selector_lookup_time(&selector, eval_ts);
 
         let mut values = vec![];
         for metric in metrics_cache {
@@ -416,13 +420,13 @@ impl Engine {
             selector.name = Some(name);
         }
 
-        let metrics_name = selector.name.as_ref().expect("Missing selector name");
-        let cache_exists = { self.ctx.data_cache.read().await.contains_key(metrics_name) };
+        let cache_key = selector_cache_key(&selector, self.selector_rollup(Some(range)));
+        let cache_exists = { self.ctx.data_cache.read().await.contains_key(&cache_key) };
         if !cache_exists {
             self.selector_load_data(&selector, Some(range)).await?;
         }
         let metrics_cache = self.ctx.data_cache.read().await;
-        let metrics_cache = match metrics_cache.get(metrics_name) {
+        let metrics_cache = match metrics_cache.get(&cache_key) {
             Some(v) => match v.get_ref_matrix_values() {
                 Some(v) => v,
                 None => return Ok(vec![]),
@@ -434,17 +438,8 @@ impl Engine {
         let eval_ts = self.time;
         // Start of the time window.
         let start = eval_ts - micros(range); // e.g. [5m]
-        let mut offset_modifier = 0;
-        if let Some(offset) = selector.offset {
-            match offset {
-                Offset::Pos(offset) => {
-                    offset_modifier = micros(offset);
-                }
-                Offset::Neg(offset) => {
-                    offset_modifier = -micros(offset);
-                }
-            }
-        };
+        // shift the samples looked up by the `@` and `offset` modifiers to the evaluation time
+        let offset_modifier = eval_ts - self.selector_lookup_time(&selector, eval_ts);
 
         let mut values = Vec::with_capacity(metrics_cache.len());
         for metric in metrics_cache {
@@ -475,25 +470,24 @@ impl Engine {
     ) -> Result<()> {
         // https://promlabs.com/blog/2020/07/02/selecting-data-in-promql/#lookback-delta
 
-        let mut start = self.ctx.start - range.map_or(self.ctx.lookback_delta, micros);
-        let mut end = self.ctx.end; // 30 minutes + 5m = 35m
-
-        if let Some(offset) = selector.offset.clone() {
-            match offset {
-                Offset::Pos(offset) => {
-                    start -= micros(offset);
-                    end -= micros(offset);
-                }
-                Offset::Neg(offset) => {
-                    start += micros(offset);
-                    end += micros(offset);
-                }
-            }
-        }
+        let rollup = self.selector_rollup(range);
+        let range = range.map_or(self.ctx.lookback_delta, micros);
+        // the samples are looked up at the evaluation times shifted by the `offset` modifier,
+        // or at the single time of the `@` modifier
+        let (start, end) = if selector.at.is_some() {
+            let lookup_time = self.selector_lookup_time(selector, self.time);
+            (lookup_time - range, lookup_time)
+        } else {
+            (
+                self.selector_lookup_time(selector, self.ctx.start) - range,
+                self.selector_lookup_time(selector, self.ctx.end),
+            )
+        };
 
         // 1. Group by metrics (sets of label name-value pairs)
         let table_name = selector.name.as_ref().unwrap();
-        let mut filters = selector
+        let tables = self.selector_tables(table_name, rollup, (start, end)).await;
+        let filters = selector
             .matchers
             .matchers
             .iter()
@@ -505,24 +499,41 @@ impl Engine {
                 }
             })
             .collect::<Vec<(_, _)>>();
-        let ctxs = self
-            .ctx
-            .table_provider
-            .create_context(&self.ctx.org_id, table_name, (start, end), &mut filters)
-            .await?;
-
         let mut tasks = Vec::new();
-        for (ctx, schema, scan_stats) in ctxs {
-            let selector = selector.clone();
-            let col_filters = &self.col_filters;
-            let task = tokio::time::timeout(Duration::from_secs(self.ctx.timeout), async move {
-                selector_load_data_from_datafusion(ctx, schema, selector, start, end, col_filters)
-                    .await
-            });
-            tasks.push(task);
-            // update stats
-            let mut ctx_scan_stats = self.ctx.scan_stats.write().await;
-            ctx_scan_stats.add(&scan_stats);
+        for (table_name, (start, end), value_column) in tables {
+            let ctxs = self
+                .ctx
+                .table_provider
+                .create_context(
+                    &self.ctx.org_id,
+                    &table_name,
+                    (start, end),
+                    &mut filters.clone(),
+                )
+                .await?;
+            for (ctx, schema, scan_stats) in ctxs {
+                let selector = selector.clone();
+                let table_name = table_name.clone();
+                let col_filters = &self.col_filters;
+                let task =
+                    tokio::time::timeout(Duration::from_secs(self.ctx.timeout), async move {
+                        selector_load_data_from_datafusion(
+                            ctx,
+                            schema,
+                            &table_name,
+                            selector,
+                            value_column,
+                            start,
+                            end,
+                            col_filters,
+                        )
+                        .await
+                    });
+                tasks.push(task);
+                // update stats
+                let mut ctx_scan_stats = self.ctx.scan_stats.write().await;
+                ctx_scan_stats.add(&scan_stats);
+            }
         }
         let task_results = try_join_all(tasks)
             .await
@@ -545,12 +556,13 @@ impl Engine {
         }
 
         // no data, return immediately
+        let cache_key = selector_cache_key(selector, rollup);
         if metrics.is_empty() {
             self.ctx
                 .data_cache
                 .write()
                 .await
-                .insert(table_name.to_string(), Value::None);
+                .insert(cache_key, Value::None);
             return Ok(());
         }
 
@@ -564,14 +576,73 @@ impl Engine {
         } else {
             Value::Matrix(metric_values)
         };
-        self.ctx
-            .data_cache
-            .write()
-            .await
-            .insert(table_name.to_string(), values);
+        self.ctx.data_cache.write().await.insert(cache_key, values);
         Ok(())
     }
 
+    /// Returns the rollup column a selector can read and the max resolution of the rollup
+    /// stream, `None` when the selector needs the raw samples.
+    ///
+    /// The instant selectors look up the last sample of the lookback window. The matrix
+    /// selectors read the rollup column of the function they are evaluated by, with at least
+    /// two samples in the window for the counter functions.
+    fn selector_rollup(&self, range: Option<Duration>) -> Option<(&'static str, i64)> {
+        match range {
+            None => Some((ROLLUP_LAST_LABEL, self.ctx.lookback_delta)),
+            Some(range) => self.rollup_column.map(|column| (column, micros(range) / 2)),
+        }
+    }
+
+    /// Returns the streams to load the samples of the metric from, with their time ranges and
+    /// the column of the sample values.
+    ///
+    /// Range queries read the samples older than the rollup offset from the coarsest rollup
+    /// stream whose resolution is at most the step and the max resolution of the selector, the
+    /// samples of a rollup stream are the aggregated rollup column of a bucket. The newer
+    /// samples are read from the metric stream.
+    async fn selector_tables(
+        &self,
+        table_name: &str,
+        rollup: Option<(&'static str, i64)>,
+        (start, end): (i64, i64),
+    ) -> Vec<(String, (i64, i64), &'static str)> {
+        let raw = vec![(table_name.to_string(), (start, end), VALUE_LABEL)];
+        let Some((rollup_column, max_resolution)) = rollup else {
+            return raw;
+        };
+        if self.ctx.start == self.ctx.end {
+            return raw;
+        }
+        let max_resolution = std::cmp::min(self.ctx.interval, max_resolution);
+        match rollup::get_rollup_stream(&self.ctx.org_id, table_name, max_resolution).await {
+            Some((rollup_stream, offset)) if offset > start + 1 => {
+                let rollup_end = std::cmp::min(end, offset - 1);
+                let mut tables = vec![(rollup_stream, (start, rollup_end), rollup_column)];
+                if end > rollup_end {
+                    tables.push((table_name.to_string(), (rollup_end, end), VALUE_LABEL));
+                }
+                tables
+            }
+            _ => raw,
+        }
+    }
+
+    /// Returns the time the samples of the selector are looked up at for the evaluation time
+    /// `eval_ts`, after applying the `@` and `offset` modifiers.
+    fn selector_lookup_time(&self, selector: &VectorSelector, eval_ts: i64) -> i64 {
+        let time = match &selector.at {
+            None => eval_ts,
+            Some(AtModifier::Start) => self.ctx.start,
+            Some(AtModifier::End) => self.ctx.end,
+            Some(AtModifier::At(time)) => micros_since_epoch(*time),
+        };
+        match &selector.offset {
+            None => time,
+            Some(Offset::Pos(offset)) => time - micros(*offset),
+            Some(Offset::Neg(offset)) => time + micros(*offset),
+        }
+    }
+
     async fn aggregate_exprs(
         &mut self,
         op: &token::TokenType,
@@ -679,12 +750,23 @@ impl Engine {
         }
     }
     async fn call_expr(&mut self, func: &Function, args: &FunctionArgs) -> Result<Value> {
-        use crate::service::promql::functions::Func;
-
         let func_name = Func::from_str(func.name).map_err(|_| {
             DataFusionError::NotImplemented(format!("Unsupported function: {}", func.name))
         })?;
 
+        let parent_rollup_column =
+            std::mem::replace(&mut self.rollup_column, rollup_column(&func_name));
+        let ret = self.call_func(func, func_name, args).await;
+        self.rollup_column = parent_rollup_column;
+        ret
+    }
+
+    async fn call_func(
+        &mut self,
+        func: &Function,
+        func_name: Func,
+        args: &FunctionArgs,
+    ) -> Result<Value> {
         // There are a few functions which need no arguments for e.g. time()
         let functions_without_args: HashSet<&str> = HashSet::from_iter(vec![
             "day_of_month",
@@ -990,16 +1072,50 @@ impl Engine {
     }
 }
 
+/// The data of a selector is cached per metric and modifiers, as the same metric can be
+/// looked up at different times in one query, e.g. `m - m offset 1h`.
+fn selector_cache_key(selector: &VectorSelector, rollup: Option<(&str, i64)>) -> String {
+    let name = selector.name.as_ref().expect("Missing selector name");
+    let key = if selector.offset.is_none() && selector.at.is_none() {
+        name.to_string()
+    } else {
+        format!("{name}/{:?}/{:?}", selector.offset, selector.at)
+    };
+    match rollup {
+        // the samples of the rollup streams differ by column and resolution
+        Some((column, max_resolution)) => format!("{key}/{column}/{max_resolution}"),
+        None => key,
+    }
+}
+
+/// Returns the rollup column a function can read from the rollup streams instead of the raw
+/// samples, `None` when the result of the function needs the raw samples.
+fn rollup_column(func: &Func) -> Option<&'static str> {
+    match func {
+        // the counter functions only need the last sample of the buckets
+        Func::Rate | Func::Increase | Func::Irate | Func::Delta | Func::Idelta => {
+            Some(ROLLUP_LAST_LABEL)
+        }
+        Func::LastOverTime => Some(ROLLUP_LAST_LABEL),
+        Func::MinOverTime => Some(ROLLUP_MIN_LABEL),
+        Func::MaxOverTime => Some(ROLLUP_MAX_LABEL),
+        Func::SumOverTime => Some(ROLLUP_SUM_LABEL),
+        _ => None,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn selector_load_data_from_datafusion(
     ctx: SessionContext,
     schema: Arc<Schema>,
+    table_name: &str,
     selector: VectorSelector,
+    value_column: &str,
     start: i64,
     end: i64,
     label_selector: &Option<HashSet<String>>,
 ) -> Result<HashMap<String, RangeValue>> {
     let cfg = config::get_config();
-    let table_name = selector.name.as_ref().unwrap();
     let mut df_group = match ctx.table(table_name).await {
         Ok(v) => v.filter(
             col(&cfg.common.column_timestamp)
@@ -1052,7 +1168,7 @@ async fn selector_load_data_from_datafusion(
                 .collect::<HashSet<_>>();
             let mut def_labels = vec![
                 HASH_LABEL.to_string(),
-                VALUE_LABEL.to_string(),
+                value_column.to_string(),
                 BUCKET_LABEL.to_string(),
                 cfg.common.column_timestamp.to_string(),
             ];
@@ -1089,7 +1205,11 @@ async fn selector_load_data_from_datafusion(
         .iter()
         .filter_map(|field| {
             let name = field.name();
-            if name == &cfg.common.column_timestamp || name == VALUE_LABEL {
+            if name == &cfg.common.column_timestamp
+                || name == VALUE_LABEL
+                || name == EXEMPLARS_LABEL
+                || name.starts_with(ROLLUP_LABEL_PREFIX)
+            {
                 None
             } else {
                 Some(col(name))
@@ -1168,7 +1288,11 @@ async fn selector_load_data_from_datafusion(
     }
 
     let batches = df_group
-        .select_columns(&[&cfg.common.column_timestamp, HASH_LABEL, VALUE_LABEL])?
+        .select(vec![
+            col(&cfg.common.column_timestamp),
+            col(HASH_LABEL),
+            cast(col(value_column), DataType::Float64).alias(VALUE_LABEL),
+        ])?
         .sort(vec![col(&cfg.common.column_timestamp).sort(true, true)])?
         .collect()
         .await?;
@@ -1203,3 +1327,149 @@ async fn selector_load_data_from_datafusion(
     }
     Ok(metrics)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use async_trait::async_trait;
+    use config::meta::search::ScanStats;
+    use datafusion::{
+        arrow::{
+            array::ArrayRef,
+            datatypes::{DataType, Field},
+            record_batch::RecordBatch,
+        },
+        datasource::MemTable,
+    };
+    use promql_parser::parser::{self, EvalStmt};
+
+    use super::*;
+    use crate::service::promql::{exec::Query, TableProvider};
+
+    const BASE: i64 = 1_700_000_000_000_000;
+    const STEP: i64 = 15_000_000;
+    const HOUR: i64 = 3_600_000_000;
+
+    /// One counter series with a sample every 15s for 3 hours after `BASE`
+    struct MemoryProvider;
+
+    #[async_trait]
+    impl TableProvider for MemoryProvider {
+        async fn create_context(
+            &self,
+            _org_id: &str,
+            stream_name: &str,
+            _time_range: (i64, i64),
+            _filters: &mut [(String, Vec<String>)],
+        ) -> Result<Vec<(SessionContext, Arc<Schema>, ScanStats)>> {
+            let cfg = config::get_config();
+            let schema = Arc::new(Schema::new(vec![
+                Field::new(HASH_LABEL, DataType::Utf8, false),
+                Field::new(NAME_LABEL, DataType::Utf8, false),
+                Field::new(&cfg.common.column_timestamp, DataType::Int64, false),
+                Field::new(VALUE_LABEL, DataType::Float64, false),
+            ]));
+            let num = (3 * HOUR / STEP) as usize;
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(StringArray::from(vec!["1"; num])),
+                Arc::new(StringArray::from(vec![stream_name; num])),
+                Arc::new(Int64Array::from_iter_values(
+                    (0..num as i64).map(|i| BASE + i * STEP),
+                )),
+                // not linear, so that the rate differs over time
+                Arc::new(Float64Array::from_iter_values(
+                    (0..num).map(|i| (i * i) as f64),
+                )),
+            ];
+            let batch = RecordBatch::try_new(schema.clone(), columns)?;
+            let ctx = SessionContext::new();
+            let table = MemTable::try_new(schema.clone(), vec![vec![batch]])?;
+            ctx.register_table(stream_name, Arc::new(table))?;
+            Ok(vec![(ctx, schema, ScanStats::default())])
+        }
+    }
+
+    async fn eval(query: &str, start: i64, end: i64) -> Vec<(i64, f64)> {
+        let mut ctx = Query::new("default", MemoryProvider, 60);
+        let stmt = EvalStmt {
+            expr: parser::parse(query).unwrap(),
+            start: UNIX_EPOCH + Duration::from_micros(start as u64),
+            end: UNIX_EPOCH + Duration::from_micros(end as u64),
+            interval: Duration::from_secs(60),
+            lookback_delta: Duration::from_secs(300),
+        };
+        let (value, ..) = ctx.exec(stmt).await.unwrap();
+        match value {
+            Value::Vector(v) => v
+                .iter()
+                .map(|v| (v.sample.timestamp, v.sample.value))
+                .collect(),
+            Value::Matrix(v) => v
+                .iter()
+                .flat_map(|v| v.samples.iter().map(|s| (s.timestamp, s.value)))
+                .collect(),
+            v => panic!("unexpected value {:?}", v.get_type()),
+        }
+    }
+
+    fn values(samples: &[(i64, f64)]) -> Vec<f64> {
+        samples.iter().map(|(_, v)| *v).collect()
+    }
+
+    #[tokio::test]
+    async fn test_offset_modifier() {
+        let time = BASE + 2 * HOUR;
+        let shifted = eval("rate(http_requests[5m])", time - HOUR, time - HOUR).await;
+        let result = eval("rate(http_requests[5m] offset 1h)", time, time).await;
+        assert!(!result.is_empty());
+        assert_eq!(values(&result), values(&shifted));
+        assert_eq!(result[0].0, time);
+        assert_ne!(
+            values(&result),
+            values(&eval("rate(http_requests[5m])", time, time).await)
+        );
+
+        // the same metric with different offsets in one query
+        let result = eval("http_requests - http_requests offset 1h", time, time).await;
+        let now = eval("http_requests", time, time).await;
+        let before = eval("http_requests", time - HOUR, time - HOUR).await;
+        assert_eq!(values(&result), vec![now[0].1 - before[0].1]);
+    }
+
+    #[tokio::test]
+    async fn test_at_modifier() {
+        let (start, end) = (BASE + HOUR, BASE + 2 * HOUR);
+        let at_end = eval("rate(http_requests[5m] @ end())", start, end).await;
+        let expected = eval("rate(http_requests[5m])", end, end).await;
+        assert_eq!(at_end.len(), ((end - start) / 60_000_000 + 1) as usize);
+        assert!(at_end.iter().all(|(_, v)| *v == expected[0].1));
+
+        let at_start = eval("http_requests @ start()", start, end).await;
+        let expected = eval("http_requests", start, start).await;
+        assert!(at_start.iter().all(|(_, v)| *v == expected[0].1));
+
+        let query = format!("http_requests @ {} offset 30m", (BASE + HOUR) / 1_000_000);
+        let result = eval(&query, end, end).await;
+        let expected = eval("http_requests", BASE + HOUR / 2, BASE + HOUR / 2).await;
+        assert_eq!(values(&result), values(&expected));
+    }
+
+    #[test]
+    fn test_rollup_column() {
+        assert_eq!(rollup_column(&Func::Rate), Some(ROLLUP_LAST_LABEL));
+        assert_eq!(rollup_column(&Func::SumOverTime), Some(ROLLUP_SUM_LABEL));
+        assert_eq!(rollup_column(&Func::MaxOverTime), Some(ROLLUP_MAX_LABEL));
+        // the results of these functions depend on every raw sample
+        assert_eq!(rollup_column(&Func::CountOverTime), None);
+        assert_eq!(rollup_column(&Func::AvgOverTime), None);
+        assert_eq!(rollup_column(&Func::Changes), None);
+
+        let selector = VectorSelector::from("up");
+        assert_eq!(selector_cache_key(&selector, None), "up");
+        assert_ne!(
+            selector_cache_key(&selector, Some((ROLLUP_LAST_LABEL, 150_000_000))),
+            selector_cache_key(&selector, Some((ROLLUP_SUM_LABEL, 150_000_000)))
+        );
+    }
+}
@@ -30,7 +30,9 @@ use config::{
 use futures::future::try_join_all;
 use hashbrown::HashMap;
 use infra::errors::{Error, ErrorCodes, Result};
+use once_cell::sync::Lazy;
 use proto::cluster_rpc;
+use regex::Regex;
 use tonic::{
     codec::CompressionEncoding,
     metadata::{MetadataKey, MetadataValue},
@@ -51,6 +53,9 @@ use crate::{
 
 pub mod grpc;
 
+static AT_START_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"@\s*start\s*\(\s*\)").unwrap());
+static AT_END_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"@\s*end\s*\(\s*\)").unwrap());
+
 #[tracing::instrument(skip_all, fields(org_id = org_id))]
 pub async fn search(
     org_id: &str,
@@ -66,7 +71,7 @@ pub async fn search(
 
 #[tracing::instrument(name = "promql:search:cluster", skip_all, fields(org_id = req.org_id))]
 async fn search_in_cluster(
-    req: cluster_rpc::MetricsQueryRequest,
+    mut req: cluster_rpc::MetricsQueryRequest,
     user_email: &str,
 ) -> Result<Value> {
     let op_start = std::time::Instant::now();
@@ -95,6 +100,12 @@ async fn search_in_cluster(
         step,
     } = req.query.as_ref().unwrap();
 
+    // the queriers only get a part of the range, resolve the `@` modifiers to the whole range
+    let req_query = req.query.as_mut().unwrap();
+    req_query.query = resolve_at_modifiers(&req_query.query, start, end);
+    // with the `@` modifier every querier may need the latest data
+    let has_at_modifier = req_query.query.contains('@');
+
     // The number of resolution steps; see the diagram at
     // https://promlabs.com/blog/2020/06/18/the-anatomy-of-a-promql-query/#range-queries
     let step = max(micros(DEFAULT_LOOKBACK), step);
@@ -136,7 +147,7 @@ async fn search_in_cluster(
         let req_query = req.query.as_mut().unwrap();
         req_query.start = worker_start;
         req_query.end = min(end, worker_start + worker_dt);
-        if req_query.end == end {
+        if req_query.end == end || has_at_modifier {
             req.need_wal = true;
         }
         let req_need_wal = req.need_wal;
@@ -371,3 +382,36 @@ fn merge_scalar_query(series: &[cluster_rpc::Series]) -> Value {
     }
     Value::Sample(sample)
 }
+
+/// Replaces `@ start()` and `@ end()` with the start and end of the query range.
+fn resolve_at_modifiers(query: &str, start: i64, end: i64) -> String {
+    let to_secs = |t: i64| {
+        format!(
+            "@ {}.{:06}",
+            t.div_euclid(1_000_000),
+            t.rem_euclid(1_000_000)
+        )
+    };
+    let query = AT_START_RE.replace_all(query, to_secs(start).as_str());
+    AT_END_RE
+        .replace_all(&query, to_secs(end).as_str())
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_at_modifiers() {
+        assert_eq!(
+            resolve_at_modifiers(
+                "rate(m[5m] @ start()) / rate(m[5m] @end( ))",
+                1_700_000_000_000_000,
+                1_700_000_060_500_000
+            ),
+            "rate(m[5m] @ 1700000000.000000) / rate(m[5m] @ 1700000060.500000)"
+        );
+        assert_eq!(resolve_at_modifiers("m offset 1h", 0, 1), "m offset 1h");
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashSet, sync::Arc};
+
+use chrono::{TimeZone, Utc};
+use chrono_tz::Tz;
+use config::{
+    get_config,
+    meta::{
+        search,
+        sql::resolve_stream_names,
+        stream::{FileMeta, StreamType},
+    },
+    utils::{
+        json, parquet::write_recordbatch_to_parquet,
+        record_batch_ext::convert_json_to_record_batch, schema::infer_json_schema_from_values,
+    },
+};
+
+use crate::{
+    common::{
+        infra::config::USERS,
+        meta::saved_view::{ExportFormat, ExportRun, ScheduledExport},
+        utils::auth::is_root_user,
+    },
+    handler::http::auth::validator::check_stream_permission,
+    service::{alerts::scheduler::get_next_trigger_time, db, search as SearchService},
+};
+
+/// Validates the scheduled export and saves it as owned by the user, the next run is scheduled
+/// from its cron
+pub async fn save(
+    org_id: &str,
+    user_id: &str,
+    mut export: ScheduledExport,
+) -> Result<(), anyhow::Error> {
+    if export.name.trim().is_empty() {
+        return Err(anyhow::anyhow!("Scheduled export name is required"));
+    }
+    if export.name.contains('/') {
+        return Err(anyhow::anyhow!("Scheduled export name cannot contain '/'"));
+    }
+    if export.destination.trim_matches('/').is_empty() {
+        return Err(anyhow::anyhow!("Scheduled export destination is required"));
+    }
+    if export.period <= 0 {
+        return Err(anyhow::anyhow!(
+            "Scheduled export period should be positive"
+        ));
+    }
+    let next_run_at = next_run_at(&export)?;
+    let view = db::saved_view::get_view(org_id, &export.view_id)
+        .await
+        .map_err(|_| anyhow::anyhow!("Saved view not found: {}", export.view_id))?;
+    let (sql, stream_type) = view_query(&view.data)?;
+    check_permissions(org_id, user_id, &sql, stream_type).await?;
+    export.owner = user_id.to_string();
+    db::saved_view::set_export(org_id, &export, next_run_at).await
+}
+
+/// Returns the next time of the cron of the export in its timezone, in microseconds
+pub(crate) fn next_run_at(export: &ScheduledExport) -> Result<i64, anyhow::Error> {
+    let tz = export
+        .timezone
+        .parse::<Tz>()
+        .map_err(|_| anyhow::anyhow!("invalid timezone: {}", export.timezone))?;
+    get_next_trigger_time(&export.cron, tz)
+}
+
+/// Checks the user is a member of the organization who can read the streams of the query
+async fn check_permissions(
+    org_id: &str,
+    user_id: &str,
+    sql: &str,
+    stream_type: StreamType,
+) -> Result<(), anyhow::Error> {
+    if is_root_user(user_id) {
+        return Ok(());
+    }
+    let Some(user) = USERS.get(&format!("{org_id}/{user_id}")).map(|v| v.clone()) else {
+        return Err(anyhow::anyhow!(
+            "User {user_id} is not a member of the organization"
+        ));
+    };
+    if !user.is_external {
+        return Ok(());
+    }
+    for stream_name in resolve_stream_names(sql)? {
+        if !check_stream_permission(
+            org_id,
+            stream_type,
+            &stream_name,
+            user_id,
+            user.role.clone(),
+        )
+        .await
+        {
+            return Err(anyhow::anyhow!(
+                "User {user_id} can't access stream {stream_name}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Runs the saved view of the export over the period before `end_time` and writes the results
+/// to the object storage
+pub async fn run(org_id: &str, export: &ScheduledExport, end_time: i64) -> ExportRun {
+    let timestamp = Utc::now().timestamp_micros();
+    match write_results(org_id, export, end_time).await {
+        Ok((path, records)) => ExportRun {
+            timestamp,
+            success: true,
+            path,
+            records,
+            error: None,
+        },
+        Err(e) => ExportRun {
+            timestamp,
+            success: false,
+            error: Some(e.to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+async fn write_results(
+    org_id: &str,
+    export: &ScheduledExport,
+    end_time: i64,
+) -> Result<(String, usize), anyhow::Error> {
+    let view = db::saved_view::get_view(org_id, &export.view_id).await?;
+    let (sql, stream_type) = view_query(&view.data)?;
+
+    // the export runs as its owner, the search applies the field access of the owner
+    if export.owner.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Scheduled export has no owner, save it again to run it"
+        ));
+    }
+    check_permissions(org_id, &export.owner, &sql, stream_type).await?;
+
+    // bound the query by the max query range of the streams
+    let mut start_time = end_time - export.period * 60 * 1_000_000;
+    for stream_name in resolve_stream_names(&sql)? {
+        if let Some(settings) = infra::schema::get_settings(org_id, &stream_name, stream_type).await
+        {
+            if settings.max_query_range > 0 {
+                start_time = start_time.max(end_time - settings.max_query_range * 3600 * 1_000_000);
+            }
+        }
+    }
+
+    let req = search::Request {
+        query: search::Query {
+            sql,
+            from: 0,
+            size: get_config().limit.scheduled_export_max_records,
+            start_time,
+            end_time,
+            ..Default::default()
+        },
+        encoding: search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: Some(search::SearchEventType::Other),
+        index_type: "".to_string(),
+    };
+    let res =
+        SearchService::search("", org_id, stream_type, Some(export.owner.clone()), &req).await?;
+    if res.hits.is_empty() {
+        return Ok(("".to_string(), 0));
+    }
+
+    let data = match export.format {
+        ExportFormat::Csv => encode_csv(&res.hits)?,
+        ExportFormat::Json => encode_json(&res.hits)?,
+        ExportFormat::Parquet => encode_parquet(&res.hits, stream_type).await?,
+    };
+    let path = format!(
+        "{}/{}/{}/{}/{}.{}",
+        export.destination.trim_matches('/'),
+        org_id,
+        export.name,
+        Utc.timestamp_nanos(end_time * 1000).format("%Y/%m/%d"),
+        end_time,
+        export.format
+    );
+    infra::storage::put(&path, data.into()).await?;
+    Ok((path, res.hits.len()))
+}
+
+/// Returns the SQL and the stream type of a saved view, the view stores the state of the logs
+/// page, the query is the SQL in SQL mode or else the filter of the selected stream
+fn view_query(data: &json::Value) -> Result<(String, StreamType), anyhow::Error> {
+    let query = data
+        .pointer("/data/query")
+        .or_else(|| data.pointer("/data/editorValue"))
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .trim();
+    let stream_type = data
+        .pointer("/data/stream/streamType")
+        .and_then(|v| v.as_str())
+        .map(StreamType::from)
+        .unwrap_or(StreamType::Logs);
+    let sql_mode = data
+        .pointer("/meta/sqlMode")
+        .and_then(|v| v.as_bool())
+        .unwrap_or_default();
+    if sql_mode {
+        if query.is_empty() {
+            return Err(anyhow::anyhow!("Saved view has no query"));
+        }
+        return Ok((query.to_string(), stream_type));
+    }
+
+    let stream = match data.pointer("/data/stream/selectedStream") {
+        Some(json::Value::Array(v)) => v.first().and_then(|v| {
+            v.as_str()
+                .or_else(|| v.get("value").and_then(|v| v.as_str()))
+        }),
+        Some(v) => v.get("value").and_then(|v| v.as_str()),
+        None => None,
+    };
+    let Some(stream) = stream.filter(|v| !v.is_empty()) else {
+        return Err(anyhow::anyhow!("Saved view has no stream"));
+    };
+    let sql = if query.is_empty() {
+        format!("SELECT * FROM \"{stream}\"")
+    } else {
+        format!("SELECT * FROM \"{stream}\" WHERE {query}")
+    };
+    Ok((sql, stream_type))
+}
+
+/// Encodes the hits as CSV, the columns are the fields in the order they first appear
+fn encode_csv(hits: &[json::Value]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut columns = Vec::new();
+    let mut seen = HashSet::new();
+    for hit in hits.iter() {
+        if let json::Value::Object(hit) = hit {
+            for key in hit.keys() {
+                if seen.insert(key.as_str()) {
+                    columns.push(key.as_str());
+                }
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&columns)?;
+    for hit in hits.iter() {
+        let row = columns.iter().map(|col| match hit.get(col) {
+            None | Some(json::Value::Null) => "".to_string(),
+            Some(json::Value::String(v)) => v.clone(),
+            Some(v) => v.to_string(),
+        });
+        writer.write_record(row)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Encodes the hits as newline delimited JSON
+fn encode_json(hits: &[json::Value]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut buf = Vec::new();
+    for hit in hits.iter() {
+        buf.extend(json::to_vec(hit)?);
+        buf.push(b'\n');
+    }
+    Ok(buf)
+}
+
+async fn encode_parquet(
+    hits: &[json::Value],
+    stream_type: StreamType,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let schema = Arc::new(infer_json_schema_from_values(hits.iter(), stream_type)?);
+    let data = hits.iter().cloned().map(Arc::new).collect::<Vec<_>>();
+    let batch = convert_json_to_record_batch(&schema, &data)?;
+    let meta = FileMeta {
+        records: hits.len() as i64,
+        ..Default::default()
+    };
+    write_recordbatch_to_parquet(schema, &[batch], &[], &meta).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_query() {
+        let data = json::json!({
+            "meta": {"sqlMode": true},
+            "data": {"query": "SELECT level FROM \"app\"", "stream": {"streamType": "logs"}}
+        });
+        assert_eq!(
+            view_query(&data).unwrap(),
+            ("SELECT level FROM \"app\"".to_string(), StreamType::Logs)
+        );
+
+        let data = json::json!({
+            "meta": {"sqlMode": false},
+            "data": {
+                "query": "level = 'error'",
+                "stream": {"streamType": "traces", "selectedStream": ["default"]}
+            }
+        });
+        assert_eq!(
+            view_query(&data).unwrap(),
+            (
+                "SELECT * FROM \"default\" WHERE level = 'error'".to_string(),
+                StreamType::Traces
+            )
+        );
+
+        let data = json::json!({
+            "data": {"stream": {"selectedStream": {"label": "app", "value": "app"}}}
+        });
+        assert_eq!(
+            view_query(&data).unwrap(),
+            ("SELECT * FROM \"app\"".to_string(), StreamType::Logs)
+        );
+        assert!(view_query(&json::json!({"data": {"query": "level = 'error'"}})).is_err());
+    }
+
+    #[test]
+    fn test_next_run_at() {
+        let mut export = ScheduledExport {
+            name: "nightly".to_string(),
+            view_id: "view".to_string(),
+            cron: "0 0 2 * * *".to_string(),
+            timezone: "Europe/Berlin".to_string(),
+            period: 1440,
+            format: ExportFormat::Csv,
+            destination: "exports".to_string(),
+            enabled: true,
+            owner: "".to_string(),
+            last_triggered_at: None,
+        };
+        // 02:00 in Berlin, whether it's summer or winter time
+        let next = Utc
+            .timestamp_nanos(next_run_at(&export).unwrap() * 1000)
+            .with_timezone(&chrono_tz::Europe::Berlin);
+        assert_eq!(next.format("%H:%M").to_string(), "02:00");
+
+        export.timezone = "Mars/Olympus".to_string();
+        assert!(next_run_at(&export).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let hits = vec![
+            json::json!({"_timestamp": 1, "message": "a,b"}),
+            json::json!({"_timestamp": 2, "code": null, "level": "error"}),
+        ];
+        assert_eq!(
+            String::from_utf8(encode_csv(&hits).unwrap()).unwrap(),
+            "_timestamp,message,code,level\n1,\"a,b\",,\n2,,,error\n"
+        );
+        assert_eq!(
+            String::from_utf8(encode_json(&hits).unwrap()).unwrap(),
+            "{\"_timestamp\":1,\"message\":\"a,b\"}\n{\"_timestamp\":2,\"code\":null,\"level\":\"error\"}\n"
+        );
+    }
+}
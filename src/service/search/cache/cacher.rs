@@ -26,13 +26,19 @@ use infra::cache::{
 };
 
 use crate::{
-    common::meta::search::{CacheQueryRequest, CachedQueryResponse, QueryDelta},
-    service::search::{
-        cache::{
-            result_utils::{get_ts_value, round_down_to_nearest_minute},
-            MultiCachedQueryResponse,
+    common::meta::{
+        organization::OrganizationSetting,
+        search::{CacheQueryRequest, CachedQueryResponse, QueryDelta},
+    },
+    service::{
+        db::organization::get_org_setting,
+        search::{
+            cache::{
+                result_utils::{get_ts_value, round_down_to_nearest_minute},
+                MultiCachedQueryResponse,
+            },
+            sql::{generate_histogram_interval, Sql, RE_HISTOGRAM, RE_SELECT_FROM},
         },
-        sql::{generate_histogram_interval, Sql, RE_HISTOGRAM, RE_SELECT_FROM},
     },
 };
 
@@ -175,6 +181,17 @@ pub async fn check_cache(
 
         let deltas = if total_hits == (sql.limit as usize) {
             *should_exec_query = false;
+            // the limit is reached with the cached hits, the hits are the first ones of the
+            // query only if the cache covers the edge of the range the query reads from
+            multi_resp.is_partial_cache = if is_descending {
+                cached_responses
+                    .iter()
+                    .all(|v| v.response_end_time < req.query.end_time)
+            } else {
+                cached_responses
+                    .iter()
+                    .all(|v| v.response_start_time > req.query.start_time)
+            };
             vec![]
         } else {
             let (deltas, updated_start_time, cache_duration) = calculate_deltas_multi(
@@ -241,6 +258,7 @@ pub async fn check_cache(
                         end_time: cached_resp.response_end_time,
                         is_aggregate,
                         is_descending,
+                        created_at: 0,
                     }),
                     req.query.start_time,
                     req.query.end_time,
@@ -295,10 +313,8 @@ pub async fn get_cached_results(
     trace_id: &str,
     cache_req: CacheQueryRequest,
 ) -> Option<CachedQueryResponse> {
-    let r = QUERY_RESULT_CACHE.read().await;
     let query_key = file_path.replace('/', "_");
-    let is_cached = r.get(&query_key).cloned();
-    drop(r);
+    let is_cached = get_cache_metas(trace_id, &query_key, file_path).await;
 
     if let Some(cache_metas) = is_cached {
         match
@@ -452,6 +468,71 @@ pub async fn get_cached_results(
     }
 }
 
+/// The seconds a cached result of the org is used for, 0 keeps it until it's evicted
+async fn get_result_cache_ttl(org_id: &str) -> i64 {
+    get_org_setting(org_id)
+        .await
+        .ok()
+        .and_then(|v| json::from_slice::<OrganizationSetting>(&v).ok())
+        .and_then(|v| v.result_cache_ttl)
+        .unwrap_or(get_config().common.result_cache_ttl)
+}
+
+fn is_expired(meta: &ResultCacheMeta, ttl: i64, now: i64) -> bool {
+    ttl > 0 && meta.created_at + ttl * 1_000_000 < now
+}
+
+fn cache_file_name(meta: &ResultCacheMeta) -> String {
+    format!(
+        "{}_{}_{}_{}.json",
+        meta.start_time,
+        meta.end_time,
+        if meta.is_aggregate { 1 } else { 0 },
+        if meta.is_descending { 1 } else { 0 }
+    )
+}
+
+/// Returns the cached results of the query, the ones older than the result cache TTL of the org
+/// are removed, so the query is cached again with fresh data
+pub async fn get_cache_metas(
+    trace_id: &str,
+    query_key: &str,
+    file_path: &str,
+) -> Option<Vec<ResultCacheMeta>> {
+    let cache_metas = QUERY_RESULT_CACHE.read().await.get(query_key).cloned()?;
+    let org_id = file_path.split('/').next().unwrap_or_default();
+    let ttl = get_result_cache_ttl(org_id).await;
+    let now = Utc::now().timestamp_micros();
+    let (cache_metas, expired): (Vec<_>, Vec<_>) = cache_metas
+        .into_iter()
+        .partition(|meta| !is_expired(meta, ttl, now));
+    if !expired.is_empty() {
+        let mut w = QUERY_RESULT_CACHE.write().await;
+        if let Some(metas) = w.get_mut(query_key) {
+            metas.retain(|meta| !expired.contains(meta));
+            if metas.is_empty() {
+                w.remove(query_key);
+            }
+        }
+        drop(w);
+        for meta in expired.iter() {
+            let file = format!("results/{}/{}", file_path, cache_file_name(meta));
+            if let Err(e) = disk::remove(trace_id, &file).await {
+                log::error!("[trace_id {trace_id}] Error removing expired cache {file}: {e}");
+            }
+        }
+        log::info!(
+            "[trace_id {trace_id}] Removed {} expired caches for query key: {query_key}",
+            expired.len()
+        );
+    }
+    if cache_metas.is_empty() {
+        None
+    } else {
+        Some(cache_metas)
+    }
+}
+
 pub fn calculate_deltas_v1(
     result_meta: &ResultCacheMeta,
     start_time: i64,
@@ -696,3 +777,56 @@ fn calculate_deltas_multi(
 
     (deltas, None, cache_duration)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_meta(created_at: i64) -> ResultCacheMeta {
+        ResultCacheMeta {
+            start_time: 0,
+            end_time: 10,
+            is_aggregate: false,
+            is_descending: true,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let now = 100_000_000;
+        assert!(!is_expired(&cache_meta(0), 0, now));
+        assert!(is_expired(&cache_meta(0), 60, now));
+        assert!(!is_expired(&cache_meta(50_000_000), 60, now));
+    }
+
+    #[tokio::test]
+    async fn test_delete_cache_only_removes_target() {
+        let root_dir = disk::get_dir().await;
+        let paths = [
+            "cache_del_org1/logs/app/111",
+            "cache_del_org1/logs/web/222",
+            "cache_del_org2/logs/app/333",
+        ];
+        for path in paths {
+            let dir = format!("{}/results/{}", root_dir, path);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(format!("{}/{}", dir, cache_file_name(&cache_meta(0))), "{}").unwrap();
+            QUERY_RESULT_CACHE
+                .write()
+                .await
+                .insert(path.replace('/', "_"), vec![cache_meta(0)]);
+        }
+
+        assert!(delete_cache("cache_del_org1/logs/app").await.unwrap());
+        let r = QUERY_RESULT_CACHE.read().await;
+        assert!(!r.contains_key("cache_del_org1_logs_app_111"));
+        assert!(r.contains_key("cache_del_org1_logs_web_222"));
+        assert!(r.contains_key("cache_del_org2_logs_app_333"));
+        drop(r);
+
+        for org in ["cache_del_org1", "cache_del_org2"] {
+            std::fs::remove_dir_all(format!("{}/results/{}", root_dir, org)).ok();
+        }
+    }
+}
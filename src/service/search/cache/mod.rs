@@ -17,7 +17,7 @@ use chrono::Utc;
 use config::{
     get_config,
     meta::{
-        search::{self, ResponseTook},
+        search::{self, ResponseTook, ResponseWarning, ResponseWarningCode},
         sql::resolve_stream_names,
         stream::StreamType,
         usage::{RequestStats, UsageType},
@@ -59,6 +59,19 @@ pub async fn search(
     let start = std::time::Instant::now();
     let started_at = Utc::now().timestamp_micros();
     let cfg = get_config();
+    // the wal only result misses the persisted data, it can't be merged with cached results
+    let use_cache = use_cache && !in_req.query.wal_only;
+
+    // the cached results are shared by the users, the denied fields are stripped from the
+    // response instead of the cached results
+    let denied_fields = SearchService::field_access::check_request(
+        org_id,
+        user_id.as_deref(),
+        stream_type,
+        &in_req.query.sql,
+        in_req.query.query_fn.as_deref(),
+    )
+    .await?;
 
     // Result caching check start
     let mut origin_sql = in_req.query.sql.clone();
@@ -90,6 +103,9 @@ pub async fn search(
     if !req.clusters.is_empty() {
         hash_body.extend(req.clusters.clone());
     }
+    if !req.query.fields.is_empty() {
+        hash_body.push(format!("fields:{}", req.query.fields.join(",")));
+    }
     let mut h = config::utils::hash::gxhash::new();
     let hashed_query = h.sum64(&hash_body.join(","));
 
@@ -105,7 +121,7 @@ pub async fn search(
         org_id, stream_type, stream_name, hashed_query
     );
     let mut c_resp: MultiCachedQueryResponse = if use_cache {
-        check_cache(
+        let c_resp = check_cache(
             trace_id,
             &rpc_req,
             &mut req,
@@ -114,7 +130,19 @@ pub async fn search(
             is_aggregate,
             &mut should_exec_query,
         )
-        .await
+        .await;
+        // only the queries which can be cached are looked up
+        if c_resp.cache_query_response {
+            let result = if c_resp.has_cached_data {
+                "hit"
+            } else {
+                "miss"
+            };
+            metrics::QUERY_RESULT_CACHE_REQUESTS
+                .with_label_values(&[org_id, result])
+                .inc();
+        }
+        c_resp
     } else {
         let query = rpc_req.clone().query.unwrap();
         match crate::service::search::Sql::new(&query, org_id, stream_type).await {
@@ -241,7 +269,8 @@ pub async fn search(
                         );
                     }
 
-                    SearchService::search(&trace_id, &org_id, stream_type, user_id, &req).await
+                    SearchService::search_unchecked(&trace_id, &org_id, stream_type, user_id, &req)
+                        .await
                 })
                 .instrument(enter_span),
             );
@@ -271,6 +300,22 @@ pub async fn search(
         }
     };
 
+    // keep the warnings of the delta searches, the merge only keeps the hits
+    for r in results.iter() {
+        if r.is_partial {
+            res.is_partial = true;
+        }
+        for warning in r.warnings.iter() {
+            res.add_warning(warning.clone());
+        }
+    }
+    if !should_exec_query && c_resp.is_partial_cache {
+        res.add_warning(ResponseWarning::new(
+            ResponseWarningCode::CacheOnly,
+            "The response is served from the result cache only, which doesn't cover the whole time range",
+        ));
+    }
+
     // do search
     let time = start.elapsed().as_secs_f64();
     http_report_metrics(start, org_id, stream_type, "", "200", "_search");
@@ -342,6 +387,7 @@ pub async fn search(
     }
     // result cache save changes Ends
 
+    SearchService::field_access::strip_response(&mut res, &denied_fields);
     Ok(res)
 }
 
@@ -440,6 +486,7 @@ fn merge_response(
             res_took.wait_queue += took_details.wait_queue;
             res_took.total += took_details.total;
             res_took.nodes.append(&mut took_details.nodes);
+            res_took.regions.append(&mut took_details.regions);
         }
         if !res.function_error.is_empty() {
             fn_error = res.function_error.clone();
@@ -576,6 +623,7 @@ async fn write_results(
                         end_time: cache_end_time,
                         is_aggregate,
                         is_descending,
+                        created_at: Utc::now().timestamp_micros(),
                     });
                 drop(w);
             }
@@ -18,9 +18,9 @@ use std::str::FromStr;
 use async_recursion::async_recursion;
 use chrono::Utc;
 use config::{get_config, meta::search::Response, utils::json};
-use infra::cache::{file_data::disk::QUERY_RESULT_CACHE, meta::ResultCacheMeta};
+use infra::cache::meta::ResultCacheMeta;
 
-use super::cacher::get_results;
+use super::cacher::{get_cache_metas, get_results};
 use crate::{
     common::meta::search::{CacheQueryRequest, ResultCacheSelectionStrategy},
     service::search::cache::{
@@ -35,10 +35,8 @@ pub async fn get_cached_results(
     cache_req: CacheQueryRequest,
 ) -> Vec<CachedQueryResponse> {
     let mut res: Vec<_> = vec![];
-    let r = QUERY_RESULT_CACHE.read().await;
     let query_key = file_path.replace('/', "_");
-    let is_cached = r.get(&query_key).cloned();
-    drop(r);
+    let is_cached = get_cache_metas(trace_id, &query_key, file_path).await;
     if is_cached.is_none() {
         log::info!(
             "[CACHE RESULT {trace_id}] No cache found for query key: {}",
@@ -17,12 +17,13 @@ use std::{str::FromStr, sync::Arc};
 
 use arrow::array::RecordBatch;
 use async_recursion::async_recursion;
+use chrono::{Duration, Utc};
 use config::{
     get_config,
     meta::{
         bitvec::BitVec,
         cluster::{IntoArcVec, Node, Role, RoleGroup},
-        search::{ScanStats, SearchEventType},
+        search::{ResponseWarning, ResponseWarningCode, ScanStats, SearchEventType},
         stream::{FileKey, QueryPartitionStrategy, StreamType},
     },
     metrics,
@@ -51,8 +52,10 @@ use crate::{
         datafusion::{
             distributed_plan::{remote_scan::RemoteScanExec, rewrite::RemoteScanRewriter},
             exec::{prepare_datafusion_context, register_udf},
+            group_limit::add_group_limit,
             optimizer::generate_optimizer_rules,
             table_provider::empty_table::NewEmptyTable,
+            udf::json_get_path_udf::register_json_get_path,
         },
         generate_filter_from_equal_items,
         request::Request,
@@ -73,7 +76,15 @@ pub async fn search(
     sql: Arc<Sql>,
     mut req: Request,
     query: SearchQuery,
-) -> Result<(Vec<RecordBatch>, ScanStats, usize, bool, usize, String)> {
+) -> Result<(
+    Vec<RecordBatch>,
+    ScanStats,
+    usize,
+    bool,
+    usize,
+    Vec<ResponseWarning>,
+    bool,
+)> {
     let start = std::time::Instant::now();
     let cfg = get_config();
     log::info!("[trace_id {trace_id}] flight->search: start {}", sql);
@@ -90,17 +101,24 @@ pub async fn search(
         .iter()
         .any(|(_, schema)| schema.schema().fields().is_empty())
     {
-        return Ok((vec![], ScanStats::new(), 0, false, 0, "".to_string()));
+        return Ok((vec![], ScanStats::new(), 0, false, 0, vec![], 0));
     }
 
-    // 1. get file id list
-    let file_id_list = get_file_id_lists(
-        &sql.org_id,
-        sql.stream_type,
-        &sql.stream_names,
-        sql.time_range,
-    )
-    .await?;
+    // 1. get file id list, a wal_only search doesn't read the persisted files
+    let file_id_list = if query.wal_only {
+        sql.stream_names
+            .iter()
+            .map(|name| (name.clone(), Vec::new()))
+            .collect::<HashMap<_, _>>()
+    } else {
+        get_file_id_lists(
+            &sql.org_id,
+            sql.stream_type,
+            &sql.stream_names,
+            sql.time_range,
+        )
+        .await?
+    };
     let file_id_list_vec = file_id_list.values().flatten().collect::<Vec<_>>();
     let file_id_list_took = start.elapsed().as_millis() as usize;
     log::info!(
@@ -117,7 +135,7 @@ pub async fn search(
 
     // 2. get inverted index file list
     let (use_fst_inverted_index, idx_file_list, idx_scan_size, idx_took) =
-        get_inverted_index_file_lists(trace_id, &req, &sql, &query).await?;
+        get_inverted_index_file_lists(trace_id, &mut req, &sql, &query).await?;
     scan_stats.idx_scan_size = idx_scan_size as i64;
     req.set_use_inverted_index(use_fst_inverted_index);
 
@@ -127,12 +145,30 @@ pub async fn search(
         .as_ref()
         .map(|v| SearchEventType::from_str(v).ok().map(RoleGroup::from))
         .unwrap_or(None);
-    let nodes = get_online_querier_nodes(trace_id, node_group).await?;
+    let mut nodes = get_online_querier_nodes(trace_id, node_group).await?;
     let querier_num = nodes.iter().filter(|node| node.is_querier()).count();
     if querier_num == 0 {
         log::error!("no querier node online");
         return Err(Error::Message("no querier node online".to_string()));
     }
+    if query.wal_only {
+        // only the ingesters hold the data which isn't persisted yet
+        nodes.retain(|node| node.is_ingester());
+        if nodes.is_empty() {
+            log::error!("[trace_id {trace_id}] flight->search: no ingester node online");
+            return Err(Error::Message(
+                "no ingester node online for the wal_only search".to_string(),
+            ));
+        }
+    }
+    let mut warnings = Vec::new();
+    if !nodes.iter().any(|node| node.is_ingester()) && may_have_wal_data(&sql) {
+        log::warn!("[trace_id {trace_id}] flight->search: no ingester node online, skip WAL");
+        warnings.push(ResponseWarning::new(
+            ResponseWarningCode::WalSkipped,
+            "No ingester is online, the latest data which is not persisted yet is missing",
+        ));
+    }
 
     // waiting in work group queue
     metrics::QUERY_PENDING_NUMS
@@ -142,8 +178,15 @@ pub async fn search(
     // 4. check work group
     let file_list_took = start.elapsed().as_millis() as usize;
     #[cfg(not(feature = "enterprise"))]
-    let (took_wait, work_group_str, locker) =
-        check_work_group(&req, trace_id, &nodes, start, file_list_took).await?;
+    let (took_wait, work_group_str, locker, _work_group_permit) = check_work_group(
+        &req,
+        trace_id,
+        &nodes,
+        scan_stats.original_size,
+        start,
+        file_list_took,
+    )
+    .await?;
     #[cfg(feature = "enterprise")]
     let (took_wait, work_group_str, work_group) = check_work_group(
         &req,
@@ -204,7 +247,14 @@ pub async fn search(
     });
 
     // 5. partition file list
-    let partitioned_file_lists = partition_file_lists(file_id_list, &nodes, node_group).await?;
+    let partitioned_file_lists = if query.wal_only {
+        file_id_list
+            .into_keys()
+            .map(|name| (name, vec![Vec::new(); nodes.len()]))
+            .collect()
+    } else {
+        partition_file_lists(file_id_list, &nodes, node_group).await?
+    };
 
     #[cfg(feature = "enterprise")]
     super::super::SEARCH_SERVER
@@ -289,7 +339,13 @@ pub async fn search(
     drop(_defer);
 
     // 9. get data from datafusion
-    let (data, mut scan_stats, partial_err): (Vec<RecordBatch>, ScanStats, String) = match task {
+    let (data, mut scan_stats, partial_err, spill_count, groups_truncated): (
+        Vec<RecordBatch>,
+        ScanStats,
+        String,
+        usize,
+        bool,
+    ) = match task {
         Ok(Ok(data)) => Ok(data),
         Ok(Err(err)) => Err(err),
         Err(err) => Err(Error::Message(err.to_string())),
@@ -297,17 +353,57 @@ pub async fn search(
 
     log::info!("[trace_id {trace_id}] flight->search: search finished");
 
+    if !partial_err.is_empty() {
+        warnings.push(ResponseWarning::new(
+            ResponseWarningCode::NodeFailed,
+            partial_err,
+        ));
+    }
+    if groups_truncated {
+        log::warn!("[trace_id {trace_id}] flight->search: groups truncated");
+        warnings.push(ResponseWarning::new(
+            ResponseWarningCode::GroupsTruncated,
+            "The query has more groups than the limit, the groups over it are omitted from the results",
+        ));
+    }
+    // spilling is slower but the data is complete
+    let is_partial = !warnings.is_empty();
+    if spill_count > 0 {
+        log::warn!("[trace_id {trace_id}] flight->search: spilled to disk {spill_count} times");
+        warnings.push(spill_warning());
+    }
+
     scan_stats.format_to_mb();
     Ok((
         data,
         scan_stats,
         took_wait,
-        !partial_err.is_empty(),
+        is_partial,
         idx_took,
-        partial_err,
+        warnings,
+        groups_truncated,
     ))
 }
 
+pub fn spill_warning() -> ResponseWarning {
+    ResponseWarning::new(
+        ResponseWarningCode::Spilled,
+        "The query exceeded its memory limit and spilled to disk, it may be slower",
+    )
+}
+
+// the data ingested within max_file_retention_time may still be in the WAL
+fn may_have_wal_data(sql: &Sql) -> bool {
+    let Some((_, end_time)) = sql.time_range else {
+        return true;
+    };
+    let retention = Duration::try_seconds(get_config().limit.max_file_retention_time as i64)
+        .unwrap()
+        .num_microseconds()
+        .unwrap();
+    end_time == 0 || end_time >= Utc::now().timestamp_micros() - retention
+}
+
 #[tracing::instrument(name = "service:search:cluster:flight:run_datafusion", skip_all)]
 pub async fn run_datafusion(
     trace_id: String,
@@ -316,7 +412,7 @@ pub async fn run_datafusion(
     nodes: Vec<Node>,
     partitioned_file_lists: HashMap<String, Vec<Vec<i64>>>,
     idx_file_list: Vec<FileKey>,
-) -> Result<(Vec<RecordBatch>, ScanStats, String)> {
+) -> Result<(Vec<RecordBatch>, ScanStats, String, usize, bool)> {
     let cfg = get_config();
     let ctx = generate_context(&req, &sql, cfg.limit.cpu_num).await?;
 
@@ -391,6 +487,18 @@ pub async fn run_datafusion(
         ));
     }
 
+    // the groups are counted on the input of the aggregations of this node
+    if !sql.group_by.is_empty() {
+        let max_groups = super::super::get_query_max_groups(&sql.org_id).await;
+        if max_groups > 0 {
+            physical_plan = add_group_limit(
+                physical_plan,
+                max_groups as usize,
+                cfg.limit.query_max_groups_policy == "truncate",
+            )?;
+        }
+    }
+
     if cfg.common.print_key_sql {
         print_plan(&physical_plan, "after");
     }
@@ -404,8 +512,16 @@ pub async fn run_datafusion(
         Err(e.into())
     } else {
         log::info!("[trace_id {trace_id}] flight->search: datafusion collect done");
-        ret.map(|data| (data, visit.scan_stats, visit.partial_err))
-            .map_err(|e| e.into())
+        ret.map(|data| {
+            (
+                data,
+                visit.scan_stats,
+                visit.partial_err,
+                visit.spill_count,
+                visit.groups_truncated,
+            )
+        })
+        .map_err(|e| e.into())
     }
 }
 
@@ -441,13 +557,31 @@ pub async fn check_work_group(
     req: &Request,
     trace_id: &str,
     nodes: &[Node],
+    scan_size: i64,
     start: std::time::Instant,
     file_list_took: usize, // the time took to get file list
-) -> Result<(usize, String, Option<infra::dist_lock::Locker>)> {
+) -> Result<(
+    usize,
+    String,
+    Option<infra::dist_lock::Locker>,
+    super::super::work_group::WorkGroupPermit,
+)> {
     let cfg = get_config();
-    let work_group_str = "global".to_string();
 
-    let locker_key = format!("/search/cluster_queue/{}", work_group_str);
+    // wait for a slot of the work group on this node, before the cluster queue so a full group
+    // doesn't hold the cluster lock
+    let work_group = super::super::work_group::WorkGroup::from_scan_size(scan_size);
+    let permit = super::super::work_group::acquire(trace_id, work_group)
+        .await
+        .map_err(|e| {
+            metrics::QUERY_PENDING_NUMS
+                .with_label_values(&[&req.org_id])
+                .dec();
+            e
+        })?;
+    let work_group_str = work_group.to_string();
+
+    let locker_key = "/search/cluster_queue/global";
     let locker = if cfg.common.local_mode || !cfg.common.feature_query_queue_enabled {
         None
     } else {
@@ -455,7 +589,7 @@ pub async fn check_work_group(
             .iter()
             .map(|node| node.uuid.to_string())
             .collect::<HashSet<_>>();
-        dist_lock::lock_with_trace_id(trace_id, &locker_key, req.timeout as u64, Some(node_ids))
+        dist_lock::lock_with_trace_id(trace_id, locker_key, req.timeout as u64, Some(node_ids))
             .await
             .map_err(|e| {
                 metrics::QUERY_PENDING_NUMS
@@ -468,10 +602,10 @@ pub async fn check_work_group(
     // done in the queue
     let took_wait = start.elapsed().as_millis() as usize - file_list_took;
     log::info!(
-        "[trace_id {trace_id}] search: wait in queue took: {} ms",
+        "[trace_id {trace_id}] search: wait in {work_group_str} queue took: {} ms",
         took_wait,
     );
-    Ok((took_wait, work_group_str, locker))
+    Ok((took_wait, work_group_str, locker, permit))
 }
 
 #[cfg(feature = "enterprise")]
@@ -717,6 +851,7 @@ pub async fn generate_context(
     // register udf
     register_udf(&ctx, &req.org_id)?;
     datafusion_functions_json::register_all(&mut ctx)?;
+    register_json_get_path(&ctx);
 
     Ok(ctx)
 }
@@ -761,7 +896,7 @@ pub async fn get_file_id_lists(
 )]
 async fn get_inverted_index_file_lists(
     trace_id: &str,
-    req: &Request,
+    req: &mut Request,
     sql: &Arc<Sql>,
     query: &SearchQuery,
 ) -> Result<(bool, Vec<FileKey>, usize, usize)> {
@@ -773,17 +908,24 @@ async fn get_inverted_index_file_lists(
     } else {
         req.inverted_index_type.as_ref().unwrap().to_string()
     };
-    let (use_inverted_index, index_terms) = super::super::is_use_inverted_index(sql);
-    let use_parquet_inverted_index =
-        use_inverted_index && (inverted_index_type == "parquet" || inverted_index_type == "both");
+    let (use_inverted_index, index_terms, index_prefix_terms) =
+        super::super::is_use_inverted_index(sql);
+    // the parquet format index doesn't support the prefix terms
+    let use_parquet_inverted_index = use_inverted_index
+        && (sql.match_items.is_some() || !index_terms.is_empty())
+        && (inverted_index_type == "parquet" || inverted_index_type == "both");
     let use_fst_inverted_index =
         use_inverted_index && (inverted_index_type == "fst" || inverted_index_type == "both");
+    if use_fst_inverted_index {
+        req.set_index_prefix_terms(index_prefix_terms);
+    }
     log::info!(
         "[trace_id {trace_id}] flight->search: use_inverted_index with parquet format {}",
         use_parquet_inverted_index
     );
 
-    if !use_parquet_inverted_index {
+    // the parquet inverted index only lists the persisted files
+    if !use_parquet_inverted_index || query.wal_only {
         return Ok((use_fst_inverted_index, vec![], 0, 0));
     }
 
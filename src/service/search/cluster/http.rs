@@ -62,6 +62,8 @@ pub async fn search(
 
     // handle query function
     #[cfg(feature = "enterprise")]
+    let mut regions_took = Vec::new();
+    #[cfg(feature = "enterprise")]
     let ret = if o2_enterprise::enterprise::common::infra::config::get_config()
         .super_cluster
         .enabled
@@ -75,19 +77,26 @@ pub async fn search(
             _req_clusters,
         )
         .await
+        .map(
+            |(data, stats, wait, is_partial, idx_took, warnings, regions)| {
+                regions_took = regions;
+                (data, stats, wait, is_partial, idx_took, warnings, false)
+            },
+        )
     } else {
         flight::search(&trace_id, sql.clone(), req, query).await
     };
     #[cfg(not(feature = "enterprise"))]
     let ret = flight::search(&trace_id, sql.clone(), req, query).await;
 
-    let (merge_batches, scan_stats, took_wait, is_partial, idx_took, partial_err) = match ret {
-        Ok(v) => v,
-        Err(e) => {
-            log::error!("[trace_id {trace_id}] http->search: err: {:?}", e);
-            return Err(e);
-        }
-    };
+    let (merge_batches, scan_stats, took_wait, is_partial, idx_took, warnings, groups_truncated) =
+        match ret {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("[trace_id {trace_id}] http->search: err: {:?}", e);
+                return Err(e);
+            }
+        };
 
     // final result
     let mut result = search::Response::new(sql.offset, sql.limit);
@@ -215,8 +224,21 @@ pub async fn search(
 
     result.set_total(total);
     result.set_histogram_interval(sql.histogram_interval);
+    let partial_err = warnings
+        .iter()
+        .map(|w| w.message.as_str())
+        .collect::<Vec<_>>()
+        .join(" \n ");
     result.set_partial(is_partial, partial_err);
+    for warning in warnings {
+        result.add_warning(warning);
+    }
+    if groups_truncated {
+        result.groups_truncated = Some(true);
+    }
     result.set_cluster_took(start.elapsed().as_millis() as usize, took_wait);
+    #[cfg(feature = "enterprise")]
+    result.set_regions_took(regions_took);
     result.set_file_count(scan_stats.files as usize);
     result.set_scan_size(scan_stats.original_size as usize);
     result.set_scan_records(scan_stats.records as usize);
@@ -0,0 +1,108 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The cost of a query estimated before it runs, from the metadata of the files in the
+//! file_list, so a query scanning more than the budget of the org can be rejected.
+
+use config::{
+    get_config,
+    meta::{search::CostEstimate, sql::resolve_stream_names, stream::StreamType},
+    utils::json,
+};
+use infra::{errors::Error, file_list::FileId};
+
+use crate::{
+    common::meta::organization::OrganizationSetting,
+    service::{db, file_list},
+};
+
+/// Returns the files, records and size the query is estimated to scan in the time range
+pub async fn estimate(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: &str,
+    time_range: (i64, i64),
+) -> Result<CostEstimate, Error> {
+    let stream_names = resolve_stream_names(sql).map_err(|e| Error::Message(e.to_string()))?;
+    let mut estimate = CostEstimate::default();
+    for stream_name in stream_names.iter() {
+        let files =
+            file_list::query_ids(org_id, stream_type, stream_name, Some(time_range)).await?;
+        add_files(&mut estimate, &files);
+    }
+    Ok(estimate)
+}
+
+/// Returns the max size in MB a query of the org can scan, 0 means no limit
+pub async fn get_max_scan_size(org_id: &str) -> i64 {
+    db::organization::get_org_setting(org_id)
+        .await
+        .ok()
+        .and_then(|v| json::from_slice::<OrganizationSetting>(&v).ok())
+        .and_then(|v| v.query_max_scan_size)
+        .unwrap_or(get_config().limit.query_max_scan_size)
+}
+
+fn add_files(estimate: &mut CostEstimate, files: &[FileId]) {
+    for file in files.iter() {
+        estimate.file_num += 1;
+        estimate.records += file.records;
+        estimate.original_size += file.original_size;
+    }
+}
+
+/// Checks the estimate against the max scan size, `max_scan_size` is in MB, 0 means no limit
+pub fn check_scan_size(estimate: &CostEstimate, max_scan_size: i64) -> Result<(), Error> {
+    if max_scan_size <= 0 || estimate.original_size <= max_scan_size * 1024 * 1024 {
+        return Ok(());
+    }
+    Err(Error::Message(format!(
+        "Query is estimated to scan {} MB in {} files, more than the limit of {max_scan_size} MB per query, set confirm=true to run it anyway",
+        estimate.original_size / 1024 / 1024,
+        estimate.file_num,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_scan_size() {
+        let files = (0..4)
+            .map(|i| FileId {
+                id: i,
+                records: 1000,
+                original_size: 256 * 1024 * 1024,
+                min_ts: 0,
+                max_ts: 0,
+            })
+            .collect::<Vec<_>>();
+        let mut estimate = CostEstimate::default();
+        add_files(&mut estimate, &files);
+        assert_eq!(
+            estimate,
+            CostEstimate {
+                file_num: 4,
+                records: 4000,
+                original_size: 1024 * 1024 * 1024,
+            }
+        );
+
+        assert!(check_scan_size(&estimate, 0).is_ok());
+        assert!(check_scan_size(&estimate, 1024).is_ok());
+        assert!(check_scan_size(&estimate, 1023).is_err());
+    }
+}
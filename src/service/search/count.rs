@@ -0,0 +1,144 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The record count of a stream summed from the `records` of the files in the file_list, so the
+//! parquet files aren't read. A file overlapping the time range is counted whole.
+
+use chrono::{Duration, DurationRound, Utc};
+use config::{
+    get_config,
+    meta::{
+        search::{self, CountResponse},
+        stream::{FileKey, PartitionTimeLevel, StreamType},
+    },
+};
+use infra::errors::Error;
+
+use crate::service::file_list;
+
+/// Returns the record count of the stream in the time range. With `include_wal` the data which
+/// isn't persisted yet is counted too, the hot window where the ingesters can still hold data is
+/// counted by a `count(*)` search instead of the file_list.
+pub async fn count(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    user_id: Option<String>,
+    time_range: (i64, i64),
+    include_wal: bool,
+) -> Result<CountResponse, Error> {
+    let start = std::time::Instant::now();
+    let (start_time, end_time) = time_range;
+    let hot_start = if include_wal {
+        hot_window_start().min(end_time)
+    } else {
+        end_time
+    };
+
+    let files = file_list::query(
+        org_id,
+        stream_name,
+        stream_type,
+        PartitionTimeLevel::Unset,
+        start_time,
+        end_time,
+    )
+    .await?;
+    let (mut total, file_count) = sum_records(&files, hot_start, include_wal);
+
+    if include_wal && end_time > hot_start {
+        let req = search::Request {
+            query: search::Query {
+                sql: format!("SELECT count(*) AS zo_sql_num FROM \"{stream_name}\""),
+                from: 0,
+                size: 1,
+                start_time: start_time.max(hot_start),
+                end_time,
+                ..Default::default()
+            },
+            encoding: search::RequestEncoding::Empty,
+            regions: vec![],
+            clusters: vec![],
+            timeout: 0,
+            search_type: Some(search::SearchEventType::Other),
+            index_type: "".to_string(),
+        };
+        let res = super::search(trace_id, org_id, stream_type, user_id, &req).await?;
+        total += res
+            .hits
+            .first()
+            .and_then(|hit| hit.get("zo_sql_num"))
+            .and_then(|v| v.as_i64())
+            .unwrap_or_default();
+    }
+
+    Ok(CountResponse {
+        total,
+        file_count,
+        include_wal,
+        took: start.elapsed().as_millis() as usize,
+    })
+}
+
+/// The start of the window where the ingesters can still hold data which isn't in the
+/// file_list, aligned to the hour as the files are partitioned by hour
+fn hot_window_start() -> i64 {
+    let cfg = get_config();
+    let start = Utc::now()
+        - Duration::try_hours(cfg.limit.ingest_allowed_upto).unwrap_or_default()
+        - Duration::try_seconds(cfg.limit.max_file_retention_time as i64).unwrap_or_default();
+    start
+        .duration_trunc(Duration::try_hours(1).unwrap())
+        .unwrap_or(start)
+        .timestamp_micros()
+}
+
+/// Sums the records of the files, with `include_wal` only the files which end before the hot
+/// window are summed, the hot window is counted by a search
+fn sum_records(files: &[FileKey], hot_start: i64, include_wal: bool) -> (i64, usize) {
+    files
+        .iter()
+        .filter(|f| !include_wal || f.meta.max_ts < hot_start)
+        .fold((0, 0), |(total, num), f| (total + f.meta.records, num + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::meta::stream::FileMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_sum_records() {
+        let hour = 3_600_000_000;
+        let hot_start = 10 * hour;
+        let files = (0..12)
+            .map(|i| FileKey {
+                key: format!("file{i}"),
+                meta: FileMeta {
+                    min_ts: i * hour,
+                    max_ts: (i + 1) * hour - 1,
+                    records: 100,
+                    ..Default::default()
+                },
+                deleted: false,
+                segment_ids: None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sum_records(&files, hot_start, false), (1200, 12));
+        assert_eq!(sum_records(&files, hot_start, true), (1000, 10));
+    }
+}
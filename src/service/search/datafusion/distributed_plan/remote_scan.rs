@@ -16,6 +16,8 @@
 use std::{
     any::Any,
     collections::HashMap,
+    future::Future,
+    pin::Pin,
     sync::Arc,
     task::{Context, Poll},
 };
@@ -26,7 +28,11 @@ use arrow_flight::{
     Ticket,
 };
 use arrow_schema::{Schema, SchemaRef};
-use config::meta::{cluster::NodeInfo, search::ScanStats, stream::FileKey};
+use config::meta::{
+    cluster::NodeInfo,
+    search::{ResponseRegionTook, ScanStats},
+    stream::FileKey,
+};
 use datafusion::{
     common::{DataFusionError, Result, Statistics},
     execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext},
@@ -68,6 +74,8 @@ pub struct RemoteScanExec {
     cache: PlanProperties,
     pub scan_stats: Arc<Mutex<ScanStats>>,
     pub partial_err: Arc<Mutex<String>>,
+    /// the timings of the regions of a super cluster search
+    pub regions_took: Arc<Mutex<Vec<ResponseRegionTook>>>,
     pub context: opentelemetry::Context,
 }
 
@@ -100,6 +108,7 @@ impl RemoteScanExec {
             cache,
             scan_stats: Arc::new(Mutex::new(ScanStats::default())),
             partial_err: Arc::new(Mutex::new(String::new())),
+            regions_took: Arc::new(Mutex::new(Vec::new())),
             context,
         }
     }
@@ -189,6 +198,7 @@ impl ExecutionPlan for RemoteScanExec {
             req,
             self.scan_stats.clone(),
             self.partial_err.clone(),
+            self.regions_took.clone(),
             self.context.clone(),
         );
         let stream = futures::stream::once(fut).try_flatten();
@@ -216,8 +226,10 @@ async fn get_remote_batch(
     req: Request,
     scan_stats: Arc<Mutex<ScanStats>>,
     partial_err: Arc<Mutex<String>>,
+    regions_took: Arc<Mutex<Vec<ResponseRegionTook>>>,
     context: opentelemetry::Context,
 ) -> Result<SendableRecordBatchStream> {
+    let output_schema = input.schema();
     let proto = ComposedPhysicalExtensionCodec {
         codecs: vec![Arc::new(EmptyExecPhysicalExtensionCodec {})],
     };
@@ -256,6 +268,11 @@ async fn get_remote_batch(
         search_event_type: req.search_event_type,
         use_inverted_index: req.use_inverted_index,
         index_type: req.inverted_index_type.clone(),
+        prefix_keys: req
+            .index_prefix_terms
+            .iter()
+            .map(|(k, v)| KvItem::new(k, v))
+            .collect(),
     };
 
     log::info!(
@@ -295,40 +312,92 @@ async fn get_remote_batch(
         .get_auth_token()
         .parse()
         .map_err(|_| DataFusionError::Internal("invalid token".to_string()))?;
-    let channel = get_cached_channel(&node.get_grpc_addr())
-        .await
-        .map_err(|err| {
+
+    // a region of a super cluster which fails or is slower than the region timeout is left out
+    // of the result, instead of failing the whole search
+    let region_start = std::time::Instant::now();
+    let region_deadline = (is_super_cluster && cfg.limit.search_region_timeout > 0).then(|| {
+        tokio::time::Instant::now()
+            + tokio::time::Duration::from_secs(cfg.limit.search_region_timeout)
+    });
+    let open_stream = async {
+        let channel = get_cached_channel(&node.get_grpc_addr())
+            .await
+            .map_err(|err| {
+                log::error!(
+                    "[trace_id {}] flight->search: node: {}, connect err: {:?}",
+                    req.trace_id.clone(),
+                    &node.get_grpc_addr(),
+                    err
+                );
+                DataFusionError::Internal("connect search node error".to_string())
+            })?;
+        let mut client =
+            FlightServiceClient::with_interceptor(channel, move |mut req: tonic::Request<()>| {
+                req.metadata_mut().insert("authorization", token.clone());
+                req.metadata_mut()
+                    .insert(org_header_key.clone(), org_id.clone());
+                Ok(req)
+            });
+        client = client
+            .send_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Gzip)
+            .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
+            .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
+
+        let mut stream = client
+            .do_get(request)
+            .await
+            .map_err(|e| DataFusionError::Execution(e.to_string()))?
+            .into_inner();
+
+        // the schema should be the first message returned, else client should error
+        let flight_data = stream
+            .message()
+            .await
+            .map_err(|e| DataFusionError::Execution(e.to_string()))?
+            .ok_or_else(|| DataFusionError::Execution("no schema in the response".to_string()))?;
+        Ok::<_, DataFusionError>((stream, flight_data))
+    };
+    let opened = match region_deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, open_stream)
+            .await
+            .unwrap_or_else(|_| {
+                Err(DataFusionError::Execution(format!(
+                    "timeout after {} seconds",
+                    cfg.limit.search_region_timeout
+                )))
+            }),
+        None => open_stream.await,
+    };
+    let (stream, flight_data) = match opened {
+        Ok(v) => v,
+        Err(e) if is_super_cluster => {
             log::error!(
-                "[trace_id {}] flight->search: node: {}, connect err: {:?}",
-                req.trace_id.clone(),
-                &node.get_grpc_addr(),
-                err
+                "[trace_id {}] flight->search: region: {}, err: {e}",
+                req.trace_id,
+                node.get_name()
             );
-            DataFusionError::Internal("connect search node error".to_string())
-        })?;
-    let mut client =
-        FlightServiceClient::with_interceptor(channel, move |mut req: tonic::Request<()>| {
-            req.metadata_mut().insert("authorization", token.clone());
-            req.metadata_mut()
-                .insert(org_header_key.clone(), org_id.clone());
-            Ok(req)
-        });
-    client = client
-        .send_compressed(CompressionEncoding::Gzip)
-        .accept_compressed(CompressionEncoding::Gzip)
-        .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
-        .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
-
-    let mut stream = client
-        .do_get(request)
-        .await
-        .map_err(|e| DataFusionError::Execution(e.to_string()))?
-        .into_inner();
+            add_partial_err(
+                &partial_err,
+                &format!("region {} failed: {e}", node.get_name()),
+            );
+            regions_took.lock().push(ResponseRegionTook {
+                region: node.get_name(),
+                took: region_start.elapsed().as_millis() as usize,
+                error: Some(e.to_string()),
+                ..Default::default()
+            });
+            return Ok(Box::pin(RecordBatchStreamAdapter::new(
+                output_schema,
+                futures::stream::empty(),
+            )));
+        }
+        Err(e) => return Err(e),
+    };
 
     let start = std::time::Instant::now();
 
-    // the schema should be the first message returned, else client should error
-    let flight_data = stream.message().await.unwrap().unwrap();
     // convert FlightData to a stream
     let schema = Arc::new(Schema::try_from(&flight_data)?);
 
@@ -341,7 +410,7 @@ async fn get_remote_batch(
         scan_stats.lock().add(&stats);
     }
 
-    Ok(Box::pin(FlightStream::new(
+    let mut flight_stream = FlightStream::new(
         req.trace_id,
         schema,
         stream,
@@ -351,7 +420,30 @@ async fn get_remote_batch(
         scan_size,
         partial_err,
         start,
-    )))
+    );
+    if is_super_cluster {
+        flight_stream =
+            flight_stream.with_region(node.get_name(), region_start, region_deadline, regions_took);
+    }
+    Ok(Box::pin(flight_stream))
+}
+
+fn add_partial_err(partial_err: &Mutex<String>, err: &str) {
+    let mut guard = partial_err.lock();
+    if guard.is_empty() {
+        guard.push_str(err);
+    } else {
+        guard.push_str(format!(" \n {}", err).as_str());
+    }
+}
+
+/// The region of a super cluster search a stream reads from
+struct FlightRegion {
+    name: String,
+    start: std::time::Instant,
+    deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+    error: Option<String>,
+    regions_took: Arc<Mutex<Vec<ResponseRegionTook>>>,
 }
 
 struct FlightStream {
@@ -364,6 +456,7 @@ struct FlightStream {
     scan_size: i64,
     partial_err: Arc<Mutex<String>>,
     start: std::time::Instant,
+    region: Option<FlightRegion>,
 }
 
 impl FlightStream {
@@ -389,8 +482,39 @@ impl FlightStream {
             scan_size,
             partial_err,
             start,
+            region: None,
         }
     }
+
+    /// Records the timing of the region when the stream is done, and ends the stream as partial
+    /// at the deadline
+    fn with_region(
+        mut self,
+        name: String,
+        start: std::time::Instant,
+        deadline: Option<tokio::time::Instant>,
+        regions_took: Arc<Mutex<Vec<ResponseRegionTook>>>,
+    ) -> Self {
+        self.region = Some(FlightRegion {
+            name,
+            start,
+            deadline: deadline.map(|v| Box::pin(tokio::time::sleep_until(v))),
+            error: None,
+            regions_took,
+        });
+        self
+    }
+
+    fn set_error(&mut self, err: String) {
+        let err = match self.region.as_mut() {
+            Some(region) => {
+                region.error = Some(err.clone());
+                format!("region {} failed: {err}", region.name)
+            }
+            None => err,
+        };
+        add_partial_err(&self.partial_err, &err);
+    }
 }
 
 impl Stream for FlightStream {
@@ -412,17 +536,24 @@ impl Stream for FlightStream {
                 Poll::Ready(Some(Ok(record_batch)))
             }
             Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(Some(Err(e))) => {
-                {
-                    let mut guard = self.partial_err.lock();
-                    let partial_err = guard.clone();
-                    if partial_err.is_empty() {
-                        guard.push_str(e.to_string().as_str());
-                    } else {
-                        guard.push_str(format!(" \n {}", e).as_str());
-                    }
+            Poll::Pending => {
+                let timed_out = self
+                    .region
+                    .as_mut()
+                    .and_then(|region| region.deadline.as_mut())
+                    .is_some_and(|deadline| deadline.as_mut().poll(cx).is_ready());
+                if timed_out {
+                    let err = format!(
+                        "timeout after {} seconds",
+                        config::get_config().limit.search_region_timeout
+                    );
+                    self.set_error(err);
+                    return Poll::Ready(None);
                 }
+                Poll::Pending
+            }
+            Poll::Ready(Some(Err(e))) => {
+                self.set_error(e.to_string());
                 Poll::Ready(None)
             }
         }
@@ -440,6 +571,15 @@ impl Drop for FlightStream {
             self.files,
             self.scan_size / 1024 / 1024,
         );
+        if let Some(region) = self.region.take() {
+            region.regions_took.lock().push(ResponseRegionTook {
+                region: region.name,
+                took: region.start.elapsed().as_millis() as usize,
+                files: self.files,
+                scan_size: self.scan_size,
+                error: region.error,
+            });
+        }
     }
 }
 
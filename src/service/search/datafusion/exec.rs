@@ -41,6 +41,7 @@ use datafusion::{
     execution::{
         cache::cache_manager::{CacheManagerConfig, FileStatisticsCache},
         context::SessionConfig,
+        disk_manager::DiskManagerConfig,
         memory_pool::{FairSpillPool, GreedyMemoryPool},
         runtime_env::{RuntimeConfig, RuntimeEnv},
         session_state::SessionStateBuilder,
@@ -54,6 +55,7 @@ use hashbrown::HashMap;
 use o2_enterprise::enterprise::{
     common::infra::config::get_config as get_o2_config, search::WorkGroup,
 };
+use once_cell::sync::Lazy;
 
 use super::{
     file_type::{FileType, GetExt},
@@ -66,6 +68,10 @@ use super::{
 const DATAFUSION_MIN_MEM: usize = 1024 * 1024 * 256; // 256MB
 const DATAFUSION_MIN_PARTITION: usize = 2; // CPU cores
 
+/// The spill directory of this process, under the configured spill dir which can be shared
+static SPILL_DIR: Lazy<String> =
+    Lazy::new(|| process_spill_dir(&get_config().memory_cache.datafusion_spill_dir));
+
 pub async fn convert_parquet_file(
     trace_id: &str,
     buf: &mut Vec<u8>,
@@ -280,6 +286,11 @@ pub async fn create_runtime_env(memory_limit: usize) -> Result<RuntimeEnv> {
         rn_config = rn_config.with_cache_manager(cache_config);
     }
 
+    rn_config = rn_config.with_disk_manager(spill_disk_manager_config(
+        cfg.memory_cache.datafusion_spill_enabled,
+        &SPILL_DIR,
+    ));
+
     let memory_size = std::cmp::max(DATAFUSION_MIN_MEM, memory_limit);
     let mem_pool = super::MemoryPoolType::from_str(&cfg.memory_cache.datafusion_memory_pool)
         .map_err(|e| {
@@ -297,6 +308,30 @@ pub async fn create_runtime_env(memory_limit: usize) -> Result<RuntimeEnv> {
     RuntimeEnv::new(rn_config)
 }
 
+/// The spill files are created in a temporary directory of the runtime env,
+/// which is removed when the query is done
+fn spill_disk_manager_config(enabled: bool, spill_dir: &str) -> DiskManagerConfig {
+    if enabled {
+        DiskManagerConfig::NewSpecified(vec![spill_dir.into()])
+    } else {
+        DiskManagerConfig::Disabled
+    }
+}
+
+fn process_spill_dir(spill_dir: &str) -> String {
+    format!("{spill_dir}o2_spill_{}/", std::process::id())
+}
+
+/// Removes the spill files left by the queries of the last run. Only the spill directory of
+/// this process is removed, the configured spill dir itself is never removed.
+pub fn clean_spill_dir() -> std::io::Result<()> {
+    let spill_dir = SPILL_DIR.as_str();
+    if std::path::Path::new(spill_dir).exists() {
+        std::fs::remove_dir_all(spill_dir)?;
+    }
+    std::fs::create_dir_all(spill_dir)
+}
+
 pub async fn prepare_datafusion_context(
     _work_group: Option<String>,
     optimizer_rules: Vec<Arc<dyn OptimizerRule + Send + Sync>>,
@@ -304,11 +339,13 @@ pub async fn prepare_datafusion_context(
     target_partitions: usize,
 ) -> Result<SessionContext, DataFusionError> {
     let cfg = get_config();
+    // a query gets its share of the memory, it spills to disk when exceeding it
+    let query_memory_size =
+        cfg.memory_cache.datafusion_max_size / cfg.memory_cache.datafusion_query_concurrency;
     #[cfg(not(feature = "enterprise"))]
-    let (memory_size, target_partition) = (cfg.memory_cache.datafusion_max_size, target_partitions);
+    let (memory_size, target_partition) = (query_memory_size, target_partitions);
     #[cfg(feature = "enterprise")]
-    let (mut memory_size, mut target_partition) =
-        (cfg.memory_cache.datafusion_max_size, target_partitions);
+    let (mut memory_size, mut target_partition) = (query_memory_size, target_partitions);
     #[cfg(feature = "enterprise")]
     if let Some(wg) = _work_group {
         if let Ok(wg) = WorkGroup::from_str(&wg) {
@@ -364,6 +401,7 @@ pub fn register_udf(ctx: &SessionContext, org_id: &str) -> Result<()> {
     ctx.register_udf(super::udf::arrsort_udf::ARR_SORT_UDF.clone());
     ctx.register_udf(super::udf::cast_to_arr_udf::CAST_TO_ARR_UDF.clone());
     ctx.register_udf(super::udf::spath_udf::SPATH_UDF.clone());
+    ctx.register_udf(super::udf::array_contains_udf::ARRAY_CONTAINS_UDF.clone());
     ctx.register_udf(super::udf::to_arr_string_udf::TO_ARR_STRING.clone());
     ctx.register_udf(super::udf::histogram_udf::HISTOGRAM_UDF.clone());
     ctx.register_udf(super::udf::match_all_udf::MATCH_ALL_RAW_UDF.clone());
@@ -508,3 +546,68 @@ pub async fn create_parquet_table(
     }
     Ok(Arc::new(table))
 }
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::array::Int64Array,
+        datasource::MemTable,
+        physical_plan::{collect, visit_execution_plan},
+    };
+
+    use super::*;
+    use crate::service::search::utlis::ScanStatsVisitor;
+
+    #[test]
+    fn test_process_spill_dir() {
+        let spill_dir = process_spill_dir("/data/spill/");
+        assert!(spill_dir.starts_with("/data/spill/o2_spill_"));
+        assert!(spill_dir.ends_with('/'));
+        assert_ne!(spill_dir, "/data/spill/");
+    }
+
+    #[tokio::test]
+    async fn test_sort_spill() {
+        let spill_dir = std::env::temp_dir().join("zo_test_sort_spill");
+        let rn_config = RuntimeConfig::new()
+            .with_memory_pool(Arc::new(FairSpillPool::new(4 * 1024 * 1024)))
+            .with_disk_manager(spill_disk_manager_config(true, spill_dir.to_str().unwrap()));
+        let session_config = SessionConfig::new()
+            .with_target_partitions(1)
+            .with_sort_spill_reservation_bytes(1024 * 1024);
+        let ctx = SessionContext::new_with_config_rt(
+            session_config,
+            Arc::new(RuntimeEnv::new(rn_config).unwrap()),
+        );
+
+        // 8MB of data, more than the memory pool
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)]));
+        let batches = (0..100)
+            .map(|i| {
+                let values = (0..10_000).map(|v| (v * 7919 + i) % 1_000_003);
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from_iter_values(values))],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let table = MemTable::try_new(schema, vec![batches]).unwrap();
+        ctx.register_table("t", Arc::new(table)).unwrap();
+
+        let plan = ctx
+            .sql("SELECT v FROM t ORDER BY v")
+            .await
+            .unwrap()
+            .create_physical_plan()
+            .await
+            .unwrap();
+        let data = collect(plan.clone(), ctx.task_ctx()).await.unwrap();
+        let num_rows = data.iter().map(|b| b.num_rows()).sum::<usize>();
+        assert_eq!(num_rows, 1_000_000);
+
+        let mut visit = ScanStatsVisitor::new();
+        visit_execution_plan(plan.as_ref(), &mut visit).unwrap();
+        assert!(visit.spill_count > 0);
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The guard of the number of groups of a GROUP BY query, `ZO_QUERY_MAX_GROUPS`. The group keys
+//! are counted on the input of the aggregations, so their hash tables never hold more than the
+//! max groups. The rows of the groups over the max groups fail the query, or are dropped with the
+//! `truncate` policy. Truncating keeps the first groups seen, so it's only allowed when the
+//! aggregation isn't sorted, a sorted query over the max groups fails as with `reject`.
+
+use std::{
+    any::Any,
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use arrow::{
+    array::BooleanArray,
+    compute::filter_record_batch,
+    record_batch::RecordBatch,
+    row::{RowConverter, SortField},
+};
+use arrow_schema::SchemaRef;
+use datafusion::{
+    common::{
+        tree_node::{Transformed, TreeNode, TreeNodeRecursion},
+        DataFusionError, Result, Statistics,
+    },
+    execution::{SendableRecordBatchStream, TaskContext},
+    physical_plan::{
+        aggregates::{AggregateExec, AggregateMode},
+        sorts::{sort::SortExec, sort_preserving_merge::SortPreservingMergeExec},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionPlan, PhysicalExpr, PlanProperties,
+    },
+};
+use futures::StreamExt;
+use parking_lot::Mutex;
+
+use super::distributed_plan::remote_scan::RemoteScanExec;
+
+/// Counts the distinct group keys of the input of an aggregation, over all its partitions
+#[derive(Debug)]
+pub struct GroupLimitExec {
+    input: Arc<dyn ExecutionPlan>,
+    group_by: Vec<Arc<dyn PhysicalExpr>>,
+    max_groups: usize,
+    truncate: bool,
+    /// The encoded keys of the groups let through, at most `max_groups`
+    groups: Arc<Mutex<HashSet<Box<[u8]>>>>,
+    /// Some groups were dropped with the `truncate` policy
+    pub truncated: Arc<AtomicBool>,
+}
+
+impl GroupLimitExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        group_by: Vec<Arc<dyn PhysicalExpr>>,
+        max_groups: usize,
+        truncate: bool,
+    ) -> Self {
+        Self {
+            input,
+            group_by,
+            max_groups,
+            truncate,
+            groups: Arc::new(Mutex::new(HashSet::new())),
+            truncated: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl DisplayAs for GroupLimitExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "GroupLimitExec: max_groups={}, truncate={}",
+                    self.max_groups, self.truncate
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for GroupLimitExec {
+    fn name(&self) -> &'static str {
+        "GroupLimitExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        mut children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self {
+            input: children.swap_remove(0),
+            group_by: self.group_by.clone(),
+            max_groups: self.max_groups,
+            truncate: self.truncate,
+            groups: self.groups.clone(),
+            truncated: self.truncated.clone(),
+        }))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let schema = self.input.schema();
+        let fields = self
+            .group_by
+            .iter()
+            .map(|expr| Ok(SortField::new(expr.data_type(&schema)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let converter = RowConverter::new(fields)?;
+        let group_by = self.group_by.clone();
+        let max_groups = self.max_groups;
+        let truncate = self.truncate;
+        let groups = self.groups.clone();
+        let truncated = self.truncated.clone();
+        let stream = self
+            .input
+            .execute(partition, context)?
+            .map(move |batch| {
+                let batch = batch?;
+                let columns = group_by
+                    .iter()
+                    .map(|expr| expr.evaluate(&batch)?.into_array(batch.num_rows()))
+                    .collect::<Result<Vec<_>>>()?;
+                let rows = converter.convert_columns(&columns)?;
+                let mut keep = Vec::with_capacity(batch.num_rows());
+                let mut groups = groups.lock();
+                for row in rows.iter() {
+                    let key = row.as_ref();
+                    if groups.contains(key) {
+                        keep.push(true);
+                    } else if groups.len() < max_groups {
+                        groups.insert(key.into());
+                        keep.push(true);
+                    } else if truncate {
+                        keep.push(false);
+                    } else {
+                        return Err(DataFusionError::ResourcesExhausted(format!(
+                            "The query has more than {max_groups} groups, narrow down the GROUP BY or the time range"
+                        )));
+                    }
+                }
+                drop(groups);
+                if keep.iter().all(|v| *v) {
+                    return Ok(batch);
+                }
+                truncated.store(true, Ordering::Relaxed);
+                Ok(filter_record_batch(&batch, &BooleanArray::from(keep))?)
+            })
+            .filter(|batch: &Result<RecordBatch>| {
+                futures::future::ready(!matches!(batch, Ok(v) if v.num_rows() == 0))
+            });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+/// Puts a [`GroupLimitExec`] under the aggregations with a GROUP BY of the plan, the plans
+/// executed by the other nodes are left as is.
+///
+/// The partial aggregations are only limited with the `reject` policy: the groups they drop
+/// would miss from the merged results of the groups kept by the final aggregation. With
+/// `truncate` they emit their groups early under memory pressure instead of holding all of them.
+pub fn add_group_limit(
+    plan: Arc<dyn ExecutionPlan>,
+    max_groups: usize,
+    truncate: bool,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let mut sorted = false;
+    plan.transform_down(|node| {
+        if node.as_any().is::<RemoteScanExec>() {
+            return Ok(Transformed::new(node, false, TreeNodeRecursion::Jump));
+        }
+        if node.as_any().is::<SortExec>() || node.as_any().is::<SortPreservingMergeExec>() {
+            sorted = true;
+            return Ok(Transformed::no(node));
+        }
+        let Some(aggregate) = node.as_any().downcast_ref::<AggregateExec>() else {
+            return Ok(Transformed::no(node));
+        };
+        let group_by = aggregate.group_expr();
+        if group_by.expr().is_empty() || !group_by.null_expr().is_empty() {
+            return Ok(Transformed::no(node));
+        }
+        let partial = matches!(
+            aggregate.mode(),
+            AggregateMode::Partial | AggregateMode::PartialReduce
+        );
+        if partial && truncate {
+            return Ok(Transformed::no(node));
+        }
+        let input = Arc::new(GroupLimitExec::new(
+            aggregate.input().clone(),
+            group_by
+                .expr()
+                .iter()
+                .map(|(expr, _)| expr.clone())
+                .collect(),
+            max_groups,
+            truncate && !sorted,
+        ));
+        Ok(Transformed::yes(
+            node.clone().with_new_children(vec![input])?,
+        ))
+    })
+    .map(|v| v.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::{
+        datasource::MemTable,
+        physical_plan::{collect, visit_execution_plan},
+        prelude::SessionContext,
+    };
+
+    use super::*;
+    use crate::service::search::utlis::ScanStatsVisitor;
+
+    async fn group_by_plan(sql: &str) -> (SessionContext, Arc<dyn ExecutionPlan>) {
+        let ctx = SessionContext::new();
+        // 10_000 distinct values, each one twice
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)]));
+        let batches = (0..20)
+            .map(|i| {
+                let values = (0..1000).map(|v| i % 10 * 1000 + v);
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from_iter_values(values))],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let table = MemTable::try_new(schema, vec![batches]).unwrap();
+        ctx.register_table("t", Arc::new(table)).unwrap();
+        let plan = ctx
+            .sql(sql)
+            .await
+            .unwrap()
+            .create_physical_plan()
+            .await
+            .unwrap();
+        (ctx, plan)
+    }
+
+    fn count_group_limits(plan: &Arc<dyn ExecutionPlan>) -> usize {
+        let mut count = 0;
+        plan.apply(|node| {
+            if node.as_any().is::<GroupLimitExec>() {
+                count += 1;
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })
+        .unwrap();
+        count
+    }
+
+    #[tokio::test]
+    async fn test_group_limit_reject() {
+        let (ctx, plan) = group_by_plan("SELECT v, count(*) FROM t GROUP BY v").await;
+        let plan = add_group_limit(plan, 100, false).unwrap();
+        // the partial and the final aggregations
+        assert_eq!(count_group_limits(&plan), 2);
+        let err = collect(plan, ctx.task_ctx()).await.unwrap_err();
+        assert!(err.to_string().contains("more than 100 groups"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_group_limit_truncate() {
+        let (ctx, plan) = group_by_plan("SELECT v, count(*) AS c FROM t GROUP BY v").await;
+        let plan = add_group_limit(plan, 100, true).unwrap();
+        // only the final aggregation
+        assert_eq!(count_group_limits(&plan), 1);
+        let data = collect(plan.clone(), ctx.task_ctx()).await.unwrap();
+        let num_rows = data.iter().map(|b| b.num_rows()).sum::<usize>();
+        assert_eq!(num_rows, 100);
+        // the kept groups have all their records
+        for batch in data.iter() {
+            let counts = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap();
+            assert!(counts.iter().all(|v| v == Some(2)));
+        }
+
+        let mut visit = ScanStatsVisitor::new();
+        visit_execution_plan(plan.as_ref(), &mut visit).unwrap();
+        assert!(visit.groups_truncated);
+    }
+
+    #[tokio::test]
+    async fn test_group_limit_truncate_sorted() {
+        let (ctx, plan) =
+            group_by_plan("SELECT v, count(*) AS c FROM t GROUP BY v ORDER BY c DESC LIMIT 10")
+                .await;
+        let plan = add_group_limit(plan, 100, true).unwrap();
+        // the first groups aren't the top ones
+        let err = collect(plan, ctx.task_ctx()).await.unwrap_err();
+        assert!(err.to_string().contains("more than 100 groups"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_group_limit_under_max() {
+        let (ctx, plan) = group_by_plan("SELECT v, count(*) FROM t GROUP BY v").await;
+        let plan = add_group_limit(plan, 10_000, false).unwrap();
+        let data = collect(plan.clone(), ctx.task_ctx()).await.unwrap();
+        let num_rows = data.iter().map(|b| b.num_rows()).sum::<usize>();
+        assert_eq!(num_rows, 10_000);
+
+        let mut visit = ScanStatsVisitor::new();
+        visit_execution_plan(plan.as_ref(), &mut visit).unwrap();
+        assert!(!visit.groups_truncated);
+    }
+}
@@ -18,6 +18,7 @@ use std::str::FromStr;
 pub mod distributed_plan;
 pub mod exec;
 pub mod file_type;
+pub mod group_limit;
 pub mod optimizer;
 pub mod storage;
 pub mod table_provider;
@@ -0,0 +1,301 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use arrow_schema::DataType;
+use datafusion::{
+    common::{tree_node::Transformed, DFSchema, Result},
+    logical_expr::{
+        expr::ScalarFunction,
+        utils::{conjunction, split_conjunction},
+        Expr, ExprSchemable, Filter, Like, LogicalPlan,
+    },
+    optimizer::{optimizer::ApplyOrder, OptimizerConfig, OptimizerRule},
+    scalar::ScalarValue,
+};
+
+use crate::service::search::datafusion::udf::REGEX_MATCH_UDF_NAME;
+
+/// The boolean regex functions which match anywhere in the string
+const REGEX_FUNCTIONS: [&str; 2] = [REGEX_MATCH_UDF_NAME, "regexp_like"];
+
+/// Optimization rule that add a range constraint for the filters matching a
+/// literal prefix, e.g. `name LIKE 'abc%'` or `re_match(name, '^abc')` gets
+/// `name >= 'abc' AND name < 'abd'`, so the parquet scan can prune the row
+/// groups by the min/max statistics.
+///
+/// The original filter is kept, the other patterns are left as they are.
+///
+/// Note: should apply before push down filter rule
+#[derive(Default)]
+pub struct AddPrefixRangeRule {}
+
+impl AddPrefixRangeRule {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl OptimizerRule for AddPrefixRangeRule {
+    fn name(&self) -> &str {
+        "add_prefix_range"
+    }
+
+    fn apply_order(&self) -> Option<ApplyOrder> {
+        Some(ApplyOrder::BottomUp)
+    }
+
+    fn supports_rewrite(&self) -> bool {
+        true
+    }
+
+    fn rewrite(
+        &self,
+        plan: LogicalPlan,
+        _config: &dyn OptimizerConfig,
+    ) -> Result<Transformed<LogicalPlan>> {
+        let LogicalPlan::Filter(filter) = plan else {
+            return Ok(Transformed::no(plan));
+        };
+        let exprs = split_conjunction(&filter.predicate);
+        let mut ranges = Vec::new();
+        for expr in exprs.iter() {
+            for range in prefix_range(expr, filter.input.schema()) {
+                // the rule runs in every optimizer pass, the range may be added already
+                if !exprs.contains(&&range) && !ranges.contains(&range) {
+                    ranges.push(range);
+                }
+            }
+        }
+        if ranges.is_empty() {
+            return Ok(Transformed::no(LogicalPlan::Filter(filter)));
+        }
+        let predicate = conjunction(exprs.into_iter().cloned().chain(ranges)).unwrap();
+        Ok(Transformed::yes(LogicalPlan::Filter(Filter::try_new(
+            predicate,
+            filter.input,
+        )?)))
+    }
+}
+
+/// Returns the range constraints of a filter matching a literal prefix
+fn prefix_range(expr: &Expr, schema: &DFSchema) -> Vec<Expr> {
+    let (expr, prefix) = match expr {
+        Expr::Like(Like {
+            negated: false,
+            expr,
+            pattern,
+            escape_char: None,
+            case_insensitive: false,
+        }) => match pattern.as_ref() {
+            Expr::Literal(ScalarValue::Utf8(Some(pattern))) => match get_like_prefix(pattern) {
+                Some(prefix) => (expr.as_ref(), prefix.to_string()),
+                None => return vec![],
+            },
+            _ => return vec![],
+        },
+        Expr::ScalarFunction(ScalarFunction { func, args })
+            if REGEX_FUNCTIONS.contains(&func.name()) && args.len() == 2 =>
+        {
+            match &args[1] {
+                Expr::Literal(ScalarValue::Utf8(Some(pattern))) => {
+                    match get_regex_prefix(pattern) {
+                        Some(prefix) => (&args[0], prefix),
+                        None => return vec![],
+                    }
+                }
+                _ => return vec![],
+            }
+        }
+        _ => return vec![],
+    };
+    // the range literal is Utf8, the other string types are not comparable without a cast
+    if !matches!(expr.get_type(schema), Ok(DataType::Utf8)) {
+        return vec![];
+    }
+
+    let upper = prefix_upper_bound(&prefix);
+    let mut ranges = vec![
+        expr.clone()
+            .gt_eq(Expr::Literal(ScalarValue::Utf8(Some(prefix)))),
+    ];
+    if let Some(upper) = upper {
+        ranges.push(
+            expr.clone()
+                .lt(Expr::Literal(ScalarValue::Utf8(Some(upper)))),
+        );
+    }
+    ranges
+}
+
+/// Returns the literal prefix of a pattern like `abc%`, or None if the pattern
+/// has other wildcards or escapes
+fn get_like_prefix(pattern: &str) -> Option<&str> {
+    let prefix = pattern.strip_suffix('%')?;
+    if prefix.is_empty() || prefix.contains(['%', '_', '\\']) {
+        return None;
+    }
+    Some(prefix)
+}
+
+/// Returns the literal prefix of an anchored regex like `^ERROR` or `^abc.*`,
+/// or None if the regex is not anchored at the start or has no literal prefix
+fn get_regex_prefix(pattern: &str) -> Option<String> {
+    let pattern = pattern.strip_prefix('^')?;
+    // the alternatives may not share the prefix
+    if pattern.contains('|') {
+        return None;
+    }
+    let mut prefix = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            // an escaped meta character is a literal, the others are classes like `\d`
+            '\\' => match chars.next() {
+                Some(c) if regex_syntax::is_meta_character(c) => c,
+                _ => break,
+            },
+            c if regex_syntax::is_meta_character(c) => break,
+            c => c,
+        };
+        match chars.peek() {
+            // the character is optional
+            Some('*' | '?' | '{') => break,
+            // the character is repeated
+            Some('+') => {
+                prefix.push(c);
+                break;
+            }
+            _ => prefix.push(c),
+        }
+    }
+    if prefix.is_empty() {
+        None
+    } else {
+        Some(prefix)
+    }
+}
+
+/// Returns the smallest string greater than all the strings with the given prefix
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars = prefix.chars().collect::<Vec<_>>();
+    while let Some(c) = chars.pop() {
+        if let Some(next) = (c as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::{Field, Schema};
+    use datafusion::{
+        logical_expr::{col, lit, table_scan, LogicalPlanBuilder},
+        optimizer::{Optimizer, OptimizerContext},
+    };
+
+    use super::*;
+    use crate::service::search::datafusion::udf::regexp_udf::REGEX_MATCH_UDF;
+
+    fn test_table() -> Result<LogicalPlan> {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("_timestamp", DataType::Int64, false),
+        ]);
+        table_scan(Some("test"), &schema, None)?.build()
+    }
+
+    fn observe(_plan: &LogicalPlan, _rule: &dyn OptimizerRule) {}
+
+    fn assert_optimized_plan_equal(plan: LogicalPlan, expected: &str) -> Result<()> {
+        let optimizer = Optimizer::with_rules(vec![Arc::new(AddPrefixRangeRule::new())]);
+        // run several passes to check the range is added only once
+        let opt_context = OptimizerContext::new().with_max_passes(3);
+        let optimized_plan = optimizer.optimize(plan, &opt_context, observe)?;
+        assert_eq!(format!("{optimized_plan}"), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_like_prefix() -> Result<()> {
+        let plan = LogicalPlanBuilder::from(test_table()?)
+            .filter(col("name").like(lit("abc%")).and(col("id").gt(lit(1))))?
+            .build()?;
+        let expected = "Filter: test.name LIKE Utf8(\"abc%\") AND test.id > Int32(1) \
+        AND test.name >= Utf8(\"abc\") AND test.name < Utf8(\"abd\")\
+        \n  TableScan: test";
+        assert_optimized_plan_equal(plan, expected)
+    }
+
+    #[test]
+    fn test_like_not_prefix() -> Result<()> {
+        for pattern in ["%abc", "a_c%", "%abc%", "abc", "%"] {
+            let plan = LogicalPlanBuilder::from(test_table()?)
+                .filter(col("name").like(lit(pattern)))?
+                .build()?;
+            let expected = format!("Filter: test.name LIKE Utf8(\"{pattern}\")\n  TableScan: test");
+            assert_optimized_plan_equal(plan, &expected)?;
+        }
+        let plan = LogicalPlanBuilder::from(test_table()?)
+            .filter(col("name").not_like(lit("abc%")))?
+            .build()?;
+        let expected = "Filter: test.name NOT LIKE Utf8(\"abc%\")\n  TableScan: test";
+        assert_optimized_plan_equal(plan, expected)
+    }
+
+    #[test]
+    fn test_regex_prefix() -> Result<()> {
+        let plan = LogicalPlanBuilder::from(test_table()?)
+            .filter(REGEX_MATCH_UDF.call(vec![col("name"), lit("^ERROR")]))?
+            .build()?;
+        let expected = "Filter: re_match(test.name, Utf8(\"^ERROR\")) \
+        AND test.name >= Utf8(\"ERROR\") AND test.name < Utf8(\"ERROS\")\
+        \n  TableScan: test";
+        assert_optimized_plan_equal(plan, expected)?;
+
+        let plan = LogicalPlanBuilder::from(test_table()?)
+            .filter(REGEX_MATCH_UDF.call(vec![col("name"), lit("abc.*")]))?
+            .build()?;
+        let expected = "Filter: re_match(test.name, Utf8(\"abc.*\"))\n  TableScan: test";
+        assert_optimized_plan_equal(plan, expected)
+    }
+
+    #[test]
+    fn test_get_regex_prefix() {
+        assert_eq!(get_regex_prefix("^ERROR"), Some("ERROR".to_string()));
+        assert_eq!(get_regex_prefix("^abc.*"), Some("abc".to_string()));
+        assert_eq!(get_regex_prefix("^a\\.b\\d+"), Some("a.b".to_string()));
+        assert_eq!(get_regex_prefix("^abc?"), Some("ab".to_string()));
+        assert_eq!(get_regex_prefix("^ab+c"), Some("ab".to_string()));
+        // not anchored, it matches anywhere
+        assert_eq!(get_regex_prefix("abc.*"), None);
+        assert_eq!(get_regex_prefix("^(foo|bar)"), None);
+        assert_eq!(get_regex_prefix("^.*abc"), None);
+        assert_eq!(get_regex_prefix("(?i)^abc"), None);
+    }
+
+    #[test]
+    fn test_prefix_upper_bound() {
+        assert_eq!(prefix_upper_bound("abc"), Some("abd".to_string()));
+        assert_eq!(prefix_upper_bound("ab\u{10FFFF}"), Some("ac".to_string()));
+        assert_eq!(prefix_upper_bound("\u{D7FF}"), Some("\u{E000}".to_string()));
+        assert_eq!(prefix_upper_bound("\u{10FFFF}"), None);
+    }
+}
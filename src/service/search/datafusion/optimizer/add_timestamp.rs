@@ -91,7 +91,10 @@ mod tests {
             and, binary_expr, col, in_subquery, lit, table_scan, JoinType, LogicalPlan,
             LogicalPlanBuilder, Operator,
         },
-        optimizer::{push_down_filter::PushDownFilter, Optimizer, OptimizerContext, OptimizerRule},
+        optimizer::{
+            decorrelate_predicate_subquery::DecorrelatePredicateSubquery,
+            push_down_filter::PushDownFilter, Optimizer, OptimizerContext, OptimizerRule,
+        },
         prelude::{SessionConfig, SessionContext},
     };
 
@@ -211,6 +214,36 @@ mod tests {
         assert_optimized_plan_equal(plan, expected)
     }
 
+    #[test]
+    fn test_in_subquery_to_semi_join() -> Result<()> {
+        let subquery = LogicalPlanBuilder::from(create_table_with_name("errors")?)
+            .project(vec![col("errors.name")])?
+            .build()?;
+        let plan = LogicalPlanBuilder::from(test_table()?)
+            .filter(in_subquery(col("test.name"), Arc::new(subquery)))?
+            .project(vec![col("test.name")])?
+            .build()?;
+
+        // the subquery is turned into a semi-join first, so both streams are bounded by the time
+        // range
+        let opt_context = OptimizerContext::new().with_max_passes(1);
+        let optimizer = Optimizer::with_rules(vec![
+            Arc::new(DecorrelatePredicateSubquery::new()),
+            Arc::new(AddTimestampRule::new(0, 5)),
+        ]);
+        let formatted_plan = format!("{}", optimizer.optimize(plan, &opt_context, observe)?);
+        assert!(formatted_plan.contains("LeftSemi Join"), "{formatted_plan}");
+        assert!(!formatted_plan.contains("Subquery:"), "{formatted_plan}");
+        assert_eq!(
+            formatted_plan
+                .matches("Filter: _timestamp >= Int64(0) AND _timestamp < Int64(5)")
+                .count(),
+            2,
+            "{formatted_plan}"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_table_scan_with_join() -> Result<()> {
         let left_table = create_table_with_name("left")?;
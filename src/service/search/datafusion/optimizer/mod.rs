@@ -15,6 +15,7 @@
 
 use std::sync::Arc;
 
+use add_prefix_range::AddPrefixRangeRule;
 use add_sort_and_limit::AddSortAndLimitRule;
 use add_timestamp::AddTimestampRule;
 use datafusion::optimizer::{
@@ -39,6 +40,7 @@ use rewrite_match::RewriteMatch;
 
 use crate::service::search::sql::Sql;
 
+pub mod add_prefix_range;
 pub mod add_sort_and_limit;
 pub mod add_timestamp;
 pub mod join_reorder;
@@ -109,6 +111,7 @@ pub fn generate_optimizer_rules(sql: &Sql) -> Vec<Arc<dyn OptimizerRule + Send +
         rules.push(Arc::new(AddSortAndLimitRule::new(limit, offset)));
     };
     rules.push(Arc::new(AddTimestampRule::new(start_time, end_time)));
+    rules.push(Arc::new(AddPrefixRangeRule::new()));
     // ************************************
 
     // Filters can't be pushed down past Limits, we should do PushDownFilter after
@@ -0,0 +1,212 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::array::{ArrayRef, BooleanArray};
+use config::utils::json;
+use datafusion::{
+    arrow::datatypes::DataType,
+    common::{cast::as_string_array, Result},
+    error::DataFusionError,
+    logical_expr::{ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility},
+    sql::sqlparser::parser::ParserError,
+};
+use once_cell::sync::Lazy;
+
+/// The name of the array_contains UDF given to DataFusion.
+pub const ARRAY_CONTAINS_UDF_NAME: &str = "array_contains";
+
+/// Implementation of array_contains, checks if an array holds a value. The array can be stored
+/// as an arrow list or as a JSON string.
+pub(crate) static ARRAY_CONTAINS_UDF: Lazy<ScalarUDF> =
+    Lazy::new(|| ScalarUDF::from(ArrayContainsUdf::new()));
+
+#[derive(Debug, Clone)]
+struct ArrayContainsUdf {
+    signature: Signature,
+}
+
+impl ArrayContainsUdf {
+    fn new() -> Self {
+        Self {
+            signature: Signature::any(2, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ArrayContainsUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        ARRAY_CONTAINS_UDF_NAME
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        if args.len() != 2 {
+            return Err(DataFusionError::SQL(
+                ParserError::ParserError(
+                    "UDF params should be: array_contains(field, value)".to_string(),
+                ),
+                None,
+            ));
+        }
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let arrays = super::json_values(&args[0])?;
+        let values = arrow::compute::cast(&args[1], &DataType::Utf8)?;
+        let values = as_string_array(&values)?;
+
+        let result = arrays
+            .iter()
+            .zip(values.iter())
+            .map(|(array, value)| match (array, value) {
+                (Some(json::Value::Array(array)), Some(value)) => Some(
+                    array
+                        .iter()
+                        .any(|v| super::stringify_json_value(v) == value),
+                ),
+                (Some(_), Some(_)) => Some(false),
+                _ => None,
+            })
+            .collect::<BooleanArray>();
+        Ok(ColumnarValue::from(Arc::new(result) as ArrayRef))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, ListBuilder, StringArray, StringBuilder};
+    use datafusion::{
+        arrow::{
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        assert_batches_eq,
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    async fn run(batch: RecordBatch, sql: &str) -> Vec<RecordBatch> {
+        let ctx = SessionContext::new();
+        ctx.register_udf(ARRAY_CONTAINS_UDF.clone());
+        let provider = MemTable::try_new(batch.schema(), vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+        ctx.sql(sql).await.unwrap().collect().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_array_contains_json_string() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("tags", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+                Arc::new(StringArray::from(vec![
+                    Some("[\"x\",\"y\"]"),
+                    Some("[\"y\",1]"),
+                    Some("not an array"),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let data = run(
+            batch.clone(),
+            "select id from t where array_contains(tags, 'y') order by id",
+        )
+        .await;
+        assert_batches_eq!(
+            vec!["+----+", "| id |", "+----+", "| a  |", "| b  |", "+----+"],
+            &data
+        );
+        let data = run(
+            batch,
+            "select id, array_contains(tags, '1') as ret from t order by id",
+        )
+        .await;
+        assert_batches_eq!(
+            vec![
+                "+----+-------+",
+                "| id | ret   |",
+                "+----+-------+",
+                "| a  | false |",
+                "| b  | true  |",
+                "| c  | false |",
+                "| d  |       |",
+                "+----+-------+",
+            ],
+            &data
+        );
+    }
+
+    #[tokio::test]
+    async fn test_array_contains_list() {
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        builder.values().append_value("x");
+        builder.values().append_value("y");
+        builder.append(true);
+        builder.values().append_value("z");
+        builder.append(true);
+        builder.append(false);
+        let tags = builder.finish();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("tags", tags.data_type().clone(), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(tags),
+            ],
+        )
+        .unwrap();
+
+        let data = run(
+            batch,
+            "select id, array_contains(tags, 'y') as ret from t order by id",
+        )
+        .await;
+        assert_batches_eq!(
+            vec![
+                "+----+-------+",
+                "| id | ret   |",
+                "+----+-------+",
+                "| a  | true  |",
+                "| b  | false |",
+                "| c  |       |",
+                "+----+-------+",
+            ],
+            &data
+        );
+    }
+}
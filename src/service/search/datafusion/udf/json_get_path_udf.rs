@@ -0,0 +1,228 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::array::{ArrayRef, StringArray};
+use config::utils::json;
+use datafusion::{
+    arrow::datatypes::DataType,
+    common::{cast::as_string_array, Result},
+    error::DataFusionError,
+    logical_expr::{ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility},
+    prelude::SessionContext,
+    sql::sqlparser::parser::ParserError,
+};
+use once_cell::sync::Lazy;
+
+/// The name of the json_get_path UDF given to DataFusion.
+pub const JSON_GET_PATH_UDF_NAME: &str = "json_get_path";
+
+/// Implementation of json_get_path, extracts the value at a path like `a.b` or `items.0.name` from
+/// an object, stored as an arrow struct or as a JSON string. The path can also be given as several
+/// arguments, e.g. `json_get_path(field, 'a', 'b')`. Returns null when the path is missing.
+pub(crate) static JSON_GET_PATH_UDF: Lazy<ScalarUDF> =
+    Lazy::new(|| ScalarUDF::from(JsonGetPathUdf::new()));
+
+/// Registers json_get_path. It has its own name, the json_get of `datafusion_functions_json`
+/// doesn't accept a dotted path or a non string column but the existing queries rely on it.
+pub fn register_json_get_path(ctx: &SessionContext) {
+    ctx.register_udf(JSON_GET_PATH_UDF.clone());
+}
+
+#[derive(Debug, Clone)]
+struct JsonGetPathUdf {
+    signature: Signature,
+}
+
+impl JsonGetPathUdf {
+    fn new() -> Self {
+        Self {
+            signature: Signature::variadic_any(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for JsonGetPathUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        JSON_GET_PATH_UDF_NAME
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        if args.len() < 2 {
+            return Err(DataFusionError::SQL(
+                ParserError::ParserError(
+                    "UDF params should be: json_get_path(field, path)".to_string(),
+                ),
+                None,
+            ));
+        }
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let values = super::json_values(&args[0])?;
+        let paths = args[1..]
+            .iter()
+            .map(|arg| arrow::compute::cast(arg, &DataType::Utf8))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let paths = paths
+            .iter()
+            .map(|path| as_string_array(path))
+            .collect::<Result<Vec<_>>>()?;
+
+        let result = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let mut value = value.as_ref()?;
+                for path in paths.iter() {
+                    if path.is_null(i) {
+                        return None;
+                    }
+                    for key in path.value(i).split('.') {
+                        value = get_value(value, key)?;
+                    }
+                }
+                match value {
+                    json::Value::Null => None,
+                    value => Some(super::stringify_json_value(value)),
+                }
+            })
+            .collect::<StringArray>();
+        Ok(ColumnarValue::from(Arc::new(result) as ArrayRef))
+    }
+}
+
+fn get_value<'a>(value: &'a json::Value, key: &str) -> Option<&'a json::Value> {
+    match value {
+        json::Value::Object(obj) => obj.get(key),
+        json::Value::Array(arr) => key.parse::<usize>().ok().and_then(|i| arr.get(i)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, Int64Array, StructArray};
+    use datafusion::{
+        arrow::{
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        assert_batches_eq,
+        datasource::MemTable,
+    };
+
+    use super::*;
+
+    async fn run(batch: RecordBatch, sql: &str) -> Vec<RecordBatch> {
+        let ctx = SessionContext::new();
+        register_json_get_path(&ctx);
+        let provider = MemTable::try_new(batch.schema(), vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+        ctx.sql(sql).await.unwrap().collect().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_get_json_string() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("attrs", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+                Arc::new(StringArray::from(vec![
+                    Some("{\"a\":{\"b\":\"x\"},\"items\":[{\"n\":1}]}"),
+                    Some("{\"a\":{\"c\":\"y\"}}"),
+                    Some("not json"),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let data = run(
+            batch.clone(),
+            "select id, json_get_path(attrs, 'a.b') as ret from t order by id",
+        )
+        .await;
+        assert_batches_eq!(
+            vec![
+                "+----+-----+",
+                "| id | ret |",
+                "+----+-----+",
+                "| a  | x   |",
+                "| b  |     |",
+                "| c  |     |",
+                "| d  |     |",
+                "+----+-----+",
+            ],
+            &data
+        );
+        let data = run(
+            batch,
+            "select id from t where json_get_path(attrs, 'items', '0.n') = '1'",
+        )
+        .await;
+        assert_batches_eq!(
+            vec!["+----+", "| id |", "+----+", "| a  |", "+----+"],
+            &data
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_get_struct() {
+        let b = Arc::new(Int64Array::from(vec![Some(1), None])) as ArrayRef;
+        let a = StructArray::from(vec![(Arc::new(Field::new("b", DataType::Int64, true)), b)]);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("attrs", a.data_type().clone(), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from(vec!["a", "b"])), Arc::new(a)],
+        )
+        .unwrap();
+
+        let data = run(
+            batch,
+            "select id, json_get_path(attrs, 'b') as ret, json_get_path(attrs, 'c.d') as missing from t order by id",
+        )
+        .await;
+        assert_batches_eq!(
+            vec![
+                "+----+-----+---------+",
+                "| id | ret | missing |",
+                "+----+-----+---------+",
+                "| a  | 1   |         |",
+                "| b  |     |         |",
+                "+----+-----+---------+",
+            ],
+            &data
+        );
+    }
+}
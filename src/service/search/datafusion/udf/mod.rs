@@ -13,11 +13,20 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::sync::Arc;
+
+use arrow::{
+    array::{Array, ArrayRef, RecordBatch},
+    datatypes::{DataType, Field, Schema},
+    json::{writer::JsonArray, WriterBuilder},
+};
 use config::utils::json;
+use datafusion::{common::cast::as_string_array, error::Result};
 
 use crate::common::meta::functions::ZoFunction;
 
 pub(crate) mod arr_descending_udf;
+pub(crate) mod array_contains_udf;
 pub(crate) mod arrcount_udf;
 pub(crate) mod arrindex_udf;
 pub(crate) mod arrjoin_udf;
@@ -26,6 +35,7 @@ pub(crate) mod arrzip_udf;
 pub(crate) mod cast_to_arr_udf;
 pub(crate) mod date_format_udf;
 pub(crate) mod histogram_udf;
+pub(crate) mod json_get_path_udf;
 pub(crate) mod match_all_udf;
 pub(crate) mod match_udf;
 pub(crate) mod regexp_udf;
@@ -44,7 +54,7 @@ pub(crate) const REGEX_MATCH_UDF_NAME: &str = "re_match";
 /// The name of the not_regex_match UDF given to DataFusion.
 pub(crate) const REGEX_NOT_MATCH_UDF_NAME: &str = "re_not_match";
 
-pub(crate) const DEFAULT_FUNCTIONS: [ZoFunction; 7] = [
+pub(crate) const DEFAULT_FUNCTIONS: [ZoFunction; 12] = [
     ZoFunction {
         name: "match_all_raw",
         text: "match_all_raw('v')",
@@ -57,6 +67,14 @@ pub(crate) const DEFAULT_FUNCTIONS: [ZoFunction; 7] = [
         name: "match_all",
         text: "match_all('v')",
     },
+    ZoFunction {
+        name: "match_all_ignore_case",
+        text: "match_all_ignore_case('v')",
+    },
+    ZoFunction {
+        name: "match_all_fields",
+        text: "match_all_fields('v', field1, field2)",
+    },
     ZoFunction {
         name: MATCH_UDF_NAME,
         text: "str_match(field, 'v')",
@@ -73,6 +91,18 @@ pub(crate) const DEFAULT_FUNCTIONS: [ZoFunction; 7] = [
         name: REGEX_NOT_MATCH_UDF_NAME,
         text: "re_not_match(field, 'pattern')",
     },
+    ZoFunction {
+        name: "regexp_like",
+        text: "regexp_like(field, 'pattern')",
+    },
+    ZoFunction {
+        name: array_contains_udf::ARRAY_CONTAINS_UDF_NAME,
+        text: "array_contains(field, 'v')",
+    },
+    ZoFunction {
+        name: json_get_path_udf::JSON_GET_PATH_UDF_NAME,
+        text: "json_get_path(field, 'a.b')",
+    },
 ];
 
 pub fn stringify_json_value(field: &json::Value) -> String {
@@ -86,3 +116,44 @@ pub fn stringify_json_value(field: &json::Value) -> String {
         _ => json::to_string(field).expect("failed to stringify json field"),
     }
 }
+
+/// Returns the values of the column as JSON, a string column holds the values as JSON strings
+/// and a column of any other type, e.g. a list or a struct, is converted to JSON. A null or a
+/// string which isn't valid JSON is `None`.
+pub(crate) fn json_values(array: &ArrayRef) -> Result<Vec<Option<json::Value>>> {
+    if array.is_empty() {
+        return Ok(vec![]);
+    }
+    match array.data_type() {
+        DataType::Utf8 => Ok(as_string_array(array)?
+            .iter()
+            .map(|v| v.and_then(|v| json::from_str(v).ok()))
+            .collect()),
+        DataType::LargeUtf8 => {
+            let array = arrow::compute::cast(array, &DataType::Utf8)?;
+            json_values(&array)
+        }
+        _ => {
+            let schema = Arc::new(Schema::new(vec![Field::new(
+                "v",
+                array.data_type().clone(),
+                true,
+            )]));
+            let batch = RecordBatch::try_new(schema, vec![array.clone()])?;
+            let mut writer = WriterBuilder::new()
+                .with_explicit_nulls(true)
+                .build::<_, JsonArray>(Vec::new());
+            writer.write(&batch)?;
+            writer.finish()?;
+            let rows: Vec<json::Value> = json::from_slice(&writer.into_inner())
+                .map_err(|e| datafusion::error::DataFusionError::External(Box::new(e)))?;
+            Ok(rows
+                .into_iter()
+                .map(|mut row| match row.get_mut("v").map(json::Value::take) {
+                    None | Some(json::Value::Null) => None,
+                    v => v,
+                })
+                .collect())
+        }
+    }
+}
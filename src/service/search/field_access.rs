@@ -0,0 +1,277 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Field level access control of the search results. The fields a role can't read are stripped
+//! from the results of a `select *`, and a query referencing one of them is rejected. The checks
+//! run in the shared search path, so every endpoint searching as a user gets them.
+
+use std::collections::HashSet;
+
+use config::{
+    get_config,
+    meta::{
+        search::Response,
+        sql::resolve_stream_names,
+        stream::{FieldAccess, StreamSettings, StreamType},
+    },
+    utils::{json, sql::referenced_columns},
+    ORIGINAL_DATA_COL_NAME,
+};
+use infra::errors::{Error, ErrorCodes};
+
+use crate::common::{infra::config::USERS, utils::auth::is_root_user};
+
+/// Checks the user can read the fields the query references, and returns the fields of its
+/// streams the user can't read, to strip them from the results. The internal searches without a
+/// user aren't checked.
+pub async fn check_request(
+    org_id: &str,
+    user_id: Option<&str>,
+    stream_type: StreamType,
+    sql: &str,
+    query_fn: Option<&str>,
+) -> Result<HashSet<String>, Error> {
+    let Some(user_id) = user_id.filter(|v| !v.is_empty()) else {
+        return Ok(HashSet::new());
+    };
+    let stream_names = resolve_stream_names(sql)
+        .map_err(|e| Error::ErrorCode(ErrorCodes::SearchSQLNotValid(e.to_string())))?;
+    let mut denied = HashSet::new();
+    for stream_name in stream_names {
+        // a stream alias is checked as the stream it belongs to
+        let stream_name =
+            infra::schema::resolve_stream_alias(org_id, &stream_name, stream_type).await;
+        if let Some(settings) = infra::schema::get_settings(org_id, &stream_name, stream_type).await
+        {
+            denied.extend(
+                get_denied_fields(org_id, user_id, &stream_name, stream_type, &settings).await?,
+            );
+        }
+    }
+
+    check_query(sql, &denied)
+        .map_err(|e| Error::ErrorCode(ErrorCodes::SearchFieldAccessDenied(e)))?;
+    // a function could copy the denied fields into other fields
+    if !denied.is_empty() && query_fn.is_some_and(|v| !v.is_empty()) {
+        return Err(Error::ErrorCode(ErrorCodes::SearchFieldAccessDenied(
+            "Functions can't be used on a stream with restricted fields".to_string(),
+        )));
+    }
+    Ok(denied)
+}
+
+/// Checks the user can read the fields of the stream, for the requests naming the fields instead
+/// of querying them, like the values of the fields
+pub async fn check_fields(
+    org_id: &str,
+    user_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    fields: &[&str],
+) -> Result<(), Error> {
+    let Some(settings) = infra::schema::get_settings(org_id, stream_name, stream_type).await else {
+        return Ok(());
+    };
+    let denied = get_denied_fields(org_id, user_id, stream_name, stream_type, &settings).await?;
+    let mut fields = fields
+        .iter()
+        .copied()
+        .filter(|f| denied.iter().any(|d| d.eq_ignore_ascii_case(f)))
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return Ok(());
+    }
+    fields.sort();
+    Err(Error::ErrorCode(ErrorCodes::SearchFieldAccessDenied(
+        format!("Unauthorized access to fields: {}", fields.join(", ")),
+    )))
+}
+
+/// Returns the fields of the stream the user can't read, the root user can read every field
+pub async fn get_denied_fields(
+    org_id: &str,
+    user_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    settings: &StreamSettings,
+) -> Result<HashSet<String>, Error> {
+    if settings.field_access.is_empty() || user_id.is_empty() || is_root_user(user_id) {
+        return Ok(HashSet::new());
+    }
+    // an unknown user could read every field
+    let Some(user) = USERS.get(&format!("{org_id}/{user_id}")).map(|v| v.clone()) else {
+        return Err(Error::Message(format!(
+            "user {user_id} not found in organization {org_id}"
+        )));
+    };
+    #[cfg(feature = "enterprise")]
+    let role = crate::common::utils::auth::get_role(user.role).to_string();
+    #[cfg(not(feature = "enterprise"))]
+    let role = user.role.to_string();
+
+    // the allow lists deny the fields of the schema, without it they would deny nothing
+    let fields = infra::schema::get(org_id, stream_name, stream_type)
+        .await?
+        .fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect::<Vec<_>>();
+    Ok(denied_fields(&settings.field_access, &role, &fields))
+}
+
+/// Returns the fields the role can't read. When the allow list of the role isn't empty every
+/// other field of the stream is denied, except the timestamp column.
+pub fn denied_fields(rules: &[FieldAccess], role: &str, fields: &[String]) -> HashSet<String> {
+    let timestamp_col = &get_config().common.column_timestamp;
+    let mut denied = HashSet::new();
+    for rule in rules.iter().filter(|r| r.role.eq_ignore_ascii_case(role)) {
+        if !rule.allow.is_empty() {
+            denied.extend(
+                fields
+                    .iter()
+                    .filter(|f| *f != timestamp_col && !rule.allow.contains(f))
+                    .cloned(),
+            );
+        }
+        denied.extend(rule.deny.iter().cloned());
+    }
+    denied
+}
+
+/// Checks the query doesn't reference a denied field, e.g. in the projection, the filters or an
+/// aggregate function
+pub fn check_query(sql: &str, denied: &HashSet<String>) -> Result<(), String> {
+    if denied.is_empty() {
+        return Ok(());
+    }
+    let columns = referenced_columns(sql).map_err(|e| e.to_string())?;
+    let mut fields = columns
+        .iter()
+        .filter(|c| denied.iter().any(|d| d.eq_ignore_ascii_case(c)))
+        .cloned()
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return Ok(());
+    }
+    fields.sort();
+    Err(format!(
+        "Unauthorized access to fields: {}",
+        fields.join(", ")
+    ))
+}
+
+/// Removes the denied fields from the hits and the columns of the response. The original record
+/// has every field, so it's removed as well.
+pub fn strip_response(res: &mut Response, denied: &HashSet<String>) {
+    if denied.is_empty() {
+        return;
+    }
+    let is_denied = |k: &String| denied.contains(k) || k == ORIGINAL_DATA_COL_NAME;
+    res.columns.retain(|c| !is_denied(c));
+    for hit in res.hits.iter_mut() {
+        if let json::Value::Object(hit) = hit {
+            hit.retain(|k, _| !is_denied(k));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> Vec<FieldAccess> {
+        vec![
+            FieldAccess {
+                role: "member".to_string(),
+                allow: vec![],
+                deny: vec!["email".to_string()],
+            },
+            FieldAccess {
+                role: "viewer".to_string(),
+                allow: vec!["message".to_string()],
+                deny: vec![],
+            },
+        ]
+    }
+
+    fn fields() -> Vec<String> {
+        ["_timestamp", "message", "email", "level"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_denied_fields() {
+        let denied = denied_fields(&rules(), "member", &fields());
+        assert_eq!(denied, HashSet::from(["email".to_string()]));
+        let denied = denied_fields(&rules(), "viewer", &fields());
+        assert_eq!(
+            denied,
+            HashSet::from(["email".to_string(), "level".to_string()])
+        );
+        assert!(denied_fields(&rules(), "admin", &fields()).is_empty());
+    }
+
+    #[test]
+    fn test_strip_select_all() {
+        let denied = denied_fields(&rules(), "member", &fields());
+        assert!(check_query("SELECT * FROM logs", &denied).is_ok());
+
+        let mut res = Response {
+            columns: vec![
+                "message".to_string(),
+                "email".to_string(),
+                "_original".to_string(),
+            ],
+            hits: vec![json::json!({
+                "_timestamp": 1,
+                "message": "hi",
+                "email": "a@b.c",
+                "_original": "{\"message\":\"hi\",\"email\":\"a@b.c\"}",
+            })],
+            ..Default::default()
+        };
+        strip_response(&mut res, &denied);
+        assert_eq!(res.columns, vec!["message".to_string()]);
+        assert_eq!(
+            res.hits,
+            vec![json::json!({"_timestamp": 1, "message": "hi"})]
+        );
+    }
+
+    #[test]
+    fn test_check_query_explicit_column() {
+        let denied = denied_fields(&rules(), "member", &fields());
+        assert!(check_query("SELECT message FROM logs WHERE level = 'info'", &denied).is_ok());
+        assert!(check_query("SELECT message, email FROM logs", &denied).is_err());
+        assert!(check_query("SELECT message FROM logs WHERE \"EMAIL\" = 'x'", &denied).is_err());
+        assert!(check_query("SELECT l.email FROM logs l", &denied).is_err());
+    }
+
+    #[test]
+    fn test_check_query_aggregate() {
+        let denied = denied_fields(&rules(), "member", &fields());
+        assert!(check_query("SELECT count(*) FROM logs", &denied).is_ok());
+        assert!(check_query("SELECT count(DISTINCT email) FROM logs", &denied).is_err());
+        assert!(
+            check_query(
+                "SELECT level, count(*) FROM logs GROUP BY level, email",
+                &denied
+            )
+            .is_err()
+        );
+    }
+}
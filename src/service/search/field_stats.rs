@@ -0,0 +1,377 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The statistics of a numeric field. The min, max and null count are taken from the column
+//! statistics in the footers of the parquet files of the file_list, so the data isn't read, a
+//! search only runs for the values the footers don't have: the average, the cardinality, and
+//! the min, max or null count when a file was written without statistics. As in the count, a
+//! file overlapping the time range is taken whole, and the data which isn't persisted yet is
+//! only seen by the search.
+
+use config::{
+    get_config,
+    meta::{
+        search::{self, FieldStatsResponse, FieldStatsSource, FieldStatsSources},
+        stream::{PartitionTimeLevel, StreamType},
+    },
+};
+use datafusion::parquet::file::{metadata::ParquetMetaData, statistics::Statistics};
+use futures::{StreamExt, TryStreamExt};
+use infra::{
+    errors::{Error, ErrorCodes},
+    schema::unwrap_stream_settings,
+};
+
+use crate::service::file_list;
+
+/// Returns the statistics of the numeric field in the time range. With `metadata_only` the
+/// average and the cardinality, which always need a search, aren't computed.
+#[allow(clippy::too_many_arguments)]
+pub async fn field_stats(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    user_id: Option<String>,
+    field: &str,
+    time_range: (i64, i64),
+    metadata_only: bool,
+) -> Result<FieldStatsResponse, Error> {
+    let start = std::time::Instant::now();
+    let (start_time, end_time) = time_range;
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(Error::ErrorCode(ErrorCodes::SearchStreamNotFound(
+            stream_name.to_string(),
+        )));
+    }
+    let settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    let denied_fields = super::field_access::get_denied_fields(
+        org_id,
+        user_id.as_deref().unwrap_or_default(),
+        stream_name,
+        stream_type,
+        &settings,
+    )
+    .await?;
+    let data_type = match schema.field_with_name(field) {
+        Ok(f) if !denied_fields.contains(field) => f.data_type().clone(),
+        _ => {
+            return Err(Error::ErrorCode(ErrorCodes::SearchFieldNotFound(
+                field.to_string(),
+            )));
+        }
+    };
+    if !data_type.is_numeric() {
+        return Err(Error::ErrorCode(
+            ErrorCodes::SearchFieldHasNoCompatibleDataType(format!(
+                "field [{field}] is {data_type}, the statistics need a numeric field"
+            )),
+        ));
+    }
+
+    let files = file_list::query(
+        org_id,
+        stream_name,
+        stream_type,
+        PartitionTimeLevel::Unset,
+        start_time,
+        end_time,
+    )
+    .await?;
+    let file_count = files.len();
+    let unsigned = data_type.is_unsigned_integer();
+    let mut stats = futures::stream::iter(files)
+        .map(|file| async move {
+            infra::storage::get_parquet_metadata(&file.key, file.meta.compressed_size as usize)
+                .await
+                .map_err(|e| Error::Message(format!("read the metadata of {}: {e}", file.key)))
+        })
+        .buffer_unordered(get_config().limit.query_thread_num.max(1))
+        .try_fold(ColumnStats::default(), |mut stats, metadata| async move {
+            stats.merge(&metadata, field, unsigned);
+            Ok(stats)
+        })
+        .await?;
+    // without files the values can only come from the data not persisted yet
+    if file_count == 0 {
+        stats.min_max_missing = true;
+        stats.null_count_missing = true;
+    }
+
+    let mut res = FieldStatsResponse {
+        field: field.to_string(),
+        min: stats.min,
+        max: stats.max,
+        null_count: stats.null_count,
+        records: stats.records,
+        file_count,
+        sources: FieldStatsSources {
+            min: source_of(stats.min_max_missing),
+            max: source_of(stats.min_max_missing),
+            null_count: source_of(stats.null_count_missing),
+            avg: (!metadata_only).then_some(FieldStatsSource::Scan),
+            cardinality: (!metadata_only).then_some(FieldStatsSource::Scan),
+        },
+        ..Default::default()
+    };
+
+    if let Some(sql) = scan_sql(stream_name, field, &stats, metadata_only) {
+        let req = search::Request {
+            query: search::Query {
+                sql,
+                from: 0,
+                size: 1,
+                start_time,
+                end_time,
+                ..Default::default()
+            },
+            encoding: search::RequestEncoding::Empty,
+            regions: vec![],
+            clusters: vec![],
+            timeout: 0,
+            search_type: Some(search::SearchEventType::Other),
+            index_type: "".to_string(),
+        };
+        let ret = super::search(trace_id, org_id, stream_type, user_id, &req).await?;
+        let hit = ret.hits.first();
+        let get = |name: &str| hit.and_then(|hit| hit.get(name));
+        if !metadata_only {
+            res.avg = get("zo_avg").and_then(|v| v.as_f64());
+            res.cardinality = get("zo_cardinality").and_then(|v| v.as_i64());
+        }
+        if stats.min_max_missing {
+            res.min = get("zo_min").and_then(|v| v.as_f64());
+            res.max = get("zo_max").and_then(|v| v.as_f64());
+        }
+        if stats.null_count_missing {
+            let records = get("zo_records")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default();
+            let not_null = get("zo_not_null")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default();
+            res.records = records;
+            res.null_count = records - not_null;
+        }
+    }
+
+    if res.records > 0 {
+        res.null_ratio = res.null_count as f64 / res.records as f64;
+    }
+    res.took = start.elapsed().as_millis() as usize;
+    Ok(res)
+}
+
+fn source_of(missing: bool) -> FieldStatsSource {
+    if missing {
+        FieldStatsSource::Scan
+    } else {
+        FieldStatsSource::Metadata
+    }
+}
+
+/// The query computing the values the metadata doesn't have, none if the metadata has them all
+fn scan_sql(
+    stream_name: &str,
+    field: &str,
+    stats: &ColumnStats,
+    metadata_only: bool,
+) -> Option<String> {
+    let mut columns = Vec::new();
+    if !metadata_only {
+        columns.push(format!("avg(\"{field}\") AS zo_avg"));
+        columns.push(format!("approx_distinct(\"{field}\") AS zo_cardinality"));
+    }
+    if stats.min_max_missing {
+        columns.push(format!("min(\"{field}\") AS zo_min"));
+        columns.push(format!("max(\"{field}\") AS zo_max"));
+    }
+    if stats.null_count_missing {
+        columns.push("count(*) AS zo_records".to_string());
+        columns.push(format!("count(\"{field}\") AS zo_not_null"));
+    }
+    if columns.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "SELECT {} FROM \"{stream_name}\"",
+        columns.join(", ")
+    ))
+}
+
+/// The statistics of a column gathered from the footers of the files
+#[derive(Debug, Default, PartialEq)]
+struct ColumnStats {
+    min: Option<f64>,
+    max: Option<f64>,
+    null_count: i64,
+    records: i64,
+    /// a row group has values but no min and max, they are searched
+    min_max_missing: bool,
+    /// a row group has no null count, it's searched
+    null_count_missing: bool,
+}
+
+impl ColumnStats {
+    /// Adds the statistics of the column in the row groups of the file. A file written before
+    /// the field was added to the stream doesn't have the column, all its rows are null.
+    fn merge(&mut self, metadata: &ParquetMetaData, field: &str, unsigned: bool) {
+        let file_metadata = metadata.file_metadata();
+        let Some(idx) = file_metadata
+            .schema_descr()
+            .columns()
+            .iter()
+            .position(|column| column.name() == field)
+        else {
+            self.records += file_metadata.num_rows();
+            self.null_count += file_metadata.num_rows();
+            return;
+        };
+        for row_group in metadata.row_groups() {
+            let rows = row_group.num_rows();
+            self.records += rows;
+            let Some(stats) = row_group.column(idx).statistics() else {
+                self.min_max_missing = true;
+                self.null_count_missing = true;
+                continue;
+            };
+            let null_count = stats.null_count_opt();
+            match null_count {
+                Some(n) => self.null_count += n as i64,
+                None => self.null_count_missing = true,
+            }
+            // a row group of nulls has no min and max
+            if null_count == Some(rows as u64) {
+                continue;
+            }
+            match min_max(stats, unsigned) {
+                Some((min, max)) => {
+                    self.min = Some(self.min.map_or(min, |v| v.min(min)));
+                    self.max = Some(self.max.map_or(max, |v| v.max(max)));
+                }
+                None => self.min_max_missing = true,
+            }
+        }
+    }
+}
+
+/// The min and max of the column chunk, the unsigned integers are stored in the signed physical
+/// types
+fn min_max(stats: &Statistics, unsigned: bool) -> Option<(f64, f64)> {
+    match stats {
+        Statistics::Int32(s) if unsigned => {
+            Some((*s.min_opt()? as u32 as f64, *s.max_opt()? as u32 as f64))
+        }
+        Statistics::Int32(s) => Some((*s.min_opt()? as f64, *s.max_opt()? as f64)),
+        Statistics::Int64(s) if unsigned => {
+            Some((*s.min_opt()? as u64 as f64, *s.max_opt()? as u64 as f64))
+        }
+        Statistics::Int64(s) => Some((*s.min_opt()? as f64, *s.max_opt()? as f64)),
+        Statistics::Float(s) => Some((*s.min_opt()? as f64, *s.max_opt()? as f64)),
+        Statistics::Double(s) => Some((*s.min_opt()?, *s.max_opt()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Float64Array, Int64Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use bytes::Bytes;
+    use datafusion::parquet::{
+        arrow::ArrowWriter,
+        file::{
+            properties::{EnabledStatistics, WriterProperties},
+            reader::{FileReader, SerializedFileReader},
+        },
+    };
+
+    use super::*;
+
+    fn write_parquet(batch: RecordBatch, statistics: EnabledStatistics) -> ParquetMetaData {
+        let props = WriterProperties::builder()
+            .set_statistics_enabled(statistics)
+            .set_max_row_group_size(2)
+            .build();
+        let mut buf = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        SerializedFileReader::new(Bytes::from(buf))
+            .unwrap()
+            .metadata()
+            .clone()
+    }
+
+    #[test]
+    fn test_column_stats_merge() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("code", DataType::Int64, true),
+            Field::new("took", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![
+                    Some(200),
+                    None,
+                    Some(404),
+                    Some(500),
+                    None,
+                ])),
+                Arc::new(Float64Array::from(vec![0.5, 1.5, 2.5, 3.5, 4.5])),
+            ],
+        )
+        .unwrap();
+        let metadata = write_parquet(batch.clone(), EnabledStatistics::Chunk);
+
+        let mut stats = ColumnStats::default();
+        stats.merge(&metadata, "code", false);
+        assert_eq!(
+            stats,
+            ColumnStats {
+                min: Some(200.0),
+                max: Some(500.0),
+                null_count: 2,
+                records: 5,
+                ..Default::default()
+            }
+        );
+        assert!(scan_sql("logs", "code", &stats, true).is_none());
+
+        // the file doesn't have the column, all its rows are null
+        stats.merge(&metadata, "status", false);
+        assert_eq!((stats.null_count, stats.records), (7, 10));
+
+        // the min and max of a file without statistics are searched
+        let mut stats = ColumnStats::default();
+        stats.merge(
+            &write_parquet(batch, EnabledStatistics::None),
+            "took",
+            false,
+        );
+        assert!(stats.min_max_missing);
+        assert_eq!(
+            scan_sql("logs", "took", &stats, true).unwrap(),
+            "SELECT min(\"took\") AS zo_min, max(\"took\") AS zo_max, count(*) AS zo_records, \
+             count(\"took\") AS zo_not_null FROM \"logs\""
+        );
+    }
+}
@@ -16,8 +16,8 @@
 use std::sync::Arc;
 
 use ::datafusion::{
-    common::tree_node::TreeNode, datasource::TableProvider, physical_plan::ExecutionPlan,
-    prelude::SessionContext,
+    common::tree_node::TreeNode, datasource::TableProvider, logical_expr::Expr,
+    physical_plan::ExecutionPlan, prelude::SessionContext,
 };
 use config::{
     cluster::LOCAL_NODE,
@@ -43,7 +43,9 @@ use crate::service::{
                 NewEmptyExecVisitor, ReplaceTableScanExec,
             },
             exec::{prepare_datafusion_context, register_udf},
+            group_limit::add_group_limit,
             table_provider::uniontable::NewUnionTable,
+            udf::json_get_path_udf::register_json_get_path,
         },
         match_file,
     },
@@ -70,6 +72,7 @@ pub async fn search(
     // register UDF
     register_udf(&ctx, &org_id)?;
     datafusion_functions_json::register_all(&mut ctx)?;
+    register_json_get_path(&ctx);
 
     // Decode physical plan from bytes
     let proto = ComposedPhysicalExtensionCodec {
@@ -139,6 +142,7 @@ pub async fn search(
         work_group: work_group.clone(),
         use_inverted_index: req.use_inverted_index,
         inverted_index_type: req.index_type.clone(),
+        range_read: cfg.limit.query_range_read && has_data_filters(empty_exec.filters()),
     });
 
     // get all tables
@@ -175,6 +179,7 @@ pub async fn search(
             schema_latest.clone(),
             &file_list,
             &req.equal_keys,
+            &req.prefix_keys,
             &req.match_all_keys,
             empty_exec.sorted_by_time(),
             file_stats_cache.clone(),
@@ -262,11 +267,31 @@ pub async fn search(
     let mut rewriter = ReplaceTableScanExec::new(union_exec);
     physical_plan = physical_plan.rewrite(&mut rewriter)?.data;
 
+    // the partial aggregations of this node can only hold the max groups, truncating is left to
+    // the final aggregation of the leader, which merges the groups of all the nodes
+    if cfg.limit.query_max_groups_policy == "reject" {
+        let max_groups = super::super::get_query_max_groups(&org_id).await;
+        if max_groups > 0 {
+            physical_plan = add_group_limit(physical_plan, max_groups as usize, false)?;
+        }
+    }
+
     log::info!("[trace_id {trace_id}] flight->search: generated physical plan");
 
     Ok((ctx, physical_plan, scan_stats))
 }
 
+/// The query filters on more than the time range, so the row groups of the files can be pruned
+fn has_data_filters(filters: &[Expr]) -> bool {
+    let column_timestamp = &get_config().common.column_timestamp;
+    filters.iter().any(|filter| {
+        filter
+            .column_refs()
+            .iter()
+            .any(|column| &column.name != column_timestamp)
+    })
+}
+
 #[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip_all, fields(org_id = org_id, stream_name = stream_name))]
 async fn get_file_list_by_ids(
@@ -37,6 +37,8 @@ pub struct QueryParams {
     pub work_group: Option<String>,
     pub use_inverted_index: bool,
     pub inverted_index_type: Option<String>,
+    /// Read the needed ranges of the parquet files instead of caching the whole files
+    pub range_read: bool,
 }
 
 fn check_memory_circuit_breaker(trace_id: &str, scan_stats: &ScanStats) -> Result<()> {
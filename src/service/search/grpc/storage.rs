@@ -30,7 +30,7 @@ use config::{
     FILE_EXT_PARQUET, INDEX_FIELD_NAME_FOR_ALL,
 };
 use datafusion::execution::cache::cache_manager::FileStatisticsCache;
-use futures::future::try_join_all;
+use futures::future::{join_all, try_join_all};
 use hashbrown::HashMap;
 use infra::{
     cache::file_data,
@@ -39,6 +39,7 @@ use infra::{
     storage,
 };
 use itertools::Itertools;
+use object_store::ObjectStore;
 use proto::cluster_rpc::KvItem;
 use tokio::sync::Semaphore;
 use tracing::Instrument;
@@ -50,13 +51,18 @@ use crate::service::{
 
 type CachedFiles = (usize, usize);
 
+/// The length of the footer metadata and the magic number at the end of a parquet file
+const PARQUET_FOOTER_SIZE: usize = 8;
+
 /// search in remote object storage
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(name = "service:search:grpc:storage", skip_all, fields(org_id = query.org_id, stream_name = query.stream_name))]
 pub async fn search(
     query: Arc<super::QueryParams>,
     schema: Arc<Schema>,
     file_list: &[FileKey],
     req_equal_terms: &[KvItem],
+    req_prefix_terms: &[KvItem],
     req_match_terms: &[String],
     sorted_by_time: bool,
     file_stat_cache: Option<FileStatisticsCache>,
@@ -131,6 +137,7 @@ pub async fn search(
             query.clone(),
             &mut files,
             req_equal_terms,
+            req_prefix_terms,
             req_match_terms,
         )
         .await?;
@@ -211,14 +218,11 @@ pub async fn search(
         super::check_memory_circuit_breaker(&query.trace_id, &scan_stats)?;
     }
 
-    // load files to local cache
-    let (cache_type, deleted_files, (mem_cached_files, disk_cached_files)) = cache_files(
-        &query.trace_id,
-        &files.iter().map(|f| f.key.as_ref()).collect_vec(),
-        &scan_stats,
-    )
-    .instrument(enter_span.clone())
-    .await?;
+    // load files to local cache, unless only the needed ranges of the files are read
+    let (cache_type, deleted_files, (mem_cached_files, disk_cached_files)) =
+        prepare_files(&query, &**storage::DEFAULT, &files, &scan_stats)
+            .instrument(enter_span.clone())
+            .await?;
     if !deleted_files.is_empty() {
         // remove deleted files from files_group
         for (_, g_files) in files_group.iter_mut() {
@@ -295,6 +299,109 @@ pub async fn search(
     Ok((tables, scan_stats))
 }
 
+/// Loads the files to the local cache. With `range_read` only the needed ranges of the files are
+/// read from `store` by the query, so the files not cached are only checked to still exist.
+async fn prepare_files(
+    query: &super::QueryParams,
+    store: &dyn ObjectStore,
+    files: &[FileKey],
+    scan_stats: &ScanStats,
+) -> Result<(file_data::CacheType, Vec<String>, CachedFiles), Error> {
+    if !query.range_read {
+        let files = files.iter().map(|f| f.key.as_ref()).collect_vec();
+        return cache_files(&query.trace_id, &files, scan_stats).await;
+    }
+    let (failed_files, cached_files) = check_files(store, files).await;
+    let mut deleted_files = Vec::new();
+    for (file, e) in failed_files {
+        if let Some(file) = remove_deleted_file(&query.trace_id, file, &e).await {
+            deleted_files.push(file);
+        }
+    }
+    Ok((file_data::CacheType::None, deleted_files, cached_files))
+}
+
+/// Checks the files not in the local cache still exist in the storage, only the footer of the
+/// parquet files is read. Returns the files failed to read with their error.
+#[tracing::instrument(name = "service:search:grpc:storage:check_files", skip_all)]
+async fn check_files(
+    store: &dyn ObjectStore,
+    files: &[FileKey],
+) -> (Vec<(String, anyhow::Error)>, CachedFiles) {
+    let semaphore = &Semaphore::new(get_config().limit.query_thread_num);
+    let tasks = files.iter().map(|file| async move {
+        let _permit = semaphore.acquire().await.unwrap();
+        if file_data::memory::exist(&file.key).await {
+            return (None, true, false);
+        }
+        if file_data::disk::exist(&file.key).await {
+            return (None, false, true);
+        }
+        let size = file.meta.compressed_size.max(0) as usize;
+        let ret = if size == 0 {
+            Err(anyhow::anyhow!("file {} data size is zero", file.key))
+        } else {
+            let range = size.saturating_sub(PARQUET_FOOTER_SIZE)..size;
+            store
+                .get_range(&file.key.as_str().into(), range)
+                .await
+                .map(|_| ())
+                .map_err(anyhow::Error::from)
+        };
+        (ret.err().map(|e| (file.key.clone(), e)), false, false)
+    });
+
+    let mut failed_files = Vec::new();
+    let mut mem_cached_files = 0;
+    let mut disk_cached_files = 0;
+    for (failed, mem_exists, disk_exists) in join_all(tasks).await {
+        if mem_exists {
+            mem_cached_files += 1;
+        } else if disk_exists {
+            disk_cached_files += 1;
+        }
+        failed_files.extend(failed);
+    }
+    (failed_files, (mem_cached_files, disk_cached_files))
+}
+
+/// In case where the parquet file is not found or has no data, we assume that it must have been
+/// deleted by some external entity, and hence we should remove the entry from file_list table.
+/// Returns the file when it's removed.
+///
+/// Caching Index files is a little different from caching parquet filesd as index files are not
+/// present in the file_list table as of now (TODO: part of phase 2).
+async fn remove_deleted_file(
+    trace_id: &str,
+    file_name: String,
+    e: &anyhow::Error,
+) -> Option<String> {
+    if !is_deleted_file(&file_name, e) {
+        log::warn!(
+            "[trace_id {trace_id}] search->storage: read file {file_name} err: {}",
+            e
+        );
+        return None;
+    }
+    // delete file from file list
+    log::warn!("found invalid file: {}", file_name);
+    if let Err(e) = file_list::delete_parquet_file(&file_name, true).await {
+        log::error!(
+            "[trace_id {trace_id}] search->storage: delete from file_list err: {}",
+            e
+        );
+    }
+    Some(file_name)
+}
+
+fn is_deleted_file(file_name: &str, e: &anyhow::Error) -> bool {
+    let e = e.to_string().to_lowercase();
+    (e.contains("not found") || e.contains("data size is zero"))
+        // only proceed if the file_name has parquet extension
+        // FIXME: Revisit, after phase 2 of FST Index
+        && file_name.ends_with(FILE_EXT_PARQUET)
+}
+
 #[tracing::instrument(name = "service:search:grpc:storage:cache_files", skip_all)]
 async fn cache_files(
     trace_id: &str,
@@ -327,8 +434,8 @@ async fn cache_files(
         let trace_id = trace_id.to_string();
         let file_name = file.to_string();
         let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let task: tokio::task::JoinHandle<(Option<String>, bool, bool)> = tokio::task::spawn(
-            async move {
+        let task: tokio::task::JoinHandle<(Option<String>, bool, bool)> =
+            tokio::task::spawn(async move {
                 let cfg = get_config();
                 let ret = match cache_type {
                     file_data::CacheType::Memory => {
@@ -363,43 +470,13 @@ async fn cache_files(
                     }
                     _ => (None, false, false),
                 };
-                // In case where the parquet file is not found or has no data, we assume that it
-                // must have been deleted by some external entity, and hence we
-                // should remove the entry from file_list table.
-                //
-                // Caching Index files is a little different from caching parquet filesd as index
-                // files are not present in the file_list table as of now
-                // (TODO: part of phase 2).
-                let file_name = if let Some(e) = ret.0 {
-                    if (e.to_string().to_lowercase().contains("not found")
-                        || e.to_string().to_lowercase().contains("data size is zero"))
-                    // only proceed if the file_name has parquet extension
-                    // FIXME: Revisit, after phase 2 of FST Index
-                        && file_name.ends_with(FILE_EXT_PARQUET)
-                    {
-                        // delete file from file list
-                        log::warn!("found invalid file: {}", file_name);
-                        if let Err(e) = file_list::delete_parquet_file(&file_name, true).await {
-                            log::error!(
-                                "[trace_id {trace_id}] search->storage: delete from file_list err: {}",
-                                e
-                            );
-                        }
-                        Some(file_name)
-                    } else {
-                        log::warn!(
-                            "[trace_id {trace_id}] search->storage: download file to cache err: {}",
-                            e
-                        );
-                        None
-                    }
-                } else {
-                    None
+                let file_name = match ret.0 {
+                    Some(e) => remove_deleted_file(&trace_id, file_name, &e).await,
+                    None => None,
                 };
                 drop(permit);
                 (file_name, ret.1, ret.2)
-            },
-        );
+            });
         tasks.push(task);
     }
 
@@ -442,6 +519,7 @@ async fn filter_file_list_by_inverted_index(
     query: Arc<super::QueryParams>,
     file_list: &mut Vec<FileKey>,
     equal_terms: &[KvItem],
+    prefix_terms: &[KvItem],
     match_terms: &[String],
 ) -> Result<usize, Error> {
     let start = std::time::Instant::now();
@@ -457,11 +535,24 @@ async fn filter_file_list_by_inverted_index(
     let index_fields = get_stream_setting_index_fields(&stream_settings);
     let index_terms_orig = super::super::filter_index_fields(&equal_terms, &index_fields);
     let index_terms = generate_filter_from_equal_items(&index_terms_orig);
+    let prefix_terms: Vec<(String, String)> = prefix_terms
+        .iter()
+        .map(|v| (v.key.to_string(), v.value.to_string()))
+        .collect::<Vec<_>>();
+    let prefix_terms = generate_filter_from_equal_items(&super::super::filter_index_fields(
+        &prefix_terms,
+        &index_fields,
+    ));
 
     // Cache the corresponding Index files
     let cfg = get_config();
     let mut scan_stats = ScanStats::new();
-    let mut file_list_map = file_list.drain(..).into_group_map_by(|f| f.key.clone());
+    // the files without an index file are searched whole, their index isn't fetched
+    let (no_index_files, indexed_files): (Vec<_>, Vec<_>) =
+        file_list.drain(..).partition(|f| f.meta.index_size == 0);
+    let mut file_list_map = indexed_files
+        .into_iter()
+        .into_group_map_by(|f| f.key.clone());
     let index_file_names = file_list_map
         .keys()
         .filter_map(|f| convert_parquet_idx_file_name(f))
@@ -510,11 +601,13 @@ async fn filter_file_list_by_inverted_index(
             .collect_vec(),
     );
     let index_terms = Arc::new(index_terms);
+    let prefix_terms = Arc::new(prefix_terms);
     let semaphore = std::sync::Arc::new(Semaphore::new(cfg.limit.query_thread_num));
     let mut tasks = Vec::new();
     for file in file_list_map.keys() {
         let full_text_term_clone = full_text_terms.clone();
         let index_terms_clone = index_terms.clone();
+        let prefix_terms_clone = prefix_terms.clone();
         let file_name = file.clone();
         let trace_id_clone = query.trace_id.to_string();
         let permit = semaphore.clone().acquire_owned().await.unwrap();
@@ -526,6 +619,7 @@ async fn filter_file_list_by_inverted_index(
                 &file_name,
                 full_text_term_clone,
                 index_terms_clone,
+                prefix_terms_clone,
             )
             .await;
             drop(permit);
@@ -541,8 +635,8 @@ async fn filter_file_list_by_inverted_index(
     {
         // Each result corresponds to a file in the file list
         match result {
-            Ok((file_name, bitvec)) => {
-                if let Some(res) = bitvec {
+            Ok((file_name, bitvec)) => match bitvec {
+                Some(res) if res.any() => {
                     // Replace the segment IDs in the existing `FileKey` with the new found segments
                     let file = file_list_map
                         .get_mut(&file_name)
@@ -552,13 +646,15 @@ async fn filter_file_list_by_inverted_index(
                         .unwrap();
                     file.segment_ids = Some(res.clone().into_vec());
                     log::info!(
-                        "[trace_id {}] search->storage: Final bitmap for fts_terms {:?} and index_terms: {:?} length {}",
+                        "[trace_id {}] search->storage: Final bitmap for fts_terms {:?}, index_terms: {:?} and prefix_terms: {:?} length {}",
                         query.trace_id,
                         *full_text_terms,
                         index_terms,
+                        prefix_terms,
                         res.len(),
                     );
-                } else {
+                }
+                Some(_) => {
                     // if the bitmap is empty then we remove the file from the list
                     log::info!(
                         "[trace_id {}] search->storage: no match found in index for file {}",
@@ -567,7 +663,9 @@ async fn filter_file_list_by_inverted_index(
                     );
                     file_list_map.remove(&file_name);
                 }
-            }
+                // the index doesn't cover the terms, search the whole file
+                None => {}
+            },
             Err(e) => {
                 log::warn!(
                     "[trace_id {}] search->storage: error filtering file via FST index. Keep file to search. error: {}",
@@ -579,6 +677,7 @@ async fn filter_file_list_by_inverted_index(
         }
     }
     file_list.extend(file_list_map.into_values().flatten());
+    file_list.extend(no_index_files);
     Ok(start.elapsed().as_millis() as usize)
 }
 
@@ -602,13 +701,15 @@ async fn fetch_file(file_name: &str) -> anyhow::Result<Vec<u8>> {
     storage::get(file_name).await.map(|bytes| bytes.to_vec())
 }
 
+/// Searches the index file of the parquet file, returns the matched segments of the parquet
+/// file, `None` if the file has no index file
 async fn inverted_index_search_in_file(
     trace_id: &str,
     parquet_file_name: &str,
     fts_terms: Arc<Vec<String>>,
     index_terms: Arc<Vec<(String, Vec<String>)>>,
+    prefix_terms: Arc<Vec<(String, Vec<String>)>>,
 ) -> anyhow::Result<(String, Option<BitVec>)> {
-    let cfg = config::get_config();
     let Some(index_file_name) = convert_parquet_idx_file_name(parquet_file_name) else {
         return Err(anyhow::anyhow!(
             "[trace_id {trace_id}] search->storage: Unable to convert parquet file name {} to index file name",
@@ -628,36 +729,61 @@ async fn inverted_index_search_in_file(
         Ok(bytes) => bytes,
     };
 
+    let res = search_index(
+        trace_id,
+        &index_file_name,
+        compressed_index_blob,
+        &fts_terms,
+        &index_terms,
+        &prefix_terms,
+    )
+    .await?;
+    Ok((parquet_file_name.into(), res))
+}
+
+/// Matches the terms against the index of a parquet file, the matches of the full text search,
+/// of the equal terms and of the prefix terms are intersected. Returns `None` when the index
+/// doesn't cover any of the terms, eg: the field was indexed after the file was written, so the
+/// whole file has to be searched. An empty bitmap means no segment matches and the file can be
+/// skipped.
+async fn search_index(
+    trace_id: &str,
+    index_file_name: &str,
+    compressed_index_blob: Vec<u8>,
+    fts_terms: &[String],
+    index_terms: &[(String, Vec<String>)],
+    prefix_terms: &[(String, Vec<String>)],
+) -> anyhow::Result<Option<BitVec>> {
+    let cfg = config::get_config();
     let mut index_reader = create_index_reader_from_puffin_bytes(compressed_index_blob).await?;
     let file_meta = index_reader.metadata().await?;
 
-    let mut res = BitVec::new();
+    let mut res = None;
 
-    if let Some(column_index_meta) = &file_meta.metas.get(INDEX_FIELD_NAME_FOR_ALL) {
-        // TODO: Add Eq and check performance
-        let matched_bv = match cfg.common.full_text_search_type.as_str() {
-            "eq" => {
-                let mut searcher = ExactSearch::new(fts_terms.as_ref(), column_index_meta);
-                searcher.search(&mut index_reader).await
-            }
-            "contains" => {
-                let mut searcher = SubstringSearch::new(fts_terms.as_ref(), column_index_meta);
-                searcher.search(&mut index_reader).await
-            }
-            // Default to prefix search
-            _ => {
-                let mut searcher = PrefixSearch::new(fts_terms.as_ref(), column_index_meta);
-                searcher.search(&mut index_reader).await
-            }
+    if !fts_terms.is_empty() {
+        let matched_bv = match file_meta.metas.get(INDEX_FIELD_NAME_FOR_ALL) {
+            // no term of the full text search fields in this file
+            None => Ok(BitVec::new()),
+            // TODO: Add Eq and check performance
+            Some(column_index_meta) => match cfg.common.full_text_search_type.as_str() {
+                "eq" => {
+                    let mut searcher = ExactSearch::new(fts_terms, column_index_meta);
+                    searcher.search(&mut index_reader).await
+                }
+                "contains" => {
+                    let mut searcher = SubstringSearch::new(fts_terms, column_index_meta);
+                    searcher.search(&mut index_reader).await
+                }
+                // Default to prefix search
+                _ => {
+                    let mut searcher = PrefixSearch::new(fts_terms, column_index_meta);
+                    searcher.search(&mut index_reader).await
+                }
+            },
         };
 
         match matched_bv {
-            Ok(bitmap) => {
-                if res.len() < bitmap.len() {
-                    res.resize(bitmap.len(), false);
-                }
-                res |= bitmap;
-            }
+            Ok(bitmap) => intersect_segments(&mut res, bitmap),
             Err(e) => {
                 log::warn!(
                     "[trace_id {trace_id}] search->storage: Error loading FST map from index file {} for full text search with error {}. Keep the file",
@@ -668,33 +794,375 @@ async fn inverted_index_search_in_file(
         }
     }
 
-    if !index_terms.is_empty() {
-        for (col, index_terms) in index_terms.iter() {
-            if let Some(column_index_meta) = file_meta.metas.get(col) {
-                let mut secondary_index_match = ExactSearch::new(index_terms, column_index_meta);
-                match secondary_index_match.search(&mut index_reader).await {
-                    Ok(bitmap) => {
-                        if res.len() < bitmap.len() {
-                            res.resize(bitmap.len(), false);
-                        }
-                        res &= bitmap;
-                    }
-                    Err(e) => {
-                        log::warn!(
-                            "[trace_id {trace_id}] search->storage: Error loading FST map from index file {} for column {} with error {}. Keep the file",
-                            index_file_name,
-                            col,
-                            e.to_string()
-                        );
-                    }
-                }
+    let column_terms = index_terms
+        .iter()
+        .map(|(col, terms)| (col, terms, false))
+        .chain(prefix_terms.iter().map(|(col, terms)| (col, terms, true)));
+    for (col, terms, is_prefix) in column_terms {
+        // the file was written before the field was indexed
+        let Some(column_index_meta) = file_meta.metas.get(col) else {
+            continue;
+        };
+        let matched_bv = if is_prefix {
+            let mut searcher = PrefixSearch::new(terms, column_index_meta);
+            searcher.search(&mut index_reader).await
+        } else {
+            let mut searcher = ExactSearch::new(terms, column_index_meta);
+            searcher.search(&mut index_reader).await
+        };
+        match matched_bv {
+            Ok(bitmap) => intersect_segments(&mut res, bitmap),
+            Err(e) => {
+                log::warn!(
+                    "[trace_id {trace_id}] search->storage: Error loading FST map from index file {} for column {} with error {}. Keep the file",
+                    index_file_name,
+                    col,
+                    e.to_string()
+                );
             }
         }
     }
 
-    Ok(if res.is_empty() {
-        (parquet_file_name.into(), None) // no match -> skip the file in search
-    } else {
-        (parquet_file_name.into(), Some(res)) // match -> take the file in search
-    })
+    Ok(res)
+}
+
+/// Intersects the matched segments, a segment past the end of a bitmap is not matched
+fn intersect_segments(res: &mut Option<BitVec>, mut bitmap: BitVec) {
+    match res {
+        None => *res = Some(bitmap),
+        Some(res) => {
+            let len = res.len().max(bitmap.len());
+            res.resize(len, false);
+            bitmap.resize(len, false);
+            *res &= bitmap;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Range, sync::Mutex};
+
+    use arrow::{
+        array::{Int64Array, StringArray},
+        record_batch::RecordBatch,
+    };
+    use arrow_schema::{DataType, Field};
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use config::meta::stream::{FileMeta, StreamType};
+    use datafusion::{
+        parquet::{
+            arrow::ArrowWriter,
+            file::{
+                properties::WriterProperties,
+                reader::{FileReader, SerializedFileReader},
+            },
+        },
+        prelude::{ParquetReadOptions, SessionContext},
+    };
+    use futures::stream::BoxStream;
+    use infra::storage::{remote::Remote, retry::RetryPolicy};
+    use object_store::{
+        memory::InMemory, path::Path, GetOptions, GetRange, GetResult, ListResult, MultipartUpload,
+        ObjectMeta, PutMultipartOpts, PutOptions, PutPayload, PutResult, Result,
+    };
+
+    use super::*;
+
+    fn index_bytes(request_ids: Vec<&str>) -> Vec<u8> {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "request_id",
+            DataType::Utf8,
+            false,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(request_ids))]).unwrap();
+        let (bytes, _) = crate::job::files::parquet::prepare_fst_index_bytes(
+            batch,
+            &[],
+            &["request_id".to_string()],
+        )
+        .unwrap()
+        .unwrap();
+        bytes
+    }
+
+    async fn matches(
+        index: &[u8],
+        index_terms: &[(String, Vec<String>)],
+        prefix_terms: &[(String, Vec<String>)],
+    ) -> Option<bool> {
+        search_index(
+            "trace_id",
+            "file.puffin",
+            index.to_vec(),
+            &[],
+            index_terms,
+            prefix_terms,
+        )
+        .await
+        .unwrap()
+        .map(|segments| segments.any())
+    }
+
+    /// A store recording the ranges read, a whole object read is recorded as `0..usize::MAX`
+    #[derive(Debug, Default)]
+    struct RangeRecorder {
+        inner: InMemory,
+        ranges: Arc<Mutex<Vec<Range<usize>>>>,
+    }
+
+    impl std::fmt::Display for RangeRecorder {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("range recorder")
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for RangeRecorder {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> Result<PutResult> {
+            self.inner.put_opts(location, payload, opts).await
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            location: &Path,
+            opts: PutMultipartOpts,
+        ) -> Result<Box<dyn MultipartUpload>> {
+            self.inner.put_multipart_opts(location, opts).await
+        }
+
+        async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+            if !options.head {
+                let range = match &options.range {
+                    Some(GetRange::Bounded(r)) => r.clone(),
+                    _ => 0..usize::MAX,
+                };
+                self.ranges.lock().unwrap().push(range);
+            }
+            self.inner.get_opts(location, options).await
+        }
+
+        async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+            self.ranges.lock().unwrap().push(range.clone());
+            self.inner.get_range(location, range).await
+        }
+
+        async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+            self.ranges.lock().unwrap().extend_from_slice(ranges);
+            self.inner.get_ranges(location, ranges).await
+        }
+
+        async fn delete(&self, location: &Path) -> Result<()> {
+            self.inner.delete(location).await
+        }
+
+        fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+            self.inner.copy(from, to).await
+        }
+
+        async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+            self.inner.copy_if_not_exists(from, to).await
+        }
+    }
+
+    /// A parquet file of 4 row groups, with the values 0..1000, 1000..2000, ...
+    fn parquet_file() -> Bytes {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("_timestamp", DataType::Int64, false),
+            Field::new("v", DataType::Int64, false),
+        ]));
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(1000)
+            .build();
+        let mut buf = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buf, schema.clone(), Some(props)).unwrap();
+        let values = Arc::new(Int64Array::from_iter_values(0..4000));
+        let batch = RecordBatch::try_new(schema, vec![values.clone(), values]).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        Bytes::from(buf)
+    }
+
+    fn query_params(range_read: bool) -> super::super::QueryParams {
+        super::super::QueryParams {
+            trace_id: "trace_id".to_string(),
+            org_id: "default".to_string(),
+            stream_type: StreamType::Logs,
+            stream_name: "t".to_string(),
+            time_range: None,
+            work_group: None,
+            use_inverted_index: false,
+            inverted_index_type: None,
+            range_read,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_index_prunes_files() {
+        let first = index_bytes(vec!["abc-1", "abc-2"]);
+        let second = index_bytes(vec!["xyz-1"]);
+        let terms = |values: &[&str]| {
+            vec![(
+                "request_id".to_string(),
+                values.iter().map(|v| v.to_string()).collect(),
+            )]
+        };
+
+        // equal terms
+        assert_eq!(matches(&first, &terms(&["xyz-1"]), &[]).await, Some(false));
+        assert_eq!(matches(&second, &terms(&["xyz-1"]), &[]).await, Some(true));
+        assert_eq!(
+            matches(&first, &terms(&["abc-2", "xyz-2"]), &[]).await,
+            Some(true)
+        );
+
+        // prefix terms, shorter than the indexed terms
+        assert_eq!(matches(&first, &[], &terms(&["abc"])).await, Some(true));
+        assert_eq!(matches(&second, &[], &terms(&["abc"])).await, Some(false));
+
+        // both must match
+        assert_eq!(
+            matches(&first, &terms(&["abc-1"]), &terms(&["xyz"])).await,
+            Some(false)
+        );
+
+        // a field not in the index doesn't prune the file
+        let other = vec![("trace_id".to_string(), vec!["abc-1".to_string()])];
+        assert_eq!(matches(&second, &other, &[]).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_range_read_pruned_row_groups() {
+        let data = parquet_file();
+        let row_groups = SerializedFileReader::new(data.clone())
+            .unwrap()
+            .metadata()
+            .row_groups()
+            .iter()
+            .map(|rg| {
+                let (start, end) = rg.columns().iter().fold((u64::MAX, 0), |(s, e), c| {
+                    let (offset, len) = c.byte_range();
+                    (s.min(offset), e.max(offset + len))
+                });
+                start as usize..end as usize
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(row_groups.len(), 4);
+
+        let store = RangeRecorder::default();
+        let ranges = store.ranges.clone();
+        let key = "files/default/logs/t/2024/01/01/00/data.parquet";
+        store.put(&key.into(), data.clone().into()).await.unwrap();
+        let remote = Arc::new(Remote::new(Box::new(store), RetryPolicy::from_config()));
+
+        // the file isn't downloaded to the cache, only its footer is checked
+        let file = FileKey::new(
+            key,
+            FileMeta {
+                compressed_size: data.len() as i64,
+                ..Default::default()
+            },
+            false,
+        );
+        let (cache_type, deleted_files, _) = prepare_files(
+            &query_params(true),
+            remote.as_ref(),
+            &[file],
+            &ScanStats::new(),
+        )
+        .await
+        .unwrap();
+        assert!(cache_type == file_data::CacheType::None);
+        assert!(deleted_files.is_empty());
+        assert_eq!(
+            *ranges.lock().unwrap(),
+            vec![data.len() - PARQUET_FOOTER_SIZE..data.len()]
+        );
+        ranges.lock().unwrap().clear();
+
+        let ctx = SessionContext::new();
+        ctx.register_object_store(&url::Url::parse("mock://").unwrap(), remote);
+        ctx.register_parquet(
+            "t",
+            "mock:///files/default/logs/t/",
+            ParquetReadOptions::default(),
+        )
+        .await
+        .unwrap();
+        let batches = ctx
+            .sql("SELECT v FROM t WHERE v >= 2100 AND v < 2200")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 100);
+
+        // only the footer and the third row group are read
+        let ranges = ranges.lock().unwrap();
+        assert!(!ranges.is_empty());
+        assert!(ranges.iter().all(|r| r.end <= data.len()), "{ranges:?}");
+        let overlaps = |a: &Range<usize>, b: &Range<usize>| a.start < b.end && b.start < a.end;
+        for (i, row_group) in row_groups.iter().enumerate() {
+            let read = ranges.iter().any(|r| overlaps(r, row_group));
+            assert_eq!(
+                read,
+                i == 2,
+                "row group {i} {row_group:?}, ranges {ranges:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_files_deleted() {
+        let store = InMemory::new();
+        let data = parquet_file();
+        let prefix = "files/default/logs/t/2024/01/01/00";
+        store
+            .put(&format!("{prefix}/a.parquet").into(), data.clone().into())
+            .await
+            .unwrap();
+        let remote = Remote::new(Box::new(store), RetryPolicy::from_config());
+        let file = |name: &str, size: usize| {
+            FileKey::new(
+                &format!("{prefix}/{name}"),
+                FileMeta {
+                    compressed_size: size as i64,
+                    ..Default::default()
+                },
+                false,
+            )
+        };
+        let files = [
+            file("a.parquet", data.len()),
+            file("b.parquet", data.len()),
+            file("c.parquet", 0),
+        ];
+
+        let (failed_files, cached_files) = check_files(&remote, &files).await;
+        assert_eq!(cached_files, (0, 0));
+        let failed_files = failed_files
+            .iter()
+            .map(|(file, e)| (file.as_str(), is_deleted_file(file, e)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failed_files,
+            vec![(files[1].key.as_str(), true), (files[2].key.as_str(), true)]
+        );
+    }
 }
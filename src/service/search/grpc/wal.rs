@@ -526,3 +526,58 @@ pub fn adapt_batch(table_schema: Arc<Schema>, batch: &RecordBatch) -> RecordBatc
     }
     RecordBatch::try_new(table_schema, cols).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{DataType, Field};
+    use config::{
+        meta::stream::{StreamSettings, StreamType},
+        utils::json,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_search_memtable_fresh_records() {
+        let (org_id, stream_name) = ("wal_only_org", "wal_only_stream");
+        infra::schema::STREAM_SETTINGS.write().await.insert(
+            format!("{org_id}/{}/{stream_name}", StreamType::Logs),
+            StreamSettings::default(),
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("_timestamp", DataType::Int64, false),
+            Field::new("message", DataType::Utf8, true),
+        ]));
+        let now = chrono::Utc::now().timestamp_micros();
+        let data = (0..3)
+            .map(|i| Arc::new(json::json!({"_timestamp": now + i, "message": format!("m{i}")})))
+            .collect::<Vec<_>>();
+        let entry = ingester::Entry {
+            stream: stream_name.into(),
+            schema_key: "wal_only_schema".into(),
+            partition_key: "2024/01/01/00".into(),
+            data_size: data.iter().map(|v| v.to_string().len()).sum(),
+            data,
+        };
+        ingester::get_writer(0, org_id, &StreamType::Logs.to_string(), stream_name)
+            .await
+            .write(schema.clone(), entry, false)
+            .await
+            .unwrap();
+
+        let query = Arc::new(super::super::QueryParams {
+            trace_id: "wal_only".to_string(),
+            org_id: org_id.to_string(),
+            stream_type: StreamType::Logs,
+            stream_name: stream_name.to_string(),
+            time_range: Some((now, now + 3)),
+            work_group: None,
+            use_inverted_index: false,
+            inverted_index_type: None,
+            range_read: false,
+        });
+        let (tables, scan_stats) = search_memtable(query, schema, &[], false).await.unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(scan_stats.records, 3);
+    }
+}
@@ -36,6 +36,7 @@ use config::{
 use hashbrown::HashMap;
 use infra::{
     errors::{Error, ErrorCodes},
+    file_list::FileId,
     schema::{get_stream_setting_index_fields, unwrap_stream_settings},
 };
 use once_cell::sync::Lazy;
@@ -67,13 +68,21 @@ use crate::{
 
 pub(crate) mod cache;
 pub(crate) mod cluster;
+pub(crate) mod cost;
+pub(crate) mod count;
 pub(crate) mod datafusion;
+pub(crate) mod field_access;
+pub(crate) mod field_stats;
 pub(crate) mod grpc;
 pub(crate) mod request;
+pub(crate) mod sample;
 pub(crate) mod sql;
 #[cfg(feature = "enterprise")]
 pub(crate) mod super_cluster;
+pub(crate) mod timings;
 pub(crate) mod utlis;
+#[cfg(not(feature = "enterprise"))]
+pub(crate) mod work_group;
 
 // Checks for #ResultArray#
 pub static RESULT_ARRAY: Lazy<Regex> =
@@ -195,6 +204,9 @@ pub async fn search_multi(
                 multi_res.response_type = res.response_type;
                 multi_res.trace_id = res.trace_id;
                 multi_res.cached_ratio = res.cached_ratio;
+                for warning in res.warnings {
+                    multi_res.add_warning(warning);
+                }
                 log::debug!(
                     "search_multi: res.hits.len() for query timerange to {} from {} : {}",
                     req.query.end_time,
@@ -353,6 +365,8 @@ pub async fn search_multi(
     Ok(multi_res)
 }
 
+/// Searches as the user, the query can't reference the fields the user can't read and they are
+/// stripped from the results
 #[tracing::instrument(name = "service:search:enter", skip_all)]
 pub async fn search(
     trace_id: &str,
@@ -360,6 +374,28 @@ pub async fn search(
     stream_type: StreamType,
     user_id: Option<String>,
     in_req: &search::Request,
+) -> Result<search::Response, Error> {
+    let denied_fields = field_access::check_request(
+        org_id,
+        user_id.as_deref(),
+        stream_type,
+        &in_req.query.sql,
+        in_req.query.query_fn.as_deref(),
+    )
+    .await?;
+    let mut res = search_unchecked(trace_id, org_id, stream_type, user_id, in_req).await?;
+    field_access::strip_response(&mut res, &denied_fields);
+    Ok(res)
+}
+
+/// Searches without the field access checks, the caller checks the request and strips the
+/// response, e.g. the result cache keeps the whole results shared by the users
+pub(crate) async fn search_unchecked(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: Option<String>,
+    in_req: &search::Request,
 ) -> Result<search::Response, Error> {
     let start = std::time::Instant::now();
     let started_at = chrono::Utc::now().timestamp_micros();
@@ -452,6 +488,31 @@ pub async fn search(
         .with_label_values(&[org_id])
         .dec();
 
+    if cfg.limit.query_timings_buffer_size > 0 {
+        let (took_detail, scan_size, scan_records) = match res.as_ref() {
+            Ok(res) => (res.took_detail.clone(), res.scan_size, res.scan_records),
+            Err(_) => (None, 0, 0),
+        };
+        timings::record(search::SearchTiming {
+            trace_id: trace_id.clone(),
+            org_id: org_id.to_string(),
+            stream_type: stream_type.to_string(),
+            sql: req_query.sql.clone(),
+            start_time: req_query.start_time,
+            end_time: req_query.end_time,
+            timestamp: chrono::Utc::now().timestamp_micros(),
+            took: start.elapsed().as_millis() as usize,
+            took_detail,
+            scan_size,
+            scan_records,
+            error: res
+                .as_ref()
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default(),
+        });
+    }
+
     // do this because of clippy warning
     match res {
         Ok(mut res) => {
@@ -528,11 +589,21 @@ pub async fn search_partition(
     trace_id: &str,
     org_id: &str,
     stream_type: StreamType,
+    user_id: Option<&str>,
     req: &search::SearchPartitionRequest,
 ) -> Result<search::SearchPartitionResponse, Error> {
     let start = std::time::Instant::now();
     let cfg = get_config();
 
+    field_access::check_request(
+        org_id,
+        user_id,
+        stream_type,
+        &req.sql,
+        req.query_fn.as_deref(),
+    )
+    .await?;
+
     let query = cluster_rpc::SearchQuery {
         start_time: req.start_time,
         end_time: req.end_time,
@@ -562,8 +633,20 @@ pub async fn search_partition(
 
     let mut files = Vec::new();
     let mut max_query_range = 0;
+    let range_policy = get_query_range_policy(org_id).await;
     for (stream, schema) in sql.schemas.iter() {
         let stream_settings = unwrap_stream_settings(schema.schema()).unwrap_or_default();
+        // the partitions are clamped by the searches, only the rejection is checked here
+        apply_max_query_range(
+            stream,
+            &mut req.start_time.clone(),
+            req.end_time,
+            stream_settings.max_query_range,
+            stream_settings
+                .query_range_policy
+                .as_deref()
+                .unwrap_or(&range_policy),
+        )?;
         if !skip_get_file_list {
             let stream_files = crate::service::file_list::query_ids(
                 &sql.org_id,
@@ -636,9 +719,31 @@ pub async fn search_partition(
     if part_num * cfg.limit.query_partition_by_secs < total_secs {
         part_num += 1;
     }
-    // if the partition number is too large, we limit it to 1000
-    if part_num > 1000 {
-        part_num = 1000;
+    // if the partition number is too large, we limit it
+    if part_num > cfg.limit.query_partition_max_num {
+        part_num = cfg.limit.query_partition_max_num;
+    }
+
+    // aggregate queries need the partitions aligned to the histogram interval
+    if req.partition_by_files && !is_aggregate {
+        let mut partitions = partition_by_files(
+            &files,
+            req.start_time,
+            req.end_time,
+            part_num,
+            max_query_range,
+        );
+        // partitions are generated by ASC order, reverse them if query is DESC order
+        match sql.order_by.first() {
+            Some((field, order_by))
+                if ts_column.as_ref() == Some(field) && order_by == &OrderBy::Asc =>
+            {
+                resp.order_by = OrderBy::Asc;
+            }
+            _ => partitions.reverse(),
+        }
+        resp.partitions = partitions;
+        return Ok(resp);
     }
     let mut step = (req.end_time - req.start_time) / part_num as i64;
     // step must be times of min_step
@@ -691,6 +796,54 @@ pub async fn search_partition(
     Ok(resp)
 }
 
+/// Splits `[start_time, end_time)` into at most `part_num` partitions by ASC order, each
+/// scanning roughly the same original size. A boundary is only placed in a gap between files,
+/// so every file is scanned by exactly one partition. Partitions longer than `max_query_range`
+/// are split further.
+fn partition_by_files(
+    files: &[FileId],
+    start_time: i64,
+    end_time: i64,
+    part_num: usize,
+    max_query_range: i64,
+) -> Vec<[i64; 2]> {
+    let mut boundaries = vec![start_time];
+    if part_num > 1 {
+        let mut files = files.iter().collect::<Vec<_>>();
+        files.sort_unstable_by_key(|f| f.min_ts);
+        let total_size = files.iter().map(|f| f.original_size).sum::<i64>();
+        let part_size = max(1, total_size / part_num as i64);
+        let mut size = 0;
+        let mut max_ts = i64::MIN;
+        for file in files {
+            // no file before this one reaches into it, so we can cut here
+            if size >= part_size && file.min_ts > max_ts && boundaries.len() < part_num {
+                let boundary = max_ts + 1;
+                if boundary > *boundaries.last().unwrap() && boundary < end_time {
+                    boundaries.push(boundary);
+                    size = 0;
+                }
+            }
+            size += file.original_size;
+            max_ts = max(max_ts, file.max_ts);
+        }
+    }
+    boundaries.push(end_time);
+
+    let mut partitions = Vec::with_capacity(boundaries.len());
+    for window in boundaries.windows(2) {
+        let (mut start, end) = (window[0], window[1]);
+        if max_query_range > 0 {
+            while end - start > max_query_range {
+                partitions.push([start, start + max_query_range]);
+                start += max_query_range;
+            }
+        }
+        partitions.push([start, end]);
+    }
+    partitions
+}
+
 #[cfg(feature = "enterprise")]
 pub async fn query_status() -> Result<search::QueryStatusResponse, Error> {
     // get nodes from cluster
@@ -1066,11 +1219,108 @@ pub fn server_internal_error(error: impl ToString) -> Error {
     Error::ErrorCode(ErrorCodes::ServerInternalError(error.to_string()))
 }
 
+/// Applies the max rows per query of the org to the requested size before the query is planned.
+/// Returns the message to report if the size was clamped, or an error if the policy rejects it.
+pub async fn check_query_max_rows(org_id: &str, size: &mut i64) -> Result<Option<String>, Error> {
+    let org_max_rows = crate::service::db::organization::get_org_setting(org_id)
+        .await
+        .ok()
+        .and_then(|v| json::from_slice::<meta::organization::OrganizationSetting>(&v).ok())
+        .and_then(|v| v.query_max_rows);
+    let cfg = get_config();
+    apply_query_max_rows(
+        size,
+        org_max_rows.unwrap_or(cfg.limit.query_max_rows),
+        cfg.limit.query_default_limit,
+        &cfg.limit.query_max_rows_policy,
+    )
+}
+
+fn apply_query_max_rows(
+    size: &mut i64,
+    max_rows: i64,
+    default_limit: i64,
+    policy: &str,
+) -> Result<Option<String>, Error> {
+    if max_rows <= 0 {
+        return Ok(None);
+    }
+    // the size less than or equal to 0 means the default limit, except the no limit flag
+    let requested = if *size > 0 {
+        *size
+    } else if *size as i32 == config::QUERY_WITH_NO_LIMIT {
+        i64::MAX
+    } else {
+        default_limit
+    };
+    if requested <= max_rows {
+        return Ok(None);
+    }
+    if policy == "reject" {
+        return Err(Error::Message(format!(
+            "Query size exceeds the limit of {max_rows} rows per query"
+        )));
+    }
+    *size = max_rows;
+    Ok(Some(format!(
+        "Query size is modified due to the limit of {max_rows} rows per query"
+    )))
+}
+
+/// Returns the maximum groups of a GROUP BY query of the org, 0 means no limit
+pub async fn get_query_max_groups(org_id: &str) -> i64 {
+    crate::service::db::organization::get_org_setting(org_id)
+        .await
+        .ok()
+        .and_then(|v| json::from_slice::<meta::organization::OrganizationSetting>(&v).ok())
+        .and_then(|v| v.query_max_groups)
+        .unwrap_or_else(|| get_config().limit.query_max_groups)
+}
+
+/// Returns the policy of the org applied to a query spanning more than the max query range of a
+/// stream, `clamp` or `reject`. The policy of a stream overrides it.
+pub async fn get_query_range_policy(org_id: &str) -> String {
+    crate::service::db::organization::get_org_setting(org_id)
+        .await
+        .ok()
+        .and_then(|v| json::from_slice::<meta::organization::OrganizationSetting>(&v).ok())
+        .and_then(|v| v.query_range_policy)
+        .unwrap_or_else(|| get_config().limit.query_range_policy.clone())
+}
+
+/// Moves the start time so the query spans at most `max_query_range` hours, or fails with the
+/// `reject` policy. Returns the warning when the time range is clamped.
+pub fn apply_max_query_range(
+    stream_name: &str,
+    start_time: &mut i64,
+    end_time: i64,
+    max_query_range: i64,
+    policy: &str,
+) -> Result<Option<String>, Error> {
+    let max_range = max_query_range * 3600 * 1_000_000;
+    if max_query_range <= 0 || end_time - *start_time <= max_range {
+        return Ok(None);
+    }
+    if policy == "reject" {
+        let requested_hours = (end_time - *start_time) as f64 / 3600.0 / 1_000_000.0;
+        return Err(Error::ErrorCode(ErrorCodes::SearchQueryRangeExceeded(
+            format!(
+                "Query time range of {requested_hours:.2} hours exceeds the max query range of {max_query_range} hours for stream {stream_name}"
+            ),
+        )));
+    }
+    *start_time = end_time - max_range;
+    Ok(Some(format!(
+        "Query duration for stream {stream_name} is modified due to query range restriction of {max_query_range} hours"
+    )))
+}
+
 #[tracing::instrument(name = "service:search_partition_multi", skip(req))]
 pub async fn search_partition_multi(
     trace_id: &str,
     org_id: &str,
     stream_type: StreamType,
+    user_id: Option<&str>,
     req: &search::MultiSearchPartitionRequest,
 ) -> Result<search::SearchPartitionResponse, Error> {
     let mut res = search::SearchPartitionResponse::default();
@@ -1080,6 +1330,7 @@ pub async fn search_partition_multi(
             trace_id,
             org_id,
             stream_type,
+            user_id,
             &search::SearchPartitionRequest {
                 start_time: req.start_time,
                 end_time: req.end_time,
@@ -1088,6 +1339,7 @@ pub async fn search_partition_multi(
                 regions: req.regions.clone(),
                 clusters: req.clusters.clone(),
                 query_fn: req.query_fn.clone(),
+                partition_by_files: req.partition_by_files,
             },
         )
         .await
@@ -1140,29 +1392,38 @@ fn generate_search_schema_diff(
     Ok(diff_fields)
 }
 
-pub fn is_use_inverted_index(sql: &Arc<Sql>) -> (bool, Vec<(String, String)>) {
+/// Returns if the inverted index can be used, the equal terms and the prefix terms (`field LIKE
+/// 'value%'`) on the index fields
+pub fn is_use_inverted_index(
+    sql: &Arc<Sql>,
+) -> (bool, Vec<(String, String)>, Vec<(String, String)>) {
     // parquet format inverted index only support single table
     if sql.stream_names.len() != 1 {
-        return (false, vec![]);
+        return (false, vec![], vec![]);
     }
 
     let cfg = get_config();
+    let schema = sql.schemas.values().next().unwrap().schema();
+    let stream_settings = infra::schema::unwrap_stream_settings(schema);
+    let index_fields = get_stream_setting_index_fields(&stream_settings);
     let index_terms = if sql.equal_items.len() == 1 {
-        let schema = sql.schemas.values().next().unwrap().schema();
-        let stream_settings = infra::schema::unwrap_stream_settings(schema);
-        let index_fields = get_stream_setting_index_fields(&stream_settings);
         filter_index_fields(sql.equal_items.values().next().unwrap(), &index_fields)
     } else {
         vec![]
     };
+    let index_prefix_terms = if sql.prefix_items.len() == 1 {
+        filter_index_fields(sql.prefix_items.values().next().unwrap(), &index_fields)
+    } else {
+        vec![]
+    };
 
     let use_inverted_index = sql.stream_type != StreamType::Index
         && sql.use_inverted_index
         && cfg.common.inverted_index_enabled
         && !cfg.common.feature_query_without_index
-        && (sql.match_items.is_some() || !index_terms.is_empty());
+        && (sql.match_items.is_some() || !index_terms.is_empty() || !index_prefix_terms.is_empty());
 
-    (use_inverted_index, index_terms)
+    (use_inverted_index, index_terms, index_prefix_terms)
 }
 
 pub fn filter_index_fields(
@@ -1202,6 +1463,38 @@ pub fn generate_filter_from_quick_text(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_partition_by_files() {
+        let file = |min_ts, max_ts, original_size| FileId {
+            id: min_ts,
+            records: 1,
+            original_size,
+            min_ts,
+            max_ts,
+        };
+        // the files 10..30 and 20..40 overlap and can't be split
+        let files = vec![
+            file(0, 9, 100),
+            file(20, 40, 100),
+            file(10, 30, 100),
+            file(50, 59, 100),
+            file(60, 69, 100),
+        ];
+        assert_eq!(partition_by_files(&files, 0, 70, 1, 0), vec![[0, 70]]);
+        assert_eq!(
+            partition_by_files(&files, 0, 70, 2, 0),
+            vec![[0, 41], [41, 70]]
+        );
+        assert_eq!(
+            partition_by_files(&files, 0, 70, 10, 0),
+            vec![[0, 10], [10, 41], [41, 60], [60, 70]]
+        );
+        assert_eq!(
+            partition_by_files(&files, 0, 70, 2, 30),
+            vec![[0, 30], [30, 41], [41, 70]]
+        );
+    }
+
     #[test]
     fn test_matches_by_partition_key_with_sql() {
         use config::meta::sql;
@@ -1296,4 +1589,89 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_apply_query_max_rows() {
+        // no limit
+        let mut size = 50_000;
+        assert_eq!(
+            apply_query_max_rows(&mut size, 0, 1000, "clamp").unwrap(),
+            None
+        );
+        assert_eq!(size, 50_000);
+
+        // within the limit
+        let mut size = 100;
+        assert_eq!(
+            apply_query_max_rows(&mut size, 1000, 1000, "clamp").unwrap(),
+            None
+        );
+        assert_eq!(size, 100);
+        let mut size = 0;
+        assert_eq!(
+            apply_query_max_rows(&mut size, 1000, 500, "reject").unwrap(),
+            None
+        );
+        assert_eq!(size, 0);
+
+        // clamped
+        let mut size = 50_000;
+        assert!(
+            apply_query_max_rows(&mut size, 1000, 1000, "clamp")
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(size, 1000);
+        let mut size = config::QUERY_WITH_NO_LIMIT as i64;
+        assert!(
+            apply_query_max_rows(&mut size, 1000, 1000, "clamp")
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(size, 1000);
+
+        // rejected
+        let mut size = 50_000;
+        assert!(apply_query_max_rows(&mut size, 1000, 1000, "reject").is_err());
+        assert_eq!(size, 50_000);
+    }
+
+    #[test]
+    fn test_apply_max_query_range() {
+        let hour = 3600 * 1_000_000;
+        let end_time = 100 * hour;
+
+        // no limit or within the limit
+        let mut start_time = 0;
+        assert_eq!(
+            apply_max_query_range("logs", &mut start_time, end_time, 0, "reject").unwrap(),
+            None
+        );
+        let mut start_time = end_time - 24 * hour;
+        assert_eq!(
+            apply_max_query_range("logs", &mut start_time, end_time, 24, "reject").unwrap(),
+            None
+        );
+        assert_eq!(start_time, end_time - 24 * hour);
+
+        // clamped
+        let mut start_time = end_time - 48 * hour;
+        assert!(
+            apply_max_query_range("logs", &mut start_time, end_time, 24, "clamp")
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(start_time, end_time - 24 * hour);
+
+        // rejected, the time range is kept
+        let mut start_time = end_time - 48 * hour - hour / 2;
+        let Err(Error::ErrorCode(ErrorCodes::SearchQueryRangeExceeded(err))) =
+            apply_max_query_range("logs", &mut start_time, end_time, 24, "reject")
+        else {
+            panic!("the query range should be rejected");
+        };
+        assert!(err.contains("48.50 hours"), "{err}");
+        assert!(err.contains("max query range of 24 hours"), "{err}");
+        assert_eq!(start_time, end_time - 48 * hour - hour / 2);
+    }
 }
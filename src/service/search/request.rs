@@ -28,6 +28,7 @@ pub struct Request {
     pub search_event_type: Option<String>, // node rule
     pub inverted_index_type: Option<String>,
     pub use_inverted_index: bool,
+    pub index_prefix_terms: Vec<(String, String)>, // field like 'value%' on the index fields
 }
 
 impl Default for Request {
@@ -43,6 +44,7 @@ impl Default for Request {
             search_event_type: None,
             inverted_index_type: None,
             use_inverted_index: false,
+            index_prefix_terms: vec![],
         }
     }
 }
@@ -70,6 +72,7 @@ impl Request {
             search_event_type,
             inverted_index_type,
             use_inverted_index: false,
+            index_prefix_terms: vec![],
         }
     }
 
@@ -96,6 +99,10 @@ impl Request {
     pub fn set_inverted_index_type(&mut self, index_type: Option<String>) {
         self.inverted_index_type = index_type;
     }
+
+    pub fn set_index_prefix_terms(&mut self, index_prefix_terms: Vec<(String, String)>) {
+        self.index_prefix_terms = index_prefix_terms;
+    }
 }
 
 impl From<FlightSearchRequest> for Request {
@@ -111,6 +118,11 @@ impl From<FlightSearchRequest> for Request {
             search_event_type: request.search_event_type,
             inverted_index_type: request.index_type,
             use_inverted_index: request.use_inverted_index,
+            index_prefix_terms: request
+                .prefix_keys
+                .into_iter()
+                .map(|v| (v.key, v.value))
+                .collect(),
         }
     }
 }
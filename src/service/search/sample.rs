@@ -0,0 +1,287 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A few of the newest records of a stream, read from the memtable of the node and from the
+//! newest row groups of the newest files in the file_list, so the cost doesn't grow with the size
+//! of the stream. Only the records returned are converted to JSON.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{Array, Int64Array, UInt32Array},
+    compute::take_record_batch,
+    record_batch::RecordBatch,
+};
+use chrono::{Duration, Utc};
+use config::{
+    cluster::LOCAL_NODE,
+    get_config,
+    meta::stream::{PartitionTimeLevel, StreamType},
+    utils::{
+        arrow::record_batches_to_json_rows,
+        json::{Map, Value},
+    },
+    ORIGINAL_DATA_COL_NAME,
+};
+use datafusion::parquet::file::{metadata::ParquetMetaData, statistics::Statistics};
+use infra::{cache::stats, errors::Error, schema::unwrap_stream_settings};
+
+use crate::{
+    common::meta::stream::{StreamProperty, StreamSample},
+    service::file_list,
+};
+
+/// Returns the `size` newest records of the stream with its schema, an empty stream only has
+/// the schema. The fields the user can't access are left out.
+pub async fn sample(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    user_id: &str,
+    size: usize,
+) -> Result<StreamSample, Error> {
+    let start = std::time::Instant::now();
+    let stream_name = &infra::schema::resolve_stream_alias(org_id, stream_name, stream_type).await;
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    let settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    let denied_fields = super::field_access::get_denied_fields(
+        org_id,
+        user_id,
+        stream_name,
+        stream_type,
+        &settings,
+    )
+    .await?;
+    let mut res = StreamSample {
+        schema: schema
+            .fields()
+            .iter()
+            .filter(|field| !denied_fields.contains(field.name()))
+            .map(|field| StreamProperty {
+                name: field.name().to_string(),
+                prop_type: field.data_type().to_string(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    if schema.fields().is_empty() || size == 0 {
+        return Ok(res);
+    }
+
+    // the data not persisted yet, only an ingester holds it
+    let mut hits = Vec::new();
+    if LOCAL_NODE.is_ingester() {
+        let batches =
+            ingester::read_from_memtable(org_id, &stream_type.to_string(), stream_name, None, &[])
+                .await
+                .unwrap_or_default();
+        let batches = batches
+            .iter()
+            .flat_map(|(_, entries)| entries.iter().map(|entry| &entry.data))
+            .collect::<Vec<_>>();
+        hits.extend(newest_rows_to_json(&batches, size)?);
+    }
+
+    // the newest row groups of the newest files, within an hour of the newest record of the
+    // stream
+    if hits.len() < size {
+        let stream_stats = stats::get_stream_stats(org_id, stream_name, stream_type);
+        let end_time = if stream_stats.doc_time_max > 0 {
+            stream_stats.doc_time_max
+        } else {
+            Utc::now().timestamp_micros()
+        };
+        let start_time = end_time - Duration::try_hours(1).unwrap().num_microseconds().unwrap();
+        let mut files = file_list::query(
+            org_id,
+            stream_name,
+            stream_type,
+            PartitionTimeLevel::Unset,
+            start_time,
+            end_time,
+        )
+        .await?;
+        files.sort_by(|a, b| b.meta.max_ts.cmp(&a.meta.max_ts));
+        let mut file_records = 0;
+        'files: for file in files {
+            let metadata = Arc::new(
+                infra::storage::get_parquet_metadata(&file.key, file.meta.compressed_size as usize)
+                    .await
+                    .map_err(|e| Error::Message(e.to_string()))?,
+            );
+            for row_group in newest_row_groups(&metadata) {
+                if hits.len() + file_records >= size {
+                    break 'files;
+                }
+                let batches =
+                    infra::storage::get_parquet_row_group(&file.key, metadata.clone(), row_group)
+                        .await
+                        .map_err(|e| Error::Message(e.to_string()))?;
+                let rows = newest_rows_to_json(&batches.iter().collect::<Vec<_>>(), size)?;
+                file_records += rows.len();
+                hits.extend(rows);
+            }
+        }
+    }
+
+    res.hits = newest_records(hits, size)
+        .into_iter()
+        .map(|mut hit| {
+            // the original record has the denied fields too
+            hit.retain(|field, _| {
+                !denied_fields.contains(field)
+                    && (denied_fields.is_empty() || field != ORIGINAL_DATA_COL_NAME)
+            });
+            Value::Object(hit)
+        })
+        .collect();
+    res.took = start.elapsed().as_millis() as usize;
+    Ok(res)
+}
+
+/// Returns the indexes of the row groups of the file, the ones with the newest records first
+fn newest_row_groups(metadata: &ParquetMetaData) -> Vec<usize> {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let idx = metadata
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .position(|column| column.name() == column_timestamp);
+    let mut row_groups = metadata
+        .row_groups()
+        .iter()
+        .enumerate()
+        .map(|(i, row_group)| {
+            let max_ts = idx
+                .and_then(|idx| row_group.column(idx).statistics())
+                .and_then(|stats| match stats {
+                    Statistics::Int64(s) => s.max_opt().copied(),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            (i, max_ts)
+        })
+        .collect::<Vec<_>>();
+    row_groups.sort_by(|a, b| b.1.cmp(&a.1));
+    row_groups.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Converts the `size` rows of the batches with the newest timestamps to JSON, the other rows
+/// aren't converted
+fn newest_rows_to_json(
+    batches: &[&RecordBatch],
+    size: usize,
+) -> Result<Vec<Map<String, Value>>, Error> {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let mut rows = Vec::new();
+    for (i, batch) in batches.iter().enumerate() {
+        let timestamps = batch
+            .column_by_name(column_timestamp)
+            .and_then(|v| v.as_any().downcast_ref::<Int64Array>());
+        for row in 0..batch.num_rows() {
+            let ts = timestamps
+                .filter(|v| v.is_valid(row))
+                .map(|v| v.value(row))
+                .unwrap_or_default();
+            rows.push((std::cmp::Reverse(ts), i, row as u32));
+        }
+    }
+    if rows.len() > size {
+        rows.select_nth_unstable(size);
+        rows.truncate(size);
+    }
+    let mut indices = vec![Vec::new(); batches.len()];
+    for (_, i, row) in rows {
+        indices[i].push(row);
+    }
+    let batches = batches
+        .iter()
+        .zip(indices)
+        .filter(|(_, indices)| !indices.is_empty())
+        .map(|(batch, indices)| take_record_batch(batch, &UInt32Array::from(indices)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::Message(e.to_string()))?;
+    record_batches_to_json_rows(&batches.iter().collect::<Vec<_>>())
+        .map_err(|e| Error::Message(e.to_string()))
+}
+
+/// Returns the `size` records with the newest timestamps, newest first
+fn newest_records(mut hits: Vec<Map<String, Value>>, size: usize) -> Vec<Map<String, Value>> {
+    let column_timestamp = &get_config().common.column_timestamp;
+    hits.sort_by_cached_key(|hit| {
+        std::cmp::Reverse(
+            hit.get(column_timestamp)
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+        )
+    });
+    hits.truncate(size);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    #[test]
+    fn test_newest_records() {
+        let hits = [3, 1, 5, 2, 4]
+            .into_iter()
+            .map(|ts| {
+                json::json!({"_timestamp": ts, "message": format!("line {ts}")})
+                    .as_object()
+                    .unwrap()
+                    .clone()
+            })
+            .collect::<Vec<_>>();
+        let timestamps = |hits: Vec<Map<String, Value>>| {
+            hits.iter()
+                .map(|hit| hit.get("_timestamp").unwrap().as_i64().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(timestamps(newest_records(hits.clone(), 3)), vec![5, 4, 3]);
+        assert_eq!(timestamps(newest_records(hits, 10)), vec![5, 4, 3, 2, 1]);
+        assert!(newest_records(vec![], 10).is_empty());
+    }
+
+    #[test]
+    fn test_newest_rows_to_json() {
+        let batch = |timestamps: Vec<i64>| {
+            let schema = Arc::new(arrow_schema::Schema::new(vec![arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Int64,
+                false,
+            )]));
+            RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(timestamps))]).unwrap()
+        };
+        let batches = [batch(vec![3, 1, 5]), batch(vec![2, 4]), batch(vec![0])];
+        let rows = newest_rows_to_json(&batches.iter().collect::<Vec<_>>(), 3).unwrap();
+        let mut timestamps = rows
+            .iter()
+            .map(|row| row.get("_timestamp").unwrap().as_i64().unwrap())
+            .collect::<Vec<_>>();
+        timestamps.sort();
+        assert_eq!(timestamps, vec![3, 4, 5]);
+        assert_eq!(
+            newest_rows_to_json(&batches.iter().collect::<Vec<_>>(), 10)
+                .unwrap()
+                .len(),
+            6
+        );
+    }
+}
@@ -20,11 +20,12 @@ use chrono::Duration;
 use config::{
     get_config,
     meta::{
+        search::{SortKey, SqlValidationError, ValidateSqlResponse},
         sql::{resolve_stream_names, OrderBy, Sql as MetaSql},
         stream::StreamType,
     },
-    utils::sql::AGGREGATE_UDF_LIST,
-    ID_COL_NAME, ORIGINAL_DATA_COL_NAME,
+    utils::{flatten::flattened_field_name, sql::AGGREGATE_UDF_LIST},
+    DISTINCT_FIELDS, ID_COL_NAME, ORIGINAL_DATA_COL_NAME,
 };
 use datafusion::arrow::datatypes::Schema;
 use hashbrown::HashMap;
@@ -43,14 +44,17 @@ use serde::Serialize;
 use sqlparser::{
     ast::{
         BinaryOperator, DuplicateTreatment, Expr, Function, FunctionArg, FunctionArgExpr,
-        FunctionArgumentList, FunctionArguments, GroupByExpr, Ident, ObjectName, Query, SelectItem,
-        SetExpr, Statement, VisitMut, VisitorMut,
+        FunctionArgumentList, FunctionArguments, GroupByExpr, Ident, ObjectName, OrderByExpr,
+        Query, SelectItem, SetExpr, Statement, TableFactor, Value, VisitMut, VisitorMut,
     },
     dialect::PostgreSqlDialect,
     parser::Parser,
 };
 
-use super::request::Request;
+use super::{
+    datafusion::udf::{json_get_path_udf::JSON_GET_PATH_UDF_NAME, MATCH_UDF_IGNORE_CASE_NAME},
+    request::Request,
+};
 
 pub static RE_ONLY_SELECT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)select[ ]+\*").unwrap());
 pub static RE_SELECT_FROM: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)SELECT (.*) FROM").unwrap());
@@ -58,6 +62,8 @@ pub static RE_WHERE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i) where (.*)").u
 
 pub static RE_HISTOGRAM: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(?i)histogram\(([^\)]*)\)").unwrap());
+static RE_ERROR_POSITION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"Line: (\d+), Column:? (\d+)").unwrap());
 
 #[derive(Clone, Debug, Serialize)]
 pub struct Sql {
@@ -95,8 +101,19 @@ impl Sql {
         let limit = query.size as i64;
         let offset = query.from as i64;
 
-        // 1. get table name
-        let stream_names = resolve_stream_names(&sql).map_err(|e| Error::Message(e.to_string()))?;
+        // 1. get table name, a stream alias is read from the stream it belongs to
+        let mut stream_aliases = HashMap::new();
+        let mut stream_names = Vec::new();
+        for name in resolve_stream_names(&sql).map_err(|e| Error::Message(e.to_string()))? {
+            match infra::schema::get_stream_alias_target(org_id, &name, stream_type).await {
+                Some(target) => {
+                    stream_names.push(target.clone());
+                    stream_aliases.insert(name, target);
+                }
+                None => stream_names.push(name),
+            }
+        }
+        let stream_names = stream_names.into_iter().unique().collect::<Vec<_>>();
         let mut total_schemas = HashMap::with_capacity(stream_names.len());
         for stream_name in stream_names.iter() {
             let schema = infra::schema::get(org_id, stream_name, stream_type)
@@ -110,6 +127,48 @@ impl Sql {
             .pop()
             .unwrap();
 
+        check_subqueries(&statement)
+            .map_err(|e| Error::ErrorCode(ErrorCodes::SearchSQLNotValid(e)))?;
+
+        if !stream_aliases.is_empty() {
+            statement.visit(&mut StreamAliasVisitor::new(&stream_aliases));
+        }
+
+        // rewrite `SELECT *` to the requested fields, before the field aliases are rewritten
+        let field_aliases = field_alias_exprs(&total_schemas);
+        if !query.fields.is_empty() {
+            let fields = projection_fields(&query.fields, &total_schemas, &field_aliases)?;
+            statement.visit(&mut FieldsProjectionVisitor::new(&fields));
+        }
+
+        // rewrite the field aliases and the computed columns of the streams to the fields they
+        // stand for
+        if !field_aliases.is_empty() {
+            statement.visit(&mut FieldAliasVisitor::new(&field_aliases));
+        }
+
+        // rewrite match_all_fields() to str_match_ignore_case() over the given fields
+        let mut match_all_fields_visitor = MatchAllFieldsVisitor::new();
+        if let ControlFlow::Break(e) = statement.visit(&mut match_all_fields_visitor) {
+            return Err(Error::Message(e));
+        }
+
+        // rewrite match_all_ignore_case() to lower() LIKE lower() over the fts fields
+        // TODO: match_all_ignore_case only support single stream
+        let fts_fields = match total_schemas.values().next() {
+            Some(schema) if total_schemas.len() == 1 => {
+                get_stream_setting_fts_fields(&unwrap_stream_settings(schema.schema()))
+                    .into_iter()
+                    .filter(|f| schema.contains_field(f))
+                    .collect::<Vec<_>>()
+            }
+            _ => vec![],
+        };
+        let mut match_all_ignore_case_visitor = MatchAllIgnoreCaseVisitor::new(&fts_fields);
+        if let ControlFlow::Break(e) = statement.visit(&mut match_all_ignore_case_visitor) {
+            return Err(e);
+        }
+
         // 2. rewrite track_total_hits
         if query.track_total_hits {
             let mut trace_total_hits_visitor = TrackTotalHitsVisitor::new();
@@ -746,6 +805,355 @@ impl VisitorMut for MatchVisitor {
     }
 }
 
+const MATCH_ALL_FIELDS_NAME: &str = "match_all_fields";
+const MATCH_ALL_IGNORE_CASE_NAME: &str = "match_all_ignore_case";
+
+/// rewrite match_all_fields('v', field1, field2) to
+/// (str_match_ignore_case(field1, 'v') OR str_match_ignore_case(field2, 'v')),
+/// so the full text search only scans the given columns
+struct MatchAllFieldsVisitor {}
+
+impl MatchAllFieldsVisitor {
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+impl VisitorMut for MatchAllFieldsVisitor {
+    type Break = String;
+
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        let Expr::Function(func) = expr else {
+            return ControlFlow::Continue(());
+        };
+        if func.name.to_string().to_lowercase() != MATCH_ALL_FIELDS_NAME {
+            return ControlFlow::Continue(());
+        }
+        let usage = format!("{MATCH_ALL_FIELDS_NAME}('value', field1, field2, ...)");
+        let args = match &func.args {
+            FunctionArguments::List(list) if list.args.len() > 1 => list
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Some(expr.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        let Some(args) = args else {
+            return ControlFlow::Break(format!("{MATCH_ALL_FIELDS_NAME} expects {usage}"));
+        };
+        let value = match &args[0] {
+            Expr::Value(Value::SingleQuotedString(_)) => args[0].clone(),
+            _ => {
+                return ControlFlow::Break(format!(
+                    "{MATCH_ALL_FIELDS_NAME} expects a string value as the first argument: {usage}"
+                ));
+            }
+        };
+        let mut exprs = Vec::with_capacity(args.len() - 1);
+        for field in args.into_iter().skip(1) {
+            if !matches!(field, Expr::Identifier(_) | Expr::CompoundIdentifier(_)) {
+                return ControlFlow::Break(format!(
+                    "{MATCH_ALL_FIELDS_NAME} expects field names after the value, got {field}"
+                ));
+            }
+            exprs.push(sql_function(
+                MATCH_UDF_IGNORE_CASE_NAME,
+                vec![field, value.clone()],
+            ));
+        }
+        *expr = sql_disjunction(exprs);
+        ControlFlow::Continue(())
+    }
+}
+
+/// rewrite match_all_ignore_case('v') to
+/// (lower(field1) LIKE lower('%v%') OR lower(field2) LIKE lower('%v%'))
+/// over the full text search fields of the stream.
+///
+/// NOTE: it can't use the inverted index and has to lowercase every value of
+/// the fields, so it is much slower than match_all(), use it only when needed.
+struct MatchAllIgnoreCaseVisitor<'a> {
+    fields: &'a [String],
+}
+
+impl<'a> MatchAllIgnoreCaseVisitor<'a> {
+    fn new(fields: &'a [String]) -> Self {
+        Self { fields }
+    }
+}
+
+impl<'a> VisitorMut for MatchAllIgnoreCaseVisitor<'a> {
+    type Break = Error;
+
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        let Expr::Function(func) = expr else {
+            return ControlFlow::Continue(());
+        };
+        if func.name.to_string().to_lowercase() != MATCH_ALL_IGNORE_CASE_NAME {
+            return ControlFlow::Continue(());
+        }
+        let value = match &func.args {
+            FunctionArguments::List(list) if list.args.len() == 1 => match &list.args[0] {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(
+                    Value::SingleQuotedString(value),
+                ))) => Some(value.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some(value) = value else {
+            return ControlFlow::Break(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(format!(
+                "{MATCH_ALL_IGNORE_CASE_NAME} expects a string value: {func}"
+            ))));
+        };
+        if self.fields.is_empty() {
+            return ControlFlow::Break(Error::ErrorCode(ErrorCodes::FullTextSearchFieldNotFound));
+        }
+        let pattern = sql_function(
+            "lower",
+            vec![Expr::Value(Value::SingleQuotedString(format!("%{value}%")))],
+        );
+        let exprs = self
+            .fields
+            .iter()
+            .map(|field| Expr::Like {
+                negated: false,
+                expr: Box::new(sql_function(
+                    "lower",
+                    vec![Expr::Identifier(Ident::with_quote('"', field))],
+                )),
+                pattern: Box::new(pattern.clone()),
+                escape_char: None,
+            })
+            .collect();
+        *expr = sql_disjunction(exprs);
+        ControlFlow::Continue(())
+    }
+}
+
+/// Returns the field aliases and the computed columns of the streams with the expression they
+/// are rewritten to. The names are checked against the schema when they are saved, but a field
+/// ingested later can take the name, the field is read then.
+fn field_alias_exprs(schemas: &HashMap<String, Arc<SchemaCache>>) -> HashMap<String, Expr> {
+    let mut exprs = HashMap::new();
+    let is_schema_field = |name: &str| schemas.values().any(|v| v.contains_field(name));
+    for schema in schemas.values() {
+        let Some(settings) = unwrap_stream_settings(schema.schema()) else {
+            continue;
+        };
+        for alias in settings.field_aliases.iter() {
+            if is_schema_field(&alias.name) {
+                continue;
+            }
+            if let Some((field, path)) = alias.split_path() {
+                exprs.entry(alias.name.clone()).or_insert_with(|| {
+                    sql_function(
+                        JSON_GET_PATH_UDF_NAME,
+                        vec![
+                            Expr::Identifier(Ident::with_quote('"', field)),
+                            Expr::Value(Value::SingleQuotedString(path.to_string())),
+                        ],
+                    )
+                });
+            }
+        }
+        for column in settings.computed_columns.iter() {
+            if is_schema_field(&column.name) {
+                continue;
+            }
+            let expr = match column.parse_expr() {
+                Ok(expr) => expr,
+                Err(e) => {
+                    log::warn!(
+                        "computed column [{}] has an invalid expression: {e}",
+                        column.name
+                    );
+                    continue;
+                }
+            };
+            // keep the precedence of the expression where it's substituted
+            let expr = match expr {
+                Expr::Identifier(_) | Expr::Function(_) | Expr::Nested(_) => expr,
+                expr => Expr::Nested(Box::new(expr)),
+            };
+            exprs.entry(column.name.clone()).or_insert(expr);
+        }
+    }
+    exprs
+}
+
+/// rewrite the field aliases to the nested access they stand for, like `kubernetes_pod_name`
+/// to `json_get_path("kubernetes", 'pod_name')`, and the computed columns to their expression, a
+/// selected alias keeps its name as the column name
+struct FieldAliasVisitor<'a> {
+    aliases: &'a HashMap<String, Expr>,
+}
+
+impl<'a> FieldAliasVisitor<'a> {
+    fn new(aliases: &'a HashMap<String, Expr>) -> Self {
+        Self { aliases }
+    }
+}
+
+impl<'a> VisitorMut for FieldAliasVisitor<'a> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        if let SetExpr::Select(select) = query.body.as_mut() {
+            for select_item in select.projection.iter_mut() {
+                if let SelectItem::UnnamedExpr(Expr::Identifier(ident)) = select_item {
+                    if let Some(expr) = self.aliases.get(&ident.value) {
+                        *select_item = SelectItem::ExprWithAlias {
+                            expr: expr.clone(),
+                            alias: Ident::with_quote('"', ident.value.clone()),
+                        };
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        if let Expr::Identifier(ident) = expr {
+            if let Some(alias_expr) = self.aliases.get(&ident.value) {
+                *expr = alias_expr.clone();
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// The fields of the `fields` parameter behind `_timestamp`, an error names the fields which
+/// aren't in the streams
+fn projection_fields(
+    fields: &[String],
+    schemas: &HashMap<String, Arc<SchemaCache>>,
+    field_aliases: &HashMap<String, Expr>,
+) -> Result<Vec<String>, Error> {
+    let column_timestamp = get_config().common.column_timestamp.clone();
+    let mut projection = vec![column_timestamp.clone()];
+    let mut unknown = Vec::new();
+    for field in fields.iter().unique() {
+        if *field == column_timestamp {
+            continue;
+        }
+        if field_aliases.contains_key(field)
+            || schemas.values().any(|schema| schema.contains_field(field))
+        {
+            projection.push(field.clone());
+        } else {
+            unknown.push(field.as_str());
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(Error::ErrorCode(ErrorCodes::SearchFieldNotFound(
+            unknown.join(", "),
+        )));
+    }
+    Ok(projection)
+}
+
+/// rewrite the `*` of the outermost `SELECT *` to the given fields
+struct FieldsProjectionVisitor<'a> {
+    fields: &'a [String],
+    visited: bool,
+}
+
+impl<'a> FieldsProjectionVisitor<'a> {
+    fn new(fields: &'a [String]) -> Self {
+        Self {
+            fields,
+            visited: false,
+        }
+    }
+}
+
+impl<'a> VisitorMut for FieldsProjectionVisitor<'a> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        if self.visited {
+            return ControlFlow::Continue(());
+        }
+        self.visited = true;
+        if let SetExpr::Select(select) = query.body.as_mut() {
+            if let [SelectItem::Wildcard(_)] = select.projection.as_slice() {
+                select.projection = self
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        SelectItem::UnnamedExpr(Expr::Identifier(Ident::with_quote(
+                            '"',
+                            field.clone(),
+                        )))
+                    })
+                    .collect();
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// rewrite the stream aliases to the streams they belong to
+struct StreamAliasVisitor<'a> {
+    aliases: &'a HashMap<String, String>,
+}
+
+impl<'a> StreamAliasVisitor<'a> {
+    fn new(aliases: &'a HashMap<String, String>) -> Self {
+        Self { aliases }
+    }
+}
+
+impl<'a> VisitorMut for StreamAliasVisitor<'a> {
+    type Break = ();
+
+    fn pre_visit_relation(&mut self, relation: &mut ObjectName) -> ControlFlow<Self::Break> {
+        if let Some(ident) = relation.0.last_mut() {
+            if let Some(stream_name) = self.aliases.get(&ident.value) {
+                *ident = Ident::with_quote('"', stream_name.clone());
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+fn sql_function(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::Function(Function {
+        name: ObjectName(vec![Ident::new(name)]),
+        parameters: FunctionArguments::None,
+        args: FunctionArguments::List(FunctionArgumentList {
+            args: args
+                .into_iter()
+                .map(|arg| FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)))
+                .collect(),
+            duplicate_treatment: None,
+            clauses: vec![],
+        }),
+        filter: None,
+        null_treatment: None,
+        over: None,
+        within_group: vec![],
+    })
+}
+
+// exprs must not be empty
+fn sql_disjunction(exprs: Vec<Expr>) -> Expr {
+    let expr = exprs
+        .into_iter()
+        .reduce(|left, right| Expr::BinaryOp {
+            left: Box::new(left),
+            op: BinaryOperator::Or,
+            right: Box::new(right),
+        })
+        .unwrap();
+    Expr::Nested(Box::new(expr))
+}
+
 struct FieldNameVisitor {
     pub field_names: HashSet<String>,
 }
@@ -769,6 +1177,58 @@ impl VisitorMut for FieldNameVisitor {
     }
 }
 
+struct FunctionNameVisitor {
+    pub function_names: HashSet<String>,
+}
+
+impl FunctionNameVisitor {
+    fn new() -> Self {
+        Self {
+            function_names: HashSet::new(),
+        }
+    }
+}
+
+impl VisitorMut for FunctionNameVisitor {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        if let Expr::Function(func) = expr {
+            self.function_names
+                .insert(func.name.to_string().to_lowercase());
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// get all alias of the select items, including the ones of the subqueries
+struct AliasVisitor {
+    pub aliases: HashSet<String>,
+}
+
+impl AliasVisitor {
+    fn new() -> Self {
+        Self {
+            aliases: HashSet::new(),
+        }
+    }
+}
+
+impl VisitorMut for AliasVisitor {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        if let SetExpr::Select(select) = query.body.as_ref() {
+            for select_item in select.projection.iter() {
+                if let SelectItem::ExprWithAlias { alias, .. } = select_item {
+                    self.aliases.insert(alias.value.clone());
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
 // add _timestamp to the query like `SELECT name FROM t` -> `SELECT _timestamp, name FROM t`
 struct AddTimestampVisitor {}
 
@@ -879,6 +1339,103 @@ impl VisitorMut for AddO2IdVisitor {
     }
 }
 
+/// Checks the subqueries of the query. Only `field IN (SELECT field2 FROM stream WHERE ...)` in
+/// the AND conditions of the WHERE clause is supported, it's planned as a semi-join of the two
+/// streams and both are bounded by the time range of the query. The other subqueries aren't
+/// turned into joins and would scan the whole stream.
+fn check_subqueries(statement: &Statement) -> Result<(), String> {
+    let mut visitor = SubqueryVisitor::new();
+    statement.visit(&mut visitor);
+    if visitor.exists {
+        return Err(
+            "EXISTS subquery is not supported, use `field IN (SELECT ...)` instead".to_string(),
+        );
+    }
+    if visitor.scalar {
+        return Err(
+            "scalar subquery is not supported, use `field IN (SELECT ...)` instead".to_string(),
+        );
+    }
+    if visitor.in_subquery == 0 {
+        return Ok(());
+    }
+
+    let mut conditions = Vec::new();
+    if let Statement::Query(query) = statement {
+        if let SetExpr::Select(select) = query.body.as_ref() {
+            if let Some(selection) = select.selection.as_ref() {
+                conditions = split_conjunction(selection);
+            }
+        }
+    }
+    let mut subqueries = 0;
+    for condition in conditions {
+        let Expr::InSubquery { subquery, .. } = condition else {
+            continue;
+        };
+        let SetExpr::Select(select) = subquery.body.as_ref() else {
+            return Err("subquery should be a single SELECT".to_string());
+        };
+        if select.from.len() != 1
+            || !select.from[0].joins.is_empty()
+            || !matches!(select.from[0].relation, TableFactor::Table { .. })
+        {
+            return Err("subquery should read a single stream".to_string());
+        }
+        if select.projection.len() != 1
+            || !matches!(
+                select.projection[0],
+                SelectItem::UnnamedExpr(_) | SelectItem::ExprWithAlias { .. }
+            )
+        {
+            return Err("subquery should select a single field".to_string());
+        }
+        let mut nested_visitor = SubqueryVisitor::new();
+        subquery.visit(&mut nested_visitor);
+        if nested_visitor.in_subquery > 0 {
+            return Err("nested subquery is not supported".to_string());
+        }
+        subqueries += 1;
+    }
+    if subqueries != visitor.in_subquery {
+        return Err(
+            "IN subquery is only supported in the AND conditions of the WHERE clause".to_string(),
+        );
+    }
+    Ok(())
+}
+
+// count the subqueries of the query, by kind
+struct SubqueryVisitor {
+    pub exists: bool,
+    pub scalar: bool,
+    pub in_subquery: usize,
+}
+
+impl SubqueryVisitor {
+    fn new() -> Self {
+        Self {
+            exists: false,
+            scalar: false,
+            in_subquery: 0,
+        }
+    }
+}
+
+impl VisitorMut for SubqueryVisitor {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        match expr {
+            Expr::Exists { .. } => self.exists = true,
+            Expr::Subquery(_) => self.scalar = true,
+            Expr::InSubquery { .. } => self.in_subquery += 1,
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
+
 fn is_complex_query(statement: &mut Statement) -> bool {
     let mut visitor = ComplexQueryVisitor::new();
     statement.visit(&mut visitor);
@@ -1205,3 +1762,938 @@ fn o2_id_is_needed(schemas: &HashMap<String, Arc<SchemaCache>>) -> bool {
         stream_setting.map_or(false, |setting| setting.store_original_data)
     })
 }
+
+/// Rewrites `SELECT DISTINCT field FROM stream` or `SELECT field FROM stream GROUP BY field`
+/// to read the distinct values stream, if the field is one of the distinct fields and
+/// there is no filter. Returns None if the query is not eligible.
+pub fn rewrite_distinct_sql(sql: &str, stream_type: StreamType) -> Option<String> {
+    // only the logs and traces ingestion record the distinct values
+    if !matches!(stream_type, StreamType::Logs | StreamType::Traces) {
+        return None;
+    }
+    rewrite_distinct_sql_for_fields(sql, stream_type, &DISTINCT_FIELDS)
+}
+
+fn rewrite_distinct_sql_for_fields(
+    sql: &str,
+    stream_type: StreamType,
+    distinct_fields: &[String],
+) -> Option<String> {
+    let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, sql).ok()?;
+    if statements.len() != 1 {
+        return None;
+    }
+    let Statement::Query(query) = statements.pop().unwrap() else {
+        return None;
+    };
+    if query.with.is_some() || query.offset.is_some() || query.fetch.is_some() {
+        return None;
+    }
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return None;
+    };
+    // the distinct values stream has no other fields to filter, e.g. match_all()
+    if select.selection.is_some() || select.having.is_some() || select.from.len() != 1 {
+        return None;
+    }
+    let from = &select.from[0];
+    let sqlparser::ast::TableFactor::Table { name, .. } = &from.relation else {
+        return None;
+    };
+    if !from.joins.is_empty() || name.0.len() != 1 {
+        return None;
+    }
+    let stream_name = &name.0[0].value;
+
+    let (field, alias) = match select.projection.as_slice() {
+        [SelectItem::UnnamedExpr(expr)] => {
+            let (path, field) = distinct_field_name(expr)?;
+            (field, path)
+        }
+        [SelectItem::ExprWithAlias { expr, alias }] => {
+            (distinct_field_name(expr)?.1, alias.value.clone())
+        }
+        _ => return None,
+    };
+    if !distinct_fields.contains(&field) {
+        return None;
+    }
+    let is_field = |expr: &Expr| distinct_field_name(expr).is_some_and(|(_, f)| f == field);
+    let is_distinct = match (&select.distinct, &select.group_by) {
+        (Some(sqlparser::ast::Distinct::Distinct), GroupByExpr::Expressions(exprs, _)) => {
+            exprs.is_empty()
+        }
+        (None, GroupByExpr::Expressions(exprs, modifiers)) => {
+            modifiers.is_empty() && matches!(exprs.as_slice(), [expr] if is_field(expr))
+        }
+        _ => false,
+    };
+    if !is_distinct {
+        return None;
+    }
+    let order_by = match query.order_by.as_ref().map(|v| v.exprs.as_slice()) {
+        None | Some([]) => "".to_string(),
+        Some([order])
+            if is_field(&order.expr)
+                || matches!(&order.expr, Expr::Identifier(v) if v.value == alias) =>
+        {
+            if order.asc == Some(false) {
+                " ORDER BY field_value DESC".to_string()
+            } else {
+                " ORDER BY field_value ASC".to_string()
+            }
+        }
+        _ => return None,
+    };
+    let limit = match query.limit.as_ref() {
+        None => "".to_string(),
+        Some(Expr::Value(Value::Number(n, _))) => format!(" LIMIT {n}"),
+        Some(_) => return None,
+    };
+
+    Some(format!(
+        "SELECT field_value AS \"{}\" FROM \"{}\" WHERE stream_type='{}' AND stream_name='{}' AND field_name='{}' GROUP BY field_value{}{}",
+        alias.replace('"', "\"\""),
+        crate::service::metadata::distinct_values::STREAM_NAME,
+        stream_type,
+        stream_name.replace('\'', "''"),
+        field.replace('\'', "''"),
+        order_by,
+        limit,
+    ))
+}
+
+/// Returns the field reference as written and the flattened field it refers to, a nested field
+/// like `kubernetes.namespace` or `"kubernetes.namespace"` is stored as `kubernetes_namespace`
+fn distinct_field_name(expr: &Expr) -> Option<(String, String)> {
+    let path = match expr {
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(idents) => idents.iter().map(|v| v.value.as_str()).join("."),
+        _ => return None,
+    };
+    let field = flattened_field_name(&path);
+    Some((path, field))
+}
+
+/// Replaces the ORDER BY of the query with the sort keys, in the order of the keys. The fields
+/// must be in the result of the query, the projection or the stream fields for a `SELECT *`.
+/// Without keys the query is returned unchanged, with its own ORDER BY.
+pub fn apply_sort_by(
+    sql: &str,
+    keys: &[SortKey],
+    stream_fields: &HashSet<String>,
+) -> Result<String, String> {
+    if keys.is_empty() {
+        return Ok(sql.to_string());
+    }
+    let mut statements =
+        Parser::parse_sql(&PostgreSqlDialect {}, sql).map_err(|e| e.to_string())?;
+    let (1, Some(Statement::Query(query))) = (statements.len(), statements.first_mut()) else {
+        return Err("sort_by requires a single select query".to_string());
+    };
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return Err("sort_by requires a single select query".to_string());
+    };
+    let mut result_fields = HashSet::new();
+    for item in select.projection.iter() {
+        match item {
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => {
+                result_fields.extend(stream_fields.iter().map(|f| f.as_str()));
+            }
+            SelectItem::UnnamedExpr(Expr::Identifier(ident)) => {
+                result_fields.insert(ident.value.as_str());
+            }
+            SelectItem::UnnamedExpr(Expr::CompoundIdentifier(idents)) => {
+                if let Some(ident) = idents.last() {
+                    result_fields.insert(ident.value.as_str());
+                }
+            }
+            SelectItem::ExprWithAlias { alias, .. } => {
+                result_fields.insert(alias.value.as_str());
+            }
+            SelectItem::UnnamedExpr(_) => {}
+        }
+    }
+    if let Some(key) = keys
+        .iter()
+        .find(|k| !result_fields.contains(k.field.as_str()))
+    {
+        return Err(format!(
+            "sort field [{}] isn't in the result of the query",
+            key.field
+        ));
+    }
+
+    let exprs = keys
+        .iter()
+        .map(|k| OrderByExpr {
+            expr: Expr::Identifier(Ident::with_quote('"', &k.field)),
+            asc: Some(k.direction == OrderBy::Asc),
+            nulls_first: None,
+            with_fill: None,
+        })
+        .collect::<Vec<_>>();
+    query.order_by = Some(sqlparser::ast::OrderBy {
+        exprs,
+        interpolate: None,
+    });
+    Ok(query.to_string())
+}
+
+/// Adds the predicate to the WHERE of the query, it's ANDed with the existing conditions. The
+/// predicate must be a single expression, eg: `k8s_pod_name = 'app-0' AND level = 'error'`.
+pub fn add_filter(sql: &str, filter: &str) -> Result<String, String> {
+    let dialect = PostgreSqlDialect {};
+    let mut parser = Parser::new(&dialect)
+        .try_with_sql(filter)
+        .map_err(|e| e.to_string())?;
+    let filter = parser.parse_expr().map_err(|e| e.to_string())?;
+    if parser.peek_token().token != sqlparser::tokenizer::Token::EOF {
+        return Err("filter should be a single expression".to_string());
+    }
+
+    let mut statements = Parser::parse_sql(&dialect, sql).map_err(|e| e.to_string())?;
+    let (1, Some(Statement::Query(query))) = (statements.len(), statements.first_mut()) else {
+        return Err("filter requires a single select query".to_string());
+    };
+    let SetExpr::Select(select) = query.body.as_mut() else {
+        return Err("filter requires a single select query".to_string());
+    };
+    select.selection = Some(match select.selection.take() {
+        Some(selection) => Expr::BinaryOp {
+            left: Box::new(Expr::Nested(Box::new(selection))),
+            op: BinaryOperator::And,
+            right: Box::new(Expr::Nested(Box::new(filter))),
+        },
+        None => filter,
+    });
+    Ok(query.to_string())
+}
+
+/// Validates the sql without executing it, returns the streams and the fields it references
+pub async fn validate(org_id: &str, stream_type: StreamType, sql: &str) -> ValidateSqlResponse {
+    let stream_names = match resolve_stream_names(sql) {
+        Ok(v) => v,
+        Err(e) => {
+            return ValidateSqlResponse {
+                errors: vec![sql_validation_error(e.to_string())],
+                ..Default::default()
+            };
+        }
+    };
+    let mut schemas = HashMap::with_capacity(stream_names.len());
+    for stream_name in stream_names.iter() {
+        // a stream alias has the schema of the stream it belongs to
+        let name = infra::schema::resolve_stream_alias(org_id, stream_name, stream_type).await;
+        if let Ok(schema) = infra::schema::get(org_id, &name, stream_type).await {
+            if !schema.fields().is_empty() {
+                schemas.insert(stream_name.clone(), Arc::new(SchemaCache::new(schema)));
+            }
+        }
+    }
+    validate_statement(sql, stream_names, &schemas)
+}
+
+fn validate_statement(
+    sql: &str,
+    stream_names: Vec<String>,
+    schemas: &HashMap<String, Arc<SchemaCache>>,
+) -> ValidateSqlResponse {
+    let mut res = ValidateSqlResponse {
+        streams: stream_names,
+        ..Default::default()
+    };
+    for stream_name in res.streams.iter() {
+        if !schemas.contains_key(stream_name) {
+            res.errors.push(sql_validation_error(format!(
+                "Stream not found: {stream_name}"
+            )));
+        }
+    }
+    let mut statement = match Parser::parse_sql(&PostgreSqlDialect {}, sql) {
+        Ok(mut v) if v.len() == 1 => v.pop().unwrap(),
+        Ok(_) => {
+            res.errors.push(sql_validation_error(
+                "sql should be a single statement".to_string(),
+            ));
+            return res;
+        }
+        Err(e) => {
+            res.errors.push(sql_validation_error(e.to_string()));
+            return res;
+        }
+    };
+
+    if let Err(e) = check_subqueries(&statement) {
+        res.errors.push(sql_validation_error(e));
+    }
+
+    let field_aliases = field_alias_exprs(schemas);
+    if !field_aliases.is_empty() {
+        statement.visit(&mut FieldAliasVisitor::new(&field_aliases));
+    }
+
+    let mut function_name_visitor = FunctionNameVisitor::new();
+    statement.visit(&mut function_name_visitor);
+    res.has_match_all = function_name_visitor
+        .function_names
+        .iter()
+        .any(|name| name.starts_with("match_all"));
+    let mut field_name_visitor = FieldNameVisitor::new();
+    statement.visit(&mut field_name_visitor);
+
+    // the same rewrites as the search, so their errors are reported
+    if let ControlFlow::Break(e) = statement.visit(&mut MatchAllFieldsVisitor::new()) {
+        res.errors.push(sql_validation_error(e));
+    }
+    let fts_fields = match schemas.values().next() {
+        Some(schema) if schemas.len() == 1 => {
+            get_stream_setting_fts_fields(&unwrap_stream_settings(schema.schema()))
+                .into_iter()
+                .filter(|f| schema.contains_field(f))
+                .collect::<Vec<_>>()
+        }
+        _ => vec![],
+    };
+    if let ControlFlow::Break(e) = statement.visit(&mut MatchAllIgnoreCaseVisitor::new(&fts_fields))
+    {
+        res.errors.push(sql_validation_error(e.to_string()));
+    }
+
+    let mut column_visitor = ColumnVisitor::new(schemas);
+    statement.visit(&mut column_visitor);
+    res.fields = column_visitor
+        .columns
+        .into_iter()
+        .map(|(stream_name, fields)| (stream_name, fields.into_iter().sorted().collect()))
+        .collect();
+
+    // the fields can only be checked when the schemas of all the streams are known
+    if res.streams.iter().all(|name| schemas.contains_key(name)) {
+        let mut alias_visitor = AliasVisitor::new();
+        statement.visit(&mut alias_visitor);
+        for field_name in field_name_visitor.field_names.iter().sorted() {
+            if !alias_visitor.aliases.contains(field_name)
+                && !schemas
+                    .values()
+                    .any(|schema| schema.contains_field(field_name))
+            {
+                res.errors.push(sql_validation_error(format!(
+                    "Field not found: {field_name}"
+                )));
+            }
+        }
+    }
+    res.valid = res.errors.is_empty();
+    res
+}
+
+/// Builds a validation error, with the position the sqlparser reports in the message if any
+fn sql_validation_error(message: String) -> SqlValidationError {
+    let position = RE_ERROR_POSITION.captures(&message).map(|caps| {
+        (
+            caps.get(1).and_then(|v| v.as_str().parse().ok()),
+            caps.get(2).and_then(|v| v.as_str().parse().ok()),
+        )
+    });
+    let (line, column) = position.unwrap_or_default();
+    SqlValidationError {
+        message,
+        line,
+        column,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{DataType, Field};
+    use config::{
+        meta::{
+            search::SortBy,
+            stream::{ComputedColumn, FieldAlias, StreamSettings},
+        },
+        utils::json,
+    };
+
+    use super::*;
+
+    fn rewrite_match_all_fields(sql: &str) -> Result<String, String> {
+        let mut statement = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+        match statement.visit(&mut MatchAllFieldsVisitor::new()) {
+            ControlFlow::Break(e) => Err(e),
+            ControlFlow::Continue(_) => Ok(statement.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_match_all_fields_visitor() {
+        assert_eq!(
+            rewrite_match_all_fields(
+                "SELECT * FROM t WHERE match_all_fields('error', message, log) AND code = 200"
+            )
+            .unwrap(),
+            "SELECT * FROM t WHERE (str_match_ignore_case(message, 'error') OR \
+             str_match_ignore_case(log, 'error')) AND code = 200"
+        );
+        assert_eq!(
+            rewrite_match_all_fields("SELECT * FROM t WHERE match_all('error')").unwrap(),
+            "SELECT * FROM t WHERE match_all('error')"
+        );
+        assert!(
+            rewrite_match_all_fields("SELECT * FROM t WHERE match_all_fields('error')").is_err()
+        );
+        assert!(
+            rewrite_match_all_fields("SELECT * FROM t WHERE match_all_fields(message, log)")
+                .is_err()
+        );
+    }
+
+    fn rewrite_match_all_ignore_case(sql: &str, fields: &[&str]) -> Result<String, Error> {
+        let fields = fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let mut statement = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+        match statement.visit(&mut MatchAllIgnoreCaseVisitor::new(&fields)) {
+            ControlFlow::Break(e) => Err(e),
+            ControlFlow::Continue(_) => Ok(statement.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_match_all_ignore_case_visitor() {
+        assert_eq!(
+            rewrite_match_all_ignore_case(
+                "SELECT * FROM t WHERE match_all_ignore_case('Error') AND code = 200",
+                &["log", "message"]
+            )
+            .unwrap(),
+            "SELECT * FROM t WHERE (lower(\"log\") LIKE lower('%Error%') OR \
+             lower(\"message\") LIKE lower('%Error%')) AND code = 200"
+        );
+        assert_eq!(
+            rewrite_match_all_ignore_case("SELECT * FROM t WHERE match_all('Error')", &["log"])
+                .unwrap(),
+            "SELECT * FROM t WHERE match_all('Error')"
+        );
+        assert!(matches!(
+            rewrite_match_all_ignore_case(
+                "SELECT * FROM t WHERE match_all_ignore_case('Error')",
+                &[]
+            ),
+            Err(Error::ErrorCode(ErrorCodes::FullTextSearchFieldNotFound))
+        ));
+        assert!(
+            rewrite_match_all_ignore_case(
+                "SELECT * FROM t WHERE match_all_ignore_case(log)",
+                &["log"]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_rewrite_distinct_sql() {
+        let expected = "SELECT field_value AS \"service_name\" FROM \"distinct_values\" WHERE stream_type='traces' AND stream_name='default' AND field_name='service_name' GROUP BY field_value";
+        assert_eq!(
+            rewrite_distinct_sql(
+                "SELECT DISTINCT service_name FROM \"default\"",
+                StreamType::Traces
+            )
+            .as_deref(),
+            Some(expected)
+        );
+        assert_eq!(
+            rewrite_distinct_sql(
+                "SELECT service_name FROM \"default\" GROUP BY service_name",
+                StreamType::Traces
+            )
+            .as_deref(),
+            Some(expected)
+        );
+        assert_eq!(
+            rewrite_distinct_sql(
+                "SELECT DISTINCT service_name AS s FROM \"default\" ORDER BY s DESC LIMIT 10",
+                StreamType::Traces
+            )
+            .as_deref(),
+            Some(
+                "SELECT field_value AS \"s\" FROM \"distinct_values\" WHERE stream_type='traces' AND stream_name='default' AND field_name='service_name' GROUP BY field_value ORDER BY field_value DESC LIMIT 10"
+            )
+        );
+
+        // not eligible
+        for sql in [
+            // not a distinct field
+            "SELECT DISTINCT host FROM \"default\"",
+            // a filter
+            "SELECT DISTINCT service_name FROM \"default\" WHERE host = 'a'",
+            "SELECT DISTINCT service_name FROM \"default\" WHERE match_all('error')",
+            // more fields
+            "SELECT DISTINCT service_name, operation_name FROM \"default\"",
+            "SELECT service_name, count(*) FROM \"default\" GROUP BY service_name",
+            // not distinct
+            "SELECT service_name FROM \"default\"",
+            "SELECT service_name FROM \"default\" GROUP BY operation_name",
+            // order by other values
+            "SELECT DISTINCT service_name FROM \"default\" ORDER BY _timestamp",
+            "SELECT DISTINCT service_name FROM \"default\" OFFSET 10",
+        ] {
+            assert_eq!(rewrite_distinct_sql(sql, StreamType::Traces), None, "{sql}");
+        }
+
+        // no distinct values are recorded for the other stream types
+        for stream_type in [StreamType::Metrics, StreamType::Metadata] {
+            assert_eq!(
+                rewrite_distinct_sql("SELECT DISTINCT service_name FROM \"default\"", stream_type),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_rewrite_distinct_sql_nested_field() {
+        let distinct_fields = vec![
+            "kubernetes_namespace".to_string(),
+            "service_name".to_string(),
+        ];
+        let rewrite =
+            |sql| rewrite_distinct_sql_for_fields(sql, StreamType::Logs, &distinct_fields);
+
+        // the nested path is tracked under its flattened name
+        assert_eq!(
+            rewrite("SELECT kubernetes.namespace FROM \"default\" GROUP BY kubernetes.namespace")
+                .as_deref(),
+            Some(
+                "SELECT field_value AS \"kubernetes.namespace\" FROM \"distinct_values\" WHERE stream_type='logs' AND stream_name='default' AND field_name='kubernetes_namespace' GROUP BY field_value"
+            )
+        );
+        assert_eq!(
+            rewrite(
+                "SELECT DISTINCT \"kubernetes.namespace\" AS ns FROM \"default\" ORDER BY ns DESC"
+            )
+            .as_deref(),
+            Some(
+                "SELECT field_value AS \"ns\" FROM \"distinct_values\" WHERE stream_type='logs' AND stream_name='default' AND field_name='kubernetes_namespace' GROUP BY field_value ORDER BY field_value DESC"
+            )
+        );
+        // the path and the flattened name refer to the same field
+        assert!(rewrite(
+            "SELECT kubernetes_namespace FROM \"default\" GROUP BY kubernetes.namespace ORDER BY kubernetes.namespace"
+        )
+        .is_some());
+
+        // not tracked
+        for sql in [
+            "SELECT kubernetes.pod_name FROM \"default\" GROUP BY kubernetes.pod_name",
+            "SELECT DISTINCT \"kubernetes.labels.app\" FROM \"default\"",
+            "SELECT kubernetes.namespace FROM \"default\" GROUP BY kubernetes.pod_name",
+        ] {
+            assert_eq!(rewrite(sql), None, "{sql}");
+        }
+        assert_eq!(
+            rewrite_distinct_sql(
+                "SELECT DISTINCT kubernetes.namespace FROM \"default\"",
+                StreamType::Logs
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_sort_by() {
+        let stream_fields = HashSet::from([
+            "_timestamp".to_string(),
+            "severity".to_string(),
+            "message".to_string(),
+        ]);
+        let keys = SortBy::Keys(vec![
+            SortKey {
+                field: "severity".to_string(),
+                direction: OrderBy::Desc,
+            },
+            SortKey {
+                field: "_timestamp".to_string(),
+                direction: OrderBy::Asc,
+            },
+        ])
+        .keys()
+        .unwrap();
+        assert_eq!(
+            apply_sort_by("SELECT * FROM t ORDER BY message", &keys, &stream_fields).unwrap(),
+            "SELECT * FROM t ORDER BY \"severity\" DESC, \"_timestamp\" ASC"
+        );
+
+        // the single string form is a one element list
+        let keys = SortBy::from("severity".to_string()).keys().unwrap();
+        assert_eq!(
+            apply_sort_by(
+                "SELECT severity, count(*) AS cnt FROM t GROUP BY severity",
+                &keys,
+                &stream_fields
+            )
+            .unwrap(),
+            "SELECT severity, count(*) AS cnt FROM t GROUP BY severity ORDER BY \"severity\" DESC"
+        );
+        let keys = SortBy::from("cnt asc, severity DESC".to_string())
+            .keys()
+            .unwrap();
+        assert_eq!(
+            apply_sort_by(
+                "SELECT severity, count(*) AS cnt FROM t GROUP BY severity",
+                &keys,
+                &stream_fields
+            )
+            .unwrap(),
+            "SELECT severity, count(*) AS cnt FROM t GROUP BY severity ORDER BY \"cnt\" ASC, \"severity\" DESC"
+        );
+
+        // not in the result
+        let keys = SortBy::from("message".to_string()).keys().unwrap();
+        assert!(
+            apply_sort_by(
+                "SELECT severity, count(*) AS cnt FROM t GROUP BY severity",
+                &keys,
+                &stream_fields
+            )
+            .is_err()
+        );
+        let keys = SortBy::from("host".to_string()).keys().unwrap();
+        assert!(apply_sort_by("SELECT * FROM t", &keys, &stream_fields).is_err());
+
+        // no keys keep the order of the query
+        let sql = "SELECT * FROM t ORDER BY message DESC";
+        assert_eq!(apply_sort_by(sql, &[], &stream_fields).unwrap(), sql);
+        assert!(SortBy::from("host up".to_string()).keys().is_err());
+    }
+
+    #[test]
+    fn test_validate_statement() {
+        let schema = Schema::new(vec![
+            Field::new("_timestamp", DataType::Int64, false),
+            Field::new("level", DataType::Utf8, true),
+            Field::new("message", DataType::Utf8, true),
+        ]);
+        let schemas = HashMap::from([("t".to_string(), Arc::new(SchemaCache::new(schema)))]);
+        let validate =
+            |sql: &str| validate_statement(sql, resolve_stream_names(sql).unwrap(), &schemas);
+
+        let res = validate(
+            "SELECT level, count(*) AS cnt FROM t WHERE match_all('error') GROUP BY level ORDER BY cnt DESC",
+        );
+        assert!(res.valid, "{:?}", res.errors);
+        assert_eq!(res.streams, vec!["t".to_string()]);
+        assert_eq!(res.fields.get("t").unwrap(), &vec!["level".to_string()]);
+        assert!(res.has_match_all);
+
+        let res = validate("SELECT message FROM t WHERE level = \"error\"");
+        assert!(!res.valid);
+        assert!(!res.has_match_all);
+        assert_eq!(res.errors[0].message, "Field not found: error");
+
+        let res = validate("SELECT * FROM t2 WHERE host = 'a'");
+        assert!(!res.valid);
+        assert_eq!(res.errors[0].message, "Stream not found: t2");
+        assert_eq!(res.errors.len(), 1);
+
+        let res = validate("SELECT * FROM t WHERE match_all_fields('error')");
+        assert!(!res.valid);
+        assert!(res.has_match_all);
+    }
+
+    #[test]
+    fn test_check_subqueries() {
+        let check = |sql: &str| {
+            let statement = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+                .unwrap()
+                .pop()
+                .unwrap();
+            check_subqueries(&statement)
+        };
+
+        assert!(check("SELECT * FROM app WHERE level = 'error'").is_ok());
+        assert!(check(
+            "SELECT * FROM app WHERE level = 'error' AND request_id IN (SELECT request_id FROM errors WHERE code >= 500)"
+        )
+        .is_ok());
+        assert!(check(
+            "SELECT count(*) FROM app WHERE (request_id IN (SELECT id AS request_id FROM errors)) AND host = 'a'"
+        )
+        .is_ok());
+
+        let err = |sql: &str| check(sql).unwrap_err();
+        assert!(
+            err("SELECT * FROM app WHERE EXISTS (SELECT 1 FROM errors WHERE errors.id = app.id)")
+                .starts_with("EXISTS subquery is not supported")
+        );
+        assert!(
+            err("SELECT *, (SELECT max(code) FROM errors) AS m FROM app")
+                .starts_with("scalar subquery is not supported")
+        );
+        assert_eq!(
+            err("SELECT * FROM app WHERE level = 'error' OR id IN (SELECT id FROM errors)"),
+            "IN subquery is only supported in the AND conditions of the WHERE clause"
+        );
+        assert_eq!(
+            err(
+                "SELECT * FROM app WHERE id IN (SELECT id FROM errors WHERE id IN (SELECT id FROM audit))"
+            ),
+            "nested subquery is not supported"
+        );
+        assert_eq!(
+            err(
+                "SELECT * FROM app WHERE id IN (SELECT e.id FROM errors AS e JOIN audit AS a ON e.id = a.id)"
+            ),
+            "subquery should read a single stream"
+        );
+        assert_eq!(
+            err("SELECT * FROM app WHERE id IN (SELECT * FROM errors)"),
+            "subquery should select a single field"
+        );
+        assert_eq!(
+            err("SELECT * FROM app WHERE id IN (SELECT id FROM errors UNION SELECT id FROM audit)"),
+            "subquery should be a single SELECT"
+        );
+    }
+
+    fn rewrite_field_aliases(sql: &str) -> String {
+        let settings = StreamSettings {
+            field_aliases: vec![FieldAlias {
+                name: "kubernetes_pod_name".to_string(),
+                path: "kubernetes.pod_name".to_string(),
+            }],
+            ..Default::default()
+        };
+        let schema = Schema::new(vec![Field::new("kubernetes", DataType::Utf8, true)])
+            .with_metadata(std::collections::HashMap::from([(
+                "settings".to_string(),
+                json::to_string(&settings).unwrap(),
+            )]));
+        let schemas = HashMap::from([("t".to_string(), Arc::new(SchemaCache::new(schema)))]);
+        let aliases = field_alias_exprs(&schemas);
+        let mut statement = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+        statement.visit(&mut FieldAliasVisitor::new(&aliases));
+        statement.to_string()
+    }
+
+    #[test]
+    fn test_field_alias_visitor() {
+        assert_eq!(
+            rewrite_field_aliases("SELECT kubernetes_pod_name, message FROM t"),
+            "SELECT json_get_path(\"kubernetes\", 'pod_name') AS \"kubernetes_pod_name\", message FROM t"
+        );
+        assert_eq!(
+            rewrite_field_aliases("SELECT * FROM t WHERE kubernetes_pod_name = 'app-0'"),
+            "SELECT * FROM t WHERE json_get_path(\"kubernetes\", 'pod_name') = 'app-0'"
+        );
+        assert_eq!(
+            rewrite_field_aliases(
+                "SELECT kubernetes_pod_name AS pod, count(*) FROM t GROUP BY kubernetes_pod_name"
+            ),
+            "SELECT json_get_path(\"kubernetes\", 'pod_name') AS pod, count(*) FROM t GROUP BY json_get_path(\"kubernetes\", 'pod_name')"
+        );
+        // the real fields are left as they are
+        assert_eq!(
+            rewrite_field_aliases("SELECT kubernetes FROM t WHERE pod_name = 'a'"),
+            "SELECT kubernetes FROM t WHERE pod_name = 'a'"
+        );
+    }
+
+    #[test]
+    fn test_field_alias_shadowed() {
+        let settings = StreamSettings {
+            field_aliases: vec![FieldAlias {
+                name: "kubernetes_pod_name".to_string(),
+                path: "kubernetes.pod_name".to_string(),
+            }],
+            computed_columns: vec![ComputedColumn {
+                name: "status".to_string(),
+                expr: "response_code".to_string(),
+            }],
+            ..Default::default()
+        };
+        // the fields were ingested after the alias and the computed column were saved
+        let schema = Schema::new(vec![
+            Field::new("kubernetes", DataType::Utf8, true),
+            Field::new("kubernetes_pod_name", DataType::Utf8, true),
+            Field::new("response_code", DataType::Int64, true),
+        ])
+        .with_metadata(std::collections::HashMap::from([(
+            "settings".to_string(),
+            json::to_string(&settings).unwrap(),
+        )]));
+        let other = Schema::new(vec![Field::new("status", DataType::Int64, true)]);
+        let schemas = HashMap::from([
+            ("t".to_string(), Arc::new(SchemaCache::new(schema))),
+            ("u".to_string(), Arc::new(SchemaCache::new(other))),
+        ]);
+        assert!(field_alias_exprs(&schemas).is_empty());
+    }
+
+    fn rewrite_computed_columns(sql: &str) -> String {
+        let settings = StreamSettings {
+            computed_columns: vec![
+                ComputedColumn {
+                    name: "latency_ms".to_string(),
+                    expr: "duration / 1000".to_string(),
+                },
+                ComputedColumn {
+                    name: "status".to_string(),
+                    expr: "response_code".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let schema = Schema::new(vec![
+            Field::new("duration", DataType::Int64, true),
+            Field::new("response_code", DataType::Int64, true),
+        ])
+        .with_metadata(std::collections::HashMap::from([(
+            "settings".to_string(),
+            json::to_string(&settings).unwrap(),
+        )]));
+        let schemas = HashMap::from([("t".to_string(), Arc::new(SchemaCache::new(schema)))]);
+        let columns = field_alias_exprs(&schemas);
+        let mut statement = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+        statement.visit(&mut FieldAliasVisitor::new(&columns));
+        statement.to_string()
+    }
+
+    #[test]
+    fn test_computed_columns() {
+        // a rename alias
+        assert_eq!(
+            rewrite_computed_columns(
+                "SELECT status, count(*) FROM t WHERE status = 500 GROUP BY status"
+            ),
+            "SELECT response_code AS \"status\", count(*) FROM t WHERE response_code = 500 GROUP BY response_code"
+        );
+        // an arithmetic column in a filter keeps its precedence
+        assert_eq!(
+            rewrite_computed_columns("SELECT latency_ms FROM t WHERE latency_ms * 2 > 100"),
+            "SELECT (duration / 1000) AS \"latency_ms\" FROM t WHERE (duration / 1000) * 2 > 100"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fields_projection() {
+        let schema = Schema::new(vec![
+            Field::new("_timestamp", DataType::Int64, false),
+            Field::new("message", DataType::Utf8, true),
+            Field::new("code", DataType::Int64, true),
+            Field::new("host", DataType::Utf8, true),
+        ]);
+        let schemas =
+            HashMap::from([("t".to_string(), Arc::new(SchemaCache::new(schema.clone())))]);
+        let requested = ["message", "code", "message"].map(String::from);
+        let fields = projection_fields(&requested, &schemas, &HashMap::new()).unwrap();
+        assert_eq!(fields, vec!["_timestamp", "message", "code"]);
+        let requested = ["message", "unknown", "other"].map(String::from);
+        assert_eq!(
+            projection_fields(&requested, &schemas, &HashMap::new())
+                .unwrap_err()
+                .to_string(),
+            Error::ErrorCode(ErrorCodes::SearchFieldNotFound(
+                "unknown, other".to_string()
+            ))
+            .to_string()
+        );
+
+        let rewrite = |sql: &str| {
+            let mut statement = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+                .unwrap()
+                .pop()
+                .unwrap();
+            statement.visit(&mut FieldsProjectionVisitor::new(&fields));
+            statement.to_string()
+        };
+        let sql = rewrite("SELECT * FROM t WHERE host = 'a'");
+        assert_eq!(
+            sql,
+            "SELECT \"_timestamp\", \"message\", \"code\" FROM t WHERE host = 'a'"
+        );
+        // an explicit projection is left as it is
+        assert_eq!(
+            rewrite("SELECT host FROM t WHERE code = 200"),
+            "SELECT host FROM t WHERE code = 200"
+        );
+
+        // only the requested columns are read from the table
+        let ctx = datafusion::prelude::SessionContext::new();
+        let table =
+            datafusion::datasource::MemTable::try_new(Arc::new(schema), vec![vec![]]).unwrap();
+        ctx.register_table("t", Arc::new(table)).unwrap();
+        let plan = ctx
+            .sql(&rewrite("SELECT * FROM t"))
+            .await
+            .unwrap()
+            .into_optimized_plan()
+            .unwrap();
+        assert!(
+            plan.display_indent()
+                .to_string()
+                .contains("TableScan: t projection=[_timestamp, message, code]"),
+            "{}",
+            plan.display_indent()
+        );
+    }
+
+    #[test]
+    fn test_stream_alias_visitor() {
+        let aliases = HashMap::from([("old_app".to_string(), "app".to_string())]);
+        let rewrite = |sql: &str| {
+            let mut statement = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+                .unwrap()
+                .pop()
+                .unwrap();
+            statement.visit(&mut StreamAliasVisitor::new(&aliases));
+            statement.to_string()
+        };
+        assert_eq!(
+            rewrite("SELECT * FROM \"old_app\" WHERE level = 'error'"),
+            "SELECT * FROM \"app\" WHERE level = 'error'"
+        );
+        assert_eq!(
+            rewrite("SELECT a.level FROM old_app AS a JOIN web AS b ON a.id = b.id"),
+            "SELECT a.level FROM \"app\" AS a JOIN web AS b ON a.id = b.id"
+        );
+        assert_eq!(
+            rewrite("SELECT * FROM web WHERE id IN (SELECT id FROM old_app)"),
+            "SELECT * FROM web WHERE id IN (SELECT id FROM \"app\")"
+        );
+    }
+
+    #[test]
+    fn test_sql_validation_error() {
+        let err = sql_validation_error(
+            "sql parser error: Expected: an expression, found: FROM at Line: 2, Column: 8"
+                .to_string(),
+        );
+        assert_eq!((err.line, err.column), (Some(2), Some(8)));
+        let err = sql_validation_error("Stream not found: t".to_string());
+        assert_eq!((err.line, err.column), (None, None));
+    }
+
+    #[test]
+    fn test_add_filter() {
+        assert_eq!(
+            add_filter("SELECT * FROM t", "pod = 'app-0' AND level = 'error'").unwrap(),
+            "SELECT * FROM t WHERE pod = 'app-0' AND level = 'error'"
+        );
+        assert_eq!(
+            add_filter("SELECT * FROM t WHERE a = 1 OR b = 2", "pod = 'app-0'").unwrap(),
+            "SELECT * FROM t WHERE (a = 1 OR b = 2) AND (pod = 'app-0')"
+        );
+        assert!(add_filter("SELECT * FROM t", "pod = 'app-0'; DROP TABLE t").is_err());
+        assert!(add_filter("SELECT * FROM t", "").is_err());
+    }
+}
@@ -44,6 +44,7 @@ use crate::service::search::{
             NewEmptyExecVisitor,
         },
         exec::prepare_datafusion_context,
+        udf::json_get_path_udf::register_json_get_path,
     },
     generate_filter_from_equal_items,
     request::Request,
@@ -78,6 +79,7 @@ pub async fn search(
 
     // register function
     datafusion_functions_json::register_all(&mut ctx)?;
+    register_json_get_path(&ctx);
 
     // Decode physical plan from bytes
     let proto = ComposedPhysicalExtensionCodec {
@@ -279,6 +281,10 @@ async fn get_inverted_index_file_lists(
     let stream_settings = infra::schema::unwrap_stream_settings(&schema);
     let index_fields = get_stream_setting_index_fields(&stream_settings);
     let index_terms = super::super::filter_index_fields(&equal_terms, &index_fields);
+    // the parquet format index doesn't support the prefix terms
+    if index_terms.is_empty() && match_terms.is_empty() {
+        return Ok((false, vec![], 0, 0));
+    }
 
     // construct SearchQuery for inverted index search
     let (start_time, end_time) = req.time_range.unwrap_or((0, 0));
@@ -19,7 +19,10 @@ use arrow::array::RecordBatch;
 use async_recursion::async_recursion;
 use config::{
     get_config,
-    meta::{cluster::NodeInfo, search::ScanStats},
+    meta::{
+        cluster::NodeInfo,
+        search::{ResponseRegionTook, ResponseWarning, ResponseWarningCode, ScanStats},
+    },
 };
 use datafusion::{
     common::{tree_node::TreeNode, DataFusionError},
@@ -33,8 +36,11 @@ use tracing::{info_span, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::service::search::{
-    cluster::flight::{generate_context, register_table},
-    datafusion::distributed_plan::{remote_scan::RemoteScanExec, rewrite::RemoteScanRewriter},
+    cluster::flight::{generate_context, register_table, spill_warning},
+    datafusion::{
+        distributed_plan::{remote_scan::RemoteScanExec, rewrite::RemoteScanRewriter},
+        udf::json_get_path_udf::register_json_get_path,
+    },
     request::Request,
     sql::Sql,
     utlis::ScanStatsVisitor,
@@ -53,7 +59,15 @@ pub async fn search(
     mut req: Request,
     req_regions: Vec<String>,
     req_clusters: Vec<String>,
-) -> Result<(Vec<RecordBatch>, ScanStats, usize, bool, usize, String)> {
+) -> Result<(
+    Vec<RecordBatch>,
+    ScanStats,
+    usize,
+    bool,
+    usize,
+    Vec<ResponseWarning>,
+    Vec<ResponseRegionTook>,
+)> {
     let _start = std::time::Instant::now();
     let cfg = get_config();
     log::info!("[trace_id {trace_id}] super cluster leader: start {}", sql);
@@ -70,11 +84,12 @@ pub async fn search(
         .iter()
         .any(|(_, schema)| schema.schema().fields().is_empty())
     {
-        return Ok((vec![], ScanStats::new(), 0, false, 0, "".to_string()));
+        return Ok((vec![], ScanStats::new(), 0, false, 0, vec![], vec![]));
     }
 
-    let (use_inverted_index, _) = super::super::is_use_inverted_index(&sql);
+    let (use_inverted_index, _, index_prefix_terms) = super::super::is_use_inverted_index(&sql);
     req.set_use_inverted_index(use_inverted_index);
+    req.set_index_prefix_terms(index_prefix_terms);
 
     // 2. get nodes
     let nodes = get_cluster_nodes(trace_id, req_regions, req_clusters).await?;
@@ -137,7 +152,7 @@ pub async fn search(
         Ok(Err(err)) => Err(err),
         Err(err) => Err(Error::Message(err.to_string())),
     };
-    let (data, mut scan_stats, partial_err) = match data {
+    let (data, mut scan_stats, partial_err, spill_count, regions_took) = match data {
         Ok(v) => v,
         Err(e) => {
             return Err(e);
@@ -146,8 +161,24 @@ pub async fn search(
 
     log::info!("[trace_id {trace_id}] super cluster leader: search finished");
 
+    let mut warnings = Vec::new();
+    if !partial_err.is_empty() {
+        warnings.push(ResponseWarning::new(
+            ResponseWarningCode::NodeFailed,
+            partial_err,
+        ));
+    }
+    // spilling is slower but the data is complete
+    let is_partial = !warnings.is_empty();
+    if spill_count > 0 {
+        log::warn!(
+            "[trace_id {trace_id}] super cluster leader: spilled to disk {spill_count} times"
+        );
+        warnings.push(spill_warning());
+    }
+
     scan_stats.format_to_mb();
-    Ok((data, scan_stats, 0, !partial_err.is_empty(), 0, partial_err))
+    Ok((data, scan_stats, 0, is_partial, 0, warnings, regions_took))
 }
 
 async fn run_datafusion(
@@ -155,7 +186,13 @@ async fn run_datafusion(
     req: Request,
     sql: Arc<Sql>,
     nodes: Vec<Arc<dyn NodeInfo>>,
-) -> Result<(Vec<RecordBatch>, ScanStats, String)> {
+) -> Result<(
+    Vec<RecordBatch>,
+    ScanStats,
+    String,
+    usize,
+    Vec<ResponseRegionTook>,
+)> {
     let cfg = get_config();
     // construct physical plan
     let mut ctx = match generate_context(&req, &sql, cfg.limit.cpu_num).await {
@@ -168,6 +205,7 @@ async fn run_datafusion(
     // register table
     register_table(&ctx, &sql).await?;
     datafusion_functions_json::register_all(&mut ctx)?;
+    register_json_get_path(&ctx);
 
     // create physical plan
     let plan = match ctx.state().create_logical_plan(&sql.sql).await {
@@ -265,7 +303,15 @@ async fn run_datafusion(
         Err(e.into())
     } else {
         log::info!("[trace_id {trace_id}] super cluster leader: datafusion collect done");
-        ret.map(|data| (data, visit.scan_stats, visit.partial_err))
-            .map_err(|e| e.into())
+        ret.map(|data| {
+            (
+                data,
+                visit.scan_stats,
+                visit.partial_err,
+                visit.spill_count,
+                visit.regions_took,
+            )
+        })
+        .map_err(|e| e.into())
     }
 }
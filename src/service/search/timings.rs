@@ -0,0 +1,78 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The timings of the recent searches, kept in memory on the node which handled the search so a
+//! slow request can be diagnosed by its trace id without tracing enabled.
+
+use std::collections::VecDeque;
+
+use config::{get_config, meta::search::SearchTiming};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+
+static TIMINGS: Lazy<RwLock<VecDeque<SearchTiming>>> = Lazy::new(|| RwLock::new(VecDeque::new()));
+
+/// Records the timing of a search, the oldest one is dropped when the buffer is full
+pub fn record(timing: SearchTiming) {
+    let size = get_config().limit.query_timings_buffer_size;
+    push(&mut TIMINGS.write(), timing, size);
+}
+
+/// Returns the timings of the searches of the trace, a trace can have several searches, e.g.
+/// the partitions of a query
+pub fn get(org_id: &str, trace_id: &str) -> Vec<SearchTiming> {
+    TIMINGS
+        .read()
+        .iter()
+        .filter(|t| t.org_id == org_id && t.trace_id == trace_id)
+        .cloned()
+        .collect()
+}
+
+fn push(timings: &mut VecDeque<SearchTiming>, timing: SearchTiming, size: usize) {
+    if size == 0 {
+        timings.clear();
+        return;
+    }
+    while timings.len() >= size {
+        timings.pop_front();
+    }
+    timings.push_back(timing);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        let mut timings = VecDeque::new();
+        for i in 0..5 {
+            let timing = SearchTiming {
+                trace_id: i.to_string(),
+                ..Default::default()
+            };
+            push(&mut timings, timing, 3);
+        }
+        let ids = timings
+            .iter()
+            .map(|t| t.trace_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["2", "3", "4"]);
+
+        push(&mut timings, SearchTiming::default(), 0);
+        assert!(timings.is_empty());
+    }
+}
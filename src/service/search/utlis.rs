@@ -13,13 +13,20 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+};
 
-use config::meta::search::ScanStats;
+use config::meta::search::{ResponseRegionTook, ScanStats};
 use datafusion::physical_plan::{ExecutionPlan, ExecutionPlanVisitor};
 use tokio::sync::Mutex;
 
-use super::{datafusion::distributed_plan::remote_scan::RemoteScanExec, DATAFUSION_RUNTIME};
+use super::{
+    datafusion::{distributed_plan::remote_scan::RemoteScanExec, group_limit::GroupLimitExec},
+    DATAFUSION_RUNTIME,
+};
 
 type Cleanup = Pin<Box<dyn Future<Output = ()> + Send>>;
 
@@ -54,6 +61,11 @@ impl Drop for AsyncDefer {
 pub struct ScanStatsVisitor {
     pub scan_stats: ScanStats,
     pub partial_err: String,
+    /// The number of spills of the sorts and aggregations of this node
+    pub spill_count: usize,
+    /// The groups over the max groups were dropped from the results
+    pub groups_truncated: bool,
+    pub regions_took: Vec<ResponseRegionTook>,
 }
 
 impl ScanStatsVisitor {
@@ -61,6 +73,9 @@ impl ScanStatsVisitor {
         ScanStatsVisitor {
             scan_stats: ScanStats::default(),
             partial_err: String::new(),
+            spill_count: 0,
+            groups_truncated: false,
+            regions_took: Vec::new(),
         }
     }
 }
@@ -69,6 +84,12 @@ impl ExecutionPlanVisitor for ScanStatsVisitor {
     type Error = datafusion::common::DataFusionError;
 
     fn pre_visit(&mut self, plan: &dyn ExecutionPlan) -> Result<bool, Self::Error> {
+        if let Some(spill_count) = plan.metrics().and_then(|m| m.spill_count()) {
+            self.spill_count += spill_count;
+        }
+        if let Some(group_limit_exec) = plan.as_any().downcast_ref::<GroupLimitExec>() {
+            self.groups_truncated |= group_limit_exec.truncated.load(Ordering::Relaxed);
+        }
         let mayby_remote_scan_exec = plan.as_any().downcast_ref::<RemoteScanExec>();
         if let Some(remote_scan_exec) = mayby_remote_scan_exec {
             {
@@ -81,6 +102,8 @@ impl ExecutionPlanVisitor for ScanStatsVisitor {
                 let err = (*guard).clone();
                 self.partial_err.push_str(&err);
             }
+            self.regions_took
+                .extend(remote_scan_exec.regions_took.lock().iter().cloned());
         }
         Ok(true)
     }
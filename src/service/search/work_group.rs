@@ -0,0 +1,188 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The concurrency limit of each work group. A query runs in the `long` group when it scans more
+//! than `ZO_SEARCH_WORK_GROUP_LONG_QUERY_SIZE`, else in the `short` group. Each group has its own
+//! slots, so the long queries can't take the slots of the short ones. When a group is full the
+//! query waits for a slot in arrival order, up to `ZO_SEARCH_WORK_GROUP_MAX_QUEUE_TIME`.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use config::{get_config, metrics};
+use infra::errors::{Error, Result};
+use once_cell::sync::Lazy;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+static LIMITER: Lazy<WorkGroupLimiter> = Lazy::new(|| {
+    let cfg = get_config();
+    WorkGroupLimiter::new(
+        cfg.limit.search_work_group_short_max_concurrency,
+        cfg.limit.search_work_group_long_max_concurrency,
+    )
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkGroup {
+    Short,
+    Long,
+}
+
+impl WorkGroup {
+    /// Returns the group of a query scanning the bytes
+    pub fn from_scan_size(scan_size: i64) -> Self {
+        let long_query_size = get_config().limit.search_work_group_long_query_size * 1024 * 1024;
+        if scan_size > long_query_size as i64 {
+            WorkGroup::Long
+        } else {
+            WorkGroup::Short
+        }
+    }
+}
+
+impl fmt::Display for WorkGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkGroup::Short => write!(f, "short"),
+            WorkGroup::Long => write!(f, "long"),
+        }
+    }
+}
+
+/// A slot of a work group, it is given back when dropped
+pub struct WorkGroupPermit {
+    work_group: WorkGroup,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for WorkGroupPermit {
+    fn drop(&mut self) {
+        metrics::QUERY_WORK_GROUP_RUNNING_NUMS
+            .with_label_values(&[&self.work_group.to_string()])
+            .dec();
+    }
+}
+
+pub struct WorkGroupLimiter {
+    short: Arc<Semaphore>,
+    long: Arc<Semaphore>,
+}
+
+impl WorkGroupLimiter {
+    pub fn new(short_max_concurrency: usize, long_max_concurrency: usize) -> Self {
+        Self {
+            short: Arc::new(Semaphore::new(short_max_concurrency.max(1))),
+            long: Arc::new(Semaphore::new(long_max_concurrency.max(1))),
+        }
+    }
+
+    /// Waits for a slot of the group, fails when none is free within the max wait
+    pub async fn acquire(
+        &self,
+        work_group: WorkGroup,
+        max_wait: Duration,
+    ) -> Result<WorkGroupPermit> {
+        let semaphore = match work_group {
+            WorkGroup::Short => self.short.clone(),
+            WorkGroup::Long => self.long.clone(),
+        };
+        let label = work_group.to_string();
+        metrics::QUERY_WORK_GROUP_QUEUED_NUMS
+            .with_label_values(&[&label])
+            .inc();
+        let permit = tokio::time::timeout(max_wait, semaphore.acquire_owned()).await;
+        metrics::QUERY_WORK_GROUP_QUEUED_NUMS
+            .with_label_values(&[&label])
+            .dec();
+        match permit {
+            Ok(Ok(permit)) => {
+                metrics::QUERY_WORK_GROUP_RUNNING_NUMS
+                    .with_label_values(&[&label])
+                    .inc();
+                Ok(WorkGroupPermit {
+                    work_group,
+                    _permit: permit,
+                })
+            }
+            Ok(Err(e)) => Err(Error::Message(e.to_string())),
+            Err(_) => Err(Error::Message(format!(
+                "the {label} work group is at capacity, no slot was free within {} seconds",
+                max_wait.as_secs()
+            ))),
+        }
+    }
+}
+
+/// Waits for a slot of the work group on this node, the search runs while the permit is held
+pub async fn acquire(trace_id: &str, work_group: WorkGroup) -> Result<WorkGroupPermit> {
+    let max_wait = Duration::from_secs(get_config().limit.search_work_group_max_queue_time);
+    let permit = LIMITER.acquire(work_group, max_wait).await;
+    if let Err(e) = permit.as_ref() {
+        log::warn!("[trace_id {trace_id}] search: work group {work_group}: {e}");
+    }
+    permit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_work_group_queue() {
+        let limiter = Arc::new(WorkGroupLimiter::new(1, 2));
+        let wait = Duration::from_secs(5);
+        let first = limiter.acquire(WorkGroup::Long, wait).await.unwrap();
+        let second = limiter.acquire(WorkGroup::Long, wait).await.unwrap();
+
+        // the long group is full, the third long query waits
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(WorkGroup::Long, wait).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!queued.is_finished());
+
+        // the short lane is reserved, a short query doesn't wait for the long ones
+        let short = limiter
+            .acquire(WorkGroup::Short, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert!(
+            limiter
+                .acquire(WorkGroup::Short, Duration::from_millis(10))
+                .await
+                .is_err()
+        );
+        assert!(!queued.is_finished());
+
+        // the queued query is admitted when a slot is free
+        drop(first);
+        let third = queued.await.unwrap().unwrap();
+        assert!(
+            limiter
+                .acquire(WorkGroup::Long, Duration::from_millis(10))
+                .await
+                .is_err()
+        );
+        drop(second);
+        drop(third);
+        drop(short);
+        assert!(
+            limiter
+                .acquire(WorkGroup::Long, Duration::from_millis(10))
+                .await
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,41 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use async_trait::async_trait;
+use config::{get_config, get_secrets_manager_client};
+
+use super::SecretBackend;
+
+/// The secrets in AWS Secrets Manager, the secrets of an org have the ids
+/// `<ZO_SECRET_AWS_PREFIX><org_id>/<name>`
+pub struct SecretsManager;
+
+#[async_trait]
+impl SecretBackend for SecretsManager {
+    async fn get(&self, org_id: &str, name: &str) -> Result<String, anyhow::Error> {
+        let secret_id = format!("{}{org_id}/{name}", get_config().secret.aws_prefix);
+        let output = get_secrets_manager_client()
+            .await
+            .get_secret_value()
+            .secret_id(&secret_id)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("{secret_id}: {}", e.into_service_error()))?;
+        output
+            .secret_string()
+            .map(|v| v.to_string())
+            .ok_or_else(|| anyhow::anyhow!("{secret_id} isn't a string secret"))
+    }
+}
@@ -0,0 +1,32 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use async_trait::async_trait;
+use config::get_config;
+
+use super::SecretBackend;
+
+/// The secrets in the environment variables of the node, `secret://env/WEBHOOK_TOKEN` reads
+/// `O2_SECRET_WEBHOOK_TOKEN`. Only the variables with the `ZO_SECRET_ENV_PREFIX` can be read,
+/// they are shared by all the orgs.
+pub struct Env;
+
+#[async_trait]
+impl SecretBackend for Env {
+    async fn get(&self, _org_id: &str, name: &str) -> Result<String, anyhow::Error> {
+        let var = format!("{}{name}", get_config().secret.env_prefix);
+        std::env::var(&var).map_err(|e| anyhow::anyhow!("env {var}: {e}"))
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The credentials kept in a secret backend instead of the db. A credential is stored as a
+//! reference, `secret://<backend>/<name>[#<key>]`, and resolved when it's used. With a `#key` the
+//! secret is a JSON object and the value of the key is taken. The resolved secrets are cached
+//! for `ZO_SECRET_CACHE_TTL` seconds.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use config::{get_config, utils::json};
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+
+pub mod aws;
+pub mod env;
+pub mod vault;
+
+pub const SECRET_REF_PREFIX: &str = "secret://";
+
+static RESOLVER: Lazy<SecretResolver> = Lazy::new(|| {
+    let resolver = SecretResolver::new(Duration::from_secs(get_config().secret.cache_ttl));
+    resolver.register("env", Arc::new(env::Env));
+    resolver.register("vault", Arc::new(vault::Vault));
+    resolver.register("aws", Arc::new(aws::SecretsManager));
+    resolver
+});
+
+/// A store the secrets are fetched from
+#[async_trait]
+pub trait SecretBackend: Send + Sync {
+    /// Fetches the secret `name` of the org
+    async fn get(&self, org_id: &str, name: &str) -> Result<String, anyhow::Error>;
+}
+
+/// A reference to a secret, `secret://<backend>/<name>[#<key>]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretRef {
+    pub backend: String,
+    pub name: String,
+    pub key: Option<String>,
+}
+
+impl SecretRef {
+    /// Parses the value, none if it isn't a reference but the credential itself
+    pub fn parse(value: &str) -> Option<Result<Self, anyhow::Error>> {
+        let reference = value.strip_prefix(SECRET_REF_PREFIX)?;
+        let (path, key) = match reference.split_once('#') {
+            Some((path, key)) => (path, Some(key)),
+            None => (reference, None),
+        };
+        let (backend, name) = path.split_once('/').unwrap_or((path, ""));
+        if backend.is_empty() || name.is_empty() || key.is_some_and(|k| k.is_empty()) {
+            return Some(Err(anyhow::anyhow!(
+                "Invalid secret reference [{value}], the format is {SECRET_REF_PREFIX}<backend>/<name>[#<key>]"
+            )));
+        }
+        // the name goes into the paths of the backends, it can't leave the path of the org
+        if name.starts_with('/')
+            || name.contains("..")
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '/' | '-'))
+        {
+            return Some(Err(anyhow::anyhow!(
+                "Invalid secret reference [{value}], the name can only have the characters [A-Za-z0-9_./-], not start with / or have .."
+            )));
+        }
+        Some(Ok(Self {
+            backend: backend.to_string(),
+            name: name.to_string(),
+            key: key.map(|k| k.to_string()),
+        }))
+    }
+
+    /// Takes the value of the key from the secret, the secret itself without a key
+    fn extract(&self, secret: String) -> Result<String, anyhow::Error> {
+        let Some(key) = self.key.as_ref() else {
+            return Ok(secret);
+        };
+        let value: json::Value = json::from_str(&secret)
+            .map_err(|_| anyhow::anyhow!("secret {} isn't a JSON object", self.name))?;
+        match value.get(key) {
+            Some(json::Value::String(v)) => Ok(v.to_string()),
+            Some(v) if !v.is_null() => Ok(v.to_string()),
+            _ => Err(anyhow::anyhow!(
+                "secret {} doesn't have the key {key}",
+                self.name
+            )),
+        }
+    }
+}
+
+pub struct SecretResolver {
+    backends: RwLock<HashMap<String, Arc<dyn SecretBackend>>>,
+    /// the secrets by org and reference, with the time they were fetched
+    cache: RwLock<HashMap<String, (String, Instant)>>,
+    ttl: Duration,
+}
+
+impl SecretResolver {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            backends: RwLock::new(HashMap::new()),
+            cache: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    /// Adds a backend, the references `secret://<name>/...` are resolved by it
+    pub fn register(&self, name: &str, backend: Arc<dyn SecretBackend>) {
+        self.backends.write().insert(name.to_string(), backend);
+    }
+
+    /// Checks the value is the credential itself or a reference to a known backend, the secret
+    /// isn't fetched
+    pub fn validate(&self, value: &str) -> Result<(), anyhow::Error> {
+        match SecretRef::parse(value) {
+            None => Ok(()),
+            Some(Err(e)) => Err(e),
+            Some(Ok(reference)) if self.backends.read().contains_key(&reference.backend) => Ok(()),
+            Some(Ok(reference)) => Err(anyhow::anyhow!(
+                "Unknown secret backend [{}]",
+                reference.backend
+            )),
+        }
+    }
+
+    /// Returns the credential: the value itself, or the secret it references
+    pub async fn resolve(&self, org_id: &str, value: &str) -> Result<String, anyhow::Error> {
+        let reference = match SecretRef::parse(value) {
+            None => return Ok(value.to_string()),
+            Some(reference) => reference?,
+        };
+        let cache_key = format!("{org_id}/{value}");
+        if let Some((secret, fetched_at)) = self.cache.read().get(&cache_key) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(secret.clone());
+            }
+        }
+
+        let backend = self
+            .backends
+            .read()
+            .get(&reference.backend)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Failed to fetch the secret [{value}]: unknown secret backend [{}]",
+                    reference.backend
+                )
+            })?;
+        let secret = backend
+            .get(org_id, &reference.name)
+            .await
+            .and_then(|secret| reference.extract(secret))
+            .map_err(|e| anyhow::anyhow!("Failed to fetch the secret [{value}]: {e}"))?;
+        let mut cache = self.cache.write();
+        cache.retain(|_, (_, fetched_at)| fetched_at.elapsed() < self.ttl);
+        cache.insert(cache_key, (secret.clone(), Instant::now()));
+        Ok(secret)
+    }
+}
+
+/// Checks the value is the credential itself or a reference to a known backend
+pub fn validate(value: &str) -> Result<(), anyhow::Error> {
+    RESOLVER.validate(value)
+}
+
+/// Returns the credential of the org: the value itself, or the secret it references
+pub async fn resolve(org_id: &str, value: &str) -> Result<String, anyhow::Error> {
+    RESOLVER.resolve(org_id, value).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Returns the org and the name of the secret with the number of fetches
+    #[derive(Default)]
+    struct CountingBackend {
+        fetches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl SecretBackend for CountingBackend {
+        async fn get(&self, org_id: &str, name: &str) -> Result<String, anyhow::Error> {
+            if name == "missing" {
+                return Err(anyhow::anyhow!("not found"));
+            }
+            let n = self.fetches.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(format!(r#"{{"token": "{org_id}-{name}-{n}"}}"#))
+        }
+    }
+
+    #[test]
+    fn test_secret_ref_parse() {
+        assert!(SecretRef::parse("Bearer abc").is_none());
+        assert_eq!(
+            SecretRef::parse("secret://vault/alerts/webhook#token")
+                .unwrap()
+                .unwrap(),
+            SecretRef {
+                backend: "vault".to_string(),
+                name: "alerts/webhook".to_string(),
+                key: Some("token".to_string()),
+            }
+        );
+        for value in [
+            "secret://",
+            "secret://vault",
+            "secret:///a",
+            "secret://env/A#",
+            "secret://vault/../other_org/x#k",
+            "secret://vault/alerts/../../x",
+            "secret://vault//x",
+            "secret://vault/a%2F..%2Fb",
+            "secret://vault/a?b",
+        ] {
+            assert!(SecretRef::parse(value).unwrap().is_err(), "{value}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_secret_resolver() {
+        let resolver = SecretResolver::new(Duration::from_millis(100));
+        let backend = Arc::new(CountingBackend::default());
+        resolver.register("mock", backend.clone());
+
+        // the values which aren't references are the credentials
+        assert_eq!(resolver.resolve("org", "abc").await.unwrap(), "abc");
+        assert!(resolver.validate("abc").is_ok());
+        assert!(resolver.validate("secret://mock/webhook").is_ok());
+        assert!(resolver.validate("secret://other/webhook").is_err());
+
+        let value = "secret://mock/webhook#token";
+        assert_eq!(
+            resolver.resolve("org", value).await.unwrap(),
+            "org-webhook-1"
+        );
+        // cached
+        assert_eq!(
+            resolver.resolve("org", value).await.unwrap(),
+            "org-webhook-1"
+        );
+        assert_eq!(backend.fetches.load(Ordering::SeqCst), 1);
+        // the cache is per org
+        assert_eq!(
+            resolver.resolve("org2", value).await.unwrap(),
+            "org2-webhook-2"
+        );
+        // fetched again when expired
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(
+            resolver.resolve("org", value).await.unwrap(),
+            "org-webhook-3"
+        );
+
+        // the whole secret without a key
+        assert_eq!(
+            resolver
+                .resolve("org", "secret://mock/webhook")
+                .await
+                .unwrap(),
+            r#"{"token": "org-webhook-4"}"#
+        );
+        // the failures tell the reference
+        for value in [
+            "secret://mock/missing",
+            "secret://mock/webhook#password",
+            "secret://other/webhook",
+        ] {
+            let err = resolver.resolve("org", value).await.unwrap_err();
+            assert!(
+                err.to_string().contains(value.split('#').next().unwrap()),
+                "{err}"
+            );
+        }
+    }
+}
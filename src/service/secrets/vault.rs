@@ -0,0 +1,60 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use config::{get_config, utils::json};
+
+use super::SecretBackend;
+
+/// The secrets in the KV v2 engine of HashiCorp Vault, the secrets of an org are under
+/// `<ZO_SECRET_VAULT_MOUNT>/data/<org_id>/`. The secret is the JSON object of its data, a key
+/// of the reference takes a value of it.
+pub struct Vault;
+
+#[async_trait]
+impl SecretBackend for Vault {
+    async fn get(&self, org_id: &str, name: &str) -> Result<String, anyhow::Error> {
+        let cfg = get_config();
+        if cfg.secret.vault_addr.is_empty() {
+            return Err(anyhow::anyhow!("ZO_SECRET_VAULT_ADDR is not set"));
+        }
+        let url = format!(
+            "{}/v1/{}/data/{org_id}/{name}",
+            cfg.secret.vault_addr.trim_end_matches('/'),
+            cfg.secret.vault_mount.trim_matches('/'),
+        );
+        let mut req = reqwest::Client::new()
+            .get(url)
+            .timeout(Duration::from_secs(cfg.secret.timeout))
+            .header("X-Vault-Token", &cfg.secret.vault_token);
+        if !cfg.secret.vault_namespace.is_empty() {
+            req = req.header("X-Vault-Namespace", &cfg.secret.vault_namespace);
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("vault responded with status {status}"));
+        }
+        let body: json::Value = json::from_slice(&resp.bytes().await?)?;
+        match body.pointer("/data/data") {
+            Some(data) if data.is_object() => Ok(data.to_string()),
+            _ => Err(anyhow::anyhow!(
+                "vault responded without the data of the secret"
+            )),
+        }
+    }
+}
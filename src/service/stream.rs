@@ -13,21 +13,31 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Error,
+};
 
 use actix_web::{http, http::StatusCode, HttpResponse};
 use config::{
     is_local_disk_storage,
-    meta::stream::{StreamSettings, StreamStats, StreamType, UpdateStreamSettings},
+    meta::{
+        search,
+        stream::{
+            check_computed_columns, check_field_aliases, check_stream_aliases, StreamPartition,
+            StreamPartitionType, StreamSettings, StreamStats, StreamType, UpdateStreamSettings,
+        },
+    },
     utils::json,
     SIZE_IN_MB, SQL_FULL_TEXT_SEARCH_FIELDS,
 };
-use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::datatypes::{DataType, Schema};
 use infra::{
     cache::stats,
     schema::{
-        unwrap_partition_time_level, unwrap_stream_settings, STREAM_RECORD_ID_GENERATOR,
-        STREAM_SCHEMAS, STREAM_SCHEMAS_COMPRESSED, STREAM_SCHEMAS_LATEST, STREAM_SETTINGS,
+        field_changes, unwrap_partition_time_level, unwrap_stream_settings,
+        STREAM_RECORD_ID_GENERATOR, STREAM_SCHEMAS, STREAM_SCHEMAS_COMPRESSED,
+        STREAM_SCHEMAS_LATEST, STREAM_SETTINGS,
     },
 };
 
@@ -36,9 +46,9 @@ use crate::{
         authz::Authz,
         http::HttpResponse as MetaHttpResponse,
         prom,
-        stream::{Stream, StreamProperty},
+        stream::{SchemaField, SchemaFieldChange, Stream, StreamProperty, StreamSchemaDiff},
     },
-    service::{db, metrics::get_prom_metadata_from_schema},
+    service::{db, ingestion, metrics::get_prom_metadata_from_schema},
 };
 
 const LOCAL: &str = "disk";
@@ -66,6 +76,99 @@ pub async fn get_stream(
     }
 }
 
+/// Returns the ingestion lag of a stream, from the file list and the memtables of the ingesters
+pub async fn get_stream_ingestion_lag(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> Result<HttpResponse, Error> {
+    let schema = match infra::schema::get(org_id, stream_name, stream_type).await {
+        Ok(schema) => schema,
+        Err(e) => {
+            return Ok(
+                HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                    StatusCode::INTERNAL_SERVER_ERROR.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    if schema == Schema::empty() {
+        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+            StatusCode::NOT_FOUND.into(),
+            "stream not found".to_string(),
+        )));
+    }
+    Ok(HttpResponse::Ok().json(ingestion::lag::get(org_id, stream_name, stream_type).await))
+}
+
+/// Returns the current fields of a stream and the field changes since the given time
+pub async fn get_stream_schema_diff(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    since: i64,
+) -> Result<HttpResponse, Error> {
+    let schema = match infra::schema::get(org_id, stream_name, stream_type).await {
+        Ok(schema) => schema,
+        Err(e) => {
+            return Ok(
+                HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                    StatusCode::INTERNAL_SERVER_ERROR.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    if schema == Schema::empty() {
+        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+            StatusCode::NOT_FOUND.into(),
+            "stream not found".to_string(),
+        )));
+    }
+    let history = match field_changes::get(org_id, stream_name, stream_type).await {
+        Ok(history) => history,
+        Err(e) => {
+            return Ok(
+                HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                    StatusCode::INTERNAL_SERVER_ERROR.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+
+    let (history_start, changes) = match history {
+        Some(history) => (
+            Some(history.start_ts),
+            history
+                .changes
+                .into_iter()
+                .filter(|c| c.timestamp >= since)
+                .map(|c| SchemaFieldChange {
+                    field: c.field,
+                    old_type: c.old_type,
+                    new_type: c.new_type,
+                    version: c.version,
+                    timestamp: c.timestamp,
+                })
+                .collect(),
+        ),
+        None => (None, vec![]),
+    };
+    let schema = field_changes::schema_fields(&schema)
+        .into_iter()
+        .map(|(name, r#type)| SchemaField { name, r#type })
+        .collect();
+    Ok(HttpResponse::Ok().json(StreamSchemaDiff {
+        stream_name: stream_name.to_string(),
+        stream_type,
+        schema,
+        history_start,
+        changes,
+    }))
+}
+
 pub async fn get_streams(
     org_id: &str,
     stream_type: Option<StreamType>,
@@ -134,6 +237,8 @@ pub fn stream_res(
     stats: Option<StreamStats>,
 ) -> Stream {
     let storage_type = if is_local_disk_storage() { LOCAL } else { S3 };
+    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    // the field aliases are queryable like the fields, their values are strings
     let mappings = schema
         .fields()
         .iter()
@@ -141,6 +246,10 @@ pub fn stream_res(
             prop_type: field.data_type().to_string(),
             name: field.name().to_string(),
         })
+        .chain(settings.field_aliases.iter().map(|alias| StreamProperty {
+            prop_type: DataType::Utf8.to_string(),
+            name: alias.name.clone(),
+        }))
         .collect::<Vec<_>>();
 
     let mut stats = stats.unwrap_or_default();
@@ -165,7 +274,6 @@ pub fn stream_res(
         None
     };
 
-    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
     settings.partition_time_level = Some(unwrap_partition_time_level(
         settings.partition_time_level,
         stream_type,
@@ -243,6 +351,88 @@ pub async fn save_stream_settings(
     let schema = infra::schema::get(org_id, stream_name, stream_type)
         .await
         .unwrap();
+
+    if let Err(e) = check_field_aliases(&settings.field_aliases, |name| {
+        schema.field_with_name(name).is_ok()
+    }) {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            e,
+        )));
+    }
+
+    if let Err(e) = check_computed_columns(&settings.computed_columns, |name| {
+        schema.field_with_name(name).is_ok()
+    }) {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            e,
+        )));
+    }
+    if let Some(column) = settings.computed_columns.iter().find(|column| {
+        settings
+            .field_aliases
+            .iter()
+            .any(|alias| alias.name == column.name)
+    }) {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            format!(
+                "computed column [{}] collides with a field alias",
+                column.name
+            ),
+        )));
+    }
+
+    if settings
+        .query_range_policy
+        .as_ref()
+        .is_some_and(|v| !["clamp", "reject"].contains(&v.as_str()))
+    {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            "query_range_policy should be one of clamp, reject".to_string(),
+        )));
+    }
+
+    if settings
+        .collapse_duplicates
+        .as_ref()
+        .is_some_and(|v| v.fields.is_empty() || v.window_secs <= 0)
+    {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            "collapse duplicates should have fields and a positive window".to_string(),
+        )));
+    }
+
+    // the aliases are resolved like stream names, so they can't be taken by another stream
+    let mut streams = HashSet::new();
+    let mut alias_targets = HashMap::new();
+    for name in settings.aliases.iter() {
+        if infra::schema::get(org_id, name, stream_type)
+            .await
+            .is_ok_and(|schema| !schema.fields().is_empty())
+        {
+            streams.insert(name.as_str());
+        }
+        if let Some(target) =
+            infra::schema::get_stream_alias_target(org_id, name, stream_type).await
+        {
+            alias_targets.insert(name.as_str(), target);
+        }
+    }
+    if let Err(e) = check_stream_aliases(
+        stream_name,
+        &settings.aliases,
+        |name| streams.contains(name),
+        |name| alias_targets.get(name).cloned(),
+    ) {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            e,
+        )));
+    }
     let mut old_partition_keys = unwrap_stream_settings(&schema)
         .unwrap_or_default()
         .partition_keys;
@@ -296,12 +486,48 @@ pub async fn update_stream_settings(
             if let Some(max_query_range) = update_settings.max_query_range {
                 settings.max_query_range = max_query_range;
             }
+            if let Some(query_range_policy) = update_settings.query_range_policy {
+                settings.query_range_policy =
+                    (!query_range_policy.is_empty()).then_some(query_range_policy);
+            }
             if let Some(store_original_data) = update_settings.store_original_data {
                 settings.store_original_data = store_original_data;
             }
+            if let Some(timestamp_settings) = update_settings.timestamp_settings {
+                settings.timestamp_settings = if timestamp_settings.field.is_empty() {
+                    None
+                } else {
+                    Some(timestamp_settings)
+                };
+            }
+            if let Some(field_access) = update_settings.field_access {
+                settings.field_access = field_access;
+            }
+            if let Some(required_fields) = update_settings.required_fields {
+                settings.required_fields = required_fields;
+            }
+            if let Some(field_aliases) = update_settings.field_aliases {
+                settings.field_aliases = field_aliases;
+            }
+            if let Some(aliases) = update_settings.aliases {
+                settings.aliases = aliases;
+            }
+            if let Some(computed_columns) = update_settings.computed_columns {
+                settings.computed_columns = computed_columns;
+            }
+            if let Some(collapse_duplicates) = update_settings.collapse_duplicates {
+                settings.collapse_duplicates = if collapse_duplicates.fields.is_empty() {
+                    None
+                } else {
+                    Some(collapse_duplicates)
+                };
+            }
             if let Some(flatten_level) = update_settings.flatten_level {
                 settings.flatten_level = Some(flatten_level);
             }
+            if let Some(flatten_array) = update_settings.flatten_array {
+                settings.flatten_array = Some(flatten_array);
+            }
 
             if let Some(data_retention) = update_settings.data_retention {
                 settings.data_retention = data_retention;
@@ -377,6 +603,15 @@ pub async fn update_stream_settings(
             if let Some(partition_time_level) = update_settings.partition_time_level {
                 settings.partition_time_level = Some(partition_time_level);
             }
+            if let Err(e) =
+                check_partition_keys(org_id, stream_name, stream_type, &settings.partition_keys)
+                    .await
+            {
+                return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e,
+                )));
+            }
             save_stream_settings(org_id, stream_name, stream_type, settings).await
         }
         None => Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
@@ -386,6 +621,119 @@ pub async fn update_stream_settings(
     }
 }
 
+/// Checks the partition keys added to the stream: the number of enabled keys is limited, the
+/// field must exist and a value partition key must have a low cardinality, because every value
+/// is a level in the storage path.
+pub async fn check_partition_keys(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    partition_keys: &[StreamPartition],
+) -> Result<(), String> {
+    let cfg = config::get_config();
+    let schema = infra::schema::get(org_id, stream_name, stream_type)
+        .await
+        .map_err(|e| e.to_string())?;
+    let old_partition_keys = unwrap_stream_settings(&schema)
+        .unwrap_or_default()
+        .partition_keys;
+    let added = added_partition_keys(
+        &old_partition_keys,
+        partition_keys,
+        cfg.limit.partition_keys_max,
+    )?;
+
+    for key in added {
+        // the stream may have no data yet
+        if !schema.fields().is_empty() && schema.field_with_name(&key.field).is_err() {
+            return Err(format!(
+                "field [{}] doesn't exist in the stream, can't be used for partition key",
+                key.field
+            ));
+        }
+        if key.types != StreamPartitionType::Value {
+            continue;
+        }
+        match partition_key_cardinality(org_id, stream_name, stream_type, &key.field).await {
+            Ok(n) if n > cfg.limit.partition_key_max_cardinality => {
+                return Err(format!(
+                    "field [{}] has about {n} distinct values in the last 24 hours, more than the max {} of a partition key, use a hash partition instead",
+                    key.field, cfg.limit.partition_key_max_cardinality
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!(
+                "check cardinality of partition key [{org_id}/{stream_type}/{stream_name}/{}] error: {e}",
+                key.field
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the enabled keys which aren't in the old keys, the number of enabled keys can't exceed
+/// the max if a key is added
+fn added_partition_keys<'a>(
+    old_partition_keys: &[StreamPartition],
+    partition_keys: &'a [StreamPartition],
+    max_keys: usize,
+) -> Result<Vec<&'a StreamPartition>, String> {
+    let added = partition_keys
+        .iter()
+        .filter(|k| {
+            !k.disabled
+                && !old_partition_keys
+                    .iter()
+                    .any(|old| old.field == k.field && !old.disabled)
+        })
+        .collect::<Vec<_>>();
+    let enabled = partition_keys.iter().filter(|k| !k.disabled).count();
+    if !added.is_empty() && enabled > max_keys {
+        return Err(format!(
+            "a stream can have at most {max_keys} partition keys, got {enabled}"
+        ));
+    }
+    Ok(added)
+}
+
+/// Returns the approximate number of distinct values of the field in the last 24 hours
+async fn partition_key_cardinality(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    field: &str,
+) -> Result<usize, anyhow::Error> {
+    let end_time = chrono::Utc::now().timestamp_micros();
+    let start_time = end_time
+        - chrono::Duration::try_hours(24)
+            .unwrap()
+            .num_microseconds()
+            .unwrap();
+    let req = search::Request {
+        query: search::Query {
+            sql: format!("SELECT approx_distinct(\"{field}\") AS num FROM \"{stream_name}\""),
+            from: 0,
+            size: 1,
+            start_time,
+            end_time,
+            ..Default::default()
+        },
+        encoding: search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+        index_type: "".to_string(),
+    };
+    let resp = crate::service::search::search("", org_id, stream_type, None, &req).await?;
+    Ok(resp
+        .hits
+        .first()
+        .and_then(|hit| hit.get("num"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or_default() as usize)
+}
+
 #[tracing::instrument]
 pub async fn delete_stream(
     org_id: &str,
@@ -449,6 +797,9 @@ pub async fn delete_stream(
     // delete stream stats cache
     stats::remove_stream_stats(org_id, stream_name, stream_type);
 
+    // delete stream ingestion lag
+    ingestion::lag::remove(org_id, stream_name, stream_type);
+
     // delete stream compaction offset
     if let Err(e) = db::compact::files::del_offset(org_id, stream_type, stream_name).await {
         return Ok(
@@ -512,10 +863,34 @@ pub async fn delete_fields(
 
 #[cfg(test)]
 mod tests {
-    use datafusion::arrow::datatypes::{DataType, Field};
+    use datafusion::arrow::datatypes::Field;
 
     use super::*;
 
+    #[test]
+    fn test_added_partition_keys() {
+        let mut disabled = StreamPartition::new("host");
+        disabled.disabled = true;
+        let old = vec![StreamPartition::new("tenant_id"), disabled];
+
+        // the enabled old keys aren't added
+        let keys = vec![StreamPartition::new("tenant_id")];
+        assert!(added_partition_keys(&old, &keys, 1).unwrap().is_empty());
+
+        // a disabled old key enabled again is added
+        let keys = vec![
+            StreamPartition::new("tenant_id"),
+            StreamPartition::new("host"),
+        ];
+        let added = added_partition_keys(&old, &keys, 2).unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].field, "host");
+        assert!(added_partition_keys(&old, &keys, 1).is_err());
+
+        // the existing keys over the max can be kept
+        assert!(added_partition_keys(&keys, &keys, 1).unwrap().is_empty());
+    }
+
     #[test]
     fn test_stream_res() {
         let stats = StreamStats::default();
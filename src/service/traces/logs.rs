@@ -0,0 +1,239 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use arrow_schema::Schema;
+use config::{
+    get_config,
+    meta::{search, stream::StreamType},
+    utils::json,
+};
+use infra::errors::{Error, Result};
+
+use crate::{
+    common::meta::traces::SpanLogs,
+    service::{db, search as SearchService},
+};
+
+const MAX_SPANS: i64 = 9999;
+
+/// Finds the logs emitted during the spans of a trace, grouped by span.
+///
+/// The span timing is read from the traces stream, then every logs stream is searched for
+/// records carrying the trace id and the span id within the span's time window. The trace id
+/// and span id fields of the logs streams are looked up in the configured field names, streams
+/// without either of them are skipped. Spans without correlated logs are returned with empty
+/// logs.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_span_logs(
+    trace_id: &str,
+    org_id: &str,
+    traces_stream: &str,
+    user_id: Option<String>,
+    span_trace_id: &str,
+    span_id: Option<&str>,
+    (start_time, end_time): (i64, i64),
+    logs_streams: Vec<String>,
+    size: i64,
+) -> Result<Vec<SpanLogs>> {
+    let cfg = get_config();
+    if !is_valid_id(span_trace_id) || !span_id.map_or(true, is_valid_id) {
+        return Err(Error::Message("invalid trace_id or span_id".to_string()));
+    }
+
+    // get the span timing from the traces stream
+    let mut query_sql = format!(
+        "SELECT span_id, service_name, operation_name, start_time, end_time FROM \"{traces_stream}\" WHERE trace_id = '{span_trace_id}'"
+    );
+    if let Some(span_id) = span_id {
+        query_sql.push_str(&format!(" AND span_id = '{span_id}'"));
+    }
+    query_sql.push_str(" ORDER BY start_time ASC");
+    let req = new_request(query_sql, start_time, end_time, MAX_SPANS);
+    let resp =
+        SearchService::search(trace_id, org_id, StreamType::Traces, user_id.clone(), &req).await?;
+    let mut spans = resp
+        .hits
+        .iter()
+        .map(|hit| SpanLogs {
+            span_id: get_str(hit, "span_id"),
+            service_name: get_str(hit, "service_name"),
+            operation_name: get_str(hit, "operation_name"),
+            start_time: hit.get("start_time").and_then(|v| v.as_i64()).unwrap_or(0),
+            end_time: hit.get("end_time").and_then(|v| v.as_i64()).unwrap_or(0),
+            logs: vec![],
+        })
+        .collect::<Vec<_>>();
+    if spans.is_empty() {
+        return Ok(spans);
+    }
+
+    // span time is nanosecond, logs time is microsecond
+    let logs_start_time = spans.iter().map(|s| s.start_time).min().unwrap() / 1000;
+    let logs_end_time = spans.iter().map(|s| s.end_time).max().unwrap() / 1000 + 1;
+    let logs_streams = if logs_streams.is_empty() {
+        db::schema::list_streams_from_cache(org_id, StreamType::Logs).await
+    } else {
+        logs_streams
+    };
+    let trace_id_fields = split_fields(&cfg.common.traces_logs_trace_id_fields);
+    let span_id_fields = split_fields(&cfg.common.traces_logs_span_id_fields);
+    for stream_name in logs_streams {
+        let schema = infra::schema::get(org_id, &stream_name, StreamType::Logs).await?;
+        let Some(trace_id_field) = find_field(&schema, &trace_id_fields) else {
+            continue;
+        };
+        let Some(span_id_field) = find_field(&schema, &span_id_fields) else {
+            continue;
+        };
+        let mut query_sql = format!(
+            "SELECT * FROM \"{stream_name}\" WHERE \"{trace_id_field}\" = '{span_trace_id}'"
+        );
+        if let Some(span_id) = span_id {
+            query_sql.push_str(&format!(" AND \"{span_id_field}\" = '{span_id}'"));
+        }
+        query_sql.push_str(&format!(" ORDER BY {} ASC", cfg.common.column_timestamp));
+        let req = new_request(query_sql, logs_start_time, logs_end_time, size);
+        let resp = SearchService::search(trace_id, org_id, StreamType::Logs, user_id.clone(), &req)
+            .await?;
+        group_logs_by_span(
+            &mut spans,
+            resp.hits,
+            span_id_field,
+            &cfg.common.column_timestamp,
+        );
+    }
+
+    Ok(spans)
+}
+
+/// Appends the logs to the span they carry the id of, dropping the logs outside of the
+/// span's time window
+fn group_logs_by_span(
+    spans: &mut [SpanLogs],
+    logs: Vec<json::Value>,
+    span_id_field: &str,
+    ts_column: &str,
+) {
+    for log in logs {
+        let Some(log_span_id) = log.get(span_id_field).and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let ts = log.get(ts_column).and_then(|v| v.as_i64()).unwrap_or(0);
+        if let Some(span) = spans.iter_mut().find(|s| {
+            s.span_id == log_span_id && s.start_time / 1000 <= ts && ts <= s.end_time / 1000
+        }) {
+            span.logs.push(log);
+        }
+    }
+}
+
+fn new_request(sql: String, start_time: i64, end_time: i64, size: i64) -> search::Request {
+    search::Request {
+        query: search::Query {
+            sql,
+            from: 0,
+            size,
+            start_time,
+            end_time,
+            sort_by: None,
+            quick_mode: false,
+            query_type: "".to_string(),
+            track_total_hits: false,
+            uses_zo_fn: false,
+            query_fn: None,
+            skip_wal: false,
+            wal_only: false,
+            fields: vec![],
+        },
+        encoding: search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+        index_type: "".to_string(),
+    }
+}
+
+fn find_field<'a>(schema: &Schema, fields: &[&'a str]) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|f| schema.field_with_name(f).is_ok())
+        .copied()
+}
+
+fn split_fields(fields: &str) -> Vec<&str> {
+    fields
+        .split(',')
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+fn get_str(hit: &json::Value, key: &str) -> String {
+    hit.get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Trace and span ids are hex strings, anything else is rejected to keep the SQL safe
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(span_id: &str, start_time: i64, end_time: i64) -> SpanLogs {
+        SpanLogs {
+            span_id: span_id.to_string(),
+            start_time,
+            end_time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_group_logs_by_span() {
+        let mut spans = vec![
+            span("a", 1_000_000, 2_000_000),
+            span("b", 1_500_000, 3_000_000),
+        ];
+        let logs = vec![
+            json::json!({"_timestamp": 1500, "spanId": "a", "message": "in a"}),
+            json::json!({"_timestamp": 2500, "spanId": "b", "message": "in b"}),
+            json::json!({"_timestamp": 2500, "spanId": "a", "message": "after a"}),
+            json::json!({"_timestamp": 1500, "message": "no span"}),
+        ];
+        group_logs_by_span(&mut spans, logs, "spanId", "_timestamp");
+        assert_eq!(spans[0].logs.len(), 1);
+        assert_eq!(spans[0].logs[0]["message"], "in a");
+        assert_eq!(spans[1].logs.len(), 1);
+        assert_eq!(spans[1].logs[0]["message"], "in b");
+
+        // spans without logs stay as empty groups
+        let mut spans = vec![span("c", 1_000_000, 2_000_000)];
+        group_logs_by_span(&mut spans, vec![], "spanId", "_timestamp");
+        assert!(spans[0].logs.is_empty());
+    }
+
+    #[test]
+    fn test_is_valid_id() {
+        assert!(is_valid_id("4bf92f3577b34da6a3ce929d0e0e4736"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("a' OR '1'='1"));
+    }
+}
@@ -55,10 +55,13 @@ use crate::{
             MetadataType,
         },
         schema::{check_for_schema, stream_schema_exists},
-        usage::report_request_usage_stats,
+        usage::{report_latency, report_request_usage_stats, LatencyKind},
     },
 };
 
+pub mod logs;
+pub mod sampling;
+
 const PARENT_SPAN_ID: &str = "reference.parent_span_id";
 const PARENT_TRACE_ID: &str = "reference.parent_trace_id";
 const REF_TYPE: &str = "reference.ref_type";
@@ -171,6 +174,9 @@ pub async fn handle_trace_request(
         Some(name) => format_stream_name(name),
         None => "default".to_owned(),
     };
+    // ingesting to a stream alias writes to the stream it belongs to
+    let traces_stream_name =
+        infra::schema::resolve_stream_alias(org_id, &traces_stream_name, StreamType::Traces).await;
     let min_ts = (Utc::now()
         - Duration::try_hours(cfg.limit.ingest_allowed_upto)
             .expect("configuration error: too large ingest_allowed_upto"))
@@ -399,6 +405,11 @@ pub async fn handle_trace_request(
         return format_response(partial_success, req_type);
     }
 
+    let json_data = if cfg.common.traces_sampling_enabled {
+        sampling::sample(org_id, &traces_stream_name, json_data)
+    } else {
+        json_data
+    };
     let mut req_stats = match write_traces(org_id, &traces_stream_name, json_data).await {
         Ok(v) => v,
         Err(e) => {
@@ -449,6 +460,7 @@ pub async fn handle_trace_request(
             StreamType::Traces.to_string().as_str(),
         ])
         .observe(time);
+    report_latency(LatencyKind::Ingest, ep, org_id, time);
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             ep,
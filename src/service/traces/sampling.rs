@@ -0,0 +1,106 @@
+// Copyright 2024 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sampling of the traces at ingest. A percentage of the traces is kept, decided by a hash of
+//! the trace id, so the ingesters receiving spans of the same trace make the same decision. The
+//! spans of the kept traces are written to the WAL before the request is acked, like without
+//! sampling.
+
+use std::collections::HashMap;
+
+use config::{
+    get_config, metrics,
+    utils::{
+        hash::{fnv, Sum64},
+        json,
+    },
+};
+
+type Record = (i64, json::Map<String, json::Value>);
+
+/// Returns if the trace is kept, `percentage` is from 0 to 100
+pub fn keep(trace_id: &str, percentage: f64) -> bool {
+    let bucket = fnv::new().sum64(trace_id) % 10000;
+    (bucket as f64) < percentage * 100.0
+}
+
+/// Returns the spans of the kept traces
+pub fn sample(org_id: &str, stream_name: &str, spans: Vec<Record>) -> Vec<Record> {
+    let percentage = get_config().common.traces_sampling_percentage;
+    let mut decisions: HashMap<String, bool> = HashMap::new();
+    let spans = spans
+        .into_iter()
+        .filter(|(_, span)| {
+            let trace_id = span
+                .get("trace_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            match decisions.get(trace_id) {
+                Some(kept) => *kept,
+                None => {
+                    let kept = keep(trace_id, percentage);
+                    decisions.insert(trace_id.to_string(), kept);
+                    kept
+                }
+            }
+        })
+        .collect();
+    for kept in decisions.into_values() {
+        metrics::TRACES_SAMPLING_TRACES
+            .with_label_values(&[org_id, stream_name, if kept { "kept" } else { "dropped" }])
+            .inc();
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keep() {
+        let kept = (0..10000)
+            .filter(|i| keep(&format!("{i:032x}"), 25.0))
+            .count();
+        assert!((2000..3000).contains(&kept), "kept {kept} traces");
+        // the decision is the same for the spans of a trace on every ingester
+        assert_eq!(keep("abc", 25.0), keep("abc", 25.0));
+        assert!(keep("abc", 100.0));
+        assert!(!keep("abc", 0.0));
+    }
+
+    #[test]
+    fn test_sample() {
+        let span = |trace_id: &str| {
+            let span = json::json!({"trace_id": trace_id});
+            (0, span.as_object().unwrap().clone())
+        };
+        let spans = (0..100)
+            .flat_map(|i| {
+                let trace_id = format!("{i:032x}");
+                [span(&trace_id), span(&trace_id)]
+            })
+            .collect::<Vec<_>>();
+        let sampled = sample("default", "default", spans);
+        // the spans of a trace are kept or dropped together
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (_, span) in sampled.iter() {
+            *counts
+                .entry(span["trace_id"].as_str().unwrap().to_string())
+                .or_default() += 1;
+        }
+        assert!(counts.values().all(|v| *v == 2));
+    }
+}
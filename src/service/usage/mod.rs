@@ -63,9 +63,16 @@ pub async fn report_request_usage_stats(
     let event: UsageEvent = usage_type.into();
     let now = DateTime::from_timestamp_micros(timestamp).unwrap();
 
-    if !get_config().common.usage_enabled {
+    let cfg = get_config();
+    if !cfg.common.usage_enabled {
         return;
     }
+    // partial results mean some part of the search failed, always record them
+    let Some(scale) = sample_usage(cfg.common.usage_sampling_ratio, stats.is_partial) else {
+        return;
+    };
+    let size = stats.size * scale;
+    let records = (stats.records as f64 * scale).round() as i64;
 
     let request_body = stats.request_body.unwrap_or(usage_type.to_string());
     let user_email = stats.user_email.unwrap_or("".to_owned());
@@ -90,11 +97,11 @@ pub async fn report_request_usage_stats(
             org_id: org_id.to_owned(),
             request_body: request_body.to_owned(),
             function: None,
-            size: stats.size,
+            size,
             unit: "MB".to_owned(),
             user_email: user_email.to_owned(),
             response_time: stats.response_time,
-            num_records: stats.records * num_functions as i64,
+            num_records: records * num_functions as i64,
             stream_type,
             stream_name: stream_name.to_owned(),
             min_ts: None,
@@ -126,12 +133,12 @@ pub async fn report_request_usage_stats(
         ),
         org_id: org_id.to_owned(),
         request_body: request_body.to_owned(),
-        size: stats.size,
+        size,
         unit: "MB".to_owned(),
         user_email,
         response_time: stats.response_time,
         function: stats.function,
-        num_records: stats.records,
+        num_records: records,
         stream_type,
         stream_name: stream_name.to_owned(),
         min_ts: stats.min_ts,
@@ -150,6 +157,23 @@ pub async fn report_request_usage_stats(
     }
 }
 
+/// Decides if a per-request usage event is recorded with the given sampling ratio.
+///
+/// Returns the factor the size and the number of records of a recorded event are scaled by,
+/// or `None` when the event is dropped. With a ratio of 0.1 one in ten events is recorded
+/// with its counters multiplied by 10, so the totals shown in the usage dashboards are
+/// estimates and single requests may show inflated numbers. Response times are averages
+/// and are never scaled. Critical events bypass sampling and are recorded unscaled.
+fn sample_usage(ratio: f64, is_critical: bool) -> Option<f64> {
+    if is_critical || ratio >= 1.0 {
+        return Some(1.0);
+    }
+    if ratio <= 0.0 || rand::random::<f64>() >= ratio {
+        return None;
+    }
+    Some(1.0 / ratio)
+}
+
 pub async fn publish_usage(mut usage: Vec<UsageData>) {
     let mut usages = USAGE_DATA.write().await;
     usages.append(&mut usage);
@@ -459,6 +483,25 @@ async fn publish_audit(
     ingestion_service::ingest(&get_config().common.usage_org, req).await
 }
 
+/// The kind of a request, the search and ingestion latencies have different buckets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LatencyKind {
+    Search,
+    Ingest,
+}
+
+/// Records the latency of a request, in seconds, for the percentiles by endpoint and org
+#[inline]
+pub fn report_latency(kind: LatencyKind, endpoint: &str, org_id: &str, time: f64) {
+    let histogram = match kind {
+        LatencyKind::Search => &metrics::HTTP_SEARCH_LATENCY,
+        LatencyKind::Ingest => &metrics::HTTP_INGEST_LATENCY,
+    };
+    histogram
+        .with_label_values(&[endpoint, org_id])
+        .observe(time);
+}
+
 #[inline]
 pub fn http_report_metrics(
     start: std::time::Instant,
@@ -479,6 +522,7 @@ pub fn http_report_metrics(
             stream_type.to_string().as_str(),
         ])
         .observe(time);
+    report_latency(LatencyKind::Search, &uri, org_id, time);
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
             &uri,
@@ -489,3 +533,56 @@ pub fn http_report_metrics(
         ])
         .inc();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_usage() {
+        assert_eq!(sample_usage(1.0, false), Some(1.0));
+        assert_eq!(sample_usage(0.1, true), Some(1.0));
+
+        let recorded = (0..10000)
+            .filter_map(|_| sample_usage(0.1, false))
+            .collect::<Vec<_>>();
+        assert!(recorded.iter().all(|scale| *scale == 10.0));
+        // the scaled total stays close to the number of events
+        let total = recorded.iter().sum::<f64>();
+        assert!((8000.0..12000.0).contains(&total));
+    }
+
+    #[test]
+    fn test_report_latency() {
+        use prometheus::core::Metric;
+
+        for time in [0.005, 0.03, 0.04, 0.2, 100.0] {
+            report_latency(
+                LatencyKind::Search,
+                "/api/org/_search",
+                "test_latency",
+                time,
+            );
+        }
+        report_latency(
+            LatencyKind::Ingest,
+            "/api/org/_search",
+            "test_latency",
+            0.01,
+        );
+
+        let metric = metrics::HTTP_SEARCH_LATENCY
+            .with_label_values(&["/api/org/_search", "test_latency"])
+            .metric();
+        let histogram = metric.get_histogram();
+        assert_eq!(histogram.get_sample_count(), 5);
+        // the bucket counts are cumulative, the default buckets are
+        // 0.01,0.05,0.1,0.25,0.5,1,2.5,5,10,30,60,120
+        let counts = histogram
+            .get_bucket()
+            .iter()
+            .map(|b| b.get_cumulative_count())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 5]);
+    }
+}